@@ -0,0 +1,15 @@
+//! Embeds `display-tuner.manifest` into the binary with the MSVC linker. Other toolchains go
+//! without; `main` sets the same DPI awareness at runtime.
+
+use std::env;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=display-tuner.manifest");
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").is_ok_and(|target_env| target_env == "msvc");
+    if msvc {
+        let manifest = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join("display-tuner.manifest");
+        println!("cargo:rustc-link-arg-bins=/MANIFEST:EMBED");
+        println!("cargo:rustc-link-arg-bins=/MANIFESTINPUT:{}", manifest.display());
+    }
+}
@@ -0,0 +1,51 @@
+/*
+ * C interface of display-tuner, built with
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * Every function returns the exit code the command line would; after DT_ERROR,
+ * DT_TIMED_OUT or DT_NO_DISPLAYS, dt_last_error gives the message for the calling thread.
+ *
+ * Memory: strings passed in stay the caller's and are only read during the call. Strings
+ * returned through an out pointer belong to the caller and must be released with
+ * dt_free_string exactly once, never with free() or CoTaskMemFree. Nothing is written to an
+ * out pointer when a function fails.
+ *
+ * Strings are NUL-terminated UTF-8.
+ */
+#ifndef DISPLAY_TUNER_H
+#define DISPLAY_TUNER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DT_OK 0
+#define DT_ERROR 1
+/* Windows applied the change with adjustments (only with "allow_changes"). */
+#define DT_ADJUSTED 2
+/* The apply call didn't return in time and may still complete in the background. */
+#define DT_TIMED_OUT 3
+/* Windows reports no active display, e.g. on a headless machine. */
+#define DT_NO_DISPLAYS 4
+
+/* The displays as a JSON array, the shape of `display-tuner list --format json`. */
+int dt_enumerate(char **out_json);
+
+/*
+ * Applies a JSON request such as {"id": 1, "width": 2560, "height": 1440, "scaling": 150}.
+ * Unset fields keep their current value; without "id" every available display is changed.
+ * "allow_changes": true lets Windows adjust a mode it refuses as-is (returns DT_ADJUSTED).
+ */
+int dt_set(const char *json_request);
+
+/* The message of the last failure on this thread; DT_ERROR if there was none. */
+int dt_last_error(char **out_message);
+
+/* Releases a string returned by this library; NULL is ignored. */
+void dt_free_string(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
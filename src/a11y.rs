@@ -0,0 +1,154 @@
+//! Accessibility presets, for people who need everything bigger without finding the right
+//! scaling on each display themselves.
+//!
+//! `a11y large-text on` sets every active display [`LARGE_TEXT_STEPS`] supported steps above
+//! its recommended scaling, after recording the scaling it had in
+//! `%APPDATA%\display-tuner\large-text.json` by device path; `off` puts those back. Displays
+//! unplugged in between are reported and forgotten, displays plugged in since are left alone.
+
+use crate::display::{app_dir, apply_display_config, enumerate_displays, DisplayConfig, DisplayInfo, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LARGE_TEXT_FILE: &str = "large-text.json";
+
+/// How many supported scaling steps above the recommended one large text goes.
+pub const LARGE_TEXT_STEPS: i32 = 2;
+
+/// The large text scaling of `display`: [`LARGE_TEXT_STEPS`] above its recommended scaling,
+/// at most its largest.
+#[must_use]
+pub fn large_text_scaling(display: &DisplayInfo) -> i32 {
+    DisplayInfo { scaling_current: display.scaling_recommended, ..display.clone() }.step_scaling(LARGE_TEXT_STEPS)
+}
+
+/// The scaling a display had before large text was turned on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedScaling {
+    pub device_path: String,
+    /// For reporting the display once it's gone.
+    pub friendly_name: String,
+    pub scaling: i32,
+}
+
+/// Whether large text is on and what to go back to, see the module documentation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargeText {
+    pub displays: Vec<RecordedScaling>,
+}
+
+impl LargeText {
+    /// `%APPDATA%\display-tuner\large-text.json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::NoAppData`](crate::display::DisplayError::NoAppData) if
+    /// `APPDATA` isn't set.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(app_dir()?.join(LARGE_TEXT_FILE))
+    }
+
+    /// The state saved at `path`, off if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file or its directory can't be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn is_on(&self) -> bool {
+        !self.displays.is_empty()
+    }
+
+    /// Records the scaling of the active `displays` not recorded yet, so turning it on twice
+    /// keeps the values from before the first time, and returns each with its large text
+    /// scaling.
+    pub fn turn_on<'a>(&mut self, displays: &'a [DisplayInfo]) -> Vec<(&'a DisplayInfo, i32)> {
+        let active: Vec<&DisplayInfo> = displays.iter().filter(|d| d.active).collect();
+        for display in &active {
+            if !self.displays.iter().any(|r| r.device_path == display.device_path) {
+                self.displays.push(RecordedScaling {
+                    device_path: display.device_path.clone(),
+                    friendly_name: display.friendly_name.clone(),
+                    scaling: display.scaling_current,
+                });
+            }
+        }
+        active.into_iter().map(|d| (d, large_text_scaling(d))).collect()
+    }
+
+    /// Forgets every recorded scaling and returns those of `displays` with the scaling to go
+    /// back to, and the recorded displays that aren't connected.
+    pub fn turn_off<'a>(&mut self, displays: &'a [DisplayInfo]) -> (Vec<(&'a DisplayInfo, i32)>, Vec<RecordedScaling>) {
+        let mut restore = Vec::new();
+        let mut missing = Vec::new();
+        for recorded in self.displays.drain(..) {
+            match displays.iter().find(|d| d.active && d.device_path == recorded.device_path) {
+                Some(display) => restore.push((display, recorded.scaling)),
+                None => missing.push(recorded),
+            }
+        }
+        (restore, missing)
+    }
+}
+
+/// What [`set_large_text`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargeTextChange {
+    /// Whether large text is on now.
+    pub on: bool,
+    /// Each display changed, with its scaling from before and now.
+    pub changed: Vec<(DisplayInfo, i32, i32)>,
+    /// Displays turned off large text for that aren't connected, left as they are.
+    pub missing: Vec<RecordedScaling>,
+}
+
+/// Turns large text on or off for every display, `None` to flip it.
+///
+/// # Errors
+///
+/// Returns an error if the state file can't be read or written, the displays can't be
+/// enumerated or a scaling can't be applied. Turning it on records the scaling first, so
+/// turning it off again undoes a partial change.
+pub fn set_large_text(on: Option<bool>) -> Result<LargeTextChange> {
+    let path = LargeText::default_path()?;
+    let mut state = LargeText::load(&path)?;
+    let displays = enumerate_displays()?;
+    let on = on.unwrap_or(!state.is_on());
+    let (targets, missing) = if on {
+        let targets = state.turn_on(&displays);
+        state.save(&path)?;
+        (targets, Vec::new())
+    } else {
+        state.turn_off(&displays)
+    };
+
+    let mut changed = Vec::new();
+    for (display, scaling) in targets {
+        if display.scaling_current != scaling {
+            apply_display_config(display, &DisplayConfig { width: display.width, height: display.height, scaling })?;
+            changed.push((display.clone(), display.scaling_current, scaling));
+        }
+    }
+    if !on {
+        state.save(&path)?;
+    }
+    Ok(LargeTextChange { on, changed, missing })
+}
@@ -0,0 +1,107 @@
+//! Machine-readable record of apply attempts, one JSON object per line.
+//!
+//! Independent of the `tracing` output and off by default; [`set_apply_log`] turns it on for
+//! the process. Logging never fails an apply: write errors are only traced.
+
+use crate::display::{ApplyReport, DisplayConfig, DisplayError, DisplayInfo};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// The log is moved to `<name>.1` (replacing an older one) once it would grow past this.
+pub const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+static APPLY_LOG: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Sets the file apply attempts are appended to, `None` to stop logging.
+pub fn set_apply_log(path: Option<PathBuf>) {
+    *APPLY_LOG.write().unwrap_or_else(std::sync::PoisonError::into_inner) = path;
+}
+
+#[must_use]
+pub fn apply_log() -> Option<PathBuf> {
+    APPLY_LOG.read().unwrap_or_else(std::sync::PoisonError::into_inner).clone()
+}
+
+/// One apply attempt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyLogEntry {
+    /// Milliseconds since the Unix epoch when the attempt finished.
+    pub timestamp_ms: u64,
+    pub source_id: u32,
+    pub device_path: String,
+    pub requested: DisplayConfig,
+    /// What the display ended up with, when it was checked afterwards.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<DisplayConfig>,
+    /// The result differs from the request, including other displays that were moved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adjusted: Option<bool>,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Win32 error or `HRESULT` behind the error, if there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+impl ApplyLogEntry {
+    /// Describes applying `requested` to `display`, which took `duration` and ended with
+    /// `result`.
+    #[must_use]
+    pub fn new(
+        display: &DisplayInfo,
+        requested: &DisplayConfig,
+        result: std::result::Result<Option<&ApplyReport>, &DisplayError>,
+        duration: Duration,
+    ) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |t| u64::try_from(t.as_millis()).unwrap_or(u64::MAX));
+        let report = result.ok().flatten();
+        let error = result.err();
+        Self {
+            timestamp_ms,
+            source_id: display.source_id,
+            device_path: display.device_path.clone(),
+            requested: requested.clone(),
+            actual: report.map(|r| r.actual.clone()),
+            adjusted: report.map(ApplyReport::adjusted),
+            ok: error.is_none(),
+            error: error.map(ToString::to_string),
+            error_code: error.and_then(DisplayError::code),
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        }
+    }
+}
+
+/// Appends `entry` to the log set with [`set_apply_log`], if any.
+pub fn record(entry: &ApplyLogEntry) {
+    let Some(path) = apply_log() else {
+        return;
+    };
+    if let Err(err) = append(&path, entry) {
+        warn!(%err, path = %path.display(), "Failed to write the apply log");
+    }
+}
+
+fn append(path: &Path, entry: &ApplyLogEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let size = fs::metadata(path).map_or(0, |m| m.len());
+    if size > 0 && size + line.len() as u64 > MAX_LOG_BYTES {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(".1");
+        fs::rename(path, rotated)?;
+    }
+    // A single write per line keeps concurrent writers from interleaving within a line.
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
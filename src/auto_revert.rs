@@ -0,0 +1,161 @@
+//! The `safety.auto_revert_seconds` net for machines administered remotely: after a command
+//! changes the configuration, a detached watchdog process restores the one from before unless
+//! another invocation confirms within the window.
+//!
+//! [`arm`] saves the state from before (see [`DisplayState`]) and a [`PendingRevert`] naming it
+//! in the app directory, then starts `display-tuner auto-revert` on its own, outside the job of
+//! a remote shell when Windows allows it, so it outlives the command and the session that ran
+//! it. [`confirm`] deletes the pending file, which the watchdog takes as cancelling. Time the
+//! machine spends asleep doesn't count: after a wake-up the window starts over.
+
+use crate::display::{app_dir, Result};
+use crate::state::DisplayState;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use windows::Win32::System::Threading::{CREATE_BREAKAWAY_FROM_JOB, CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+const PENDING_FILE: &str = "auto-revert.json";
+const STATE_FILE: &str = "auto-revert-state.json";
+
+/// How often the watchdog checks for a confirmation.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A poll this much later than [`POLL_INTERVAL`] means the machine slept in between.
+pub const SLEEP_GAP: Duration = Duration::from_secs(10);
+
+/// The subcommand the watchdog runs as.
+pub const WATCHDOG_COMMAND: &str = "auto-revert";
+
+/// A revert waiting for its deadline, kept in `%APPDATA%\display-tuner\auto-revert.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRevert {
+    /// Tells the watchdog of this revert from one started by a later command.
+    pub id: u64,
+    pub window_secs: u64,
+    /// The state to restore.
+    pub state: PathBuf,
+}
+
+/// The deadline of a watchdog that polled last at `last` and now at `now`: `deadline`, or a
+/// full `window` from `now` if the gap between the polls shows the machine was asleep.
+#[must_use]
+pub fn next_deadline(deadline: SystemTime, window: Duration, last: SystemTime, now: SystemTime) -> SystemTime {
+    match now.duration_since(last) {
+        Ok(gap) if gap >= POLL_INTERVAL + SLEEP_GAP => now + window,
+        _ => deadline,
+    }
+}
+
+/// Starts the watchdog restoring `before` in `window` unless [`confirm`]ed. Does nothing if
+/// the configuration is still `before`, so commands that changed nothing aren't reverted.
+/// Returns whether a watchdog was started.
+///
+/// # Errors
+///
+/// Returns an error if the files can't be written or the watchdog can't be started.
+pub fn arm(before: &DisplayState, window: Duration) -> Result<bool> {
+    if DisplayState::capture()? == *before {
+        return Ok(false);
+    }
+    let dir = app_dir()?;
+    fs::create_dir_all(&dir)?;
+    let state = dir.join(STATE_FILE);
+    before.save(&state)?;
+    let id = u64::from(std::process::id()) << 32 | u64::from(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos());
+    let pending = PendingRevert { id, window_secs: window.as_secs(), state };
+    fs::write(dir.join(PENDING_FILE), serde_json::to_vec(&pending)?)?;
+    spawn_watchdog(id)?;
+    info!(seconds = window.as_secs(), "Auto-revert armed");
+    Ok(true)
+}
+
+fn spawn_watchdog(id: u64) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let spawn = |flags: u32| {
+        Command::new(&exe)
+            .args([WATCHDOG_COMMAND, &id.to_string()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .creation_flags(flags)
+            .spawn()
+    };
+    let detached = DETACHED_PROCESS.0 | CREATE_NEW_PROCESS_GROUP.0;
+    // Remote shells run commands in a job that kills everything with it; leaving it is only
+    // allowed if the job says so.
+    match spawn(detached | CREATE_BREAKAWAY_FROM_JOB.0) {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+            warn!("Can't leave the job of this process, the auto-revert watchdog ends with it");
+            spawn(detached).map(drop).map_err(Into::into)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Cancels the pending revert, if any; returns whether there was one.
+///
+/// # Errors
+///
+/// Returns an error if the pending file exists but can't be deleted.
+pub fn confirm() -> Result<bool> {
+    let dir = app_dir()?;
+    match fs::remove_file(dir.join(PENDING_FILE)) {
+        Ok(()) => {
+            // The watchdog may be reading it right now; it stops either way.
+            let _ = fs::remove_file(dir.join(STATE_FILE));
+            info!("Auto-revert confirmed");
+            Ok(true)
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn load_pending(path: &Path) -> Option<PendingRevert> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+/// The watchdog: waits out the window of the revert `id` and restores its state unless it was
+/// confirmed or replaced by a later command's. Returns whether it restored.
+///
+/// # Errors
+///
+/// Returns an error if restoring fails.
+pub fn run_watchdog(id: u64) -> Result<bool> {
+    let pending_path = app_dir()?.join(PENDING_FILE);
+    let Some(pending) = load_pending(&pending_path).filter(|p| p.id == id) else {
+        return Ok(false);
+    };
+    let window = Duration::from_secs(pending.window_secs);
+    let mut last = SystemTime::now();
+    let mut deadline = last + window;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if load_pending(&pending_path).is_none_or(|p| p.id != id) {
+            info!("Auto-revert cancelled");
+            return Ok(false);
+        }
+        let now = SystemTime::now();
+        let next = next_deadline(deadline, window, last, now);
+        if next != deadline {
+            info!("Woke from sleep, the auto-revert window starts over");
+        }
+        (deadline, last) = (next, now);
+        if now >= deadline {
+            break;
+        }
+    }
+    warn!("Not confirmed, restoring the previous display configuration");
+    let result = DisplayState::load(&pending.state).and_then(|state| state.restore(true));
+    let _ = fs::remove_file(&pending_path);
+    let _ = fs::remove_file(&pending.state);
+    result.map(|_| true)
+}
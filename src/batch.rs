@@ -0,0 +1,442 @@
+//! Changes to several displays in one go, for `set --display`.
+//!
+//! Every clause is checked against the current displays before anything is applied; the
+//! resolutions, rotations, refresh rates and displays to turn off then go to Windows in one
+//! `SetDisplayConfig` call, followed by the scaling of each display. If a scaling fails, the
+//! displays are put back the way they were.
+
+use crate::display::{apply_combined, apply_display_config, enumerate_displays, match_mode, CombinedChange, ConfigSnapshot, DisplayConfig, DisplayError, DisplayInfo, DisplayMode, ModeMatch, RefreshRate, Result, Rotation, SourceMode};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use tracing::warn;
+
+/// One `--display` clause: `ID:KEY=VALUE,...` or `ID:disable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayClause {
+    pub source_id: u32,
+    pub action: ClauseAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClauseAction {
+    Disable,
+    Set(ClauseSettings),
+}
+
+/// The settings of a clause; unset ones stay as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClauseSettings {
+    /// Desktop size, as with `set --res`.
+    pub res: Option<(u32, u32)>,
+    pub refresh: Option<RefreshRate>,
+    pub scaling: Option<i32>,
+    pub rotate: Option<Rotation>,
+}
+
+impl FromStr for DisplayClause {
+    type Err = DisplayError;
+
+    /// Keys are `res`, `refresh`, `scaling` and `rotate`, case-insensitive.
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = |reason: String| DisplayError::InvalidClause { clause: text.to_string(), reason };
+        let (id, rest) = text
+            .split_once(':')
+            .ok_or_else(|| invalid("expected ID:KEY=VALUE,... or ID:disable".to_string()))?;
+        let source_id = id
+            .trim()
+            .parse()
+            .map_err(|_| invalid(format!("expected a display id before `:`, found `{}`", id.trim())))?;
+        if rest.trim().eq_ignore_ascii_case("disable") {
+            return Ok(Self { source_id, action: ClauseAction::Disable });
+        }
+
+        let mut settings = ClauseSettings::default();
+        for pair in rest.split(',') {
+            if pair.trim().eq_ignore_ascii_case("disable") {
+                return Err(invalid("`disable` can't be combined with settings".to_string()));
+            }
+            let (key, value) = pair
+                .split_once('=')
+                .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim()))
+                .ok_or_else(|| invalid(format!("expected KEY=VALUE, found `{}`", pair.trim())))?;
+            let duplicate = match key.as_str() {
+                "res" => settings.res.replace(parse_res(value).map_err(invalid)?).is_some(),
+                "refresh" => settings.refresh.replace(parse_refresh(value).map_err(invalid)?).is_some(),
+                "scaling" => settings.scaling.replace(parse_scaling(value).map_err(invalid)?).is_some(),
+                "rotate" => settings.rotate.replace(parse_rotate(value).map_err(invalid)?).is_some(),
+                _ => {
+                    return Err(invalid(format!(
+                        "unknown key `{key}`, expected one of res, refresh, scaling, rotate, or disable alone"
+                    )));
+                }
+            };
+            if duplicate {
+                return Err(invalid(format!("`{key}` is given twice")));
+            }
+        }
+        Ok(Self { source_id, action: ClauseAction::Set(settings) })
+    }
+}
+
+fn parse_res(value: &str) -> std::result::Result<(u32, u32), String> {
+    crate::display::parse_resolution(value).ok_or_else(|| format!("expected res=WIDTHxHEIGHT, found `{value}`"))
+}
+
+fn parse_refresh(value: &str) -> std::result::Result<RefreshRate, String> {
+    crate::display::parse_refresh_rate(value).ok_or_else(|| format!("expected refresh=HZ, max or min, found `{value}`"))
+}
+
+fn parse_scaling(value: &str) -> std::result::Result<i32, String> {
+    crate::display::parse_scaling(value).ok_or_else(|| format!("expected scaling=PERCENT, found `{value}`"))
+}
+
+fn parse_rotate(value: &str) -> std::result::Result<Rotation, String> {
+    match value.trim_end_matches('°') {
+        "0" => Ok(Rotation::Identity),
+        "90" => Ok(Rotation::Rotate90),
+        "180" => Ok(Rotation::Rotate180),
+        "270" => Ok(Rotation::Rotate270),
+        _ => Err(format!("expected rotate=0, 90, 180 or 270, found `{value}`")),
+    }
+}
+
+/// What [`apply_batch`] does to one display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Disable,
+    /// Each part is `None` if the display already has it.
+    Change {
+        source: Option<SourceMode>,
+        refresh: Option<u32>,
+        scaling: Option<i32>,
+    },
+}
+
+/// One clause checked against the current displays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedDisplay {
+    pub source_id: u32,
+    /// The display as it is now, `None` if no active display has the id.
+    pub display: Option<DisplayInfo>,
+    /// `None` if the clause has problems.
+    pub step: Option<Step>,
+    /// Adjustments made to the request, e.g. a resolution swapped to fit the orientation.
+    pub notes: Vec<String>,
+    /// Why the clause can't be applied.
+    pub problems: Vec<String>,
+}
+
+impl PlannedDisplay {
+    /// Whether the display already has everything the clause asks for.
+    #[must_use]
+    pub fn unchanged(&self) -> bool {
+        self.step == Some(Step::Change { source: None, refresh: None, scaling: None })
+    }
+}
+
+impl fmt::Display for PlannedDisplay {
+    /// Describes the step, e.g. `1920x1080, 144Hz, 100%`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.step {
+            None => f.write_str(&self.problems.join("; ")),
+            Some(Step::Disable) => f.write_str("turn off"),
+            Some(_) if self.unchanged() => f.write_str("already set"),
+            Some(Step::Change { source, refresh, scaling }) => {
+                let mut parts = Vec::new();
+                if let Some(source) = source {
+                    let rotation = self.display.as_ref().is_none_or(|d| d.rotation != source.rotation);
+                    parts.push(format!("{}x{}", source.width, source.height));
+                    if rotation {
+                        parts.push(format!("rotated {}", source.rotation));
+                    }
+                }
+                if let Some(hz) = refresh {
+                    parts.push(format!("{hz}Hz"));
+                }
+                if let Some(scaling) = scaling {
+                    parts.push(format!("{scaling}%"));
+                }
+                f.write_str(&parts.join(", "))
+            }
+        }
+    }
+}
+
+/// The checked clauses, in the order given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchPlan {
+    pub displays: Vec<PlannedDisplay>,
+}
+
+impl BatchPlan {
+    /// How many clauses can't be applied.
+    #[must_use]
+    pub fn rejected(&self) -> usize {
+        self.displays.iter().filter(|d| !d.problems.is_empty()).count()
+    }
+
+    /// Whether the plan turns off the display `source_id`.
+    #[must_use]
+    pub fn turns_off(&self, source_id: u32) -> bool {
+        self.displays.iter().any(|d| d.source_id == source_id && d.step == Some(Step::Disable))
+    }
+
+    /// Whether the plan turns off the display `source_id` or changes its mode (resolution,
+    /// rotation or refresh rate), either of which can leave it blank.
+    #[must_use]
+    pub fn changes_mode(&self, source_id: u32) -> bool {
+        self.displays.iter().filter(|d| d.source_id == source_id).any(|d| match &d.step {
+            Some(Step::Disable) => true,
+            Some(Step::Change { source, refresh, .. }) => source.is_some() || refresh.is_some(),
+            None => false,
+        })
+    }
+}
+
+/// Checks every clause against `displays` without changing anything. `modes` holds each
+/// display's mode list (see [`supported_modes`]); a display without one is only checked
+/// against the resolution limits, and can't use `refresh=max` or `refresh=min`.
+///
+/// [`supported_modes`]: crate::display::supported_modes
+#[must_use]
+pub fn plan_batch(clauses: &[DisplayClause], displays: &[DisplayInfo], modes: &BTreeMap<u32, Vec<DisplayMode>>) -> BatchPlan {
+    let mut plan = BatchPlan::default();
+    for (index, clause) in clauses.iter().enumerate() {
+        let display = displays.iter().find(|d| d.source_id == clause.source_id && d.active);
+        let mut planned = PlannedDisplay {
+            source_id: clause.source_id,
+            display: display.cloned(),
+            step: None,
+            notes: Vec::new(),
+            problems: Vec::new(),
+        };
+        if clauses[..index].iter().any(|c| c.source_id == clause.source_id) {
+            planned.problems.push(format!("display {} appears in more than one clause", clause.source_id));
+        }
+        match display {
+            None => planned.problems.push(DisplayError::DisplayNotFound(clause.source_id).to_string()),
+            Some(display) if !display.target_available => {
+                planned.problems.push(DisplayError::TargetUnavailable(clause.source_id).to_string());
+            }
+            Some(display) => {
+                let step = match &clause.action {
+                    ClauseAction::Disable => Step::Disable,
+                    ClauseAction::Set(settings) => {
+                        let modes = modes.get(&display.source_id).map_or(&[][..], Vec::as_slice);
+                        plan_settings(display, settings, modes, &mut planned)
+                    }
+                };
+                if planned.problems.is_empty() {
+                    planned.step = Some(step);
+                }
+            }
+        }
+        plan.displays.push(planned);
+    }
+
+    let active = displays.iter().filter(|d| d.active).count();
+    let disabled = plan.displays.iter().filter(|d| d.step == Some(Step::Disable)).count();
+    if disabled > 0 && disabled >= active {
+        for planned in plan.displays.iter_mut().filter(|d| d.step == Some(Step::Disable)) {
+            planned.step = None;
+            planned.problems.push("turning it off would leave no display on".to_string());
+        }
+    }
+    plan
+}
+
+/// The [`Step::Change`] for `settings`, recording notes and problems in `planned`.
+fn plan_settings(
+    display: &DisplayInfo,
+    settings: &ClauseSettings,
+    modes: &[DisplayMode],
+    planned: &mut PlannedDisplay,
+) -> Step {
+    let id = display.source_id;
+    let rotation = settings.rotate.unwrap_or(display.rotation);
+    let (width, height) = match settings.res {
+        Some((width, height)) => match match_mode(modes, (width, height), rotation) {
+            ModeMatch::Transposed => {
+                planned.notes.push(format!("{width}x{height} isn't supported at {rotation}, using {height}x{width}"));
+                (height, width)
+            }
+            ModeMatch::Unsupported if !modes.is_empty() => {
+                planned.problems.push(format!("display {id} doesn't support {width}x{height} at {rotation}"));
+                (width, height)
+            }
+            ModeMatch::Supported | ModeMatch::Unsupported => (width, height),
+        },
+        None if rotation.is_portrait() != display.rotation.is_portrait() => (display.height, display.width),
+        None => (display.width, display.height),
+    };
+    let scaling = settings.scaling.unwrap_or(display.scaling_current);
+    let config = DisplayConfig { width, height, scaling };
+    if let Err(error) = config.validate(display, modes) {
+        planned.problems.push(DisplayError::Invalid { source_id: id, error }.to_string());
+    }
+
+    let resized = (width, height) != (display.width, display.height);
+    let (listed_width, listed_height) = rotation.orient((width, height));
+    let refresh = match settings.refresh {
+        Some(RefreshRate::Hz(hz)) if modes.is_empty() => Some(hz),
+        Some(rate) => {
+            let hz = rate.pick(modes, listed_width, listed_height);
+            if hz.is_none() {
+                planned.problems.push(format!("display {id} doesn't offer {rate} at {width}x{height}"));
+            }
+            hz
+        }
+        None => None,
+    };
+
+    if settings.scaling.is_some() && scaling != display.scaling_current {
+        let supported = display.supported_scaling();
+        if display.custom_scaling {
+            planned.problems.push(DisplayError::CustomScalingActive(id).to_string());
+        } else if !supported.contains(&scaling) {
+            planned.problems.push(format!(
+                "display {id} supports {}-{}% scaling",
+                supported.first().copied().unwrap_or(display.scaling_current),
+                supported.last().copied().unwrap_or(display.scaling_current)
+            ));
+        }
+    }
+
+    let source = SourceMode { width, height, rotation, ..SourceMode::of(display) };
+    Step::Change {
+        source: (resized || rotation != display.rotation).then_some(source),
+        refresh: refresh.filter(|&hz| resized || hz != display.refresh_rate),
+        scaling: (scaling != display.scaling_current).then_some(scaling),
+    }
+}
+
+/// What became of one display in [`apply_batch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOutcome {
+    Applied,
+    /// The display already had the settings.
+    Unchanged,
+    /// Applying failed, with the error.
+    Failed(String),
+    /// Applied, then put back because another display failed.
+    RolledBack,
+}
+
+impl fmt::Display for BatchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Applied => f.write_str("applied"),
+            Self::Unchanged => f.write_str("unchanged"),
+            Self::Failed(error) => write!(f, "failed ({error})"),
+            Self::RolledBack => f.write_str("rolled back"),
+        }
+    }
+}
+
+/// Applies `plan`, returning the outcome for each of its displays in order.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::BatchRejected`] without changing anything if any clause has
+/// problems, [`DisplayError::ApplyTimedOut`] if a call runs over the apply timeout, or an
+/// error if the configuration can't be queried or a rollback fails.
+pub fn apply_batch(plan: &BatchPlan) -> Result<Vec<BatchOutcome>> {
+    let rejected = plan.rejected();
+    if rejected > 0 {
+        return Err(DisplayError::BatchRejected(rejected));
+    }
+    let mut outcomes: Vec<BatchOutcome> = plan
+        .displays
+        .iter()
+        .map(|d| if d.unchanged() { BatchOutcome::Unchanged } else { BatchOutcome::Applied })
+        .collect();
+
+    let snapshot = ConfigSnapshot::take()?;
+    let combined: Vec<CombinedChange> = plan
+        .displays
+        .iter()
+        .filter_map(|planned| match planned.step.as_ref()? {
+            Step::Disable => Some(CombinedChange::Disable(planned.source_id)),
+            Step::Change { source: None, refresh: None, .. } => None,
+            Step::Change { source, refresh, .. } => {
+                let display = planned.display.as_ref()?;
+                let source = source.unwrap_or_else(|| SourceMode::of(display));
+                Some(CombinedChange::Mode { source, refresh: *refresh })
+            }
+        })
+        .collect();
+    if !combined.is_empty() {
+        match apply_combined(&combined) {
+            Ok(()) => {}
+            Err(DisplayError::ApplyTimedOut) => return Err(DisplayError::ApplyTimedOut),
+            Err(err) => {
+                // Nothing was applied, so the scaling isn't attempted either.
+                for outcome in outcomes.iter_mut().filter(|o| **o == BatchOutcome::Applied) {
+                    *outcome = BatchOutcome::Failed(err.to_string());
+                }
+                return Ok(outcomes);
+            }
+        }
+    }
+
+    let mut scaled = Vec::new();
+    for (index, planned) in plan.displays.iter().enumerate() {
+        let Some(Step::Change { scaling: Some(scaling), .. }) = planned.step else {
+            continue;
+        };
+        match scale(planned.source_id, scaling) {
+            Ok(()) => scaled.push(planned),
+            Err(DisplayError::ApplyTimedOut) => return Err(DisplayError::ApplyTimedOut),
+            Err(err) => {
+                outcomes[index] = BatchOutcome::Failed(err.to_string());
+                roll_back(&scaled, &snapshot, !combined.is_empty())?;
+                for outcome in outcomes.iter_mut().filter(|o| **o == BatchOutcome::Applied) {
+                    *outcome = BatchOutcome::RolledBack;
+                }
+                return Ok(outcomes);
+            }
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Undoes an [`apply_batch`] of `plan` that succeeded: puts back the scaling it changed, then
+/// the configuration in `snapshot`, taken before applying.
+///
+/// # Errors
+///
+/// Returns an error if the configuration can't be restored.
+pub fn undo_batch(plan: &BatchPlan, snapshot: &ConfigSnapshot) -> Result<()> {
+    let scaled: Vec<&PlannedDisplay> = plan
+        .displays
+        .iter()
+        .filter(|d| matches!(d.step, Some(Step::Change { scaling: Some(_), .. })))
+        .collect();
+    roll_back(&scaled, snapshot, true)
+}
+
+/// Sets the scaling of the display with `source_id` as it is after the combined change.
+fn scale(source_id: u32, scaling: i32) -> Result<()> {
+    let now = enumerate_displays()?
+        .into_iter()
+        .find(|d| d.source_id == source_id)
+        .ok_or(DisplayError::DisplayNotFound(source_id))?;
+    apply_display_config(&now, &DisplayConfig { scaling, ..DisplayConfig::builder().resolve(&now) })
+}
+
+/// Puts back the scaling of `scaled`, then the configuration in `snapshot` if `restore`.
+fn roll_back(scaled: &[&PlannedDisplay], snapshot: &ConfigSnapshot, restore: bool) -> Result<()> {
+    for planned in scaled {
+        if let Some(display) = &planned.display
+            && let Err(err) = scale(display.source_id, display.scaling_current)
+        {
+            let source_id = display.source_id;
+            warn!(source_id, %err, "Scaling not rolled back");
+        }
+    }
+    if restore {
+        snapshot.restore()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use crate::display::{enumerate_displays, DisplayInfo, Result};
+use crate::raw;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How a window copes with DPI, read from its DPI awareness context. Windows scales the
+/// bitmaps of windows that aren't per-monitor aware, which makes them blurry on a display
+/// whose scaling differs from what they render at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DpiAwareness {
+    /// Renders at 96 DPI everywhere.
+    Unaware,
+    /// Unaware, but GDI draws text and shapes at the display's DPI; other content is stretched.
+    UnawareGdiScaled,
+    /// Renders at the DPI of the primary display when the user signed in.
+    System,
+    PerMonitor,
+    PerMonitorV2,
+    /// The window's context couldn't be read.
+    Unknown,
+}
+
+impl DpiAwareness {
+    #[must_use]
+    pub fn is_per_monitor(self) -> bool {
+        matches!(self, Self::PerMonitor | Self::PerMonitorV2)
+    }
+}
+
+impl fmt::Display for DpiAwareness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unaware => "unaware",
+            Self::UnawareGdiScaled => "unaware (GDI scaled)",
+            Self::System => "system",
+            Self::PerMonitor => "per-monitor",
+            Self::PerMonitorV2 => "per-monitor v2",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+/// A visible top-level window, as returned by [`raw::top_level_windows`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopLevelWindow {
+    pub title: String,
+    pub process_id: u32,
+    /// File name of the executable; `None` if the process couldn't be opened.
+    pub process_name: Option<String>,
+    /// GDI device name of the monitor showing most of the window.
+    pub monitor_device: String,
+    pub awareness: DpiAwareness,
+}
+
+/// The windows of one process on one display that Windows scales as bitmaps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlurryProcess {
+    /// Executable file name, or `pid <id>` if it couldn't be read.
+    pub process: String,
+    pub awareness: DpiAwareness,
+    pub windows: Vec<String>,
+}
+
+/// A display not at 100% scaling, with the processes whose windows on it are likely blurry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DisplayBlur {
+    pub source_id: u32,
+    pub friendly_name: String,
+    pub scaling: i32,
+    pub processes: Vec<BlurryProcess>,
+}
+
+/// Groups the windows that aren't per-monitor aware by the display they're on and their
+/// process name. Only active displays not at 100% scaling are listed, each even if none of its
+/// windows is blurry; windows whose awareness is unknown are left out.
+#[must_use]
+pub fn group_blurry_windows(displays: &[DisplayInfo], windows: &[TopLevelWindow]) -> Vec<DisplayBlur> {
+    displays
+        .iter()
+        .filter(|d| d.active && d.scaling_current != 100)
+        .map(|display| {
+            let mut processes: BTreeMap<(String, DpiAwareness), Vec<String>> = BTreeMap::new();
+            for window in windows.iter().filter(|w| {
+                w.monitor_device.eq_ignore_ascii_case(&display.gdi_device_name)
+                    && !w.awareness.is_per_monitor()
+                    && w.awareness != DpiAwareness::Unknown
+            }) {
+                let process = window.process_name.clone().unwrap_or_else(|| format!("pid {}", window.process_id));
+                processes.entry((process, window.awareness)).or_default().push(window.title.clone());
+            }
+            DisplayBlur {
+                source_id: display.source_id,
+                friendly_name: display.friendly_name.clone(),
+                scaling: display.scaling_current,
+                processes: processes
+                    .into_iter()
+                    .map(|((process, awareness), windows)| BlurryProcess { process, awareness, windows })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Finds the windows likely to be blurry on the displays connected now.
+///
+/// # Errors
+///
+/// Returns an error if the displays or the windows can't be enumerated.
+pub fn blur_check() -> Result<Vec<DisplayBlur>> {
+    Ok(group_blurry_windows(&enumerate_displays()?, &raw::top_level_windows()?))
+}
+
+impl fmt::Display for DisplayBlur {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (id {}) at {}%", self.friendly_name, self.source_id, self.scaling)?;
+        if self.processes.is_empty() {
+            return write!(f, ": no blurry windows");
+        }
+        for process in &self.processes {
+            write!(f, "\n  {} [{}]", process.process, process.awareness)?;
+            for title in &process.windows {
+                write!(f, "\n    {title}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! Typed access to a running daemon (see [`crate::daemon`]) for other Rust programs, instead
+//! of writing JSON to the pipe by hand.
+//!
+//! ```no_run
+//! use display_tuner::client::{DaemonClient, DisplayConfigDelta, Selector};
+//!
+//! let mut client = DaemonClient::connect()?;
+//! for display in client.list()? {
+//!     println!("{display}");
+//! }
+//! client.set(Selector::Id(1), DisplayConfigDelta { scaling: Some(150), ..DisplayConfigDelta::default() })?;
+//! # Ok::<(), display_tuner::display::DisplayError>(())
+//! ```
+
+use crate::daemon::{open_pipe, ProgressMessage, Request, Response, PROTOCOL_VERSION};
+use crate::display::{DisplayCapabilities, DisplayError, DisplayInfo, Result};
+use crate::metrics::Stats;
+use crate::progress::DisplayProgress;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use tracing::debug;
+
+/// The displays a [`DaemonClient::set`] call changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selector {
+    Id(u32),
+    /// A stable handle from [`DaemonClient::handles`], which survives dock and undock.
+    Handle(u32),
+    /// Every display that's awake.
+    All,
+}
+
+/// Settings to change; unset ones keep their current value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DisplayConfigDelta {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Scaling in percent, e.g. `150`.
+    pub scaling: Option<i32>,
+}
+
+/// A connection to the daemon that has agreed on [`PROTOCOL_VERSION`]. Requests are answered
+/// one at a time; the daemon serves one client at a time, so keep connections short.
+#[derive(Debug)]
+pub struct DaemonClient<S = File> {
+    stream: BufReader<S>,
+}
+
+impl DaemonClient<File> {
+    /// Connects to the daemon on [`PIPE_NAME`](crate::daemon::PIPE_NAME).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::DaemonNotRunning`] if nobody listens on the pipe, or
+    /// [`DisplayError::ProtocolMismatch`] if the daemon speaks another protocol version.
+    pub fn connect() -> Result<Self> {
+        Self::over(open_pipe()?)
+    }
+}
+
+impl<S: Read + Write> DaemonClient<S> {
+    /// Talks to a daemon over `stream`, starting with the version handshake.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::ProtocolMismatch`] if the daemon speaks another protocol
+    /// version, or an error if the exchange fails.
+    pub fn over(stream: S) -> Result<Self> {
+        let mut client = Self { stream: BufReader::new(stream) };
+        let response = client.request(&Request::Hello { version: PROTOCOL_VERSION })?;
+        match response.version {
+            Some(PROTOCOL_VERSION) if response.ok => Ok(client),
+            // Daemons from before versioning reject `hello` as an unknown command.
+            daemon => Err(DisplayError::ProtocolMismatch { client: PROTOCOL_VERSION, daemon: daemon.unwrap_or(0) }),
+        }
+    }
+
+    /// Sends `request` and returns the response as is, including failed ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the exchange fails or the daemon closes the connection.
+    pub fn request(&mut self, request: &Request) -> Result<Response> {
+        self.request_with_progress(request, &mut |_| {})
+    }
+
+    /// [`request`](Self::request), passing the [`ProgressMessage`]s before the response to
+    /// `on_progress`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the exchange fails or the daemon closes the connection.
+    pub fn request_with_progress(&mut self, request: &Request, on_progress: &mut dyn FnMut(&DisplayProgress)) -> Result<Response> {
+        debug!(?request, "Sending request to the daemon");
+        let writer = self.stream.get_mut();
+        serde_json::to_writer(&mut *writer, request)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+            }
+            match serde_json::from_str::<ProgressMessage>(&line) {
+                Ok(message) => on_progress(&message.progress),
+                Err(_) => return Ok(serde_json::from_str(&line)?),
+            }
+        }
+    }
+
+    /// Sends `request` and returns the displays after it was handled.
+    fn displays(&mut self, request: &Request) -> Result<Vec<DisplayInfo>> {
+        let response = self.request(request)?;
+        if !response.ok {
+            return Err(DisplayError::Daemon(response.error.unwrap_or_else(|| "request failed".to_string())));
+        }
+        Ok(response.displays)
+    }
+
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] if the daemon can't enumerate the displays, or an error
+    /// if the exchange fails.
+    pub fn list(&mut self) -> Result<Vec<DisplayInfo>> {
+        self.displays(&Request::List)
+    }
+
+    /// The displays, each with the handle the daemon keeps for it while Windows reshuffles
+    /// source ids, see [`crate::handles`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] if the daemon can't enumerate the displays, or an error
+    /// if the exchange fails.
+    pub fn handles(&mut self) -> Result<Vec<(u32, DisplayInfo)>> {
+        let response = self.request(&Request::List)?;
+        if !response.ok {
+            return Err(DisplayError::Daemon(response.error.unwrap_or_else(|| "request failed".to_string())));
+        }
+        Ok(response.handles.into_iter().zip(response.displays).collect())
+    }
+
+    /// The displays, each with its capabilities; the daemon keeps the DDC/CI answers until the
+    /// displays change.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] if the daemon can't probe the displays, or an error if
+    /// the exchange fails.
+    pub fn capabilities(&mut self) -> Result<Vec<(DisplayInfo, DisplayCapabilities)>> {
+        let response = self.request(&Request::Capabilities)?;
+        if !response.ok {
+            return Err(DisplayError::Daemon(response.error.unwrap_or_else(|| "request failed".to_string())));
+        }
+        Ok(response.displays.into_iter().zip(response.capabilities).collect())
+    }
+
+    /// Changes the displays picked by `selector` and returns all displays afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] with the daemon's message if the change fails, or an
+    /// error if the exchange fails.
+    pub fn set(&mut self, selector: Selector, delta: DisplayConfigDelta) -> Result<Vec<DisplayInfo>> {
+        let (id, handle) = match selector {
+            Selector::Id(id) => (Some(id), None),
+            Selector::Handle(handle) => (None, Some(handle)),
+            Selector::All => (None, None),
+        };
+        self.displays(&Request::Set { id, handle, width: delta.width, height: delta.height, scaling: delta.scaling })
+    }
+
+    /// Applies the saved profile `name` and returns the displays afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] with the daemon's message if the profile doesn't exist
+    /// or can't be applied, or an error if the exchange fails.
+    pub fn apply_profile(&mut self, name: &str) -> Result<Vec<DisplayInfo>> {
+        self.displays(&Request::ProfileApply { name: name.to_string(), progress: false })
+    }
+
+    /// [`apply_profile`](Self::apply_profile), passing each display's progress to
+    /// `on_progress` as the daemon reports it, e.g. for a progress bar.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] with the daemon's message if the profile doesn't exist
+    /// or can't be applied, or an error if the exchange fails.
+    pub fn apply_profile_with_progress(&mut self, name: &str, mut on_progress: impl FnMut(&DisplayProgress)) -> Result<Vec<DisplayInfo>> {
+        let response = self.request_with_progress(&Request::ProfileApply { name: name.to_string(), progress: true }, &mut on_progress)?;
+        if !response.ok {
+            return Err(DisplayError::Daemon(response.error.unwrap_or_else(|| "request failed".to_string())));
+        }
+        Ok(response.displays)
+    }
+
+    /// Undoes the last change made through the daemon and returns the displays afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] if there's nothing to revert or reverting fails, or an
+    /// error if the exchange fails.
+    pub fn revert(&mut self) -> Result<Vec<DisplayInfo>> {
+        self.displays(&Request::Revert)
+    }
+
+    /// The daemon's counters since it started, see [`crate::metrics`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::Daemon`] if the daemon doesn't answer with them, or an error if
+    /// the exchange fails.
+    pub fn stats(&mut self) -> Result<Stats> {
+        let response = self.request(&Request::Stats)?;
+        match response.stats {
+            Some(stats) if response.ok => Ok(stats),
+            _ => Err(DisplayError::Daemon(response.error.unwrap_or_else(|| "no stats in the response".to_string()))),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::display::{DisplayError, Result};
+use crate::groups::GroupConfig;
+use crate::display::app_dir;
+use crate::selector::{Aliases, DisplayFilter};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// What `config init` writes: every setting, commented out at its default.
+pub const TEMPLATE: &str = r#"# display-tuner settings. Flags given on the command line take precedence.
+
+[output]
+# Output of `list`: "table", "json" or "csv".
+# format = "table"
+# Highlight the table in color when printing to a terminal.
+# color = true
+# Advise `set` users lowering the resolution to raise the scaling instead.
+# advice = true
+
+[safety]
+# Revert `set` unless the change is confirmed within this many seconds.
+# confirm = 15
+# Revert every command changing the displays (set, swap, arrange, copy-config, profile apply,
+# state restore) unless another display-tuner command, e.g. `display-tuner confirm`, runs
+# within this many seconds. The revert happens even if the command's process is gone.
+# auto_revert_seconds = 20
+# Validate every change before applying any of them, like `set --safe`.
+# validate_first = false
+# Retry applying after a transient failure, waiting this many milliseconds before the first
+# retry and twice as long before each further one.
+# retries = 0
+# retry_delay = 500
+
+# Names for single displays, as in groups.toml.
+[aliases]
+# tv = { vendor = "SAM" }
+
+# Named sets of displays, as in groups.toml.
+[groups]
+# desk = [{ vendor = "DEL" }, { connector = "hdmi" }]
+"#;
+
+/// Defaults for the command line, loaded from `%APPDATA%\display-tuner\config.toml`. Every
+/// setting is optional; see [`TEMPLATE`] for the full shape.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub output: OutputDefaults,
+    #[serde(default)]
+    pub safety: SafetyDefaults,
+    /// Merged with those of `groups.toml`, see [`Config::merge_groups`].
+    #[serde(default)]
+    pub aliases: Aliases,
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<DisplayFilter>>,
+}
+
+/// The `[output]` section.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputDefaults {
+    pub format: Option<OutputFormat>,
+    pub color: Option<bool>,
+    /// Whether `set` prints advice; `--quiet` turns it off too.
+    pub advice: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// The `[safety]` section.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SafetyDefaults {
+    /// Seconds to wait for confirmation before reverting.
+    pub confirm: Option<u64>,
+    /// Seconds before [`auto_revert`](crate::auto_revert) restores the configuration.
+    pub auto_revert_seconds: Option<u64>,
+    #[serde(default)]
+    pub validate_first: bool,
+    pub retries: Option<u32>,
+    /// Milliseconds before the first retry.
+    pub retry_delay: Option<u64>,
+}
+
+impl Config {
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::InvalidConfig`] naming the line and key if `text` isn't valid
+    /// TOML of this shape.
+    pub fn parse(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|err| config_error(text, &err))
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// `%APPDATA%\display-tuner\config.toml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::NoAppData`] if `APPDATA` isn't set.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(app_dir()?.join(CONFIG_FILE))
+    }
+
+    /// Loads the settings from [`default_path`](Self::default_path); no file means defaults.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load_default() -> Result<Self> {
+        match Self::load(&Self::default_path()?) {
+            Err(DisplayError::Io(err)) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    /// Adds the aliases and groups defined here to `groups`; those of `groups` win when both
+    /// define the same name.
+    #[must_use]
+    pub fn merge_groups(&self, mut groups: GroupConfig) -> GroupConfig {
+        for (name, filter) in &self.aliases {
+            groups.aliases.entry(name.clone()).or_insert_with(|| filter.clone());
+        }
+        for (name, members) in &self.groups {
+            groups.groups.entry(name.clone()).or_insert_with(|| members.clone());
+        }
+        groups
+    }
+}
+
+/// Writes [`TEMPLATE`] to `path`, creating its directory.
+///
+/// # Errors
+///
+/// Returns an I/O error of kind [`ErrorKind::AlreadyExists`] if there is a file at `path`
+/// already, which is left untouched.
+pub fn write_template(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(TEMPLATE.as_bytes())?;
+    Ok(())
+}
+
+/// Locates a TOML error in `text`: the line it starts on and the key on that line, qualified
+/// by the table it's in (`safety.retries`).
+fn config_error(text: &str, err: &toml::de::Error) -> DisplayError {
+    let start = err.span().map_or(0, |span| span.start.min(text.len()));
+    let line = text[..start].matches('\n').count() + 1;
+    let mut table = None;
+    for header in text.lines().take(line) {
+        let header = header.trim();
+        if let Some(name) = header.strip_prefix('[') {
+            table = Some(name.trim_start_matches('[').split(']').next().unwrap_or_default().trim());
+        }
+    }
+    let current = text.lines().nth(line - 1).unwrap_or_default().trim();
+    let key = if current.starts_with('[') {
+        table.unwrap_or_default().to_string()
+    } else {
+        let name = current.split('=').next().unwrap_or_default().trim().trim_matches('"');
+        match table {
+            Some(table) if !name.is_empty() => format!("{table}.{name}"),
+            _ => name.to_string(),
+        }
+    };
+    DisplayError::InvalidConfig {
+        line,
+        key,
+        message: err.message().trim().to_string(),
+    }
+}
@@ -0,0 +1,249 @@
+//! Copies one display's settings onto another, property by property, for `copy-config`.
+//!
+//! Position and primary status stay with the destination; everything else that describes how
+//! the desktop looks on the monitor is copied where the destination supports it.
+
+use crate::display::{apply_display_config, apply_source_modes, enumerate_displays, set_refresh_rate, set_scale_mode, supported_modes, DisplayConfig, DisplayError, DisplayInfo, DisplayMode, RefreshRate, Result, SourceMode};
+use std::fmt;
+use tracing::debug;
+
+/// A setting [`copy_config`] copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Resolution,
+    RefreshRate,
+    Scaling,
+    Rotation,
+    ScaleMode,
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Resolution => "resolution",
+            Self::RefreshRate => "refresh rate",
+            Self::Scaling => "scaling",
+            Self::Rotation => "rotation",
+            Self::ScaleMode => "scale mode",
+        })
+    }
+}
+
+/// What happened to one property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Copied,
+    /// The destination already had the value.
+    Unchanged,
+    /// The destination doesn't support the value, with the reason.
+    Skipped(String),
+    /// Applying the value failed, with the error.
+    Failed(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Copied => f.write_str("copied"),
+            Self::Unchanged => f.write_str("unchanged"),
+            Self::Skipped(reason) => write!(f, "skipped ({reason})"),
+            Self::Failed(error) => write!(f, "failed ({error})"),
+        }
+    }
+}
+
+/// One property of a copy: the source's value, as text, and what became of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyCopy {
+    pub property: Property,
+    pub value: String,
+    pub outcome: Outcome,
+}
+
+/// The result of copying onto one destination display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyReport {
+    pub source_id: u32,
+    pub friendly_name: String,
+    /// One entry per [`Property`], in declaration order.
+    pub properties: Vec<PropertyCopy>,
+}
+
+impl CopyReport {
+    /// Whether applying any property failed; skipped properties don't count.
+    #[must_use]
+    pub fn failed(&self) -> bool {
+        self.properties.iter().any(|p| matches!(p.outcome, Outcome::Failed(_)))
+    }
+
+    /// Records the result of applying `properties` together.
+    fn record(&mut self, properties: &[Property], result: Result<()>) -> Result<()> {
+        let outcome = match result {
+            Ok(()) => Outcome::Copied,
+            // The call is still running, so the remaining properties would race with it.
+            Err(DisplayError::ApplyTimedOut) => return Err(DisplayError::ApplyTimedOut),
+            Err(err) => Outcome::Failed(err.to_string()),
+        };
+        for entry in self.properties.iter_mut().filter(|p| properties.contains(&p.property)) {
+            entry.outcome = outcome.clone();
+        }
+        Ok(())
+    }
+
+    fn copies(&self, property: Property) -> bool {
+        self.properties.iter().any(|p| p.property == property && p.outcome == Outcome::Copied)
+    }
+}
+
+/// The landscape size of `display`'s current mode.
+fn native_size(display: &DisplayInfo) -> (u32, u32) {
+    display.unrotated_size()
+}
+
+/// Whether `modes` lists `size` in either orientation; drivers differ in which one they report
+/// for a rotated display.
+fn mode_listed(modes: &[DisplayMode], (width, height): (u32, u32)) -> bool {
+    modes
+        .iter()
+        .any(|m| (m.width, m.height) == (width, height) || (m.width, m.height) == (height, width))
+}
+
+/// Decides, without changing anything, what copying `from` onto `to` would do. `modes` is
+/// `to`'s mode list (see [`supported_modes`]); every property is planned as
+/// [`Outcome::Copied`], [`Outcome::Unchanged`] or [`Outcome::Skipped`].
+#[must_use]
+pub fn plan_copy(from: &DisplayInfo, to: &DisplayInfo, modes: &[DisplayMode]) -> CopyReport {
+    let target = to.source_id;
+    let resolution = native_size(from);
+    let resolution_outcome = if resolution == native_size(to) {
+        Outcome::Unchanged
+    } else if mode_listed(modes, resolution) {
+        Outcome::Copied
+    } else {
+        Outcome::Skipped(format!("display {target} doesn't support it"))
+    };
+
+    let (width, height) = if resolution_outcome == Outcome::Copied { resolution } else { native_size(to) };
+    let refresh_outcome = if from.refresh_rate == 0 {
+        Outcome::Skipped("unknown on the source display".to_string())
+    } else if from.refresh_rate == to.refresh_rate && (width, height) == native_size(to) {
+        Outcome::Unchanged
+    } else if [(width, height), (height, width)]
+        .iter()
+        .any(|&(w, h)| RefreshRate::Hz(from.refresh_rate).pick(modes, w, h).is_some())
+    {
+        Outcome::Copied
+    } else {
+        Outcome::Skipped(format!("display {target} doesn't offer it at {width}x{height}"))
+    };
+
+    let supported = to.supported_scaling();
+    let scaling_outcome = if from.scaling_current == to.scaling_current {
+        Outcome::Unchanged
+    } else if to.custom_scaling {
+        Outcome::Skipped(format!("display {target} has a custom scaling override"))
+    } else if supported.contains(&from.scaling_current) {
+        Outcome::Copied
+    } else {
+        Outcome::Skipped(format!(
+            "display {target} supports {}-{}%",
+            supported.first().copied().unwrap_or(to.scaling_current),
+            supported.last().copied().unwrap_or(to.scaling_current)
+        ))
+    };
+
+    let changed = |same: bool| if same { Outcome::Unchanged } else { Outcome::Copied };
+    let (from_width, from_height) = resolution;
+    CopyReport {
+        source_id: to.source_id,
+        friendly_name: to.friendly_name.clone(),
+        properties: vec![
+            PropertyCopy {
+                property: Property::Resolution,
+                value: format!("{from_width}x{from_height}"),
+                outcome: resolution_outcome,
+            },
+            PropertyCopy {
+                property: Property::RefreshRate,
+                value: format!("{}Hz", from.refresh_rate),
+                outcome: refresh_outcome,
+            },
+            PropertyCopy {
+                property: Property::Scaling,
+                value: format!("{}%", from.scaling_current),
+                outcome: scaling_outcome,
+            },
+            PropertyCopy {
+                property: Property::Rotation,
+                value: from.rotation.to_string(),
+                outcome: changed(from.rotation == to.rotation),
+            },
+            PropertyCopy {
+                property: Property::ScaleMode,
+                value: from.scale_mode.to_string(),
+                outcome: changed(from.scale_mode == to.scale_mode),
+            },
+        ],
+    }
+}
+
+/// `display` as enumerated right now, so every step applies against fresh state.
+fn current(source_id: u32) -> Result<DisplayInfo> {
+    enumerate_displays()?
+        .into_iter()
+        .find(|d| d.source_id == source_id)
+        .ok_or(DisplayError::DisplayNotFound(source_id))
+}
+
+/// Copies resolution, refresh rate, scaling, rotation and scale mode from `from` to `to`,
+/// leaving `to`'s position and primary status alone. Properties `to` doesn't support are
+/// skipped, and a property that fails to apply doesn't stop the others.
+///
+/// Resolution and rotation change in one `SetDisplayConfig` call, so they fail together.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::TargetUnavailable`] if `to` is asleep, [`DisplayError::ApplyTimedOut`]
+/// if a call runs over the apply timeout, or an error if the displays can't be enumerated.
+pub fn copy_config(from: &DisplayInfo, to: &DisplayInfo) -> Result<CopyReport> {
+    if !to.target_available {
+        return Err(DisplayError::TargetUnavailable(to.source_id));
+    }
+    let source_id = to.source_id;
+    let modes = supported_modes(to).unwrap_or_else(|err| {
+        debug!(source_id, %err, "No mode list, copying without one");
+        Vec::new()
+    });
+    let mut report = plan_copy(from, to, &modes);
+
+    let resolution = report.copies(Property::Resolution);
+    let rotation = report.copies(Property::Rotation);
+    if resolution || rotation {
+        let native = if resolution { native_size(from) } else { native_size(to) };
+        let turned = if rotation { from.rotation } else { to.rotation };
+        let (width, height) = turned.orient(native);
+        let mode = SourceMode { width, height, rotation: turned, ..SourceMode::of(to) };
+        let properties: Vec<Property> = [(resolution, Property::Resolution), (rotation, Property::Rotation)]
+            .into_iter()
+            .filter_map(|(copies, property)| copies.then_some(property))
+            .collect();
+        report.record(&properties, apply_source_modes(&[mode]))?;
+    }
+    if report.copies(Property::RefreshRate) {
+        let result = current(source_id).and_then(|now| set_refresh_rate(&now, from.refresh_rate));
+        report.record(&[Property::RefreshRate], result)?;
+    }
+    if report.copies(Property::ScaleMode) {
+        let result = current(source_id).and_then(|now| set_scale_mode(&now, from.scale_mode));
+        report.record(&[Property::ScaleMode], result)?;
+    }
+    if report.copies(Property::Scaling) {
+        let result = current(source_id).and_then(|now| {
+            let config = DisplayConfig::builder().scaling(from.scaling_current).resolve(&now);
+            apply_display_config(&now, &config)
+        });
+        report.record(&[Property::Scaling], result)?;
+    }
+
+    Ok(report)
+}
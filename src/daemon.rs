@@ -0,0 +1,463 @@
+use crate::client::DaemonClient;
+use crate::ddc::DdcCache;
+use crate::display::{apply_display_config, enumerate_displays, DisplayCapabilities, DisplayConfig, DisplayError, DisplayInfo, DisplayTuner, Result};
+use crate::handles::HandleTable;
+use crate::layout::{desktop_summary, DesktopSummary};
+use crate::metrics::{self, Phase, Stats};
+use crate::profile::{apply_profile, apply_profile_with_progress, Profile};
+use crate::progress::DisplayProgress;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::windows::io::FromRawHandle;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+use windows::Win32::Foundation::{CloseHandle, LocalFree, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, HANDLE, HLOCAL};
+use windows::Win32::Security::Authorization::{ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1};
+use windows::Win32::Security::{GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY, TOKEN_USER};
+use windows::Win32::Storage::FileSystem::{FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX};
+use windows::Win32::System::Pipes::{ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT};
+use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+use windows::core::{HSTRING, PWSTR};
+
+pub const PIPE_NAME: &str = r"\\.\pipe\display-tuner";
+
+/// Version of the request/response format, bumped on incompatible changes. Clients announce
+/// theirs with [`Request::Hello`].
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long a client keeps retrying while the daemon is busy with another client.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const BUSY_RETRY: Duration = Duration::from_millis(50);
+const CREATE_RETRY: Duration = Duration::from_millis(500);
+const PIPE_BUFFER_SIZE: u32 = 64 * 1024;
+
+/// A command sent to the daemon, one JSON object per line, e.g.
+/// `{"command":"set","id":1,"scaling":150}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Announces the client's [`PROTOCOL_VERSION`]; the daemon answers with its own and fails
+    /// the request if they differ. Optional, plain JSON clients can skip it.
+    Hello {
+        version: u32,
+    },
+    List,
+    /// Lists the displays with their capabilities. DDC/CI answers are cached until the
+    /// displays change.
+    Capabilities,
+    /// Unset fields keep their current value; without `id` or `handle` every display is
+    /// changed. `handle` is one of [`Response::handles`] and takes precedence over `id`.
+    Set {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        handle: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        width: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        height: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "crate::display::deserialize_optional_scaling")]
+        scaling: Option<i32>,
+    },
+    /// With `progress`, a [`ProgressMessage`] line per display and phase comes before the
+    /// response.
+    ProfileApply {
+        name: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        progress: bool,
+    },
+    /// Restores the displays as they were before the last change made through the daemon.
+    Revert,
+    /// The daemon's counters since it started, see [`metrics`].
+    Stats,
+}
+
+/// The daemon's answer to a [`Request`], one JSON object per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Stable code of the error, see [`DisplayError::kind`]; `error` has the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// The displays after handling the request.
+    #[serde(default)]
+    pub displays: Vec<DisplayInfo>,
+    /// The virtual desktop those displays span.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desktop: Option<DesktopSummary>,
+    /// The daemon's [`PROTOCOL_VERSION`], in answer to [`Request::Hello`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// Those of each display, in the same order, in answer to [`Request::Capabilities`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<DisplayCapabilities>,
+    /// The stable handle of each display, in the same order; see [`HandleTable`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub handles: Vec<u32>,
+    /// In answer to [`Request::Stats`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+}
+
+/// How far a request asking for progress got, sent before its [`Response`], e.g.
+/// `{"progress":{"index":2,"total":6,"scaling":"running",...}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressMessage {
+    pub progress: DisplayProgress,
+}
+
+impl Response {
+    fn from_result(result: Result<Vec<DisplayInfo>>) -> Self {
+        match result {
+            Ok(displays) => Self { ok: true, desktop: desktop_summary(&displays), displays, ..Self::default() },
+            Err(err) => Self::failure(err.kind(), err.to_string()),
+        }
+    }
+
+    fn failure(code: &str, message: String) -> Self {
+        Self { ok: false, error: Some(message), code: Some(code.to_string()), ..Self::default() }
+    }
+
+    fn from_capabilities(result: Result<(Vec<DisplayInfo>, Vec<DisplayCapabilities>)>) -> Self {
+        match result {
+            Ok((displays, capabilities)) => Self { capabilities, ..Self::from_result(Ok(displays)) },
+            Err(err) => Self::from_result(Err(err)),
+        }
+    }
+
+    fn hello(client: u32) -> Self {
+        let response = if client == PROTOCOL_VERSION {
+            Self { ok: true, ..Self::default() }
+        } else {
+            let mismatch = DisplayError::ProtocolMismatch { client, daemon: PROTOCOL_VERSION };
+            Self::failure(mismatch.kind(), format!("Protocol version {client} isn't supported, the daemon speaks {PROTOCOL_VERSION}"))
+        };
+        Self { version: Some(PROTOCOL_VERSION), ..response }
+    }
+}
+
+/// State kept between requests.
+#[derive(Debug, Default)]
+pub struct Daemon {
+    /// Displays before the last change, restored by [`Request::Revert`].
+    previous: Option<Vec<DisplayInfo>>,
+    /// DDC/CI answers for [`Request::Capabilities`]; probing takes up to a few seconds.
+    ddc: DdcCache,
+    handles: HandleTable,
+    /// Where `handles` is saved, `None` to keep it in memory.
+    handles_path: Option<PathBuf>,
+}
+
+impl Daemon {
+    /// A daemon keeping the display handles in `path` (see [`HandleTable::default_path`]),
+    /// starting from those saved there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the saved handles can't be read.
+    pub fn with_handles(path: PathBuf) -> Result<Self> {
+        Ok(Self { handles: HandleTable::load(&path)?, handles_path: Some(path), ..Self::default() })
+    }
+
+    /// Handles one request. Displays are enumerated fresh every time, so topology changes and
+    /// sleep/resume between requests need no special handling.
+    pub fn handle(&mut self, request: &Request) -> Response {
+        self.handle_with_progress(request, &mut |_| {})
+    }
+
+    /// [`handle`](Self::handle), telling `on_progress` how a profile apply goes.
+    pub fn handle_with_progress(&mut self, request: &Request, on_progress: &mut dyn FnMut(&DisplayProgress)) -> Response {
+        debug!(?request, "Handling request");
+        let started = Instant::now();
+        let response = self.respond(request, on_progress);
+        // Clients are served one at a time, so the request held up everyone else this long.
+        let elapsed = started.elapsed();
+        metrics::record_phase(Phase::Request, elapsed);
+        metrics::record_lag(elapsed);
+        response
+    }
+
+    fn respond(&mut self, request: &Request, on_progress: &mut dyn FnMut(&DisplayProgress)) -> Response {
+        let response = match request {
+            Request::Hello { version } => return Response::hello(*version),
+            Request::Stats => return Response { ok: true, stats: Some(metrics::snapshot()), ..Response::default() },
+            Request::Capabilities => Response::from_capabilities(self.capabilities()),
+            _ => {
+                let result = self.dispatch(request, on_progress);
+                if matches!(request, Request::Set { .. } | Request::ProfileApply { .. } | Request::Revert) {
+                    metrics::record_apply(result.as_ref().err().map(DisplayError::kind));
+                }
+                Response::from_result(result)
+            }
+        };
+        Response { handles: self.assign_handles(&response.displays), ..response }
+    }
+
+    /// The handle of each of `displays`, saving the table when it changed.
+    fn assign_handles(&mut self, displays: &[DisplayInfo]) -> Vec<u32> {
+        // Only new, moved and forgotten monitors are worth a write; `last_seen` goes along.
+        let layout = |table: &HandleTable| table.entries.iter().map(|e| (e.handle, e.device_path.clone())).collect::<Vec<_>>();
+        let before = layout(&self.handles);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let handles = self.handles.assign(displays, now);
+        if let (true, Some(path)) = (layout(&self.handles) != before, &self.handles_path)
+            && let Err(err) = self.handles.save(path)
+        {
+            warn!(%err, "Failed to save the display handles");
+        }
+        handles
+    }
+
+    fn capabilities(&mut self) -> Result<(Vec<DisplayInfo>, Vec<DisplayCapabilities>)> {
+        let tuner = DisplayTuner::new()?;
+        let capabilities = tuner.capabilities_cached(tuner.displays(), &mut self.ddc)?;
+        Ok((tuner.displays().to_vec(), capabilities))
+    }
+
+    fn dispatch(&mut self, request: &Request, on_progress: &mut dyn FnMut(&DisplayProgress)) -> Result<Vec<DisplayInfo>> {
+        let displays = enumerate_displays()?;
+        match request {
+            Request::Hello { .. } | Request::List | Request::Capabilities | Request::Stats => return Ok(displays),
+            Request::Set { id, handle, width, height, scaling } => {
+                let id = match handle {
+                    Some(handle) => {
+                        self.assign_handles(&displays);
+                        let display = self.handles.resolve(*handle, &displays).ok_or(DisplayError::UnknownHandle(*handle))?;
+                        Some(display.source_id)
+                    }
+                    None => *id,
+                };
+                // Without an id, monitors that are asleep are skipped rather than failing the request.
+                let targets: Vec<&DisplayInfo> = displays
+                    .iter()
+                    .filter(|d| id.map_or(d.target_available, |id| d.source_id == id))
+                    .collect();
+                if let (Some(id), true) = (id, targets.is_empty()) {
+                    return Err(DisplayError::DisplayNotFound(id));
+                }
+                for display in targets {
+                    let config = DisplayConfig::builder()
+                        .width(*width)
+                        .height(*height)
+                        .scaling(*scaling)
+                        .resolve(display);
+                    apply_display_config(display, &config)?;
+                }
+            }
+            Request::ProfileApply { name, .. } => apply_profile_with_progress(&Profile::load(name)?, &displays, on_progress)?,
+            Request::Revert => {
+                let previous = self.previous.take().ok_or(DisplayError::NothingToRevert)?;
+                apply_profile(&Profile::capture(&previous), &displays)?;
+            }
+        }
+
+        self.ddc.invalidate();
+        let after = enumerate_displays()?;
+        #[cfg(feature = "notify")]
+        crate::notify::announce(&displays, &after, *request != Request::Revert);
+        if *request != Request::Revert {
+            self.previous = Some(displays);
+        }
+        Ok(after)
+    }
+}
+
+/// Serves requests on [`PIPE_NAME`] for good, one client at a time so changes
+/// from concurrent clients never interleave.
+///
+/// The pipe only accepts local clients running as the current user.
+///
+/// # Errors
+///
+/// Returns an error if the pipe can't be created, e.g. because another daemon is running.
+pub fn serve() -> Result<()> {
+    let security = UserOnlySecurity::new()?;
+    let mut daemon = Daemon::with_handles(HandleTable::default_path()?)?;
+    metrics::mark_started();
+    info!(pipe = PIPE_NAME, "Daemon listening");
+
+    let mut pipe = create_pipe(&security, true)?;
+    loop {
+        // SAFETY: pipe is a valid pipe handle; without an OVERLAPPED the call blocks.
+        let connected = unsafe { ConnectNamedPipe(pipe, None) };
+        // The next instance exists before this one closes, so clients never find the pipe
+        // missing and nobody else can take the name over in between.
+        let next = next_pipe(&security);
+        if let Err(err) = connected
+            && err.code() != ERROR_PIPE_CONNECTED.to_hresult()
+        {
+            warn!(%err, "Client failed to connect");
+            // SAFETY: the handle is owned here and not used afterwards.
+            let _ = unsafe { CloseHandle(pipe) };
+        } else {
+            // SAFETY: the File takes ownership of the connected handle and closes it on drop.
+            let stream = unsafe { File::from_raw_handle(pipe.0) };
+            if let Err(err) = serve_client(&stream, &mut daemon) {
+                warn!(%err, "Client connection failed");
+            }
+        }
+        pipe = next;
+    }
+}
+
+/// Creates another instance of the pipe while the daemon holds one, retrying until it succeeds.
+fn next_pipe(security: &UserOnlySecurity) -> HANDLE {
+    loop {
+        match create_pipe(security, false) {
+            Ok(pipe) => return pipe,
+            Err(err) => {
+                warn!(%err, "Couldn't create the next pipe instance, retrying");
+                thread::sleep(CREATE_RETRY);
+            }
+        }
+    }
+}
+
+fn serve_client(stream: &File, daemon: &mut Daemon) -> Result<()> {
+    let mut writer = stream;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request @ Request::ProfileApply { progress: true, .. }) => {
+                // A client gone while the apply runs shows up when writing the response.
+                daemon.handle_with_progress(&request, &mut |progress| {
+                    let _ = write_line(&mut writer, &ProgressMessage { progress: progress.clone() });
+                })
+            }
+            Ok(request) => daemon.handle(&request),
+            Err(err) => {
+                let message = format!("Invalid request: {err}");
+                Response::failure(DisplayError::from(err).kind(), message)
+            }
+        };
+        write_line(&mut writer, &response)?;
+    }
+    Ok(())
+}
+
+fn write_line(writer: &mut impl Write, message: &impl Serialize) -> Result<()> {
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Sends `request` to a running daemon and waits for its response, see [`DaemonClient`].
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DaemonNotRunning`] if nobody listens on the pipe,
+/// [`DisplayError::ProtocolMismatch`] if the daemon speaks another protocol version, or an
+/// error if the exchange fails.
+pub fn send_request(request: &Request) -> Result<Response> {
+    DaemonClient::connect()?.request(request)
+}
+
+/// Opens the client end of [`PIPE_NAME`], waiting while the daemon is busy with another client.
+pub(crate) fn open_pipe() -> Result<File> {
+    let mut waited = Duration::ZERO;
+    loop {
+        match OpenOptions::new().read(true).write(true).open(PIPE_NAME) {
+            Ok(stream) => return Ok(stream),
+            Err(err) if err.raw_os_error() == Some(i32::try_from(ERROR_PIPE_BUSY.0)?) && waited < BUSY_TIMEOUT => {
+                thread::sleep(BUSY_RETRY);
+                waited += BUSY_RETRY;
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(DisplayError::DaemonNotRunning),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Creates an instance of [`PIPE_NAME`]; the `first` one fails if the name is already taken,
+/// e.g. by another daemon.
+fn create_pipe(security: &UserOnlySecurity, first: bool) -> Result<HANDLE> {
+    let attributes = security.attributes();
+    let first_instance = if first { FILE_FLAG_FIRST_PIPE_INSTANCE } else { FILE_FLAGS_AND_ATTRIBUTES(0) };
+    // SAFETY: the name is a valid string and the attributes outlive the call.
+    let pipe = unsafe {
+        CreateNamedPipeW(
+            &HSTRING::from(PIPE_NAME),
+            PIPE_ACCESS_DUPLEX | first_instance,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            PIPE_BUFFER_SIZE,
+            PIPE_BUFFER_SIZE,
+            0,
+            Some(&raw const attributes),
+        )
+    };
+    if pipe.is_invalid() {
+        return Err(windows::core::Error::from_thread().into());
+    }
+    Ok(pipe)
+}
+
+/// Security descriptor granting access to the current user only.
+struct UserOnlySecurity {
+    descriptor: PSECURITY_DESCRIPTOR,
+}
+
+impl UserOnlySecurity {
+    fn new() -> Result<Self> {
+        let sid = current_user_sid()?;
+        let sddl = HSTRING::from(format!("D:P(A;;GA;;;{sid})"));
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        // SAFETY: valid SDDL string and out-pointer; the descriptor is freed in drop.
+        unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(&sddl, SDDL_REVISION_1, &raw mut descriptor, None)?;
+        }
+        Ok(Self { descriptor })
+    }
+
+    fn attributes(&self) -> SECURITY_ATTRIBUTES {
+        SECURITY_ATTRIBUTES {
+            nLength: u32::try_from(size_of::<SECURITY_ATTRIBUTES>()).unwrap_or(u32::MAX),
+            lpSecurityDescriptor: self.descriptor.0,
+            bInheritHandle: false.into(),
+        }
+    }
+}
+
+impl Drop for UserOnlySecurity {
+    fn drop(&mut self) {
+        // SAFETY: allocated by ConvertStringSecurityDescriptorToSecurityDescriptorW.
+        unsafe {
+            LocalFree(Some(HLOCAL(self.descriptor.0)));
+        }
+    }
+}
+
+/// SID of the user running the process, e.g. `S-1-5-21-...`.
+fn current_user_sid() -> Result<String> {
+    // SAFETY: every out-pointer is valid, the buffer is sized by the first
+    // GetTokenInformation call and the token and string are released before returning.
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &raw mut token)?;
+
+        let mut len = 0u32;
+        // Fails with ERROR_INSUFFICIENT_BUFFER, reporting the size needed.
+        let _ = GetTokenInformation(token, TokenUser, None, 0, &raw mut len);
+        // u64 keeps the buffer aligned for TOKEN_USER.
+        let mut buffer = vec![0u64; (len as usize).div_ceil(size_of::<u64>())];
+        let info = GetTokenInformation(token, TokenUser, Some(buffer.as_mut_ptr().cast()), len, &raw mut len);
+        let _ = CloseHandle(token);
+        info?;
+
+        let user = &*buffer.as_ptr().cast::<TOKEN_USER>();
+        let mut sid = PWSTR::null();
+        ConvertSidToStringSidW(user.User.Sid, &raw mut sid)?;
+        let text = sid.to_string();
+        LocalFree(Some(HLOCAL(sid.0.cast())));
+        Ok(text?)
+    }
+}
@@ -0,0 +1,110 @@
+//! Probing monitors over DDC/CI for [`DisplayCapabilities::ddc_ci`](crate::display::DisplayCapabilities::ddc_ci).
+//!
+//! A request goes over the monitor's I2C bus and takes 100-500 ms when the monitor answers,
+//! and up to a few seconds when it doesn't. Each monitor is therefore probed on a thread of its
+//! own, and all of them together get [`PROBE_TIMEOUT`]: a probe still running then (a dead
+//! bus, say) gives no answer and finishes in the background.
+
+use crate::display::{DisplayInfo, Result};
+use crate::raw;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How long [`DdcProbes::wait`] waits for the probes.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Asks whether the monitor at a desktop point answers, like [`raw::ddc_ci_responds`].
+pub type Probe = fn(i32, i32) -> Result<bool>;
+
+/// Probes running in the background, see [`DdcProbes::start`].
+#[derive(Debug)]
+pub struct DdcProbes {
+    count: usize,
+    receiver: Receiver<(usize, Result<bool>)>,
+    deadline: Instant,
+}
+
+impl DdcProbes {
+    /// Starts probing the monitor of each of `displays`.
+    #[must_use]
+    pub fn start(displays: &[&DisplayInfo]) -> Self {
+        Self::start_with(displays, PROBE_TIMEOUT, raw::ddc_ci_responds)
+    }
+
+    /// Starts `probe` for each of `displays` on a thread of its own, at the display's position.
+    #[must_use]
+    pub fn start_with(displays: &[&DisplayInfo], timeout: Duration, probe: Probe) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        for (index, display) in displays.iter().enumerate() {
+            let (sender, x, y) = (sender.clone(), display.position_x, display.position_y);
+            thread::spawn(move || {
+                // Fails only once `wait` gave up on this probe, which then counts as no answer.
+                let _ = sender.send((index, probe(x, y)));
+            });
+        }
+        Self { count: displays.len(), receiver, deadline: Instant::now() + timeout }
+    }
+
+    /// Whether each monitor answered, in the order they were started in. Waits until the
+    /// timeout after starting at most; probes that failed or are still running give `None`.
+    #[must_use]
+    pub fn wait(self) -> Vec<Option<bool>> {
+        let mut responds = vec![None; self.count];
+        for _ in 0..self.count {
+            match self.receiver.recv_timeout(self.deadline.saturating_duration_since(Instant::now())) {
+                Ok((index, Ok(answer))) => responds[index] = Some(answer),
+                Ok((index, Err(err))) => debug!(index, %err, "DDC/CI probe failed"),
+                Err(_) => {
+                    debug!("DDC/CI probes timed out");
+                    break;
+                }
+            }
+        }
+        responds
+    }
+}
+
+/// DDC/CI answers kept between calls, e.g. by the daemon, by monitor device path. They're
+/// forgotten whenever the displays differ from those of the previous call, since any display
+/// change can mean another monitor, and on [`DdcCache::invalidate`]. A probe that failed or
+/// timed out isn't an answer, so the monitor is probed again next time.
+#[derive(Debug, Default)]
+pub struct DdcCache {
+    displays: Vec<DisplayInfo>,
+    responds: HashMap<String, bool>,
+}
+
+impl DdcCache {
+    /// Forgets every answer; call it after changing the displays.
+    pub fn invalidate(&mut self) {
+        self.displays.clear();
+        self.responds.clear();
+    }
+
+    /// Whether the monitor of each of `displays` answers, probing in parallel those not
+    /// answered for yet; those that still don't answer count as `false`.
+    pub fn probe(&mut self, displays: &[DisplayInfo]) -> Vec<bool> {
+        self.probe_with(displays, PROBE_TIMEOUT, raw::ddc_ci_responds)
+    }
+
+    /// [`probe`](Self::probe) with another timeout and probe.
+    pub fn probe_with(&mut self, displays: &[DisplayInfo], timeout: Duration, probe: Probe) -> Vec<bool> {
+        if self.displays != displays {
+            self.invalidate();
+            self.displays = displays.to_vec();
+        }
+        let unknown: Vec<&DisplayInfo> = displays.iter().filter(|d| !self.responds.contains_key(&d.device_path)).collect();
+        if !unknown.is_empty() {
+            let answers = DdcProbes::start_with(&unknown, timeout, probe).wait();
+            for (display, answer) in unknown.iter().zip(answers) {
+                if let Some(answer) = answer {
+                    self.responds.insert(display.device_path.clone(), answer);
+                }
+            }
+        }
+        displays.iter().map(|d| self.responds.get(&d.device_path).copied().unwrap_or(false)).collect()
+    }
+}
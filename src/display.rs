@@ -1,11 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fmt;
-use std::mem::size_of;
+use std::sync::{mpsc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tracing::{debug, info};
-use windows::Win32::Devices::Display::{DisplayConfigGetDeviceInfo, DisplayConfigSetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig, SetDisplayConfig, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME, DISPLAYCONFIG_DEVICE_INFO_HEADER, DISPLAYCONFIG_DEVICE_INFO_TYPE, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE, DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_TARGET_DEVICE_NAME, DISPLAYCONFIG_TARGET_DEVICE_NAME_FLAGS, DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY, QDC_ONLY_ACTIVE_PATHS, SDC_APPLY, SDC_USE_SUPPLIED_DISPLAY_CONFIG};
+use tracing::{debug, info, warn};
+use crate::apply_log::{self, ApplyLogEntry};
+use crate::explain::{EnumerationTrace, ScalingSource};
+use crate::layout::{self, Rect};
+use crate::metrics::{self, Phase};
+use crate::link::{link_info, LinkInfo};
+use crate::plan::{self, execute_with, plan, Target};
+use crate::raw::{self, query_display_config, set_display_config, ModeInfo, PathInfo};
+use crate::throttle;
+use windows::Win32::Devices::Display::{DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EMBEDDED, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EXTERNAL, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_USB_TUNNEL, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DVI, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HD15, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_VIRTUAL, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_WIRED, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_LVDS, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_MIRACAST, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_UDI_EMBEDDED, DISPLAYCONFIG_RATIONAL, DISPLAYCONFIG_ROTATION, DISPLAYCONFIG_ROTATION_IDENTITY, DISPLAYCONFIG_ROTATION_ROTATE180, DISPLAYCONFIG_ROTATION_ROTATE270, DISPLAYCONFIG_ROTATION_ROTATE90, DISPLAYCONFIG_SCALING, DISPLAYCONFIG_SCALING_ASPECTRATIOCENTEREDMAX, DISPLAYCONFIG_SCALING_CENTERED, DISPLAYCONFIG_SCALING_IDENTITY, DISPLAYCONFIG_SCALING_STRETCHED, DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY, QDC_ALL_PATHS, QDC_ONLY_ACTIVE_PATHS, QDC_VIRTUAL_REFRESH_RATE_AWARE, SET_DISPLAY_CONFIG_FLAGS, SDC_ALLOW_CHANGES, SDC_APPLY, SDC_SAVE_TO_DATABASE, SDC_USE_SUPPLIED_DISPLAY_CONFIG, SDC_VALIDATE, SDC_VIRTUAL_REFRESH_RATE_AWARE};
+use windows::Win32::Foundation::POINTL;
+use windows::Win32::Graphics::Gdi::{DISPLAYCONFIG_COLOR_ENCODING, DISPLAYCONFIG_COLOR_ENCODING_INTENSITY, DISPLAYCONFIG_COLOR_ENCODING_YCBCR420, DISPLAYCONFIG_COLOR_ENCODING_YCBCR422, DISPLAYCONFIG_COLOR_ENCODING_YCBCR444};
 
+/// SDR white levels Windows accepts, in nits.
+pub const SDR_WHITE_LEVEL_RANGE: std::ops::RangeInclusive<u32> = 80..=480;
+
+/// The scaling values `set` can apply, in the order of the steps of the CCD DPI query. Windows
+/// can report others as current, see [`DisplayInfo::has_custom_scaling`].
 pub const DPI_VALUES: [i32; 12] = [100, 125, 150, 175, 200, 225, 250, 300, 350, 400, 450, 500];
 
+/// Directory of the settings and state files, `%APPDATA%\display-tuner`.
+#[cfg(any(feature = "profiles", feature = "watch"))]
+pub(crate) fn app_dir() -> Result<std::path::PathBuf> {
+    let app_data = std::env::var_os("APPDATA").ok_or(DisplayError::NoAppData)?;
+    Ok(std::path::PathBuf::from(app_data).join("display-tuner"))
+}
+
+/// Separators between groups of thousands that [`parse_dimension`] skips: period, comma, space,
+/// no-break space, thin space and narrow no-break space, as locales write and paste them.
+const GROUP_SEPARATORS: [char; 6] = ['.', ',', ' ', '\u{a0}', '\u{2009}', '\u{202f}'];
+
+/// A number from text with a period or a comma as the decimal separator, `59.94` or `59,94`,
+/// whatever the locale. Group separators aren't accepted, as `1.000` could be either.
+#[must_use]
+pub fn parse_decimal(text: &str) -> Option<f64> {
+    text.trim().replace(',', ".").parse().ok()
+}
+
+/// A pixel count from text, with or without thousands separators (`3840`, `3.840`, `3,840`,
+/// `3 840`), see [`GROUP_SEPARATORS`]. Groups after a separator have three digits, so `1.5`
+/// isn't read as 15.
+#[must_use]
+pub fn parse_dimension(text: &str) -> Option<u32> {
+    let mut groups = text.trim().split(GROUP_SEPARATORS);
+    let first = groups.next()?;
+    let mut digits = first.to_string();
+    for group in groups {
+        if !(1..=3).contains(&first.len()) || group.len() != 3 {
+            return None;
+        }
+        digits.push_str(group);
+    }
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// `WIDTHxHEIGHT` from text, each side read by [`parse_dimension`]: `2560x1440`, `3.840x2.160`
+/// or `2560 × 1440`. `None` unless both are positive.
+#[must_use]
+pub fn parse_resolution(text: &str) -> Option<(u32, u32)> {
+    let (width, height) = text.split_once(['x', 'X', '×'])?;
+    Some((parse_dimension(width)?, parse_dimension(height)?)).filter(|&(width, height)| width > 0 && height > 0)
+}
+
+/// Scaling numbers below this are ratios, the others percentages, see [`scaling_percent`].
+pub const SCALING_RATIO_LIMIT: f64 = 10.0;
+
+/// Scaling in whole percent from a number as files and requests give it: a percentage, whole
+/// (`150`) or not (`150.0`), or a ratio (`1.5`). Numbers below [`SCALING_RATIO_LIMIT`] are
+/// ratios, since no display scales to less than 100%; so `2` is 200%, not 2%. Fractions of a
+/// percent are rounded. `None` for zero, negative or non-finite numbers.
+#[must_use]
+pub fn scaling_percent(value: f64) -> Option<i32> {
+    if !value.is_finite() || value <= 0.0 {
+        return None;
+    }
+    let percent = if value < SCALING_RATIO_LIMIT { value * 100.0 } else { value };
+    // Checked against the i32 range just before, so the cast can't truncate.
+    #[allow(clippy::cast_possible_truncation)]
+    (percent.round() <= f64::from(i32::MAX)).then(|| percent.round() as i32)
+}
+
+/// Scaling from text: `150%` and `1.5x` say which they are, a bare number goes by the rule of
+/// [`scaling_percent`]. The decimal separator may be a comma, see [`parse_decimal`].
+#[must_use]
+pub fn parse_scaling(text: &str) -> Option<i32> {
+    let text = text.trim();
+    if let Some(percent) = text.strip_suffix('%') {
+        let percent = parse_decimal(percent)?;
+        return (percent >= 1.0).then_some(percent).and_then(scaling_percent);
+    }
+    if let Some(ratio) = text.strip_suffix(['x', 'X']) {
+        let ratio = parse_decimal(ratio)?;
+        return (ratio < SCALING_RATIO_LIMIT).then_some(ratio).and_then(scaling_percent);
+    }
+    scaling_percent(parse_decimal(text)?)
+}
+
+/// Deserializes a scaling number with [`scaling_percent`], for `#[serde(deserialize_with)]`; it
+/// serializes as whole percent.
+///
+/// # Errors
+///
+/// Returns an error if the value isn't a number or isn't a valid scaling.
+pub fn deserialize_scaling<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<i32, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    scaling_percent(value).ok_or_else(|| serde::de::Error::custom(format!("invalid scaling {value}, expected a percentage like 150 or a ratio like 1.5")))
+}
+
+/// [`deserialize_scaling`] for optional fields, which also need `#[serde(default)]`.
+///
+/// # Errors
+///
+/// Returns an error if the value isn't a number or isn't a valid scaling.
+pub fn deserialize_optional_scaling<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<i32>, D::Error> {
+    deserialize_scaling(deserializer).map(Some)
+}
+
+/// The rate Dynamic Refresh Rate drops to when little is moving on screen. Windows offers DRR
+/// from twice that up.
+pub const DRR_MIN_REFRESH: u32 = 60;
+
+/// Variants come and go with the crate features, so matches need a wildcard arm.
+#[non_exhaustive]
 #[derive(Debug, Error)]
 pub enum DisplayError {
     #[error("Failed to query display config: {0}")]
@@ -13,322 +139,3114 @@ pub enum DisplayError {
 
     #[error("Failed to get monitor friendly name: {0}")]
     GetMonitorFriendlyName(i32),
+    #[error("Failed to get source device name: {0}")]
+    GetSourceDeviceName(i32),
+    #[error("Failed to get adapter name: {0}")]
+    GetAdapterName(i32),
+    #[error("Failed to get the target's base output technology: {0}")]
+    GetTargetBaseType(i32),
+    #[error("No display modes reported for {0}")]
+    NoDisplayModes(String),
     #[error("Failed to get DPI info: {0}")]
     GetDpiInfo(i32),
     #[error("DPI index out of range")]
     DpiIndexOutOfRange,
+    #[error("Display {0} not found")]
+    DisplayNotFound(u32),
+    #[error("No display with source id {id}{hint}")]
+    NoDisplayWithId { id: u32, hint: String },
+    #[error("No display at index {index}, there are {count}")]
+    IndexOutOfRange { index: usize, count: usize },
+    #[error("No display source matches monitor device '{0}'")]
+    UnmatchedMonitorDevice(String),
+    #[error("No window has focus")]
+    NoForegroundWindow,
+    #[error("Failed to get advanced color info: {0}")]
+    GetAdvancedColorInfo(i32),
+    #[error("Failed to get SDR white level: {0}")]
+    GetSdrWhiteLevel(i32),
+    #[error("Failed to get the preferred mode: {0}")]
+    GetPreferredMode(i32),
+    #[error("No EDID stored for monitor '{0}'")]
+    EdidUnavailable(String),
+    #[error("Failed to set SDR white level: {0}")]
+    SetSdrWhiteLevel(i32),
+    #[error("Display {0} doesn't have HDR enabled, SDR content brightness only applies in HDR mode")]
+    AdvancedColorDisabled(u32),
+    #[error("SDR content brightness must be between 80 and 480 nits, got {0}")]
+    SdrWhiteLevelOutOfRange(u32),
+    #[error("Display {source_id} runs at {current}-bit; Windows offers no way to set {requested}-bit on any OS version or driver, use the GPU control panel")]
+    BitDepthNotControllable { source_id: u32, current: u32, requested: u32 },
+    #[error("Dynamic refresh rate on display {0} requires Windows 11 and a supported panel running at 120Hz or more")]
+    DrrUnsupported(u32),
+    #[error("Display {source_id} does not support {width}x{height}")]
+    UnsupportedMode { source_id: u32, width: u32, height: u32 },
+    #[error("Display {source_id} has no {refresh} refresh rate at {width}x{height}")]
+    UnsupportedRefreshRate { source_id: u32, width: u32, height: u32, refresh: RefreshRate },
+    #[error("Display {source_id} has no mode {mode} (see `list --modes`); nearest: {nearest}")]
+    NoSuchMode { source_id: u32, mode: ModeSpec, nearest: String },
+    #[error("Display {source_id} has no mode within {RES_SCALE_TOLERANCE}% of {width}x{height}; nearest: {nearest}")]
+    NoCloseMode { source_id: u32, width: u32, height: u32, nearest: DisplayMode },
+    #[error("Display {source_id} does not support {scaling}% scaling")]
+    UnsupportedScaling { source_id: u32, scaling: i32 },
+    #[error("Display {source_id} can't have an effective {width}x{height} workspace; achievable: {achievable}")]
+    UnreachableEffectiveSize { source_id: u32, width: u32, height: u32, achievable: String },
+    #[error("Running in a Remote Desktop session: resolutions come from the RDP client and display changes usually fail; pass --force-remote to try anyway")]
+    RemoteSession,
+    #[error("Display configuration must run in the interactive user session, but this process runs in session {session_id} on window station '{window_station}' (e.g. as a service or SYSTEM); launch it in the user's session, or pass --session-check off to try anyway")]
+    NonInteractiveSession { session_id: u32, window_station: String },
+    /// The query succeeded but found no display to use, e.g. on a headless machine or a VM
+    /// with a virtual GPU; failed queries have their own variants.
+    #[error("No active displays found: no display is attached or awake, or the process has no access to the interactive desktop")]
+    NoDisplays,
+    #[error("ChangeDisplaySettingsEx failed: {0}")]
+    ChangeDisplaySettings(i32),
+    #[error("Display {0} changed since it was enumerated (monitor swapped or resolution changed elsewhere); refresh and try again")]
+    Stale(u32),
+    #[error("Display {0}: display configuration changed concurrently (another program reconfigured the displays), retry")]
+    ConcurrentChange(u32),
+    #[error("Display {0}: display target currently unavailable (asleep or disconnected)")]
+    TargetUnavailable(u32),
+    #[error("Display {0}: custom scaling override active; clear it first or use --clear-custom-scaling")]
+    CustomScalingActive(u32),
+    #[error("Display {0} would overlap display {1}")]
+    Overlap(u32, u32),
+    #[error("The layout would span {width}x{height} pixels, more than the 32767x32767 Windows allows")]
+    DesktopTooLarge { width: u32, height: u32 },
+    #[error("The layout exceeds Windows virtual desktop limits by {0} pixels (coordinates must stay within -32768..32767)")]
+    DesktopOutOfRange(u64),
+    #[error("apply timed out (the call may still complete in the background)")]
+    ApplyTimedOut,
+    #[error("Cancelled, the displays changed so far were put back")]
+    Cancelled,
+    #[error("Display {0} didn't settle in time (still switching modes, asleep, or the work area wasn't updated)")]
+    NotSettled(u32),
+    #[error("Display {source_id}: {error}; pass --force to apply it anyway")]
+    Invalid { source_id: u32, error: ValidationError },
 
-    #[error("Failed to set display configuration: {0}")]
+    #[error("Failed to set display configuration: {reason} (error {code})", reason = set_config_reason(*.0), code = .0)]
     SetDisplayConfig(i32),
+    #[error("The OS would reject this configuration: {reason} (error {code})", reason = set_config_reason(*.0), code = .0)]
+    ConfigRejected(i32),
     #[error("Failed to set DPI scaling: {0}")]
     SetDpiScaling(i32),
     #[error("Integer conversion error: {0}")]
     IntConversionError(#[from] std::num::TryFromIntError),
+
+    #[error("Profile '{0}' not found")]
+    ProfileNotFound(String),
+    #[error("Invalid profile name '{0}': it can't be empty or contain a path")]
+    InvalidProfileName(String),
+    #[error("APPDATA is not set, can't locate the settings directory")]
+    NoAppData,
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[cfg(any(feature = "profiles", feature = "watch"))]
+    #[error("Invalid rules file: {0}")]
+    Rules(#[from] toml::de::Error),
+    #[cfg(feature = "ffi")]
+    #[error("Invalid argument: {0}")]
+    FfiArgument(&'static str),
+    #[error("Invalid config file, line {line} ('{key}'): {message}")]
+    InvalidConfig { line: usize, key: String, message: String },
+    #[error("Rule '{rule}' refers to unknown display '{reference}'")]
+    UnknownReference { rule: String, reference: String },
+    #[error("Invalid hotkey '{0}'")]
+    InvalidHotkey(String),
+    #[error("Invalid time '{0}', expected HH:MM")]
+    InvalidTime(String),
+    #[error("Invalid scaling expectation '{0}', expected 'recommended' or a percentage")]
+    InvalidExpectation(String),
+    #[error("Invalid --display clause '{clause}': {reason}")]
+    InvalidClause { clause: String, reason: String },
+    #[error("{0} --display clause(s) can't be applied, nothing was changed")]
+    BatchRejected(usize),
+    #[error("Hotkey {0} can't be registered, another program probably uses it")]
+    HotkeyUnavailable(String),
+    #[error("Unknown display group '{0}', groups are defined in %APPDATA%\\display-tuner\\groups.toml")]
+    UnknownGroup(String),
+    #[error("Unknown display alias '{alias}' (defined: {defined}), aliases are defined in %APPDATA%\\display-tuner\\groups.toml")]
+    UnknownAlias { alias: String, defined: String },
+    #[error("The display aliased '{0}' isn't connected")]
+    AliasNotConnected(String),
+    #[error("No change to revert")]
+    NothingToRevert,
+    #[error("The display-tuner daemon isn't running")]
+    DaemonNotRunning,
+    #[error("No connected display has handle {0}, list the displays through the daemon for their handles")]
+    UnknownHandle(u32),
+    #[error("The daemon speaks protocol version {daemon} but this client speaks {client} (0 is a daemon from before versioning); restart the daemon from the same display-tuner build")]
+    ProtocolMismatch { client: u32, daemon: u32 },
+    #[error("Daemon: {0}")]
+    Daemon(String),
+    #[error("Invalid text: {0}")]
+    Utf16(#[from] std::string::FromUtf16Error),
+    #[error("Display {0} isn't known to Windows color management")]
+    NoColorDevice(u32),
+    #[error("Color management is unavailable for display {source_id}: {err}")]
+    ColorManagement { source_id: u32, err: windows::core::Error },
+    #[error("State file version {0} isn't supported by this build")]
+    StateVersion(u32),
+    #[error("The display configuration holds a mode of unknown type {0}")]
+    UnknownModeType(i32),
+    #[error("The attached monitors differ from the saved state (missing: {missing}; not in it: {unexpected}); pass --partial to restore the monitors present")]
+    StateMismatch { missing: String, unexpected: String },
+    #[error("Windows API error: {0}")]
+    Windows(#[from] windows::core::Error),
 }
 
 pub type Result<T> = std::result::Result<T, DisplayError>;
 
-#[derive(Debug, Clone)]
-pub struct DisplayInfo {
-    pub friendly_name: String,
-    pub source_id: u32,
-    pub width: u32,
-    pub height: u32,
-    pub scaling_current: i32,
-    pub scaling_recommended: i32,
-}
+impl DisplayError {
+    /// The Win32 error code or `HRESULT` reported by Windows, if the error carries one.
+    #[must_use]
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Self::GetMonitorFriendlyName(code)
+            | Self::GetSourceDeviceName(code)
+            | Self::GetAdapterName(code)
+            | Self::GetTargetBaseType(code)
+            | Self::GetDpiInfo(code)
+            | Self::GetAdvancedColorInfo(code)
+            | Self::GetSdrWhiteLevel(code)
+            | Self::GetPreferredMode(code)
+            | Self::SetSdrWhiteLevel(code)
+            | Self::ChangeDisplaySettings(code)
+            | Self::SetDisplayConfig(code)
+            | Self::ConfigRejected(code)
+            | Self::SetDpiScaling(code) => Some(*code),
+            Self::QueryDisplayConfig(code) => i32::try_from(*code).ok(),
+            Self::Windows(err) | Self::ColorManagement { err, .. } => Some(err.code().0),
+            Self::Io(err) => err.raw_os_error(),
+            _ => None,
+        }
+    }
 
-impl fmt::Display for DisplayInfo {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Example: [id:12345] My Monitor — 2560x1440 @ 125% (rec 150%)
-        write!(
-            f,
-            "[id:{}] {} — {}x{} @ {}%",
-            self.source_id, self.friendly_name, self.width, self.height, self.scaling_current
-        )?;
-        if self.scaling_recommended != self.scaling_current {
-            write!(f, " (rec {}%)", self.scaling_recommended)?;
+    /// Stable machine-readable name of the error, e.g. `display_not_found`: the `code` of its
+    /// JSON form (see [`ErrorReport`](crate::report::ErrorReport)). Unlike the message, it
+    /// doesn't change once released.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::QueryDisplayConfig(..) => "query_display_config",
+            Self::GetMonitorFriendlyName(..) => "get_monitor_friendly_name",
+            Self::GetSourceDeviceName(..) => "get_source_device_name",
+            Self::GetAdapterName(..) => "get_adapter_name",
+            Self::GetTargetBaseType(..) => "get_target_base_type",
+            Self::NoDisplayModes(..) => "no_display_modes",
+            Self::GetDpiInfo(..) => "get_dpi_info",
+            Self::DpiIndexOutOfRange => "dpi_index_out_of_range",
+            Self::DisplayNotFound(..) => "display_not_found",
+            Self::NoDisplayWithId { .. } => "no_display_with_id",
+            Self::IndexOutOfRange { .. } => "index_out_of_range",
+            Self::UnmatchedMonitorDevice(..) => "unmatched_monitor_device",
+            Self::NoForegroundWindow => "no_foreground_window",
+            Self::GetAdvancedColorInfo(..) => "get_advanced_color_info",
+            Self::GetSdrWhiteLevel(..) => "get_sdr_white_level",
+            Self::GetPreferredMode(..) => "get_preferred_mode",
+            Self::EdidUnavailable(..) => "edid_unavailable",
+            Self::SetSdrWhiteLevel(..) => "set_sdr_white_level",
+            Self::AdvancedColorDisabled(..) => "advanced_color_disabled",
+            Self::SdrWhiteLevelOutOfRange(..) => "sdr_white_level_out_of_range",
+            Self::BitDepthNotControllable { .. } => "bit_depth_not_controllable",
+            Self::DrrUnsupported(..) => "drr_unsupported",
+            Self::UnsupportedMode { .. } => "unsupported_mode",
+            Self::UnsupportedRefreshRate { .. } => "unsupported_refresh_rate",
+            Self::NoSuchMode { .. } => "no_such_mode",
+            Self::NoCloseMode { .. } => "no_close_mode",
+            Self::UnsupportedScaling { .. } => "unsupported_scaling",
+            Self::UnreachableEffectiveSize { .. } => "unreachable_effective_size",
+            Self::RemoteSession => "remote_session",
+            Self::NonInteractiveSession { .. } => "non_interactive_session",
+            Self::NoDisplays => "no_displays",
+            Self::ChangeDisplaySettings(..) => "change_display_settings",
+            Self::Stale(..) => "stale",
+            Self::ConcurrentChange(..) => "concurrent_change",
+            Self::TargetUnavailable(..) => "target_unavailable",
+            Self::CustomScalingActive(..) => "custom_scaling_active",
+            Self::Overlap(..) => "overlap",
+            Self::DesktopTooLarge { .. } => "desktop_too_large",
+            Self::DesktopOutOfRange(..) => "desktop_out_of_range",
+            Self::ApplyTimedOut => "apply_timed_out",
+            Self::Cancelled => "cancelled",
+            Self::NotSettled(..) => "not_settled",
+            Self::Invalid { .. } => "invalid",
+            Self::SetDisplayConfig(..) => "set_display_config",
+            Self::ConfigRejected(..) => "config_rejected",
+            Self::SetDpiScaling(..) => "set_dpi_scaling",
+            Self::IntConversionError(..) => "int_conversion_error",
+            Self::ProfileNotFound(..) => "profile_not_found",
+            Self::InvalidProfileName(..) => "invalid_profile_name",
+            Self::NoAppData => "no_app_data",
+            Self::Io(..) => "io",
+            Self::Json(..) => "json",
+            #[cfg(any(feature = "profiles", feature = "watch"))]
+            Self::Rules(..) => "rules",
+            #[cfg(feature = "ffi")]
+            Self::FfiArgument(..) => "ffi_argument",
+            Self::InvalidConfig { .. } => "invalid_config",
+            Self::UnknownReference { .. } => "unknown_reference",
+            Self::InvalidHotkey(..) => "invalid_hotkey",
+            Self::InvalidTime(..) => "invalid_time",
+            Self::InvalidExpectation(..) => "invalid_expectation",
+            Self::InvalidClause { .. } => "invalid_clause",
+            Self::BatchRejected(..) => "batch_rejected",
+            Self::HotkeyUnavailable(..) => "hotkey_unavailable",
+            Self::UnknownGroup(..) => "unknown_group",
+            Self::UnknownAlias { .. } => "unknown_alias",
+            Self::AliasNotConnected(..) => "alias_not_connected",
+            Self::NothingToRevert => "nothing_to_revert",
+            Self::DaemonNotRunning => "daemon_not_running",
+            Self::UnknownHandle(..) => "unknown_handle",
+            Self::ProtocolMismatch { .. } => "protocol_mismatch",
+            Self::Daemon(..) => "daemon",
+            Self::Utf16(..) => "utf16",
+            Self::NoColorDevice(..) => "no_color_device",
+            Self::ColorManagement { .. } => "color_management",
+            Self::StateVersion(..) => "state_version",
+            Self::UnknownModeType(..) => "unknown_mode_type",
+            Self::StateMismatch { .. } => "state_mismatch",
+            Self::Windows(..) => "windows",
         }
-        Ok(())
+    }
+
+    /// What the error is about as named values, e.g. `source_id` and `scaling` for
+    /// [`UnsupportedScaling`](Self::UnsupportedScaling): the `details` of its JSON form. Empty
+    /// for errors that only carry a message or an OS error code.
+    #[must_use]
+    pub fn details(&self) -> serde_json::Map<String, serde_json::Value> {
+        use serde_json::json;
+        let details = match self {
+            Self::DisplayNotFound(source_id)
+            | Self::AdvancedColorDisabled(source_id)
+            | Self::DrrUnsupported(source_id)
+            | Self::Stale(source_id)
+            | Self::ConcurrentChange(source_id)
+            | Self::TargetUnavailable(source_id)
+            | Self::CustomScalingActive(source_id)
+            | Self::NotSettled(source_id)
+            | Self::NoColorDevice(source_id)
+            | Self::ColorManagement { source_id, .. } => json!({ "source_id": source_id }),
+            Self::NoDisplayWithId { id, .. } => json!({ "source_id": id }),
+            Self::IndexOutOfRange { index, count } => json!({ "index": index, "count": count }),
+            Self::UnmatchedMonitorDevice(device) => json!({ "device": device }),
+            Self::NoDisplayModes(device) | Self::EdidUnavailable(device) => json!({ "device_path": device }),
+            Self::SdrWhiteLevelOutOfRange(nits) => json!({ "nits": nits }),
+            Self::BitDepthNotControllable { source_id, current, requested } => {
+                json!({ "source_id": source_id, "current": current, "requested": requested })
+            }
+            Self::UnsupportedMode { source_id, width, height } => json!({ "source_id": source_id, "width": width, "height": height }),
+            Self::UnsupportedRefreshRate { source_id, width, height, refresh } => {
+                json!({ "source_id": source_id, "width": width, "height": height, "refresh": refresh.to_string() })
+            }
+            Self::NoSuchMode { source_id, mode, nearest } => json!({ "source_id": source_id, "mode": mode.to_string(), "nearest": nearest }),
+            Self::NoCloseMode { source_id, width, height, nearest } => {
+                json!({ "source_id": source_id, "width": width, "height": height, "nearest": nearest.to_string() })
+            }
+            Self::UnsupportedScaling { source_id, scaling } => json!({ "source_id": source_id, "scaling": scaling }),
+            Self::UnreachableEffectiveSize { source_id, width, height, achievable } => {
+                json!({ "source_id": source_id, "width": width, "height": height, "achievable": achievable })
+            }
+            Self::NonInteractiveSession { session_id, window_station } => json!({ "session_id": session_id, "window_station": window_station }),
+            Self::Overlap(source_id, other) => json!({ "source_id": source_id, "other_source_id": other }),
+            Self::DesktopTooLarge { width, height } => json!({ "width": width, "height": height }),
+            Self::DesktopOutOfRange(excess) => json!({ "excess": excess }),
+            Self::Invalid { source_id, error } => json!({ "source_id": source_id, "reason": error.to_string() }),
+            Self::ProfileNotFound(profile) | Self::InvalidProfileName(profile) => json!({ "profile": profile }),
+            Self::InvalidConfig { line, key, .. } => json!({ "line": line, "key": key }),
+            Self::UnknownReference { rule, reference } => json!({ "rule": rule, "reference": reference }),
+            Self::InvalidHotkey(hotkey) | Self::HotkeyUnavailable(hotkey) => json!({ "hotkey": hotkey }),
+            Self::InvalidTime(time) => json!({ "time": time }),
+            Self::InvalidExpectation(expectation) => json!({ "expectation": expectation }),
+            Self::InvalidClause { clause, reason } => json!({ "clause": clause, "reason": reason }),
+            Self::BatchRejected(rejected) => json!({ "rejected": rejected }),
+            Self::UnknownGroup(group) => json!({ "group": group }),
+            Self::UnknownAlias { alias, .. } | Self::AliasNotConnected(alias) => json!({ "alias": alias }),
+            Self::UnknownHandle(handle) => json!({ "handle": handle }),
+            Self::ProtocolMismatch { client, daemon } => json!({ "client": client, "daemon": daemon }),
+            Self::StateVersion(version) => json!({ "version": version }),
+            Self::UnknownModeType(info_type) => json!({ "info_type": info_type }),
+            Self::StateMismatch { missing, unexpected } => json!({ "missing": missing, "unexpected": unexpected }),
+            _ => return serde_json::Map::new(),
+        };
+        let serde_json::Value::Object(details) = details else {
+            unreachable!("every arm builds an object");
+        };
+        details
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct DisplayConfig {
-    pub width: u32,
-    pub height: u32,
-    pub scaling: i32,
+/// Explains a `SetDisplayConfig` error code.
+fn set_config_reason(code: i32) -> &'static str {
+    match code {
+        // ERROR_ACCESS_DENIED
+        5 => "access denied, the session isn't attached to the console",
+        // ERROR_GEN_FAILURE
+        31 => "the display driver failed to apply it",
+        // ERROR_NOT_SUPPORTED
+        50 => "the display driver doesn't support configuring displays (not a WDDM driver)",
+        // ERROR_INVALID_PARAMETER
+        87 => "the display or adapter doesn't support this mode or layout",
+        // ERROR_BAD_CONFIGURATION
+        1610 => "no working combination of source and target modes was found",
+        _ => "unknown error",
+    }
 }
 
-#[repr(C)]
-struct DpiScaleGet {
-    header: DISPLAYCONFIG_DEVICE_INFO_HEADER,
-    min_scale_rel: i32,
-    cur_scale_rel: i32,
-    max_scale_rel: i32,
+/// Whether a `SetDisplayConfig` error code is worth retrying: drivers report these for a
+/// moment after resume from sleep or a GPU reset.
+fn is_transient_error(code: i32) -> bool {
+    // ERROR_GEN_FAILURE, ERROR_BUSY
+    matches!(code, 31 | 170)
 }
 
-#[repr(C)]
-struct DpiScaleSet {
-    header: DISPLAYCONFIG_DEVICE_INFO_HEADER,
-    scale_rel: i32,
+/// Whether a `SetDisplayConfig` error code means the requested mode itself was refused.
+pub(crate) fn is_mode_error(code: i32) -> bool {
+    matches!(code, 87 | 1610)
 }
 
-pub fn enumerate_displays() -> Result<Vec<DisplayInfo>> {
-    let mut displays = Vec::new();
+/// Physical connection type of a display, as reported by the target's output technology.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Connector {
+    Vga,
+    Dvi,
+    Hdmi,
+    #[serde(rename = "dp")]
+    DisplayPort,
+    /// Built-in panel (LVDS, embedded DisplayPort/UDI or internal).
+    Internal,
+    Miracast,
+    /// Indirect display driver (USB docks, virtual displays).
+    Indirect,
+    #[default]
+    Other,
+}
+
+impl From<DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY> for Connector {
+    fn from(technology: DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY) -> Self {
+        match technology {
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HD15 => Self::Vga,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DVI => Self::Dvi,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI => Self::Hdmi,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EXTERNAL
+            | DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_USB_TUNNEL => Self::DisplayPort,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_LVDS
+            | DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EMBEDDED
+            | DISPLAYCONFIG_OUTPUT_TECHNOLOGY_UDI_EMBEDDED
+            | DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL => Self::Internal,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_MIRACAST => Self::Miracast,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_WIRED
+            | DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_VIRTUAL => Self::Indirect,
+            _ => Self::Other,
+        }
+    }
+}
 
-    let (paths, modes) = get_display_config()?;
+impl Connector {
+    /// Whether this is a built-in panel. All-in-one desktops report their screen as one too.
+    #[must_use]
+    pub fn is_internal(self) -> bool {
+        self == Self::Internal
+    }
+}
 
-    for path in &paths {
-        debug!("Processing path...");
+impl fmt::Display for Connector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Vga => "vga",
+            Self::Dvi => "dvi",
+            Self::Hdmi => "hdmi",
+            Self::DisplayPort => "dp",
+            Self::Internal => "internal",
+            Self::Miracast => "miracast",
+            Self::Indirect => "indirect",
+            Self::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
 
-        let source_mode_idx;
-        unsafe {
-            source_mode_idx = path.sourceInfo.Anonymous.modeInfoIdx as usize;
-        }
+/// Pixel encoding on the link to the display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorEncoding {
+    #[default]
+    Rgb,
+    YCbCr444,
+    YCbCr422,
+    YCbCr420,
+    Intensity,
+}
 
-        if source_mode_idx == 0xFFFF_FFFF || source_mode_idx >= modes.len() {
-            debug!("Skipping invalid mode index: {}", source_mode_idx);
-            continue;
+impl From<DISPLAYCONFIG_COLOR_ENCODING> for ColorEncoding {
+    fn from(encoding: DISPLAYCONFIG_COLOR_ENCODING) -> Self {
+        match encoding {
+            DISPLAYCONFIG_COLOR_ENCODING_YCBCR444 => Self::YCbCr444,
+            DISPLAYCONFIG_COLOR_ENCODING_YCBCR422 => Self::YCbCr422,
+            DISPLAYCONFIG_COLOR_ENCODING_YCBCR420 => Self::YCbCr420,
+            DISPLAYCONFIG_COLOR_ENCODING_INTENSITY => Self::Intensity,
+            _ => Self::Rgb,
         }
+    }
+}
 
-        let mode = &modes[source_mode_idx];
-        if mode.infoType != DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE {
-            debug!("Skipping non-source mode");
-            continue;
+impl fmt::Display for ColorEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Rgb => "RGB",
+            Self::YCbCr444 => "YCbCr 4:4:4",
+            Self::YCbCr422 => "YCbCr 4:2:2",
+            Self::YCbCr420 => "YCbCr 4:2:0",
+            Self::Intensity => "intensity",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Orientation of a display, clockwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    #[default]
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl From<DISPLAYCONFIG_ROTATION> for Rotation {
+    fn from(rotation: DISPLAYCONFIG_ROTATION) -> Self {
+        match rotation {
+            DISPLAYCONFIG_ROTATION_ROTATE90 => Self::Rotate90,
+            DISPLAYCONFIG_ROTATION_ROTATE180 => Self::Rotate180,
+            DISPLAYCONFIG_ROTATION_ROTATE270 => Self::Rotate270,
+            _ => Self::Identity,
         }
+    }
+}
 
-        let (width, height);
-        unsafe {
-            width = mode.Anonymous.sourceMode.width;
-            height = mode.Anonymous.sourceMode.height;
+impl From<Rotation> for DISPLAYCONFIG_ROTATION {
+    fn from(rotation: Rotation) -> Self {
+        match rotation {
+            Rotation::Identity => DISPLAYCONFIG_ROTATION_IDENTITY,
+            Rotation::Rotate90 => DISPLAYCONFIG_ROTATION_ROTATE90,
+            Rotation::Rotate180 => DISPLAYCONFIG_ROTATION_ROTATE180,
+            Rotation::Rotate270 => DISPLAYCONFIG_ROTATION_ROTATE270,
         }
+    }
+}
 
-        let friendly_name = get_display_name_from_path(path)?;
-        let scaling = get_display_scaling_from_path(path)?;
+impl Rotation {
+    /// Turned a quarter, so the desktop is taller than wide on a landscape monitor.
+    #[must_use]
+    pub fn is_portrait(self) -> bool {
+        matches!(self, Self::Rotate90 | Self::Rotate270)
+    }
 
-        let disp = DisplayInfo {
-            friendly_name,
-            source_id: path.sourceInfo.id,
-            width,
-            height,
-            scaling_current: scaling.0,
-            scaling_recommended: scaling.1,
+    /// Converts a size between the monitor's own orientation and the desktop's, swapping
+    /// width and height for portrait. It's its own inverse.
+    #[must_use]
+    pub fn orient(self, (width, height): (u32, u32)) -> (u32, u32) {
+        if self.is_portrait() { (height, width) } else { (width, height) }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let degrees = match self {
+            Self::Identity => 0,
+            Self::Rotate90 => 90,
+            Self::Rotate180 => 180,
+            Self::Rotate270 => 270,
         };
-        info!("{disp}");
-        displays.push(disp);
+        write!(f, "{degrees}°")
     }
+}
 
-    Ok(displays)
+/// How the desktop image is fitted to a monitor running a different resolution than the
+/// image, e.g. a 1920x1080 desktop on a 2560x1440 panel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleMode {
+    /// No scaling, or whatever the driver prefers.
+    #[default]
+    Identity,
+    Centered,
+    Stretched,
+    /// Stretched as far as the aspect ratio allows, with black bars.
+    AspectRatio,
 }
 
-pub fn apply_display_config(
-    display: &DisplayInfo,
-    config: &DisplayConfig,
-) -> Result<()> {
-    let resolution_changed = display.width != config.width || display.height != config.height;
-    let scaling_changed = display.scaling_current != config.scaling;
+impl From<DISPLAYCONFIG_SCALING> for ScaleMode {
+    fn from(scaling: DISPLAYCONFIG_SCALING) -> Self {
+        match scaling {
+            DISPLAYCONFIG_SCALING_CENTERED => Self::Centered,
+            DISPLAYCONFIG_SCALING_STRETCHED => Self::Stretched,
+            DISPLAYCONFIG_SCALING_ASPECTRATIOCENTEREDMAX => Self::AspectRatio,
+            _ => Self::Identity,
+        }
+    }
+}
 
-    if !resolution_changed && !scaling_changed {
-        debug!("Display configuration already matches target, skipping");
-        return Ok(());
+impl From<ScaleMode> for DISPLAYCONFIG_SCALING {
+    fn from(mode: ScaleMode) -> Self {
+        match mode {
+            ScaleMode::Identity => DISPLAYCONFIG_SCALING_IDENTITY,
+            ScaleMode::Centered => DISPLAYCONFIG_SCALING_CENTERED,
+            ScaleMode::Stretched => DISPLAYCONFIG_SCALING_STRETCHED,
+            ScaleMode::AspectRatio => DISPLAYCONFIG_SCALING_ASPECTRATIOCENTEREDMAX,
+        }
     }
+}
 
-    if resolution_changed {
-        apply_display_resolution(display, config)?;
+impl fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Identity => "identity",
+            Self::Centered => "centered",
+            Self::Stretched => "stretched",
+            Self::AspectRatio => "aspect ratio",
+        })
     }
+}
+
+/// The LUID of a graphics adapter. Source ids are numbered per adapter, so on systems with
+/// several GPUs only the pair identifies a source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AdapterId {
+    pub low: u32,
+    pub high: i32,
+}
 
-    if scaling_changed {
-        apply_display_scaling(display, config)?;
+impl AdapterId {
+    /// Zero, as in displays saved before adapters were recorded; matches any adapter.
+    #[must_use]
+    pub fn is_unknown(self) -> bool {
+        self == Self::default()
     }
+}
 
-    Ok(())
+// Independent facts reported by Windows, not a state machine.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayInfo {
+    pub friendly_name: String,
+    pub source_id: u32,
+    /// The adapter the source belongs to, see [`AdapterId`].
+    #[serde(default)]
+    pub adapter_id: AdapterId,
+    /// Device path of that adapter, the same for every display it drives: the graphics chip of
+    /// a USB dock tells apart displays plugged into the dock from those plugged into the
+    /// machine. Empty if Windows didn't report it.
+    #[serde(default)]
+    pub adapter_name: String,
+    pub width: u32,
+    pub height: u32,
+    pub scaling_current: i32,
+    pub scaling_recommended: i32,
+    /// Highest scaling Windows allows for this display.
+    pub scaling_max: i32,
+    /// Scaling as stored for the CCD API and as the shell applies it (`GetDpiForMonitor`),
+    /// `None` if that query failed. `scaling_current` follows the shell when they differ, see
+    /// [`scaling_mismatch`](Self::scaling_mismatch).
+    #[serde(default)]
+    pub scaling_ccd: Option<i32>,
+    #[serde(default)]
+    pub scaling_shell: Option<i32>,
+    /// Refresh rate in Hz, rounded down like the driver's mode list; 0 if unknown.
+    #[serde(default)]
+    pub refresh_rate: u32,
+    /// Windows 11 can switch the display between [`DRR_MIN_REFRESH`] and `refresh_rate` by
+    /// itself (Dynamic Refresh Rate), and whether it does, see
+    /// [`dynamic_refresh_range`](Self::dynamic_refresh_range).
+    #[serde(default)]
+    pub drr_supported: bool,
+    #[serde(default)]
+    pub drr_enabled: bool,
+    /// The monitor's preferred mode in its own orientation, before rotation; 0 if it reports
+    /// none. See [`native_mode`].
+    #[serde(default)]
+    pub native_width: u32,
+    #[serde(default)]
+    pub native_height: u32,
+    #[serde(default)]
+    pub native_refresh: u32,
+    /// A system-wide custom scaling (the legacy `LogPixels` setting) overrides per-monitor
+    /// scaling; `scaling_current` is then the effective value, which may not be in
+    /// [`DPI_VALUES`].
+    pub custom_scaling: bool,
+    pub connector: Connector,
+    /// Built-in panel of a laptop or all-in-one, see [`Connector::is_internal`].
+    #[serde(default)]
+    pub internal: bool,
+    pub active: bool,
+    /// The monitor can be driven right now; it isn't while asleep or switching inputs.
+    pub target_available: bool,
+    /// Raw `DISPLAYCONFIG_TARGET_*` status flags of the target.
+    pub target_status: u32,
+    /// Top-left corner of the display on the virtual desktop.
+    pub position_x: i32,
+    pub position_y: i32,
+    /// The primary display sits at the desktop origin.
+    pub primary: bool,
+    /// The part of the display not covered by the taskbar and docked toolbars, where windows
+    /// are placed; `None` if the window manager couldn't be asked.
+    #[serde(default)]
+    pub work_area: Option<Rect>,
+    pub rotation: Rotation,
+    #[serde(default)]
+    pub scale_mode: ScaleMode,
+    /// GDI device name of the source, e.g. `\\.\DISPLAY1`.
+    pub gdi_device_name: String,
+    /// Device interface path of the monitor, stable for a given monitor on a given port.
+    pub device_path: String,
+    pub edid_manufacturer_id: u16,
+    pub edid_product_id: u16,
+    /// Bits per color channel on the link, 0 if Windows doesn't report it.
+    pub bits_per_color: u32,
+    pub color_encoding: ColorEncoding,
+    /// Advanced color (HDR) is turned on.
+    pub hdr_enabled: bool,
+    /// SDR content brightness in nits, only reported with HDR enabled.
+    pub sdr_white_level: Option<u32>,
+    /// From the EDID, only filled in by [`load_edid`](crate::edid::load_edid).
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub model_name: Option<String>,
+    #[serde(default)]
+    pub manufacture_week: Option<u8>,
+    #[serde(default)]
+    pub manufacture_year: Option<u16>,
+    /// How the values above were obtained, only filled in by
+    /// [`load_trace`](crate::explain::load_trace).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<EnumerationTrace>,
 }
 
-fn get_display_config() -> Result<(Vec<DISPLAYCONFIG_PATH_INFO>, Vec<DISPLAYCONFIG_MODE_INFO>)>
-{
-    unsafe {
-        let mut path_count = 0u32;
-        let mut mode_count = 0u32;
+impl DisplayInfo {
+    /// Three-letter plug and play vendor code from the EDID, e.g. `DEL` or `GSM`.
+    #[must_use]
+    pub fn edid_vendor(&self) -> String {
+        // The id is stored big-endian in the EDID: three 5-bit letters, 'A' = 1.
+        let id = self.edid_manufacturer_id.swap_bytes();
+        [10, 5, 0]
+            .iter()
+            .map(|shift| {
+                let letter = (id >> shift) & 0x1f;
+                if (1..=26).contains(&letter) {
+                    char::from(b'A' + u8::try_from(letter - 1).unwrap_or(0))
+                } else {
+                    '?'
+                }
+            })
+            .collect()
+    }
 
-        let result = GetDisplayConfigBufferSizes(
-            QDC_ONLY_ACTIVE_PATHS,
-            &raw mut path_count,
-            &raw mut mode_count,
-        );
-        if result.0 != 0 {
-            return Err(DisplayError::QueryDisplayConfig(result.0));
-        }
+    /// Width of the desktop apps see at the current scaling, see [`effective_size`].
+    #[must_use]
+    pub fn effective_width(&self) -> u32 {
+        effective_size(self.width, self.height, self.scaling_current).0
+    }
 
-        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
-        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+    /// Height of the desktop apps see at the current scaling, see [`effective_size`].
+    #[must_use]
+    pub fn effective_height(&self) -> u32 {
+        effective_size(self.width, self.height, self.scaling_current).1
+    }
 
-        let result = QueryDisplayConfig(
-            QDC_ONLY_ACTIVE_PATHS,
-            &raw mut path_count,
-            paths.as_mut_ptr(),
-            &raw mut mode_count,
-            modes.as_mut_ptr(),
-            None,
-        );
-        if result.0 != 0 {
-            return Err(DisplayError::QueryDisplayConfig(result.0));
+    /// The current resolution in the monitor's own orientation, before rotation.
+    #[must_use]
+    pub fn unrotated_size(&self) -> (u32, u32) {
+        self.rotation.orient((self.width, self.height))
+    }
+
+    /// The preferred mode the monitor reports, `None` if it reports none.
+    #[must_use]
+    pub fn reported_native_mode(&self) -> Option<DisplayMode> {
+        (self.native_width > 0 && self.native_height > 0).then_some(DisplayMode {
+            width: self.native_width,
+            height: self.native_height,
+            refresh: self.native_refresh,
+        })
+    }
+
+    /// The range Dynamic Refresh Rate moves the refresh rate in, `None` while it's off. A
+    /// measured refresh rate anywhere in it is expected.
+    #[must_use]
+    pub fn dynamic_refresh_range(&self) -> Option<(u32, u32)> {
+        (self.drr_enabled && self.refresh_rate > DRR_MIN_REFRESH).then_some((DRR_MIN_REFRESH, self.refresh_rate))
+    }
+
+    /// Whether the display runs at another resolution or refresh rate than the native mode it
+    /// reports. `false` if it reports none or a refresh rate is unknown and the sizes match.
+    #[must_use]
+    pub fn differs_from_native(&self) -> bool {
+        self.reported_native_mode().is_some_and(|native| {
+            (native.width, native.height) != self.unrotated_size()
+                || (native.refresh > 0 && self.refresh_rate > 0 && native.refresh != self.refresh_rate)
+        })
+    }
+
+    /// The supported scaling `steps` positions above (or below, if negative) the current one,
+    /// clamped to the supported range.
+    #[must_use]
+    pub fn step_scaling(&self, steps: i32) -> i32 {
+        let supported = self.supported_scaling();
+        let Some(last) = supported.len().checked_sub(1) else {
+            return self.scaling_current;
+        };
+        let (position, steps) = self.step_origin(&supported, steps);
+        supported[position.saturating_add_signed(steps).min(last)]
+    }
+
+    /// Whether [`step_scaling`](Self::step_scaling) can move all `steps` positions without
+    /// hitting the smallest or largest supported value.
+    #[must_use]
+    pub fn can_step_scaling(&self, steps: i32) -> bool {
+        let supported = self.supported_scaling();
+        let (position, steps) = self.step_origin(&supported, steps);
+        position.checked_add_signed(steps).is_some_and(|target| target < supported.len())
+    }
+
+    /// The largest supported scaling not above `scaling`, or the smallest supported one if
+    /// `scaling` is below all of them.
+    #[must_use]
+    pub fn clamp_scaling(&self, scaling: i32) -> i32 {
+        let supported = self.supported_scaling();
+        supported
+            .iter()
+            .rev()
+            .find(|&&v| v <= scaling)
+            .or_else(|| supported.first())
+            .copied()
+            .unwrap_or(self.scaling_current)
+    }
+
+    /// Where stepping through `supported` starts: the first value not below the current one.
+    /// From a custom value between two supported ones that is already the first step up, so
+    /// positive `steps` are one fewer.
+    fn step_origin(&self, supported: &[i32], steps: i32) -> (usize, isize) {
+        let position = supported
+            .iter()
+            .position(|&v| v >= self.scaling_current)
+            .unwrap_or(supported.len().saturating_sub(1));
+        let mut steps = isize::try_from(steps).unwrap_or(0);
+        if steps > 0 && supported.get(position).is_some_and(|&v| v > self.scaling_current) {
+            steps -= 1;
         }
+        (position, steps)
+    }
 
-        debug!("Retrieved {} paths and {} modes", path_count, mode_count);
+    /// The (CCD, shell) scaling when both are known and disagree, as with a system-wide override
+    /// or after some in-place upgrades of Windows.
+    #[must_use]
+    pub fn scaling_mismatch(&self) -> Option<(i32, i32)> {
+        match (self.scaling_ccd, self.scaling_shell) {
+            (Some(ccd), Some(shell)) if ccd != shell => Some((ccd, shell)),
+            _ => None,
+        }
+    }
 
-        Ok((paths, modes))
+    /// Whether the current scaling is one `set` couldn't apply: a system-wide override, or a
+    /// per-monitor value outside [`DPI_VALUES`] such as 110% from Windows 11's custom scaling.
+    #[must_use]
+    pub fn has_custom_scaling(&self) -> bool {
+        self.custom_scaling || !DPI_VALUES.contains(&self.scaling_current)
     }
-}
 
-fn get_display_name_from_path(path: &DISPLAYCONFIG_PATH_INFO) -> Result<String> {
-    let mut target_name = DISPLAYCONFIG_TARGET_DEVICE_NAME {
-        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
-            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
-            size: u32::try_from(size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>())?,
-            adapterId: path.targetInfo.adapterId,
-            id: path.targetInfo.id,
-        },
-        flags: DISPLAYCONFIG_TARGET_DEVICE_NAME_FLAGS::default(),
-        outputTechnology: DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY::default(),
-        edidManufactureId: 0,
-        edidProductCodeId: 0,
-        connectorInstance: 0,
-        monitorFriendlyDeviceName: [0; 64],
-        monitorDevicePath: [0; 128],
-    };
+    /// The number Windows Settings shows for this display under "Identify", taken from the
+    /// GDI device name (`\\.\DISPLAY2` is 2); `None` without a GDI name.
+    #[must_use]
+    pub fn display_number(&self) -> Option<u32> {
+        self.gdi_device_name
+            .strip_prefix(r"\\.\DISPLAY")
+            .and_then(|n| n.parse().ok())
+    }
 
-    let result;
-    unsafe {
-        result = DisplayConfigGetDeviceInfo(&raw mut target_name.header);
+    /// Scaling values from [`DPI_VALUES`] that can be applied to this display.
+    #[must_use]
+    pub fn supported_scaling(&self) -> Vec<i32> {
+        DPI_VALUES
+            .iter()
+            .copied()
+            .filter(|&v| v <= self.scaling_max)
+            .collect()
     }
 
-    if result == 0 {
-        let friendly_name = String::from_utf16_lossy(&target_name.monitorFriendlyDeviceName)
-            .trim_end_matches('\0')
-            .to_string();
-        Ok(friendly_name)
-    } else {
-        Err(DisplayError::GetMonitorFriendlyName(result))
+    /// The supported scaling closest to giving a `effective` workspace (width, height) on a
+    /// `physical` mode, e.g. 150% for 2560x1440 on a 3840x2160 panel.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::UnreachableEffectiveSize`], listing the sizes that can be had,
+    /// if the needed scaling is outside the display's range.
+    pub fn scaling_for_effective(&self, physical: (u32, u32), effective: (u32, u32)) -> Result<i32> {
+        let (width, height) = (u64::from(physical.0), u64::from(physical.1));
+        let (ew, eh) = (u64::from(effective.0), u64::from(effective.1));
+        // Mean of the horizontal and vertical ratios in percent, rounded.
+        let denominator = (2 * ew * eh).max(1);
+        let needed = (100 * (width * eh + height * ew) + denominator / 2) / denominator;
+        let needed = i32::try_from(needed).unwrap_or(i32::MAX);
+
+        let supported = self.supported_scaling();
+        match (supported.first(), supported.last()) {
+            (Some(&min), Some(&max)) if (min..=max).contains(&needed) => Ok(supported
+                .iter()
+                .copied()
+                .min_by_key(|v| (v - needed).abs())
+                .unwrap_or(min)),
+            _ => Err(DisplayError::UnreachableEffectiveSize {
+                source_id: self.source_id,
+                width: effective.0,
+                height: effective.1,
+                achievable: supported
+                    .iter()
+                    .map(|&scaling| {
+                        let (w, h) = effective_size(physical.0, physical.1, scaling);
+                        format!("{w}x{h} ({scaling}%)")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            }),
+        }
     }
 }
 
-fn get_display_scaling_from_path(path: &DISPLAYCONFIG_PATH_INFO) -> Result<(i32, i32)> {
-    let mut dpi_info = DpiScaleGet {
-        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
-            r#type: DISPLAYCONFIG_DEVICE_INFO_TYPE(-3i32),
-            size: u32::try_from(size_of::<DpiScaleGet>())?,
-            adapterId: path.sourceInfo.adapterId,
-            id: path.sourceInfo.id,
-        },
-        min_scale_rel: 0,
-        cur_scale_rel: 0,
-        max_scale_rel: 0,
-    };
+/// The workspace size apps see on a `width`x`height` mode at `scaling` percent.
+///
+/// Windows scales each axis with `MulDiv(pixels, 96, dpi)`, which rounds to the nearest
+/// integer, so 1366 pixels at 125% are 1093 wide rather than 1092.
+#[must_use]
+pub fn effective_size(width: u32, height: u32, scaling: i32) -> (u32, u32) {
+    let scaling = u64::from(u32::try_from(scaling).unwrap_or(100).max(1));
+    let scale = |pixels: u32| u32::try_from((u64::from(pixels) * 100 + scaling / 2) / scaling).unwrap_or(u32::MAX);
+    (scale(width), scale(height))
+}
 
-    unsafe {
-        let result = DisplayConfigGetDeviceInfo(&raw mut dpi_info.header);
-        if result != 0 {
-            return Err(DisplayError::GetDpiInfo(result));
+impl fmt::Display for DisplayInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Example: [id:12345] My Monitor — 2560x1440 @ 125% (rec 150%)
+        write!(
+            f,
+            "[id:{}] {} — {}x{} @ {}%",
+            self.source_id, self.friendly_name, self.width, self.height, self.scaling_current
+        )?;
+        if self.scaling_current != 100 {
+            write!(f, " (effective {}x{})", self.effective_width(), self.effective_height())?;
         }
+        if self.has_custom_scaling() {
+            write!(f, " (custom)")?;
+        } else if self.scaling_recommended != self.scaling_current {
+            write!(f, " (rec {}%)", self.scaling_recommended)?;
+        }
+        if self.bits_per_color > 0 {
+            write!(f, ", {}-bit {}", self.bits_per_color, self.color_encoding)?;
+        }
+        if let Some(nits) = self.sdr_white_level {
+            write!(f, ", HDR (SDR {nits} nits)")?;
+        }
+        if !self.target_available {
+            write!(f, ", unavailable")?;
+        }
+        Ok(())
     }
+}
 
-    let min_abs = dpi_info.min_scale_rel.unsigned_abs() as usize;
+/// A snapshot of the connected displays.
+///
+/// The snapshot is taken once and never updated behind your back, while the real
+/// configuration changes whenever a monitor is plugged in, docked or woken up. Keep it for a
+/// single operation, or [`refresh`](Self::refresh) it before acting after any wait. Applying a
+/// [`DisplayInfo`] that no longer matches the live configuration fails with
+/// [`DisplayError::Stale`] rather than changing the wrong display:
+///
+/// ```no_run
+/// use display_tuner::display::{apply_display_config, DisplayConfig, DisplayError, DisplayTuner};
+///
+/// let mut tuner = DisplayTuner::new()?;
+/// // ... later, e.g. after a display change notification
+/// if !tuner.is_current()? {
+///     tuner.refresh()?;
+/// }
+/// let display = &tuner.displays()[0];
+/// let config = DisplayConfig::builder().scaling(125).resolve(display);
+/// match apply_display_config(display, &config) {
+///     // Changed in between: refresh and decide again.
+///     Err(DisplayError::Stale(_)) => tuner.refresh()?,
+///     result => result?,
+/// }
+/// # Ok::<(), DisplayError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct DisplayTuner {
+    displays: Vec<DisplayInfo>,
+    generation: u64,
+    taken_at: Instant,
+}
 
-    let cur_index = min_abs.wrapping_add(dpi_info.cur_scale_rel as usize);
-    let rec_index = cur_index.wrapping_sub(dpi_info.cur_scale_rel as usize);
+impl DisplayTuner {
+    /// Takes the first snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the displays can't be enumerated.
+    pub fn new() -> Result<Self> {
+        Ok(Self { displays: enumerate_displays()?, generation: 0, taken_at: Instant::now() })
+    }
 
-    if cur_index < DPI_VALUES.len() {
-        Ok((DPI_VALUES[cur_index], DPI_VALUES[rec_index]))
-    } else {
-        Err(DisplayError::DpiIndexOutOfRange)
+    #[must_use]
+    pub fn displays(&self) -> &[DisplayInfo] {
+        &self.displays
     }
-}
 
-fn apply_display_resolution(
-    display: &DisplayInfo,
-    config: &DisplayConfig,
-) -> Result<()> {
-    let old_width = display.width;
-    let old_height = display.height;
-    let new_width = config.width;
-    let new_height = config.height;
-    info!(
-            old_width,
-            old_height, new_width, new_height, "Changing resolution"
-        );
+    /// Replaces the snapshot with a fresh enumeration and bumps the generation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the displays can't be enumerated; the old snapshot is kept then.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.displays = enumerate_displays()?;
+        self.generation += 1;
+        self.taken_at = Instant::now();
+        Ok(())
+    }
 
-    let (paths, mut modes) = get_display_config()?;
+    /// Counts the refreshes, so callers can tell whether data they derived is older than the
+    /// snapshot.
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
 
-    unsafe {
-        let path = paths
-            .iter()
-            .find(|path| path.sourceInfo.id == display.source_id)
-            .unwrap();
+    /// Time since the snapshot was taken.
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        self.taken_at.elapsed()
+    }
 
-        let mode_idx = path.sourceInfo.Anonymous.modeInfoIdx as usize;
-        let mode = &mut modes[mode_idx];
+    /// Whether a fresh enumeration still matches the snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the displays can't be enumerated.
+    pub fn is_current(&self) -> Result<bool> {
+        Ok(enumerate_displays()? == self.displays)
+    }
 
-        mode.Anonymous.sourceMode.width = config.width;
-        mode.Anonymous.sourceMode.height = config.height;
+    /// Probes what can be changed on `display`. A probe that fails, e.g. a monitor that
+    /// doesn't answer DDC/CI or a driver without a mode list, leaves its fields `false` or 0
+    /// instead of failing the call. The DDC/CI probe can take a few seconds, see
+    /// [`capabilities_all`](Self::capabilities_all) for several displays.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::DisplayNotFound`] if `display` isn't part of the snapshot.
+    pub fn capabilities(&self, display: &DisplayInfo) -> Result<DisplayCapabilities> {
+        let mut all = self.capabilities_all(std::slice::from_ref(display))?;
+        Ok(all.remove(0))
+    }
 
-        let result = SetDisplayConfig(
-            Some(&paths),
-            Some(&modes),
-            SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG,
-        );
+    /// [`capabilities`](Self::capabilities) of each of `displays`, in the same order. The
+    /// DDC/CI probes run in parallel, one thread per monitor and within
+    /// [`PROBE_TIMEOUT`](crate::ddc::PROBE_TIMEOUT) together; the rest is queried in turn.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::DisplayNotFound`] if one of `displays` isn't part of the
+    /// snapshot.
+    pub fn capabilities_all(&self, displays: &[DisplayInfo]) -> Result<Vec<DisplayCapabilities>> {
+        #[cfg(feature = "ddcci")]
+        let all = self.capabilities_cached(displays, &mut crate::ddc::DdcCache::default())?;
+        #[cfg(not(feature = "ddcci"))]
+        let all = displays.iter().map(|d| self.local_capabilities(d)).collect::<Result<Vec<_>>>()?;
+        Ok(all)
+    }
 
-        if result != 0 {
-            return Err(DisplayError::SetDisplayConfig(result));
+    /// [`capabilities_all`](Self::capabilities_all) reusing the DDC/CI answers in `cache`.
+    ///
+    /// # Errors
+    ///
+    /// As [`capabilities_all`](Self::capabilities_all).
+    #[cfg(feature = "ddcci")]
+    pub fn capabilities_cached(&self, displays: &[DisplayInfo], cache: &mut crate::ddc::DdcCache) -> Result<Vec<DisplayCapabilities>> {
+        let mut all = displays.iter().map(|d| self.local_capabilities(d)).collect::<Result<Vec<_>>>()?;
+        for (capabilities, responds) in all.iter_mut().zip(cache.probe(displays)) {
+            capabilities.ddc_ci = responds;
         }
+        Ok(all)
+    }
 
-        info!("Resolution changed successfully");
+    /// The capabilities Windows itself reports, all but DDC/CI.
+    fn local_capabilities(&self, display: &DisplayInfo) -> Result<DisplayCapabilities> {
+        if !self.displays.iter().any(|d| d.source_id == display.source_id) {
+            return Err(DisplayError::DisplayNotFound(display.source_id));
+        }
+        let source_id = display.source_id;
+        let modes = supported_modes(display).unwrap_or_else(|err| {
+            debug!(source_id, %err, "No mode list for capabilities");
+            Vec::new()
+        });
+        let mut capabilities = DisplayCapabilities::from_modes(display, &modes);
+        capabilities.hdr_supported = hdr_supported(display).unwrap_or_else(|err| {
+            debug!(source_id, %err, "HDR support unknown");
+            false
+        });
+        capabilities.rotation = rotation_supported(display);
+        capabilities.link = link_info(display).inspect_err(|err| debug!(source_id, %err, "No link info")).ok();
+        Ok(capabilities)
     }
+}
 
-    Ok(())
+/// What can be changed on a display, see [`DisplayTuner::capabilities`].
+// Independent facts reported by Windows, not a state machine.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayCapabilities {
+    /// Distinct resolutions in the driver's mode list.
+    pub resolutions: usize,
+    /// Lowest and highest scaling Windows offers for the display.
+    pub scaling_min: i32,
+    pub scaling_max: i32,
+    /// Advanced color (HDR) can be turned on.
+    pub hdr_supported: bool,
+    /// The monitor answered a DDC/CI request, so its own settings (brightness, input) can be
+    /// controlled. Only probed with the `ddcci` feature, `false` without it.
+    #[serde(default)]
+    pub ddc_ci: bool,
+    /// Windows accepts the display rotated.
+    pub rotation: bool,
+    pub internal: bool,
+    /// The current resolution runs at other refresh rates too; 59 and 60Hz count as the same.
+    pub other_refresh_rates: bool,
+    /// The signal on the link now, `None` if the target mode couldn't be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkInfo>,
 }
 
-fn apply_display_scaling(display: &DisplayInfo, config: &DisplayConfig) -> Result<()> {
-    let old_scaling = display.scaling_current;
-    let new_scaling = config.scaling;
-    info!(old_scaling, new_scaling, "Changing DPI scaling");
+impl DisplayCapabilities {
+    /// The capabilities that follow from `display` and its mode list (see
+    /// [`supported_modes`]); the probed ones are left `false`.
+    #[must_use]
+    pub fn from_modes(display: &DisplayInfo, modes: &[DisplayMode]) -> Self {
+        let resolutions: BTreeSet<(u32, u32)> = modes.iter().map(|m| (m.width, m.height)).collect();
+        let scaling = display.supported_scaling();
+        Self {
+            resolutions: resolutions.len(),
+            scaling_min: scaling.first().copied().unwrap_or(display.scaling_current),
+            scaling_max: scaling.last().copied().unwrap_or(display.scaling_current),
+            internal: display.internal,
+            // 0 and 1 stand for the hardware default rate.
+            other_refresh_rates: modes.iter().any(|m| {
+                m.width == display.width
+                    && m.height == display.height
+                    && m.refresh > 1
+                    && m.refresh.abs_diff(display.refresh_rate) > 1
+            }),
+            ..Self::default()
+        }
+    }
+}
 
-    let (paths, _modes) = get_display_config()?;
-    let path = paths
-        .iter()
-        .find(|path| path.sourceInfo.id == display.source_id)
-        .unwrap();
+impl fmt::Display for DisplayCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        write!(
+            f,
+            "{} resolutions, scaling {}-{}%, other refresh rates: {}, HDR: {}",
+            self.resolutions,
+            self.scaling_min,
+            self.scaling_max,
+            yes_no(self.other_refresh_rates),
+            yes_no(self.hdr_supported),
+        )?;
+        #[cfg(feature = "ddcci")]
+        write!(f, ", DDC/CI: {}", yes_no(self.ddc_ci))?;
+        write!(f, ", rotation: {}, built-in: {}", yes_no(self.rotation), yes_no(self.internal))?;
+        match &self.link {
+            Some(link) => write!(f, ", link: {link}"),
+            None => Ok(()),
+        }
+    }
+}
 
-    let current_scale = get_display_scaling_from_path(path)?;
-    let recommended_scale = current_scale.1;
+fn hdr_supported(display: &DisplayInfo) -> Result<bool> {
+    let (paths, _) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    let path = find_path(&paths, display)?;
+    Ok(raw::advanced_color_supported(&raw::advanced_color_info(path)?))
+}
 
-    let recommended_scale_idx = i32::try_from(DPI_VALUES
-        .iter()
-        .position(|&v| v == recommended_scale)
-        .unwrap())?;
+/// Validates the display turned upside down (or back upright). A half turn keeps the desktop
+/// size, so the answer doesn't depend on the neighbouring displays.
+fn rotation_supported(display: &DisplayInfo) -> bool {
+    let rotation = if display.rotation == Rotation::Rotate180 { Rotation::Identity } else { Rotation::Rotate180 };
+    let turned = SourceMode { rotation, ..SourceMode::of(display) };
+    source_modes_config(&[turned])
+        .and_then(|(paths, modes)| set_display_config(&paths, &modes, SDC_VALIDATE | SDC_USE_SUPPLIED_DISPLAY_CONFIG))
+        .is_ok()
+}
 
-    let target_scale_idx = i32::try_from(DPI_VALUES
-        .iter()
-        .position(|&v| v == config.scaling)
-        .unwrap())?;
-
-    unsafe {
-        let mut dpi_set = DpiScaleSet {
-            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
-                r#type: DISPLAYCONFIG_DEVICE_INFO_TYPE(-4i32),
-                size: u32::try_from(size_of::<DpiScaleSet>())?,
-                adapterId: path.sourceInfo.adapterId,
-                id: display.source_id,
-            },
-            scale_rel: target_scale_idx - recommended_scale_idx,
-        };
+/// A resolution and refresh rate the display driver reports as supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh: u32,
+}
+
+impl fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{} @ {}Hz", self.width, self.height, self.refresh)
+    }
+}
+
+/// A refresh rate to switch to, see [`RefreshRate::pick`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshRate {
+    Hz(u32),
+    /// The highest rate supported at the resolution.
+    Max,
+    /// The lowest rate supported at the resolution.
+    Min,
+}
+
+impl RefreshRate {
+    /// Picks the rate from `modes` (see [`supported_modes`]) for `width`x`height`, `None` if
+    /// nothing matches.
+    ///
+    /// The driver reports fractional rates rounded down, so 119.88Hz shows up as 119 next to
+    /// 120. When a rate and the one right above it are both offered, they're treated as the
+    /// same rate and the upper one wins, which is the integer one unless the monitor offers
+    /// nothing but fractions; the exact fraction is never known, see [`set_refresh_rate`].
+    #[must_use]
+    pub fn pick(self, modes: &[DisplayMode], width: u32, height: u32) -> Option<u32> {
+        // 0 and 1 stand for the hardware default rate.
+        let rates: BTreeSet<u32> = modes
+            .iter()
+            .filter(|m| m.width == width && m.height == height && m.refresh > 1)
+            .map(|m| m.refresh)
+            .collect();
+        match self {
+            Self::Hz(hz) => rates.contains(&hz).then_some(hz),
+            Self::Max => rates.last().copied(),
+            Self::Min => rates
+                .first()
+                .map(|&hz| if rates.contains(&(hz + 1)) { hz + 1 } else { hz }),
+        }
+    }
+}
+
+impl fmt::Display for RefreshRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hz(hz) => write!(f, "{hz}Hz"),
+            Self::Max => f.write_str("max"),
+            Self::Min => f.write_str("min"),
+        }
+    }
+}
+
+/// A refresh rate from text: `max`, `min`, or a rate with an optional `Hz`, whole or
+/// fractional with a period or a comma (`144`, `59.94`, `59,94Hz`). Fractional rates are
+/// rounded down, as the driver reports them, see [`RefreshRate::pick`].
+#[must_use]
+pub fn parse_refresh_rate(text: &str) -> Option<RefreshRate> {
+    let text = text.trim().to_ascii_lowercase();
+    match text.strip_suffix("hz").unwrap_or(&text).trim_end() {
+        "max" => Some(RefreshRate::Max),
+        "min" => Some(RefreshRate::Min),
+        hz => {
+            if let Ok(hz) = hz.parse() {
+                return Some(RefreshRate::Hz(hz));
+            }
+            let hz = parse_decimal(hz).filter(|hz| (0.0..10_000.0).contains(hz))?;
+            // Checked against the range just before, so the cast can't truncate or lose the sign.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            Some(RefreshRate::Hz(hz.floor() as u32))
+        }
+    }
+}
+
+/// A mode picked for `set --mode`, see [`parse_mode`] and [`find_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeSpec {
+    /// The number of the mode in the listing of [`supported_modes`], from 1.
+    Index(usize),
+    /// A resolution in the monitor's own orientation and a refresh rate in thousandths of a
+    /// hertz, since rates like 143.98Hz are common.
+    Mode { width: u32, height: u32, millihertz: u32 },
+}
 
-        let result = DisplayConfigSetDeviceInfo(&raw mut dpi_set.header);
-        if result != 0 {
-            return Err(DisplayError::SetDpiScaling(result));
+impl ModeSpec {
+    /// The mode of `modes` (see [`supported_modes`], whose order the numbers refer to) this
+    /// stands for. The driver lists fractional rates rounded down, so 143.98Hz matches a listed
+    /// 144Hz or, failing that, 143Hz.
+    #[must_use]
+    pub fn find(self, modes: &[DisplayMode]) -> Option<DisplayMode> {
+        match self {
+            Self::Index(index) => modes.get(index.checked_sub(1)?).copied(),
+            Self::Mode { width, height, millihertz } => [(millihertz + 500) / 1000, millihertz / 1000]
+                .into_iter()
+                .find_map(|refresh| modes.iter().find(|m| **m == DisplayMode { width, height, refresh }))
+                .copied(),
+        }
+    }
+
+    /// Up to `count` of `modes` closest to this one, with their numbers in listing order: those
+    /// numbered next to it, or those closest in resolution and then refresh rate.
+    #[must_use]
+    pub fn nearest(self, modes: &[DisplayMode], count: usize) -> Vec<(usize, DisplayMode)> {
+        let mut numbered: Vec<(usize, DisplayMode)> = modes.iter().copied().enumerate().map(|(i, m)| (i + 1, m)).collect();
+        match self {
+            Self::Index(index) => numbered.sort_by_key(|&(i, _)| i.abs_diff(index)),
+            Self::Mode { width, height, millihertz } => numbered.sort_by_key(|(_, m)| {
+                (m.width.abs_diff(width) + m.height.abs_diff(height), m.refresh.saturating_mul(1000).abs_diff(millihertz))
+            }),
+        }
+        numbered.truncate(count);
+        numbered.sort_unstable_by_key(|&(i, _)| i);
+        numbered
+    }
+}
+
+impl fmt::Display for ModeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "#{index}"),
+            Self::Mode { width, height, millihertz } if millihertz % 1000 == 0 => write!(f, "{width}x{height} @ {}Hz", millihertz / 1000),
+            Self::Mode { width, height, millihertz } => {
+                let fraction = format!("{:03}", millihertz % 1000);
+                write!(f, "{width}x{height} @ {}.{}Hz", millihertz / 1000, fraction.trim_end_matches('0'))
+            }
         }
+    }
+}
+
+/// A mode from text: its number in the listing of `list --modes` (`17`), or
+/// `WIDTHxHEIGHT@RATE` with a whole or fractional rate and an optional `Hz` (`1920x1080@144`,
+/// `2560x1440@143.98hz`). The size may have thousands separators as [`parse_resolution`]
+/// reads them and the rate a decimal comma (`3.840x2.160@59,94`). `None` for anything else,
+/// number 0 or a rate of 0.
+#[must_use]
+pub fn parse_mode(text: &str) -> Option<ModeSpec> {
+    let text = text.trim();
+    if let Ok(index) = text.parse::<usize>() {
+        return (index > 0).then_some(ModeSpec::Index(index));
+    }
+    let (size, rate) = text.split_once('@')?;
+    let (width, height) = parse_resolution(size)?;
+    let rate = rate.trim().to_ascii_lowercase();
+    let hz = parse_decimal(rate.strip_suffix("hz").unwrap_or(&rate))?;
+    if !(hz > 0.0 && hz < 10_000.0) {
+        return None;
+    }
+    // Checked against the range just before, so the cast can't truncate or lose the sign.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let millihertz = (hz * 1000.0).round() as u32;
+    Some(ModeSpec::Mode { width, height, millihertz })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    pub width: u32,
+    pub height: u32,
+    pub scaling: i32,
+}
+
+/// Smallest resolution [`DisplayConfig::validate`] accepts, in either orientation.
+pub const MIN_RESOLUTION: (u32, u32) = (640, 480);
+
+/// Largest width or height [`DisplayConfig::validate`] accepts.
+pub const MAX_DIMENSION: u32 = 16384;
+
+/// Why [`DisplayConfig::validate`] refused a resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ValidationError {
+    #[error("{width}x{height} is below the minimum of 640x480")]
+    TooSmall { width: u32, height: u32 },
+    #[error("{width}x{height} is above the maximum of 16384 pixels per side")]
+    TooLarge { width: u32, height: u32 },
+    #[error("{dimension} {value} is not a multiple of 8")]
+    NotMultipleOf8 { dimension: &'static str, value: u32 },
+}
+
+impl DisplayConfig {
+    /// Starts a partial configuration, see [`DisplayConfigBuilder`].
+    #[must_use]
+    pub fn builder() -> DisplayConfigBuilder {
+        DisplayConfigBuilder::default()
+    }
+
+    /// Checks that the resolution is plausible for `display`. The resolution it already runs at
+    /// and any listed in `modes` (see [`supported_modes`]) always pass, so 1366x768 or 1680x1050
+    /// panels work. Any other resolution has to be at least [`MIN_RESOLUTION`] (portrait sizes
+    /// count), at most [`MAX_DIMENSION`] per side, and have both dimensions multiples of 8.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first constraint the resolution violates.
+    pub fn validate(&self, display: &DisplayInfo, modes: &[DisplayMode]) -> std::result::Result<(), ValidationError> {
+        let (width, height) = (self.width, self.height);
+        if (width, height) == (display.width, display.height)
+            || match_mode(modes, (width, height), display.rotation) == ModeMatch::Supported
+        {
+            return Ok(());
+        }
+        if width.max(height) < MIN_RESOLUTION.0 || width.min(height) < MIN_RESOLUTION.1 {
+            return Err(ValidationError::TooSmall { width, height });
+        }
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(ValidationError::TooLarge { width, height });
+        }
+        for (dimension, value) in [("width", width), ("height", height)] {
+            if value % 8 != 0 {
+                return Err(ValidationError::NotMultipleOf8 { dimension, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pairs each refresh rate of `refresh` with the change for the same display in `changes`, by
+/// source id. Displays without a rate of their own are left alone, as are rates for displays
+/// no longer among `changes`.
+#[must_use]
+pub fn refresh_targets<'a>(
+    changes: &'a [(DisplayInfo, DisplayConfig)],
+    refresh: &[(DisplayInfo, u32)],
+) -> Vec<(&'a DisplayInfo, &'a DisplayConfig, u32)> {
+    refresh
+        .iter()
+        .filter_map(|(disp, hz)| {
+            changes.iter().find(|(d, _)| d.source_id == disp.source_id).map(|(d, target)| (d, target, *hz))
+        })
+        .collect()
+}
+
+/// A partial configuration: settings left unset keep the display's current value when
+/// [`resolve`](Self::resolve)d.
+///
+/// ```no_run
+/// use display_tuner::display::{apply_display_config, enumerate_displays, DisplayConfig};
+///
+/// for display in enumerate_displays()? {
+///     apply_display_config(&display, &DisplayConfig::builder().scaling(150).resolve(&display))?;
+/// }
+/// # Ok::<(), display_tuner::display::DisplayError>(())
+/// ```
+///
+/// Setters take either a value or an `Option`, so optional input passes straight through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DisplayConfigBuilder {
+    width: Option<u32>,
+    height: Option<u32>,
+    scaling: Option<i32>,
+    refresh: Option<RefreshRate>,
+    rotation: Option<Rotation>,
+}
+
+impl DisplayConfigBuilder {
+    #[must_use]
+    pub fn width(mut self, width: impl Into<Option<u32>>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    #[must_use]
+    pub fn height(mut self, height: impl Into<Option<u32>>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    /// Scaling in percent, e.g. `150`.
+    #[must_use]
+    pub fn scaling(mut self, scaling: impl Into<Option<i32>>) -> Self {
+        self.scaling = scaling.into();
+        self
+    }
+
+    /// Refresh rate, picked at the resolved resolution by [`resolve_refresh`](Self::resolve_refresh).
+    #[must_use]
+    pub fn refresh(mut self, refresh: impl Into<Option<RefreshRate>>) -> Self {
+        self.refresh = refresh.into();
+        self
+    }
+
+    /// The rotation the resolution is meant for. It isn't applied with the configuration,
+    /// see [`apply_source_modes`], but decides how the resolution is matched against the
+    /// supported modes.
+    #[must_use]
+    pub fn rotation(mut self, rotation: impl Into<Option<Rotation>>) -> Self {
+        self.rotation = rotation.into();
+        self
+    }
+
+    /// The rotation `display` ends up with.
+    #[must_use]
+    pub fn resolve_rotation(&self, display: &DisplayInfo) -> Rotation {
+        self.rotation.unwrap_or(display.rotation)
+    }
+
+    /// The configuration for `display`, with unset settings taken from its current state.
+    /// When the rotation turns a landscape desktop to portrait or back, the current resolution
+    /// is turned with it.
+    #[must_use]
+    pub fn resolve(&self, display: &DisplayInfo) -> DisplayConfig {
+        let (width, height) = if self.resolve_rotation(display).is_portrait() == display.rotation.is_portrait() {
+            (display.width, display.height)
+        } else {
+            (display.height, display.width)
+        };
+        DisplayConfig {
+            width: self.width.unwrap_or(width),
+            height: self.height.unwrap_or(height),
+            scaling: self.scaling.unwrap_or(display.scaling_current),
+        }
+    }
+
+    /// The refresh rate in Hz to switch `display` to at the resolved resolution, `None` if no
+    /// rate was requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::UnsupportedRefreshRate`] if the driver offers no matching rate, or
+    /// an error if the modes can't be listed.
+    pub fn resolve_refresh(&self, display: &DisplayInfo) -> Result<Option<u32>> {
+        let Some(rate) = self.refresh else {
+            return Ok(None);
+        };
+        let config = self.resolve(display);
+        // The driver lists modes in the monitor's own orientation.
+        let (width, height) = self.resolve_rotation(display).orient((config.width, config.height));
+        rate.pick(&supported_modes(display)?, width, height)
+            .map(Some)
+            .ok_or(DisplayError::UnsupportedRefreshRate {
+                source_id: display.source_id,
+                width: config.width,
+                height: config.height,
+                refresh: rate,
+            })
+    }
+}
+
+/// Resolution, desktop position and rotation for one display source, see [`apply_source_modes`].
+///
+/// `width` and `height` are desktop dimensions, i.e. already swapped for portrait rotations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMode {
+    pub source_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub rotation: Rotation,
+}
+
+impl SourceMode {
+    #[must_use]
+    pub fn of(display: &DisplayInfo) -> Self {
+        Self {
+            source_id: display.source_id,
+            width: display.width,
+            height: display.height,
+            x: display.position_x,
+            y: display.position_y,
+            rotation: display.rotation,
+        }
+    }
+}
+
+/// Fails outside the interactive user session unless [`set_session_check`] turned the check
+/// off. A failed query doesn't block anything.
+fn check_session() -> Result<()> {
+    if !session_check() {
+        return Ok(());
+    }
+    match raw::session_info() {
+        Ok(session) if !session.is_interactive() => Err(DisplayError::NonInteractiveSession {
+            session_id: session.session_id,
+            window_station: session.window_station,
+        }),
+        Ok(_) => Ok(()),
+        Err(err) => {
+            debug!(%err, "Couldn't query the session");
+            Ok(())
+        }
+    }
+}
+
+/// Enumerates the displays attached to the active display paths.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NonInteractiveSession`] outside the interactive user session (see
+/// [`set_session_check`]), [`DisplayError::NoDisplays`] if the query succeeds but finds no
+/// active display, so the list is never empty, or an error if the display configuration, a monitor name or the DPI info can't
+/// be queried.
+pub fn enumerate_displays() -> Result<Vec<DisplayInfo>> {
+    let displays = metrics::time(Phase::Enumerate, query_displays)?;
+    metrics::observe_topology(&displays);
+    Ok(displays)
+}
+
+fn query_displays() -> Result<Vec<DisplayInfo>> {
+    check_session()?;
+    let mut displays = Vec::new();
+
+    let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    if paths.is_empty() {
+        return Err(DisplayError::NoDisplays);
+    }
+    let drr = drr_paths();
+
+    for path in &paths {
+        debug!("Processing path...");
+
+        let Some(mode) = raw::source_mode(path, &modes) else {
+            debug!("Skipping path without a valid source mode");
+            continue;
+        };
+        let (width, height, position) = (mode.width, mode.height, mode.position);
+
+        let target_name = raw::target_device_name(path)?;
+        let gdi_device_name = raw::source_gdi_name(path)?;
+        let adapter_name = raw::adapter_device_path(path).inspect_err(|err| debug!(%err, "No adapter name")).unwrap_or_default();
+        let scaling = display_scaling(path, position)?;
+        // Not available before Windows 10 1709 or on some virtual displays.
+        let (bits_per_color, color_encoding, hdr_enabled) = match raw::advanced_color_info(path) {
+            Ok(color) => (
+                color.bitsPerColorChannel,
+                ColorEncoding::from(color.colorEncoding),
+                raw::advanced_color_enabled(&color),
+            ),
+            Err(err) => {
+                debug!(%err, "No advanced color info");
+                (0, ColorEncoding::default(), false)
+            }
+        };
+        let sdr_white_level = if hdr_enabled {
+            raw::sdr_white_level(path).ok().map(sdr_level_to_nits)
+        } else {
+            None
+        };
+        let (native_width, native_height, native_refresh) = match raw::target_preferred_mode(path) {
+            Ok(preferred) => (
+                preferred.width,
+                preferred.height,
+                refresh_hz(preferred.targetMode.targetVideoSignalInfo.vSyncFreq),
+            ),
+            Err(err) => {
+                debug!(%err, "No preferred mode");
+                (0, 0, 0)
+            }
+        };
+
+        let connector = Connector::from(path.targetInfo.outputTechnology);
+        let refresh_rate = refresh_hz(path.targetInfo.refreshRate);
+        let disp = DisplayInfo {
+            friendly_name: raw::utf16_to_string(&target_name.monitorFriendlyDeviceName),
+            source_id: path.sourceInfo.id,
+            adapter_id: path.adapter_id(),
+            adapter_name,
+            width,
+            height,
+            scaling_current: scaling.current,
+            scaling_recommended: scaling.recommended,
+            scaling_max: scaling.max,
+            scaling_ccd: scaling.ccd,
+            scaling_shell: scaling.shell,
+            refresh_rate,
+            drr_supported: drr.is_some() && refresh_rate >= 2 * DRR_MIN_REFRESH,
+            drr_enabled: drr.as_ref().is_some_and(|on| on.contains(&(path.adapter_id(), path.source_id()))),
+            native_width,
+            native_height,
+            native_refresh,
+            custom_scaling: scaling.custom,
+            connector,
+            internal: connector.is_internal(),
+            active: path.is_active(),
+            target_available: path.target_available(),
+            target_status: path.target_status(),
+            position_x: position.x,
+            position_y: position.y,
+            primary: position.x == 0 && position.y == 0,
+            work_area: None,
+            rotation: Rotation::from(path.targetInfo.rotation),
+            scale_mode: ScaleMode::from(path.targetInfo.scaling),
+            gdi_device_name,
+            device_path: raw::utf16_to_string(&target_name.monitorDevicePath),
+            edid_manufacturer_id: target_name.edidManufactureId,
+            edid_product_id: target_name.edidProductCodeId,
+            bits_per_color,
+            color_encoding,
+            hdr_enabled,
+            sdr_white_level,
+            serial_number: None,
+            model_name: None,
+            manufacture_week: None,
+            manufacture_year: None,
+            debug: None,
+        };
+        info!("{disp}");
+        displays.push(disp);
+    }
+
+    // Virtual GPUs may report paths without a source mode.
+    if displays.is_empty() {
+        return Err(DisplayError::NoDisplays);
+    }
+    fill_work_areas(&mut displays);
+    Ok(displays)
+}
+
+/// The connected monitors no active path drives, such as one disconnected in the Windows display
+/// settings, once each with [`DisplayInfo::active`] unset. Only what the target reports is
+/// filled in (name, connector, EDID ids, native mode); without a source there's no resolution,
+/// position or scaling, and `source_id` is just the first source that could drive it. Targets
+/// whose monitor name can't be queried are left out.
+///
+/// # Errors
+///
+/// Returns an error if the display configuration can't be queried.
+pub fn enumerate_inactive_displays() -> Result<Vec<DisplayInfo>> {
+    check_session()?;
+    // QDC_ALL_PATHS lists a target once per source that could drive it.
+    let (paths, _) = query_display_config(QDC_ALL_PATHS)?;
+    let target = |path: &PathInfo| (path.targetInfo.adapterId.LowPart, path.targetInfo.adapterId.HighPart, path.targetInfo.id);
+    let active: Vec<_> = paths.iter().filter(|path| path.is_active()).map(target).collect();
+    let mut seen = Vec::new();
+    let mut displays = Vec::new();
+    for path in paths.iter().filter(|path| path.target_available() && !path.is_active()) {
+        if active.contains(&target(path)) || seen.contains(&target(path)) {
+            continue;
+        }
+        seen.push(target(path));
+        let target_name = match raw::target_device_name(path) {
+            Ok(name) => name,
+            Err(err) => {
+                debug!(target_id = path.targetInfo.id, %err, "Skipping inactive target without a name");
+                continue;
+            }
+        };
+        let (native_width, native_height, native_refresh) = raw::target_preferred_mode(path).map_or((0, 0, 0), |preferred| {
+            (preferred.width, preferred.height, refresh_hz(preferred.targetMode.targetVideoSignalInfo.vSyncFreq))
+        });
+        let connector = Connector::from(path.targetInfo.outputTechnology);
+        let disp = DisplayInfo {
+            friendly_name: raw::utf16_to_string(&target_name.monitorFriendlyDeviceName),
+            source_id: path.sourceInfo.id,
+            adapter_id: path.adapter_id(),
+            native_width,
+            native_height,
+            native_refresh,
+            connector,
+            internal: connector.is_internal(),
+            active: false,
+            target_available: true,
+            target_status: path.target_status(),
+            device_path: raw::utf16_to_string(&target_name.monitorDevicePath),
+            edid_manufacturer_id: target_name.edidManufactureId,
+            edid_product_id: target_name.edidProductCodeId,
+            ..DisplayInfo::default()
+        };
+        debug!(name = disp.friendly_name, "Inactive display");
+        displays.push(disp);
+    }
+    Ok(displays)
+}
+
+/// Fills in [`DisplayInfo::work_area`] from the monitor the window manager has for each GDI
+/// device. Without an answer they stay `None`; nothing else depends on them.
+fn fill_work_areas(displays: &mut [DisplayInfo]) {
+    match raw::monitor_work_areas() {
+        Ok(areas) => {
+            for display in displays {
+                display.work_area = areas
+                    .iter()
+                    .find(|(device, _)| device.eq_ignore_ascii_case(&display.gdi_device_name))
+                    .map(|(_, area)| *area);
+            }
+        }
+        Err(err) => debug!(%err, "No work areas"),
+    }
+}
+
+/// The active sources (adapter and source id) with Dynamic Refresh Rate on, `None` if the OS
+/// doesn't know `QDC_VIRTUAL_REFRESH_RATE_AWARE` (before Windows 11).
+fn drr_paths() -> Option<Vec<(AdapterId, u32)>> {
+    match query_display_config(QDC_ONLY_ACTIVE_PATHS | QDC_VIRTUAL_REFRESH_RATE_AWARE) {
+        Ok((paths, _)) => Some(
+            paths
+                .iter()
+                .filter(|p| p.boost_refresh_rate())
+                .map(|p| (p.adapter_id(), p.source_id()))
+                .collect(),
+        ),
+        Err(err) => {
+            debug!(%err, "No virtual refresh rate support");
+            None
+        }
+    }
+}
+
+fn refresh_hz(rate: DISPLAYCONFIG_RATIONAL) -> u32 {
+    rate.Numerator.checked_div(rate.Denominator).unwrap_or(0)
+}
+
+/// Lists the modes the driver supports for `display`, highest resolution and refresh first:
+/// by width, then height, then refresh rate, each descending. The order only depends on the
+/// modes, so the numbers `list --modes` prints stay valid for [`ModeSpec::Index`] until the
+/// driver reports other modes.
+///
+/// # Errors
+///
+/// Returns an error if the driver reports no modes for the display.
+pub fn supported_modes(display: &DisplayInfo) -> Result<Vec<DisplayMode>> {
+    let mut modes: Vec<DisplayMode> = raw::enum_display_settings(&display.gdi_device_name)?
+        .iter()
+        .map(|devmode| DisplayMode {
+            width: devmode.dmPelsWidth,
+            height: devmode.dmPelsHeight,
+            refresh: devmode.dmDisplayFrequency,
+        })
+        .collect();
+
+    if modes.is_empty() {
+        return Err(DisplayError::NoDisplayModes(display.gdi_device_name.clone()));
+    }
+
+    modes.sort_unstable_by(|a, b| b.cmp(a));
+    modes.dedup();
+    let source_id = display.source_id;
+    let count = modes.len();
+    debug!(source_id, count, "Enumerated supported modes");
+
+    Ok(modes)
+}
+
+/// The mode of `display` that `spec` stands for, see [`ModeSpec::find`].
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NoSuchMode`], listing the nearest modes, if none matches, or an error
+/// if the modes can't be listed.
+pub fn find_mode(display: &DisplayInfo, spec: ModeSpec) -> Result<DisplayMode> {
+    let modes = supported_modes(display)?;
+    spec.find(&modes).ok_or_else(|| DisplayError::NoSuchMode {
+        source_id: display.source_id,
+        mode: spec,
+        nearest: spec
+            .nearest(&modes, 3)
+            .iter()
+            .map(|(i, mode)| format!("{i}) {mode}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    })
+}
+
+/// How a requested resolution relates to the supported modes, see [`match_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeMatch {
+    Supported,
+    /// Only supported with width and height swapped, usually a portrait size asked for a
+    /// landscape display or the other way round.
+    Transposed,
+    Unsupported,
+}
+
+/// Looks up the desktop size `width`x`height` of a display turned by `rotation` in `modes` (see
+/// [`supported_modes`]), which the driver lists in the monitor's own orientation.
+#[must_use]
+pub fn match_mode(modes: &[DisplayMode], (width, height): (u32, u32), rotation: Rotation) -> ModeMatch {
+    let listed = |size: (u32, u32)| modes.iter().any(|m| (m.width, m.height) == size);
+    let (width, height) = rotation.orient((width, height));
+    if listed((width, height)) {
+        ModeMatch::Supported
+    } else if listed((height, width)) {
+        ModeMatch::Transposed
+    } else {
+        ModeMatch::Unsupported
+    }
+}
+
+/// A display's native mode, see [`native_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativeMode {
+    /// In the monitor's own orientation, before rotation.
+    pub mode: DisplayMode,
+    /// The monitor reported it; otherwise it's the largest of the supported modes.
+    pub reported: bool,
+}
+
+impl NativeMode {
+    /// The resolution to set on a display turned by `rotation`, which swaps width and height
+    /// for portrait.
+    #[must_use]
+    pub fn rotated_size(&self, rotation: Rotation) -> (u32, u32) {
+        rotation.orient((self.mode.width, self.mode.height))
+    }
+}
+
+/// The native mode of `display`: the preferred mode its monitor reports or, for monitors that
+/// report none (some TVs), the largest of `modes` (see [`supported_modes`]) at its highest
+/// refresh rate. `None` if neither is known.
+#[must_use]
+pub fn native_mode(display: &DisplayInfo, modes: &[DisplayMode]) -> Option<NativeMode> {
+    if let Some(mode) = display.reported_native_mode() {
+        return Some(NativeMode { mode, reported: true });
+    }
+    let largest = modes
+        .iter()
+        .max_by_key(|m| (u64::from(m.width) * u64::from(m.height), m.refresh))?;
+    // Drivers list a rotated display's modes in either orientation; match the unrotated one.
+    let (width, height) = display.unrotated_size();
+    let swap = (largest.width >= largest.height) != (width >= height);
+    let (width, height) = if swap { (largest.height, largest.width) } else { (largest.width, largest.height) };
+    Some(NativeMode { mode: DisplayMode { width, height, refresh: largest.refresh }, reported: false })
+}
+
+/// How far the mode [`find_scaled_mode`] picks may be off the exact size, in percent.
+pub const RES_SCALE_TOLERANCE: u32 = 5;
+
+/// A resolution as a percentage of the native one, see [`scale_resolution`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaledResolution {
+    /// The percentage of the native resolution in each dimension, rounded.
+    pub exact: (u32, u32),
+    /// The supported mode nearest to `exact`, in the orientation of the native mode.
+    pub mode: DisplayMode,
+    /// How far `mode` is off `exact`, in percent of the dimension that's off the most.
+    pub deviation: f64,
+}
+
+/// The mode among `modes` (see [`supported_modes`]) nearest to `percent` of `native` in each
+/// dimension, the highest refresh rate of the first listed at that size; `None` without modes.
+/// Modes are compared in the orientation of `native`, whichever way the driver lists them.
+#[must_use]
+pub fn scale_resolution(native: DisplayMode, modes: &[DisplayMode], percent: u32) -> Option<ScaledResolution> {
+    let scale = |size: u32| u32::try_from((u64::from(size) * u64::from(percent) + 50) / 100).unwrap_or(u32::MAX);
+    let exact = (scale(native.width), scale(native.height));
+    let off = |size: u32, target: u32| f64::from(size.abs_diff(target)) * 100.0 / f64::from(target.max(1));
+    let landscape = native.width >= native.height;
+    modes
+        .iter()
+        .map(|m| {
+            let mode = if (m.width >= m.height) == landscape { *m } else { DisplayMode { width: m.height, height: m.width, ..*m } };
+            let deviation = off(mode.width, exact.0).max(off(mode.height, exact.1));
+            ScaledResolution { exact, mode, deviation }
+        })
+        .min_by(|a, b| a.deviation.total_cmp(&b.deviation))
+}
+
+/// [`scale_resolution`] of the native mode of `display` (see [`native_mode`]).
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NoDisplayModes`] if the driver lists no modes,
+/// [`DisplayError::NoCloseMode`] if the nearest one is more than [`RES_SCALE_TOLERANCE`] off
+/// unless `force`, or an error if the modes can't be listed.
+pub fn find_scaled_mode(display: &DisplayInfo, percent: u32, force: bool) -> Result<ScaledResolution> {
+    let modes = supported_modes(display)?;
+    let no_modes = || DisplayError::NoDisplayModes(display.gdi_device_name.clone());
+    let native = native_mode(display, &modes).ok_or_else(no_modes)?;
+    let scaled = scale_resolution(native.mode, &modes, percent).ok_or_else(no_modes)?;
+    if scaled.deviation > f64::from(RES_SCALE_TOLERANCE) && !force {
+        let (width, height) = scaled.exact;
+        return Err(DisplayError::NoCloseMode { source_id: display.source_id, width, height, nearest: scaled.mode });
+    }
+    Ok(scaled)
+}
+
+/// Advice against a non-native resolution, see [`native_resolution_advice`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativeAdvice {
+    pub source_id: u32,
+    /// The resolution asked for.
+    pub requested: (u32, u32),
+    /// The native resolution, in the orientation asked for.
+    pub native: (u32, u32),
+    /// The scaling giving about the same workspace at the native resolution.
+    pub scaling: i32,
+}
+
+impl fmt::Display for NativeAdvice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ((width, height), (native_width, native_height)) = (self.requested, self.native);
+        write!(
+            f,
+            "Display {id}: {width}x{height} isn't the native {native_width}x{native_height}, so the monitor stretches it and \
+             text looks blurry. For about the same size but sharp, keep the native resolution and scale to {scaling}%: \
+             display-tuner set --id {id} --native --scaling {scaling}",
+            id = self.source_id,
+            scaling = self.scaling,
+        )
+    }
+}
+
+/// Advice for setting `display` to `requested` instead of its `native` resolution (both in the
+/// orientation it ends up in) while keeping its scaling: the scaling that makes everything as
+/// big at the native resolution, which Windows renders sharply. `None` when the request isn't
+/// lower than native, has another aspect ratio (an old game's 4:3, say) or changes the scaling
+/// itself (`scaling_given`), and when no scaling in [`DPI_VALUES`] would differ from the
+/// current one.
+#[must_use]
+pub fn native_resolution_advice(display: &DisplayInfo, requested: (u32, u32), native: (u32, u32), scaling_given: bool) -> Option<NativeAdvice> {
+    let ((width, height), (native_width, native_height)) = (requested, native);
+    if scaling_given || width == 0 || height == 0 || width > native_width || height > native_height || requested == native {
+        return None;
+    }
+    // Same aspect ratio within 1%.
+    let (across, down) = (u64::from(width) * u64::from(native_height), u64::from(height) * u64::from(native_width));
+    if across.abs_diff(down) * 100 > across.max(down) {
+        return None;
+    }
+    let needed = f64::from(display.scaling_current) * f64::from(native_width) / f64::from(width);
+    let scaling = DPI_VALUES
+        .iter()
+        .copied()
+        .min_by(|a, b| (f64::from(*a) - needed).abs().total_cmp(&(f64::from(*b) - needed).abs()))?;
+    (scaling != display.scaling_current).then_some(NativeAdvice { source_id: display.source_id, requested, native, scaling })
+}
+
+/// Switches `display` to `hz` at its current resolution.
+///
+/// Only the target's vertical sync frequency is replaced; the rest of the signal timing still
+/// describes the old rate, so Windows is allowed to pick the timing that matches.
+///
+/// `hz` is written as the whole rate `hz/1`, as the driver lists modes with their rates rounded
+/// down and doesn't report the exact fraction. Where the monitor offers 120Hz next to 119.982Hz,
+/// that asks for 120Hz; a rate only offered as a fraction, such as 59.94Hz listed as 59, is
+/// asked for as 59Hz and left to Windows to match, so it may end up at another rate.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::TargetUnavailable`] if the monitor is asleep, or an error if the
+/// display isn't found or Windows rejects the rate.
+pub fn set_refresh_rate(display: &DisplayInfo, hz: u32) -> Result<()> {
+    if !display.target_available {
+        return Err(DisplayError::TargetUnavailable(display.source_id));
+    }
+    let source_id = display.source_id;
+    info!(source_id, hz, "Changing refresh rate");
+    let build = || refresh_config(display, hz);
+    set_with_retries(build, SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES)
+}
+
+fn refresh_config(display: &DisplayInfo, hz: u32) -> Result<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    let (mut paths, mut modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    let not_found = || DisplayError::DisplayNotFound(display.source_id);
+
+    let index = paths
+        .iter()
+        .position(|p| p.source_id() == display.source_id)
+        .ok_or_else(not_found)?;
+    let rate = DISPLAYCONFIG_RATIONAL { Numerator: hz, Denominator: 1 };
+    let mode = raw::target_mode_mut(&paths[index], &mut modes).ok_or_else(not_found)?;
+    mode.targetVideoSignalInfo.vSyncFreq = rate;
+    paths[index].targetInfo.refreshRate = rate;
+
+    Ok((paths, modes))
+}
+
+/// Changes how `display` fits its desktop image to the monitor, see [`ScaleMode`].
+///
+/// # Errors
+///
+/// Returns [`DisplayError::TargetUnavailable`] if the monitor is asleep, or an error if the
+/// display isn't found or Windows rejects the mode.
+pub fn set_scale_mode(display: &DisplayInfo, mode: ScaleMode) -> Result<()> {
+    if !display.target_available {
+        return Err(DisplayError::TargetUnavailable(display.source_id));
+    }
+    let source_id = display.source_id;
+    info!(source_id, %mode, "Changing scale mode");
+    throttle::wait_for_apply(source_id);
+    let build = || {
+        let (mut paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+        let path = paths
+            .iter_mut()
+            .find(|p| p.source_id() == source_id)
+            .ok_or(DisplayError::DisplayNotFound(source_id))?;
+        path.targetInfo.scaling = mode.into();
+        Ok((paths, modes))
+    };
+    set_with_retries(build, SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG)
+}
+
+/// How often a `SetDisplayConfig` call failing with a transient error is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Additional attempts after the first one.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every further one.
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        DEFAULT_RETRY_POLICY
+    }
+}
+
+const DEFAULT_RETRY_POLICY: RetryPolicy = RetryPolicy {
+    retries: 0,
+    delay: Duration::from_millis(500),
+};
+
+static RETRY_POLICY: RwLock<RetryPolicy> = RwLock::new(DEFAULT_RETRY_POLICY);
+
+/// Sets the retry policy used by every function that applies a configuration in this process.
+pub fn set_retry_policy(policy: RetryPolicy) {
+    *RETRY_POLICY.write().unwrap_or_else(std::sync::PoisonError::into_inner) = policy;
+}
+
+#[must_use]
+pub fn retry_policy() -> RetryPolicy {
+    *RETRY_POLICY.read().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+static APPLY_TIMEOUT: RwLock<Option<Duration>> = RwLock::new(None);
+
+/// Limits how long each blocking apply call (`SetDisplayConfig`, the DPI change,
+/// `ChangeDisplaySettingsEx`) may take for the rest of the process, `None` to wait as long as
+/// it takes (the default).
+///
+/// A call that runs over fails with [`DisplayError::ApplyTimedOut`]. Windows can't cancel it,
+/// so it keeps running on a background thread and may still change the display.
+pub fn set_apply_timeout(timeout: Option<Duration>) {
+    *APPLY_TIMEOUT.write().unwrap_or_else(std::sync::PoisonError::into_inner) = timeout;
+}
+
+#[must_use]
+pub fn apply_timeout() -> Option<Duration> {
+    *APPLY_TIMEOUT.read().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Runs `call` on a worker thread when an [`apply_timeout`] is set and gives up waiting for it
+/// once the timeout passes.
+fn with_timeout<T: Send + 'static>(call: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    let Some(timeout) = apply_timeout() else {
+        return call();
+    };
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // Nobody is listening any more if the caller timed out.
+        let _ = sender.send(call());
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            warn!(?timeout, "Apply call didn't return in time, leaving it running");
+            Err(DisplayError::ApplyTimedOut)
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => panic!("apply worker thread panicked"),
+    }
+}
+
+static PERSIST: AtomicBool = AtomicBool::new(true);
+
+/// Sets whether applied configurations are saved so Windows restores them after a reboot or
+/// when the same displays are connected again (the default).
+///
+/// This adds `SDC_SAVE_TO_DATABASE` to every `SetDisplayConfig` call that applies a supplied
+/// configuration, the only kind the flag is valid with, and `CDS_UPDATEREGISTRY` to the legacy
+/// path. DPI scaling is stored per monitor by Windows either way.
+pub fn set_persist(persist: bool) {
+    PERSIST.store(persist, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn persist() -> bool {
+    PERSIST.load(Ordering::Relaxed)
+}
+
+static SESSION_CHECK: AtomicBool = AtomicBool::new(true);
+
+/// Sets whether [`enumerate_displays`] refuses to run outside the interactive user session
+/// (the default), where Windows reports no displays or ignores changes.
+pub fn set_session_check(check: bool) {
+    SESSION_CHECK.store(check, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn session_check() -> bool {
+    SESSION_CHECK.load(Ordering::Relaxed)
+}
+
+static BROADCAST: AtomicBool = AtomicBool::new(true);
+
+/// Sets whether running apps are notified after a scaling change so they rescale right away
+/// (the default). Scripts making many rapid changes can turn it off and skip the broadcast.
+pub fn set_broadcast(broadcast: bool) {
+    BROADCAST.store(broadcast, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn broadcast() -> bool {
+    BROADCAST.load(Ordering::Relaxed)
+}
+
+/// How strictly `SetDisplayConfig` has to honour a requested mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApplyPolicy {
+    /// Apply exactly the requested mode or fail.
+    #[default]
+    Strict,
+    /// If the exact mode is refused, retry with `SDC_ALLOW_CHANGES` so the OS may adjust the
+    /// refresh rate or other displays' positions.
+    AllowChanges,
+}
+
+/// Which goes first when an apply changes both the scaling and the mode of a display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOrder {
+    /// Set the scaling, then switch the mode.
+    ScalingFirst,
+    /// Switch the mode, then set the scaling.
+    ResolutionFirst,
+}
+
+impl ApplyOrder {
+    /// The order that never shows the desktop at a larger scaling than either end: scaling
+    /// first when it goes down, as going from 4K at 200% to 1080p at 100% mode first would
+    /// show 1080p at 200% in between, and the mode first when it goes up.
+    #[must_use]
+    pub fn least_transient(from: i32, to: i32) -> Self {
+        if to < from { Self::ScalingFirst } else { Self::ResolutionFirst }
+    }
+}
+
+static APPLY_ORDER: RwLock<Option<ApplyOrder>> = RwLock::new(None);
+
+/// Sets the order of scaling and mode changes for the rest of the process, `None` for
+/// [`ApplyOrder::least_transient`] (the default).
+pub fn set_apply_order(order: Option<ApplyOrder>) {
+    *APPLY_ORDER.write().unwrap_or_else(std::sync::PoisonError::into_inner) = order;
+}
+
+#[must_use]
+pub fn apply_order() -> Option<ApplyOrder> {
+    *APPLY_ORDER.read().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// What a display looks like after applying a [`DisplayConfig`], compared to the request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyReport {
+    pub source_id: u32,
+    pub requested: DisplayConfig,
+    pub actual: DisplayConfig,
+    /// The strict call was refused and the OS was allowed to adjust the configuration.
+    pub allowed_changes: bool,
+    /// Other displays the OS moved or resized, as (before, after).
+    pub side_effects: Vec<(SourceMode, SourceMode)>,
+    /// Scaling that was stored but only takes effect after signing out; `actual` has the
+    /// scaling in effect until then. See [`scaling_pending`].
+    pub pending_scaling: Option<i32>,
+    /// The display's work area after the change, which moves with the resolution and scaling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub work_area: Option<Rect>,
+    pub timings: ApplyTimings,
+}
+
+/// How long each phase of an apply took; phases that didn't run are zero. In JSON, each is a
+/// number of milliseconds with an `_ms` suffix.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyTimings {
+    /// Enumerating the displays before the change.
+    #[serde(rename = "enumerate_ms", with = "millis")]
+    pub enumerate: Duration,
+    #[serde(rename = "resolution_ms", with = "millis")]
+    pub resolution: Duration,
+    #[serde(rename = "scaling_ms", with = "millis")]
+    pub scaling: Duration,
+    /// Enumerating again afterwards and comparing with the request.
+    #[serde(rename = "verification_ms", with = "millis")]
+    pub verification: Duration,
+}
+
+/// A [`Duration`] as whole milliseconds.
+pub(crate) mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+impl ApplyTimings {
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.enumerate + self.resolution + self.scaling + self.verification
+    }
+}
+
+impl fmt::Display for ApplyTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "enumerate {} ms, resolution {} ms, scaling {} ms, verification {} ms",
+            self.enumerate.as_millis(),
+            self.resolution.as_millis(),
+            self.scaling.as_millis(),
+            self.verification.as_millis()
+        )
+    }
+}
+
+impl ApplyReport {
+    /// Compares the displays before and after applying `requested` to `source_id`.
+    #[must_use]
+    pub fn compare(
+        source_id: u32,
+        requested: &DisplayConfig,
+        before: &[DisplayInfo],
+        after: &[DisplayInfo],
+        allowed_changes: bool,
+    ) -> Self {
+        let now = after.iter().find(|d| d.source_id == source_id);
+        let actual = now.map_or_else(
+            || DisplayConfig { width: 0, height: 0, scaling: 0 },
+            |d| DisplayConfig { width: d.width, height: d.height, scaling: d.scaling_current },
+        );
+        let side_effects = before
+            .iter()
+            .filter(|d| d.source_id != source_id)
+            .filter_map(|old| {
+                let new = after.iter().find(|d| d.source_id == old.source_id)?;
+                let (old, new) = (SourceMode::of(old), SourceMode::of(new));
+                (old != new).then_some((old, new))
+            })
+            .collect();
+
+        Self {
+            source_id,
+            requested: requested.clone(),
+            actual,
+            allowed_changes,
+            side_effects,
+            pending_scaling: None,
+            work_area: now.and_then(|d| d.work_area),
+            timings: ApplyTimings::default(),
+        }
+    }
+
+    /// Whether the result differs from the request in any way. A scaling waiting for a
+    /// sign-out counts as applied.
+    #[must_use]
+    pub fn adjusted(&self) -> bool {
+        let scaling = self.pending_scaling.unwrap_or(self.actual.scaling);
+        let actual = DisplayConfig { scaling, ..self.actual.clone() };
+        self.requested != actual || !self.side_effects.is_empty()
+    }
+}
+
+/// Applies `config` to `display`, changing only the settings that differ.
+///
+/// # Errors
+///
+/// Returns an error if the resolution or the DPI scaling can't be applied.
+pub fn apply_display_config(
+    display: &DisplayInfo,
+    config: &DisplayConfig,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut timings = ApplyTimings::default();
+    let result = apply_display_config_inner(display, config, ApplyPolicy::Strict, &mut timings).map(|_| ());
+    if differs(display, config) {
+        let source_id = display.source_id;
+        info!(source_id, %timings, "Apply finished");
+        apply_log::record(&ApplyLogEntry::new(display, config, result.as_ref().map(|()| None), started.elapsed()));
+    }
+    result
+}
+
+fn differs(display: &DisplayInfo, config: &DisplayConfig) -> bool {
+    (display.width, display.height, display.scaling_current) != (config.width, config.height, config.scaling)
+}
+
+/// The API that carried out a resolution change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApplyBackend {
+    /// `SetDisplayConfig`, the CCD API.
+    #[default]
+    Ccd,
+    /// `ChangeDisplaySettingsEx` on the display's GDI device.
+    Legacy,
+}
+
+impl fmt::Display for ApplyBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ccd => "SetDisplayConfig",
+            Self::Legacy => "ChangeDisplaySettingsEx",
+        })
+    }
+}
+
+/// Applies `config` to `display`, changing the resolution through `backend`.
+///
+/// Some drivers reject supplied CCD configurations for valid modes, so when `SetDisplayConfig`
+/// fails on the resolution the legacy API is tried as well. Returns the backend that changed
+/// the resolution.
+///
+/// # Errors
+///
+/// Returns an error if neither backend can apply the resolution, or the scaling can't be set.
+pub fn apply_display_config_via(
+    display: &DisplayInfo,
+    config: &DisplayConfig,
+    backend: ApplyBackend,
+) -> Result<ApplyBackend> {
+    let resolution_changed = display.width != config.width || display.height != config.height;
+    if backend == ApplyBackend::Ccd {
+        match apply_display_config(display, config) {
+            Err(DisplayError::SetDisplayConfig(code)) if resolution_changed => {
+                warn!(code, "SetDisplayConfig failed, falling back to ChangeDisplaySettingsEx");
+            }
+            result => return result.map(|()| ApplyBackend::Ccd),
+        }
+    }
+
+    if resolution_changed {
+        set_display_mode_legacy(display, config.width, config.height, None)?;
+    }
+    let now = DisplayInfo { width: config.width, height: config.height, ..display.clone() };
+    apply_display_config(&now, config)?;
+    Ok(ApplyBackend::Legacy)
+}
+
+/// Sets the resolution and optionally the refresh rate of `display` through
+/// `ChangeDisplaySettingsEx`, saving it to the registry unless [`set_persist`] turned that off.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::TargetUnavailable`] if the monitor is asleep, or
+/// [`DisplayError::ChangeDisplaySettings`] if the mode is rejected.
+pub fn set_display_mode_legacy(display: &DisplayInfo, width: u32, height: u32, refresh: Option<u32>) -> Result<()> {
+    if !display.target_available {
+        return Err(DisplayError::TargetUnavailable(display.source_id));
+    }
+    let device = display.gdi_device_name.clone();
+    info!(device = device.as_str(), width, height, ?refresh, "Changing mode through ChangeDisplaySettingsEx");
+    let persist = persist();
+    with_timeout(move || raw::change_display_settings(&device, width, height, refresh, persist))
+}
+
+/// Applies `config` to `display` under `policy`, then re-enumerates and reports what the
+/// displays actually ended up with.
+///
+/// # Errors
+///
+/// Returns an error if the change can't be applied even with the fallback, or the displays
+/// can't be enumerated.
+pub fn apply_display_config_with(
+    display: &DisplayInfo,
+    config: &DisplayConfig,
+    policy: ApplyPolicy,
+) -> Result<ApplyReport> {
+    let started = Instant::now();
+    let mut timings = ApplyTimings::default();
+    let result = enumerate_displays().and_then(|before| {
+        timings.enumerate = started.elapsed();
+        let allowed_changes = apply_display_config_inner(display, config, policy, &mut timings)?;
+        let verifying = Instant::now();
+        let after = enumerate_displays()?;
+        let mut report = ApplyReport::compare(display.source_id, config, &before, &after, allowed_changes);
+        if display.scaling_current != config.scaling && scaling_pending(display, config.scaling).unwrap_or(false) {
+            report.pending_scaling = Some(config.scaling);
+        }
+        timings.verification = verifying.elapsed();
+        report.timings = timings;
+        Ok(report)
+    });
+    let source_id = display.source_id;
+    info!(source_id, %timings, "Apply finished");
+    if differs(display, config) {
+        apply_log::record(&ApplyLogEntry::new(display, config, result.as_ref().map(Some), started.elapsed()));
+    }
+    result
+}
+
+/// Returns whether the `SDC_ALLOW_CHANGES` fallback was needed. The time spent on each change
+/// goes into `timings`.
+///
+/// If another program reconfigures the displays while this runs, it's tried once more on fresh
+/// data when the [`RetryPolicy`] allows retries.
+fn apply_display_config_inner(
+    display: &DisplayInfo,
+    config: &DisplayConfig,
+    policy: ApplyPolicy,
+    timings: &mut ApplyTimings,
+) -> Result<bool> {
+    match apply_guarded(display, config, policy, timings) {
+        Err(DisplayError::ConcurrentChange(source_id)) if retry_policy().retries > 0 => {
+            warn!(source_id, "Display configuration changed concurrently, retrying with fresh data");
+            let fresh = enumerate_displays()?
+                .into_iter()
+                .find(|d| d.source_id == source_id && d.device_path == display.device_path)
+                .ok_or(DisplayError::Stale(source_id))?;
+            apply_guarded(&fresh, config, policy, timings)
+        }
+        result => result,
+    }
+}
+
+/// [`apply_display_config_inner`] without the retry: plans the change and executes it. Every
+/// query that a change is built from must match the configuration seen before it, so a change
+/// made by another program in between fails with [`DisplayError::ConcurrentChange`] instead of
+/// mixing with ours.
+fn apply_guarded(
+    display: &DisplayInfo,
+    config: &DisplayConfig,
+    policy: ApplyPolicy,
+    timings: &mut ApplyTimings,
+) -> Result<bool> {
+    let changes = plan(std::slice::from_ref(display), &[Target::config(display.source_id, config)])?;
+    if changes.is_empty() {
+        debug!("Display configuration already matches target, skipping");
+        return Ok(false);
+    }
+    let report = execute_with(&changes, policy)?;
+    timings.resolution = report.timings.resolution;
+    timings.scaling = report.timings.scaling;
+    Ok(report.allowed_changes)
+}
+
+/// Fails with [`DisplayError::Stale`] unless `display` still describes the live configuration:
+/// the same monitor on its source, at the same resolution. Returns the fingerprint of that
+/// configuration (see [`raw::config_fingerprint`]).
+pub(crate) fn check_fresh(display: &DisplayInfo) -> Result<u64> {
+    let stale = || DisplayError::Stale(display.source_id);
+    let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    let path = raw::find_adapter_path(&paths, display.adapter_id, display.source_id).ok_or_else(stale)?;
+    let mode = raw::source_mode(path, &modes).ok_or_else(stale)?;
+    let device_path = raw::utf16_to_string(&raw::target_device_name(path)?.monitorDevicePath);
+    if device_path != display.device_path || (mode.width, mode.height) != (display.width, display.height) {
+        return Err(stale());
+    }
+    Ok(raw::config_fingerprint(&paths, &modes))
+}
+
+/// Fails with [`DisplayError::ConcurrentChange`] unless `paths` and `modes`, just queried to
+/// build a change to `display` on, have the `baseline` fingerprint.
+pub(crate) fn check_unchanged(display: &DisplayInfo, paths: &[PathInfo], modes: &[ModeInfo], baseline: u64) -> Result<()> {
+    if raw::config_fingerprint(paths, modes) != baseline {
+        let source_id = display.source_id;
+        warn!(source_id, "Display configuration changed since the apply started");
+        return Err(DisplayError::ConcurrentChange(source_id));
+    }
+    Ok(())
+}
+
+/// Checks whether `config` could be applied to `display` without changing anything.
+///
+/// The resolution goes through `SetDisplayConfig` with `SDC_VALIDATE`; scaling isn't part of
+/// that API, so it's checked against the values the display supports.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::TargetUnavailable`], [`DisplayError::UnsupportedScaling`],
+/// [`DisplayError::CustomScalingActive`] or [`DisplayError::ConfigRejected`] explaining why the OS would refuse the change, or an error if the configuration can't be queried.
+pub fn validate_display_config(display: &DisplayInfo, config: &DisplayConfig) -> Result<()> {
+    if !display.target_available {
+        return Err(DisplayError::TargetUnavailable(display.source_id));
+    }
+    if config.scaling != display.scaling_current && display.custom_scaling {
+        return Err(DisplayError::CustomScalingActive(display.source_id));
+    }
+    if config.scaling != display.scaling_current && !display.supported_scaling().contains(&config.scaling) {
+        return Err(DisplayError::UnsupportedScaling {
+            source_id: display.source_id,
+            scaling: config.scaling,
+        });
+    }
+
+    if display.width != config.width || display.height != config.height {
+        let (paths, modes) = resolution_config(display, config, None)?;
+        match set_display_config(&paths, &modes, SDC_VALIDATE | SDC_USE_SUPPLIED_DISPLAY_CONFIG) {
+            Err(DisplayError::SetDisplayConfig(code)) => return Err(DisplayError::ConfigRejected(code)),
+            result => result?,
+        }
+    }
+
+    let source_id = display.source_id;
+    debug!(source_id, "Configuration validated");
+    Ok(())
+}
+
+/// Checks the bits per color channel of `display` against `bits`.
+///
+/// Neither the CCD API nor `SET_ADVANCED_COLOR_STATE` (which only toggles HDR) controls the
+/// link bit depth on any Windows release, and the color mode overrides of Windows 11 24H2 take
+/// no bit depth either, so this only succeeds if the display already runs at `bits`.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::BitDepthNotControllable`] if `bits` differs from the current depth.
+pub fn set_bit_depth(display: &DisplayInfo, bits: u32) -> Result<()> {
+    if display.bits_per_color == bits {
+        let source_id = display.source_id;
+        debug!(source_id, bits, "Bit depth already matches");
+        return Ok(());
+    }
+    Err(DisplayError::BitDepthNotControllable {
+        source_id: display.source_id,
+        current: display.bits_per_color,
+        requested: bits,
+    })
+}
+
+/// Turns Dynamic Refresh Rate on or off for `display`.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DrrUnsupported`] if the OS or the display doesn't offer it, or an
+/// error if the OS rejects the change.
+pub fn set_dynamic_refresh(display: &DisplayInfo, enabled: bool) -> Result<()> {
+    if !display.drr_supported {
+        return Err(DisplayError::DrrUnsupported(display.source_id));
+    }
+    let source_id = display.source_id;
+    if display.drr_enabled == enabled {
+        debug!(source_id, enabled, "Dynamic refresh rate already set");
+        return Ok(());
+    }
+    info!(source_id, enabled, "Changing dynamic refresh rate");
+    let build = || {
+        let (mut paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS | QDC_VIRTUAL_REFRESH_RATE_AWARE)?;
+        paths
+            .iter_mut()
+            .find(|p| p.adapter_id() == display.adapter_id && p.source_id() == source_id)
+            .ok_or(DisplayError::DisplayNotFound(source_id))?
+            .set_boost_refresh_rate(enabled);
+        Ok((paths, modes))
+    };
+    set_with_retries(build, SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_VIRTUAL_REFRESH_RATE_AWARE).map_err(|err| match err {
+        // ERROR_NOT_SUPPORTED, ERROR_INVALID_PARAMETER: the driver refuses the flag for panels
+        // without variable refresh.
+        DisplayError::SetDisplayConfig(50 | 87) => DisplayError::DrrUnsupported(source_id),
+        err => err,
+    })
+}
+
+/// Sets the SDR content brightness of an HDR display, in nits.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::SdrWhiteLevelOutOfRange`] outside [`SDR_WHITE_LEVEL_RANGE`],
+/// [`DisplayError::AdvancedColorDisabled`] if HDR is off, or an error if the OS rejects it.
+pub fn set_sdr_white_level(display: &DisplayInfo, nits: u32) -> Result<()> {
+    if !SDR_WHITE_LEVEL_RANGE.contains(&nits) {
+        return Err(DisplayError::SdrWhiteLevelOutOfRange(nits));
+    }
+    if !display.hdr_enabled {
+        return Err(DisplayError::AdvancedColorDisabled(display.source_id));
+    }
+
+    let (paths, _modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    let path = find_path(&paths, display)?;
+    let old_nits = display.sdr_white_level;
+    info!(?old_nits, nits, "Changing SDR white level");
+    raw::set_sdr_white_level(path, nits_to_sdr_level(nits))
+}
+
+/// Converts the API's SDR white level (1000 = 80 nits) to nits.
+fn sdr_level_to_nits(level: u32) -> u32 {
+    level * 80 / 1000
+}
+
+fn nits_to_sdr_level(nits: u32) -> u32 {
+    nits * 1000 / 80
+}
+
+/// Exchanges resolution, position (and with it primary status) and scaling between two displays.
+///
+/// Resolutions and positions change in a single `SetDisplayConfig` call, the DPI values are
+/// swapped afterwards. The largest scaling a display takes goes with its resolution, so for
+/// displays of different resolutions it's only known once they've swapped; if the other's
+/// scaling is too large then, the resolutions and positions are put back.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::UnsupportedMode`] before changing anything if either display can't
+/// take the other's resolution, [`DisplayError::UnsupportedScaling`] if either can't take the
+/// other's scaling at its new resolution, or an error if applying fails.
+pub fn swap_display_configs(a: &DisplayInfo, b: &DisplayInfo) -> Result<()> {
+    for (display, other) in [(a, b), (b, a)] {
+        let same_size = (display.width, display.height) == (other.width, other.height);
+        if !same_size
+            && !supported_modes(display)?
+                .iter()
+                .any(|m| m.width == other.width && m.height == other.height)
+        {
+            return Err(DisplayError::UnsupportedMode {
+                source_id: display.source_id,
+                width: other.width,
+                height: other.height,
+            });
+        }
+        if same_size && other.scaling_current > display.scaling_max {
+            return Err(DisplayError::UnsupportedScaling {
+                source_id: display.source_id,
+                scaling: other.scaling_current,
+            });
+        }
+    }
+
+    let first = a.source_id;
+    let second = b.source_id;
+    info!(first, second, "Swapping display configurations");
+
+    apply_source_modes(&[
+        SourceMode {
+            source_id: a.source_id,
+            width: b.width,
+            height: b.height,
+            x: b.position_x,
+            y: b.position_y,
+            rotation: a.rotation,
+        },
+        SourceMode {
+            source_id: b.source_id,
+            width: a.width,
+            height: a.height,
+            x: a.position_x,
+            y: a.position_y,
+            rotation: b.rotation,
+        },
+    ])?;
+
+    if a.scaling_current != b.scaling_current {
+        // Each display now has the other's resolution, and the recommended and largest scaling
+        // that go with it.
+        let recommended_a = plan::settled_recommended(&mut plan::Windows, a, (b.width, b.height))?;
+        let recommended_b = plan::settled_recommended(&mut plan::Windows, b, (a.width, a.height))?;
+        let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+        for (display, other) in [(a, b), (b, a)] {
+            let (_, _, max) = get_display_scaling_from_path(find_path(&paths, display)?)?;
+            if other.scaling_current > max {
+                apply_source_modes(&[SourceMode::of(a), SourceMode::of(b)])?;
+                return Err(DisplayError::UnsupportedScaling {
+                    source_id: display.source_id,
+                    scaling: other.scaling_current,
+                });
+            }
+        }
+        let baseline = raw::config_fingerprint(&paths, &modes);
+        apply_display_scaling(a, b.scaling_current, recommended_a, baseline)?;
+        apply_display_scaling(b, a.scaling_current, recommended_b, baseline)?;
+    }
+
+    Ok(())
+}
+
+/// Sets resolution, position and rotation of several sources in one `SetDisplayConfig` call.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DesktopOutOfRange`] if a source reaches past the virtual desktop
+/// coordinates Windows accepts, or an error if a source isn't active or the OS rejects the
+/// configuration.
+pub fn apply_source_modes(sources: &[SourceMode]) -> Result<()> {
+    let layout: Vec<(u32, Rect)> = sources
+        .iter()
+        .map(|s| (s.source_id, Rect { x: s.x, y: s.y, width: s.width, height: s.height }))
+        .collect();
+    let excess = layout::desktop_excess(&layout);
+    if excess > 0 {
+        return Err(DisplayError::DesktopOutOfRange(excess));
+    }
+    for source in sources {
+        throttle::wait_for_apply(source.source_id);
+    }
+    set_with_retries(|| source_modes_config(sources), SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG)?;
+
+    info!("Source modes applied successfully");
+    Ok(())
+}
+
+/// One display's part of an [`apply_combined`] call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombinedChange {
+    /// Resize, move or rotate the source, optionally at a new refresh rate.
+    Mode { source: SourceMode, refresh: Option<u32> },
+    /// Turn the display off.
+    Disable(u32),
+}
+
+impl CombinedChange {
+    #[must_use]
+    pub fn source_id(&self) -> u32 {
+        match self {
+            Self::Mode { source, .. } => source.source_id,
+            Self::Disable(source_id) => *source_id,
+        }
+    }
+}
+
+/// Applies `changes` in a single `SetDisplayConfig` call after validating them together, so
+/// either all of them take effect or none. Windows may adjust the signal timing to fit new
+/// refresh rates, as with [`set_refresh_rate`].
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DisplayNotFound`] if a display isn't active, or an error if Windows
+/// rejects the combined configuration.
+pub fn apply_combined(changes: &[CombinedChange]) -> Result<()> {
+    for change in changes {
+        throttle::wait_for_apply(change.source_id());
+    }
+    let build = || combined_config(changes);
+    set_with_retries(build, SDC_VALIDATE | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES)?;
+    set_with_retries(build, SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES)?;
+
+    info!(count = changes.len(), "Combined change applied successfully");
+    Ok(())
+}
+
+fn combined_config(changes: &[CombinedChange]) -> Result<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    let sources: Vec<SourceMode> = changes
+        .iter()
+        .filter_map(|change| match change {
+            CombinedChange::Mode { source, .. } => Some(*source),
+            CombinedChange::Disable(_) => None,
+        })
+        .collect();
+    let (mut paths, mut modes) = source_modes_config(&sources)?;
+
+    for change in changes {
+        let source_id = change.source_id();
+        let index = paths
+            .iter()
+            .position(|p| p.source_id() == source_id)
+            .ok_or(DisplayError::DisplayNotFound(source_id))?;
+        match change {
+            CombinedChange::Mode { refresh: Some(hz), .. } => {
+                let rate = DISPLAYCONFIG_RATIONAL { Numerator: *hz, Denominator: 1 };
+                let mode = raw::target_mode_mut(&paths[index], &mut modes)
+                    .ok_or(DisplayError::DisplayNotFound(source_id))?;
+                mode.targetVideoSignalInfo.vSyncFreq = rate;
+                paths[index].targetInfo.refreshRate = rate;
+            }
+            CombinedChange::Mode { refresh: None, .. } => {}
+            CombinedChange::Disable(_) => paths[index].deactivate(),
+        }
+        debug!(?change, "Prepared combined change");
+    }
+
+    Ok((paths, modes))
+}
+
+/// The active paths and modes as queried, to put back with [`ConfigSnapshot::restore`] after
+/// changes that span several calls.
+#[derive(Clone)]
+pub struct ConfigSnapshot {
+    paths: Vec<PathInfo>,
+    modes: Vec<ModeInfo>,
+}
+
+impl ConfigSnapshot {
+    /// # Errors
+    ///
+    /// Returns an error if the configuration can't be queried.
+    pub fn take() -> Result<Self> {
+        let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+        Ok(Self { paths, modes })
+    }
+
+    /// Asks Windows whether it would accept the snapshot (`SDC_VALIDATE`), without applying it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::ConfigRejected`] if it wouldn't.
+    pub fn validate(&self) -> Result<()> {
+        match set_display_config(&self.paths, &self.modes, SDC_VALIDATE | SDC_USE_SUPPLIED_DISPLAY_CONFIG) {
+            Err(DisplayError::SetDisplayConfig(code)) => Err(DisplayError::ConfigRejected(code)),
+            result => result,
+        }
+    }
+
+    /// Applies the snapshot again, which also turns displays back on that were turned off since.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Windows rejects the configuration, e.g. because a display was unplugged.
+    pub fn restore(&self) -> Result<()> {
+        let build = || Ok((self.paths.clone(), self.modes.clone()));
+        set_with_retries(build, SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES)
+    }
+}
+
+/// Current configuration with `sources` resized, moved and rotated.
+fn source_modes_config(sources: &[SourceMode]) -> Result<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    let (mut paths, mut modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+
+    for source in sources {
+        let path = paths
+            .iter_mut()
+            .find(|path| path.source_id() == source.source_id)
+            .ok_or(DisplayError::DisplayNotFound(source.source_id))?;
+        path.targetInfo.rotation = source.rotation.into();
+        let mode = raw::source_mode_mut(path, &mut modes)
+            .ok_or(DisplayError::DisplayNotFound(source.source_id))?;
+        mode.width = source.width;
+        mode.height = source.height;
+        mode.position.x = source.x;
+        mode.position.y = source.y;
+        debug!(?source, "Prepared source mode");
+    }
+
+    Ok((paths, modes))
+}
+
+/// Builds a configuration with `build` and applies it, retrying transient failures according
+/// to the [`RetryPolicy`]. The configuration is rebuilt before every attempt because path and
+/// mode indices may shift after a driver reset.
+pub(crate) fn set_with_retries(
+    build: impl Fn() -> Result<(Vec<PathInfo>, Vec<ModeInfo>)>,
+    flags: SET_DISPLAY_CONFIG_FLAGS,
+) -> Result<()> {
+    let supplied_apply = SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG;
+    let flags = if persist() && flags.0 & supplied_apply.0 == supplied_apply.0 {
+        flags | SDC_SAVE_TO_DATABASE
+    } else {
+        flags
+    };
+    let policy = retry_policy();
+    let mut delay = policy.delay;
+    let mut attempt = 0;
+    loop {
+        let (paths, modes) = build()?;
+        match metrics::time(Phase::DisplayConfig, || with_timeout(move || set_display_config(&paths, &modes, flags))) {
+            Err(DisplayError::SetDisplayConfig(code)) if is_transient_error(code) && attempt < policy.retries => {
+                attempt += 1;
+                info!(code, attempt, ?delay, "SetDisplayConfig failed transiently, retrying");
+                thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The path of `display`, matched by adapter and source id.
+fn find_path<'a>(paths: &'a [PathInfo], display: &DisplayInfo) -> Result<&'a PathInfo> {
+    raw::find_adapter_path(paths, display.adapter_id, display.source_id)
+        .ok_or(DisplayError::DisplayNotFound(display.source_id))
+}
+
+/// Scaling from the CCD DPI query as (current, recommended, max). The values are steps
+/// relative to the recommended one, which sits `-min` steps above 100%; current is `None` if it
+/// would be below 100%. Max is capped to [`DPI_VALUES`], since only those can be set.
+fn get_display_scaling_from_path(path: &PathInfo) -> Result<(Option<i32>, i32, i32)> {
+    scaling_from_steps(raw::dpi_scale(path)?)
+}
+
+fn scaling_from_steps(dpi_info: raw::DpiScale) -> Result<(Option<i32>, i32, i32)> {
+    let rec_index = usize::try_from(dpi_info.min.unsigned_abs())?;
+    let max_index = rec_index
+        .saturating_add(usize::try_from(dpi_info.max.unsigned_abs())?)
+        .min(DPI_VALUES.len() - 1);
+    let current = rec_index
+        .checked_add_signed(isize::try_from(dpi_info.current)?)
+        .map(scaling_at_index);
+
+    Ok((current, scaling_at_index(rec_index), DPI_VALUES[max_index]))
+}
+
+/// The scaling `index` steps above 100%: [`DPI_VALUES`], continued past its end in steps of
+/// its last interval for the indexes some drivers report for very dense panels.
+#[must_use]
+pub fn scaling_at_index(index: usize) -> i32 {
+    let last = DPI_VALUES.len() - 1;
+    DPI_VALUES.get(index).copied().unwrap_or_else(|| {
+        let step = DPI_VALUES[last] - DPI_VALUES[last - 1];
+        let beyond = i32::try_from(index - last).unwrap_or(i32::MAX);
+        DPI_VALUES[last].saturating_add(step.saturating_mul(beyond))
+    })
+}
+
+/// The index of `scaling` for [`scaling_at_index`], `None` if it isn't one of those values.
+fn scaling_index(scaling: i32) -> Option<usize> {
+    if let Some(index) = DPI_VALUES.iter().position(|&v| v == scaling) {
+        return Some(index);
+    }
+    let last = DPI_VALUES.len() - 1;
+    let step = DPI_VALUES[last] - DPI_VALUES[last - 1];
+    let beyond = scaling - DPI_VALUES[last];
+    if beyond > 0 && beyond % step == 0 { last.checked_add(usize::try_from(beyond / step).ok()?) } else { None }
+}
+
+/// Steps from the `recommended` scaling to `scaling`, the value the DPI call takes; negative
+/// below it. `None` if either isn't a value of [`scaling_at_index`].
+#[must_use]
+pub fn scaling_steps(recommended: i32, scaling: i32) -> Option<i32> {
+    let to = i32::try_from(scaling_index(scaling)?).ok()?;
+    let from = i32::try_from(scaling_index(recommended)?).ok()?;
+    Some(to - from)
+}
+
+/// The desktop size of `display` and its recommended scaling, as Windows reports them now.
+pub(crate) fn live_scaling(display: &DisplayInfo) -> Result<((u32, u32), i32)> {
+    let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    let path = find_path(&paths, display)?;
+    let mode = raw::source_mode(path, &modes).ok_or(DisplayError::DisplayNotFound(display.source_id))?;
+    let (_, recommended, _) = get_display_scaling_from_path(path)?;
+    Ok(((mode.width, mode.height), recommended))
+}
+
+/// What [`display_scaling`] found out about one display.
+pub(crate) struct ScalingInfo {
+    pub(crate) current: i32,
+    pub(crate) custom: bool,
+    pub(crate) recommended: i32,
+    pub(crate) max: i32,
+    pub(crate) ccd: Option<i32>,
+    pub(crate) shell: Option<i32>,
+    /// The raw answers behind them, for [`load_trace`](crate::explain::load_trace).
+    pub(crate) steps: raw::DpiScale,
+    pub(crate) effective_dpi: Option<u32>,
+    pub(crate) source: ScalingSource,
+}
+
+/// Scaling of the display at `position`, from both the CCD query and the monitor's effective
+/// DPI.
+///
+/// A custom system-wide scaling doesn't show up in the CCD query, which keeps reporting the
+/// per-monitor value (or one outside the table). The monitor's effective DPI does, so a
+/// mismatch means an override is active and the effective value is reported instead. The same
+/// mismatch turns up after some in-place upgrades; the shell goes by the effective DPI then too.
+pub(crate) fn display_scaling(path: &PathInfo, position: POINTL) -> Result<ScalingInfo> {
+    let steps = raw::dpi_scale(path)?;
+    let (table, recommended, max) = scaling_from_steps(steps)?;
+    let effective_dpi = raw::effective_dpi_at(position.x, position.y);
+    let dpi = effective_dpi.as_ref().ok().copied();
+    let effective = effective_dpi.map(dpi_to_scaling);
+    let shell = effective.as_ref().ok().copied();
+    let (current, custom, source) = match (table, effective) {
+        (Some(table), Ok(effective)) if table == effective => (table, false, ScalingSource::Ccd),
+        (_, Ok(effective)) => {
+            let source_id = path.source_id();
+            debug!(source_id, ?table, effective, "Effective DPI doesn't match per-monitor scaling");
+            (effective, true, ScalingSource::EffectiveDpi)
+        }
+        (Some(table), Err(err)) => {
+            debug!(%err, "Can't query effective DPI");
+            (table, false, ScalingSource::Ccd)
+        }
+        (None, Err(err)) => {
+            // Nothing better to go on; showing the display beats failing the enumeration.
+            let source_id = path.source_id();
+            warn!(source_id, %err, "Current scaling unknown, reporting the recommended one");
+            (recommended, false, ScalingSource::Recommended)
+        }
+    };
+    Ok(ScalingInfo { current, custom, recommended, max, ccd: table, shell, steps, effective_dpi: dpi, source })
+}
+
+/// How long [`scaling_pending`] gives the effective DPI to catch up with a new scaling.
+const PENDING_SCALING_CHECKS: u32 = 5;
+const PENDING_SCALING_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether `scaling`, just set on `display`, is stored but not in effect: the CCD query
+/// reports it while `GetDpiForMonitor` still returns the old DPI. Some builds, older Windows 10
+/// ones in particular, only apply such a change at the next sign-in.
+///
+/// # Errors
+///
+/// Returns an error if the display can't be queried.
+pub fn scaling_pending(display: &DisplayInfo, scaling: i32) -> Result<bool> {
+    for attempt in 0..PENDING_SCALING_CHECKS {
+        if attempt > 0 {
+            thread::sleep(PENDING_SCALING_INTERVAL);
+        }
+        let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+        let path = find_path(&paths, display)?;
+        let (stored, _, _) = get_display_scaling_from_path(path)?;
+        if stored != Some(scaling) {
+            // Not stored either, so there's nothing a sign-out would change.
+            return Ok(false);
+        }
+        let position = raw::source_mode(path, &modes).map_or(POINTL::default(), |mode| mode.position);
+        if dpi_to_scaling(raw::effective_dpi_at(position.x, position.y)?) == scaling {
+            return Ok(false);
+        }
+    }
+    let source_id = display.source_id;
+    warn!(source_id, scaling, "Scaling change pending until sign-out");
+    Ok(true)
+}
+
+/// Converts a DPI value to a scaling percentage, 96 DPI being 100%.
+#[must_use]
+pub fn dpi_to_scaling(dpi: u32) -> i32 {
+    i32::try_from((u64::from(dpi) * 100 + 48) / 96).unwrap_or(i32::MAX)
+}
+
+/// Removes a custom system-wide scaling override so per-monitor scaling applies again. Windows
+/// picks the change up at the next sign-in.
+///
+/// # Errors
+///
+/// Returns an error if the setting can't be written.
+pub fn clear_custom_scaling() -> Result<()> {
+    raw::clear_custom_scaling()
+}
+
+/// Current configuration with the source mode of `display` resized to `config`, provided it's
+/// still the `baseline` one if given.
+fn resolution_config(
+    display: &DisplayInfo,
+    config: &DisplayConfig,
+    baseline: Option<u64>,
+) -> Result<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    let (paths, mut modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    if let Some(baseline) = baseline {
+        check_unchanged(display, &paths, &modes, baseline)?;
+    }
+
+    let path = find_path(&paths, display)?;
+    let mode = raw::source_mode_mut(path, &mut modes).ok_or(DisplayError::DisplayNotFound(display.source_id))?;
+    mode.width = config.width;
+    mode.height = config.height;
+
+    Ok((paths, modes))
+}
+
+/// Sets the scaling of `display` to `scaling`, counting the steps the DPI call takes from
+/// `recommended`: the recommended scaling the display has at the time, which a mode switch
+/// changes along with the resolution.
+pub(crate) fn apply_display_scaling(display: &DisplayInfo, scaling: i32, recommended: i32, baseline: u64) -> Result<()> {
+    let unsupported = || DisplayError::UnsupportedScaling { source_id: display.source_id, scaling };
+    if !DPI_VALUES.contains(&scaling) {
+        return Err(unsupported());
+    }
+    let old_scaling = display.scaling_current;
+    let new_scaling = scaling;
+    info!(old_scaling, new_scaling, recommended, "Changing DPI scaling");
+    set_scaling_steps(display, scaling_steps(recommended, scaling).ok_or_else(unsupported)?, baseline)
+}
+
+/// Sets the scaling of `display` to `steps` from its recommended scaling, provided the
+/// configuration is still the `baseline` one; the DPI call addresses the display by the path
+/// found in it.
+pub(crate) fn set_scaling_steps(display: &DisplayInfo, steps: i32, baseline: u64) -> Result<()> {
+    let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    check_unchanged(display, &paths, &modes, baseline)?;
+    let path = *find_path(&paths, display)?;
 
-        info!("DPI scaling changed successfully");
+    with_timeout(move || raw::set_dpi_scale(&path, steps))?;
+    info!("DPI scaling changed successfully");
+    if broadcast() {
+        raw::broadcast_dpi_change();
     }
 
     Ok(())
@@ -0,0 +1,225 @@
+use crate::display::{enumerate_displays, supported_modes, validate_display_config, DisplayConfig, DisplayInfo};
+use crate::link::{bandwidth_hint, link_info};
+use crate::raw;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Context for the report that doesn't decide whether the configuration applies.
+    Info,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "PASS",
+            Self::Fail => "FAIL",
+            Self::Info => "INFO",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// The configuration to diagnose; unset fields keep the display's current values and without
+/// `id` the primary display is checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DoctorRequest {
+    pub id: Option<u32>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub scaling: Option<i32>,
+    /// Refresh rate in Hz, only weighed against the link's bandwidth.
+    pub refresh: Option<u32>,
+}
+
+/// Outcome of [`diagnose`], printed one check per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Whether the requested configuration passed every check.
+    #[must_use]
+    pub fn applicable(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    fn add(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check { name, status, detail: detail.into() });
+    }
+
+    fn check(&mut self, name: &'static str, passed: bool, detail: impl Into<String>) {
+        let status = if passed { CheckStatus::Pass } else { CheckStatus::Fail };
+        self.add(name, status, detail);
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.status, check.name, check.detail)?;
+        }
+        if self.applicable() {
+            write!(f, "The configuration can be applied")
+        } else {
+            write!(f, "The configuration can't be applied")
+        }
+    }
+}
+
+/// Runs every check for `request`, stopping early only when there's no display to check.
+#[must_use]
+pub fn diagnose(request: &DoctorRequest) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    match raw::os_build() {
+        Ok(build) => report.add("OS build", CheckStatus::Info, build),
+        Err(err) => report.add("OS build", CheckStatus::Info, format!("unknown ({err})")),
+    }
+    let remote = raw::is_remote_session();
+    report.check(
+        "Session",
+        !remote,
+        if remote { "Remote Desktop, displays are virtualized by the client" } else { "local console" },
+    );
+    match raw::session_info() {
+        Ok(session) => report.check(
+            "Window station",
+            session.is_interactive(),
+            format!("{} in session {}", session.window_station, session.session_id),
+        ),
+        Err(err) => report.add("Window station", CheckStatus::Info, format!("unknown ({err})")),
+    }
+    let awareness = raw::thread_dpi_awareness();
+    report.add(
+        "DPI awareness",
+        CheckStatus::Info,
+        if awareness.is_per_monitor() {
+            awareness.to_string()
+        } else {
+            format!("{awareness}, cursor and window coordinates are scaled on displays with different scaling")
+        },
+    );
+
+    let displays = match enumerate_displays() {
+        Ok(displays) => {
+            report.check("Enumeration", true, format!("{} display(s) found", displays.len()));
+            displays
+        }
+        Err(err) => {
+            report.check("Enumeration", false, err.to_string());
+            return report;
+        }
+    };
+    let Some(display) = find_target(&displays, request.id) else {
+        let detail = request.id.map_or_else(
+            || "no active display".to_string(),
+            |id| format!("no display with id {id}, see `list`"),
+        );
+        report.check("Target display", false, detail);
+        return report;
+    };
+    report.check("Target display", true, display.to_string());
+    report.check(
+        "Target availability",
+        display.target_available,
+        if display.target_available { "available" } else { "unavailable (asleep or disconnected)" },
+    );
+
+    let config = DisplayConfig::builder()
+        .width(request.width)
+        .height(request.height)
+        .scaling(request.scaling)
+        .resolve(display);
+    check_mode(&mut report, display, &config);
+    check_link(&mut report, display, &config, request.refresh.unwrap_or(display.refresh_rate));
+    check_scaling(&mut report, display, &config);
+
+    // Scaling was covered above; this isolates what SetDisplayConfig thinks of the resolution.
+    let resolution = DisplayConfig { scaling: display.scaling_current, ..config };
+    match validate_display_config(display, &resolution) {
+        Ok(()) => report.check("SDC_VALIDATE", true, "accepted"),
+        Err(err) => report.check("SDC_VALIDATE", false, err.to_string()),
+    }
+
+    report
+}
+
+fn find_target(displays: &[DisplayInfo], id: Option<u32>) -> Option<&DisplayInfo> {
+    match id {
+        Some(id) => displays.iter().find(|d| d.source_id == id),
+        None => displays.iter().find(|d| d.primary).or_else(|| displays.first()),
+    }
+}
+
+fn check_mode(report: &mut DoctorReport, display: &DisplayInfo, config: &DisplayConfig) {
+    let resolution = format!("{}x{}", config.width, config.height);
+    match supported_modes(display) {
+        Ok(modes) => {
+            let supported = modes.iter().any(|m| m.width == config.width && m.height == config.height);
+            let detail = if supported {
+                format!("{resolution} is supported")
+            } else {
+                format!("{resolution} isn't among the {} modes the driver lists", modes.len())
+            };
+            report.check("Resolution", supported, detail);
+        }
+        Err(err) => report.check("Resolution", false, err.to_string()),
+    }
+}
+
+/// The adapter and the link's signal, and whether the mode seems to need more than the link
+/// carries now; a hint only, so never a failure.
+fn check_link(report: &mut DoctorReport, display: &DisplayInfo, config: &DisplayConfig, refresh: u32) {
+    let adapter = if display.adapter_name.is_empty() { "unknown" } else { &display.adapter_name };
+    report.add("Adapter", CheckStatus::Info, adapter);
+    let link = match link_info(display) {
+        Ok(link) => link,
+        Err(err) => return report.add("Link", CheckStatus::Info, format!("unknown ({err})")),
+    };
+    report.add("Link", CheckStatus::Info, link.to_string());
+    let detail = bandwidth_hint(&link, config.width, config.height, refresh).unwrap_or_else(|| {
+        format!("{}x{} @ {refresh}Hz fits the current signal", config.width, config.height)
+    });
+    report.add("Link bandwidth", CheckStatus::Info, detail);
+}
+
+fn check_scaling(report: &mut DoctorReport, display: &DisplayInfo, config: &DisplayConfig) {
+    let unchanged = config.scaling == display.scaling_current;
+    let supported = display.supported_scaling();
+    let in_range = unchanged || supported.contains(&config.scaling);
+    let range = match (supported.first(), supported.last()) {
+        (Some(min), Some(max)) => format!("{min}%-{max}%"),
+        _ => "none".to_string(),
+    };
+    report.check("Scaling", in_range, format!("{}% (supported {range})", config.scaling));
+
+    let detail = if display.custom_scaling {
+        format!("active, effective scaling {}%", display.scaling_current)
+    } else {
+        "none".to_string()
+    };
+    report.check("Custom scaling override", unchanged || !display.custom_scaling, detail);
+
+    let detail = match (display.scaling_ccd, display.scaling_shell) {
+        (Some(ccd), Some(shell)) if ccd != shell => {
+            format!("the shell uses {shell}% but the CCD query reports {ccd}%; changes may not show until sign-out")
+        }
+        (Some(ccd), Some(_)) => format!("both report {ccd}%"),
+        (ccd, shell) => format!(
+            "CCD {}, shell {}",
+            ccd.map_or_else(|| "unknown".to_string(), |v| format!("{v}%")),
+            shell.map_or_else(|| "unknown".to_string(), |v| format!("{v}%")),
+        ),
+    };
+    report.check("Scaling sources", unchanged || display.scaling_mismatch().is_none(), detail);
+}
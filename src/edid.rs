@@ -0,0 +1,85 @@
+//! Monitor details only the EDID carries: serial number, model name and date of manufacture.
+//!
+//! Reading the EDID means a registry lookup per monitor, so [`enumerate_displays`] leaves these
+//! fields empty; call [`load_edid`] on the displays that need them.
+//!
+//! [`enumerate_displays`]: crate::display::enumerate_displays
+
+use crate::display::DisplayInfo;
+use crate::raw;
+use tracing::debug;
+
+const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+const BLOCK_LEN: usize = 128;
+/// Offsets of the four 18-byte descriptors in the base block.
+const DESCRIPTORS: [usize; 4] = [54, 72, 90, 108];
+const SERIAL_TAG: u8 = 0xff;
+const NAME_TAG: u8 = 0xfc;
+
+/// What [`parse_edid`] found; each field is `None` if the EDID doesn't carry it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdidInfo {
+    /// The serial number descriptor, or the numeric serial from the header if there's none.
+    pub serial_number: Option<String>,
+    /// The display name descriptor, e.g. `DELL U2720Q`.
+    pub model_name: Option<String>,
+    /// Week of the year, 1-54; not every manufacturer fills it in.
+    pub manufacture_week: Option<u8>,
+    pub manufacture_year: Option<u16>,
+}
+
+/// Parses the base block of `edid`. A blob that's too short, lacks the fixed header or fails
+/// its checksum yields an empty [`EdidInfo`] rather than an error.
+#[must_use]
+pub fn parse_edid(edid: &[u8]) -> EdidInfo {
+    let Some(block) = edid.get(..BLOCK_LEN) else {
+        return EdidInfo::default();
+    };
+    if block[..HEADER.len()] != HEADER || block.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+        return EdidInfo::default();
+    }
+
+    let descriptor = |tag| {
+        DESCRIPTORS.iter().map(|&offset| &block[offset..offset + 18]).find_map(|d| {
+            // Display descriptors start with a zero pixel clock, then a reserved zero byte.
+            (d[..3] == [0, 0, 0] && d[3] == tag).then(|| descriptor_text(&d[5..])).flatten()
+        })
+    };
+    let numeric_serial = u32::from_le_bytes([block[12], block[13], block[14], block[15]]);
+    let (week, year) = (block[16], block[17]);
+    EdidInfo {
+        serial_number: descriptor(SERIAL_TAG).or_else(|| (numeric_serial != 0).then(|| numeric_serial.to_string())),
+        model_name: descriptor(NAME_TAG),
+        // 0xff marks `year` as the model year rather than the date of manufacture.
+        manufacture_week: (1..=54).contains(&week).then_some(week),
+        manufacture_year: (week != 0xff && year != 0).then(|| 1990 + u16::from(year)),
+    }
+}
+
+/// The text of a descriptor: up to 13 ASCII characters ended by a line feed and padded with
+/// spaces. `None` if it's empty or not printable.
+fn descriptor_text(bytes: &[u8]) -> Option<String> {
+    let text = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    if !text.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        return None;
+    }
+    let text = String::from_utf8_lossy(text).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Fills in the EDID fields of `display` from the EDID Windows stored for its monitor. A
+/// monitor without a readable EDID keeps them `None`.
+pub fn load_edid(display: &mut DisplayInfo) {
+    let info = match raw::monitor_edid(&display.device_path) {
+        Ok(edid) => parse_edid(&edid),
+        Err(err) => {
+            let source_id = display.source_id;
+            debug!(source_id, %err, "No EDID");
+            EdidInfo::default()
+        }
+    };
+    display.serial_number = info.serial_number;
+    display.model_name = info.model_name;
+    display.manufacture_week = info.manufacture_week;
+    display.manufacture_year = info.manufacture_year;
+}
@@ -0,0 +1,162 @@
+//! `list --explain`: the raw values behind what [`enumerate_displays`] reports, for tracking down
+//! why the same monitor gets another scaling on another machine.
+//!
+//! Like the EDID fields, the trace isn't part of enumeration; [`load_trace`] queries the
+//! display's path again through the same code and fills in [`DisplayInfo::debug`].
+//!
+//! [`enumerate_displays`]: crate::display::enumerate_displays
+
+use crate::display::{display_scaling, DisplayError, DisplayInfo, Result};
+use crate::raw::{self, query_display_config, DpiScale};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::debug;
+use windows::Win32::Devices::Display::QDC_ONLY_ACTIVE_PATHS;
+use windows::Win32::Foundation::POINTL;
+
+/// `friendlyNameFromEdid` and `friendlyNameForced`, see [`raw::target_name_flags`].
+const NAME_FROM_EDID: u32 = 1;
+const NAME_FORCED: u32 = 2;
+
+/// Which value [`DisplayInfo::scaling_current`] was taken from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingSource {
+    /// The CCD DPI query, agreeing with the effective DPI or without one to compare.
+    #[default]
+    Ccd,
+    /// The monitor's effective DPI, which differs from the CCD query under custom scaling.
+    EffectiveDpi,
+    /// Neither query gave a value, so the recommended scaling stands in.
+    Recommended,
+}
+
+impl fmt::Display for ScalingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ccd => "the CCD DPI query",
+            Self::EffectiveDpi => "the effective DPI",
+            Self::Recommended => "the recommended scaling, nothing else was reported",
+        })
+    }
+}
+
+/// Where [`DisplayInfo::friendly_name`] came from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameSource {
+    /// The display name descriptor of the monitor's EDID.
+    Edid,
+    /// An override, e.g. from the monitor's INF.
+    Forced,
+    /// The driver's generic name, such as `Generic PnP Monitor`.
+    Driver,
+    /// No name; the display is shown by its number.
+    #[default]
+    None,
+}
+
+impl NameSource {
+    /// The source of `name`, given the flags of its [`raw::target_device_name`] answer.
+    #[must_use]
+    pub fn of(flags: u32, name: &str) -> Self {
+        if name.is_empty() {
+            Self::None
+        } else if flags & NAME_FORCED != 0 {
+            Self::Forced
+        } else if flags & NAME_FROM_EDID != 0 {
+            Self::Edid
+        } else {
+            Self::Driver
+        }
+    }
+}
+
+impl fmt::Display for NameSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Edid => "the EDID",
+            Self::Forced => "an override",
+            Self::Driver => "the driver",
+            Self::None => "none, shown by number",
+        })
+    }
+}
+
+/// The `DpiScaleGet` answer: steps relative to the recommended scaling.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DpiSteps {
+    pub min: i32,
+    pub current: i32,
+    pub max: i32,
+}
+
+impl From<DpiScale> for DpiSteps {
+    fn from(scale: DpiScale) -> Self {
+        Self { min: scale.min, current: scale.current, max: scale.max }
+    }
+}
+
+/// How enumeration arrived at the values of one display; `debug` in the JSON of
+/// `list --explain`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnumerationTrace {
+    pub dpi_steps: DpiSteps,
+    /// From `GetDpiForMonitor`, `None` if that failed.
+    pub effective_dpi: Option<u32>,
+    pub scaling_source: ScalingSource,
+    /// Indexes into the mode array of the `QueryDisplayConfig` call, `None` without a mode.
+    pub source_mode_index: Option<usize>,
+    pub target_mode_index: Option<usize>,
+    /// Raw `DISPLAYCONFIG_PATH_*` flags.
+    pub path_flags: u32,
+    /// The driver supports virtual mode on the path. Queries here aren't virtual mode aware,
+    /// so the modes are the plain source and target ones either way.
+    pub virtual_mode: bool,
+    pub name_source: NameSource,
+}
+
+/// Indented lines for the table output, one per aspect.
+impl fmt::Display for EnumerationTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let DpiSteps { min, current, max } = self.dpi_steps;
+        let dpi = self.effective_dpi.map_or_else(|| "unknown".to_string(), |dpi| dpi.to_string());
+        writeln!(f, "  scaling: from {} (DPI steps min {min}, current {current:+}, max {max}; effective DPI {dpi})", self.scaling_source)?;
+        let index = |i: Option<usize>| i.map_or_else(|| "none".to_string(), |i| format!("#{i}"));
+        writeln!(f, "  modes: source {}, target {}", index(self.source_mode_index), index(self.target_mode_index))?;
+        let virtual_mode = if self.virtual_mode { "supported" } else { "not supported" };
+        writeln!(f, "  path flags: {:#010x}, virtual mode {virtual_mode} (not used)", self.path_flags)?;
+        write!(f, "  name: from {}", self.name_source)
+    }
+}
+
+/// Fills in [`DisplayInfo::debug`]. A display whose path can't be queried again, e.g. because
+/// it was just disconnected, keeps it `None`.
+pub fn load_trace(display: &mut DisplayInfo) {
+    match trace(display) {
+        Ok(trace) => display.debug = Some(trace),
+        Err(err) => {
+            let source_id = display.source_id;
+            debug!(source_id, %err, "No enumeration trace");
+        }
+    }
+}
+
+fn trace(display: &DisplayInfo) -> Result<EnumerationTrace> {
+    let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    let path = raw::find_adapter_path(&paths, display.adapter_id, display.source_id)
+        .ok_or(DisplayError::DisplayNotFound(display.source_id))?;
+    let position = raw::source_mode(path, &modes).map_or(POINTL::default(), |mode| mode.position);
+    let scaling = display_scaling(path, position)?;
+    let target_name = raw::target_device_name(path)?;
+    Ok(EnumerationTrace {
+        dpi_steps: scaling.steps.into(),
+        effective_dpi: scaling.effective_dpi,
+        scaling_source: scaling.source,
+        source_mode_index: path.source_mode_index(),
+        target_mode_index: path.target_mode_index(),
+        path_flags: path.flags(),
+        virtual_mode: path.supports_virtual_mode(),
+        name_source: NameSource::of(raw::target_name_flags(&target_name), &display.friendly_name),
+    })
+}
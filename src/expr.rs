@@ -0,0 +1,427 @@
+//! Filter expressions such as `connector=hdmi && width<2560`, used by `--where` and by the
+//! `where` key of rule `match` tables.
+//!
+//! An expression is one or more conditions joined by `&&`, all of which must hold. A condition
+//! is a field, an operator and a value; the yes/no fields also stand alone for `=true`:
+//!
+//! | Field | Operators | Value |
+//! |---|---|---|
+//! | `name`, `vendor` | `=`, `!=`, `~` (contains) | text, case-insensitive |
+//! | `connector` | `=`, `!=` | `hdmi`, `dp`, `internal`, ... |
+//! | `id`, `width`, `height`, `scaling`, `refresh` | `=`, `!=`, `<`, `<=`, `>`, `>=` | number |
+//! | `internal`, `external`, `active` | `=`, `!=` | `true` or `false` |
+//!
+//! Values with spaces or operator characters go in quotes: `name~"LG TV"`.
+
+use crate::display::{Connector, DisplayInfo};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+const CONNECTORS: [Connector; 8] = [
+    Connector::Vga,
+    Connector::Dvi,
+    Connector::Hdmi,
+    Connector::DisplayPort,
+    Connector::Internal,
+    Connector::Miracast,
+    Connector::Indirect,
+    Connector::Other,
+];
+
+/// A parsed filter expression, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterExpr {
+    source: String,
+    conditions: Vec<Condition>,
+}
+
+impl FilterExpr {
+    /// Whether every condition holds for `display`.
+    #[must_use]
+    pub fn matches(&self, display: &DisplayInfo) -> bool {
+        self.conditions.iter().all(|c| c.holds(display))
+    }
+}
+
+/// The expression as written.
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { input, tokens: lex(input)?, next: 0 };
+        let mut conditions = vec![parser.condition()?];
+        while let Some(token) = parser.advance() {
+            if token.kind != TokenKind::And {
+                return Err(parser.error(&token.span, format!("expected `&&` before {}", token.kind)));
+            }
+            conditions.push(parser.condition()?);
+        }
+        Ok(Self { source: input.trim().to_string(), conditions })
+    }
+}
+
+impl<'de> Deserialize<'de> for FilterExpr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for FilterExpr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+/// Why an expression doesn't parse, with the offending part of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub input: String,
+    /// Byte range of the offending token; empty at the end of the input if something is missing.
+    pub span: Range<usize>,
+}
+
+/// The message, then the input with the offending token underlined.
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.input[..self.span.start].chars().count();
+        let width = self.input[self.span.clone()].chars().count().max(1);
+        write!(f, "{}\n  {}\n  {}{}", self.message, self.input, " ".repeat(column), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Vendor,
+    Connector,
+    Id,
+    Width,
+    Height,
+    Scaling,
+    Refresh,
+    Internal,
+    External,
+    Active,
+}
+
+impl Field {
+    const ALL: [Self; 11] = [
+        Self::Name,
+        Self::Vendor,
+        Self::Connector,
+        Self::Id,
+        Self::Width,
+        Self::Height,
+        Self::Scaling,
+        Self::Refresh,
+        Self::Internal,
+        Self::External,
+        Self::Active,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Vendor => "vendor",
+            Self::Connector => "connector",
+            Self::Id => "id",
+            Self::Width => "width",
+            Self::Height => "height",
+            Self::Scaling => "scaling",
+            Self::Refresh => "refresh",
+            Self::Internal => "internal",
+            Self::External => "external",
+            Self::Active => "active",
+        }
+    }
+
+    fn operators(self) -> &'static [Op] {
+        match self {
+            Self::Name | Self::Vendor => &[Op::Eq, Op::Ne, Op::Contains],
+            Self::Connector | Self::Internal | Self::External | Self::Active => &[Op::Eq, Op::Ne],
+            Self::Id | Self::Width | Self::Height | Self::Scaling | Self::Refresh => {
+                &[Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge]
+            }
+        }
+    }
+
+    fn is_flag(self) -> bool {
+        matches!(self, Self::Internal | Self::External | Self::Active)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Contains => "~",
+        }
+    }
+
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Ne => ordering.is_ne(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+            Self::Contains => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Text(String),
+    Connector(Connector),
+    Number(i64),
+    Flag(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Condition {
+    field: Field,
+    op: Op,
+    value: Value,
+}
+
+impl Condition {
+    fn holds(&self, display: &DisplayInfo) -> bool {
+        match &self.value {
+            Value::Text(text) => {
+                let actual = match self.field {
+                    Field::Vendor => display.edid_vendor(),
+                    _ => display.friendly_name.clone(),
+                }
+                .to_lowercase();
+                match self.op {
+                    Op::Contains => actual.contains(text.as_str()),
+                    op => op.accepts(actual.as_str().cmp(text)),
+                }
+            }
+            Value::Connector(connector) => (display.connector == *connector) == (self.op == Op::Eq),
+            Value::Number(number) => {
+                let actual = match self.field {
+                    Field::Id => i64::from(display.source_id),
+                    Field::Width => i64::from(display.width),
+                    Field::Height => i64::from(display.height),
+                    Field::Refresh => i64::from(display.refresh_rate),
+                    _ => i64::from(display.scaling_current),
+                };
+                self.op.accepts(actual.cmp(number))
+            }
+            Value::Flag(flag) => {
+                let actual = match self.field {
+                    Field::Internal => display.internal,
+                    Field::External => !display.internal,
+                    _ => display.active,
+                };
+                (actual == *flag) == (self.op == Op::Eq)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Word(String),
+    /// A value in quotes, never a field name.
+    Quoted(String),
+    Op(Op),
+    And,
+}
+
+/// Names the token in error messages.
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) => write!(f, "`{word}`"),
+            Self::Quoted(text) => write!(f, "\"{text}\""),
+            Self::Op(op) => write!(f, "`{}`", op.symbol()),
+            Self::And => f.write_str("`&&`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+fn error(input: &str, span: Range<usize>, message: impl Into<String>) -> ParseError {
+    ParseError { message: message.into(), input: input.to_string(), span }
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !"&|=!<>~\"'".contains(c)
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let followed_by = |chars: &mut std::iter::Peekable<std::str::CharIndices>, next: char| {
+            chars.next_if(|&(_, c)| c == next).is_some()
+        };
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '&' if followed_by(&mut chars, '&') => TokenKind::And,
+            '&' => return Err(error(input, start..start + 1, "expected `&&`")),
+            '|' => return Err(error(input, start..start + 1, "only `&&` is supported between conditions")),
+            '=' => {
+                // `==` is accepted as a synonym.
+                followed_by(&mut chars, '=');
+                TokenKind::Op(Op::Eq)
+            }
+            '!' if followed_by(&mut chars, '=') => TokenKind::Op(Op::Ne),
+            '!' => return Err(error(input, start..start + 1, "expected `!=`")),
+            '<' if followed_by(&mut chars, '=') => TokenKind::Op(Op::Le),
+            '<' => TokenKind::Op(Op::Lt),
+            '>' if followed_by(&mut chars, '=') => TokenKind::Op(Op::Ge),
+            '>' => TokenKind::Op(Op::Gt),
+            '~' => TokenKind::Op(Op::Contains),
+            '"' | '\'' => {
+                let text: String = chars.by_ref().map_while(|(_, q)| (q != c).then_some(q)).collect();
+                let end = start + c.len_utf8() + text.len();
+                if end >= input.len() || !input[end..].starts_with(c) {
+                    return Err(error(input, start..input.len(), "unterminated quote"));
+                }
+                tokens.push(Token { kind: TokenKind::Quoted(text), span: start..end + c.len_utf8() });
+                continue;
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|&(_, c)| is_word_char(c)) {
+                    end = i + c.len_utf8();
+                }
+                TokenKind::Word(input[start..end].to_string())
+            }
+        };
+        let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+        tokens.push(Token { kind, span: start..end });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.next).map(|t| &t.kind)
+    }
+
+    fn error(&self, span: &Range<usize>, message: impl Into<String>) -> ParseError {
+        error(self.input, span.clone(), message)
+    }
+
+    /// An empty span after the last token, for things missing at the end.
+    fn end(&self) -> Range<usize> {
+        let end = self.input.trim_end().len();
+        end..end
+    }
+
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let Some(token) = self.advance() else {
+            return Err(self.error(&self.end(), "expected a condition such as `width<2560`"));
+        };
+        let TokenKind::Word(name) = &token.kind else {
+            return Err(self.error(&token.span, format!("expected a field name, found {}", token.kind)));
+        };
+        let Some(field) = Field::ALL.into_iter().find(|f| f.name().eq_ignore_ascii_case(name)) else {
+            let fields: Vec<&str> = Field::ALL.iter().map(|f| f.name()).collect();
+            return Err(self.error(&token.span, format!("unknown field `{name}`, expected one of {}", fields.join(", "))));
+        };
+        if field.is_flag() && matches!(self.peek(), None | Some(TokenKind::And)) {
+            return Ok(Condition { field, op: Op::Eq, value: Value::Flag(true) });
+        }
+
+        let op = match self.advance() {
+            Some(Token { kind: TokenKind::Op(op), span }) => {
+                if !field.operators().contains(&op) {
+                    let allowed: Vec<&str> = field.operators().iter().map(|op| op.symbol()).collect();
+                    let message = format!("`{}` doesn't apply to `{}`, use {}", op.symbol(), field.name(), allowed.join(" "));
+                    return Err(self.error(&span, message));
+                }
+                op
+            }
+            Some(other) => {
+                return Err(self.error(&other.span, format!("expected an operator after `{}`, found {}", field.name(), other.kind)));
+            }
+            None => return Err(self.error(&self.end(), format!("expected an operator after `{}`", field.name()))),
+        };
+
+        let (text, span) = match self.advance() {
+            Some(Token { kind: TokenKind::Word(text) | TokenKind::Quoted(text), span }) => (text, span),
+            Some(other) => {
+                return Err(self.error(&other.span, format!("expected a value after `{}`, found {}", op.symbol(), other.kind)));
+            }
+            None => return Err(self.error(&self.end(), format!("expected a value after `{}`", op.symbol()))),
+        };
+        let value = self.value(field, &text, &span)?;
+        Ok(Condition { field, op, value })
+    }
+
+    fn value(&self, field: Field, text: &str, span: &Range<usize>) -> Result<Value, ParseError> {
+        match field {
+            Field::Name | Field::Vendor => Ok(Value::Text(text.to_lowercase())),
+            Field::Connector => CONNECTORS
+                .into_iter()
+                .find(|c| c.to_string().eq_ignore_ascii_case(text))
+                .map(Value::Connector)
+                .ok_or_else(|| {
+                    let names: Vec<String> = CONNECTORS.iter().map(ToString::to_string).collect();
+                    self.error(span, format!("unknown connector `{text}`, expected one of {}", names.join(", ")))
+                }),
+            Field::Internal | Field::External | Field::Active => match text.to_ascii_lowercase().as_str() {
+                "true" | "yes" => Ok(Value::Flag(true)),
+                "false" | "no" => Ok(Value::Flag(false)),
+                _ => Err(self.error(span, format!("expected `true` or `false` for `{}`", field.name()))),
+            },
+            Field::Id | Field::Width | Field::Height | Field::Scaling | Field::Refresh => text
+                .trim_end_matches('%')
+                .parse::<u32>()
+                .map(|n| Value::Number(i64::from(n)))
+                .map_err(|_| self.error(span, format!("expected a number for `{}`, found `{text}`", field.name()))),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! A C-compatible surface for calling the library from other languages, e.g. C# through
+//! P/Invoke, declared in `include/display_tuner.h`. Requests and results are JSON.
+//!
+//! Build the DLL with `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+//!
+//! # Return codes
+//!
+//! Every function returns the exit code the CLI would: [`DT_OK`], [`DT_ERROR`],
+//! [`DT_ADJUSTED`], [`DT_TIMED_OUT`] or [`DT_NO_DISPLAYS`]. After a failure, [`dt_last_error`] has the message.
+//!
+//! # Memory
+//!
+//! - Strings passed in are borrowed for the duration of the call and stay the caller's.
+//! - Strings handed out through an `out` pointer belong to the caller, who releases each one
+//!   with [`dt_free_string`], exactly once. They come from Rust's allocator, so `free`,
+//!   `CoTaskMemFree` or `Marshal.FreeHGlobal` must not be used on them. On failure nothing is
+//!   written to `out`.
+//!
+//! No function unwinds into the caller: a panic is caught and reported as [`DT_ERROR`].
+
+use crate::display::{apply_display_config, apply_display_config_with, enumerate_displays, ApplyPolicy, DisplayConfig, DisplayError, DisplayInfo, Result};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+pub const DT_OK: i32 = 0;
+pub const DT_ERROR: i32 = 1;
+/// Windows applied the change with adjustments, only with `allow_changes`.
+pub const DT_ADJUSTED: i32 = 2;
+/// The apply call didn't return in time and may still complete in the background.
+pub const DT_TIMED_OUT: i32 = 3;
+/// Windows reports no active display, see [`DisplayError::NoDisplays`].
+pub const DT_NO_DISPLAYS: i32 = 4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The request [`dt_set`] takes, e.g. `{"id": 1, "width": 2560, "height": 1440}`. Unset fields
+/// keep their current value; without `id` every available display is changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SetRequest {
+    #[serde(default)]
+    pub id: Option<u32>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Whole percent, a fractional percent or a ratio, see [`crate::display::scaling_percent`].
+    #[serde(default, deserialize_with = "crate::display::deserialize_optional_scaling")]
+    pub scaling: Option<i32>,
+    /// Let Windows adjust a mode it refuses as-is, see [`ApplyPolicy::AllowChanges`].
+    #[serde(default)]
+    pub allow_changes: bool,
+}
+
+impl SetRequest {
+    /// Applies the request; returns whether Windows adjusted anything.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::DisplayNotFound`] if `id` isn't connected, or an error if a
+    /// change can't be applied.
+    pub fn apply(&self) -> Result<bool> {
+        let displays = enumerate_displays()?;
+        // Without an id, monitors that are asleep are skipped rather than failing the request.
+        let targets: Vec<&DisplayInfo> = displays
+            .iter()
+            .filter(|d| self.id.map_or(d.target_available, |id| d.source_id == id))
+            .collect();
+        if let (Some(id), true) = (self.id, targets.is_empty()) {
+            return Err(DisplayError::DisplayNotFound(id));
+        }
+        let mut adjusted = false;
+        for display in targets {
+            let config = DisplayConfig::builder()
+                .width(self.width)
+                .height(self.height)
+                .scaling(self.scaling)
+                .resolve(display);
+            if self.allow_changes {
+                adjusted |= apply_display_config_with(display, &config, ApplyPolicy::AllowChanges)?.adjusted();
+            } else {
+                apply_display_config(display, &config)?;
+            }
+        }
+        Ok(adjusted)
+    }
+}
+
+/// Writes the displays as a JSON array of `DisplayInfo` objects (the shape of `list --format
+/// json`) to `*out_json`.
+///
+/// # Safety
+///
+/// `out_json` must be null or valid for writing a pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dt_enumerate(out_json: *mut *mut c_char) -> i32 {
+    guard(|| {
+        if out_json.is_null() {
+            return Err(DisplayError::FfiArgument("out_json is null"));
+        }
+        let json = serde_json::to_string(&enumerate_displays()?)?;
+        // SAFETY: checked for null above; the caller guarantees it's writable.
+        unsafe { *out_json = into_c_string(json) };
+        Ok(DT_OK)
+    })
+}
+
+/// Applies a [`SetRequest`] given as a NUL-terminated UTF-8 JSON string.
+///
+/// # Safety
+///
+/// `json_request` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dt_set(json_request: *const c_char) -> i32 {
+    guard(|| {
+        if json_request.is_null() {
+            return Err(DisplayError::FfiArgument("json_request is null"));
+        }
+        // SAFETY: checked for null above; the caller guarantees the terminating NUL.
+        let text = unsafe { CStr::from_ptr(json_request) }
+            .to_str()
+            .map_err(|_| DisplayError::FfiArgument("json_request isn't UTF-8"))?;
+        let request: SetRequest = serde_json::from_str(text)?;
+        Ok(if request.apply()? { DT_ADJUSTED } else { DT_OK })
+    })
+}
+
+/// Writes the message of the last failure on this thread to `*out_message`; [`DT_ERROR`]
+/// without writing anything if nothing failed yet.
+///
+/// # Safety
+///
+/// `out_message` must be null or valid for writing a pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dt_last_error(out_message: *mut *mut c_char) -> i32 {
+    catch_unwind(|| {
+        let Some(message) = LAST_ERROR.with_borrow(Clone::clone) else {
+            return DT_ERROR;
+        };
+        if out_message.is_null() {
+            return DT_ERROR;
+        }
+        // SAFETY: checked for null above; the caller guarantees it's writable.
+        unsafe { *out_message = message.into_raw() };
+        DT_OK
+    })
+    .unwrap_or(DT_ERROR)
+}
+
+/// Releases a string handed out by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `string` must be null or a pointer received from this library that wasn't freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dt_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: the caller guarantees it came from `CString::into_raw` here and is freed once.
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Runs `call`, turning errors and panics into return codes and remembering the message.
+fn guard(call: impl FnOnce() -> Result<i32>) -> i32 {
+    let (code, message) = match catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(code)) => return code,
+        Ok(Err(err)) => (error_code(&err), err.to_string()),
+        Err(panic) => {
+            let reason = panic
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            (DT_ERROR, format!("Internal error: {reason}"))
+        }
+    };
+    LAST_ERROR.set(Some(CString::new(message.replace('\0', " ")).unwrap_or_default()));
+    code
+}
+
+fn error_code(err: &DisplayError) -> i32 {
+    match err {
+        DisplayError::ApplyTimedOut => DT_TIMED_OUT,
+        DisplayError::NoDisplays => DT_NO_DISPLAYS,
+        _ => DT_ERROR,
+    }
+}
+
+/// `serde_json` escapes NUL characters, so JSON always converts.
+fn into_c_string(json: String) -> *mut c_char {
+    CString::new(json).unwrap_or_default().into_raw()
+}
@@ -0,0 +1,105 @@
+use crate::display::{DisplayError, DisplayInfo, Result};
+use crate::display::app_dir;
+use crate::selector::{Aliases, DisplayFilter};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const GROUPS_FILE: &str = "groups.toml";
+
+/// Named sets of displays and names for single displays, loaded from a TOML file. Members and
+/// aliases use the same fields as rule `match` tables, typically a device path or EDID ids:
+///
+/// ```toml
+/// [groups]
+/// left-bank = [{ device_path = '\\?\DISPLAY#DEL41A8#...' }, { vendor = "DEL", product = 16809 }]
+///
+/// [aliases]
+/// tv = { vendor = "SAM" }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfig {
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<DisplayFilter>>,
+    /// See [`resolve_alias`](crate::selector::resolve_alias).
+    #[serde(default)]
+    pub aliases: Aliases,
+}
+
+impl GroupConfig {
+    /// # Errors
+    ///
+    /// Returns an error if `text` isn't valid TOML of this shape.
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// `%APPDATA%\display-tuner\groups.toml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::NoAppData`] if `APPDATA` isn't set.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(app_dir()?.join(GROUPS_FILE))
+    }
+
+    /// Loads the groups from [`default_path`](Self::default_path); no file means no groups.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load_default() -> Result<Self> {
+        match Self::load(&Self::default_path()?) {
+            Err(DisplayError::Io(err)) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    /// The groups `display` belongs to, in name order.
+    #[must_use]
+    pub fn groups_of(&self, display: &DisplayInfo) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|(_, members)| members.iter().any(|m| m.matches(display)))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Members matching none of `displays`, as (group, member).
+    #[must_use]
+    pub fn missing_members<'a>(&'a self, displays: &[DisplayInfo]) -> Vec<(&'a str, &'a DisplayFilter)> {
+        self.groups
+            .iter()
+            .flat_map(|(name, members)| members.iter().map(move |m| (name.as_str(), m)))
+            .filter(|(_, member)| !displays.iter().any(|d| member.matches(d)))
+            .collect()
+    }
+}
+
+/// The connected displays in group `name`, in the order of `displays`. Members that aren't
+/// connected are left out, so the result may be empty.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::UnknownGroup`] if `config` has no such group.
+pub fn resolve_group(config: &GroupConfig, name: &str, displays: &[DisplayInfo]) -> Result<Vec<DisplayInfo>> {
+    let members = config
+        .groups
+        .get(name)
+        .ok_or_else(|| DisplayError::UnknownGroup(name.to_string()))?;
+    Ok(displays
+        .iter()
+        .filter(|d| members.iter().any(|m| m.matches(d)))
+        .cloned()
+        .collect())
+}
@@ -0,0 +1,153 @@
+//! Stable handles the daemon gives physical displays, for tools that keep referring to a
+//! display while Windows reshuffles source ids on dock and undock.
+//!
+//! A display is known by its device path, which is stable for a monitor on a given port, and
+//! failing that by its EDID ids: a monitor moved to another port keeps its handle when it's the
+//! only one of its model missing. Handles are never reused, so a handle a tool kept around can't
+//! come to mean another monitor. The table is kept in `%APPDATA%\display-tuner\handles.json`,
+//! so handles also outlive a daemon restart; monitors not seen for [`EVICT_AFTER_SECS`] are
+//! forgotten and at most [`MAX_ENTRIES`] are kept.
+
+use crate::display::{app_dir, DisplayInfo, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HANDLES_FILE: &str = "handles.json";
+
+/// Monitors not seen for this long lose their handle.
+pub const EVICT_AFTER_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// The most monitors remembered; those seen longest ago go first.
+pub const MAX_ENTRIES: usize = 64;
+
+/// A monitor the daemon has seen and its handle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandleEntry {
+    pub handle: u32,
+    /// Where it was seen last.
+    pub device_path: String,
+    pub edid_manufacturer_id: u16,
+    pub edid_product_id: u16,
+    /// Unix time in seconds.
+    pub last_seen: u64,
+}
+
+impl HandleEntry {
+    fn same_model(&self, display: &DisplayInfo) -> bool {
+        (self.edid_manufacturer_id, self.edid_product_id) != (0, 0)
+            && (self.edid_manufacturer_id, self.edid_product_id) == (display.edid_manufacturer_id, display.edid_product_id)
+    }
+}
+
+/// The handles of every monitor seen, see the module documentation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandleTable {
+    /// The handle the next new monitor gets; handles start at 1.
+    pub next: u32,
+    pub entries: Vec<HandleEntry>,
+}
+
+impl HandleTable {
+    /// `%APPDATA%\display-tuner\handles.json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::NoAppData`](crate::display::DisplayError::NoAppData) if
+    /// `APPDATA` isn't set.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(app_dir()?.join(HANDLES_FILE))
+    }
+
+    /// The table saved at `path`, empty if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file or its directory can't be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The handle of each of `displays`, in the same order, giving new monitors new handles and
+    /// marking all of them seen at `now`. Forgets monitors past [`EVICT_AFTER_SECS`] and beyond
+    /// [`MAX_ENTRIES`].
+    pub fn assign(&mut self, displays: &[DisplayInfo], now: u64) -> Vec<u32> {
+        let mut claimed: Vec<Option<usize>> = displays
+            .iter()
+            .map(|display| self.entries.iter().position(|e| e.device_path.eq_ignore_ascii_case(&display.device_path)))
+            .collect();
+        // Monitors on another port than before, when that's unambiguous.
+        for (i, display) in displays.iter().enumerate() {
+            if claimed[i].is_some() {
+                continue;
+            }
+            let unclaimed = |index: &usize| !claimed.contains(&Some(*index));
+            let candidates: Vec<usize> = (0..self.entries.len()).filter(unclaimed).filter(|&e| self.entries[e].same_model(display)).collect();
+            let [entry] = candidates[..] else {
+                continue;
+            };
+            let twins = displays.iter().zip(&claimed).filter(|(d, c)| c.is_none() && self.entries[entry].same_model(d)).count();
+            if twins == 1 {
+                claimed[i] = Some(entry);
+            }
+        }
+
+        let handles = displays
+            .iter()
+            .zip(claimed)
+            .map(|(display, claimed)| {
+                let index = claimed.unwrap_or_else(|| self.insert(display));
+                let entry = &mut self.entries[index];
+                entry.device_path.clone_from(&display.device_path);
+                entry.last_seen = now;
+                entry.handle
+            })
+            .collect();
+        self.evict(now);
+        handles
+    }
+
+    fn insert(&mut self, display: &DisplayInfo) -> usize {
+        self.next = self.next.max(1);
+        self.entries.push(HandleEntry {
+            handle: self.next,
+            device_path: display.device_path.clone(),
+            edid_manufacturer_id: display.edid_manufacturer_id,
+            edid_product_id: display.edid_product_id,
+            last_seen: 0,
+        });
+        self.next += 1;
+        self.entries.len() - 1
+    }
+
+    fn evict(&mut self, now: u64) {
+        self.entries.retain(|e| now.saturating_sub(e.last_seen) <= EVICT_AFTER_SECS);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.sort_by_key(|e| std::cmp::Reverse(e.last_seen));
+            self.entries.truncate(MAX_ENTRIES);
+            self.entries.sort_by_key(|e| e.handle);
+        }
+    }
+
+    /// The display among `displays` that has `handle`, `None` if the handle is unknown or its
+    /// monitor isn't connected.
+    #[must_use]
+    pub fn resolve<'a>(&self, handle: u32, displays: &'a [DisplayInfo]) -> Option<&'a DisplayInfo> {
+        let entry = self.entries.iter().find(|e| e.handle == handle)?;
+        displays.iter().find(|d| d.device_path.eq_ignore_ascii_case(&entry.device_path))
+    }
+}
@@ -0,0 +1,202 @@
+use crate::display::{DisplayError, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use tracing::debug;
+use windows::Win32::UI::Input::KeyboardAndMouse::{RegisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN};
+use windows::Win32::UI::WindowsAndMessaging::{GetMessageW, MSG, WM_HOTKEY};
+
+/// A key combination such as `Ctrl+Alt+F1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hotkey {
+    /// `MOD_*` flags.
+    pub modifiers: u32,
+    /// Virtual key code.
+    pub key: u16,
+}
+
+/// Key names accepted besides letters, digits and `F1`-`F24`.
+const KEY_NAMES: [(&str, u16); 16] = [
+    ("plus", 0xBB),
+    ("minus", 0xBD),
+    ("left", 0x25),
+    ("up", 0x26),
+    ("right", 0x27),
+    ("down", 0x28),
+    ("space", 0x20),
+    ("enter", 0x0D),
+    ("tab", 0x09),
+    ("esc", 0x1B),
+    ("home", 0x24),
+    ("end", 0x23),
+    ("pageup", 0x21),
+    ("pagedown", 0x22),
+    ("insert", 0x2D),
+    ("delete", 0x2E),
+];
+
+const MODIFIER_NAMES: [(&str, HOT_KEY_MODIFIERS); 4] =
+    [("ctrl", MOD_CONTROL), ("alt", MOD_ALT), ("shift", MOD_SHIFT), ("win", MOD_WIN)];
+
+impl FromStr for Hotkey {
+    type Err = DisplayError;
+
+    /// Parses `+`-separated modifiers followed by one key, case-insensitive.
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || DisplayError::InvalidHotkey(text.to_string());
+        let parts: Vec<String> = text.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let (key, modifiers) = parts.split_last().ok_or_else(invalid)?;
+
+        let mut flags = 0;
+        for modifier in modifiers {
+            let modifier = if modifier == "control" { "ctrl" } else { modifier };
+            let (_, flag) = MODIFIER_NAMES
+                .iter()
+                .find(|(name, _)| *name == modifier)
+                .ok_or_else(invalid)?;
+            flags |= flag.0;
+        }
+        if flags == 0 {
+            // Bare keys would swallow normal typing.
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            modifiers: flags,
+            key: key_code(key).ok_or_else(invalid)?,
+        })
+    }
+}
+
+fn key_code(name: &str) -> Option<u16> {
+    if let [c] = name.as_bytes()
+        && c.is_ascii_alphanumeric()
+    {
+        return Some(u16::from(c.to_ascii_uppercase()));
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u16>().ok())
+        && (1..=24).contains(&n)
+    {
+        return Some(0x70 + n - 1);
+    }
+    KEY_NAMES.iter().find(|(n, _)| *n == name).map(|(_, code)| *code)
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, flag) in MODIFIER_NAMES {
+            if self.modifiers & flag.0 != 0 {
+                let mut chars = name.chars();
+                let first = chars.next().map(|c| c.to_ascii_uppercase()).unwrap_or_default();
+                write!(f, "{first}{}+", chars.as_str())?;
+            }
+        }
+        match self.key {
+            0x30..=0x39 | 0x41..=0x5A => write!(f, "{}", char::from(u8::try_from(self.key).unwrap_or(b'?'))),
+            0x70..=0x87 => write!(f, "F{}", self.key - 0x6F),
+            code => match KEY_NAMES.iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{}{}", name[..1].to_uppercase(), &name[1..]),
+                None => write!(f, "0x{code:02X}"),
+            },
+        }
+    }
+}
+
+/// Hotkeys mapped to display-tuner command lines, loaded from a TOML file:
+///
+/// ```toml
+/// [hotkeys]
+/// "Ctrl+Alt+F1" = "profile apply docked"
+/// "Ctrl+Alt+Plus" = "set --current --scaling +1"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HotkeyConfig {
+    #[serde(default)]
+    pub hotkeys: BTreeMap<String, String>,
+}
+
+impl HotkeyConfig {
+    /// # Errors
+    ///
+    /// Returns an error if `text` isn't valid TOML of this shape.
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The bindings with parsed hotkeys, sorted by their key text.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::InvalidHotkey`] for the first key combination that can't be
+    /// parsed.
+    pub fn bindings(&self) -> Result<Vec<(Hotkey, String)>> {
+        self.hotkeys
+            .iter()
+            .map(|(keys, command)| Ok((keys.parse()?, command.clone())))
+            .collect()
+    }
+}
+
+/// Result of [`register_hotkeys`].
+#[derive(Debug)]
+pub struct Registration {
+    /// Yields the index of a hotkey each time it's pressed.
+    pub pressed: Receiver<usize>,
+    /// Hotkeys that couldn't be registered, usually because another program already owns them.
+    pub failed: Vec<(Hotkey, DisplayError)>,
+}
+
+/// Registers `hotkeys` on a background thread that runs for the rest of the process.
+///
+/// # Errors
+///
+/// Returns an error if the listener thread can't report back.
+pub fn register_hotkeys(hotkeys: &[Hotkey]) -> Result<Registration> {
+    let (pressed_tx, pressed_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let hotkeys = hotkeys.to_vec();
+
+    thread::spawn(move || {
+        let mut failed = Vec::new();
+        for (index, hotkey) in hotkeys.iter().enumerate() {
+            let Ok(id) = i32::try_from(index) else {
+                continue;
+            };
+            // SAFETY: registers for this thread's queue, no pointers involved.
+            let result = unsafe {
+                RegisterHotKey(None, id, HOT_KEY_MODIFIERS(hotkey.modifiers) | MOD_NOREPEAT, u32::from(hotkey.key))
+            };
+            match result {
+                Ok(()) => debug!(%hotkey, "Registered hotkey"),
+                Err(_) => failed.push((*hotkey, DisplayError::HotkeyUnavailable(hotkey.to_string()))),
+            }
+        }
+        let _ = ready_tx.send(failed);
+
+        let mut msg = MSG::default();
+        // SAFETY: msg is a valid out-pointer; thread messages have no window.
+        while unsafe { GetMessageW(&raw mut msg, None, 0, 0) }.as_bool() {
+            if msg.message == WM_HOTKEY && pressed_tx.send(msg.wParam.0).is_err() {
+                break;
+            }
+        }
+    });
+
+    let failed = ready_rx
+        .recv()
+        .map_err(|_| DisplayError::Windows(windows::core::Error::from_thread()))?;
+    Ok(Registration { pressed: pressed_rx, failed })
+}
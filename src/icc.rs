@@ -0,0 +1,147 @@
+//! Color profile associations of the displays, as the Windows Color System keeps them, for
+//! `icc`.
+//!
+//! Each monitor has system-wide associations and, per user, optional ones of their own that
+//! replace them (the "Use my settings for this device" checkbox in Color Management).
+
+use crate::display::{DisplayError, DisplayInfo, Result};
+use crate::raw;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use tracing::debug;
+
+/// Whose profile associations Windows uses for a monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileScope {
+    User,
+    System,
+}
+
+impl fmt::Display for ProfileScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::User => "user",
+            Self::System => "system",
+        })
+    }
+}
+
+/// The color management state of one display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColorStatus {
+    pub source_id: u32,
+    pub friendly_name: String,
+    /// The associations in effect.
+    pub scope: ProfileScope,
+    /// File name of the default profile in the system-wide associations.
+    pub system_profile: Option<String>,
+    /// File name of the default profile in the current user's associations, kept while they're
+    /// turned off.
+    pub user_profile: Option<String>,
+    /// The profile in effect has an `MHC2` tag, as written by the Windows HDR Calibration app.
+    pub hdr_calibration: bool,
+}
+
+impl ColorStatus {
+    /// File name of the default profile in effect.
+    #[must_use]
+    pub fn active_profile(&self) -> Option<&str> {
+        match self.scope {
+            ProfileScope::User => self.user_profile.as_deref(),
+            ProfileScope::System => self.system_profile.as_deref(),
+        }
+    }
+}
+
+impl fmt::Display for ColorStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let none = || "none".to_string();
+        write!(f, "{} (id {}): {} profiles", self.friendly_name, self.source_id, self.scope)?;
+        if self.hdr_calibration {
+            f.write_str(", HDR calibration (MHC2)")?;
+        }
+        write!(f, "\n  system: {}", self.system_profile.clone().unwrap_or_else(none))?;
+        write!(f, "\n  user: {}", self.user_profile.clone().unwrap_or_else(none))
+    }
+}
+
+/// Whether the ICC profile `icc` has an `MHC2` tag, the HDR calibration Windows 11 applies in
+/// the display pipeline. Data that isn't an ICC profile, or is cut short, has none.
+#[must_use]
+pub fn has_mhc2_tag(icc: &[u8]) -> bool {
+    const HEADER: usize = 128;
+    const ENTRY: usize = 12;
+    if icc.get(36..40) != Some(b"acsp") {
+        return false;
+    }
+    let Some(count) = icc.get(HEADER..HEADER + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])) else {
+        return false;
+    };
+    (0..usize::try_from(count).unwrap_or_default())
+        .map_while(|i| icc.get(HEADER + 4 + i * ENTRY..HEADER + 8 + i * ENTRY))
+        .any(|signature| signature == b"MHC2")
+}
+
+/// Reads the profile associations of `display` and checks the profile in effect for HDR
+/// calibration. While HDR is on, the advanced color profiles are the ones reported.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NoColorDevice`] if color management doesn't know the monitor, or
+/// [`DisplayError::ColorManagement`] if it can't be queried, e.g. with the Windows Color
+/// System unavailable.
+pub fn color_status(display: &DisplayInfo) -> Result<ColorStatus> {
+    let source_id = display.source_id;
+    let device = color_device(display)?;
+    let wcs = |err: DisplayError| match err {
+        DisplayError::Windows(err) => DisplayError::ColorManagement { source_id, err },
+        err => err,
+    };
+    let profile = |per_user| -> Result<Option<String>> {
+        let advanced = if display.hdr_enabled { raw::default_color_profile(&device, per_user, true).map_err(wcs)? } else { None };
+        match advanced {
+            Some(name) => Ok(Some(name)),
+            None => raw::default_color_profile(&device, per_user, false).map_err(wcs),
+        }
+    };
+    let scope = if raw::uses_per_user_profiles(&device).map_err(wcs)? { ProfileScope::User } else { ProfileScope::System };
+    let mut status = ColorStatus {
+        source_id,
+        friendly_name: display.friendly_name.clone(),
+        scope,
+        system_profile: profile(false)?,
+        user_profile: profile(true)?,
+        hdr_calibration: false,
+    };
+    if let Some(name) = status.active_profile() {
+        let path = raw::color_directory().map_err(wcs)?.join(name);
+        status.hdr_calibration = match fs::read(&path) {
+            Ok(icc) => has_mhc2_tag(&icc),
+            Err(err) => {
+                debug!(%err, path = %path.display(), "Profile not readable");
+                false
+            }
+        };
+    }
+    Ok(status)
+}
+
+/// Makes Windows use the current user's profile associations for `display`, or the
+/// system-wide ones again. The user's associations are kept either way.
+///
+/// # Errors
+///
+/// As for [`color_status`].
+pub fn set_profile_scope(display: &DisplayInfo, scope: ProfileScope) -> Result<()> {
+    let source_id = display.source_id;
+    raw::set_per_user_profiles(&color_device(display)?, scope == ProfileScope::User).map_err(|err| match err {
+        DisplayError::Windows(err) => DisplayError::ColorManagement { source_id, err },
+        err => err,
+    })
+}
+
+fn color_device(display: &DisplayInfo) -> Result<String> {
+    raw::color_device_id(&display.gdi_device_name, &display.device_path).ok_or(DisplayError::NoColorDevice(display.source_id))
+}
@@ -0,0 +1,145 @@
+//! Large number overlays like the "Identify" button in the Windows display settings.
+
+use crate::display::{DisplayInfo, Result};
+use std::time::Duration;
+use tracing::debug;
+use windows::Win32::Foundation::{COLORREF, ERROR_CLASS_ALREADY_EXISTS, HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::{BeginPaint, CreateFontW, DeleteObject, DrawTextW, EndPaint, GetStockObject, SelectObject, SetBkMode, SetTextColor, BLACK_BRUSH, CLIP_DEFAULT_PRECIS, DEFAULT_CHARSET, DEFAULT_PITCH, DT_CENTER, DT_SINGLELINE, DT_VCENTER, FF_SWISS, FW_BOLD, HBRUSH, OUT_DEFAULT_PRECIS, PAINTSTRUCT, TRANSPARENT, CLEARTYPE_QUALITY};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::HiDpi::{SetThreadDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2};
+use windows::Win32::UI::WindowsAndMessaging::{CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetClientRect, GetMessageW, GetWindowTextW, KillTimer, RegisterClassW, SetTimer, MSG, WM_PAINT, WM_TIMER, WNDCLASSW, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_POPUP, WS_VISIBLE};
+use windows::core::{w, HSTRING};
+
+const CLASS_NAME: windows::core::PCWSTR = w!("display-tuner-identify");
+
+/// The label shown on `display`: its Settings number, or the source id for displays without a
+/// GDI name.
+#[must_use]
+pub fn identify_label(display: &DisplayInfo) -> String {
+    display
+        .display_number()
+        .map_or_else(|| format!("id {}", display.source_id), |number| number.to_string())
+}
+
+/// Shows each display's label in a borderless topmost window in its bottom-left corner for
+/// `duration`, blocking until they're gone.
+///
+/// # Errors
+///
+/// Returns an error if a window can't be created.
+pub fn identify(displays: &[DisplayInfo], duration: Duration) -> Result<()> {
+    // SAFETY: all pointers passed are valid for the calls; every window created here is
+    // destroyed before returning, and the timer is a thread timer killed the same way.
+    unsafe {
+        // Physical pixels, so the desktop coordinates of the displays can be used as they are.
+        let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        let instance = GetModuleHandleW(None)?;
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(identify_proc),
+            hInstance: instance.into(),
+            lpszClassName: CLASS_NAME,
+            hbrBackground: HBRUSH(GetStockObject(BLACK_BRUSH).0),
+            ..WNDCLASSW::default()
+        };
+        // Already registered by an earlier call in a resident process.
+        if RegisterClassW(&raw const class) == 0 {
+            let err = windows::core::Error::from_thread();
+            if err.code() != ERROR_CLASS_ALREADY_EXISTS.to_hresult() {
+                SetThreadDpiAwarenessContext(previous);
+                return Err(err.into());
+            }
+        }
+
+        let mut windows = Vec::new();
+        let mut result = Ok(());
+        for display in displays.iter().filter(|d| d.active) {
+            let height = i32::try_from(display.height).unwrap_or(i32::MAX);
+            let size = (height / 4).max(1);
+            let margin = size / 4;
+            let label = identify_label(display);
+            let source_id = display.source_id;
+            debug!(source_id, %label, "Showing identify overlay");
+            match CreateWindowExW(
+                WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+                CLASS_NAME,
+                &HSTRING::from(label),
+                WS_POPUP | WS_VISIBLE,
+                display.position_x + margin,
+                display.position_y + height - size - margin,
+                size,
+                size,
+                None,
+                None,
+                Some(instance.into()),
+                None,
+            ) {
+                Ok(hwnd) => windows.push(hwnd),
+                Err(err) => {
+                    result = Err(err.into());
+                    break;
+                }
+            }
+        }
+
+        if result.is_ok() && !windows.is_empty() {
+            let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+            let timer = SetTimer(None, 0, millis, None);
+            let mut msg = MSG::default();
+            while GetMessageW(&raw mut msg, None, 0, 0).as_bool() {
+                if msg.message == WM_TIMER && msg.hwnd.is_invalid() {
+                    break;
+                }
+                DispatchMessageW(&raw const msg);
+            }
+            let _ = KillTimer(None, timer);
+        }
+        for hwnd in windows {
+            let _ = DestroyWindow(hwnd);
+        }
+        SetThreadDpiAwarenessContext(previous);
+        result
+    }
+}
+
+extern "system" fn identify_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg == WM_PAINT {
+        // SAFETY: hwnd is the window being painted and every GDI object created is released.
+        unsafe {
+            let mut text = [0u16; 32];
+            let len = usize::try_from(GetWindowTextW(hwnd, &mut text)).unwrap_or(0);
+            let mut rect = RECT::default();
+            let _ = GetClientRect(hwnd, &raw mut rect);
+
+            let mut paint = PAINTSTRUCT::default();
+            let dc = BeginPaint(hwnd, &raw mut paint);
+            // Shorter labels get bigger digits.
+            let font_height = if len > 2 { rect.bottom / 3 } else { rect.bottom * 3 / 4 };
+            let font = CreateFontW(
+                font_height,
+                0,
+                0,
+                0,
+                FW_BOLD.0.cast_signed(),
+                0,
+                0,
+                0,
+                DEFAULT_CHARSET,
+                OUT_DEFAULT_PRECIS,
+                CLIP_DEFAULT_PRECIS,
+                CLEARTYPE_QUALITY,
+                u32::from(DEFAULT_PITCH.0 | FF_SWISS.0),
+                w!("Segoe UI"),
+            );
+            let old = SelectObject(dc, font.into());
+            SetTextColor(dc, COLORREF(0x00FF_FFFF));
+            SetBkMode(dc, TRANSPARENT);
+            DrawTextW(dc, &mut text[..len], &raw mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+            SelectObject(dc, old);
+            let _ = DeleteObject(font.into());
+            let _ = EndPaint(hwnd, &raw const paint);
+        }
+        return LRESULT(0);
+    }
+    // SAFETY: forwards the message unchanged.
+    unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+}
@@ -0,0 +1,479 @@
+use crate::display::{DisplayError, DisplayInfo, Result, SourceMode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A display's area on the virtual desktop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    #[must_use]
+    pub fn of(display: &DisplayInfo) -> Self {
+        Self {
+            x: display.position_x,
+            y: display.position_y,
+            width: display.width,
+            height: display.height,
+        }
+    }
+
+    #[must_use]
+    pub fn right(&self) -> i32 {
+        self.x.saturating_add_unsigned(self.width)
+    }
+
+    #[must_use]
+    pub fn bottom(&self) -> i32 {
+        self.y.saturating_add_unsigned(self.height)
+    }
+
+    /// Whether the two rects share any area; touching edges don't count.
+    #[must_use]
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.x < other.right() && other.x < self.right() && self.y < other.bottom() && other.y < self.bottom()
+    }
+
+    /// The smallest rect containing both.
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let (right, bottom) = (self.right().max(other.right()), self.bottom().max(other.bottom()));
+        Self { x, y, width: right.abs_diff(x), height: bottom.abs_diff(y) }
+    }
+
+    #[must_use]
+    pub fn area(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{} at ({}, {})", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// The virtual desktop spanned by the active displays, see [`desktop_summary`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DesktopSummary {
+    /// Bounding box of all active displays; the primary display's top-left corner is (0, 0),
+    /// so displays left of or above it make `x`/`y` negative.
+    pub bounds: Rect,
+    /// Pixels of all active displays together, less than the area of `bounds` when they don't
+    /// fill it.
+    pub pixels: u64,
+    pub displays: Vec<DisplayArea>,
+}
+
+/// One display's rect in virtual desktop coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayArea {
+    pub source_id: u32,
+    #[serde(flatten)]
+    pub rect: Rect,
+}
+
+/// Sums up the virtual desktop of the active `displays`, `None` if no display is active.
+#[must_use]
+pub fn desktop_summary(displays: &[DisplayInfo]) -> Option<DesktopSummary> {
+    let areas: Vec<DisplayArea> = displays
+        .iter()
+        .filter(|d| d.active)
+        .map(|d| DisplayArea { source_id: d.source_id, rect: Rect::of(d) })
+        .collect();
+    let bounds = areas.iter().map(|a| a.rect).reduce(|a, b| a.union(&b))?;
+    Some(DesktopSummary {
+        bounds,
+        pixels: areas.iter().map(|a| a.rect.area()).sum(),
+        displays: areas,
+    })
+}
+
+/// Side of the reference display to place a display on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    LeftOf,
+    RightOf,
+    Above,
+    Below,
+}
+
+/// Alignment along the edge shared with the reference display: `Start` is top (left of/right of)
+/// or left (above/below).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    #[serde(alias = "top", alias = "left")]
+    Start,
+    Center,
+    #[serde(alias = "bottom", alias = "right")]
+    End,
+}
+
+/// Positions `rect` next to `reference` without changing its size.
+#[must_use]
+pub fn place(rect: Rect, reference: Rect, placement: Placement, align: Align) -> Rect {
+    let align_on = |start: i32, reference_len: u32, len: u32| {
+        let slack = i64::from(reference_len) - i64::from(len);
+        let offset = match align {
+            Align::Start => 0,
+            Align::Center => slack.div_euclid(2),
+            Align::End => slack,
+        };
+        saturate(i64::from(start) + offset)
+    };
+
+    let (x, y) = match placement {
+        Placement::LeftOf => (
+            reference.x.saturating_sub_unsigned(rect.width),
+            align_on(reference.y, reference.height, rect.height),
+        ),
+        Placement::RightOf => (reference.right(), align_on(reference.y, reference.height, rect.height)),
+        Placement::Above => (
+            align_on(reference.x, reference.width, rect.width),
+            reference.y.saturating_sub_unsigned(rect.height),
+        ),
+        Placement::Below => (align_on(reference.x, reference.width, rect.width), reference.bottom()),
+    };
+
+    Rect { x, y, ..rect }
+}
+
+fn saturate(value: i64) -> i32 {
+    i32::try_from(value).unwrap_or(if value < 0 { i32::MIN } else { i32::MAX })
+}
+
+/// Shifts the whole layout so the display `primary` sits at the desktop origin.
+pub fn normalize(layout: &mut [(u32, Rect)], primary: u32) {
+    let Some((dx, dy)) = layout
+        .iter()
+        .find(|(id, _)| *id == primary)
+        .map(|(_, rect)| (rect.x, rect.y))
+    else {
+        return;
+    };
+    for (_, rect) in layout.iter_mut() {
+        rect.x = saturate(i64::from(rect.x) - i64::from(dx));
+        rect.y = saturate(i64::from(rect.y) - i64::from(dy));
+    }
+}
+
+/// Lowest virtual desktop coordinate Windows accepts.
+pub const MIN_DESKTOP_COORD: i32 = -32768;
+
+/// Highest virtual desktop coordinate Windows accepts, for the right and bottom edges too.
+pub const MAX_DESKTOP_COORD: i32 = 32767;
+
+/// How many pixels the farthest edge of `layout` reaches past [`MIN_DESKTOP_COORD`] or
+/// [`MAX_DESKTOP_COORD`], 0 if every display fits.
+#[must_use]
+pub fn desktop_excess(layout: &[(u32, Rect)]) -> u64 {
+    let (min, max) = (i64::from(MIN_DESKTOP_COORD), i64::from(MAX_DESKTOP_COORD));
+    layout
+        .iter()
+        .flat_map(|(_, rect)| {
+            let (x, y) = (i64::from(rect.x), i64::from(rect.y));
+            [min - x, min - y, x + i64::from(rect.width) - max, y + i64::from(rect.height) - max]
+        })
+        .max()
+        .map_or(0, |excess| excess.max(0).unsigned_abs())
+}
+
+/// Shifts `layout` so `primary` sits at the origin, which keeps its bounding box as close to
+/// the origin as Windows allows, and checks it fits the virtual desktop coordinates.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DesktopOutOfRange`] with the pixels it's off by if a display still
+/// reaches past [`MIN_DESKTOP_COORD`] or [`MAX_DESKTOP_COORD`].
+pub fn fit_desktop(layout: &mut [(u32, Rect)], primary: u32) -> Result<()> {
+    normalize(layout, primary);
+    match desktop_excess(layout) {
+        0 => Ok(()),
+        excess => Err(DisplayError::DesktopOutOfRange(excess)),
+    }
+}
+
+/// Returns the first pair of displays whose rects overlap.
+#[must_use]
+pub fn find_overlap(layout: &[(u32, Rect)]) -> Option<(u32, u32)> {
+    layout.iter().enumerate().find_map(|(i, (a, ra))| {
+        layout[i + 1..]
+            .iter()
+            .find(|(_, rb)| ra.overlaps(rb))
+            .map(|(b, _)| (*a, *b))
+    })
+}
+
+/// Computes the layout after moving display `source_id` next to display `reference_id`.
+///
+/// The result covers every display, renormalized so the primary stays at (0,0).
+///
+/// # Errors
+///
+/// Returns an error if either display is unknown, the new position would overlap another display
+/// or the layout wouldn't fit the virtual desktop, see [`fit_desktop`].
+pub fn place_display(
+    displays: &[DisplayInfo],
+    source_id: u32,
+    reference_id: u32,
+    placement: Placement,
+    align: Align,
+) -> Result<Vec<SourceMode>> {
+    let find = |id: u32| {
+        displays
+            .iter()
+            .find(|d| d.source_id == id)
+            .ok_or(DisplayError::DisplayNotFound(id))
+    };
+    let moving = find(source_id)?;
+    let reference = find(reference_id)?;
+
+    let placed = place(Rect::of(moving), Rect::of(reference), placement, align);
+    let mut layout: Vec<(u32, Rect)> = displays
+        .iter()
+        .map(|d| (d.source_id, if d.source_id == source_id { placed } else { Rect::of(d) }))
+        .collect();
+
+    if let Some((a, b)) = find_overlap(&layout) {
+        return Err(DisplayError::Overlap(a, b));
+    }
+    if let Some(primary) = displays.iter().find(|d| d.primary) {
+        fit_desktop(&mut layout, primary.source_id)?;
+    }
+
+    Ok(displays
+        .iter()
+        .zip(layout)
+        .map(|(d, (_, rect))| SourceMode {
+            x: rect.x,
+            y: rect.y,
+            ..SourceMode::of(d)
+        })
+        .collect())
+}
+
+/// Positions after resizing some displays of `layout`, so the displays attached to them stay
+/// attached in the same arrangement. `resized` gives the new (width, height) by source id.
+///
+/// A display is attached to the nearest display on its left whose rows it shares, and to the
+/// nearest one above it whose columns it shares. Its left edge follows the right edge of the
+/// first, its top edge the bottom edge of the second; with only one of them, it keeps its
+/// alignment with that display along the shared edge (top, bottom, center or the same offset).
+/// A gap between a display and the one it's attached to grows and shrinks in proportion to
+/// that display. Displays attached to nothing, like the top-left one, keep their position, so a
+/// resized display keeps its top-left corner unless it's attached itself.
+///
+/// The result may overlap, e.g. when one display of a grid shrinks; see [`find_overlap`].
+#[must_use]
+pub fn reflow(layout: &[(u32, Rect)], resized: &[(u32, (u32, u32))]) -> Vec<(u32, Rect)> {
+    let old: Vec<Rect> = layout.iter().map(|(_, rect)| *rect).collect();
+    let mut new: Vec<Rect> = layout
+        .iter()
+        .map(|(id, rect)| {
+            let (width, height) = resized.iter().find(|(r, _)| r == id).map_or((rect.width, rect.height), |(_, size)| *size);
+            Rect { width, height, ..*rect }
+        })
+        .collect();
+    // The display each one is attached to on the left and above.
+    let anchors: Vec<(Option<usize>, Option<usize>)> = (0..old.len()).map(|i| (nearest(&old, i, true), nearest(&old, i, false))).collect();
+
+    // Displays are only attached to displays left of or above them, so every pass settles at
+    // least one more.
+    for _ in 0..new.len() {
+        let mut settled = true;
+        for (i, &(left, top)) in anchors.iter().enumerate() {
+            let x = start_on(&old, &new, i, true, left, top);
+            let y = start_on(&old, &new, i, false, top, left);
+            if (x, y) != (new[i].x, new[i].y) {
+                (new[i].x, new[i].y) = (x, y);
+                settled = false;
+            }
+        }
+        if settled {
+            break;
+        }
+    }
+    layout.iter().zip(new).map(|((id, _), rect)| (*id, rect)).collect()
+}
+
+/// A rect's (start, length) along the x axis if `horizontal`, along the y axis otherwise.
+fn span(rect: &Rect, horizontal: bool) -> (i32, u32) {
+    if horizontal { (rect.x, rect.width) } else { (rect.y, rect.height) }
+}
+
+fn end((start, len): (i32, u32)) -> i64 {
+    i64::from(start) + i64::from(len)
+}
+
+/// The display of `rects` that `rects[i]` is attached to on its left if `horizontal`, above it
+/// otherwise: the nearest one ending before it starts that shares part of its span across the
+/// axis, the one sharing the most among equally near ones.
+fn nearest(rects: &[Rect], i: usize, horizontal: bool) -> Option<usize> {
+    let (start, across) = (span(&rects[i], horizontal).0, span(&rects[i], !horizontal));
+    rects
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .filter_map(|(j, other)| {
+            let other_across = span(other, !horizontal);
+            let shared = end(across).min(end(other_across)) - i64::from(across.0.max(other_across.0));
+            let gap = i64::from(start) - end(span(other, horizontal));
+            (gap >= 0 && shared > 0).then_some((j, gap, shared))
+        })
+        .min_by_key(|&(_, gap, shared)| (gap, -shared))
+        .map(|(j, _, _)| j)
+}
+
+/// Where `new[i]` starts along one axis, with `along` the display it's attached to on that
+/// axis and `across` the one on the other, see [`reflow`].
+fn start_on(old: &[Rect], new: &[Rect], i: usize, horizontal: bool, along: Option<usize>, across: Option<usize>) -> i32 {
+    match (along, across) {
+        (Some(j), _) => follow(span(&old[i], horizontal).0, span(&old[j], horizontal), span(&new[j], horizontal)),
+        (None, Some(j)) => align_along(span(&old[i], horizontal), span(&old[j], horizontal), span(&new[i], horizontal).1, span(&new[j], horizontal)),
+        (None, None) => span(&new[i], horizontal).0,
+    }
+}
+
+/// Where a display starts that started at `start`, after the display `old` (start, length) it's
+/// attached to became `new`; the gap between them scales with that display's length.
+fn follow(start: i32, old: (i32, u32), new: (i32, u32)) -> i32 {
+    let gap = i64::from(start) - end(old);
+    let gap = if gap == 0 || old.1 == 0 {
+        gap
+    } else {
+        (gap * i64::from(new.1) * 2 + i64::from(old.1)).div_euclid(i64::from(old.1) * 2)
+    };
+    saturate(end(new) + gap)
+}
+
+/// Start of a span that was at `old` (start, length) next to a display spanning `reference`,
+/// once it's `len` long and that display spans `new`, keeping the [`Align`] it had, or else
+/// its offset from the display's start.
+fn align_along(old: (i32, u32), reference: (i32, u32), len: u32, new: (i32, u32)) -> i32 {
+    let offset = if old.0 == reference.0 {
+        0
+    } else if end(old) == end(reference) {
+        i64::from(new.1) - i64::from(len)
+    } else if end(old) + i64::from(old.0) == end(reference) + i64::from(reference.0) {
+        (i64::from(new.1) - i64::from(len)).div_euclid(2)
+    } else {
+        i64::from(old.0) - i64::from(reference.0)
+    };
+    saturate(i64::from(new.0) + offset)
+}
+
+/// [`reflow`] for the active `displays`, where `resized` holds the new size and rotation of
+/// those that change (their position is ignored). The result covers every active display,
+/// renormalized so the primary stays at (0,0), ready for
+/// [`apply_source_modes`](crate::display::apply_source_modes).
+///
+/// # Errors
+///
+/// Returns [`DisplayError::Overlap`] if the reflowed displays would overlap, or
+/// [`DisplayError::DesktopOutOfRange`] if they wouldn't fit the virtual desktop.
+pub fn reflow_layout(displays: &[DisplayInfo], resized: &[SourceMode]) -> Result<Vec<SourceMode>> {
+    let active: Vec<&DisplayInfo> = displays.iter().filter(|d| d.active).collect();
+    let sources: Vec<SourceMode> = active
+        .iter()
+        .map(|d| resized.iter().find(|s| s.source_id == d.source_id).copied().unwrap_or_else(|| SourceMode::of(d)))
+        .collect();
+    let old: Vec<(u32, Rect)> = active.iter().map(|d| (d.source_id, Rect::of(d))).collect();
+    let sizes: Vec<(u32, (u32, u32))> = sources.iter().map(|s| (s.source_id, (s.width, s.height))).collect();
+    let mut layout = reflow(&old, &sizes);
+
+    if let Some((a, b)) = find_overlap(&layout) {
+        return Err(DisplayError::Overlap(a, b));
+    }
+    if let Some(primary) = active.iter().find(|d| d.primary) {
+        fit_desktop(&mut layout, primary.source_id)?;
+    }
+    Ok(sources
+        .iter()
+        .zip(layout)
+        .map(|(source, (_, rect))| SourceMode { x: rect.x, y: rect.y, ..*source })
+        .collect())
+}
+
+/// Widest and tallest virtual desktop Windows accepts, in pixels.
+pub const MAX_DESKTOP_SPAN: u32 = 32767;
+
+/// A quick layout for all active displays, see [`preset_layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Left to right, top-aligned.
+    Row,
+    /// Top to bottom, left-aligned.
+    Column,
+    /// Rows of `columns` displays; each row starts below the tallest display of the one above.
+    Grid { columns: usize },
+}
+
+/// Lays out the active `displays` as `preset`, each at its current size. `order` lists source
+/// ids to place first, in that order; the others follow by current position (x for a row, y
+/// for a column, reading order for a grid). The result is renormalized so the primary display
+/// stays at (0,0).
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DisplayNotFound`] if `order` names a display that isn't active, or
+/// [`DisplayError::DesktopTooLarge`] if the layout spans more than [`MAX_DESKTOP_SPAN`], or
+/// [`DisplayError::DesktopOutOfRange`] if it reaches past the coordinates Windows accepts
+/// around the primary.
+pub fn preset_layout(displays: &[DisplayInfo], order: &[u32], preset: Preset) -> Result<Vec<SourceMode>> {
+    let mut rest: Vec<&DisplayInfo> = displays.iter().filter(|d| d.active).collect();
+    match preset {
+        Preset::Row => rest.sort_by_key(|d| (d.position_x, d.position_y)),
+        Preset::Column | Preset::Grid { .. } => rest.sort_by_key(|d| (d.position_y, d.position_x)),
+    }
+    let mut ordered = Vec::with_capacity(rest.len());
+    for &id in order {
+        if ordered.iter().any(|d: &&DisplayInfo| d.source_id == id) {
+            continue;
+        }
+        let index = rest
+            .iter()
+            .position(|d| d.source_id == id)
+            .ok_or(DisplayError::DisplayNotFound(id))?;
+        ordered.push(rest.remove(index));
+    }
+    ordered.append(&mut rest);
+
+    let columns = match preset {
+        Preset::Row => ordered.len(),
+        Preset::Column => 1,
+        Preset::Grid { columns } => columns,
+    }
+    .max(1);
+    let mut layout = Vec::with_capacity(ordered.len());
+    let (mut x, mut y, mut row_height) = (0_i32, 0_i32, 0);
+    for (i, display) in ordered.iter().enumerate() {
+        if i > 0 && i % columns == 0 {
+            (x, y, row_height) = (0, y.saturating_add_unsigned(row_height), 0);
+        }
+        layout.push((display.source_id, Rect { x, y, width: display.width, height: display.height }));
+        x = x.saturating_add_unsigned(display.width);
+        row_height = row_height.max(display.height);
+    }
+
+    if let Some(bounds) = layout.iter().map(|(_, rect)| *rect).reduce(|a, b| a.union(&b))
+        && (bounds.width > MAX_DESKTOP_SPAN || bounds.height > MAX_DESKTOP_SPAN)
+    {
+        return Err(DisplayError::DesktopTooLarge { width: bounds.width, height: bounds.height });
+    }
+    if let Some(primary) = ordered.iter().find(|d| d.primary) {
+        fit_desktop(&mut layout, primary.source_id)?;
+    }
+
+    Ok(ordered
+        .iter()
+        .zip(layout)
+        .map(|(d, (_, rect))| SourceMode { x: rect.x, y: rect.y, ..SourceMode::of(d) })
+        .collect())
+}
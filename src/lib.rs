@@ -1 +1,66 @@
+//! Enumerate and change display resolution, scaling and layout on Windows.
+//!
+//! Only the `core` modules are built by default. The features `ddcci`, `profiles`, `daemon`,
+//! `watch` and `notify` add the rest; `cli` enables everything the binary needs.
+
+#[cfg(feature = "profiles")]
+pub mod a11y;
+pub mod apply_log;
+#[cfg(feature = "profiles")]
+pub mod auto_revert;
+pub mod batch;
+pub mod blur;
+#[cfg(feature = "daemon")]
+pub mod client;
+#[cfg(feature = "profiles")]
+pub mod config;
+pub mod copy;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(feature = "ddcci")]
+pub mod ddc;
 pub mod display;
+pub mod doctor;
+pub mod edid;
+pub mod explain;
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "profiles")]
+pub mod groups;
+#[cfg(feature = "daemon")]
+pub mod handles;
+#[cfg(feature = "watch")]
+pub mod hotkeys;
+pub mod icc;
+pub mod identify;
+pub mod layout;
+pub mod link;
+pub mod metrics;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+#[cfg(feature = "notify")]
+pub mod notify;
+pub mod plan;
+#[cfg(feature = "profiles")]
+pub mod policy;
+#[cfg(feature = "profiles")]
+pub mod profile;
+pub mod progress;
+pub mod raw;
+pub mod report;
+pub mod rescue;
+#[cfg(feature = "watch")]
+pub mod rules;
+#[cfg(feature = "watch")]
+pub mod schedule;
+pub mod selector;
+pub mod selftest;
+pub mod settle;
+#[cfg(feature = "profiles")]
+pub mod startup;
+pub mod state;
+pub mod table;
+pub mod throttle;
+#[cfg(feature = "watch")]
+pub mod watch;
@@ -0,0 +1,147 @@
+//! The signal on the link to each monitor and the bandwidth modes need, for telling a mode the
+//! cable or port can't carry (HDMI 2.0 instead of 2.1, DP HBR2 instead of HBR3) from one the
+//! driver rejects for other reasons.
+//!
+//! Windows doesn't report what a link could carry, only the signal it carries now. Comparing a
+//! mode's estimated bandwidth with that is a heuristic: a link renegotiates for a bigger mode
+//! when it can, so the comparison is only worth mentioning once applying the mode failed.
+
+use crate::display::{ColorEncoding, Connector, DisplayError, DisplayInfo, Result};
+use crate::raw::{self, query_display_config};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use windows::Win32::Devices::Display::QDC_ONLY_ACTIVE_PATHS;
+
+/// Horizontal blanking of CVT reduced blanking v2 timings, in pixels.
+pub const CVT_RB_H_BLANK: u32 = 80;
+
+/// Minimum vertical blanking of CVT reduced blanking v2 timings, in microseconds.
+pub const CVT_RB_MIN_V_BLANK_US: u64 = 460;
+
+/// Bits per color channel assumed when Windows doesn't report the link's.
+const DEFAULT_BITS_PER_COLOR: u32 = 8;
+
+/// How far in percent a mode's estimate has to exceed the current signal for
+/// [`bandwidth_hint`], so the current mode's own timing never counts.
+const HINT_MARGIN_PERCENT: u64 = 5;
+
+/// Approximate pixel clock in Hz of `width`x`height` at `refresh` Hz with CVT reduced blanking
+/// v2 timings, which are about the leanest a monitor accepts.
+#[must_use]
+pub fn pixel_rate(width: u32, height: u32, refresh: u32) -> u64 {
+    let refresh = u64::from(refresh.max(1));
+    let frame_us = 1_000_000 / refresh;
+    // Lines the vertical blanking needs on top of the active ones, rounded up.
+    let active_us = frame_us.saturating_sub(CVT_RB_MIN_V_BLANK_US).max(1);
+    let total_lines = (u64::from(height) * frame_us).div_ceil(active_us);
+    u64::from(width + CVT_RB_H_BLANK) * total_lines * refresh
+}
+
+/// Bits on the link per pixel for `bits_per_color` (0 for unknown, taken as 8) and `encoding`;
+/// chroma subsampling sends fewer.
+#[must_use]
+pub fn bits_per_pixel(bits_per_color: u32, encoding: ColorEncoding) -> u32 {
+    let bits = if bits_per_color == 0 { DEFAULT_BITS_PER_COLOR } else { bits_per_color };
+    match encoding {
+        ColorEncoding::YCbCr422 => 2 * bits,
+        ColorEncoding::YCbCr420 => bits * 3 / 2,
+        ColorEncoding::Intensity => bits,
+        ColorEncoding::Rgb | ColorEncoding::YCbCr444 => 3 * bits,
+    }
+}
+
+/// Approximate bits per second `width`x`height` at `refresh` Hz needs, see [`pixel_rate`].
+#[must_use]
+pub fn mode_bandwidth(width: u32, height: u32, refresh: u32, bits_per_pixel: u32) -> u64 {
+    pixel_rate(width, height, refresh) * u64::from(bits_per_pixel)
+}
+
+/// The signal a display gets now, from its target mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkInfo {
+    /// The connector the monitor plugs into.
+    pub connector: Connector,
+    /// What the GPU drives underneath, e.g. DP for an HDMI adapter; the same as `connector`
+    /// without one.
+    pub base_connector: Connector,
+    /// Pixel clock in Hz.
+    pub pixel_rate: u64,
+    /// The visible pixels and the whole frame with blanking.
+    pub active: (u32, u32),
+    pub total: (u32, u32),
+    pub bits_per_pixel: u32,
+}
+
+impl LinkInfo {
+    /// Bits per second the signal carries.
+    #[must_use]
+    pub fn bandwidth(&self) -> u64 {
+        self.pixel_rate * u64::from(self.bits_per_pixel)
+    }
+}
+
+impl fmt::Display for LinkInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.connector)?;
+        if self.base_connector != self.connector {
+            write!(f, " (over {})", self.base_connector)?;
+        }
+        let tenths_mhz = (self.pixel_rate + 50_000) / 100_000;
+        write!(
+            f,
+            ", {}x{} of {}x{} at {}.{} MHz, {} bits per pixel, ~{} Gbps",
+            self.active.0,
+            self.active.1,
+            self.total.0,
+            self.total.1,
+            tenths_mhz / 10,
+            tenths_mhz % 10,
+            self.bits_per_pixel,
+            gbps(self.bandwidth())
+        )
+    }
+}
+
+/// `bits` per second in Gbps with one decimal, or none from 10 Gbps on.
+fn gbps(bits: u64) -> String {
+    let tenths = (bits + 50_000_000) / 100_000_000;
+    if tenths < 100 { format!("{}.{}", tenths / 10, tenths % 10) } else { format!("{}", (bits + 500_000_000) / 1_000_000_000) }
+}
+
+/// Why `width`x`height` at `refresh` Hz may have failed on `link`: it needs more bandwidth
+/// than the link carries now. `None` if it doesn't.
+#[must_use]
+pub fn bandwidth_hint(link: &LinkInfo, width: u32, height: u32, refresh: u32) -> Option<String> {
+    let needed = mode_bandwidth(width, height, refresh, link.bits_per_pixel);
+    let carried = link.bandwidth();
+    (carried > 0 && needed * 100 > carried * (100 + HINT_MARGIN_PERCENT)).then(|| {
+        format!(
+            "{width}x{height} @ {refresh}Hz needs ~{} Gbps; the current {} link is reporting ~{} Gbps, check the cable and port",
+            gbps(needed),
+            link.base_connector,
+            gbps(carried)
+        )
+    })
+}
+
+/// The link of `display` as the target mode describes it.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DisplayNotFound`] if the display has no active path or target mode,
+/// or an error if the configuration can't be queried.
+pub fn link_info(display: &DisplayInfo) -> Result<LinkInfo> {
+    let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    let not_found = || DisplayError::DisplayNotFound(display.source_id);
+    let path = raw::find_adapter_path(&paths, display.adapter_id, display.source_id).ok_or_else(not_found)?;
+    let signal = raw::target_mode(path, &modes).ok_or_else(not_found)?.targetVideoSignalInfo;
+    let base_connector = raw::target_base_type(path).map_or(display.connector, Connector::from);
+    Ok(LinkInfo {
+        connector: display.connector,
+        base_connector,
+        pixel_rate: signal.pixelRate,
+        active: (signal.activeSize.cx, signal.activeSize.cy),
+        total: (signal.totalSize.cx, signal.totalSize.cy),
+        bits_per_pixel: bits_per_pixel(display.bits_per_color, display.color_encoding),
+    })
+}
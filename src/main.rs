@@ -1,82 +1,3214 @@
 use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
-use display_tuner::display::{apply_display_config, enumerate_displays, DisplayConfig};
+use clap::error::{ContextKind, ContextValue};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use display_tuner::display::{apply_display_config, apply_display_config_via, apply_display_config_with, set_display_mode_legacy, ApplyBackend, ApplyOrder, ApplyPolicy, ApplyReport, ApplyTimings, DisplayTuner, RetryPolicy, set_apply_order, set_apply_timeout, set_bit_depth, set_dynamic_refresh, clear_custom_scaling, effective_size, scaling_pending, set_broadcast, set_persist, set_session_check, set_retry_policy, set_sdr_white_level, enumerate_displays, enumerate_inactive_displays, set_refresh_rate, refresh_targets, supported_modes, swap_display_configs, validate_display_config, Connector, match_mode, native_mode, ModeMatch, Rotation, SourceMode, DisplayConfig, DisplayConfigBuilder, DisplayError, DisplayInfo, RefreshRate, native_resolution_advice, parse_scaling};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+use tracing_subscriber::EnvFilter;
+use display_tuner::a11y::set_large_text;
+use display_tuner::apply_log::set_apply_log;
+use display_tuner::auto_revert;
+use display_tuner::batch::{apply_batch, plan_batch, undo_batch, BatchOutcome, DisplayClause};
+use display_tuner::blur::blur_check;
+use display_tuner::copy::{copy_config, CopyReport};
+use display_tuner::daemon::{self, send_request, Request};
+use display_tuner::display::{apply_combined, apply_source_modes, ConfigSnapshot, find_mode, find_scaled_mode, parse_mode, parse_refresh_rate, parse_resolution, CombinedChange, DisplayMode, ModeSpec};
+use display_tuner::doctor::{diagnose, DoctorRequest};
+use display_tuner::link::{bandwidth_hint, link_info};
+use display_tuner::progress::{cancel_on_ctrl_c, DisplayProgress};
+use display_tuner::selftest::selftest;
+use display_tuner::settle::{wait_for_configuration, wait_for_desktop};
+use display_tuner::icc::{color_status, set_profile_scope, ProfileScope};
+use display_tuner::identify::identify;
+use display_tuner::edid::load_edid;
+use display_tuner::explain::load_trace;
+use display_tuner::expr::FilterExpr;
+use display_tuner::config::{write_template, Config, OutputFormat};
+use display_tuner::groups::{resolve_group, GroupConfig};
+use display_tuner::hotkeys::{register_hotkeys, Hotkey, HotkeyConfig};
+use display_tuner::notify;
+use display_tuner::layout::{desktop_summary, place_display, preset_layout, reflow_layout, Align, Placement, Preset, Rect};
+use display_tuner::plan::{execute, plan, ChangeSet, Target};
+use display_tuner::policy::{check_policy, CheckResult, Policy, ScalingExpectation};
+use display_tuner::profile::{apply_profile, apply_profile_with_progress, diff_profile, match_displays, Profile};
+use display_tuner::report::{CommandReport, ErrorReport, Outcome, USAGE_ERROR};
+use display_tuner::raw::{console_monitor_device, is_remote_session, set_process_per_monitor_aware, sign_out, virtual_screen};
+use display_tuner::rescue::rescue_offscreen_windows;
+use display_tuner::rules::{apply_rules, RuleSet};
+use display_tuner::schedule::{unix_time, ActionQueue, TimeOfDay};
+use display_tuner::startup;
+use display_tuner::state::DisplayState;
+use display_tuner::table::{enable_colors, render_csv, render_table};
+use display_tuner::throttle::{set_apply_interval, Coalescer};
+use display_tuner::watch::{power_source, watch_events, PowerSource, WatchEvent};
+use display_tuner::selector::{display_at_index, display_of_focused_window, display_under_cursor, filter_displays, id_not_found, resolve_alias, sort_displays, DisplayFilter, SortKey};
 
+// Each flag is an independent switch on the command line.
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug)]
 #[command(name = "display-tuner", about = "Tune Windows display resolution and scaling", version)]
 struct Cli {
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+    /// Retry applying this many times when Windows reports a transient failure, and once when
+    /// another program changes the displays mid-apply [default: 0]
+    #[arg(long, global = true)]
+    retries: Option<u32>,
+    /// Wait before the first retry, doubled for each further one [default: 500]
+    #[arg(long, global = true, value_name = "MS")]
+    retry_delay: Option<u64>,
+    /// Forward the command to a running daemon (list, set, profile apply, revert)
+    #[arg(long, global = true)]
+    via_daemon: bool,
+    /// Don't save changes for Windows to restore after a reboot, and don't record them for
+    /// `reapply`
+    #[arg(long, global = true)]
+    no_persist: bool,
+    /// Don't notify running apps after a scaling change (for scripts making many changes)
+    #[arg(long, global = true)]
+    no_broadcast: bool,
+    /// Append one JSON line per apply attempt to this file (or set `DISPLAY_TUNER_LOG_JSON`)
+    #[arg(long, global = true, value_name = "PATH")]
+    log_json: Option<PathBuf>,
+    /// Give up on an apply call that hasn't returned after this many seconds (exit code 3)
+    #[arg(long, global = true, value_name = "SECONDS")]
+    timeout: Option<u64>,
+    /// Restore the configuration from before a command changing the displays unless another
+    /// display-tuner command, e.g. `confirm`, runs within this many seconds, even if this
+    /// process is gone by then (0 turns off `safety.auto_revert_seconds`)
+    #[arg(long, global = true, value_name = "SECONDS")]
+    timeout_revert: Option<u64>,
+    /// After a command changing the displays, wait until they show the new configuration, are
+    /// back from the mode switch and their work areas are updated, for at most this many
+    /// seconds (10 without a value); warns if they don't settle in time
+    #[arg(long, global = true, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "10")]
+    wait_for_stable: Option<u64>,
+    /// Refuse to run outside the interactive user session, e.g. as a service or SYSTEM, where
+    /// Windows reports no displays or ignores changes; `off` tries anyway
+    #[arg(long, global = true, value_enum, default_value_t = SessionCheckArg::On)]
+    session_check: SessionCheckArg,
+    /// When a display changes both scaling and resolution, which goes first [default: scaling
+    /// first when it goes down, resolution first when it goes up, so the desktop is never shown
+    /// larger than before or after]
+    #[arg(long, global = true, value_enum)]
+    apply_order: Option<OrderArg>,
+    /// Print a single JSON object on stdout however the command ends: `{"ok":true,"result":...}`
+    /// with the command's JSON output, if any, or the error's `code`, `message` and `details`
+    /// with `"ok":false`; text output goes to stderr
+    #[arg(long, global = true)]
+    json_errors: bool,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SessionCheckArg {
+    On,
+    Off,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OrderArg {
+    ScalingFirst,
+    ResolutionFirst,
+}
+
+impl From<OrderArg> for ApplyOrder {
+    fn from(order: OrderArg) -> Self {
+        match order {
+            OrderArg::ScalingFirst => Self::ScalingFirst,
+            OrderArg::ResolutionFirst => Self::ResolutionFirst,
+        }
+    }
+}
+
+/// Environment variable naming the apply log when `--log-json` isn't given.
+const LOG_JSON_ENV: &str = "DISPLAY_TUNER_LOG_JSON";
+
+/// Milliseconds before the first retry unless `--retry-delay` or `config.toml` says otherwise.
+const DEFAULT_RETRY_DELAY_MS: u64 = 500;
+
+/// Seconds the interactive mode waits for confirmation before reverting.
+const INTERACTIVE_CONFIRM_SECS: u64 = 15;
+
+/// Seconds `set --display` waits for confirmation before putting the display showing the
+/// console back the way it was.
+const CONSOLE_CHANGE_CONFIRM_SECS: u64 = 15;
+
+/// Exit code of `set --allow-changes` when the OS applied the change with adjustments.
+const ADJUSTED_EXIT_CODE: u8 = 2;
+
+/// Exit code when an apply call ran over `--timeout`.
+const TIMED_OUT_EXIT_CODE: u8 = 3;
+
+/// Exit code when Windows reports no active display, e.g. on a headless machine.
+const NO_DISPLAYS_EXIT_CODE: u8 = 4;
+
+/// Quiet period after a display change before the watch loop re-evaluates its rules, so a
+/// burst of notifications during a dock/undock is handled once.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Quiet period after a power source change, so a loose charger connector flapping between AC
+/// and battery doesn't re-apply profiles every second.
+const POWER_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// Changes to the same display are at least this far apart in the resident modes (daemon,
+/// watch, hotkeys), so a burst of commands can't send the shell into a redraw loop.
+const MIN_APPLY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Set by `--format json` and `--json-errors`, whose document is all that goes to stdout.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--json-errors`.
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// The JSON document of a command run with `--json-errors`, printed as the `result` of its
+/// [`Outcome`].
+static JSON_RESULT: Mutex<Option<serde_json::Value>> = Mutex::new(None);
+
+/// `--wait-for-stable` in seconds for `set`, which knows what each display should show; 0 when
+/// not given.
+static STABLE_WAIT_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// `println!` for what commands print as text, which goes to stderr with `--format json` or
+/// `--json-errors`, where stdout only gets the JSON document.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `print!` for a question answered on the same line, see [`say!`].
+macro_rules! ask {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprint!($($arg)*);
+        } else {
+            print!($($arg)*);
+            io::stdout().flush()?;
+        }
+    };
+}
+
+/// Longest the watch loop sleeps before checking the clock against its schedule. Timeouts
+/// don't necessarily count time spent in sleep, so waiting for the next entry in one go could
+/// fire it late.
+const SCHEDULE_POLL: Duration = Duration::from_mins(1);
+
+/// How long `complete-ids` reuses its cached enumeration, keeping tab completion fast.
+const COMPLETION_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Injected at the top of the generated PowerShell completer: values for `--id` and `--name`
+/// come from the displays connected at completion time.
+const POWERSHELL_DYNAMIC_COMPLETER: &str = r#"
+    $previous = $commandAst.CommandElements |
+        Where-Object { $_.Extent.EndOffset -lt $cursorPosition } |
+        Select-Object -Last 1
+    $field = @{ '--id' = 0; '--name' = 1 }[[string]$previous]
+    if ($null -ne $field) {
+        display-tuner complete-ids | ForEach-Object {
+            $value = ($_ -split "`t", 2)[$field]
+            if ($value -like "$wordToComplete*") {
+                $text = if ($value -match '\s') { "'$($value -replace "'", "''")'" } else { $value }
+                $label = $_ -replace "`t", '  '
+                [CompletionResult]::new($text, $label, [CompletionResultType]::ParameterValue, $label)
+            }
+        }
+        return
+    }
+"#;
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// List detected displays and their current settings
-    List,
+    List(ListArgs),
     /// Apply settings
     Set(SetArgs),
+    /// Exchange position, resolution, scaling and primary status between two displays
+    Swap {
+        /// Source id of the first display
+        first: u32,
+        /// Source id of the second display
+        second: u32,
+    /// Afterwards, move windows left off-screen onto the nearest display
+        #[arg(long)]
+        rescue_windows: bool,
+    },
+    /// Position a display relative to another one
+    Arrange(ArrangeArgs),
+    /// Copy resolution, refresh rate, scaling, rotation and scale mode from one display to
+    /// others (position and primary status stay); exit code 1 if a property fails to apply
+    CopyConfig {
+        /// Source id of the display to copy from
+        #[arg(long)]
+        from: u32,
+        /// Source id of the display to copy to, or `all` for every other display
+        #[arg(long, value_name = "ID|all", value_parser = parse_copy_target)]
+        to: CopyTarget,
+    },
+    /// Save, apply and compare display profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Keep displays compliant with a rules file, re-applying it on every display change, and
+    /// switch profiles when the power source changes
+    Watch {
+        /// TOML file with `[[rule]]` entries
+        #[arg(long, required_unless_present_any = ["on_battery", "on_ac", "show_queue"])]
+        rules: Option<PathBuf>,
+        /// Profile to apply when running on battery
+        #[arg(long, value_name = "PROFILE")]
+        on_battery: Option<String>,
+        /// Profile to apply when running on AC power
+        #[arg(long, value_name = "PROFILE")]
+        on_ac: Option<String>,
+        /// Print the schedule entries that fire in the next 24 hours and exit
+        #[arg(long, requires = "rules")]
+        dry_run: bool,
+        /// List the schedule entries queued for a display that wasn't connected, and exit
+        #[arg(long, conflicts_with_all = ["rules", "on_battery", "on_ac", "dry_run"])]
+        show_queue: bool,
+        /// Show a toast notification for each change made
+        #[arg(long, conflicts_with_all = ["dry_run", "show_queue"])]
+        notify: bool,
+    },
+    /// Serve commands from other processes on the `\\.\pipe\display-tuner` named pipe
+    Daemon {
+        /// Show a toast notification with a Revert button for each change made
+        #[arg(long)]
+        notify: bool,
+    },
+    /// Restore the last successfully applied settings of the connected displays
+    Reapply,
+    /// Run `reapply` at logon
+    Startup {
+        #[command(subcommand)]
+        command: StartupCommand,
+    },
+    /// Accessibility presets
+    A11y {
+        #[command(subcommand)]
+        command: A11yCommand,
+    },
+    /// Diagnose why a configuration can't be applied (exit code 1 if it can't)
+    Doctor {
+        /// Display source id; defaults to the primary display
+        #[arg(long)]
+        id: Option<u32>,
+        #[arg(long)]
+        width: Option<u32>,
+        #[arg(long)]
+        height: Option<u32>,
+        /// Scaling percentage
+        #[arg(long)]
+        scaling: Option<i32>,
+        /// Refresh rate in Hz, checked against the link's bandwidth
+        #[arg(long)]
+        refresh: Option<u32>,
+    },
+    /// Prove the display APIs work on this machine without changing anything visible: each step
+    /// timed, exit code 1 if any fails
+    Selftest {
+        /// Also apply the current configuration and scaling again, to check write permissions
+        #[arg(long)]
+        destructive: bool,
+        /// Print the report as JSON
+        #[arg(long, value_enum, default_value_t)]
+        format: ReportFormat,
+    },
+    /// Check the displays against a policy without changing anything (exit code 1 if any
+    /// display deviates)
+    Check {
+        /// Scaling every display should have: `recommended` or a percentage
+        #[arg(long, value_name = "SCALING", required_unless_present = "file")]
+        expect_scaling: Option<ScalingExpectation>,
+        /// JSON policy with per-display resolutions and scaling and the expected primary display
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// List windows likely to be blurry: those of processes that aren't per-monitor DPI aware,
+    /// on displays not at 100% scaling
+    BlurCheck {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show or change which color profile associations Windows uses for a display
+    Icc {
+        #[command(subcommand)]
+        command: IccCommand,
+    },
+    /// Save the complete display configuration to a file, or restore it exactly
+    State {
+        #[command(subcommand)]
+        command: StateCommand,
+    },
+    /// Undo the last change made through the daemon
+    Revert,
+    /// Print the running daemon's counters as JSON: applies, failures by error code, the last
+    /// topology change, event loop lag and latency histograms
+    Stats,
+    /// Keep the changes of the last command, cancelling its `--timeout-revert` (any other
+    /// command does so too)
+    Confirm,
+    /// The watchdog `--timeout-revert` starts
+    #[command(name = "auto-revert", hide = true)]
+    AutoRevert { id: u64 },
+    /// Show each display's number in large type on the display itself
+    Identify {
+        /// How long to show the numbers
+        #[arg(long, value_name = "SECONDS", default_value_t = 3)]
+        seconds: u64,
+    },
+    /// Run the commands bound to global hotkeys until stopped
+    Hotkeys {
+        /// TOML file with a `[hotkeys]` table mapping key combinations to commands
+        #[arg(long)]
+        config: PathBuf,
+        /// Presses of the same hotkey within this many milliseconds run its command once
+        #[arg(long, value_name = "MS", default_value_t = 300)]
+        coalesce: u64,
+    },
+    /// Pick a display and settings from prompts (default without a subcommand)
+    Interactive,
+    /// Show or create the file with defaults for the command line flags
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print `<id>\t<name>` for each display, used by the PowerShell completer
+    #[command(hide = true)]
+    CompleteIds,
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileCommand {
+    /// Save the current settings of all displays
+    Save { name: String },
+    /// Apply a saved profile to the connected displays
+    Apply {
+        name: String,
+        /// Print the result, or the error, as JSON
+        #[arg(long, value_enum, default_value = "text")]
+        format: ReportFormat,
+    },
+    /// Show what applying a profile would change; exits with 1 if anything differs
+    Diff { name: String },
+    /// List saved profiles
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print where `config.toml` is expected
+    Path,
+    /// Write a commented template to that path (never overwrites an existing file)
+    Init,
+}
+
+#[derive(Subcommand, Debug)]
+enum IccCommand {
+    /// Show per display whether the user's or the system's profiles apply, the default profile
+    /// of each, and whether the one in effect holds an HDR calibration (MHC2)
+    Status {
+        /// Display source id; defaults to every active display
+        #[arg(long)]
+        id: Option<u32>,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Use the current user's profile associations for a display, or the system-wide ones
+    Scope {
+        /// Display source id
+        #[arg(long)]
+        id: u32,
+        #[arg(value_enum)]
+        scope: ScopeArg,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StateCommand {
+    /// Write every path, mode and the per-display scaling to FILE
+    Save { file: PathBuf },
+    /// Apply a saved state as it was; refused if the attached monitors differ from it
+    Restore {
+        file: PathBuf,
+        /// Restore the monitors that are attached and leave the missing ones out
+        #[arg(long)]
+        partial: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StartupCommand {
+    /// Register `display-tuner reapply` to run when you sign in
+    Install,
+    /// Remove the logon entry
+    Remove,
+    /// Show whether the logon entry is installed
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+enum A11yCommand {
+    /// Scale every display two steps above its recommended scaling, or back to what it had
+    LargeText {
+        #[arg(value_enum)]
+        state: SwitchArg,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SwitchArg {
+    On,
+    Off,
+    Toggle,
+}
+
+// Each flag is an independent switch on the command line.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(clap::Args, Debug)]
+struct ListArgs {
+    /// Only displays whose name contains this text (case-insensitive)
+    #[arg(long)]
+    name: Option<String>,
+    /// Only displays at least this many pixels wide
+    #[arg(long)]
+    min_width: Option<u32>,
+    /// Only displays on this connector type
+    #[arg(long, value_enum)]
+    connector: Option<ConnectorArg>,
+    /// Only active displays
+    #[arg(long, conflicts_with = "inactive")]
+    active: bool,
+    /// Only inactive displays: connected monitors that are turned off in the Windows display
+    /// settings, which aren't listed otherwise
+    #[arg(long)]
+    inactive: bool,
+    /// Sort order of the output
+    #[arg(long, value_enum, default_value_t = SortArg::Id)]
+    sort: SortArg,
+    /// Print one compact line per display
+    #[arg(long, conflicts_with = "format")]
+    brief: bool,
+    /// Print machine-readable output instead of the table
+    #[arg(long, value_enum)]
+    format: Option<ListFormat>,
+    /// Read each monitor's serial number, model name and date of manufacture from its EDID
+    /// (slower)
+    #[arg(long)]
+    edid: bool,
+    /// Show the raw values behind each display's scaling, modes and name (under `debug` in
+    /// JSON), for comparing machines
+    #[arg(long)]
+    explain: bool,
+    /// Only displays in this group (see `groups.toml`)
+    #[arg(long, value_name = "GROUP")]
+    group: Option<String>,
+    /// Only the display with this alias (see `groups.toml`)
+    #[arg(long, value_name = "ALIAS", conflicts_with = "group")]
+    alias: Option<String>,
+    /// Only displays matching a filter expression, e.g. "connector=hdmi && width<2560"
+    #[arg(long = "where", value_name = "EXPR")]
+    filter_expr: Option<FilterExpr>,
+    /// Show the groups each display belongs to and warn about members that aren't connected
+    #[arg(long)]
+    groups: bool,
+    /// Don't highlight the table in color (also off when piped or with `NO_COLOR` set)
+    #[arg(long)]
+    no_color: bool,
+    /// Highlight the table even if `config.toml` turns colors off
+    #[arg(long, conflicts_with = "no_color")]
+    color: bool,
+    /// Probe and show what can be changed on each display (takes a few seconds)
+    #[arg(long)]
+    capabilities: bool,
+    /// Number the modes of each active display for `set --mode`, highest resolution and refresh
+    /// rate first
+    #[arg(long)]
+    modes: bool,
+    /// Summarize the virtual desktop: its bounds, total pixels and each display's rect
+    #[arg(long)]
+    desktop: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ConnectorArg {
+    Dp,
+    Hdmi,
+    Internal,
+}
+
+impl From<ConnectorArg> for Connector {
+    fn from(arg: ConnectorArg) -> Self {
+        match arg {
+            ConnectorArg::Dp => Self::DisplayPort,
+            ConnectorArg::Hdmi => Self::Hdmi,
+            ConnectorArg::Internal => Self::Internal,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ScopeArg {
+    User,
+    System,
+}
+
+impl From<ScopeArg> for ProfileScope {
+    fn from(scope: ScopeArg) -> Self {
+        match scope {
+            ScopeArg::User => Self::User,
+            ScopeArg::System => Self::System,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DrrArg {
+    On,
+    Off,
+}
+
+/// Output of the commands that change displays, and of `selftest`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ReportFormat {
+    /// Messages for humans
+    #[default]
+    Text,
+    /// A single JSON document on stdout, also on failure; messages go to stderr
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ListFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SortArg {
+    Id,
+    Name,
+    Resolution,
+}
+
+impl From<SortArg> for SortKey {
+    fn from(arg: SortArg) -> Self {
+        match arg {
+            SortArg::Id => Self::Id,
+            SortArg::Name => Self::Name,
+            SortArg::Resolution => Self::Resolution,
+        }
+    }
+}
+
+impl ListArgs {
+    fn filter(&self) -> DisplayFilter {
+        let active = match (self.active, self.inactive) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        DisplayFilter {
+            name: self.name.clone(),
+            min_width: self.min_width,
+            connector: self.connector.map(Connector::from),
+            active,
+            expr: self.filter_expr.clone(),
+            ..DisplayFilter::default()
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("placement")))]
+struct ArrangeArgs {
+    /// Source id of the display to move
+    #[arg(long, required_unless_present = "preset", requires = "placement")]
+    id: Option<u32>,
+    /// Place it left of this display
+    #[arg(long, value_name = "ID", group = "placement")]
+    left_of: Option<u32>,
+    /// Place it right of this display
+    #[arg(long, value_name = "ID", group = "placement")]
+    right_of: Option<u32>,
+    /// Place it above this display
+    #[arg(long, value_name = "ID", group = "placement")]
+    above: Option<u32>,
+    /// Place it below this display
+    #[arg(long, value_name = "ID", group = "placement")]
+    below: Option<u32>,
+    /// Alignment along the shared edge
+    #[arg(long, value_enum, default_value_t = AlignArg::Top)]
+    align: AlignArg,
+    /// Lay out all active displays at their current resolution instead of moving one; the
+    /// primary display stays at the top-left corner of the desktop
+    #[arg(long, value_enum, conflicts_with_all = ["id", "placement"])]
+    preset: Option<PresetArg>,
+    /// Source ids in the order to place them, e.g. 2,1,3; the others follow by position
+    #[arg(long, value_name = "ID,...", value_delimiter = ',', requires = "preset")]
+    order: Vec<u32>,
+    /// Displays per row of `--preset grid`
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), required_if_eq("preset", "grid"))]
+    cols: Option<u32>,
+    /// Print where each display would go without applying it
+    #[arg(long)]
+    dry_run: bool,
+    /// Afterwards, move windows left off-screen onto the nearest display
+    #[arg(long, conflicts_with = "dry_run")]
+    rescue_windows: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PresetArg {
+    Row,
+    Column,
+    Grid,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum AlignArg {
+    Top,
+    Left,
+    Center,
+    Bottom,
+    Right,
+}
+
+impl From<AlignArg> for Align {
+    fn from(arg: AlignArg) -> Self {
+        match arg {
+            AlignArg::Top | AlignArg::Left => Self::Start,
+            AlignArg::Center => Self::Center,
+            AlignArg::Bottom | AlignArg::Right => Self::End,
+        }
+    }
+}
+
+/// `--scaling` value: a percentage or ratio (`150`, `150%`, `1.5x`), or `+N`/`-N` steps through
+/// the supported values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalingArg {
+    Percent(i32),
+    Steps(i32),
+}
+
+impl ScalingArg {
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        if text.starts_with(['+', '-']) {
+            text.parse().map(Self::Steps).map_err(|e| format!("{e}"))
+        } else {
+            parse_scaling(text)
+                .map(Self::Percent)
+                .ok_or_else(|| format!("expected a percentage like 150 or 150%, or a ratio like 1.5x, found `{text}`"))
+        }
+    }
+}
+
+fn parse_mode_arg(text: &str) -> std::result::Result<ModeSpec, String> {
+    parse_mode(text).ok_or_else(|| format!("expected a mode number from `list --modes` or WxH@HZ like 1920x1080@144, found `{text}`"))
+}
+
+fn parse_res_scale(text: &str) -> std::result::Result<u32, String> {
+    match text.trim().trim_end_matches('%').parse() {
+        Ok(percent @ 1..=400) => Ok(percent),
+        _ => Err(format!("expected a percentage from 1% to 400%, found `{text}`")),
+    }
+}
+
+fn parse_refresh(text: &str) -> std::result::Result<RefreshRate, String> {
+    parse_refresh_rate(text).ok_or_else(|| format!("expected a rate like 144 or 59.94, max or min, found `{text}`"))
+}
+
+/// Where `copy-config` copies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CopyTarget {
+    Id(u32),
+    All,
+}
+
+fn parse_copy_target(text: &str) -> std::result::Result<CopyTarget, String> {
+    if text.eq_ignore_ascii_case("all") {
+        return Ok(CopyTarget::All);
+    }
+    text.parse().map(CopyTarget::Id).map_err(|e| format!("{e}"))
 }
 
+fn parse_rotation(text: &str) -> std::result::Result<Rotation, String> {
+    match text.trim_end_matches('°') {
+        "0" => Ok(Rotation::Identity),
+        "90" => Ok(Rotation::Rotate90),
+        "180" => Ok(Rotation::Rotate180),
+        "270" => Ok(Rotation::Rotate270),
+        _ => Err("expected 0, 90, 180 or 270".to_string()),
+    }
+}
+
+fn parse_size(text: &str) -> std::result::Result<(u32, u32), String> {
+    parse_resolution(text).ok_or_else(|| format!("expected a positive WIDTHxHEIGHT, e.g. 2560x1440, found `{text}`"))
+}
+
+impl ArrangeArgs {
+    fn preset(&self) -> Option<Preset> {
+        Some(match self.preset? {
+            PresetArg::Row => Preset::Row,
+            PresetArg::Column => Preset::Column,
+            PresetArg::Grid => Preset::Grid {
+                columns: self.cols.and_then(|n| usize::try_from(n).ok()).expect("clap requires --cols for a grid"),
+            },
+        })
+    }
+
+    fn placement(&self) -> (Placement, u32) {
+        match (self.left_of, self.right_of, self.above, self.below) {
+            (Some(id), ..) => (Placement::LeftOf, id),
+            (_, Some(id), ..) => (Placement::RightOf, id),
+            (_, _, Some(id), _) => (Placement::Above, id),
+            (.., Some(id)) => (Placement::Below, id),
+            _ => unreachable!("clap requires one placement flag"),
+        }
+    }
+}
+
+// Each flag is an independent switch on the command line.
+#[allow(clippy::struct_excessive_bools)]
 #[derive(clap::Args, Debug)]
 struct SetArgs {
+    /// Alias of the target display (see `groups.toml`), e.g. `set left --scaling 125`
+    #[arg(value_name = "ALIAS", conflicts_with_all = ["id", "all", "current", "focused", "display_number", "group"])]
+    alias: Option<String>,
+    /// Apply to the displays matching a filter expression, e.g. "connector=hdmi && width<2560"
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        conflicts_with_all = ["alias", "id", "all", "current", "focused", "display_number", "group"]
+    )]
+    filter_expr: Option<FilterExpr>,
     /// Target display source id; omit applying to all or use --all
     #[arg(long)]
     id: Option<u32>,
     /// Apply to all displays (overrides --id)
     #[arg(long)]
     all: bool,
+    /// Apply to the display the mouse cursor is on
+    #[arg(long, conflicts_with_all = ["id", "all", "focused"])]
+    current: bool,
+    /// Apply to the display showing the focused window
+    #[arg(long, conflicts_with_all = ["id", "all"])]
+    focused: bool,
+    /// Apply to the display with this number in Windows Settings (see `identify`)
+    #[arg(long, value_name = "N", conflicts_with_all = ["id", "all", "current", "focused"])]
+    display_number: Option<u32>,
+    /// Apply to the Nth display (from 1) in the `Index` column of `list`, which counts all
+    /// displays in order of source id
+    #[arg(long, value_name = "N", conflicts_with_all = ["id", "all", "current", "focused", "display_number", "group"])]
+    index: Option<usize>,
+    /// Only change built-in panels (combines with the other selectors, e.g. `--all`)
+    #[arg(long, conflicts_with = "external_only")]
+    internal_only: bool,
+    /// Only change external displays (combines with the other selectors, e.g. `--all`)
+    #[arg(long)]
+    external_only: bool,
+    /// Apply to the connected displays of this group (see `groups.toml`)
+    #[arg(long, value_name = "GROUP", conflicts_with_all = ["id", "all", "current", "focused", "display_number"])]
+    group: Option<String>,
+    /// Resolution, e.g. 2560x1440; a size that's only supported the other way round is swapped
+    /// to match the orientation
+    #[arg(long, value_name = "WxH", value_parser = parse_size, conflicts_with_all = ["width", "height", "native"])]
+    res: Option<(u32, u32)>,
+    /// Change several displays at once, e.g. `--display 1:res=1920x1080,refresh=max,scaling=100
+    /// --display 2:disable` (keys: res, refresh, scaling, rotate); every clause is checked first
+    /// and the changes are applied together
+    #[arg(
+        long = "display",
+        value_name = "ID:KEY=VALUE,...",
+        conflicts_with_all = [
+            "alias", "filter_expr", "id", "all", "current", "focused", "display_number", "index", "group", "internal_only",
+            "external_only", "res", "width", "height", "scaling", "scaling_match", "effective", "refresh", "native",
+            "rotate", "legacy_api", "force_remote", "clear_custom_scaling", "bit_depth", "sdr_brightness",
+            "drr", "confirm", "dry_run", "no_flicker", "safe", "allow_changes", "sign_out", "reflow", "mode", "res_scale",
+        ]
+    )]
+    clauses: Vec<DisplayClause>,
     /// Width in pixels (e.g. 1920)
     #[arg(long)]
     width: Option<u32>,
     /// Height in pixels (e.g. 1080)
     #[arg(long)]
     height: Option<u32>,
-    /// Scaling percentage (100,125,150,175,...)
+    /// Scaling percentage (100,125,150,175,...), or +N/-N steps from the current value
+    #[arg(long, allow_hyphen_values = true, value_parser = ScalingArg::parse)]
+    scaling: Option<ScalingArg>,
+    /// Copy the scaling of this display (source id), clamped to what each display supports
+    #[arg(long, value_name = "ID", conflicts_with = "scaling")]
+    scaling_match: Option<u32>,
+    /// Pick the scaling that gives this effective workspace size, e.g. 2560x1440
+    #[arg(long, value_name = "WxH", value_parser = parse_size, conflicts_with_all = ["scaling", "scaling_match"])]
+    effective: Option<(u32, u32)>,
+    /// Refresh rate in Hz, or max/min for the extremes supported at the (requested) resolution
+    #[arg(long, value_name = "HZ|max|min", value_parser = parse_refresh, conflicts_with = "confirm")]
+    refresh: Option<RefreshRate>,
+    /// Restore the native resolution and refresh rate the monitor reports (the largest supported
+    /// mode if it reports none)
+    #[arg(long, conflicts_with_all = ["width", "height", "refresh", "confirm"])]
+    native: bool,
+    /// Switch resolution and refresh rate together to a mode: its number from `list --modes`,
+    /// or WxH@HZ in the monitor's own orientation, e.g. 1920x1080@144 or 2560x1440@143.98hz
+    #[arg(
+        long,
+        value_name = "N|WxH@HZ",
+        value_parser = parse_mode_arg,
+        conflicts_with_all = ["width", "height", "res", "refresh", "native", "confirm", "legacy_api", "reflow", "no_flicker"]
+    )]
+    mode: Option<ModeSpec>,
+    /// Resolution as a percentage of the native one in each dimension, e.g. 50%, snapped to
+    /// the nearest supported mode (which may be off by 5% at most unless --force)
+    #[arg(long, value_name = "PERCENT", value_parser = parse_res_scale, conflicts_with_all = ["width", "height", "res", "native", "mode"])]
+    res_scale: Option<u32>,
+    /// Rotate clockwise by 0, 90, 180 or 270 degrees; the resolution turns with the display
+    #[arg(long, value_name = "DEGREES", value_parser = parse_rotation, conflicts_with_all = ["confirm", "validate_only", "legacy_api", "force_remote"])]
+    rotate: Option<Rotation>,
+    /// Change the resolution through `ChangeDisplaySettingsEx` instead of `SetDisplayConfig`
+    /// (also used as a fallback when `SetDisplayConfig` fails)
+    #[arg(long, conflicts_with = "allow_changes")]
+    legacy_api: bool,
+    /// Try to apply even inside a Remote Desktop session (resolution via `ChangeDisplaySettingsEx`)
+    #[arg(long, conflicts_with_all = ["confirm", "validate_only", "safe", "allow_changes", "refresh"])]
+    force_remote: bool,
+    /// Apply resolutions below 640x480, above 16384 or not a multiple of 8, and --res-scale
+    /// modes off by more than 5%; with --display and input that isn't a terminal, turn off or
+    /// change the mode of the display showing this console
+    #[arg(long)]
+    force: bool,
+    /// With --display, turn off or change the mode of the display showing this console without
+    /// asking (it's still reverted unless confirmed)
+    #[arg(long, requires = "clauses")]
+    yes_i_know: bool,
+    /// Remove a system-wide custom scaling override so per-monitor scaling applies again
+    /// (after signing out)
+    #[arg(long)]
+    clear_custom_scaling: bool,
+    /// Check the bits per color channel (e.g. 8 or 10): fails unless the display already runs at
+    /// it, as Windows can't change it
+    #[arg(long, value_parser = clap::value_parser!(u32).range(6..=16))]
+    bit_depth: Option<u32>,
+    /// SDR content brightness of an HDR display in nits (80-480)
+    #[arg(long, value_name = "NITS")]
+    sdr_brightness: Option<u32>,
+    /// Let Windows 11 lower the refresh rate to 60Hz while little moves on screen (Dynamic
+    /// Refresh Rate, needs a supported panel at 120Hz or more)
+    #[arg(long, value_enum, value_name = "on|off")]
+    drr: Option<DrrArg>,
+    /// Revert unless the change is confirmed within this many seconds (0 overrides a default
+    /// from `config.toml`)
+    #[arg(long, value_name = "SECONDS")]
+    confirm: Option<u64>,
+    /// Only check whether the OS would accept the change, don't apply it
+    #[arg(long, conflicts_with_all = ["confirm", "safe", "sdr_brightness", "drr"])]
+    validate_only: bool,
+    /// Print the changes each display would get, without checking or applying them
+    #[arg(long, conflicts_with_all = ["confirm", "safe", "validate_only", "sign_out", "clear_custom_scaling", "bit_depth", "sdr_brightness", "drr"])]
+    dry_run: bool,
+    /// Apply only what doesn't blank the screen (scaling) and report the resolution changes
+    /// skipped, e.g. during a video call
+    #[arg(long, conflicts_with_all = ["refresh", "native", "rotate", "legacy_api", "force_remote", "bit_depth", "drr"])]
+    no_flicker: bool,
+    /// Don't print advice, such as keeping the native resolution and raising the scaling
+    /// instead of lowering the resolution (or set `advice = false` under `[output]` in
+    /// config.toml)
+    #[arg(long)]
+    quiet: bool,
+    /// Validate every change before applying any of them
+    #[arg(long)]
+    safe: bool,
+    /// If the exact mode is rejected, let Windows adjust it and report what changed
+    /// (exit code 2 when adjusted)
     #[arg(long)]
-    scaling: Option<i32>,
+    allow_changes: bool,
+    /// If a scaling change only takes effect after signing out, offer to sign out right away
+    #[arg(long)]
+    sign_out: bool,
+    /// Move the displays right of and below a display whose resolution changes along with its
+    /// edges, so they stay attached as before (gaps scale with the display); applied in one
+    /// call with the resolution
+    #[arg(long, conflicts_with_all = ["confirm", "validate_only", "legacy_api", "force_remote", "no_flicker"])]
+    reflow: bool,
+    /// Afterwards, move windows left off-screen (on a display that was disabled or moved) onto
+    /// the nearest display, keeping their size where it fits
+    #[arg(long, conflicts_with_all = ["validate_only", "dry_run"])]
+    rescue_windows: bool,
+    /// Print the result (each display's report, or the plan with --dry-run), or the error, as
+    /// JSON
+    #[arg(long, value_enum, default_value = "text", conflicts_with_all = ["confirm", "sign_out"])]
+    format: ReportFormat,
+}
+
+impl From<OutputFormat> for ListFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Table => Self::Table,
+            OutputFormat::Json => Self::Json,
+            OutputFormat::Csv => Self::Csv,
+        }
+    }
 }
 
-fn main() -> Result<()> {
-    //let subscriber = tracing_subscriber::fmt()
-    //    .finish();
-    //tracing::subscriber::set_global_default(subscriber)?;
+impl SetArgs {
+    /// Whether any selector picks displays, as opposed to `set --clear-custom-scaling` alone.
+    fn selects_displays(&self) -> bool {
+        self.alias.is_some()
+            || self.filter_expr.is_some()
+            || self.id.is_some()
+            || self.display_number.is_some()
+            || self.index.is_some()
+            || self.group.is_some()
+            || self.all
+            || self.current
+            || self.focused
+    }
+}
 
-    let cli = Cli::parse();
-    
-    match cli.command {
-        Commands::List => {
-            let displays = enumerate_displays()?;
-            for d in &displays {
-                println!("{d}");
+/// What [`apply_with_confirm`] did beyond applying the changes.
+#[derive(Debug, Default)]
+struct ApplyOutcome {
+    /// Those of the displays the apply compared afterwards, see [`apply_reports`] for the rest.
+    reports: Vec<ApplyReport>,
+    /// The OS adjusted a change (with `--allow-changes`).
+    adjusted: bool,
+    /// A scaling change is stored but waits for the user to sign out.
+    sign_out_needed: bool,
+    /// The changes weren't confirmed in time and were reverted.
+    reverted: bool,
+    /// The source ids of the displays that failed and were left unchanged.
+    failed: Vec<u32>,
+}
+
+impl ApplyOutcome {
+    /// Fails if any display did, once the caller has put back what else it changed for them.
+    fn into_result(self) -> Result<Self> {
+        if self.failed.is_empty() {
+            return Ok(self);
+        }
+        let failed: Vec<String> = self.failed.iter().map(ToString::to_string).collect();
+        Err(anyhow!("Display(s) {} failed and were left unchanged", failed.join(", ")))
+    }
+}
+
+fn main() -> ExitCode {
+    // What the library does, such as the retries of a failed apply, goes to stderr; RUST_LOG
+    // overrides the level.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,display_tuner=info")))
+        .with_writer(std::io::stderr)
+        .init();
+
+    // Before any window or cursor coordinates are read; the manifest does it already for MSVC builds.
+    if let Err(err) = set_process_per_monitor_aware() {
+        warn!(%err, "Process isn't per-monitor DPI aware");
+    }
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // The command line didn't parse, so the flag is looked for by hand.
+        Err(err) if err.use_stderr() && std::env::args_os().any(|arg| arg == "--json-errors") => {
+            return print_outcome(Err(usage_error(&err)));
+        }
+        Err(err) => err.exit(),
+    };
+    if cli.json_errors {
+        JSON_ERRORS.store(true, Ordering::Relaxed);
+        JSON_OUTPUT.store(true, Ordering::Relaxed);
+        let result = run(cli);
+        return print_outcome(result.map_err(|err| (error_exit_code(&err), error_report(&err))));
+    }
+    let reports_json = cli.command.as_ref().is_some_and(reports_json);
+    match run(cli) {
+        Ok(code) => code,
+        // Failed before the command could fill in its report, e.g. on a broken config file.
+        Err(err) if reports_json => {
+            let mut report = CommandReport::default();
+            report.finish(error_exit_code(&err), Some(error_report(&err)));
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+            ExitCode::from(report.exit_code)
+        }
+        Err(err) => {
+            let code = error_exit_code(&err);
+            if code == 1 {
+                eprintln!("Error: {err:?}");
+            } else {
+                eprintln!("Error: {err}");
+            }
+            ExitCode::from(code)
+        }
+    }
+}
+
+/// Whether `command` prints a [`CommandReport`] with `--format json`.
+fn reports_json(command: &Commands) -> bool {
+    match command {
+        Commands::Set(args) => args.format == ReportFormat::Json,
+        Commands::Profile { command: ProfileCommand::Apply { format, .. } } => *format == ReportFormat::Json,
+        _ => false,
+    }
+}
+
+/// The exit code for a command failing with `err`.
+fn error_exit_code(err: &anyhow::Error) -> u8 {
+    match err.downcast_ref::<DisplayError>() {
+        Some(DisplayError::ApplyTimedOut) => TIMED_OUT_EXIT_CODE,
+        Some(DisplayError::NoDisplays) => NO_DISPLAYS_EXIT_CODE,
+        _ => 1,
+    }
+}
+
+/// The JSON form of `err`, with its stable code if it's a [`DisplayError`].
+fn error_report(err: &anyhow::Error) -> ErrorReport {
+    err.downcast_ref::<DisplayError>().map_or_else(|| ErrorReport::other(format!("{err:#}")), ErrorReport::from)
+}
+
+/// The exit code and JSON form of a command line clap rejects.
+fn usage_error(err: &clap::Error) -> (u8, ErrorReport) {
+    let rendered = err.render().to_string();
+    let message = rendered.lines().next().unwrap_or_default();
+    let mut report = ErrorReport::other(message.strip_prefix("error: ").unwrap_or(message));
+    report.code = USAGE_ERROR.to_string();
+    if let Some(ContextValue::String(argument)) = err.get(ContextKind::InvalidArg) {
+        report.details.insert("argument".to_string(), argument.clone().into());
+    }
+    (u8::try_from(err.exit_code()).unwrap_or(1), report)
+}
+
+/// The number behind `code`; `ExitCode` hides it, so it's looked up among those commands return
+/// on success.
+fn exit_code_number(code: ExitCode) -> u8 {
+    [0, ADJUSTED_EXIT_CODE].into_iter().find(|&n| ExitCode::from(n) == code).unwrap_or(1)
+}
+
+/// Prints the [`Outcome`] of a command run with `--json-errors`, the only thing on stdout.
+fn print_outcome(result: std::result::Result<ExitCode, (u8, ErrorReport)>) -> ExitCode {
+    let outcome = match result {
+        Ok(code) => Outcome::success(exit_code_number(code), JSON_RESULT.lock().unwrap_or_else(PoisonError::into_inner).take()),
+        Err((exit_code, error)) => Outcome::failure(exit_code, error),
+    };
+    println!("{}", serde_json::to_string_pretty(&outcome).unwrap_or_default());
+    ExitCode::from(outcome.exit_code)
+}
+
+/// Prints a JSON document a command outputs, or keeps it as the `result` with `--json-errors`.
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        *JSON_RESULT.lock().unwrap_or_else(PoisonError::into_inner) = Some(serde_json::to_value(value)?);
+    } else {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    }
+    Ok(())
+}
+
+/// Runs `command` for `--format json`: its messages go to stderr, and stdout gets the
+/// [`CommandReport`] it fills in, with the error if it fails. With `--json-errors` the error
+/// is returned for the [`Outcome`] instead.
+fn run_json(command: impl FnOnce(&mut CommandReport) -> Result<ExitCode>) -> Result<ExitCode> {
+    JSON_OUTPUT.store(true, Ordering::Relaxed);
+    let mut report = CommandReport::default();
+    match command(&mut report) {
+        Ok(code) => report.finish(exit_code_number(code), None),
+        Err(err) if JSON_ERRORS.load(Ordering::Relaxed) => return Err(err),
+        Err(err) => report.finish(error_exit_code(&err), Some(error_report(&err))),
+    }
+    print_json(&report)?;
+    Ok(ExitCode::from(report.exit_code))
+}
+
+fn run(cli: Cli) -> Result<ExitCode> {
+    let mut command = cli.command.unwrap_or(Commands::Interactive);
+    // Before the config is loaded, so a broken file can't keep a revert from being confirmed
+    // or carried out.
+    let revert_cancelled = confirms_revert(&command) && auto_revert::confirm()?;
+    match command {
+        Commands::Confirm => {
+            say!("{}", if revert_cancelled { "Confirmed, the changes stay" } else { "Nothing to confirm" });
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::AutoRevert { id } => {
+            auto_revert::run_watchdog(id)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ if revert_cancelled => eprintln!("Auto-revert cancelled, the last changes stay"),
+        _ => {}
+    }
+    // `config init` and `config path` have to work while the file doesn't parse.
+    let config = match command {
+        Commands::Config { .. } => Config::default(),
+        _ => Config::load_default()?,
+    };
+    set_retry_policy(RetryPolicy {
+        retries: cli.retries.or(config.safety.retries).unwrap_or_default(),
+        delay: Duration::from_millis(cli.retry_delay.or(config.safety.retry_delay).unwrap_or(DEFAULT_RETRY_DELAY_MS)),
+    });
+    set_persist(!cli.no_persist);
+    set_apply_log(cli.log_json.or_else(|| std::env::var_os(LOG_JSON_ENV).filter(|p| !p.is_empty()).map(PathBuf::from)));
+    set_broadcast(!cli.no_broadcast);
+    set_session_check(cli.session_check == SessionCheckArg::On);
+    set_apply_timeout(cli.timeout.map(Duration::from_secs));
+    set_apply_order(cli.apply_order.map(ApplyOrder::from));
+
+    if cli.via_daemon {
+        return run_via_daemon(&command);
+    }
+    apply_config_defaults(&mut command, &config);
+
+    let applies = matches!(
+        command,
+        Commands::Set(_)
+            | Commands::Swap { .. }
+            | Commands::CopyConfig { .. }
+            | Commands::Arrange(_)
+            | Commands::Profile { command: ProfileCommand::Apply { .. } }
+            | Commands::A11y { .. }
+            | Commands::Interactive
+    );
+    let revert_window = cli.timeout_revert.or(config.safety.auto_revert_seconds).filter(|&secs| secs > 0);
+    let before = match revert_window {
+        Some(_) if reverts(&command) => Some(DisplayState::capture()?),
+        _ => None,
+    };
+    let settles = reverts(&command);
+    STABLE_WAIT_SECS.store(cli.wait_for_stable.unwrap_or_default(), Ordering::Relaxed);
+    let result = dispatch(command);
+    // Even a failed command may have changed some displays.
+    if let (Some(before), Some(secs)) = (before, revert_window) {
+        match auto_revert::arm(&before, Duration::from_secs(secs)) {
+            Ok(true) => eprintln!("Reverting in {secs} s unless another display-tuner command runs, e.g. `display-tuner confirm`"),
+            Ok(false) => {}
+            Err(err) => eprintln!("Warning: failed to start the auto-revert watchdog, the changes stay: {err}"),
+        }
+    }
+    let code = result?;
+    if let Some(secs) = cli.wait_for_stable.filter(|_| settles) {
+        warn_unsettled(wait_for_desktop(Duration::from_secs(secs)));
+    }
+    if applies && !cli.no_persist {
+        // What's applied now worked, so it's the state to restore.
+        if let Err(err) = enumerate_displays().and_then(|displays| Profile::record_state(&displays)) {
+            eprintln!("Failed to record the applied settings: {err}");
+        }
+    }
+    Ok(code)
+}
+
+/// Whether `command` changes the displays and so gets `--timeout-revert`. The interactive mode
+/// asks to confirm each change itself.
+fn reverts(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Set(_)
+            | Commands::Swap { .. }
+            | Commands::CopyConfig { .. }
+            | Commands::Arrange(_)
+            | Commands::Profile { command: ProfileCommand::Apply { .. } }
+            | Commands::State { command: StateCommand::Restore { .. } }
+            | Commands::A11y { .. }
+    )
+}
+
+/// Whether running `command` shows someone is still in control and cancels a pending
+/// `--timeout-revert`. Not so for what runs unattended at logon or in the background, nor the
+/// shell completer.
+fn confirms_revert(command: &Commands) -> bool {
+    !matches!(
+        command,
+        Commands::AutoRevert { .. }
+            | Commands::Reapply
+            | Commands::Daemon { .. }
+            | Commands::Watch { .. }
+            | Commands::Hotkeys { .. }
+            | Commands::CompleteIds
+    )
+}
+
+/// Fills in the settings `config.toml` has and the command line leaves open.
+fn apply_config_defaults(command: &mut Commands, config: &Config) {
+    match command {
+        Commands::List(args) => {
+            if args.format.is_none() && !args.brief {
+                args.format = config.output.format.map(ListFormat::from);
+            }
+            if config.output.color == Some(false) && !args.color {
+                args.no_color = true;
             }
         }
         Commands::Set(args) => {
-            let mut displays = enumerate_displays()?;
+            // These changes can't be confirmed or validated first, see the flag conflicts.
+            let unguarded = !args.clauses.is_empty() || args.force_remote || args.validate_only;
+            if args.confirm.is_none() && !unguarded && args.refresh.is_none() && !args.native && args.mode.is_none() && args.rotate.is_none()
+                && !args.reflow && args.format == ReportFormat::Text
+            {
+                args.confirm = config.safety.confirm;
+            }
+            if config.safety.validate_first && !unguarded {
+                args.safe = true;
+            }
+            if config.output.advice == Some(false) {
+                args.quiet = true;
+            }
+        }
+        _ => {}
+    }
+}
 
-            if !args.all {
-                if let Some(id) = args.id {
-                    displays.retain(|d| d.source_id == id);
-                } else {
-                   return Err(anyhow!("No display source id specified"));
+fn dispatch(command: Commands) -> Result<ExitCode> {
+    match command {
+        Commands::List(args) => run_list(&args)?,
+        Commands::Set(args) => {
+            let code = match args.format {
+                ReportFormat::Json => run_json(|report| run_set(&args, report))?,
+                ReportFormat::Text => run_set(&args, &mut CommandReport::default())?,
+            };
+            if args.rescue_windows {
+                print_rescued_windows()?;
+            }
+            return Ok(code);
+        }
+        Commands::Swap { first, second, rescue_windows } => {
+            run_swap(first, second)?;
+            if rescue_windows {
+                print_rescued_windows()?;
+            }
+        }
+        Commands::Arrange(args) => {
+            run_arrange(&args)?;
+            if args.rescue_windows {
+                print_rescued_windows()?;
+            }
+        }
+        Commands::CopyConfig { from, to } => return run_copy_config(from, to),
+        Commands::Profile { command } => return run_profile(command),
+        Commands::Watch { rules, on_battery, on_ac, dry_run, show_queue, notify } => {
+            if show_queue {
+                print_queue(&ActionQueue::load(&ActionQueue::default_path()?)?);
+            } else if dry_run {
+                print_schedule(&RuleSet::load(rules.as_deref().expect("--dry-run requires --rules"))?)?;
+            } else {
+                set_apply_interval(Some(MIN_APPLY_INTERVAL));
+                if notify {
+                    notify::enable();
                 }
+                run_watch(rules.as_deref(), on_battery.as_deref(), on_ac.as_deref())?;
             }
-
-            if displays.is_empty() {
-                return Err(anyhow!("No matching displays found"));
+        }
+        Commands::Daemon { notify } => {
+            set_apply_interval(Some(MIN_APPLY_INTERVAL));
+            if notify {
+                notify::enable();
             }
-
-            for disp in &displays {
-                let target =
-                    DisplayConfig {
-                        width: args.width.unwrap_or(disp.width),
-                        height: args.height.unwrap_or(disp.height),
-                        scaling: args.scaling.unwrap_or(disp.scaling_current),
-                    };
-                println!("Applying to display {}: {target:?}", disp.source_id);
-                apply_display_config(disp, &target)?;
+            daemon::serve()?;
+        }
+        Commands::Reapply => run_reapply()?,
+        Commands::Startup { command } => run_startup(&command)?,
+        Commands::A11y { command: A11yCommand::LargeText { state } } => run_large_text(state)?,
+        Commands::Icc { command } => run_icc(&command)?,
+        Commands::State { command } => run_state(&command)?,
+        Commands::Doctor { id, width, height, scaling, refresh } => {
+            let report = diagnose(&DoctorRequest { id, width, height, scaling, refresh });
+            say!("{report}");
+            if !report.applicable() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Commands::Selftest { destructive, format } => {
+            let report = selftest(destructive);
+            match format {
+                ReportFormat::Text => say!("{report}"),
+                ReportFormat::Json => print_json(&report)?,
+            }
+            if !report.passed {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Commands::Check { expect_scaling, file } => return run_check(expect_scaling, file.as_deref()),
+        Commands::Hotkeys { config, coalesce } => {
+            set_apply_interval(Some(MIN_APPLY_INTERVAL));
+            run_hotkeys(&config, Duration::from_millis(coalesce))?;
+        }
+        Commands::BlurCheck { json } => {
+            let report = blur_check()?;
+            if json {
+                print_json(&report)?;
+            } else if report.is_empty() {
+                say!("Every active display is at 100% scaling");
+            } else {
+                for display in &report {
+                    say!("{display}");
+                }
             }
         }
+        Commands::Identify { seconds } => identify(&enumerate_displays()?, Duration::from_secs(seconds))?,
+        Commands::Revert => return run_via_daemon(&Commands::Revert),
+        Commands::Stats => return run_via_daemon(&Commands::Stats),
+        Commands::Confirm | Commands::AutoRevert { .. } => {} // Handled by `run`.
+        Commands::Interactive => run_interactive()?,
+        Commands::Completions { shell } => run_completions(shell)?,
+        Commands::CompleteIds => run_complete_ids()?,
+        Commands::Config { command } => run_config(&command)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_check(expect_scaling: Option<ScalingExpectation>, file: Option<&Path>) -> Result<ExitCode> {
+    let mut policy = file.map(Policy::load).transpose()?.unwrap_or_default();
+    if expect_scaling.is_some() {
+        policy.scaling = expect_scaling;
+    }
+    if print_check(&check_policy(&policy, &enumerate_displays()?)) {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
     }
+}
 
+fn run_list(args: &ListArgs) -> Result<()> {
+    let tuner = DisplayTuner::new()?;
+    let mut all = tuner.displays().to_vec();
+    if args.inactive {
+        all.extend(enumerate_inactive_displays()?);
+    }
+    let displays = print_list(&all, args)?;
+    if args.capabilities {
+        let active: Vec<DisplayInfo> = displays.iter().filter(|d| d.active).cloned().collect();
+        for (d, capabilities) in active.iter().zip(tuner.capabilities_all(&active)?) {
+            say!("Display {} ({}): {capabilities}", d.source_id, d.friendly_name);
+        }
+    }
+    if args.modes {
+        for d in displays.iter().filter(|d| d.active) {
+            say!("Display {} ({}):", d.source_id, d.friendly_name);
+            // Modes are listed in the monitor's own orientation.
+            let (width, height) = d.rotation.orient((d.width, d.height));
+            let current = DisplayMode { width, height, refresh: d.refresh_rate };
+            for (i, mode) in supported_modes(d)?.iter().enumerate() {
+                let note = if *mode == current { " (current)" } else { "" };
+                say!("{:>5}) {mode}{note}", i + 1);
+            }
+        }
+    }
     Ok(())
 }
+
+/// Prints the displays of `all` that `args` selects and returns them.
+fn print_list(all: &[DisplayInfo], args: &ListArgs) -> Result<Vec<DisplayInfo>> {
+    let groups = load_groups()?;
+    let mut displays = match (&args.group, &args.alias) {
+        (Some(name), _) => resolve_group(&groups, name, all)?,
+        (None, Some(alias)) => vec![resolve_alias(&groups.aliases, alias, all)?.clone()],
+        (None, None) => all.to_vec(),
+    };
+    displays = filter_displays(displays, &args.filter());
+    sort_displays(&mut displays, args.sort.into());
+    if args.edid {
+        displays.iter_mut().for_each(load_edid);
+    }
+    if args.explain {
+        displays.iter_mut().for_each(load_trace);
+    }
+    if !args.brief && matches!(args.format, None | Some(ListFormat::Table)) && is_remote_session() {
+        say!("Remote Desktop session: resolutions come from the RDP client");
+    }
+    match args.format {
+        // The displays are the result then, whatever the format.
+        _ if JSON_ERRORS.load(Ordering::Relaxed) => print_json(&displays)?,
+        Some(ListFormat::Json) => print_json(&displays)?,
+        Some(ListFormat::Csv) => print!("{}", render_csv(&displays, args.edid)),
+        None if args.brief => {
+            for d in &displays {
+                say!("{}", brief_line(d));
+            }
+        }
+        None | Some(ListFormat::Table) => print!("{}", render_table(&displays, all, &groups.aliases, use_color(args.no_color))),
+    }
+    if args.explain && matches!(args.format, None | Some(ListFormat::Table)) {
+        for d in &displays {
+            if let Some(trace) = &d.debug {
+                say!("Display {} ({}):\n{trace}", d.source_id, d.friendly_name);
+            }
+        }
+    }
+    for d in &displays {
+        if let Some((ccd, shell)) = d.scaling_mismatch() {
+            eprintln!(
+                "Warning: display {} uses {shell}% scaling but Windows reports {ccd}% for it (custom scaling or a \
+                 stale setting after an upgrade; `doctor --id {}` has details)",
+                d.source_id, d.source_id
+            );
+        }
+    }
+    if args.groups {
+        print_groups(&groups, &displays, all);
+    }
+    if args.desktop {
+        print_desktop(all);
+    }
+    Ok(displays)
+}
+
+/// The desktop spanned by all active displays, whatever the filters, checked against what
+/// the window manager reports.
+fn print_desktop(all: &[DisplayInfo]) {
+    let Some(desktop) = desktop_summary(all) else {
+        say!("No active displays");
+        return;
+    };
+    say!(
+        "Desktop: {}, {} pixels on {} displays",
+        desktop.bounds,
+        desktop.pixels,
+        desktop.displays.len()
+    );
+    for area in &desktop.displays {
+        let name = all
+            .iter()
+            .find(|d| d.source_id == area.source_id)
+            .map_or("", |d| d.friendly_name.as_str());
+        say!("  {} {name}: {}", area.source_id, area.rect);
+    }
+    let (x, y, width, height) = virtual_screen();
+    let reported = Rect { x, y, width: width.unsigned_abs(), height: height.unsigned_abs() };
+    if reported != desktop.bounds {
+        eprintln!("Warning: Windows reports the virtual desktop as {reported}; the display query may be stale");
+    }
+}
+
+fn print_groups(groups: &GroupConfig, displays: &[DisplayInfo], all: &[DisplayInfo]) {
+    if groups.groups.is_empty() {
+        say!("No groups defined");
+        return;
+    }
+    for d in displays {
+        let names = groups.groups_of(d);
+        let names = if names.is_empty() { "-".to_string() } else { names.join(", ") };
+        say!("Display {} ({}): {names}", d.source_id, d.friendly_name);
+    }
+    for (group, member) in groups.missing_members(all) {
+        eprintln!("Warning: group '{group}' member {member} isn't connected");
+    }
+}
+
+/// Colors only go to a terminal, and `NO_COLOR` (any non-empty value) turns them off.
+fn use_color(no_color: bool) -> bool {
+    !no_color
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && io::stdout().is_terminal()
+        && enable_colors()
+}
+
+fn run_set(args: &SetArgs, report: &mut CommandReport) -> Result<ExitCode> {
+    let remote = is_remote_session();
+    if remote && !args.force_remote {
+        return Err(DisplayError::RemoteSession.into());
+    }
+    if !args.clauses.is_empty() {
+        return run_batch(args);
+    }
+    let mut all = enumerate_displays()?;
+    if args.clear_custom_scaling {
+        clear_custom_scaling()?;
+        say!("Removed the custom scaling override; sign out and back in for per-monitor scaling to take effect");
+        if !args.selects_displays() {
+            return Ok(ExitCode::SUCCESS);
+        }
+        // Scaling set now is stored per monitor and shows up with the next sign-in.
+        for disp in &mut all {
+            disp.custom_scaling = false;
+        }
+    }
+    let displays = select_targets(args, &all)?;
+    if displays.is_empty() {
+        return Err(anyhow!("No matching displays found"));
+    }
+
+    let reference = args
+        .scaling_match
+        .map(|id| {
+            all.iter()
+                .find(|d| d.source_id == id)
+                .map(|d| d.scaling_current)
+                .ok_or(DisplayError::DisplayNotFound(id))
+        })
+        .transpose()?;
+    let mut refresh = Vec::new();
+    let mut changes = displays
+        .into_iter()
+        .map(|disp| {
+            let builder = mode_builder(args, &disp)?;
+            let scaling = match args.effective {
+                Some(size) => effective_scaling(&disp, &builder.resolve(&disp), size)?,
+                None => target_scaling(&disp, args.scaling, reference),
+            };
+            if let Some(hz) = builder.resolve_refresh(&disp)? {
+                say!("Display {} refresh rate: {hz}Hz", disp.source_id);
+                refresh.push((disp.clone(), hz));
+            }
+            let target = builder.scaling(scaling).resolve(&disp);
+            Ok((disp, target))
+        })
+        .collect::<Result<Vec<(DisplayInfo, DisplayConfig)>>>()?;
+    if !args.force {
+        validate_resolutions(&changes)?;
+    }
+    skip_mode_switches(args, &all, &mut changes)?;
+    advise_native_resolution(args, &changes);
+
+    if remote {
+        return run_set_remote(&changes);
+    }
+    if args.validate_only {
+        return validate_only(&changes);
+    }
+    if args.dry_run {
+        print_dry_run(args, &all, &changes, &refresh, report)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.safe {
+        for (disp, target) in &changes {
+            validate_display_config(disp, target)?;
+        }
+    }
+
+    let console = console_display(&all).filter(|&id| changes_console_mode(args, id, &changes, &refresh));
+    if let Some(source_id) = console {
+        confirm_console_change(args, source_id, false)?;
+    }
+    let snapshot = console.map(|_| ConfigSnapshot::take()).transpose()?;
+    let result = apply_set(args, &all, &mut changes, &mut refresh);
+    // Even a failed apply may have changed the console display; someone who can't see it any
+    // more gets it back by waiting.
+    if let Some(snapshot) = &snapshot
+        && !result.as_ref().is_ok_and(|outcome| outcome.reverted)
+        && !confirm_within(Duration::from_secs(CONSOLE_CHANGE_CONFIRM_SECS))?
+    {
+        say!("Reverting the changes");
+        undo_set(&changes, snapshot)?;
+        revert_color_settings(args, &changes)?;
+        result?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let outcome = result?;
+    if outcome.sign_out_needed && args.sign_out {
+        offer_sign_out()?;
+    }
+    if args.format == ReportFormat::Json {
+        report.reports = apply_reports(&all, &changes, outcome.reports)?;
+    }
+    Ok(if outcome.adjusted { ExitCode::from(ADJUSTED_EXIT_CODE) } else { ExitCode::SUCCESS })
+}
+
+/// The part of `set` that changes the displays: the layout, the color settings, the mode and
+/// scaling of each display (with `--confirm`), then the refresh rates.
+fn apply_set(
+    args: &SetArgs,
+    all: &[DisplayInfo],
+    changes: &mut [(DisplayInfo, DisplayConfig)],
+    refresh: &mut Vec<(DisplayInfo, u32)>,
+) -> Result<ApplyOutcome> {
+    apply_layout_first(args, all, changes, refresh)?;
+    apply_color_settings(args, changes)?;
+
+    let policy = if args.allow_changes { ApplyPolicy::AllowChanges } else { ApplyPolicy::Strict };
+    let backend = if args.legacy_api { ApplyBackend::Legacy } else { ApplyBackend::Ccd };
+    let timeout = args.confirm.filter(|&secs| secs > 0).map(Duration::from_secs);
+    let outcome = apply_with_confirm(changes, timeout, policy, backend).inspect_err(|_| hint_link_bandwidth(changes, refresh))?;
+    if outcome.reverted {
+        revert_color_settings(args, changes)?;
+    } else if !outcome.failed.is_empty() {
+        hint_link_bandwidth(changes, refresh);
+        // The displays that failed were put back, so their color settings go back too.
+        let failed: Vec<(DisplayInfo, DisplayConfig)> =
+            changes.iter().filter(|(disp, _)| outcome.failed.contains(&disp.source_id)).cloned().collect();
+        if let Err(err) = revert_color_settings(args, &failed) {
+            eprintln!("Warning: reverting the color settings failed: {err}");
+        }
+    }
+    let outcome = outcome.into_result()?;
+    for (disp, target, hz) in refresh_targets(changes, refresh) {
+        if args.legacy_api {
+            set_display_mode_legacy(disp, target.width, target.height, Some(hz))
+        } else {
+            set_refresh_rate(disp, hz)
+        }
+        .inspect_err(|_| hint_link_bandwidth(changes, refresh))?;
+    }
+    wait_for_changes(changes);
+    Ok(outcome)
+}
+
+/// Whether `changes` (and `refresh`, `--rotate`) switch the mode of the display with `source_id`.
+fn changes_console_mode(args: &SetArgs, source_id: u32, changes: &[(DisplayInfo, DisplayConfig)], refresh: &[(DisplayInfo, u32)]) -> bool {
+    changes.iter().filter(|(disp, _)| disp.source_id == source_id).any(|(disp, target)| {
+        (target.width, target.height) != (disp.width, disp.height)
+            || args.rotate.is_some_and(|rotation| rotation != disp.rotation)
+            || refresh.iter().any(|(d, hz)| d.source_id == source_id && *hz != disp.refresh_rate)
+    })
+}
+
+/// Puts the displays of `changes` back as `snapshot` has them: their scaling first, as
+/// [`undo_batch`] does, then the modes.
+fn undo_set(changes: &[(DisplayInfo, DisplayConfig)], snapshot: &ConfigSnapshot) -> Result<()> {
+    let now = enumerate_displays()?;
+    for (before, _) in changes {
+        if let Some(disp) = now.iter().find(|d| d.source_id == before.source_id && d.scaling_current != before.scaling_current) {
+            apply_display_config(disp, &DisplayConfig { scaling: before.scaling_current, ..DisplayConfig::builder().resolve(disp) })?;
+        }
+    }
+    Ok(snapshot.restore()?)
+}
+
+/// `--wait-for-stable`: waits for each display of `changes` to show its new configuration,
+/// sharing the timeout between them.
+fn wait_for_changes(changes: &[(DisplayInfo, DisplayConfig)]) {
+    let secs = STABLE_WAIT_SECS.load(Ordering::Relaxed);
+    if secs == 0 {
+        return;
+    }
+    let deadline = Instant::now() + Duration::from_secs(secs);
+    warn_unsettled(
+        changes
+            .iter()
+            .try_for_each(|(disp, target)| wait_for_configuration(disp, target, deadline.saturating_duration_since(Instant::now()))),
+    );
+}
+
+/// Waiting for the displays to settle is best effort, so running out of time only warns.
+fn warn_unsettled(result: display_tuner::display::Result<()>) {
+    if let Err(err) = result {
+        eprintln!("Warning: {err}, continuing");
+    }
+}
+
+/// After an apply failed: the displays whose new mode seems to need more bandwidth than their
+/// link carries now, since that error says nothing about cables or ports.
+fn hint_link_bandwidth(changes: &[(DisplayInfo, DisplayConfig)], refresh: &[(DisplayInfo, u32)]) {
+    for (disp, target) in changes {
+        let hz = refresh.iter().find(|(d, _)| d.source_id == disp.source_id).map_or(disp.refresh_rate, |&(_, hz)| hz);
+        let hint = link_info(disp).ok().and_then(|link| bandwidth_hint(&link, target.width, target.height, hz));
+        if let Some(hint) = hint {
+            eprintln!("Display {}: {hint}", disp.source_id);
+        }
+    }
+}
+
+/// `set --validate-only`: whether the OS would accept each change.
+fn validate_only(changes: &[(DisplayInfo, DisplayConfig)]) -> Result<ExitCode> {
+    let mut rejected = 0;
+    for (disp, target) in changes {
+        match validate_display_config(disp, target) {
+            Ok(()) => say!("Display {}: {target:?} would be accepted", disp.source_id),
+            Err(err) => {
+                say!("Display {}: {err}", disp.source_id);
+                rejected += 1;
+            }
+        }
+    }
+    if rejected > 0 {
+        return Err(anyhow!("{rejected} change(s) would be rejected"));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// What each display of `changes` ended up with, for `--format json`: the reports the apply
+/// `made`, and for the other displays the request compared with the displays `before` and now.
+fn apply_reports(before: &[DisplayInfo], changes: &[(DisplayInfo, DisplayConfig)], mut made: Vec<ApplyReport>) -> Result<Vec<ApplyReport>> {
+    let after = enumerate_displays()?;
+    Ok(changes
+        .iter()
+        .map(|(disp, target)| match made.iter().position(|r| r.source_id == disp.source_id) {
+            Some(index) => made.swap_remove(index),
+            None => ApplyReport::compare(disp.source_id, target, before, &after, false),
+        })
+        .collect())
+}
+
+/// Suggests the native resolution with more scaling to those lowering the resolution to make
+/// things bigger, unless `--quiet`. Advice never fails the command, so a display whose modes
+/// can't be read gets none.
+fn advise_native_resolution(args: &SetArgs, changes: &[(DisplayInfo, DisplayConfig)]) {
+    if args.quiet || args.native || args.res_scale.is_some() {
+        return;
+    }
+    let scaling_given = args.scaling.is_some() || args.scaling_match.is_some() || args.effective.is_some();
+    for (disp, target) in changes.iter().filter(|(disp, target)| (disp.width, disp.height) != (target.width, target.height)) {
+        let Some(native) = supported_modes(disp).ok().and_then(|modes| native_mode(disp, &modes)) else {
+            continue;
+        };
+        let native = native.rotated_size(args.rotate.unwrap_or(disp.rotation));
+        if let Some(advice) = native_resolution_advice(disp, (target.width, target.height), native, scaling_given) {
+            say!("{advice}");
+        }
+    }
+}
+
+/// With `--no-flicker`, drops the changes that blank the screen and reports them.
+fn skip_mode_switches(args: &SetArgs, all: &[DisplayInfo], changes: &mut [(DisplayInfo, DisplayConfig)]) -> Result<()> {
+    if !args.no_flicker {
+        return Ok(());
+    }
+    let (_, skipped) = plan_set(args, all, changes, &[])?.split_by_impact();
+    for change in &skipped.changes {
+        say!("Skipped, needs a mode switch: {change}");
+    }
+    // The other mode changes conflict with --no-flicker, so only resolutions are left to drop.
+    for (disp, target) in changes {
+        (target.width, target.height) = (disp.width, disp.height);
+    }
+    Ok(())
+}
+
+/// The changes `set` would make, for `--dry-run`.
+fn plan_set(
+    args: &SetArgs,
+    all: &[DisplayInfo],
+    changes: &[(DisplayInfo, DisplayConfig)],
+    refresh: &[(DisplayInfo, u32)],
+) -> Result<ChangeSet> {
+    let targets: Vec<Target> = changes
+        .iter()
+        .map(|(disp, target)| Target {
+            refresh: refresh.iter().find(|(d, _)| d.source_id == disp.source_id).map(|&(_, hz)| hz),
+            rotation: args.rotate,
+            ..Target::config(disp.source_id, target)
+        })
+        .collect();
+    Ok(plan(all, &targets)?)
+}
+
+/// `set --dry-run`: the changes per display (in the report with `--format json`), and with
+/// `--reflow` the displays that move.
+fn print_dry_run(
+    args: &SetArgs,
+    all: &[DisplayInfo],
+    changes: &[(DisplayInfo, DisplayConfig)],
+    refresh: &[(DisplayInfo, u32)],
+    report: &mut CommandReport,
+) -> Result<()> {
+    let planned = plan_set(args, all, changes, refresh)?;
+    if args.format == ReportFormat::Json {
+        report.changes = Some(planned);
+    } else {
+        say!("{planned}");
+    }
+    if args.reflow {
+        print_moves(all, &reflowed_sources(args, all, changes)?);
+    }
+    Ok(())
+}
+
+/// The layout of all displays once `changes` (and `--rotate`) are applied with `--reflow`.
+fn reflowed_sources(args: &SetArgs, all: &[DisplayInfo], changes: &[(DisplayInfo, DisplayConfig)]) -> Result<Vec<SourceMode>> {
+    let resized: Vec<SourceMode> = changes
+        .iter()
+        .map(|(disp, target)| SourceMode {
+            width: target.width,
+            height: target.height,
+            rotation: args.rotate.unwrap_or(disp.rotation),
+            ..SourceMode::of(disp)
+        })
+        .collect();
+    Ok(reflow_layout(all, &resized)?)
+}
+
+fn print_moves(all: &[DisplayInfo], sources: &[SourceMode]) {
+    for source in sources {
+        if all.iter().any(|d| d.source_id == source.source_id && (d.position_x, d.position_y) != (source.x, source.y)) {
+            say!("Display {} moves to ({}, {})", source.source_id, source.x, source.y);
+        }
+    }
+}
+
+/// `--reflow`, `--mode` and `--rotate` change the layout before the rest is applied.
+fn apply_layout_first(
+    args: &SetArgs,
+    all: &[DisplayInfo],
+    changes: &mut [(DisplayInfo, DisplayConfig)],
+    refresh: &mut Vec<(DisplayInfo, u32)>,
+) -> Result<()> {
+    match args.rotate {
+        _ if args.reflow => reflow_displays(args, all, changes),
+        _ if args.mode.is_some() => switch_modes(changes, args.rotate, refresh),
+        Some(rotation) => rotate_displays(changes, rotation),
+        None => Ok(()),
+    }
+}
+
+/// `set --mode`: switches resolution, refresh rate and any `--rotate` of each display together,
+/// in one `SetDisplayConfig` call. Leaves `changes` describing the displays afterwards and
+/// `refresh` empty, so only the scaling remains.
+fn switch_modes(
+    changes: &mut [(DisplayInfo, DisplayConfig)],
+    rotation: Option<Rotation>,
+    refresh: &mut Vec<(DisplayInfo, u32)>,
+) -> Result<()> {
+    let switches: Vec<CombinedChange> = changes
+        .iter()
+        .map(|(disp, target)| {
+            let hz = refresh.iter().find(|(d, _)| d.source_id == disp.source_id).map(|&(_, hz)| hz);
+            let rotation = rotation.unwrap_or(disp.rotation);
+            let source = SourceMode { width: target.width, height: target.height, rotation, ..SourceMode::of(disp) };
+            (disp, source, hz)
+        })
+        .filter(|(disp, source, hz)| *source != SourceMode::of(disp) || hz.is_some_and(|hz| hz != disp.refresh_rate))
+        .map(|(disp, source, refresh)| {
+            say!("Switching display {} to {}x{}{}", disp.source_id, source.width, source.height, refresh.map_or(String::new(), |hz| format!(" @ {hz}Hz")));
+            CombinedChange::Mode { source, refresh }
+        })
+        .collect();
+    refresh.clear();
+    if switches.is_empty() {
+        return Ok(());
+    }
+    apply_combined(&switches)?;
+    refresh_changes(changes)
+}
+
+/// `set --reflow`: applies the new resolutions and rotation together with the positions that
+/// keep the other displays attached, in one `SetDisplayConfig` call. Like [`rotate_displays`],
+/// it leaves `changes` describing the displays afterwards, so only the scaling remains.
+fn reflow_displays(args: &SetArgs, all: &[DisplayInfo], changes: &mut [(DisplayInfo, DisplayConfig)]) -> Result<()> {
+    let sources = reflowed_sources(args, all, changes)?;
+    if all.iter().filter(|d| d.active).zip(&sources).all(|(d, source)| SourceMode::of(d) == *source) {
+        return Ok(());
+    }
+    print_moves(all, &sources);
+    apply_source_modes(&sources)?;
+    refresh_changes(changes)
+}
+
+/// `set --bit-depth`, `--sdr-brightness` and `--drr`, which apply apart from the mode and
+/// scaling, once everything else is checked.
+fn apply_color_settings(args: &SetArgs, changes: &[(DisplayInfo, DisplayConfig)]) -> Result<()> {
+    if let Some(bits) = args.bit_depth {
+        for (disp, _) in changes {
+            set_bit_depth(disp, bits)?;
+        }
+    }
+    if let Some(nits) = args.sdr_brightness {
+        for (disp, _) in changes {
+            say!("Setting SDR content brightness of display {} to {nits} nits", disp.source_id);
+            set_sdr_white_level(disp, nits)?;
+        }
+    }
+    if let Some(drr) = args.drr {
+        for (disp, _) in changes {
+            let enabled = drr == DrrArg::On;
+            say!("Turning dynamic refresh rate of display {} {}", disp.source_id, if enabled { "on" } else { "off" });
+            set_dynamic_refresh(disp, enabled)?;
+        }
+    }
+    Ok(())
+}
+
+/// Puts the SDR content brightness and dynamic refresh rate of `changes` back to what they were
+/// before [`apply_color_settings`], once the rest was reverted for want of a confirmation or
+/// because those displays failed.
+fn revert_color_settings(args: &SetArgs, changes: &[(DisplayInfo, DisplayConfig)]) -> Result<()> {
+    if args.sdr_brightness.is_none() && args.drr.is_none() {
+        return Ok(());
+    }
+    // The setters skip displays already in the requested state, so they need them as they are now.
+    let now = enumerate_displays()?;
+    for (before, _) in changes {
+        let Some(disp) = now.iter().find(|d| d.source_id == before.source_id) else {
+            continue;
+        };
+        if let (Some(_), Some(nits)) = (args.sdr_brightness, before.sdr_white_level) {
+            say!("Reverting SDR content brightness of display {} to {nits} nits", disp.source_id);
+            set_sdr_white_level(disp, nits)?;
+        }
+        if args.drr.is_some() && disp.drr_enabled != before.drr_enabled {
+            say!("Reverting dynamic refresh rate of display {}", disp.source_id);
+            set_dynamic_refresh(disp, before.drr_enabled)?;
+        }
+    }
+    Ok(())
+}
+
+/// `set --display`: checks every clause, then applies them together and reports each display.
+fn run_batch(args: &SetArgs) -> Result<ExitCode> {
+    let displays = enumerate_displays()?;
+    // A display without a mode list is still checked against the resolution limits.
+    let modes = displays
+        .iter()
+        .filter(|d| args.clauses.iter().any(|c| c.source_id == d.source_id))
+        .filter_map(|d| Some((d.source_id, supported_modes(d).ok()?)))
+        .collect();
+    let plan = plan_batch(&args.clauses, &displays, &modes);
+    for planned in &plan.displays {
+        for note in &planned.notes {
+            say!("Display {}: {note}", planned.source_id);
+        }
+    }
+    let rejected = plan.rejected();
+    if rejected > 0 || args.validate_only {
+        for planned in &plan.displays {
+            let verdict = if planned.problems.is_empty() { "ok" } else { "rejected" };
+            say!("Display {}: {planned} ({verdict})", planned.source_id);
+        }
+        if rejected > 0 {
+            return Err(DisplayError::BatchRejected(rejected).into());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let console = console_display(&displays).filter(|&id| plan.changes_mode(id));
+    if let Some(source_id) = console {
+        confirm_console_change(args, source_id, plan.turns_off(source_id))?;
+    }
+    let snapshot = console.map(|_| ConfigSnapshot::take()).transpose()?;
+    let outcomes = apply_batch(&plan)?;
+    for (planned, outcome) in plan.displays.iter().zip(&outcomes) {
+        say!("Display {}: {planned}: {outcome}", planned.source_id);
+    }
+    if outcomes.iter().any(|o| matches!(o, BatchOutcome::Failed(_))) {
+        return Err(anyhow!("The --display changes failed and no display was left changed"));
+    }
+    // Someone who can't see the console any more gets the display back by waiting.
+    if let Some(snapshot) = &snapshot
+        && !confirm_within(Duration::from_secs(CONSOLE_CHANGE_CONFIRM_SECS))?
+    {
+        say!("Reverting the --display changes");
+        undo_batch(&plan, snapshot)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The display showing this console, or the foreground window, `None` if that can't be told.
+fn console_display(displays: &[DisplayInfo]) -> Option<u32> {
+    match console_monitor_device() {
+        Ok(device) => displays.iter().find(|d| d.gdi_device_name.eq_ignore_ascii_case(&device)).map(|d| d.source_id),
+        Err(err) => {
+            warn!(%err, "Can't tell which display shows the console");
+            None
+        }
+    }
+}
+
+/// Turning off the display showing the console, or changing its mode, can leave the user
+/// without a visible screen, so it takes `--yes-i-know`, a "y" at the prompt, or `--force` when
+/// input isn't a terminal.
+fn confirm_console_change(args: &SetArgs, source_id: u32, turns_off: bool) -> Result<()> {
+    if args.yes_i_know || (args.force && !io::stdin().is_terminal()) {
+        return Ok(());
+    }
+    let (action, question) = if turns_off { ("turn it off", "Turn it off") } else { ("change its mode", "Change its mode") };
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Display {source_id} shows this console; pass --yes-i-know (or --force when input isn't a terminal) to {action}"
+        ));
+    }
+    say!("Display {source_id} shows this console, if you {action} you may be left without a visible screen");
+    if prompt(&format!("{question} anyway? [y/N]"))?.eq_ignore_ascii_case("y") {
+        Ok(())
+    } else {
+        Err(anyhow!("Nothing was changed"))
+    }
+}
+
+fn offer_sign_out() -> Result<()> {
+    if prompt("Sign out now? Unsaved work in open apps is lost [y/N]")?.eq_ignore_ascii_case("y") {
+        sign_out()?;
+    }
+    Ok(())
+}
+
+fn run_via_daemon(command: &Commands) -> Result<ExitCode> {
+    let request = match command {
+        Commands::List(args) if args.capabilities => Request::Capabilities,
+        Commands::List(_) => Request::List,
+        Commands::Set(args) => {
+            if args.current
+                || args.focused
+                || args.alias.is_some()
+                || args.filter_expr.is_some()
+                || args.native
+                || args.res.is_some()
+                || args.res_scale.is_some()
+                || !args.clauses.is_empty()
+                || args.yes_i_know
+                || args.rotate.is_some()
+                || args.display_number.is_some()
+                || args.index.is_some()
+                || args.group.is_some()
+                || args.sign_out
+                || args.internal_only
+                || args.external_only
+                || args.confirm.is_some()
+                || args.validate_only
+                || args.dry_run
+                || args.safe
+                || args.allow_changes
+                || args.bit_depth.is_some()
+                || args.sdr_brightness.is_some()
+                || args.drr.is_some()
+                || args.scaling_match.is_some()
+                || args.effective.is_some()
+                || args.refresh.is_some()
+                || args.mode.is_some()
+                || args.clear_custom_scaling
+                || args.rescue_windows
+                || args.no_flicker
+                || args.reflow
+                || args.format == ReportFormat::Json
+            {
+                return Err(anyhow!(
+                    "Only --id/--all, --width, --height and --scaling can be forwarded to the daemon"
+                ));
+            }
+            let id = if args.all {
+                None
+            } else {
+                Some(args.id.ok_or_else(|| anyhow!("No display source id specified"))?)
+            };
+            let scaling = match args.scaling {
+                Some(ScalingArg::Steps(_)) => {
+                    return Err(anyhow!("Relative --scaling steps can't be forwarded to the daemon"));
+                }
+                Some(ScalingArg::Percent(percent)) => Some(percent),
+                None => None,
+            };
+            Request::Set { id, handle: None, width: args.width, height: args.height, scaling }
+        }
+        Commands::Profile { command: ProfileCommand::Apply { name, format: ReportFormat::Text } } => Request::ProfileApply { name: name.clone(), progress: false },
+        Commands::Revert => Request::Revert,
+        Commands::Stats => Request::Stats,
+        _ => return Err(anyhow!("This command can't be forwarded to the daemon")),
+    };
+
+    let response = send_request(&request)?;
+    if !response.ok {
+        return Err(anyhow!(response.error.unwrap_or_else(|| "Daemon request failed".to_string())));
+    }
+    if let Some(stats) = &response.stats {
+        print_json(stats)?;
+    }
+    if let Commands::List(args) = command {
+        let shown = print_list(&response.displays, args)?;
+        for (display, capabilities) in response.displays.iter().zip(&response.capabilities) {
+            if shown.iter().any(|d| d.source_id == display.source_id) {
+                say!("Display {} ({}): {capabilities}", display.source_id, display.friendly_name);
+            }
+        }
+        if !args.brief && matches!(args.format, None | Some(ListFormat::Table)) {
+            for (display, handle) in response.displays.iter().zip(&response.handles) {
+                if shown.iter().any(|d| d.source_id == display.source_id) {
+                    say!("Display {} ({}): daemon handle {handle}", display.source_id, display.friendly_name);
+                }
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses a command line from a hotkey binding as if it were passed to display-tuner.
+fn parse_command_line(line: &str) -> std::result::Result<Cli, clap::Error> {
+    Cli::try_parse_from(std::iter::once("display-tuner").chain(line.split_whitespace()))
+}
+
+fn run_hotkeys(config: &Path, coalesce: Duration) -> Result<()> {
+    let bindings = HotkeyConfig::load(config)?.bindings()?;
+    if bindings.is_empty() {
+        return Err(anyhow!("No hotkeys configured in {}", config.display()));
+    }
+    for (hotkey, line) in &bindings {
+        let cli = parse_command_line(line).map_err(|e| anyhow!("Hotkey {hotkey}: invalid command '{line}'\n{e}"))?;
+        if !matches!(
+            cli.command,
+            Some(Commands::List(_) | Commands::Set(_) | Commands::Swap { .. } | Commands::Arrange(_) | Commands::Profile { .. } | Commands::Revert)
+        ) {
+            return Err(anyhow!("Hotkey {hotkey}: '{line}' can't be run from a hotkey"));
+        }
+    }
+
+    let keys: Vec<Hotkey> = bindings.iter().map(|(hotkey, _)| *hotkey).collect();
+    let registration = register_hotkeys(&keys)?;
+    for (_, err) in &registration.failed {
+        eprintln!("{err}");
+    }
+    if registration.failed.len() == keys.len() {
+        return Err(anyhow!("None of the hotkeys could be registered"));
+    }
+    say!("Listening for {} hotkey(s), press Ctrl+C to stop", keys.len() - registration.failed.len());
+
+    let mut queue = Coalescer::new(coalesce, MIN_APPLY_INTERVAL);
+    loop {
+        let pressed = match queue.next_due() {
+            Some(due) => match registration.pressed.recv_timeout(due.saturating_duration_since(Instant::now())) {
+                Ok(index) => Some(index),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match registration.pressed.recv() {
+                Ok(index) => Some(index),
+                Err(_) => break,
+            },
+        };
+        if let Some(index) = pressed {
+            queue.push(index, (), Instant::now());
+        }
+        for (index, ()) in queue.pop_due(Instant::now()) {
+            let Some((hotkey, line)) = bindings.get(index) else {
+                continue;
+            };
+            say!("{hotkey}: {line}");
+            if let Err(err) = parse_command_line(line).map_err(anyhow::Error::from).and_then(run) {
+                eprintln!("{hotkey}: {err:#}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints a pass/fail line per display and returns whether all passed.
+fn print_check(results: &[CheckResult]) -> bool {
+    for result in results {
+        let verdict = if result.passed() { "PASS" } else { "FAIL" };
+        say!("{verdict}  [id:{}] {}", result.source_id, result.friendly_name);
+        for field in &result.failures {
+            say!("        {}: {} (expected {})", field.field, field.actual, field.expected);
+        }
+    }
+    results.iter().all(CheckResult::passed)
+}
+
+/// Moves the windows a change left off-screen onto a display, for `--rescue-windows`.
+fn print_rescued_windows() -> Result<()> {
+    for window in rescue_offscreen_windows()? {
+        say!("{window}");
+    }
+    Ok(())
+}
+
+fn run_swap(first: u32, second: u32) -> Result<()> {
+    if first == second {
+        return Err(anyhow!("Can't swap display {first} with itself"));
+    }
+    let displays = enumerate_displays()?;
+    let find = |id: u32| {
+        displays
+            .iter()
+            .find(|d| d.source_id == id)
+            .ok_or_else(|| anyhow!("No display with source id {id}"))
+    };
+    let (a, b) = (find(first)?, find(second)?);
+
+    say!("Swapping display {first} and display {second}");
+    swap_display_configs(a, b)?;
+    Ok(())
+}
+
+fn run_copy_config(from: u32, to: CopyTarget) -> Result<ExitCode> {
+    if is_remote_session() {
+        return Err(DisplayError::RemoteSession.into());
+    }
+    if to == CopyTarget::Id(from) {
+        return Err(anyhow!("Can't copy display {from} onto itself"));
+    }
+    let displays = enumerate_displays()?;
+    let source = displays
+        .iter()
+        .find(|d| d.source_id == from)
+        .ok_or_else(|| anyhow!("No display with source id {from}"))?;
+    let targets: Vec<u32> = match to {
+        CopyTarget::Id(id) => vec![id],
+        CopyTarget::All => displays
+            .iter()
+            .filter(|d| d.source_id != from)
+            .filter(|d| {
+                if !d.target_available {
+                    say!("Skipping display {}: target currently unavailable (asleep or disconnected)", d.source_id);
+                }
+                d.target_available
+            })
+            .map(|d| d.source_id)
+            .collect(),
+    };
+    if targets.is_empty() {
+        return Err(anyhow!("No other display to copy to"));
+    }
+
+    let mut failed = false;
+    for id in targets {
+        // Earlier copies may have moved this display, so look it up again.
+        let target = enumerate_displays()?
+            .into_iter()
+            .find(|d| d.source_id == id)
+            .ok_or_else(|| anyhow!("No display with source id {id}"))?;
+        say!("Copying display {from} to display {id} ({})", target.friendly_name);
+        let report = copy_config(source, &target)?;
+        print_copy_report(&report);
+        failed |= report.failed();
+    }
+    Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
+
+fn print_copy_report(report: &CopyReport) {
+    for entry in &report.properties {
+        say!("  {} {}: {}", entry.property, entry.value, entry.outcome);
+    }
+}
+
+fn run_arrange(args: &ArrangeArgs) -> Result<()> {
+    let displays = enumerate_displays()?;
+    let layout = if let Some(preset) = args.preset() {
+        preset_layout(&displays, &args.order, preset)?
+    } else {
+        let id = args.id.expect("clap requires --id without --preset");
+        let (placement, reference) = args.placement();
+        if reference == id {
+            return Err(anyhow!("Can't place display {reference} relative to itself"));
+        }
+        place_display(&displays, id, reference, placement, args.align.into())?
+    };
+    for source in &layout {
+        say!("Display {}: {}x{} at ({}, {})", source.source_id, source.width, source.height, source.x, source.y);
+    }
+    if !args.dry_run {
+        apply_source_modes(&layout)?;
+    }
+    Ok(())
+}
+
+fn run_profile(command: ProfileCommand) -> Result<ExitCode> {
+    match command {
+        ProfileCommand::Save { name } => {
+            Profile::capture(&enumerate_displays()?).save(&name)?;
+            say!("Saved profile '{name}'");
+        }
+        ProfileCommand::Apply { name, format } => {
+            let apply = |report: &mut CommandReport| run_profile_apply(&name, format, report);
+            return match format {
+                ReportFormat::Json => run_json(apply),
+                ReportFormat::Text => apply(&mut CommandReport::default()),
+            };
+        }
+        ProfileCommand::Diff { name } => {
+            let diff = diff_profile(&Profile::load(&name)?, &enumerate_displays()?);
+            if diff.is_empty() {
+                say!("All displays match profile '{name}'");
+                return Ok(ExitCode::SUCCESS);
+            }
+            for display in &diff.changed {
+                say!("[id:{}] {}", display.source_id, display.friendly_name);
+                for field in &display.fields {
+                    say!("  {}: {} (profile: {})", field.field, field.actual, field.expected);
+                }
+            }
+            for entry in &diff.missing {
+                say!("In profile but not connected: {}", entry.label());
+            }
+            for display in &diff.unexpected {
+                say!("Connected but not in profile: {display}");
+            }
+            return Ok(ExitCode::FAILURE);
+        }
+        ProfileCommand::List => {
+            for name in Profile::list()? {
+                say!("{name}");
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_profile_apply(name: &str, format: ReportFormat, report: &mut CommandReport) -> Result<ExitCode> {
+    let profile = Profile::load(name)?;
+    let before = enumerate_displays()?;
+    let mut printer = ProgressPrinter::new();
+    let result = {
+        let _ctrl_c = cancel_on_ctrl_c()?;
+        apply_profile_with_progress(&profile, &before, &mut |progress| printer.show(progress))
+    };
+    if let Err(err) = result {
+        report.progress = printer.last;
+        return Err(err.into());
+    }
+    say!("Applied profile '{name}'");
+    if format == ReportFormat::Json {
+        let after = enumerate_displays()?;
+        report.reports = match_displays(&profile, &before)
+            .pairs
+            .iter()
+            .map(|(entry, disp)| {
+                let requested = DisplayConfig { width: entry.width, height: entry.height, scaling: entry.scaling };
+                ApplyReport::compare(disp.source_id, &requested, &before, &after, false)
+            })
+            .collect();
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints a line per display of a long apply once it's done; on a terminal, the display under
+/// way shows in between on a line of its own that's overwritten.
+struct ProgressPrinter {
+    tty: bool,
+    /// Width of the line to overwrite, 0 if there is none.
+    pending: usize,
+    frame: usize,
+    /// The latest progress of each display, for the report.
+    last: Vec<DisplayProgress>,
+}
+
+impl ProgressPrinter {
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+    fn new() -> Self {
+        let tty = !JSON_OUTPUT.load(Ordering::Relaxed) && io::stdout().is_terminal();
+        Self { tty, pending: 0, frame: 0, last: Vec::new() }
+    }
+
+    fn show(&mut self, progress: &DisplayProgress) {
+        match self.last.iter_mut().find(|p| p.index == progress.index) {
+            Some(last) => last.clone_from(progress),
+            None => self.last.push(progress.clone()),
+        }
+        if progress.is_finished() {
+            if self.tty {
+                print!("\r{:width$}\r", "", width = self.pending);
+                self.pending = 0;
+            }
+            say!("{progress}");
+        } else if self.tty {
+            let finished = self.last.iter().filter(|p| p.is_finished()).count();
+            let line = format!("{} {:>3}% {progress}", Self::SPINNER[self.frame % Self::SPINNER.len()], finished * 100 / progress.total.max(1));
+            self.frame += 1;
+            print!("\r{line:width$}", width = self.pending);
+            self.pending = line.chars().count();
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+fn run_reapply() -> Result<()> {
+    let displays = enumerate_displays()?;
+    let state = Profile::load_state()?.recorded_for(&displays);
+    for display in &displays {
+        if !state.displays.iter().any(|e| e.device_path == display.device_path) {
+            say!("No recorded settings for {display}, leaving it alone");
+        }
+    }
+    if state.displays.is_empty() {
+        return Ok(());
+    }
+    apply_profile(&state, &displays)?;
+    say!("Restored {} display(s)", state.displays.len());
+    Ok(())
+}
+
+fn run_icc(command: &IccCommand) -> Result<()> {
+    let displays = enumerate_displays()?;
+    match *command {
+        IccCommand::Status { id, json } => {
+            let statuses = displays
+                .iter()
+                .filter(|d| id.map_or(d.active, |id| d.source_id == id))
+                .map(color_status)
+                .collect::<Result<Vec<_>, _>>()?;
+            if let (Some(id), true) = (id, statuses.is_empty()) {
+                return Err(id_not_found(&displays, id).into());
+            }
+            if json {
+                print_json(&statuses)?;
+            } else {
+                for status in &statuses {
+                    say!("{status}");
+                }
+            }
+        }
+        IccCommand::Scope { id, scope } => {
+            let display = displays.iter().find(|d| d.source_id == id).ok_or_else(|| id_not_found(&displays, id))?;
+            set_profile_scope(display, scope.into())?;
+            say!("Display {id} uses the {} profile associations", ProfileScope::from(scope));
+        }
+    }
+    Ok(())
+}
+
+fn run_state(command: &StateCommand) -> Result<()> {
+    match command {
+        StateCommand::Save { file } => {
+            let state = DisplayState::capture()?;
+            state.save(file)?;
+            say!("Saved {} monitor(s), {} path(s) and {} mode(s) to {}", state.monitors.len(), state.paths.len(), state.modes.len(), file.display());
+        }
+        StateCommand::Restore { file, partial } => {
+            for name in DisplayState::load(file)?.restore(*partial)? {
+                say!("Skipped, not attached: {name}");
+            }
+            say!("Restored {}", file.display());
+        }
+    }
+    Ok(())
+}
+
+fn run_startup(command: &StartupCommand) -> Result<()> {
+    match command {
+        StartupCommand::Install => say!("Installed logon entry: {}", startup::install()?),
+        StartupCommand::Remove => {
+            if startup::remove()? {
+                say!("Removed logon entry");
+            } else {
+                say!("No logon entry was installed");
+            }
+        }
+        StartupCommand::Status => match startup::status()? {
+            Some(command) => say!("Installed: {command}"),
+            None => say!("Not installed"),
+        },
+    }
+    Ok(())
+}
+
+fn run_large_text(state: SwitchArg) -> Result<()> {
+    let on = match state {
+        SwitchArg::On => Some(true),
+        SwitchArg::Off => Some(false),
+        SwitchArg::Toggle => None,
+    };
+    let change = set_large_text(on)?;
+    for (display, from, to) in &change.changed {
+        say!("Display {} ({}): {from}% -> {to}%", display.source_id, display.friendly_name);
+    }
+    for recorded in &change.missing {
+        say!("Not connected, left as it is: {} (was {}%)", recorded.friendly_name, recorded.scaling);
+    }
+    say!("Large text is {}", if change.on { "on" } else { "off" });
+    Ok(())
+}
+
+fn run_config(command: &ConfigCommand) -> Result<()> {
+    let path = Config::default_path()?;
+    match command {
+        ConfigCommand::Path => say!("{}", path.display()),
+        ConfigCommand::Init => match write_template(&path) {
+            Ok(()) => say!("Wrote {}", path.display()),
+            Err(DisplayError::Io(err)) if err.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(anyhow!("{} already exists, not overwriting it", path.display()));
+            }
+            Err(err) => return Err(err.into()),
+        },
+    }
+    Ok(())
+}
+
+/// The groups and aliases of `groups.toml` and `config.toml`.
+fn load_groups() -> Result<GroupConfig> {
+    Ok(Config::load_default()?.merge_groups(GroupConfig::load_default()?))
+}
+
+fn run_watch(rules_path: Option<&Path>, on_battery: Option<&str>, on_ac: Option<&str>) -> Result<()> {
+    let rules = rules_path.map(RuleSet::load).transpose()?;
+    let on_battery = on_battery.map(Profile::load).transpose()?;
+    let on_ac = on_ac.map(Profile::load).transpose()?;
+    let events = watch_events()?;
+    if let Some(rules) = &rules {
+        say!(
+            "Watching display changes with {} rules and {} schedule entries",
+            rules.rules.len(),
+            rules.schedule.len()
+        );
+    }
+
+    let queue_path = ActionQueue::default_path()?;
+    let mut queue = ActionQueue::load(&queue_path)?;
+    let mut power_applied = None;
+    let mut checked = TimeOfDay::now();
+    loop {
+        if let Some(rules) = &rules {
+            checked = TimeOfDay::now();
+            let before = queue.clone();
+            // Without displays nothing applies anyway; apply_rules reports the error.
+            let displays = enumerate_displays().ok();
+            let rules = displays.as_ref().map_or_else(|| rules.clone(), |displays| queue.apply_to(rules, displays, unix_time()));
+            save_queue(&queue, &before, &queue_path);
+            match apply_rules(&rules) {
+                Ok(true) => {
+                    say!("Applied rules");
+                    announce_changes(displays.as_deref());
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("Failed to apply rules: {e}"),
+            }
+        }
+
+        if on_battery.is_some() || on_ac.is_some() {
+            let source = power_source()?;
+            if power_applied != Some(source) {
+                power_applied = Some(source);
+                let profile = match source {
+                    PowerSource::Battery => on_battery.as_ref(),
+                    PowerSource::Ac => on_ac.as_ref(),
+                };
+                if let Some(profile) = profile {
+                    say!("Running on {source:?} power, applying its profile");
+                    match enumerate_displays().and_then(|displays| apply_profile(profile, &displays).map(|()| displays)) {
+                        Ok(displays) => announce_changes(Some(&displays)),
+                        Err(e) => {
+                            eprintln!("Failed to apply profile: {e}");
+                            // Try again on the next event.
+                            power_applied = None;
+                        }
+                    }
+                }
+            }
+        }
+
+        let schedule = rules.as_ref().filter(|r| !r.schedule.is_empty());
+        let Some(event) = next_event(&events, schedule, &mut checked)? else {
+            if let Some(rules) = &rules {
+                let before = queue.clone();
+                record_missed(rules, checked, &mut queue);
+                save_queue(&queue, &before, &queue_path);
+            }
+            continue;
+        };
+        let mut debounce = match event {
+            WatchEvent::PowerSourceChanged => POWER_DEBOUNCE,
+            WatchEvent::DisplayChanged | WatchEvent::Resumed => WATCH_DEBOUNCE,
+        };
+        while let Ok(event) = events.recv_timeout(debounce) {
+            if event == WatchEvent::PowerSourceChanged {
+                debounce = POWER_DEBOUNCE;
+            }
+        }
+    }
+}
+
+/// Shows a toast for what changed since `before` if `--notify` asked for it; without `before`
+/// there's nothing to compare with. Watch changes aren't made through the daemon, so they can't
+/// be reverted from the toast.
+fn announce_changes(before: Option<&[DisplayInfo]>) {
+    if let (Some(before), Ok(after)) = (before, enumerate_displays()) {
+        notify::announce(before, &after, false);
+    }
+}
+
+/// Queues the schedule entries that fired since `checked` without a display to apply to, see
+/// [`ActionQueue::record`].
+fn record_missed(rules: &RuleSet, checked: TimeOfDay, queue: &mut ActionQueue) {
+    match enumerate_displays() {
+        Ok(displays) => {
+            let fired = rules.fired_between(checked, TimeOfDay::now());
+            queue.record(&fired, &displays, unix_time());
+        }
+        Err(err) => eprintln!("Can't tell which schedule entries missed their displays: {err}"),
+    }
+}
+
+/// Writes `queue` to `path` if it differs from `before`; a failure only costs the queue after a
+/// restart, so it's reported and watching goes on.
+fn save_queue(queue: &ActionQueue, before: &ActionQueue, path: &Path) {
+    if queue != before
+        && let Err(err) = queue.save(path)
+    {
+        eprintln!("Can't save the schedule queue to {}: {err}", path.display());
+    }
+}
+
+/// `watch --show-queue`: the queued entries still waiting for their display.
+fn print_queue(queue: &ActionQueue) {
+    let now = unix_time();
+    let mut pending = queue.pending(now).peekable();
+    if pending.peek().is_none() {
+        say!("No queued schedule entries");
+    }
+    for action in pending {
+        let minutes = |seconds: u64| seconds.div_ceil(60);
+        let (waited, left) = (minutes(now.saturating_sub(action.fired)), minutes(action.expires - now));
+        say!(
+            "{} ({}) for {}: {}; queued {}h {:02}m ago, expires in {}h {:02}m",
+            action.entry, action.at, action.display, action.apply, waited / 60, waited % 60, left / 60, left % 60
+        );
+    }
+}
+
+/// Waits for the next watch event, or `None` once a schedule entry fires. The time to wait is
+/// worked out from the clock on every wake-up, so sleep and clock changes don't make the
+/// schedule drift; `checked` is the time up to which fired entries have been handled.
+fn next_event(events: &Receiver<WatchEvent>, schedule: Option<&RuleSet>, checked: &mut TimeOfDay) -> Result<Option<WatchEvent>> {
+    let Some(schedule) = schedule else {
+        return Ok(Some(events.recv()?));
+    };
+    loop {
+        let now = TimeOfDay::now();
+        let fired = schedule.fired_between(*checked, now);
+        if !fired.is_empty() {
+            for entry in fired {
+                say!("Schedule entry '{}' ({}) fired", entry.name, entry.at);
+            }
+            return Ok(None);
+        }
+        *checked = now;
+        let wait = schedule
+            .upcoming(now)
+            .first()
+            .map_or(SCHEDULE_POLL, |(wait, _)| (*wait).min(SCHEDULE_POLL));
+        match events.recv_timeout(wait) {
+            Err(RecvTimeoutError::Timeout) => {}
+            event => return Ok(Some(event?)),
+        }
+    }
+}
+
+/// `watch --dry-run`: the schedule entries of the next 24 hours in firing order, with the
+/// connected displays each one would change.
+fn print_schedule(rules: &RuleSet) -> Result<()> {
+    let now = TimeOfDay::now();
+    let upcoming = rules.upcoming(now);
+    if upcoming.is_empty() {
+        say!("No schedule entries");
+        return Ok(());
+    }
+    let displays = enumerate_displays()?;
+    for (wait, entry) in upcoming {
+        let minutes = wait.as_secs().div_ceil(60);
+        say!("{} (in {}h {:02}m)  {}: {}", entry.at, minutes / 60, minutes % 60, entry.name, entry.apply);
+        let targets: Vec<&DisplayInfo> = displays
+            .iter()
+            .filter(|d| rules.scheduled_for(d, entry.at).is_some_and(|e| std::ptr::eq(e, entry)))
+            .collect();
+        if targets.is_empty() {
+            say!("    no connected display ({})", entry.criteria);
+        }
+        for display in targets {
+            say!("    {} {}", display.source_id, display.friendly_name);
+        }
+    }
+    Ok(())
+}
+
+fn run_interactive() -> Result<()> {
+    let displays = enumerate_displays()?;
+    if displays.is_empty() {
+        return Err(anyhow!("No matching displays found"));
+    }
+
+    for (i, d) in displays.iter().enumerate() {
+        say!("{:>3}) {d}", i + 1);
+    }
+    let index = prompt_choice("Display", displays.len(), false)?.expect("a display choice is required");
+    let disp = &displays[index];
+
+    let mut resolutions: Vec<(u32, u32)> = Vec::new();
+    for mode in supported_modes(disp)? {
+        if !resolutions.contains(&(mode.width, mode.height)) {
+            resolutions.push((mode.width, mode.height));
+        }
+    }
+    say!("Resolution:");
+    for (i, (width, height)) in resolutions.iter().enumerate() {
+        let current = if (*width, *height) == (disp.width, disp.height) { " (current)" } else { "" };
+        say!("{:>3}) {width}x{height}{current}", i + 1);
+    }
+    let (width, height) = prompt_choice("Resolution [Enter keeps current]", resolutions.len(), true)?
+        .map_or((disp.width, disp.height), |i| resolutions[i]);
+
+    let scalings = disp.supported_scaling();
+    say!("Scaling:");
+    for (i, scaling) in scalings.iter().enumerate() {
+        let mut notes = Vec::new();
+        if *scaling == disp.scaling_current {
+            notes.push("current");
+        }
+        if *scaling == disp.scaling_recommended {
+            notes.push("recommended");
+        }
+        let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+        say!("{:>3}) {scaling}%{notes}", i + 1);
+    }
+    let scaling = prompt_choice("Scaling [Enter keeps current]", scalings.len(), true)?
+        .map_or(disp.scaling_current, |i| scalings[i]);
+
+    let target = DisplayConfig { width, height, scaling };
+    let summary = describe_changes(disp, &target);
+    if summary.is_empty() {
+        say!("Nothing to change");
+        return Ok(());
+    }
+    say!("Display {} will change:", disp.source_id);
+    for line in &summary {
+        say!("  {line}");
+    }
+    if !prompt("Apply? [y/N]")?.eq_ignore_ascii_case("y") {
+        say!("Cancelled");
+        return Ok(());
+    }
+
+    apply_with_confirm(
+        &[(disp.clone(), target)],
+        Some(Duration::from_secs(INTERACTIVE_CONFIRM_SECS)),
+        ApplyPolicy::Strict,
+        ApplyBackend::Ccd,
+    )?
+    .into_result()?;
+    Ok(())
+}
+
+fn run_completions(shell: Shell) -> Result<()> {
+    let mut cmd = Cli::command();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cmd, "display-tuner", &mut script);
+    let mut script = String::from_utf8(script)?;
+
+    if shell == Shell::PowerShell {
+        let anchor = "param($wordToComplete, $commandAst, $cursorPosition)\n";
+        let at = script
+            .find(anchor)
+            .ok_or_else(|| anyhow!("Unexpected PowerShell completion script layout"))?
+            + anchor.len();
+        script.insert_str(at, POWERSHELL_DYNAMIC_COMPLETER);
+    }
+
+    io::stdout().write_all(script.as_bytes())?;
+    Ok(())
+}
+
+fn run_complete_ids() -> Result<()> {
+    let cache = std::env::temp_dir().join("display-tuner-complete-ids");
+    let fresh = fs::metadata(&cache)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < COMPLETION_CACHE_TTL);
+
+    let candidates = if let Some(cached) = fresh.then(|| fs::read_to_string(&cache).ok()).flatten() {
+        cached
+    } else {
+        let mut candidates = String::new();
+        for d in enumerate_displays()? {
+            writeln!(candidates, "{}\t{}", d.source_id, d.friendly_name)?;
+        }
+        // A stale or missing cache only costs speed, so write failures are ignored.
+        let _ = fs::write(&cache, &candidates);
+        candidates
+    };
+
+    print!("{candidates}");
+    Ok(())
+}
+
+/// Refuses resolutions that are almost certainly typos, see `DisplayConfig::validate`.
+fn validate_resolutions(changes: &[(DisplayInfo, DisplayConfig)]) -> Result<()> {
+    for (disp, target) in changes {
+        // Without a mode list only the heuristics are left.
+        let modes = supported_modes(disp).unwrap_or_default();
+        target
+            .validate(disp, &modes)
+            .map_err(|error| DisplayError::Invalid { source_id: disp.source_id, error })?;
+    }
+    Ok(())
+}
+
+/// Best-effort `set` inside a Remote Desktop session: the resolution goes through
+/// `ChangeDisplaySettingsEx`, which the virtual display driver accepts more often than the CCD API.
+fn run_set_remote(changes: &[(DisplayInfo, DisplayConfig)]) -> Result<ExitCode> {
+    say!("Remote Desktop session: applying best effort");
+    for (disp, target) in changes {
+        let mut now = disp.clone();
+        if (disp.width, disp.height) != (target.width, target.height) {
+            say!("Changing display {} to {}x{}", disp.source_id, target.width, target.height);
+            set_display_mode_legacy(disp, target.width, target.height, None)?;
+            (now.width, now.height) = (target.width, target.height);
+        }
+        apply_display_config(&now, target)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The displays `set` addresses: by alias, `--current`/`--focused`, `--where`, `--id` or `--all`.
+fn select_targets(args: &SetArgs, all: &[DisplayInfo]) -> Result<Vec<DisplayInfo>> {
+    let mut displays = all.to_vec();
+
+    if let Some(alias) = &args.alias {
+        displays = vec![resolve_alias(&load_groups()?.aliases, alias, all)?.clone()];
+    } else if args.current {
+        displays = vec![display_under_cursor(all)?.clone()];
+    } else if args.focused {
+        displays = vec![display_of_focused_window(all)?.clone()];
+    } else if let Some(name) = &args.group {
+        displays = resolve_group(&load_groups()?, name, all)?;
+    } else if let Some(expr) = &args.filter_expr {
+        let filter = DisplayFilter { expr: Some(expr.clone()), ..DisplayFilter::default() };
+        displays = filter_displays(displays, &filter);
+        if displays.is_empty() {
+            return Err(anyhow!("No display matches --where '{expr}'"));
+        }
+    } else if let Some(number) = args.display_number {
+        let filter = DisplayFilter { display_number: Some(number), ..DisplayFilter::default() };
+        displays = filter_displays(displays, &filter);
+    } else if let Some(index) = args.index {
+        displays = vec![display_at_index(all, index)?.clone()];
+    } else if !args.all {
+        if let Some(id) = args.id {
+            let filter = DisplayFilter { id: Some(id), ..DisplayFilter::default() };
+            displays = filter_displays(displays, &filter);
+            if displays.is_empty() {
+                return Err(id_not_found(all, id).into());
+            }
+        } else {
+           return Err(anyhow!("No display source id specified"));
+        }
+    }
+
+    if args.internal_only || args.external_only {
+        displays.retain(|disp| disp.internal == args.internal_only);
+    }
+    if args.all || args.group.is_some() {
+        displays.retain(|disp| {
+            if !disp.target_available {
+                say!("Skipping display {}: target currently unavailable (asleep or disconnected)", disp.source_id);
+            }
+            disp.target_available
+        });
+    }
+    Ok(displays)
+}
+
+/// The resolution and refresh rate `set` asks of `disp`: the mode of `--mode`, its native mode
+/// with `--native`, the mode nearest to a part of it with `--res-scale`, otherwise the flags. Both follow the rotation `disp` ends up with.
+fn mode_builder(args: &SetArgs, disp: &DisplayInfo) -> Result<DisplayConfigBuilder> {
+    let builder = DisplayConfig::builder().rotation(args.rotate);
+    let rotation = builder.resolve_rotation(disp);
+    if let Some(spec) = args.mode {
+        let mode = find_mode(disp, spec)?;
+        let (width, height) = rotation.orient((mode.width, mode.height));
+        // 0 and 1 stand for the hardware default rate.
+        let refresh = (mode.refresh > 1).then_some(RefreshRate::Hz(mode.refresh));
+        return Ok(builder.width(width).height(height).refresh(refresh));
+    }
+    if let Some(percent) = args.res_scale {
+        let scaled = find_scaled_mode(disp, percent, args.force)?;
+        let ((exact_width, exact_height), mode) = (scaled.exact, scaled.mode);
+        say!(
+            "Display {}: {percent}% of the native resolution is {exact_width}x{exact_height}, using {}x{}",
+            disp.source_id, mode.width, mode.height
+        );
+        let (width, height) = rotation.orient((mode.width, mode.height));
+        return Ok(builder.width(width).height(height).refresh(args.refresh));
+    }
+    if !args.native {
+        let (width, height) = match args.res {
+            Some((width, height)) => {
+                let (width, height) = oriented_resolution(disp, (width, height), rotation)?;
+                (Some(width), Some(height))
+            }
+            None => (args.width, args.height),
+        };
+        return Ok(builder.width(width).height(height).refresh(args.refresh));
+    }
+    let native = native_mode(disp, &supported_modes(disp)?)
+        .ok_or_else(|| DisplayError::NoDisplayModes(disp.gdi_device_name.clone()))?;
+    if !native.reported {
+        say!("Display {} reports no native mode, using its largest mode {}", disp.source_id, native.mode);
+    }
+    let (width, height) = native.rotated_size(rotation);
+    // 0 and 1 stand for the hardware default rate.
+    let refresh = (native.mode.refresh > 1).then_some(RefreshRate::Hz(native.mode.refresh));
+    Ok(builder.width(width).height(height).refresh(refresh))
+}
+
+/// `--res` for `disp` turned by `rotation`, swapped with a notice if only the other orientation
+/// is supported. Unsupported sizes are passed on for Windows to reject (or adjust).
+fn oriented_resolution(disp: &DisplayInfo, (width, height): (u32, u32), rotation: Rotation) -> Result<(u32, u32)> {
+    match match_mode(&supported_modes(disp)?, (width, height), rotation) {
+        ModeMatch::Transposed => {
+            say!(
+                "Display {}: {width}x{height} isn't supported at {rotation}, using {height}x{width}",
+                disp.source_id
+            );
+            Ok((height, width))
+        }
+        ModeMatch::Supported | ModeMatch::Unsupported => Ok((width, height)),
+    }
+}
+
+/// Turns the displays in `changes` to `rotation` along with their target resolution, then
+/// refreshes their state so the remaining settings apply on top of the new orientation.
+fn rotate_displays(changes: &mut [(DisplayInfo, DisplayConfig)], rotation: Rotation) -> Result<()> {
+    let sources: Vec<SourceMode> = changes
+        .iter()
+        .filter(|(disp, _)| disp.rotation != rotation)
+        .map(|(disp, target)| SourceMode { width: target.width, height: target.height, rotation, ..SourceMode::of(disp) })
+        .collect();
+    if sources.is_empty() {
+        return Ok(());
+    }
+    for source in &sources {
+        say!("Rotating display {} to {rotation}", source.source_id);
+    }
+    apply_source_modes(&sources)?;
+    refresh_changes(changes)
+}
+
+/// Replaces the displays in `changes` with how they look now, after a change applied apart.
+fn refresh_changes(changes: &mut [(DisplayInfo, DisplayConfig)]) -> Result<()> {
+    let current = enumerate_displays()?;
+    for (disp, _) in changes {
+        if let Some(now) = current.iter().find(|d| d.source_id == disp.source_id) {
+            *disp = now.clone();
+        }
+    }
+    Ok(())
+}
+
+/// The scaling closest to an `effective` workspace at the target resolution, printed along
+/// with the size it actually gives.
+fn effective_scaling(disp: &DisplayInfo, target: &DisplayConfig, effective: (u32, u32)) -> Result<i32> {
+    let scaling = disp.scaling_for_effective((target.width, target.height), effective)?;
+    let (width, height) = effective_size(target.width, target.height, scaling);
+    say!("Display {}: {scaling}% scaling, effective workspace {width}x{height}", disp.source_id);
+    Ok(scaling)
+}
+
+/// The scaling `disp` should get, reporting displays that can't reach the requested value.
+fn target_scaling(disp: &DisplayInfo, scaling: Option<ScalingArg>, reference: Option<i32>) -> i32 {
+    match (scaling, reference) {
+        (_, Some(reference)) => {
+            let clamped = disp.clamp_scaling(reference);
+            if clamped != reference {
+                say!("Display {} doesn't support {reference}%, using {clamped}%", disp.source_id);
+            }
+            clamped
+        }
+        (Some(ScalingArg::Steps(steps)), None) => {
+            let stepped = disp.step_scaling(steps);
+            if !disp.can_step_scaling(steps) {
+                say!("Display {} can't move {steps:+} scaling steps, stopping at {stepped}%", disp.source_id);
+            }
+            stepped
+        }
+        (Some(ScalingArg::Percent(percent)), None) => percent,
+        (None, None) => disp.scaling_current,
+    }
+}
+
+/// Applies every change, then asks for confirmation if `timeout` is set and reverts without it.
+///
+/// Returns whether the OS adjusted any change (only possible with [`ApplyPolicy::AllowChanges`])
+/// and which displays failed; [`ApplyOutcome::into_result`] turns those into the error.
+fn apply_with_confirm(
+    changes: &[(DisplayInfo, DisplayConfig)],
+    timeout: Option<Duration>,
+    policy: ApplyPolicy,
+    backend: ApplyBackend,
+) -> Result<ApplyOutcome> {
+    let mut outcome = ApplyOutcome::default();
+    let mut applied = Vec::new();
+    for (disp, target) in changes {
+        say!("Applying to display {}: {target:?}", disp.source_id);
+        let planned = match plan(std::slice::from_ref(disp), &[Target::config(disp.source_id, target)]) {
+            Ok(planned) => planned,
+            // Refused before anything changed, so there's nothing to revert.
+            Err(err) => {
+                say!("Display {} failed: {err}", disp.source_id);
+                outcome.failed.push(disp.source_id);
+                continue;
+            }
+        };
+        let started = Instant::now();
+        let result = if policy == ApplyPolicy::Strict {
+            apply_display_config_via(disp, target, backend).map(|used| {
+                if (disp.width, disp.height) != (target.width, target.height) {
+                    say!("Display {} resolution changed through {used}", disp.source_id);
+                }
+                let pending = disp.scaling_current != target.scaling
+                    && scaling_pending(disp, target.scaling).unwrap_or(false);
+                pending.then(|| pending_report(disp, target))
+            })
+        } else {
+            apply_display_config_with(disp, target, policy).map(Some)
+        };
+        match result {
+            Ok(report) => {
+                print_apply_time(disp.source_id, started.elapsed(), report.as_ref().map(|r| r.timings));
+                if let Some(report) = report {
+                    if report.adjusted() {
+                        print_adjustments(&report);
+                        outcome.adjusted = true;
+                    }
+                    if let Some(pending) = report.pending_scaling {
+                        print_sign_out_notice(report.source_id, report.actual.scaling, pending);
+                        outcome.sign_out_needed = true;
+                    }
+                    outcome.reports.push(report);
+                }
+                applied.push((disp.source_id, planned));
+            }
+            // The call is still running, so reverting or moving on would race with it.
+            Err(DisplayError::ApplyTimedOut) => return Err(DisplayError::ApplyTimedOut.into()),
+            Err(err) => {
+                say!("Display {} failed: {err}", disp.source_id);
+                // A resolution change may have gone through before the scaling failed.
+                revert(&planned)?;
+                outcome.failed.push(disp.source_id);
+            }
+        }
+    }
+
+    let confirmed = match timeout {
+        Some(timeout) if !applied.is_empty() => confirm_within(timeout)?,
+        _ => true,
+    };
+    if !confirmed {
+        for (source_id, planned) in applied {
+            say!("Reverting display {source_id}");
+            revert(&planned)?;
+        }
+    }
+    outcome.adjusted &= confirmed;
+    outcome.sign_out_needed &= confirmed;
+    outcome.reverted = !confirmed;
+    Ok(outcome)
+}
+
+/// Report for a strict apply whose scaling waits for a sign-out; the rest went through as
+/// requested.
+fn pending_report(disp: &DisplayInfo, target: &DisplayConfig) -> ApplyReport {
+    ApplyReport {
+        source_id: disp.source_id,
+        requested: target.clone(),
+        actual: DisplayConfig { scaling: disp.scaling_current, ..target.clone() },
+        allowed_changes: false,
+        side_effects: Vec::new(),
+        pending_scaling: Some(target.scaling),
+        work_area: None,
+        timings: ApplyTimings::default(),
+    }
+}
+
+/// Prints how long applying to `source_id` took, broken down by phase when it was measured.
+fn print_apply_time(source_id: u32, elapsed: Duration, timings: Option<ApplyTimings>) {
+    match timings.filter(|t| !t.total().is_zero()) {
+        Some(timings) => say!("Display {source_id} applied in {} ms ({timings})", elapsed.as_millis()),
+        None => say!("Display {source_id} applied in {} ms", elapsed.as_millis()),
+    }
+}
+
+fn print_sign_out_notice(source_id: u32, current: i32, pending: i32) {
+    say!();
+    say!("!! Display {source_id}: scaling {pending}% is saved but Windows still shows {current}%.");
+    say!("!! Sign out and back in to finish the change (or run again with --sign-out).");
+    say!();
+}
+
+/// Undoes `planned`, planning the inverse against the displays as they are now: Windows may
+/// have adjusted the change, or applied only part of it.
+fn revert(planned: &ChangeSet) -> Result<()> {
+    if planned.is_empty() {
+        return Ok(());
+    }
+    execute(&plan(&enumerate_displays()?, &planned.inverse().targets())?)?;
+    Ok(())
+}
+
+fn print_adjustments(report: &ApplyReport) {
+    let (requested, actual) = (&report.requested, &report.actual);
+    say!("Display {} applied with adjustments:", report.source_id);
+    if (requested.width, requested.height) != (actual.width, actual.height) {
+        say!(
+            "  resolution: requested {}x{}, got {}x{}",
+            requested.width, requested.height, actual.width, actual.height
+        );
+    }
+    if report.pending_scaling.is_none() && requested.scaling != actual.scaling {
+        say!("  scaling: requested {}%, got {}%", requested.scaling, actual.scaling);
+    }
+    for (before, after) in &report.side_effects {
+        say!(
+            "  display {}: {}x{} at ({}, {}) -> {}x{} at ({}, {})",
+            before.source_id, before.width, before.height, before.x, before.y, after.width, after.height, after.x, after.y
+        );
+    }
+}
+
+fn confirm_within(timeout: Duration) -> Result<bool> {
+    ask!("Keep these settings? [y/N] (reverting in {}s) ", timeout.as_secs());
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut line = String::new();
+        // Closed input isn't an answer, so the timeout still runs.
+        if matches!(io::stdin().lock().read_line(&mut line), Ok(n) if n > 0) {
+            let _ = tx.send(line);
+        }
+    });
+
+    if let Ok(line) = rx.recv_timeout(timeout) {
+        Ok(line.trim().eq_ignore_ascii_case("y"))
+    } else {
+        say!();
+        Ok(false)
+    }
+}
+
+fn prompt(question: &str) -> Result<String> {
+    ask!("{question}: ");
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(anyhow!("Input closed"));
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Asks for a 1-based choice until the answer is valid; returns the 0-based index.
+fn prompt_choice(question: &str, count: usize, allow_empty: bool) -> Result<Option<usize>> {
+    loop {
+        let answer = prompt(&format!("{question} [1-{count}]"))?;
+        if answer.is_empty() && allow_empty {
+            return Ok(None);
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=count).contains(&n) => return Ok(Some(n - 1)),
+            _ => say!("Please enter a number between 1 and {count}"),
+        }
+    }
+}
+
+fn describe_changes(disp: &DisplayInfo, target: &DisplayConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+    if (disp.width, disp.height) != (target.width, target.height) {
+        lines.push(format!(
+            "resolution: {}x{} -> {}x{}",
+            disp.width, disp.height, target.width, target.height
+        ));
+    }
+    if disp.scaling_current != target.scaling {
+        lines.push(format!("scaling: {}% -> {}%", disp.scaling_current, target.scaling));
+    }
+    lines
+}
+
+fn brief_line(d: &DisplayInfo) -> String {
+    format!(
+        "{} {} {}x{} {}% {}",
+        d.source_id, d.friendly_name, d.width, d.height, d.scaling_current, d.connector
+    )
+}
@@ -0,0 +1,206 @@
+//! Counters for monitoring a long-running process, read by the daemon's `stats` command.
+//!
+//! Everything is kept in memory for the life of the process and updated with relaxed atomics,
+//! so recording costs next to nothing; only failures take a lock, to count them by error code.
+//! [`snapshot`] reads the counters as [`Stats`], whose JSON form is kept stable for scrapers:
+//! fields may be added, but none are renamed or removed.
+
+use crate::display::DisplayInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Upper bounds of the latency buckets, in milliseconds; a last bucket takes everything slower.
+pub const BUCKET_BOUNDS_MS: [u64; 9] = [10, 50, 100, 250, 500, 1000, 2500, 5000, 10_000];
+
+const BUCKETS: usize = BUCKET_BOUNDS_MS.len() + 1;
+
+/// A step whose latency is recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Querying the displays.
+    Enumerate,
+    /// One `SetDisplayConfig` call, with its retries.
+    DisplayConfig,
+    /// Setting the scaling of one display.
+    Scaling,
+    /// Handling one daemon request, from reading it to answering it.
+    Request,
+}
+
+struct Histogram {
+    counts: [AtomicU64; BUCKETS],
+    sum_ms: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self { counts: [const { AtomicU64::new(0) }; BUCKETS], sum_ms: AtomicU64::new(0) }
+    }
+
+    fn record(&self, elapsed: Duration) {
+        let ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        let bucket = BUCKET_BOUNDS_MS.iter().position(|&bound| ms <= bound).unwrap_or(BUCKET_BOUNDS_MS.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencyHistogram {
+        let counts: Vec<u64> = self.counts.iter().map(|c| c.load(Ordering::Relaxed)).collect();
+        LatencyHistogram {
+            count: counts.iter().sum(),
+            sum_ms: self.sum_ms.load(Ordering::Relaxed),
+            bounds_ms: BUCKET_BOUNDS_MS.to_vec(),
+            counts,
+        }
+    }
+}
+
+struct Registry {
+    applies: AtomicU64,
+    failures: Mutex<BTreeMap<&'static str, u64>>,
+    /// Hash of the connected displays last seen, 0 before the first enumeration.
+    topology: AtomicU64,
+    /// Unix time in seconds, 0 if the topology didn't change yet.
+    topology_changed: AtomicU64,
+    last_lag_ms: AtomicU64,
+    max_lag_ms: AtomicU64,
+    phases: [Histogram; 4],
+}
+
+static REGISTRY: Registry = Registry {
+    applies: AtomicU64::new(0),
+    failures: Mutex::new(BTreeMap::new()),
+    topology: AtomicU64::new(0),
+    topology_changed: AtomicU64::new(0),
+    last_lag_ms: AtomicU64::new(0),
+    max_lag_ms: AtomicU64::new(0),
+    phases: [const { Histogram::new() }; 4],
+};
+
+static STARTED: OnceLock<u64> = OnceLock::new();
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Marks the start of the process for [`Stats::started_at`]; otherwise the first [`snapshot`]
+/// counts as the start.
+pub fn mark_started() {
+    STARTED.get_or_init(unix_now);
+}
+
+pub fn record_phase(phase: Phase, elapsed: Duration) {
+    REGISTRY.phases[phase as usize].record(elapsed);
+}
+
+/// Runs `f`, recording how long it took under `phase`.
+pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    record_phase(phase, started.elapsed());
+    result
+}
+
+/// Counts a change that was asked for, and its error code if it failed.
+pub fn record_apply(failure: Option<&'static str>) {
+    REGISTRY.applies.fetch_add(1, Ordering::Relaxed);
+    if let Some(code) = failure {
+        let mut failures = REGISTRY.failures.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        *failures.entry(code).or_default() += 1;
+    }
+}
+
+/// Notes the displays just enumerated, recording the time when another set of displays is
+/// connected or active than the last time.
+pub fn observe_topology(displays: &[DisplayInfo]) {
+    let mut paths: Vec<(String, bool)> = displays.iter().map(|d| (d.device_path.to_lowercase(), d.active)).collect();
+    paths.sort();
+    let mut hasher = DefaultHasher::new();
+    paths.hash(&mut hasher);
+    // 0 stands for "not seen yet".
+    let topology = hasher.finish().max(1);
+    let previous = REGISTRY.topology.swap(topology, Ordering::Relaxed);
+    if previous != 0 && previous != topology {
+        REGISTRY.topology_changed.store(unix_now(), Ordering::Relaxed);
+    }
+}
+
+/// Records how long the event loop was held up by one piece of work, during which nothing else
+/// could be served.
+pub fn record_lag(lag: Duration) {
+    let ms = u64::try_from(lag.as_millis()).unwrap_or(u64::MAX);
+    REGISTRY.last_lag_ms.store(ms, Ordering::Relaxed);
+    REGISTRY.max_lag_ms.fetch_max(ms, Ordering::Relaxed);
+}
+
+/// The counters as they are now.
+#[must_use]
+pub fn snapshot() -> Stats {
+    let started_at = *STARTED.get_or_init(unix_now);
+    let failures = REGISTRY.failures.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    let phase = |phase: Phase| REGISTRY.phases[phase as usize].snapshot();
+    Stats {
+        started_at,
+        uptime_secs: unix_now().saturating_sub(started_at),
+        applies: REGISTRY.applies.load(Ordering::Relaxed),
+        failures: failures.iter().map(|(code, count)| ((*code).to_string(), *count)).collect(),
+        last_topology_change: Some(REGISTRY.topology_changed.load(Ordering::Relaxed)).filter(|&t| t != 0),
+        event_loop: EventLoopStats {
+            last_lag_ms: REGISTRY.last_lag_ms.load(Ordering::Relaxed),
+            max_lag_ms: REGISTRY.max_lag_ms.load(Ordering::Relaxed),
+        },
+        phases: PhaseStats {
+            enumerate: phase(Phase::Enumerate),
+            display_config: phase(Phase::DisplayConfig),
+            scaling: phase(Phase::Scaling),
+            request: phase(Phase::Request),
+        },
+    }
+}
+
+/// The counters of a process, see the [module docs](self). Times are Unix seconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    pub started_at: u64,
+    pub uptime_secs: u64,
+    /// Changes asked for, successful or not.
+    pub applies: u64,
+    /// Failed changes by [error code](crate::display::DisplayError::kind).
+    pub failures: BTreeMap<String, u64>,
+    /// When other displays were last connected or turned on or off, `None` if they weren't
+    /// since the start.
+    pub last_topology_change: Option<u64>,
+    pub event_loop: EventLoopStats,
+    pub phases: PhaseStats,
+}
+
+/// How long single requests held up the daemon, which serves one client at a time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventLoopStats {
+    pub last_lag_ms: u64,
+    pub max_lag_ms: u64,
+}
+
+/// Latencies by [`Phase`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseStats {
+    pub enumerate: LatencyHistogram,
+    pub display_config: LatencyHistogram,
+    pub scaling: LatencyHistogram,
+    pub request: LatencyHistogram,
+}
+
+/// How many times took up to each of `bounds_ms`: `counts[i]` holds those above
+/// `bounds_ms[i - 1]` and up to `bounds_ms[i]`, and the extra last count those above all of
+/// them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    pub count: u64,
+    pub sum_ms: u64,
+    pub bounds_ms: Vec<u64>,
+    pub counts: Vec<u64>,
+}
@@ -0,0 +1,122 @@
+//! Async wrappers around the blocking calls, for applications running an async runtime
+//! (enabled by the `async` feature).
+//!
+//! Enumerating and applying go through Win32 calls that take hundreds of milliseconds, or
+//! seconds on a slow dock, and would stall the runtime's worker threads. Each wrapper runs its
+//! call on a thread of its own and returns a [`Blocking`] future that resolves with the
+//! result. It doesn't depend on a particular runtime; any executor can poll it.
+//!
+//! ```no_run
+//! # async fn example() -> display_tuner::display::Result<()> {
+//! use display_tuner::display::DisplayConfig;
+//! use display_tuner::nonblocking::{apply_display_config_async, enumerate_displays_async};
+//!
+//! for display in enumerate_displays_async().await? {
+//!     let config = DisplayConfig::builder().scaling(150).resolve(&display);
+//!     apply_display_config_async(display, config).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Cancellation
+//!
+//! Dropping a future doesn't cancel its call: Windows offers no way to abort
+//! `SetDisplayConfig` once it runs, so the thread finishes the call and the display may still
+//! change. The result is discarded. To bound how long a caller waits, use
+//! [`set_apply_timeout`](crate::display::set_apply_timeout) or the runtime's own timeout;
+//! either way the call keeps running in the background.
+
+use crate::copy::{self, CopyReport};
+use crate::display::{self, ApplyPolicy, ApplyReport, DisplayConfig, DisplayInfo, DisplayMode, Result, SourceMode};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// The result of a call running on its own thread, see [`spawn_blocking`].
+///
+/// A panic in the call is resumed when the future is polled.
+#[derive(Debug)]
+#[must_use = "the call runs either way, but its result is lost unless the future is awaited"]
+pub struct Blocking<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Runs `call` on a new thread and returns a future for its result.
+pub fn spawn_blocking<T: Send + 'static>(call: impl FnOnce() -> T + Send + 'static) -> Blocking<T> {
+    let shared = Arc::new(Mutex::new(Shared { result: None, waker: None }));
+    let finished = Arc::clone(&shared);
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(call));
+        let mut shared = finished.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    });
+    Blocking { shared }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        match shared.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// [`display::enumerate_displays`] on its own thread.
+pub fn enumerate_displays_async() -> Blocking<Result<Vec<DisplayInfo>>> {
+    spawn_blocking(display::enumerate_displays)
+}
+
+/// [`display::supported_modes`] on its own thread.
+pub fn supported_modes_async(display: DisplayInfo) -> Blocking<Result<Vec<DisplayMode>>> {
+    spawn_blocking(move || display::supported_modes(&display))
+}
+
+/// [`display::apply_display_config`] on its own thread.
+pub fn apply_display_config_async(display: DisplayInfo, config: DisplayConfig) -> Blocking<Result<()>> {
+    spawn_blocking(move || display::apply_display_config(&display, &config))
+}
+
+/// [`display::apply_display_config_with`] on its own thread.
+pub fn apply_display_config_with_async(
+    display: DisplayInfo,
+    config: DisplayConfig,
+    policy: ApplyPolicy,
+) -> Blocking<Result<ApplyReport>> {
+    spawn_blocking(move || display::apply_display_config_with(&display, &config, policy))
+}
+
+/// [`display::validate_display_config`] on its own thread.
+pub fn validate_display_config_async(display: DisplayInfo, config: DisplayConfig) -> Blocking<Result<()>> {
+    spawn_blocking(move || display::validate_display_config(&display, &config))
+}
+
+/// [`display::apply_source_modes`] on its own thread.
+pub fn apply_source_modes_async(sources: Vec<SourceMode>) -> Blocking<Result<()>> {
+    spawn_blocking(move || display::apply_source_modes(&sources))
+}
+
+/// [`copy::copy_config`] on its own thread.
+pub fn copy_config_async(from: DisplayInfo, to: DisplayInfo) -> Blocking<Result<CopyReport>> {
+    spawn_blocking(move || copy::copy_config(&from, &to))
+}
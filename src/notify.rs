@@ -0,0 +1,204 @@
+//! Toast notifications announcing the changes resident modes make on their own, e.g. the daemon
+//! applying a profile after docking, so they don't pass for Windows acting up.
+//!
+//! Nothing is shown until [`enable`] is called, which `watch --notify` and `daemon --notify` do.
+//! Toasts are shown by a thread of their own: [`announce`] never blocks, and a toast that can't
+//! be shown is only logged.
+
+use crate::display::{DisplayInfo, Result};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+use windows::Win32::System::Registry::{RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ};
+use windows::Win32::System::WinRT::{RoInitialize, RO_INIT_MULTITHREADED};
+use windows::core::{w, HSTRING};
+
+/// The `AppUserModelID` toasts are shown under, registered for the current user by [`enable`].
+pub const APP_ID: &str = "Kuhnix46.DisplayTuner";
+
+/// The shortest time between two toasts; changes announced sooner are only logged.
+pub const MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The `arguments` of the Revert button, handed back when it's clicked.
+const REVERT_ARGUMENT: &str = "revert";
+
+/// Allows one toast per `interval`.
+#[derive(Debug)]
+pub struct ToastLimiter {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl ToastLimiter {
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last: None }
+    }
+
+    /// Whether a toast may be shown at `now`, counting it as shown if so.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        if self.last.is_some_and(|last| now.saturating_duration_since(last) < self.interval) {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+/// One toast: a line per changed display and optionally a Revert button.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toast {
+    pub lines: Vec<String>,
+    /// Whether clicking Revert sends [`Request::Revert`](crate::daemon::Request::Revert) to the
+    /// daemon, which only undoes changes made through it.
+    pub revert: bool,
+}
+
+impl Toast {
+    /// The toast's `ToastGeneric` document.
+    #[must_use]
+    pub fn xml(&self) -> String {
+        let actions = if self.revert {
+            format!(r#"<actions><action content="Revert" arguments="{REVERT_ARGUMENT}" activationType="foreground"/></actions>"#)
+        } else {
+            String::new()
+        };
+        format!(
+            r#"<toast><visual><binding template="ToastGeneric"><text>Display settings changed</text><text>{}</text></binding></visual>{actions}</toast>"#,
+            escape(&self.lines.join("\n"))
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// What changed between `before` and `after`, one line per display like
+/// "Set DELL U2720 to 3840x2160 @ 150%". Displays are matched by device path; those only in
+/// one of the lists aren't announced.
+#[must_use]
+pub fn change_lines(before: &[DisplayInfo], after: &[DisplayInfo]) -> Vec<String> {
+    after
+        .iter()
+        .filter(|display| {
+            before.iter().find(|d| d.device_path == display.device_path).is_some_and(|d| {
+                (d.width, d.height, d.scaling_current) != (display.width, display.height, display.scaling_current)
+            })
+        })
+        .map(|display| {
+            let name = if display.friendly_name.is_empty() { format!("display {}", display.source_id) } else { display.friendly_name.clone() };
+            format!("Set {name} to {}x{} @ {}%", display.width, display.height, display.scaling_current)
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct Notifier {
+    limiter: ToastLimiter,
+    sender: Sender<Toast>,
+}
+
+static NOTIFIER: Mutex<Option<Notifier>> = Mutex::new(None);
+
+/// Shows toasts for the changes [`announce`]d for the rest of the process, at most one per
+/// [`MIN_INTERVAL`]. Registers [`APP_ID`] first, which Windows needs to show them at all; if that
+/// fails, toasts are attempted anyway.
+pub fn enable() {
+    if let Err(err) = register_app_id() {
+        warn!(%err, "Can't register the app for toast notifications");
+    }
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || show_toasts(&receiver));
+    *NOTIFIER.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Notifier { limiter: ToastLimiter::new(MIN_INTERVAL), sender });
+}
+
+/// Announces the changes between `before` and `after` (see [`change_lines`]) if [`enable`]d.
+/// `revertible` adds the Revert button; pass it only for changes made through the daemon.
+pub fn announce(before: &[DisplayInfo], after: &[DisplayInfo], revertible: bool) {
+    let mut notifier = NOTIFIER.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    let Some(notifier) = notifier.as_mut() else {
+        return;
+    };
+    let lines = change_lines(before, after);
+    if lines.is_empty() {
+        return;
+    }
+    if !notifier.limiter.allow(Instant::now()) {
+        debug!(?lines, "Toast skipped, the previous one was too recent");
+        return;
+    }
+    let toast = Toast { lines, revert: revertible && cfg!(feature = "daemon") };
+    // Fails only once the thread showing toasts gave up, which it logged.
+    let _ = notifier.sender.send(toast);
+}
+
+/// Adds [`APP_ID`] with a display name under the per-user `AppUserModelId` key.
+fn register_app_id() -> Result<()> {
+    let key = HSTRING::from(format!(r"Software\Classes\AppUserModelId\{APP_ID}"));
+    let data: Vec<u16> = "Display Tuner".encode_utf16().chain(std::iter::once(0)).collect();
+    // SAFETY: the key and value names are NUL-terminated; the data is too, and its byte size given.
+    unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            &key,
+            w!("DisplayName"),
+            REG_SZ.0,
+            Some(data.as_ptr().cast()),
+            u32::try_from(data.len() * size_of::<u16>())?,
+        )
+        .ok()?;
+    }
+    Ok(())
+}
+
+fn show_toasts(receiver: &Receiver<Toast>) {
+    // SAFETY: initializes WinRT for this thread, which stays in it until the process exits.
+    if let Err(err) = unsafe { RoInitialize(RO_INIT_MULTITHREADED) } {
+        warn!(%err, "Can't initialize WinRT, no toasts will be shown");
+        return;
+    }
+    // Clicks on the Revert button are only reported while the toast is alive.
+    let mut shown = None;
+    for toast in receiver {
+        match show(&toast) {
+            Ok(notification) => drop(shown.replace(notification)),
+            Err(err) => warn!(%err, "Failed to show a toast"),
+        }
+    }
+}
+
+fn show(toast: &Toast) -> windows::core::Result<ToastNotification> {
+    let document = XmlDocument::new()?;
+    document.LoadXml(&HSTRING::from(toast.xml()))?;
+    let notification = ToastNotification::CreateToastNotification(&document)?;
+    #[cfg(feature = "daemon")]
+    if toast.revert {
+        notification.Activated(&windows::Foundation::TypedEventHandler::new(|_, args: windows::core::Ref<windows::core::IInspectable>| on_activated(args.ok()?)))?;
+    }
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?.Show(&notification)?;
+    info!(lines = ?toast.lines, "Toast shown");
+    Ok(notification)
+}
+
+/// Sends [`Request::Revert`](crate::daemon::Request::Revert) to the daemon when Revert was clicked.
+#[cfg(feature = "daemon")]
+fn on_activated(args: &windows::core::IInspectable) -> windows::core::Result<()> {
+    use windows::UI::Notifications::ToastActivatedEventArgs;
+    use windows::core::Interface;
+
+    if args.cast::<ToastActivatedEventArgs>()?.Arguments()? != REVERT_ARGUMENT {
+        return Ok(());
+    }
+    info!("Reverting from the toast");
+    match crate::daemon::send_request(&crate::daemon::Request::Revert) {
+        Ok(response) if !response.ok => warn!(error = ?response.error, "Revert from the toast failed"),
+        Ok(_) => {}
+        Err(err) => warn!(%err, "Revert from the toast failed"),
+    }
+    Ok(())
+}
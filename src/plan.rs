@@ -0,0 +1,580 @@
+//! Deciding what to change, apart from changing it.
+//!
+//! [`plan`] compares the displays with the requested [`Target`]s and returns a [`ChangeSet`]
+//! listing the operations each display needs; it doesn't touch the OS, so a change set can be
+//! printed for a dry run, kept to roll back with [`ChangeSet::inverse`], or checked in tests.
+//! [`execute`] carries one out, and [`execute_on`] does so through another [`Backend`] than the
+//! live displays, e.g. in tests. [`ChangeSet::split_by_impact`] separates what can be applied
+//! without the screen going dark.
+
+use crate::display::{self, ApplyOrder, ApplyPolicy, ApplyTimings, DisplayConfig, DisplayError, DisplayInfo, Result, Rotation, DPI_VALUES};
+use crate::raw::{self, query_display_config, ModeInfo, PathInfo};
+use crate::{settle, throttle};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+use windows::Win32::Devices::Display::{DISPLAYCONFIG_RATIONAL, QDC_ONLY_ACTIVE_PATHS, SDC_ALLOW_CHANGES, SDC_APPLY, SDC_USE_SUPPLIED_DISPLAY_CONFIG};
+
+/// What one display should end up with; unset fields keep the current value.
+///
+/// `width` and `height` are desktop dimensions. When `rotation` turns a landscape desktop to
+/// portrait or back and they're unset, the current resolution turns with it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Target {
+    pub source_id: u32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Scaling in percent, one of [`DPI_VALUES`].
+    pub scaling: Option<i32>,
+    /// Refresh rate in Hz, as resolved by
+    /// [`DisplayConfigBuilder::resolve_refresh`](crate::display::DisplayConfigBuilder::resolve_refresh).
+    pub refresh: Option<u32>,
+    pub rotation: Option<Rotation>,
+}
+
+impl Target {
+    /// The resolution and scaling of `config` for display `source_id`.
+    #[must_use]
+    pub fn config(source_id: u32, config: &DisplayConfig) -> Self {
+        Self {
+            source_id,
+            width: Some(config.width),
+            height: Some(config.height),
+            scaling: Some(config.scaling),
+            ..Self::default()
+        }
+    }
+}
+
+/// One change to a display, with the value it replaces so it can be inverted. In JSON, the
+/// `operation` field names it, e.g. `{"operation":"set_scaling","from":100,"to":150}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum Operation {
+    /// Desktop resolution as (width, height).
+    SetMode { from: (u32, u32), to: (u32, u32) },
+    SetRefresh { from: u32, to: u32 },
+    SetRotation { from: Rotation, to: Rotation },
+    SetScaling { from: i32, to: i32 },
+}
+
+impl Operation {
+    /// The operation that undoes this one.
+    #[must_use]
+    pub fn inverse(self) -> Self {
+        match self {
+            Self::SetMode { from, to } => Self::SetMode { from: to, to: from },
+            Self::SetRefresh { from, to } => Self::SetRefresh { from: to, to: from },
+            Self::SetRotation { from, to } => Self::SetRotation { from: to, to: from },
+            Self::SetScaling { from, to } => Self::SetScaling { from: to, to: from },
+        }
+    }
+
+    /// Whether it goes through `SetDisplayConfig`; scaling has a call of its own.
+    fn is_mode(self) -> bool {
+        !matches!(self, Self::SetScaling { .. })
+    }
+
+    /// Whether the screen goes dark while it's carried out.
+    #[must_use]
+    pub fn impact(self) -> Impact {
+        match self {
+            Self::SetScaling { .. } => Impact::Seamless,
+            Self::SetMode { .. } | Self::SetRefresh { .. } | Self::SetRotation { .. } => Impact::ModeSwitch,
+        }
+    }
+}
+
+/// How an [`Operation`] shows on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Impact {
+    /// Applied while the picture stays, like a scaling change.
+    Seamless,
+    /// The display reconfigures and blanks for a second or so, which disrupts a video call or
+    /// a screen share.
+    ModeSwitch,
+}
+
+impl fmt::Display for Impact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Seamless => "seamless",
+            Self::ModeSwitch => "mode switch",
+        })
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SetMode { from, to } => write!(f, "resolution {}x{} -> {}x{}", from.0, from.1, to.0, to.1),
+            Self::SetRefresh { from, to } => write!(f, "refresh {from}Hz -> {to}Hz"),
+            Self::SetRotation { from, to } => write!(f, "rotation {from} -> {to}"),
+            Self::SetScaling { from, to } => write!(f, "scaling {from}% -> {to}%"),
+        }
+    }
+}
+
+/// The operations for one display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayChange {
+    /// The display as it was planned from; [`execute`] fails with [`DisplayError::Stale`] if it
+    /// no longer matches.
+    pub display: DisplayInfo,
+    pub operations: Vec<Operation>,
+}
+
+impl DisplayChange {
+    /// The display as it looks once the operations went through as planned.
+    #[must_use]
+    pub fn after(&self) -> DisplayInfo {
+        let mut display = self.display.clone();
+        for operation in &self.operations {
+            match *operation {
+                Operation::SetMode { to, .. } => (display.width, display.height) = to,
+                Operation::SetRefresh { to, .. } => display.refresh_rate = to,
+                Operation::SetRotation { to, .. } => display.rotation = to,
+                Operation::SetScaling { to, .. } => display.scaling_current = to,
+            }
+        }
+        display
+    }
+
+    /// The scaling it sets, if any.
+    #[must_use]
+    pub fn scaling(&self) -> Option<i32> {
+        self.operations.iter().find_map(|op| match *op {
+            Operation::SetScaling { to, .. } => Some(to),
+            _ => None,
+        })
+    }
+
+    /// Whether the scaling is set before the mode operations under `order`, `None` for
+    /// [`ApplyOrder::least_transient`]. `false` without a scaling change.
+    #[must_use]
+    pub fn scaling_first(&self, order: Option<ApplyOrder>) -> bool {
+        let Some((from, to)) = self.operations.iter().find_map(|op| match *op {
+            Operation::SetScaling { from, to } => Some((from, to)),
+            _ => None,
+        }) else {
+            return false;
+        };
+        order.unwrap_or_else(|| ApplyOrder::least_transient(from, to)) == ApplyOrder::ScalingFirst
+    }
+
+    /// The target that leads to the state after the operations.
+    #[must_use]
+    pub fn target(&self) -> Target {
+        let mut target = Target { source_id: self.display.source_id, ..Target::default() };
+        for operation in &self.operations {
+            match *operation {
+                Operation::SetMode { to, .. } => (target.width, target.height) = (Some(to.0), Some(to.1)),
+                Operation::SetRefresh { to, .. } => target.refresh = Some(to),
+                Operation::SetRotation { to, .. } => target.rotation = Some(to),
+                Operation::SetScaling { to, .. } => target.scaling = Some(to),
+            }
+        }
+        target
+    }
+}
+
+impl fmt::Display for DisplayChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Display {}: ", self.display.source_id)?;
+        for (i, operation) in self.operations.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{operation} ({})", operation.impact())?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything [`plan`] decided to change; displays already matching their target aren't in it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    pub changes: Vec<DisplayChange>,
+}
+
+impl ChangeSet {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The change set that puts the displays back, planned from the state this one leads to.
+    #[must_use]
+    pub fn inverse(&self) -> Self {
+        Self {
+            changes: self
+                .changes
+                .iter()
+                .map(|change| DisplayChange {
+                    display: change.after(),
+                    operations: change.operations.iter().map(|op| op.inverse()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The targets the changes lead to. Planning them against freshly enumerated displays
+    /// gives a change set that also works when the OS adjusted a change, which
+    /// [`inverse`](Self::inverse) alone can't know about.
+    #[must_use]
+    pub fn targets(&self) -> Vec<Target> {
+        self.changes.iter().map(DisplayChange::target).collect()
+    }
+
+    /// Splits the set into the seamless operations and those that need a mode switch, see
+    /// [`Operation::impact`]; a display is left out of a part it has no operations in.
+    #[must_use]
+    pub fn split_by_impact(&self) -> (Self, Self) {
+        let part = |impact: Impact| Self {
+            changes: self
+                .changes
+                .iter()
+                .map(|change| DisplayChange {
+                    display: change.display.clone(),
+                    operations: change.operations.iter().copied().filter(|op| op.impact() == impact).collect(),
+                })
+                .filter(|change| !change.operations.is_empty())
+                .collect(),
+        };
+        (part(Impact::Seamless), part(Impact::ModeSwitch))
+    }
+}
+
+impl fmt::Display for ChangeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("No changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Works out the operations that take `displays` to `targets`, without touching the OS.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::DisplayNotFound`] for a target without a display,
+/// [`DisplayError::TargetUnavailable`] if a display that needs changes is asleep,
+/// [`DisplayError::CustomScalingActive`] for a scaling change under a custom scaling override,
+/// or [`DisplayError::UnsupportedScaling`] for a value outside [`DPI_VALUES`].
+pub fn plan(displays: &[DisplayInfo], targets: &[Target]) -> Result<ChangeSet> {
+    let mut changes = Vec::new();
+    for target in targets {
+        let display = displays
+            .iter()
+            .find(|d| d.source_id == target.source_id)
+            .ok_or(DisplayError::DisplayNotFound(target.source_id))?;
+        let operations = operations(display, target)?;
+        if operations.is_empty() {
+            let source_id = display.source_id;
+            debug!(source_id, "Display already matches its target");
+        } else {
+            changes.push(DisplayChange { display: display.clone(), operations });
+        }
+    }
+    Ok(ChangeSet { changes })
+}
+
+fn operations(display: &DisplayInfo, target: &Target) -> Result<Vec<Operation>> {
+    let rotation = target.rotation.unwrap_or(display.rotation);
+    let turned = if rotation.is_portrait() == display.rotation.is_portrait() {
+        (display.width, display.height)
+    } else {
+        (display.height, display.width)
+    };
+    let size = (target.width.unwrap_or(turned.0), target.height.unwrap_or(turned.1));
+
+    let mut operations = Vec::new();
+    if rotation != display.rotation {
+        operations.push(Operation::SetRotation { from: display.rotation, to: rotation });
+    }
+    if size != (display.width, display.height) {
+        operations.push(Operation::SetMode { from: (display.width, display.height), to: size });
+    }
+    if let Some(hz) = target.refresh.filter(|&hz| hz != display.refresh_rate) {
+        operations.push(Operation::SetRefresh { from: display.refresh_rate, to: hz });
+    }
+    if let Some(scaling) = target.scaling.filter(|&s| s != display.scaling_current) {
+        if display.custom_scaling {
+            return Err(DisplayError::CustomScalingActive(display.source_id));
+        }
+        if !DPI_VALUES.contains(&scaling) {
+            return Err(DisplayError::UnsupportedScaling { source_id: display.source_id, scaling });
+        }
+        operations.push(Operation::SetScaling { from: display.scaling_current, to: scaling });
+    }
+    if !operations.is_empty() && !display.target_available {
+        return Err(DisplayError::TargetUnavailable(display.source_id));
+    }
+    Ok(operations)
+}
+
+/// What [`execute`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// Source ids of the displays changed, in order.
+    pub applied: Vec<u32>,
+    /// The exact modes were refused and the OS was allowed to adjust them, see
+    /// [`ApplyPolicy::AllowChanges`].
+    pub allowed_changes: bool,
+    /// Time spent on the modes and on scaling; `enumerate` and `verification` stay zero.
+    pub timings: ApplyTimings,
+}
+
+/// Carries out `changes` under [`ApplyPolicy::Strict`], see [`execute_with`].
+///
+/// # Errors
+///
+/// As for [`execute_with`].
+pub fn execute(changes: &ChangeSet) -> Result<ExecutionReport> {
+    execute_with(changes, ApplyPolicy::Strict)
+}
+
+/// Carries out `changes` on Windows under [`apply_order`](display::apply_order), see
+/// [`execute_on`].
+///
+/// # Errors
+///
+/// As for [`execute_on`].
+pub fn execute_with(changes: &ChangeSet, policy: ApplyPolicy) -> Result<ExecutionReport> {
+    execute_on(&mut Windows, changes, policy, display::apply_order())
+}
+
+/// The calls [`execute_on`] carries out a change set with, so it can run against something
+/// other than the live displays.
+pub trait Backend {
+    /// Fails with [`DisplayError::Stale`] unless `display` still describes the live
+    /// configuration, and returns the fingerprint of that configuration.
+    ///
+    /// # Errors
+    ///
+    /// As described, or if the configuration can't be queried.
+    fn check_fresh(&mut self, display: &DisplayInfo) -> Result<u64>;
+
+    /// Carries out the mode operations of `changes` in one go, provided the configuration is
+    /// still the `baseline` one. Returns whether the OS had to be allowed to adjust them (see
+    /// [`ApplyPolicy::AllowChanges`]) and the fingerprint of the configuration after.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the OS rejects the modes or the configuration changed meanwhile.
+    fn set_modes(&mut self, changes: &[&DisplayChange], baseline: u64, policy: ApplyPolicy) -> Result<(bool, u64)>;
+
+    /// The desktop size of `display` and its recommended scaling, as reported now.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the display or its scaling can't be queried.
+    fn live_scaling(&mut self, display: &DisplayInfo) -> Result<((u32, u32), i32)>;
+
+    /// Sets the scaling of `display` to `steps` from its recommended scaling, provided the
+    /// configuration is still the `baseline` one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the OS rejects the value or the configuration changed meanwhile.
+    fn set_scaling_steps(&mut self, display: &DisplayInfo, steps: i32, baseline: u64) -> Result<()>;
+}
+
+/// The live displays: `SetDisplayConfig` for the modes and the DPI call for scaling.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Windows;
+
+impl Backend for Windows {
+    fn check_fresh(&mut self, display: &DisplayInfo) -> Result<u64> {
+        display::check_fresh(display)
+    }
+
+    fn set_modes(&mut self, changes: &[&DisplayChange], baseline: u64, policy: ApplyPolicy) -> Result<(bool, u64)> {
+        let Some(first) = changes.first() else {
+            return Ok((false, baseline));
+        };
+        let refresh = changes.iter().flat_map(|c| &c.operations).any(|op| matches!(op, Operation::SetRefresh { .. }));
+        let flags = if refresh {
+            SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES
+        } else {
+            SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG
+        };
+        let build = || mode_config(&first.display, changes, baseline);
+        info!(count = changes.len(), "Changing display modes");
+        let mut allowed_changes = false;
+        match display::set_with_retries(build, flags) {
+            Err(DisplayError::SetDisplayConfig(code))
+                if policy == ApplyPolicy::AllowChanges && display::is_mode_error(code) =>
+            {
+                warn!(code, "Exact mode rejected, retrying with SDC_ALLOW_CHANGES");
+                display::set_with_retries(build, flags | SDC_ALLOW_CHANGES)?;
+                allowed_changes = true;
+            }
+            result => result?,
+        }
+        // Our own change is what the scaling step has to find.
+        let (paths, modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+        Ok((allowed_changes, raw::config_fingerprint(&paths, &modes)))
+    }
+
+    fn live_scaling(&mut self, display: &DisplayInfo) -> Result<((u32, u32), i32)> {
+        display::live_scaling(display)
+    }
+
+    fn set_scaling_steps(&mut self, display: &DisplayInfo, steps: i32, baseline: u64) -> Result<()> {
+        display::set_scaling_steps(display, steps, baseline)
+    }
+}
+
+/// How long a scaling step after a mode switch waits for the display to report its new mode.
+pub const SETTLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The recommended scaling of `display` once it reports the desktop `size`: a mode switch
+/// moves the recommended scaling along with the resolution, and the answers lag behind the
+/// switch for a moment.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NotSettled`] if the display doesn't report `size` within
+/// [`SETTLE_TIMEOUT`], or an error if it can't be queried.
+pub fn settled_recommended(backend: &mut impl Backend, display: &DisplayInfo, size: (u32, u32)) -> Result<i32> {
+    let started = Instant::now();
+    loop {
+        let (reported, recommended) = backend.live_scaling(display)?;
+        if reported == size {
+            return Ok(recommended);
+        }
+        if started.elapsed() >= SETTLE_TIMEOUT {
+            return Err(DisplayError::NotSettled(display.source_id));
+        }
+        thread::sleep(settle::POLL_INTERVAL);
+    }
+}
+
+/// Carries out `changes` through `backend`: every resolution, refresh rate and rotation in a
+/// single call, and the scaling of each display before or after it as
+/// [`DisplayChange::scaling_first`] says under `order`. Windows may adjust the signal timing to
+/// fit a new refresh rate, as with [`set_refresh_rate`](crate::display::set_refresh_rate).
+///
+/// The DPI call takes steps from the recommended scaling the display has at that moment. Before
+/// the mode switch, or without a new resolution, that's the one it was planned with; after a
+/// switch to a new resolution it's read back once the display reports the new mode. Counting
+/// from the recommended scaling of the plan instead would be off by as many steps as the switch
+/// moved it, e.g. 1080p at 100% recommended to 4K at 150% lands 175% on 225%. A scaling set
+/// first is set again that way if the switch moved the recommended scaling, as Windows keeps
+/// the steps rather than the value.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::Stale`] if a display no longer looks as planned,
+/// [`DisplayError::ConcurrentChange`] if another program reconfigures the displays meanwhile,
+/// [`DisplayError::NotSettled`] if a display doesn't report its new mode in time, or an error
+/// if the OS rejects a change. Operations carried out before the failure stay applied; roll
+/// them back with the [`inverse`](ChangeSet::inverse) of the set.
+pub fn execute_on(backend: &mut impl Backend, changes: &ChangeSet, policy: ApplyPolicy, order: Option<ApplyOrder>) -> Result<ExecutionReport> {
+    let mut report = ExecutionReport::default();
+    let mut baseline = 0;
+    for change in &changes.changes {
+        throttle::wait_for_apply(change.display.source_id);
+        baseline = backend.check_fresh(&change.display)?;
+    }
+
+    let started = Instant::now();
+    for change in changes.changes.iter().filter(|c| c.scaling_first(order)) {
+        set_scaling(backend, change, change.display.scaling_recommended, baseline)?;
+    }
+    report.timings.scaling = started.elapsed();
+
+    let mode_changes: Vec<&DisplayChange> =
+        changes.changes.iter().filter(|c| c.operations.iter().any(|op| op.is_mode())).collect();
+    if !mode_changes.is_empty() {
+        let started = Instant::now();
+        (report.allowed_changes, baseline) = backend.set_modes(&mode_changes, baseline, policy)?;
+        report.timings.resolution = started.elapsed();
+    }
+
+    let started = Instant::now();
+    for change in &changes.changes {
+        let planned = change.display.scaling_recommended;
+        let resized = change.operations.iter().any(|op| matches!(op, Operation::SetMode { .. }));
+        if change.scaling_first(order) {
+            if resized && change.scaling().is_some() {
+                let after = change.after();
+                let recommended = settled_recommended(backend, &change.display, (after.width, after.height))?;
+                if recommended != planned {
+                    debug!(planned, recommended, "Recommended scaling moved with the mode, setting the scaling again");
+                    set_scaling(backend, change, recommended, baseline)?;
+                }
+            }
+        } else {
+            let recommended = if resized && change.scaling().is_some() {
+                let after = change.after();
+                settled_recommended(backend, &change.display, (after.width, after.height))?
+            } else {
+                planned
+            };
+            set_scaling(backend, change, recommended, baseline)?;
+        }
+    }
+    report.timings.scaling += started.elapsed();
+    report.applied = changes.changes.iter().map(|c| c.display.source_id).collect();
+    Ok(report)
+}
+
+/// Sets the scaling `change` asks for, if any, counting the steps from `recommended`.
+fn set_scaling(backend: &mut impl Backend, change: &DisplayChange, recommended: i32, baseline: u64) -> Result<()> {
+    let Some(scaling) = change.scaling() else {
+        return Ok(());
+    };
+    let display = &change.display;
+    let steps = display::scaling_steps(recommended, scaling)
+        .ok_or(DisplayError::UnsupportedScaling { source_id: display.source_id, scaling })?;
+    let (old_scaling, new_scaling) = (display.scaling_current, scaling);
+    info!(old_scaling, new_scaling, recommended, "Changing DPI scaling");
+    backend.set_scaling_steps(display, steps, baseline)
+}
+
+/// Current configuration with the mode operations of `changes` applied, provided it's still the
+/// `baseline` one.
+fn mode_config(first: &DisplayInfo, changes: &[&DisplayChange], baseline: u64) -> Result<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    let (mut paths, mut modes) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    display::check_unchanged(first, &paths, &modes, baseline)?;
+
+    for change in changes {
+        let display = &change.display;
+        let not_found = || DisplayError::DisplayNotFound(display.source_id);
+        let index = paths
+            .iter()
+            .position(|p| p.adapter_id() == display.adapter_id && p.source_id() == display.source_id)
+            .ok_or_else(not_found)?;
+        for operation in &change.operations {
+            match *operation {
+                Operation::SetMode { to: (width, height), .. } => {
+                    let mode = raw::source_mode_mut(&paths[index], &mut modes).ok_or_else(not_found)?;
+                    mode.width = width;
+                    mode.height = height;
+                }
+                Operation::SetRefresh { to, .. } => {
+                    let rate = DISPLAYCONFIG_RATIONAL { Numerator: to, Denominator: 1 };
+                    let mode = raw::target_mode_mut(&paths[index], &mut modes).ok_or_else(not_found)?;
+                    mode.targetVideoSignalInfo.vSyncFreq = rate;
+                    paths[index].targetInfo.refreshRate = rate;
+                }
+                Operation::SetRotation { to, .. } => paths[index].targetInfo.rotation = to.into(),
+                Operation::SetScaling { .. } => {}
+            }
+        }
+        debug!(%change, "Prepared change");
+    }
+
+    Ok((paths, modes))
+}
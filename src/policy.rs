@@ -0,0 +1,220 @@
+//! Expected display settings and the comparison behind `check` and `profile diff`.
+
+use crate::display::{parse_scaling, scaling_percent, DisplayError, DisplayInfo, Result, Rotation};
+use crate::profile::FieldDiff;
+use crate::selector::DisplayFilter;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The scaling a display should run at: a fixed value or whatever Windows recommends for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "ScalingValue")]
+pub enum ScalingExpectation {
+    Recommended,
+    Percent(i32),
+}
+
+/// What `scaling` looks like in a policy file: `"recommended"` or a number, see
+/// [`scaling_percent`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScalingValue {
+    Number(f64),
+    Text(String),
+}
+
+impl TryFrom<ScalingValue> for ScalingExpectation {
+    type Error = DisplayError;
+
+    fn try_from(value: ScalingValue) -> Result<Self> {
+        match value {
+            ScalingValue::Number(number) => scaling_percent(number)
+                .map(Self::Percent)
+                .ok_or_else(|| DisplayError::InvalidExpectation(number.to_string())),
+            ScalingValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl FromStr for ScalingExpectation {
+    type Err = DisplayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("recommended") {
+            return Ok(Self::Recommended);
+        }
+        parse_scaling(s)
+            .map(Self::Percent)
+            .ok_or_else(|| DisplayError::InvalidExpectation(s.to_string()))
+    }
+}
+
+impl ScalingExpectation {
+    /// The scaling `display` should have.
+    #[must_use]
+    pub fn target(self, display: &DisplayInfo) -> i32 {
+        match self {
+            Self::Recommended => display.scaling_recommended,
+            Self::Percent(percent) => percent,
+        }
+    }
+}
+
+impl fmt::Display for ScalingExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recommended => f.write_str("recommended"),
+            Self::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
+
+/// Settings one display is expected to have; unset fields aren't checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expectation {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub scaling: Option<ScalingExpectation>,
+    pub position: Option<(i32, i32)>,
+    pub rotation: Option<Rotation>,
+    pub primary: Option<bool>,
+}
+
+/// The fields of `display` that don't meet `expected`, in a fixed order.
+#[must_use]
+pub fn compare(expected: &Expectation, display: &DisplayInfo) -> Vec<FieldDiff> {
+    let mut fields = Vec::new();
+    let mut compare = |field, expected: String, actual: String| {
+        if expected != actual {
+            fields.push(FieldDiff { field, expected, actual });
+        }
+    };
+    if expected.width.is_some() || expected.height.is_some() {
+        compare(
+            "resolution",
+            format!(
+                "{}x{}",
+                expected.width.unwrap_or(display.width),
+                expected.height.unwrap_or(display.height)
+            ),
+            format!("{}x{}", display.width, display.height),
+        );
+    }
+    if let Some(scaling) = expected.scaling {
+        let target = scaling.target(display);
+        let actual = format!("{}%", display.scaling_current);
+        let expected = match scaling {
+            ScalingExpectation::Recommended if target != display.scaling_current => format!("recommended {target}%"),
+            _ => format!("{target}%"),
+        };
+        compare("scaling", expected, actual);
+    }
+    if let Some((x, y)) = expected.position {
+        compare("position", format!("({x}, {y})"), format!("({}, {})", display.position_x, display.position_y));
+    }
+    if let Some(rotation) = expected.rotation {
+        compare("rotation", rotation.to_string(), display.rotation.to_string());
+    }
+    if let Some(primary) = expected.primary {
+        let yes_no = |value: bool| if value { "yes" } else { "no" }.to_string();
+        compare("primary", yes_no(primary), yes_no(display.primary));
+    }
+    fields
+}
+
+/// Settings the displays must have, loaded from a JSON file:
+///
+/// ```json
+/// {
+///   "scaling": "recommended",
+///   "displays": [{ "match": { "vendor": "DEL", "product": 16809 }, "width": 3840, "height": 2160 }],
+///   "primary": { "connector": "internal" }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Scaling for displays whose entry doesn't set one.
+    #[serde(default)]
+    pub scaling: Option<ScalingExpectation>,
+    /// Per-display expectations, the first matching entry applies.
+    #[serde(default)]
+    pub displays: Vec<PolicyEntry>,
+    /// The display that must be primary.
+    #[serde(default)]
+    pub primary: Option<DisplayFilter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyEntry {
+    #[serde(default, rename = "match")]
+    pub criteria: DisplayFilter,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub scaling: Option<ScalingExpectation>,
+}
+
+/// How one display fared in [`check_policy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub source_id: u32,
+    pub friendly_name: String,
+    /// Empty if the display passes.
+    pub failures: Vec<FieldDiff>,
+}
+
+impl CheckResult {
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Policy {
+    /// # Errors
+    ///
+    /// Returns an error if `text` isn't a valid policy.
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or isn't a valid policy.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// What the policy expects of `display`.
+    #[must_use]
+    pub fn expectation(&self, display: &DisplayInfo) -> Expectation {
+        let entry = self.displays.iter().find(|e| e.criteria.matches(display));
+        Expectation {
+            width: entry.and_then(|e| e.width),
+            height: entry.and_then(|e| e.height),
+            scaling: entry.and_then(|e| e.scaling).or(self.scaling),
+            primary: self.primary.as_ref().map(|filter| filter.matches(display)),
+            ..Expectation::default()
+        }
+    }
+}
+
+/// Checks every display against `policy`, in the order of `displays`.
+#[must_use]
+pub fn check_policy(policy: &Policy, displays: &[DisplayInfo]) -> Vec<CheckResult> {
+    displays
+        .iter()
+        .map(|display| CheckResult {
+            source_id: display.source_id,
+            friendly_name: display.friendly_name.clone(),
+            failures: compare(&policy.expectation(display), display),
+        })
+        .collect()
+}
@@ -0,0 +1,671 @@
+use crate::display::{app_dir, apply_display_config, apply_source_modes, enumerate_displays, ConfigSnapshot, Connector, DisplayConfig, DisplayError, DisplayInfo, Result, Rotation, SourceMode};
+use crate::policy::{compare, Expectation, ScalingExpectation};
+use crate::progress::{self, DisplayProgress, PhaseStatus};
+use crate::selector::DisplayFilter;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{info, warn};
+
+const STATE_FILE: &str = "state.json";
+
+/// How a display is connected: the same monitor may want other settings on a dock than plugged
+/// into the laptop directly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionContext {
+    pub connector: Connector,
+    /// Case-insensitive substring of [`DisplayInfo::adapter_name`]; empty matches any adapter.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub adapter: String,
+}
+
+impl ConnectionContext {
+    #[must_use]
+    pub fn matches(&self, display: &DisplayInfo) -> bool {
+        self.connector == display.connector
+            && display.adapter_name.to_lowercase().contains(&self.adapter.to_lowercase())
+    }
+}
+
+/// Which display a profile entry stands for by its place rather than its identity, so the entry
+/// still applies after a monitor is replaced with another unit. Written `"internal"`,
+/// `"external[0]"` or `{ "any": { "min_width": 3840 } }` in a profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SlotSpec", into = "SlotSpec")]
+pub enum Slot {
+    /// The built-in panel, see [`DisplayInfo::internal`].
+    Internal,
+    /// The external display at this position in slot order, from 0.
+    External(usize),
+    /// Any display the filter matches.
+    Any(DisplayFilter),
+}
+
+impl Slot {
+    /// Whether the slot names a position (`internal`, `external[N]`) rather than describing the
+    /// display; those are resolved first, see [`match_displays`].
+    #[must_use]
+    pub fn is_positional(&self) -> bool {
+        !matches!(self, Self::Any(_))
+    }
+
+    /// Indices into `displays` of the displays the slot stands for, the one to take first
+    /// first. Slot order puts active displays first, then goes left to right and top to
+    /// bottom by position, then by source id and device path, so it doesn't depend on the
+    /// enumeration order. `external[N]` stands for at most one display.
+    #[must_use]
+    pub fn candidates(&self, displays: &[DisplayInfo]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..displays.len()).collect();
+        order.sort_by(|&a, &b| {
+            let key = |d: &DisplayInfo| (!d.active, d.position_x, d.position_y, d.source_id);
+            key(&displays[a]).cmp(&key(&displays[b])).then_with(|| displays[a].device_path.cmp(&displays[b].device_path))
+        });
+        match self {
+            Self::Internal => order.into_iter().filter(|&i| displays[i].internal).collect(),
+            Self::External(n) => order.into_iter().filter(|&i| !displays[i].internal).nth(*n).into_iter().collect(),
+            Self::Any(filter) => order.into_iter().filter(|&i| filter.matches(&displays[i])).collect(),
+        }
+    }
+}
+
+/// `internal`, `external[0]` or `any {min_width=3840}`.
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Internal => f.write_str("internal"),
+            Self::External(n) => write!(f, "external[{n}]"),
+            Self::Any(filter) => write!(f, "any {{{filter}}}"),
+        }
+    }
+}
+
+/// How a [`Slot`] is written in a profile.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SlotSpec {
+    Name(String),
+    Any { any: DisplayFilter },
+}
+
+impl TryFrom<SlotSpec> for Slot {
+    type Error = String;
+
+    fn try_from(spec: SlotSpec) -> std::result::Result<Self, String> {
+        let name = match spec {
+            SlotSpec::Any { any } => return Ok(Self::Any(any)),
+            SlotSpec::Name(name) => name,
+        };
+        if name.eq_ignore_ascii_case("internal") {
+            return Ok(Self::Internal);
+        }
+        name.to_ascii_lowercase()
+            .strip_prefix("external[")
+            .and_then(|rest| rest.strip_suffix(']')?.trim().parse().ok())
+            .map(Self::External)
+            .ok_or_else(|| format!("unknown slot `{name}`, expected internal, external[N] or {{\"any\": {{...}}}}"))
+    }
+}
+
+impl From<Slot> for SlotSpec {
+    fn from(slot: Slot) -> Self {
+        match slot {
+            Slot::Any(any) => Self::Any { any },
+            slot => Self::Name(slot.to_string()),
+        }
+    }
+}
+
+/// Saved settings of one display.
+///
+/// Entries are matched back to connected displays by device path first and by EDID
+/// manufacturer/product ids second, so a profile survives moving a monitor to another port.
+/// An entry with a [`connection`](Self::connection) only applies while the display is connected
+/// that way, and wins over a generic entry for the same display. An entry with a
+/// [`slot`](Self::slot) goes by its device path if it has one and by its slot otherwise, see
+/// [`match_displays`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileEntry {
+    /// Empty in entries that only go by their [`slot`](Self::slot).
+    #[serde(default)]
+    pub device_path: String,
+    #[serde(default)]
+    pub edid_manufacturer_id: u16,
+    #[serde(default)]
+    pub edid_product_id: u16,
+    #[serde(default)]
+    pub friendly_name: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(deserialize_with = "crate::display::deserialize_scaling")]
+    pub scaling: i32,
+    pub position_x: i32,
+    pub position_y: i32,
+    pub rotation: Rotation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<ConnectionContext>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<Slot>,
+}
+
+impl ProfileEntry {
+    #[must_use]
+    pub fn capture(display: &DisplayInfo) -> Self {
+        Self {
+            device_path: display.device_path.clone(),
+            edid_manufacturer_id: display.edid_manufacturer_id,
+            edid_product_id: display.edid_product_id,
+            friendly_name: display.friendly_name.clone(),
+            width: display.width,
+            height: display.height,
+            scaling: display.scaling_current,
+            position_x: display.position_x,
+            position_y: display.position_y,
+            rotation: display.rotation,
+            connection: None,
+            slot: None,
+        }
+    }
+
+    /// The monitor's name, or the slot of entries without one, for messages.
+    #[must_use]
+    pub fn label(&self) -> String {
+        match &self.slot {
+            Some(slot) if self.friendly_name.is_empty() => format!("slot {slot}"),
+            _ => self.friendly_name.clone(),
+        }
+    }
+
+    /// The settings this entry expects of its display.
+    #[must_use]
+    pub fn expectation(&self) -> Expectation {
+        Expectation {
+            width: Some(self.width),
+            height: Some(self.height),
+            scaling: Some(ScalingExpectation::Percent(self.scaling)),
+            position: Some((self.position_x, self.position_y)),
+            rotation: Some(self.rotation),
+            primary: None,
+        }
+    }
+
+    fn same_path(&self, display: &DisplayInfo) -> bool {
+        !self.device_path.is_empty() && self.device_path == display.device_path
+    }
+
+    fn same_model(&self, display: &DisplayInfo) -> bool {
+        self.edid_manufacturer_id == display.edid_manufacturer_id
+            && self.edid_product_id == display.edid_product_id
+    }
+
+    /// Whether the entry is for displays connected like `display`: with a matching
+    /// [`connection`](Self::connection) if `specific`, without one otherwise.
+    fn fits(&self, display: &DisplayInfo, specific: bool) -> bool {
+        match &self.connection {
+            Some(connection) => specific && connection.matches(display),
+            None => !specific,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub displays: Vec<ProfileEntry>,
+}
+
+impl Profile {
+    #[must_use]
+    pub fn capture(displays: &[DisplayInfo]) -> Self {
+        Self {
+            displays: displays.iter().map(ProfileEntry::capture).collect(),
+        }
+    }
+
+    /// Directory holding the saved profiles, `%APPDATA%\display-tuner\profiles`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::NoAppData`] if `APPDATA` isn't set.
+    pub fn dir() -> Result<PathBuf> {
+        Ok(app_dir()?.join("profiles"))
+    }
+
+    /// Replaces the generic entries of `displays` (by device path) with their current settings
+    /// and keeps the entries of displays not connected now and those for a
+    /// [`connection`](ProfileEntry::connection).
+    pub fn merge(&mut self, displays: &[DisplayInfo]) {
+        for display in displays {
+            self.displays.retain(|e| e.connection.is_some() || e.device_path != display.device_path);
+            self.displays.push(ProfileEntry::capture(display));
+        }
+    }
+
+    /// The last successfully applied settings of every display seen so far, kept in
+    /// `%APPDATA%\display-tuner\state.json`; empty if nothing was recorded yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state file exists but can't be read or parsed.
+    pub fn load_state() -> Result<Self> {
+        let path = app_dir()?.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// The entries whose device path belongs to one of `displays`, so applying the result leaves
+    /// displays without a record of their own alone.
+    #[must_use]
+    pub fn recorded_for(&self, displays: &[DisplayInfo]) -> Self {
+        Self {
+            displays: self
+                .displays
+                .iter()
+                .filter(|e| displays.iter().any(|d| d.device_path == e.device_path))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Records the current settings of `displays` in the state file, see [`Profile::merge`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state file can't be read or written.
+    pub fn record_state(displays: &[DisplayInfo]) -> Result<()> {
+        let mut state = Self::load_state()?;
+        state.merge(displays);
+        let dir = app_dir()?;
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(STATE_FILE), serde_json::to_string_pretty(&state)?)?;
+        Ok(())
+    }
+
+    /// The file of the profile `name` in [`Profile::dir`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::InvalidProfileName`] if `name` is empty or could point outside the
+    /// profile directory: a path separator, `..` or a drive prefix.
+    pub fn path(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\', ':']) || name.contains("..") {
+            return Err(DisplayError::InvalidProfileName(name.to_string()));
+        }
+        Ok(Self::dir()?.join(format!("{name}.json")))
+    }
+
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::ProfileNotFound`] if no profile is saved under `name`,
+    /// [`DisplayError::InvalidProfileName`] for a name that isn't a plain file name, or an error
+    /// if it can't be read or parsed.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::path(name)?;
+        if !path.exists() {
+            return Err(DisplayError::ProfileNotFound(name.to_string()));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::InvalidProfileName`] for a name that isn't a plain file name, or an
+    /// error if the profile directory or file can't be written.
+    pub fn save(&self, name: &str) -> Result<()> {
+        let path = Self::path(name)?;
+        fs::create_dir_all(Self::dir()?)?;
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Names of the saved profiles, sorted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the profile directory exists but can't be read.
+    pub fn list() -> Result<Vec<String>> {
+        let dir = Self::dir()?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut names: Vec<String> = fs::read_dir(dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "json").then(|| path.file_stem()?.to_str().map(String::from))?
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// Profile entries paired with connected displays.
+#[derive(Debug)]
+pub struct ProfileMatch<'a> {
+    pub pairs: Vec<(&'a ProfileEntry, &'a DisplayInfo)>,
+    /// In the profile but not connected.
+    pub missing: Vec<&'a ProfileEntry>,
+    /// Connected but not in the profile.
+    pub unexpected: Vec<&'a DisplayInfo>,
+    /// Entries for a display that another entry for the same model was paired with instead,
+    /// one of them [`connection`](ProfileEntry::connection)-specific. Not missing: the display
+    /// is just connected the other way.
+    pub superseded: Vec<&'a ProfileEntry>,
+}
+
+/// Pairs profile entries with displays: exact device path matches first, then same EDID model
+/// among what's left, in order. In each round, entries whose
+/// [`connection`](ProfileEntry::connection) matches go before generic ones; entries for another
+/// connection never match.
+///
+/// Entries with a [`slot`](ProfileEntry::slot) take part in the device path round only and are
+/// then resolved by slot among the displays still untaken: positional slots (`internal`,
+/// `external[N]`) before `any` slots, connection-specific before generic within each, in
+/// profile order. Each entry takes the first untaken of its slot's
+/// [candidates](Slot::candidates). When two entries resolve to the same display the one that
+/// comes first this way gets it; for the other, an `any` slot moves on to the next display it
+/// matches, while a positional slot stays unpaired, as it names that one display.
+#[must_use]
+pub fn match_displays<'a>(profile: &'a Profile, displays: &'a [DisplayInfo]) -> ProfileMatch<'a> {
+    let mut paired: Vec<Option<&DisplayInfo>> = vec![None; profile.displays.len()];
+    let mut taken = vec![false; displays.len()];
+
+    for specific in [true, false] {
+        claim(profile, displays, &mut paired, &mut taken, |e, d| e.fits(d, specific) && e.same_path(d));
+    }
+    for specific in [true, false] {
+        claim(profile, displays, &mut paired, &mut taken, |e, d| e.slot.is_none() && e.fits(d, specific) && e.same_model(d));
+    }
+    for positional in [true, false] {
+        for specific in [true, false] {
+            claim_slots(profile, displays, &mut paired, &mut taken, |slot, e, d| {
+                slot.is_positional() == positional && e.fits(d, specific)
+            });
+        }
+    }
+
+    let mut result = ProfileMatch {
+        pairs: Vec::new(),
+        missing: Vec::new(),
+        unexpected: Vec::new(),
+        superseded: Vec::new(),
+    };
+    for (entry, display) in profile.displays.iter().zip(&paired) {
+        match display {
+            Some(display) => result.pairs.push((entry, display)),
+            None => result.missing.push(entry),
+        }
+    }
+    let (superseded, missing) = result.missing.into_iter().partition(|entry| {
+        result.pairs.iter().any(|(paired, display)| {
+            (entry.connection.is_some() || paired.connection.is_some()) && entry.same_model(display)
+        })
+    });
+    (result.superseded, result.missing) = (superseded, missing);
+    result.unexpected = displays
+        .iter()
+        .zip(taken)
+        .filter_map(|(d, taken)| (!taken).then_some(d))
+        .collect();
+    result
+}
+
+/// Gives every unpaired entry the first untaken display accepted by `matches`.
+fn claim<'a>(
+    profile: &Profile,
+    displays: &'a [DisplayInfo],
+    paired: &mut [Option<&'a DisplayInfo>],
+    taken: &mut [bool],
+    matches: impl Fn(&ProfileEntry, &DisplayInfo) -> bool,
+) {
+    for (entry, display) in profile.displays.iter().zip(paired.iter_mut()) {
+        if display.is_some() {
+            continue;
+        }
+        if let Some(i) = (0..displays.len()).find(|&i| !taken[i] && matches(entry, &displays[i])) {
+            taken[i] = true;
+            *display = Some(&displays[i]);
+        }
+    }
+}
+
+/// Gives every unpaired entry with a slot accepted by `matches` the first untaken candidate of
+/// its slot that `matches` accepts.
+fn claim_slots<'a>(
+    profile: &Profile,
+    displays: &'a [DisplayInfo],
+    paired: &mut [Option<&'a DisplayInfo>],
+    taken: &mut [bool],
+    matches: impl Fn(&Slot, &ProfileEntry, &DisplayInfo) -> bool,
+) {
+    for (entry, display) in profile.displays.iter().zip(paired.iter_mut()) {
+        let Some(slot) = entry.slot.as_ref().filter(|_| display.is_none()) else {
+            continue;
+        };
+        if let Some(i) = slot.candidates(displays).into_iter().find(|&i| !taken[i] && matches(slot, entry, &displays[i])) {
+            taken[i] = true;
+            *display = Some(&displays[i]);
+        }
+    }
+}
+
+/// A setting that differs from what was expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayDiff {
+    pub source_id: u32,
+    pub friendly_name: String,
+    pub fields: Vec<FieldDiff>,
+}
+
+/// Differences between a profile and the connected displays.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileDiff {
+    /// Matched displays with at least one differing field.
+    pub changed: Vec<DisplayDiff>,
+    pub missing: Vec<ProfileEntry>,
+    pub unexpected: Vec<DisplayInfo>,
+}
+
+impl ProfileDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// Compares `profile` against the connected `displays` field by field.
+#[must_use]
+pub fn diff_profile(profile: &Profile, displays: &[DisplayInfo]) -> ProfileDiff {
+    let matched = match_displays(profile, displays);
+
+    let changed = matched
+        .pairs
+        .iter()
+        .filter_map(|(entry, display)| {
+            let fields = compare(&entry.expectation(), display);
+            (!fields.is_empty()).then(|| DisplayDiff {
+                source_id: display.source_id,
+                friendly_name: display.friendly_name.clone(),
+                fields,
+            })
+        })
+        .collect();
+
+    ProfileDiff {
+        changed,
+        missing: matched.missing.into_iter().cloned().collect(),
+        unexpected: matched.unexpected.into_iter().cloned().collect(),
+    }
+}
+
+/// Applies the resolution, position, rotation and scaling saved in `profile` to the matching
+/// displays.
+///
+/// Everything but scaling goes through a single `SetDisplayConfig` call; displays not in the
+/// profile keep their settings.
+///
+/// # Errors
+///
+/// Returns an error if the configuration or a DPI value can't be applied.
+pub fn apply_profile(profile: &Profile, displays: &[DisplayInfo]) -> Result<()> {
+    apply_profile_with_progress(profile, displays, &mut |_| {})
+}
+
+/// [`apply_profile`], telling `on_progress` about each display as a phase starts and finishes;
+/// scaling is set one display after another, which takes seconds with many displays.
+///
+/// Before each step it checks whether the apply was [cancelled](progress::cancel) since it
+/// started. If so, it puts back the scaling it set and the configuration from before, and fails.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::Cancelled`] if the apply was cancelled, or an error if the
+/// configuration or a DPI value can't be applied.
+pub fn apply_profile_with_progress(profile: &Profile, displays: &[DisplayInfo], on_progress: &mut dyn FnMut(&DisplayProgress)) -> Result<()> {
+    progress::clear_cancel();
+    let matched = match_displays(profile, displays);
+    for entry in &matched.missing {
+        warn!(name = entry.label(), "Profile display not connected, skipping");
+    }
+
+    let sources: Vec<SourceMode> = matched
+        .pairs
+        .iter()
+        .map(|(entry, display)| SourceMode {
+            source_id: display.source_id,
+            width: entry.width,
+            height: entry.height,
+            x: entry.position_x,
+            y: entry.position_y,
+            rotation: entry.rotation,
+        })
+        .collect();
+    let resized: Vec<bool> = matched
+        .pairs
+        .iter()
+        .zip(&sources)
+        .map(|((_, d), s)| SourceMode::of(d) != *s)
+        .collect();
+    let mut run = ProgressRun::new(&matched.pairs, on_progress);
+    let snapshot = ConfigSnapshot::take()?;
+    if resized.contains(&true) {
+        for (index, _) in resized.iter().enumerate().filter(|(_, resized)| **resized) {
+            run.update(index, |p| p.resolution = PhaseStatus::Running);
+        }
+        if let Err(err) = progress::check_cancelled().and_then(|()| apply_source_modes(&sources)) {
+            let status = if matches!(err, DisplayError::Cancelled) { PhaseStatus::Skipped } else { PhaseStatus::Failed };
+            run.finish_all(status);
+            return Err(err);
+        }
+    }
+    for (index, resized) in resized.iter().enumerate() {
+        let status = if *resized { PhaseStatus::Ok } else { PhaseStatus::Unchanged };
+        run.update(index, |p| p.resolution = status);
+    }
+
+    let current = enumerate_displays()?;
+    let mut scaled = Vec::new();
+    for (index, (entry, matched_display)) in matched.pairs.iter().enumerate() {
+        let Some(now) = current.iter().find(|d| d.source_id == matched_display.source_id) else {
+            run.update(index, |p| p.scaling = PhaseStatus::Unchanged);
+            continue;
+        };
+        if progress::is_cancelled() {
+            run.roll_back(&scaled, resized.contains(&true).then_some(&snapshot));
+            return Err(DisplayError::Cancelled);
+        }
+        run.update(index, |p| p.scaling = PhaseStatus::Running);
+        let config = DisplayConfig::builder().scaling(entry.scaling).resolve(now);
+        if let Err(err) = apply_display_config(now, &config) {
+            run.finish_all(PhaseStatus::Failed);
+            return Err(err);
+        }
+        let status = if config.scaling == now.scaling_current { PhaseStatus::Unchanged } else { PhaseStatus::Ok };
+        run.update(index, |p| p.scaling = status);
+        if status == PhaseStatus::Ok {
+            scaled.push((index, now.source_id, now.scaling_current));
+        }
+    }
+
+    info!("Profile applied");
+    Ok(())
+}
+
+/// The progress of the displays of an [`apply_profile_with_progress`] call.
+struct ProgressRun<'a> {
+    progress: Vec<DisplayProgress>,
+    started: Instant,
+    on_progress: &'a mut dyn FnMut(&DisplayProgress),
+}
+
+impl<'a> ProgressRun<'a> {
+    fn new(pairs: &[(&ProfileEntry, &DisplayInfo)], on_progress: &'a mut dyn FnMut(&DisplayProgress)) -> Self {
+        let total = pairs.len();
+        let progress = pairs.iter().enumerate().map(|(i, (_, display))| DisplayProgress::new(i + 1, total, display)).collect();
+        Self { progress, started: Instant::now(), on_progress }
+    }
+
+    fn update(&mut self, index: usize, change: impl FnOnce(&mut DisplayProgress)) {
+        let progress = &mut self.progress[index];
+        change(progress);
+        progress.elapsed = self.started.elapsed();
+        (self.on_progress)(progress);
+    }
+
+    /// Ends the phases still running with `status` and skips those that didn't start.
+    fn finish_all(&mut self, status: PhaseStatus) {
+        for index in 0..self.progress.len() {
+            if !self.progress[index].is_finished() {
+                self.update(index, |p| {
+                    for phase in [&mut p.resolution, &mut p.scaling] {
+                        match phase {
+                            PhaseStatus::Running => *phase = status,
+                            PhaseStatus::Pending => *phase = PhaseStatus::Skipped,
+                            _ => {}
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// Puts back the scaling of the displays `scaled` (index, source id and scaling from
+    /// before), then the configuration in `snapshot`. Failures are only logged: the apply is
+    /// cancelled either way.
+    fn roll_back(&mut self, scaled: &[(usize, u32, i32)], snapshot: Option<&ConfigSnapshot>) {
+        let current = enumerate_displays().unwrap_or_default();
+        for &(index, source_id, scaling) in scaled.iter().rev() {
+            let result = current
+                .iter()
+                .find(|d| d.source_id == source_id)
+                .ok_or(DisplayError::DisplayNotFound(source_id))
+                .and_then(|now| apply_display_config(now, &DisplayConfig::builder().scaling(scaling).resolve(now)));
+            match result {
+                Ok(()) => self.update(index, |p| p.scaling = PhaseStatus::RolledBack),
+                Err(err) => warn!(source_id, %err, "Scaling not rolled back"),
+            }
+        }
+        if let Some(snapshot) = snapshot {
+            match snapshot.restore() {
+                Ok(()) => {
+                    for index in 0..self.progress.len() {
+                        if self.progress[index].resolution == PhaseStatus::Ok {
+                            self.update(index, |p| p.resolution = PhaseStatus::RolledBack);
+                        }
+                    }
+                }
+                Err(err) => warn!(%err, "Configuration not rolled back"),
+            }
+        }
+        self.finish_all(PhaseStatus::Skipped);
+    }
+}
@@ -0,0 +1,163 @@
+//! Progress of applies that change several displays one after another, e.g.
+//! [`apply_profile_with_progress`](crate::profile::apply_profile_with_progress): a
+//! [`DisplayProgress`] per display as each phase starts and finishes, and cancelling between
+//! steps.
+//!
+//! Cancelling is process-wide: [`cancel`] (or Ctrl+C while a [`CtrlCGuard`] is held) makes the
+//! next step check fail with [`DisplayError::Cancelled`] after putting back what the apply
+//! changed so far.
+
+use crate::display::{DisplayError, DisplayInfo, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use windows::Win32::Foundation::{FALSE, TRUE};
+use windows::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT};
+use windows::core::BOOL;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Where one phase of a display's change stands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseStatus {
+    #[default]
+    Pending,
+    Running,
+    Ok,
+    /// Nothing to change.
+    Unchanged,
+    Failed,
+    /// Applied, then put back because the apply was cancelled.
+    RolledBack,
+    /// Not started because the apply stopped early, cancelled or after a failure.
+    Skipped,
+}
+
+impl PhaseStatus {
+    /// Whether the phase is over, however it ended.
+    #[must_use]
+    pub fn is_finished(self) -> bool {
+        !matches!(self, Self::Pending | Self::Running)
+    }
+}
+
+impl fmt::Display for PhaseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pending => "pending",
+            Self::Running => "...",
+            Self::Ok => "ok",
+            Self::Unchanged => "unchanged",
+            Self::Failed => "failed",
+            Self::RolledBack => "rolled back",
+            Self::Skipped => "skipped",
+        })
+    }
+}
+
+/// How far the change of one display got.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayProgress {
+    /// Position among the displays changed, from 1.
+    pub index: usize,
+    pub total: usize,
+    pub source_id: u32,
+    pub name: String,
+    /// Resolution, position and rotation.
+    pub resolution: PhaseStatus,
+    pub scaling: PhaseStatus,
+    /// Since the display's first phase started.
+    #[serde(rename = "elapsed_ms", with = "crate::display::millis")]
+    pub elapsed: Duration,
+}
+
+impl DisplayProgress {
+    /// The `index`th of `total` displays, nothing started yet.
+    #[must_use]
+    pub fn new(index: usize, total: usize, display: &DisplayInfo) -> Self {
+        let name = if display.friendly_name.is_empty() {
+            format!("Display {}", display.source_id)
+        } else {
+            display.friendly_name.clone()
+        };
+        Self { index, total, source_id: display.source_id, name, ..Self::default() }
+    }
+
+    /// Whether every phase of the display is over.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.resolution.is_finished() && self.scaling.is_finished()
+    }
+}
+
+/// `[2/6] DELL U2720: resolution ok, scaling ok (1.2s)`.
+impl fmt::Display for DisplayProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}/{}] {}: resolution {}", self.index, self.total, self.name, self.resolution)?;
+        if self.resolution.is_finished() {
+            write!(f, ", scaling {}", self.scaling)?;
+        }
+        write!(f, " ({}.{}s)", self.elapsed.as_secs(), self.elapsed.subsec_millis() / 100)
+    }
+}
+
+/// Asks the apply under way to stop at its next step and put back what it changed. Applies
+/// started afterwards aren't affected.
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::Relaxed);
+}
+
+/// Forgets an earlier [`cancel`]; every apply calls it as it starts.
+pub(crate) fn clear_cancel() {
+    CANCELLED.store(false, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Fails with [`DisplayError::Cancelled`] once [`cancel`] was called, for the step checks.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::Cancelled`] if the apply was cancelled.
+pub fn check_cancelled() -> Result<()> {
+    if is_cancelled() { Err(DisplayError::Cancelled) } else { Ok(()) }
+}
+
+/// Ctrl+C and Ctrl+Break [`cancel`] instead of ending the process while held; see
+/// [`cancel_on_ctrl_c`].
+#[derive(Debug)]
+pub struct CtrlCGuard(());
+
+impl Drop for CtrlCGuard {
+    fn drop(&mut self) {
+        // SAFETY: removes the handler `cancel_on_ctrl_c` added.
+        let _ = unsafe { SetConsoleCtrlHandler(Some(on_ctrl), false) };
+    }
+}
+
+/// Makes Ctrl+C cancel the apply until the guard is dropped, so an interrupted apply puts the
+/// displays back instead of leaving them half changed. Clears an earlier cancel.
+///
+/// # Errors
+///
+/// Returns an error if the console handler can't be installed.
+pub fn cancel_on_ctrl_c() -> Result<CtrlCGuard> {
+    clear_cancel();
+    // SAFETY: `on_ctrl` stays valid for the life of the process.
+    unsafe { SetConsoleCtrlHandler(Some(on_ctrl), true)? };
+    Ok(CtrlCGuard(()))
+}
+
+unsafe extern "system" fn on_ctrl(ctrl_type: u32) -> BOOL {
+    if ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT {
+        cancel();
+        TRUE
+    } else {
+        FALSE
+    }
+}
@@ -0,0 +1,1527 @@
+//! Thin wrappers over the CCD (Connecting and Configuring Displays) API.
+//!
+//! Use this layer for settings the rest of the crate doesn't cover: query the configuration,
+//! tweak the structures and hand them back to [`set_display_config`]. Everything else in the
+//! crate is built on these functions, and all `unsafe` code talking to the display APIs lives
+//! here.
+//!
+//! # Invariants
+//!
+//! - `DISPLAYCONFIG_PATH_INFO` refers to entries of the mode array by index. Indices are only
+//!   meaningful for the arrays returned by the same [`query_display_config`] call; keep paths
+//!   and modes together, and don't reorder or drop modes you pass back.
+//! - The path's mode index fields are a union. Without `QDC_VIRTUAL_MODE_AWARE` (never passed
+//!   by this crate) the `modeInfoIdx` member is the valid one, which is what
+//!   [`PathInfo::source_mode_index`] and [`PathInfo::target_mode_index`] read. If you query
+//!   with that flag, read the split 16-bit fields from the raw structure yourself.
+//! - `DISPLAYCONFIG_MODE_INFO` is a union tagged by `infoType`; [`ModeInfo`] only hands out the
+//!   member matching the tag.
+//!
+//! # Coordinates
+//!
+//! Desktop coordinates are physical pixels with the primary display's top-left corner at
+//! (0, 0), as the CCD API reports source positions whatever the process's DPI awareness. The
+//! window manager functions (`GetCursorPos`, `MonitorFromPoint`, `GetWindowRect`,
+//! `GetMonitorInfo`, `SetWindowPos`, `GetSystemMetrics`) scale coordinates for processes that
+//! aren't per-monitor aware, so every function here that passes coordinates to or from them
+//! switches the calling thread to per-monitor v2 awareness for the call. The binary makes the
+//! whole process per-monitor v2 aware at startup, see [`set_process_per_monitor_aware`].
+
+use crate::blur::{DpiAwareness, TopLevelWindow};
+use crate::display::{AdapterId, DisplayError, Result};
+use crate::layout::Rect;
+use crate::metrics::{self, Phase};
+use crate::rescue::WindowFrame;
+use std::ffi::c_void;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use tracing::debug;
+use windows::Win32::Devices::Display::{DisplayConfigGetDeviceInfo, DisplayConfigSetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig, SetDisplayConfig, DISPLAYCONFIG_ADAPTER_NAME, DISPLAYCONFIG_DEVICE_INFO_GET_ADAPTER_NAME, DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO, DISPLAYCONFIG_DEVICE_INFO_GET_SDR_WHITE_LEVEL, DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_BASE_TYPE, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_PREFERRED_MODE, DISPLAYCONFIG_DEVICE_INFO_HEADER, DISPLAYCONFIG_DEVICE_INFO_TYPE, DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_DESKTOP_IMAGE_INFO, DISPLAYCONFIG_MODE_INFO_TYPE_DESKTOP_IMAGE, DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE, DISPLAYCONFIG_MODE_INFO_TYPE_TARGET, DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_SDR_WHITE_LEVEL, DISPLAYCONFIG_SOURCE_DEVICE_NAME, DISPLAYCONFIG_SOURCE_MODE, DISPLAYCONFIG_TARGET_BASE_TYPE, DISPLAYCONFIG_TARGET_DEVICE_NAME, DISPLAYCONFIG_TARGET_MODE, DISPLAYCONFIG_TARGET_PREFERRED_MODE, DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY, QUERY_DISPLAY_CONFIG_FLAGS, SET_DISPLAY_CONFIG_FLAGS, DISPLAYCONFIG_VIDEO_SIGNAL_INFO};
+#[cfg(feature = "ddcci")]
+use windows::Win32::Devices::Display::{DestroyPhysicalMonitors, GetCapabilitiesStringLength, GetNumberOfPhysicalMonitorsFromHMONITOR, GetPhysicalMonitorsFromHMONITOR, PHYSICAL_MONITOR};
+use windows::Win32::Graphics::Gdi::{ChangeDisplaySettingsExW, EnumDisplayDevicesW, EnumDisplayMonitors, EnumDisplaySettingsW, GetMonitorInfoW, MonitorFromPoint, MonitorFromWindow, CDS_TYPE, CDS_UPDATEREGISTRY, DEVMODEW, DISPLAY_DEVICEW, DISPLAYCONFIG_PATH_ACTIVE, DISPLAYCONFIG_PATH_SUPPORT_VIRTUAL_MODE, DISP_CHANGE_SUCCESSFUL, DM_DISPLAYFREQUENCY, DM_PELSHEIGHT, DM_PELSWIDTH, DISPLAYCONFIG_PATH_MODE_IDX_INVALID, ENUM_DISPLAY_SETTINGS_MODE, HDC, HMONITOR, MONITORINFOEXW, MONITOR_DEFAULTTONEAREST};
+use windows::Win32::Foundation::{CloseHandle, ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, ERROR_NOT_FOUND, HANDLE, HWND, LPARAM, POINT, RECT, WPARAM};
+use windows::Win32::System::Registry::{RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, REG_DWORD, RRF_RT_REG_BINARY, RRF_RT_REG_DWORD, RRF_RT_REG_SZ};
+use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+use windows::Win32::System::StationsAndDesktops::{GetProcessWindowStation, GetUserObjectInformationW, UOI_FLAGS, UOI_NAME, USEROBJECTFLAGS};
+use windows::Win32::System::Threading::{GetCurrentProcessId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION};
+use windows::Win32::System::Shutdown::{ExitWindowsEx, EWX_LOGOFF, SHTDN_REASON_FLAG_PLANNED, SHTDN_REASON_MAJOR_OTHER};
+use windows::Win32::UI::ColorSystem::{GetColorDirectoryW, WcsGetDefaultColorProfile, WcsGetDefaultColorProfileSize, WcsGetUsePerUserProfiles, WcsSetUsePerUserProfiles, CPST_EXTENDED_DISPLAY_COLOR_MODE, CPST_NONE, CPT_ICC, WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER, WCS_PROFILE_MANAGEMENT_SCOPE_SYSTEM_WIDE};
+use windows::Win32::UI::HiDpi::{AreDpiAwarenessContextsEqual, GetAwarenessFromDpiAwarenessContext, GetDpiForMonitor, GetThreadDpiAwarenessContext, GetWindowDpiAwarenessContext, SetProcessDpiAwarenessContext, SetThreadDpiAwarenessContext, DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, DPI_AWARENESS_CONTEXT_UNAWARE_GDISCALED, DPI_AWARENESS_PER_MONITOR_AWARE, DPI_AWARENESS_SYSTEM_AWARE, DPI_AWARENESS_UNAWARE, MDT_EFFECTIVE_DPI};
+use windows::Win32::System::Console::GetConsoleWindow;
+use windows::Win32::UI::WindowsAndMessaging::{EnumWindows, GetCursorPos, GetForegroundWindow, GetSystemMetrics, GetWindowLongW, GetWindowRect, GetWindowTextW, GetWindowThreadProcessId, IsIconic, EDD_GET_DEVICE_INTERFACE_NAME, IsWindowVisible, SendMessageTimeoutW, SetWindowPos, GWL_EXSTYLE, HWND_BROADCAST, SMTO_ABORTIFHUNG, SMTO_NORMAL, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_REMOTESESSION, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, SPI_SETLOGICALDPIOVERRIDE, SWP_NOACTIVATE, SWP_NOZORDER, WINDOW_EX_STYLE, WM_SETTINGCHANGE, WS_EX_TOOLWINDOW};
+use windows::core::{w, BOOL, HSTRING, PCWSTR, PWSTR};
+
+/// `DISPLAYCONFIG_PATH_INFO` flag for Dynamic Refresh Rate, from the Windows 11 SDK; the
+/// `windows` crate doesn't define it yet.
+pub const DISPLAYCONFIG_PATH_BOOST_REFRESH_RATE: u32 = 0x10;
+
+/// Owned copy of a `DISPLAYCONFIG_PATH_INFO`; derefs to the raw structure.
+#[derive(Clone, Copy, Default)]
+pub struct PathInfo(pub DISPLAYCONFIG_PATH_INFO);
+
+impl PathInfo {
+    #[must_use]
+    pub fn source_id(&self) -> u32 {
+        self.0.sourceInfo.id
+    }
+
+    /// The adapter driving the source; source ids are only unique per adapter.
+    #[must_use]
+    pub fn adapter_id(&self) -> AdapterId {
+        let luid = self.0.sourceInfo.adapterId;
+        AdapterId { low: luid.LowPart, high: luid.HighPart }
+    }
+
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.0.flags & DISPLAYCONFIG_PATH_ACTIVE != 0
+    }
+
+    /// Whether the target can currently be driven; an active path's monitor can still be
+    /// unavailable while it's asleep or switching inputs.
+    #[must_use]
+    pub fn target_available(&self) -> bool {
+        self.0.targetInfo.targetAvailable.as_bool()
+    }
+
+    /// The target's `DISPLAYCONFIG_TARGET_*` status flags.
+    #[must_use]
+    pub fn target_status(&self) -> u32 {
+        self.0.targetInfo.statusFlags
+    }
+
+    /// The raw `DISPLAYCONFIG_PATH_*` flags.
+    #[must_use]
+    pub fn flags(&self) -> u32 {
+        self.0.flags
+    }
+
+    /// Whether the driver supports virtual mode (desktop image modes) on the path. Queries here
+    /// aren't virtual mode aware, so the path still uses plain source and target modes.
+    #[must_use]
+    pub fn supports_virtual_mode(&self) -> bool {
+        self.0.flags & DISPLAYCONFIG_PATH_SUPPORT_VIRTUAL_MODE != 0
+    }
+
+    /// Dynamic Refresh Rate is on for the path. Only reported and honored with
+    /// `QDC_VIRTUAL_REFRESH_RATE_AWARE` and `SDC_VIRTUAL_REFRESH_RATE_AWARE`, Windows 11 and later.
+    #[must_use]
+    pub fn boost_refresh_rate(&self) -> bool {
+        self.0.flags & DISPLAYCONFIG_PATH_BOOST_REFRESH_RATE != 0
+    }
+
+    pub fn set_boost_refresh_rate(&mut self, enabled: bool) {
+        if enabled {
+            self.0.flags |= DISPLAYCONFIG_PATH_BOOST_REFRESH_RATE;
+        } else {
+            self.0.flags &= !DISPLAYCONFIG_PATH_BOOST_REFRESH_RATE;
+        }
+    }
+
+    /// Index of the source mode in the mode array, `None` if the path has none.
+    #[must_use]
+    pub fn source_mode_index(&self) -> Option<usize> {
+        // SAFETY: queried without QDC_VIRTUAL_MODE_AWARE, so modeInfoIdx is the active member.
+        let index = unsafe { self.0.sourceInfo.Anonymous.modeInfoIdx };
+        mode_index(index)
+    }
+
+    /// Marks the path inactive and drops its mode references; a supplied configuration turns
+    /// the display off this way.
+    pub fn deactivate(&mut self) {
+        self.0.flags &= !DISPLAYCONFIG_PATH_ACTIVE;
+        self.0.sourceInfo.Anonymous.modeInfoIdx = DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+        self.0.targetInfo.Anonymous.modeInfoIdx = DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+    }
+
+    /// Index of the target mode in the mode array, `None` if the path has none.
+    #[must_use]
+    pub fn target_mode_index(&self) -> Option<usize> {
+        // SAFETY: as above.
+        let index = unsafe { self.0.targetInfo.Anonymous.modeInfoIdx };
+        mode_index(index)
+    }
+}
+
+fn mode_index(index: u32) -> Option<usize> {
+    (index != DISPLAYCONFIG_PATH_MODE_IDX_INVALID).then_some(index as usize)
+}
+
+impl Deref for PathInfo {
+    type Target = DISPLAYCONFIG_PATH_INFO;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for PathInfo {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Hash for PathInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (source, target) = (&self.0.sourceInfo, &self.0.targetInfo);
+        (source.adapterId.LowPart, source.adapterId.HighPart, source.id, source.statusFlags).hash(state);
+        (target.adapterId.LowPart, target.adapterId.HighPart, target.id, target.statusFlags).hash(state);
+        (self.source_mode_index(), self.target_mode_index(), self.0.flags).hash(state);
+        (target.outputTechnology.0, target.rotation.0, target.scaling.0, target.scanLineOrdering.0).hash(state);
+        (target.refreshRate.Numerator, target.refreshRate.Denominator, target.targetAvailable.as_bool()).hash(state);
+    }
+}
+
+/// Owned copy of a `DISPLAYCONFIG_MODE_INFO`; derefs to the raw structure.
+#[derive(Clone, Copy, Default)]
+pub struct ModeInfo(pub DISPLAYCONFIG_MODE_INFO);
+
+/// Hashes the member `infoType` names. Desktop image modes only show up in virtual mode aware
+/// queries, which aren't used, so only their header counts.
+impl Hash for ModeInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0.infoType.0, self.0.id, self.0.adapterId.LowPart, self.0.adapterId.HighPart).hash(state);
+        if let Some(mode) = self.source_mode() {
+            (mode.width, mode.height, mode.pixelFormat.0, mode.position.x, mode.position.y).hash(state);
+        } else if let Some(mode) = self.target_mode() {
+            let signal = &mode.targetVideoSignalInfo;
+            (signal.pixelRate, signal.activeSize.cx, signal.activeSize.cy, signal.totalSize.cx, signal.totalSize.cy).hash(state);
+            (signal.vSyncFreq.Numerator, signal.vSyncFreq.Denominator).hash(state);
+            (signal.hSyncFreq.Numerator, signal.hSyncFreq.Denominator).hash(state);
+        }
+    }
+}
+
+/// A hash of a queried configuration, equal for two queries only if nothing changed between
+/// them.
+#[must_use]
+pub fn config_fingerprint(paths: &[PathInfo], modes: &[ModeInfo]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    paths.hash(&mut hasher);
+    modes.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl ModeInfo {
+    /// The source mode, `None` if this entry holds another kind of mode.
+    #[must_use]
+    pub fn source_mode(&self) -> Option<&DISPLAYCONFIG_SOURCE_MODE> {
+        // SAFETY: the union member is only read when infoType says it's the valid one.
+        if self.0.infoType == DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE {
+            Some(unsafe { &self.0.Anonymous.sourceMode })
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub fn source_mode_mut(&mut self) -> Option<&mut DISPLAYCONFIG_SOURCE_MODE> {
+        // SAFETY: as above.
+        if self.0.infoType == DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE {
+            Some(unsafe { &mut self.0.Anonymous.sourceMode })
+        } else {
+            None
+        }
+    }
+
+    /// The target mode, `None` if this entry holds another kind of mode.
+    #[must_use]
+    pub fn target_mode(&self) -> Option<&DISPLAYCONFIG_TARGET_MODE> {
+        // SAFETY: as above.
+        if self.0.infoType == DISPLAYCONFIG_MODE_INFO_TYPE_TARGET {
+            Some(unsafe { &self.0.Anonymous.targetMode })
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub fn target_mode_mut(&mut self) -> Option<&mut DISPLAYCONFIG_TARGET_MODE> {
+        // SAFETY: as above.
+        if self.0.infoType == DISPLAYCONFIG_MODE_INFO_TYPE_TARGET {
+            Some(unsafe { &mut self.0.Anonymous.targetMode })
+        } else {
+            None
+        }
+    }
+
+    /// The desktop image info, `None` if this entry holds another kind of mode. Only virtual
+    /// mode aware queries report these.
+    #[must_use]
+    pub fn desktop_image_info(&self) -> Option<&DISPLAYCONFIG_DESKTOP_IMAGE_INFO> {
+        // SAFETY: as above.
+        if self.0.infoType == DISPLAYCONFIG_MODE_INFO_TYPE_DESKTOP_IMAGE {
+            Some(unsafe { &self.0.Anonymous.desktopImageInfo })
+        } else {
+            None
+        }
+    }
+}
+
+/// The `videoStandard` member of `signal`, whose bits also make up the additional signal info.
+#[must_use]
+pub fn video_standard(signal: &DISPLAYCONFIG_VIDEO_SIGNAL_INFO) -> u32 {
+    // SAFETY: both union members are a plain u32.
+    unsafe { signal.Anonymous.videoStandard }
+}
+
+impl Deref for ModeInfo {
+    type Target = DISPLAYCONFIG_MODE_INFO;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ModeInfo {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Source mode of `path`, if it has a valid one in `modes`.
+#[must_use]
+pub fn source_mode<'a>(path: &PathInfo, modes: &'a [ModeInfo]) -> Option<&'a DISPLAYCONFIG_SOURCE_MODE> {
+    modes.get(path.source_mode_index()?)?.source_mode()
+}
+
+/// Target mode of `path`, if it has a valid one in `modes`.
+#[must_use]
+pub fn target_mode<'a>(path: &PathInfo, modes: &'a [ModeInfo]) -> Option<&'a DISPLAYCONFIG_TARGET_MODE> {
+    modes.get(path.target_mode_index()?)?.target_mode()
+}
+
+#[must_use]
+pub fn source_mode_mut<'a>(
+    path: &PathInfo,
+    modes: &'a mut [ModeInfo],
+) -> Option<&'a mut DISPLAYCONFIG_SOURCE_MODE> {
+    modes.get_mut(path.source_mode_index()?)?.source_mode_mut()
+}
+
+#[must_use]
+pub fn target_mode_mut<'a>(
+    path: &PathInfo,
+    modes: &'a mut [ModeInfo],
+) -> Option<&'a mut DISPLAYCONFIG_TARGET_MODE> {
+    modes.get_mut(path.target_mode_index()?)?.target_mode_mut()
+}
+
+/// Queries the display configuration, returning copies of the path and mode arrays.
+///
+/// The configuration can grow between sizing the buffers and querying them when a display is
+/// plugged in, which `QueryDisplayConfig` reports as `ERROR_INSUFFICIENT_BUFFER`; the pair is
+/// then repeated, see [`retry_insufficient_buffer`]. The arrays are cut to the counts the query
+/// returned, which can be smaller than the buffer sizes.
+///
+/// `QDC_DATABASE_CURRENT` isn't supported as it needs a topology id out-parameter.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::QueryDisplayConfig`] with the Win32 error code on failure.
+pub fn query_display_config(flags: QUERY_DISPLAY_CONFIG_FLAGS) -> Result<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    retry_insufficient_buffer(|| query_display_config_once(flags))
+}
+
+/// `ERROR_INSUFFICIENT_BUFFER`.
+const INSUFFICIENT_BUFFER: u32 = 122;
+
+/// How often [`query_display_config`] sizes and queries before giving up.
+pub const QUERY_ATTEMPTS: u32 = 5;
+
+/// Runs `query` until it stops failing with `ERROR_INSUFFICIENT_BUFFER`, at most
+/// [`QUERY_ATTEMPTS`] times. Any other result is returned right away.
+///
+/// # Errors
+///
+/// Returns the last error of `query`.
+pub fn retry_insufficient_buffer<T>(mut query: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        match query() {
+            Err(DisplayError::QueryDisplayConfig(INSUFFICIENT_BUFFER)) if attempt < QUERY_ATTEMPTS => {
+                debug!(attempt, "Display configuration grew while querying it, retrying");
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn query_display_config_once(flags: QUERY_DISPLAY_CONFIG_FLAGS) -> Result<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    let mut path_count = 0u32;
+    let mut mode_count = 0u32;
+
+    // SAFETY: the counts are valid out-pointers and the buffers are sized from them.
+    unsafe {
+        let result = GetDisplayConfigBufferSizes(flags, &raw mut path_count, &raw mut mode_count);
+        if result.0 != 0 {
+            return Err(DisplayError::QueryDisplayConfig(result.0));
+        }
+
+        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+
+        let result = QueryDisplayConfig(
+            flags,
+            &raw mut path_count,
+            paths.as_mut_ptr(),
+            &raw mut mode_count,
+            modes.as_mut_ptr(),
+            None,
+        );
+        if result.0 != 0 {
+            return Err(DisplayError::QueryDisplayConfig(result.0));
+        }
+
+        paths.truncate(path_count as usize);
+        modes.truncate(mode_count as usize);
+        debug!("Retrieved {} paths and {} modes", path_count, mode_count);
+
+        Ok((
+            paths.into_iter().map(PathInfo).collect(),
+            modes.into_iter().map(ModeInfo).collect(),
+        ))
+    }
+}
+
+/// Passes `paths` and `modes` to `SetDisplayConfig`.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::SetDisplayConfig`] with the Win32 error code if the OS rejects the
+/// configuration.
+pub fn set_display_config(paths: &[PathInfo], modes: &[ModeInfo], flags: SET_DISPLAY_CONFIG_FLAGS) -> Result<()> {
+    let paths: Vec<DISPLAYCONFIG_PATH_INFO> = paths.iter().map(|p| p.0).collect();
+    let modes: Vec<DISPLAYCONFIG_MODE_INFO> = modes.iter().map(|m| m.0).collect();
+
+    // SAFETY: both arrays are valid for the duration of the call; the OS validates indices.
+    let result = unsafe {
+        SetDisplayConfig(
+            (!paths.is_empty()).then_some(paths.as_slice()),
+            (!modes.is_empty()).then_some(modes.as_slice()),
+            flags,
+        )
+    };
+    if result != 0 {
+        return Err(DisplayError::SetDisplayConfig(result));
+    }
+    Ok(())
+}
+
+/// Finds the path driving source `source_id`.
+#[must_use]
+pub fn find_path(paths: &[PathInfo], source_id: u32) -> Option<&PathInfo> {
+    paths.iter().find(|path| path.source_id() == source_id)
+}
+
+/// Finds the path of source `source_id` on adapter `adapter_id`. An unknown adapter (see
+/// [`AdapterId::is_unknown`]) matches the first path with the source id, like [`find_path`].
+#[must_use]
+pub fn find_adapter_path(paths: &[PathInfo], adapter_id: AdapterId, source_id: u32) -> Option<&PathInfo> {
+    paths
+        .iter()
+        .find(|path| path.source_id() == source_id && (adapter_id.is_unknown() || path.adapter_id() == adapter_id))
+}
+
+/// Finds the path whose target has the monitor device path `device_path` (case-insensitive).
+///
+/// # Errors
+///
+/// Returns an error if a target name can't be queried.
+pub fn find_path_by_device_path<'a>(paths: &'a [PathInfo], device_path: &str) -> Result<Option<&'a PathInfo>> {
+    for path in paths {
+        let name = target_device_name(path)?;
+        if utf16_to_string(&name.monitorDevicePath).eq_ignore_ascii_case(device_path) {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn utf16_to_string(buffer: &[u16]) -> String {
+    String::from_utf16_lossy(buffer).trim_end_matches('\0').to_string()
+}
+
+/// Monitor name, device path and EDID ids of the path's target.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::GetMonitorFriendlyName`] if the query fails.
+pub fn target_device_name(path: &PathInfo) -> Result<DISPLAYCONFIG_TARGET_DEVICE_NAME> {
+    let mut target_name = DISPLAYCONFIG_TARGET_DEVICE_NAME {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+            size: u32::try_from(size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>())?,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        ..DISPLAYCONFIG_TARGET_DEVICE_NAME::default()
+    };
+
+    // SAFETY: the header is the first field and its size covers the whole structure.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut target_name.header) };
+    if result == 0 {
+        Ok(target_name)
+    } else {
+        Err(DisplayError::GetMonitorFriendlyName(result))
+    }
+}
+
+/// The `friendlyNameFromEdid` (bit 0), `friendlyNameForced` (bit 1) and `edidIdsValid` (bit 2)
+/// flags of a [`target_device_name`] answer.
+#[must_use]
+pub fn target_name_flags(name: &DISPLAYCONFIG_TARGET_DEVICE_NAME) -> u32 {
+    // SAFETY: both members of the union are the same 32 bits.
+    unsafe { name.flags.Anonymous.value }
+}
+
+/// GDI device name of the path's source, such as `\\.\DISPLAY1`.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::GetSourceDeviceName`] if the query fails.
+pub fn source_gdi_name(path: &PathInfo) -> Result<String> {
+    let mut source_name = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+            size: u32::try_from(size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>())?,
+            adapterId: path.sourceInfo.adapterId,
+            id: path.sourceInfo.id,
+        },
+        viewGdiDeviceName: [0; 32],
+    };
+
+    // SAFETY: as above.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut source_name.header) };
+    if result == 0 {
+        Ok(utf16_to_string(&source_name.viewGdiDeviceName))
+    } else {
+        Err(DisplayError::GetSourceDeviceName(result))
+    }
+}
+
+/// The output technology underneath the path's target: for a target reporting the connector
+/// the monitor plugs into, such as HDMI from a DP adapter or DP tunnelled over USB, the one the
+/// GPU actually drives.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::GetTargetBaseType`] if the query fails.
+pub fn target_base_type(path: &PathInfo) -> Result<DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY> {
+    let mut base_type = DISPLAYCONFIG_TARGET_BASE_TYPE {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_BASE_TYPE,
+            size: u32::try_from(size_of::<DISPLAYCONFIG_TARGET_BASE_TYPE>())?,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        ..DISPLAYCONFIG_TARGET_BASE_TYPE::default()
+    };
+
+    // SAFETY: as above.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut base_type.header) };
+    if result == 0 {
+        Ok(base_type.baseOutputTechnology)
+    } else {
+        Err(DisplayError::GetTargetBaseType(result))
+    }
+}
+
+/// Device path of the adapter driving the path's target, e.g. `\\?\PCI#VEN_10DE&DEV_2484...`.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::GetAdapterName`] if the query fails.
+pub fn adapter_device_path(path: &PathInfo) -> Result<String> {
+    let mut adapter_name = DISPLAYCONFIG_ADAPTER_NAME {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_ADAPTER_NAME,
+            size: u32::try_from(size_of::<DISPLAYCONFIG_ADAPTER_NAME>())?,
+            adapterId: path.targetInfo.adapterId,
+            id: 0,
+        },
+        ..DISPLAYCONFIG_ADAPTER_NAME::default()
+    };
+
+    // SAFETY: as above.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut adapter_name.header) };
+    if result == 0 {
+        Ok(utf16_to_string(&adapter_name.adapterDevicePath))
+    } else {
+        Err(DisplayError::GetAdapterName(result))
+    }
+}
+
+/// Advanced color state, encoding and bits per color channel of the path's target.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::GetAdvancedColorInfo`] if the query fails, e.g. before Windows 10
+/// 1709.
+pub fn advanced_color_info(path: &PathInfo) -> Result<DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO> {
+    let mut color_info = DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO,
+            size: u32::try_from(size_of::<DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO>())?,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        ..DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO::default()
+    };
+
+    // SAFETY: the header is the first field and its size covers the whole structure.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut color_info.header) };
+    if result == 0 {
+        Ok(color_info)
+    } else {
+        Err(DisplayError::GetAdvancedColorInfo(result))
+    }
+}
+
+/// Whether advanced color (HDR) is enabled according to `info`'s flag bits.
+#[must_use]
+pub fn advanced_color_enabled(info: &DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO) -> bool {
+    // SAFETY: both union members are a plain u32; bit 1 is advancedColorEnabled.
+    unsafe { info.Anonymous.value & 0b10 != 0 }
+}
+
+/// Whether the path's target can do advanced color (HDR) according to `info`'s flag bits.
+#[must_use]
+pub fn advanced_color_supported(info: &DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO) -> bool {
+    // SAFETY: both union members are a plain u32; bit 0 is advancedColorSupported.
+    unsafe { info.Anonymous.value & 0b1 != 0 }
+}
+
+/// SDR white level of the path's target in thousandths of 80 nits (1000 = 80 nits).
+///
+/// # Errors
+///
+/// Returns [`DisplayError::GetSdrWhiteLevel`] if the query fails.
+pub fn sdr_white_level(path: &PathInfo) -> Result<u32> {
+    let mut level = DISPLAYCONFIG_SDR_WHITE_LEVEL {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SDR_WHITE_LEVEL,
+            size: u32::try_from(size_of::<DISPLAYCONFIG_SDR_WHITE_LEVEL>())?,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        SDRWhiteLevel: 0,
+    };
+
+    // SAFETY: the header is the first field and its size covers the whole structure.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut level.header) };
+    if result == 0 {
+        Ok(level.SDRWhiteLevel)
+    } else {
+        Err(DisplayError::GetSdrWhiteLevel(result))
+    }
+}
+
+/// The mode the path's target prefers, usually the panel's native resolution, in the target's
+/// own orientation.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::GetPreferredMode`] if the query fails; some TVs and virtual
+/// displays report no preferred mode.
+pub fn target_preferred_mode(path: &PathInfo) -> Result<DISPLAYCONFIG_TARGET_PREFERRED_MODE> {
+    let mut mode = DISPLAYCONFIG_TARGET_PREFERRED_MODE {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_PREFERRED_MODE,
+            size: u32::try_from(size_of::<DISPLAYCONFIG_TARGET_PREFERRED_MODE>())?,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        ..DISPLAYCONFIG_TARGET_PREFERRED_MODE::default()
+    };
+
+    // SAFETY: the header is the first field and its size covers the whole structure.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut mode.header) };
+    if result == 0 {
+        Ok(mode)
+    } else {
+        Err(DisplayError::GetPreferredMode(result))
+    }
+}
+
+/// Undocumented counterpart of `DISPLAYCONFIG_SDR_WHITE_LEVEL` used by the Settings app,
+/// device info type -18.
+#[repr(C)]
+struct SdrWhiteLevelSet {
+    header: DISPLAYCONFIG_DEVICE_INFO_HEADER,
+    sdr_white_level: u32,
+    /// Non-zero commits the value; zero is a preview while dragging the slider.
+    final_value: u8,
+}
+
+/// Sets the SDR white level of the path's target, in the unit of [`sdr_white_level`].
+///
+/// # Errors
+///
+/// Returns [`DisplayError::SetSdrWhiteLevel`] if the OS rejects the value.
+pub fn set_sdr_white_level(path: &PathInfo, level: u32) -> Result<()> {
+    let mut set = SdrWhiteLevelSet {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_TYPE(-18i32),
+            size: u32::try_from(size_of::<SdrWhiteLevelSet>())?,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        sdr_white_level: level,
+        final_value: 1,
+    };
+
+    // SAFETY: repr(C) structure starting with the header, size set accordingly.
+    let result = unsafe { DisplayConfigSetDeviceInfo(&raw mut set.header) };
+    if result != 0 {
+        return Err(DisplayError::SetSdrWhiteLevel(result));
+    }
+    Ok(())
+}
+
+/// DPI scaling steps relative to the recommended value, as reported by the undocumented
+/// device info types -3 (get) and -4 (set).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DpiScale {
+    /// Steps below the recommended value, negative or zero.
+    pub min: i32,
+    pub current: i32,
+    pub max: i32,
+}
+
+#[repr(C)]
+struct DpiScaleGet {
+    header: DISPLAYCONFIG_DEVICE_INFO_HEADER,
+    min_scale_rel: i32,
+    cur_scale_rel: i32,
+    max_scale_rel: i32,
+}
+
+#[repr(C)]
+struct DpiScaleSet {
+    header: DISPLAYCONFIG_DEVICE_INFO_HEADER,
+    scale_rel: i32,
+}
+
+/// # Errors
+///
+/// Returns [`DisplayError::GetDpiInfo`] if the query fails.
+pub fn dpi_scale(path: &PathInfo) -> Result<DpiScale> {
+    let mut dpi_info = DpiScaleGet {
+        header: source_info_header(path, DISPLAYCONFIG_DEVICE_INFO_TYPE(-3i32), size_of::<DpiScaleGet>())?,
+        min_scale_rel: 0,
+        cur_scale_rel: 0,
+        max_scale_rel: 0,
+    };
+
+    // SAFETY: repr(C) structure starting with the header, size set accordingly.
+    let result = unsafe { DisplayConfigGetDeviceInfo(&raw mut dpi_info.header) };
+    if result != 0 {
+        return Err(DisplayError::GetDpiInfo(result));
+    }
+    Ok(DpiScale {
+        min: dpi_info.min_scale_rel,
+        current: dpi_info.cur_scale_rel,
+        max: dpi_info.max_scale_rel,
+    })
+}
+
+/// Header of a device info request about the source of `path`: the adapter and source id
+/// both come from the path, so a request can't reach a same-numbered source on another adapter.
+fn source_info_header(
+    path: &PathInfo,
+    info_type: DISPLAYCONFIG_DEVICE_INFO_TYPE,
+    size: usize,
+) -> Result<DISPLAYCONFIG_DEVICE_INFO_HEADER> {
+    Ok(DISPLAYCONFIG_DEVICE_INFO_HEADER {
+        r#type: info_type,
+        size: u32::try_from(size)?,
+        adapterId: path.sourceInfo.adapterId,
+        id: path.sourceInfo.id,
+    })
+}
+
+/// The header [`set_dpi_scale`] sends for `path`.
+///
+/// # Errors
+///
+/// Doesn't fail in practice; the request size always fits.
+pub fn dpi_set_header(path: &PathInfo) -> Result<DISPLAYCONFIG_DEVICE_INFO_HEADER> {
+    source_info_header(path, DISPLAYCONFIG_DEVICE_INFO_TYPE(-4i32), size_of::<DpiScaleSet>())
+}
+
+/// Sets the path's DPI scaling to `relative` steps from the recommended value.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::SetDpiScaling`] if the OS rejects the value.
+pub fn set_dpi_scale(path: &PathInfo, relative: i32) -> Result<()> {
+    let mut dpi_set = DpiScaleSet {
+        header: dpi_set_header(path)?,
+        scale_rel: relative,
+    };
+
+    // SAFETY: as above.
+    let result = metrics::time(Phase::Scaling, || unsafe { DisplayConfigSetDeviceInfo(&raw mut dpi_set.header) });
+    if result != 0 {
+        return Err(DisplayError::SetDpiScaling(result));
+    }
+    Ok(())
+}
+
+/// Effective DPI of the monitor at desktop point (`x`, `y`), 96 being 100%.
+///
+/// Queried as a per-monitor DPI aware thread, so the value isn't virtualized to the process's
+/// awareness and reflects a custom system-wide scaling if one is active.
+///
+/// # Errors
+///
+/// Returns an error if the monitor's DPI can't be queried.
+pub fn effective_dpi_at(x: i32, y: i32) -> Result<u32> {
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    // SAFETY: the out-pointers are valid; the thread's previous awareness is restored before
+    // returning.
+    let result = unsafe {
+        let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        let monitor = MonitorFromPoint(POINT { x, y }, MONITOR_DEFAULTTONEAREST);
+        let result = GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &raw mut dpi_x, &raw mut dpi_y);
+        if !previous.is_invalid() {
+            SetThreadDpiAwarenessContext(previous);
+        }
+        result
+    };
+    result?;
+    Ok(dpi_x)
+}
+
+/// Whether the monitor showing the desktop point (`x`, `y`) in physical pixels answers a
+/// DDC/CI capabilities request. The request goes over the monitor's I2C bus and takes up to a
+/// few seconds when it doesn't answer.
+///
+/// # Errors
+///
+/// Returns an error if the physical monitor handles can't be obtained.
+#[cfg(feature = "ddcci")]
+pub fn ddc_ci_responds(x: i32, y: i32) -> Result<bool> {
+    // SAFETY: the out-pointers are valid, the physical monitor handles are destroyed before
+    // returning and the thread's previous awareness is restored right away.
+    unsafe {
+        let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        let monitor = MonitorFromPoint(POINT { x, y }, MONITOR_DEFAULTTONEAREST);
+        if !previous.is_invalid() {
+            SetThreadDpiAwarenessContext(previous);
+        }
+        let mut count = 0;
+        GetNumberOfPhysicalMonitorsFromHMONITOR(monitor, &raw mut count)?;
+        let mut physical = vec![PHYSICAL_MONITOR::default(); usize::try_from(count)?];
+        GetPhysicalMonitorsFromHMONITOR(monitor, &mut physical)?;
+        let responds = physical.iter().any(|m| {
+            let mut len = 0;
+            GetCapabilitiesStringLength(m.hPhysicalMonitor, &raw mut len) != 0 && len > 0
+        });
+        let _ = DestroyPhysicalMonitors(&physical);
+        Ok(responds)
+    }
+}
+
+const DESKTOP_KEY: PCWSTR = w!(r"Control Panel\Desktop");
+
+/// Removes the current user's legacy custom scaling (`LogPixels` with `Win8DpiScaling`) so
+/// per-monitor scaling applies again after the next sign-in.
+///
+/// # Errors
+///
+/// Returns an error if the registry values can't be written.
+pub fn clear_custom_scaling() -> Result<()> {
+    let per_monitor = 0u32;
+    // SAFETY: constant key and value names; the data pointer covers the announced 4 bytes.
+    unsafe {
+        let deleted = RegDeleteKeyValueW(HKEY_CURRENT_USER, DESKTOP_KEY, w!("LogPixels"));
+        if deleted != ERROR_FILE_NOT_FOUND {
+            deleted.ok()?;
+        }
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            DESKTOP_KEY,
+            w!("Win8DpiScaling"),
+            REG_DWORD.0,
+            Some((&raw const per_monitor).cast()),
+            u32::try_from(size_of::<u32>())?,
+        )
+        .ok()?;
+    }
+    debug!("Cleared custom scaling override");
+    Ok(())
+}
+
+/// How long each top-level window gets to handle [`broadcast_dpi_change`].
+const BROADCAST_TIMEOUT_MS: u32 = 1000;
+
+/// Tells top-level windows that the DPI settings changed, the way the Settings app does, so
+/// running apps rescale without a restart. Hung windows are skipped and slow ones get
+/// [`BROADCAST_TIMEOUT_MS`].
+pub fn broadcast_dpi_change() {
+    // SAFETY: no pointers are passed; the result out-pointer is optional.
+    let result = unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            WPARAM(SPI_SETLOGICALDPIOVERRIDE.0 as usize),
+            LPARAM(0),
+            SMTO_NORMAL | SMTO_ABORTIFHUNG,
+            BROADCAST_TIMEOUT_MS,
+            None,
+        )
+    };
+    debug!(delivered = result.0 != 0, "Broadcast DPI setting change");
+}
+
+/// Whether the process runs in a Remote Desktop session, where the display stack is
+/// virtualized by the client.
+#[must_use]
+pub fn is_remote_session() -> bool {
+    // SAFETY: plain query without pointers.
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+/// `WSF_VISIBLE`: the window station has visible display surfaces.
+const WSF_VISIBLE: u32 = 1;
+
+/// The session and window station the process runs in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// 0 is the session of services, which has no interactive desktop.
+    pub session_id: u32,
+    /// E.g. `WinSta0`, or `Service-0x0-3e7$` for a service running as SYSTEM.
+    pub window_station: String,
+    /// The window station can show anything; only `WinSta0` can.
+    pub visible: bool,
+}
+
+impl SessionInfo {
+    /// Whether display configuration can work here: it needs the visible window station of a
+    /// user session.
+    #[must_use]
+    pub fn is_interactive(&self) -> bool {
+        self.session_id != 0 && self.visible
+    }
+}
+
+/// The session and window station of the process.
+///
+/// # Errors
+///
+/// Returns an error if either can't be queried.
+pub fn session_info() -> Result<SessionInfo> {
+    let mut session_id = 0;
+    // SAFETY: the pointer is valid for the call.
+    unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &raw mut session_id)? };
+
+    // SAFETY: the handle belongs to the process and isn't closed.
+    let station = HANDLE(unsafe { GetProcessWindowStation()? }.0);
+    let mut name = [0u16; 256];
+    let mut flags = USEROBJECTFLAGS::default();
+    // SAFETY: each buffer is valid for the size passed along with it.
+    unsafe {
+        GetUserObjectInformationW(station, UOI_NAME, Some(name.as_mut_ptr().cast()), u32::try_from(size_of_val(&name))?, None)?;
+        GetUserObjectInformationW(station, UOI_FLAGS, Some((&raw mut flags).cast()), u32::try_from(size_of::<USEROBJECTFLAGS>())?, None)?;
+    }
+    Ok(SessionInfo { session_id, window_station: utf16_to_string(&name), visible: flags.dwFlags & WSF_VISIBLE != 0 })
+}
+
+/// The virtual screen as the window manager reports it, (x, y, width, height) in physical
+/// pixels.
+#[must_use]
+pub fn virtual_screen() -> (i32, i32, i32, i32) {
+    // SAFETY: plain queries without pointers; the thread's previous awareness is restored.
+    unsafe {
+        let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        let screen = (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        );
+        if !previous.is_invalid() {
+            SetThreadDpiAwarenessContext(previous);
+        }
+        screen
+    }
+}
+
+/// Signs the current user out, closing their apps; returns once the request is queued.
+///
+/// # Errors
+///
+/// Returns an error if Windows refuses the request.
+pub fn sign_out() -> Result<()> {
+    // SAFETY: no pointers involved.
+    unsafe { ExitWindowsEx(EWX_LOGOFF, SHTDN_REASON_MAJOR_OTHER | SHTDN_REASON_FLAG_PLANNED)? };
+    Ok(())
+}
+
+const CURRENT_VERSION_KEY: PCWSTR = w!(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion");
+
+/// The OS build with its update revision, e.g. `22631.4317`.
+///
+/// # Errors
+///
+/// Returns an error if the build number can't be read from the registry.
+pub fn os_build() -> Result<String> {
+    let mut build = [0u16; 32];
+    let mut len = u32::try_from(size_of_val(&build))?;
+    let mut revision = 0u32;
+    let mut revision_len = u32::try_from(size_of::<u32>())?;
+    // SAFETY: the buffers and their byte lengths match; RegGetValueW NUL-terminates strings.
+    unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            CURRENT_VERSION_KEY,
+            w!("CurrentBuildNumber"),
+            RRF_RT_REG_SZ,
+            None,
+            Some(build.as_mut_ptr().cast()),
+            Some(&raw mut len),
+        )
+        .ok()?;
+        // Missing before Windows 10.
+        let has_revision = RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            CURRENT_VERSION_KEY,
+            w!("UBR"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some((&raw mut revision).cast()),
+            Some(&raw mut revision_len),
+        )
+        .is_ok();
+        let build = utf16_to_string(&build);
+        Ok(if has_revision { format!("{build}.{revision}") } else { build })
+    }
+}
+
+/// The registry key holding the `Device Parameters` of the monitor with device interface path
+/// `device_path`, e.g. `\\?\DISPLAY#DEL41A8#5&1a2b3c&0&UID4352#{e6f07b5f-...}` maps to
+/// `SYSTEM\CurrentControlSet\Enum\DISPLAY\DEL41A8\5&1a2b3c&0&UID4352\Device Parameters`.
+/// `None` if the path doesn't have that shape.
+#[must_use]
+pub fn monitor_parameters_key(device_path: &str) -> Option<String> {
+    let path = device_path.strip_prefix(r"\\?\").unwrap_or(device_path);
+    let mut parts = path.split('#');
+    let (Some(class), Some(hardware_id), Some(instance)) = (parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+    if [class, hardware_id, instance].iter().any(|part| part.is_empty() || part.contains('\\')) {
+        return None;
+    }
+    Some(format!(r"SYSTEM\CurrentControlSet\Enum\{class}\{hardware_id}\{instance}\Device Parameters"))
+}
+
+/// The raw EDID the monitor with device interface path `device_path` reported, base block and
+/// extensions, as Windows stored it in the registry.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::EdidUnavailable`] if the path has no registry key or the key has no
+/// EDID, or an error if reading it fails.
+pub fn monitor_edid(device_path: &str) -> Result<Vec<u8>> {
+    let unavailable = || DisplayError::EdidUnavailable(device_path.to_string());
+    let key: Vec<u16> = monitor_parameters_key(device_path)
+        .ok_or_else(unavailable)?
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut len = 0u32;
+    // SAFETY: the key is NUL-terminated; the first call only reports the size, the second
+    // writes at most `len` bytes into a buffer of that size.
+    unsafe {
+        let size = RegGetValueW(HKEY_LOCAL_MACHINE, PCWSTR(key.as_ptr()), w!("EDID"), RRF_RT_REG_BINARY, None, None, Some(&raw mut len));
+        if size == ERROR_FILE_NOT_FOUND {
+            return Err(unavailable());
+        }
+        size.ok()?;
+        let mut edid = vec![0u8; usize::try_from(len)?];
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            w!("EDID"),
+            RRF_RT_REG_BINARY,
+            None,
+            Some(edid.as_mut_ptr().cast()),
+            Some(&raw mut len),
+        )
+        .ok()?;
+        edid.truncate(usize::try_from(len)?);
+        Ok(edid)
+    }
+}
+
+/// Modes the driver lists for the GDI device `device_name`, in driver order.
+///
+/// # Errors
+///
+/// Returns an error if `DEVMODEW`'s size doesn't fit its size field.
+pub fn enum_display_settings(device_name: &str) -> Result<Vec<DEVMODEW>> {
+    let device_name: Vec<u16> = device_name.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut devmode = DEVMODEW {
+        dmSize: u16::try_from(size_of::<DEVMODEW>())?,
+        ..DEVMODEW::default()
+    };
+
+    let mut settings = Vec::new();
+    let mut mode_num = 0;
+    loop {
+        // SAFETY: the name is NUL-terminated and outlives the call; dmSize is set.
+        let found = unsafe {
+            EnumDisplaySettingsW(
+                PCWSTR(device_name.as_ptr()),
+                ENUM_DISPLAY_SETTINGS_MODE(mode_num),
+                &raw mut devmode,
+            )
+        };
+        if !found.as_bool() {
+            break;
+        }
+        settings.push(devmode);
+        mode_num += 1;
+    }
+    Ok(settings)
+}
+
+/// Changes the resolution, and the refresh rate if given, of the GDI device `device_name`
+/// through the legacy `ChangeDisplaySettingsEx`, which sometimes works where the CCD API
+/// doesn't, e.g. in Remote Desktop sessions or with drivers rejecting supplied configurations.
+/// With `persist` the mode is also saved to the registry.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::ChangeDisplaySettings`] with the `DISP_CHANGE_*` code on failure.
+pub fn change_display_settings(
+    device_name: &str,
+    width: u32,
+    height: u32,
+    refresh: Option<u32>,
+    persist: bool,
+) -> Result<()> {
+    let device_name: Vec<u16> = device_name.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut devmode = DEVMODEW {
+        dmSize: u16::try_from(size_of::<DEVMODEW>())?,
+        dmFields: DM_PELSWIDTH | DM_PELSHEIGHT,
+        dmPelsWidth: width,
+        dmPelsHeight: height,
+        ..DEVMODEW::default()
+    };
+    if let Some(refresh) = refresh {
+        devmode.dmFields |= DM_DISPLAYFREQUENCY;
+        devmode.dmDisplayFrequency = refresh;
+    }
+    // SAFETY: the name is NUL-terminated, the devmode is fully initialized and both outlive
+    // the call.
+    let result = unsafe {
+        ChangeDisplaySettingsExW(
+            PCWSTR(device_name.as_ptr()),
+            Some(&raw const devmode),
+            None,
+            if persist { CDS_UPDATEREGISTRY } else { CDS_TYPE::default() },
+            None,
+        )
+    };
+    if result != DISP_CHANGE_SUCCESSFUL {
+        return Err(DisplayError::ChangeDisplaySettings(result.0));
+    }
+    Ok(())
+}
+
+/// GDI device name of the monitor the mouse cursor is on.
+///
+/// # Errors
+///
+/// Returns an error if the cursor position or the monitor info can't be queried.
+pub fn cursor_monitor_device() -> Result<String> {
+    let mut point = POINT::default();
+    // SAFETY: point is a valid out-pointer.
+    let monitor = per_monitor_aware(|| unsafe {
+        GetCursorPos(&raw mut point)?;
+        Ok::<_, DisplayError>(MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST))
+    })?;
+    monitor_device_name(monitor)
+}
+
+/// GDI device name of the monitor showing most of the foreground window.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NoForegroundWindow`] if no window has focus, or an error if the
+/// monitor info can't be queried.
+pub fn foreground_monitor_device() -> Result<String> {
+    // No coordinates involved, so the awareness doesn't matter here.
+    // SAFETY: plain calls without pointers; the window handle is checked before use.
+    let monitor = unsafe {
+        let window = GetForegroundWindow();
+        if window.is_invalid() {
+            return Err(DisplayError::NoForegroundWindow);
+        }
+        MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST)
+    };
+    monitor_device_name(monitor)
+}
+
+/// GDI device name of the monitor showing this process's console window, or the foreground
+/// window if the console has no visible window of its own (in Windows Terminal, say).
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NoForegroundWindow`] if neither window is there, or an error if the
+/// monitor info can't be queried.
+pub fn console_monitor_device() -> Result<String> {
+    // SAFETY: plain calls without pointers; the window handle is checked before use.
+    let console = unsafe {
+        let window = GetConsoleWindow();
+        (!window.is_invalid() && IsWindowVisible(window).as_bool()).then(|| MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST))
+    };
+    console.map_or_else(foreground_monitor_device, monitor_device_name)
+}
+
+/// GDI device name of the monitor showing the desktop point (`x`, `y`) in physical pixels, as
+/// the window manager maps points to an `HMONITOR`.
+///
+/// # Errors
+///
+/// Returns an error if the monitor info can't be queried.
+pub fn monitor_device_at(x: i32, y: i32) -> Result<String> {
+    // SAFETY: a plain call without pointers.
+    let monitor = per_monitor_aware(|| unsafe { MonitorFromPoint(POINT { x, y }, MONITOR_DEFAULTTONEAREST) });
+    monitor_device_name(monitor)
+}
+
+fn monitor_device_name(monitor: HMONITOR) -> Result<String> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = u32::try_from(size_of::<MONITORINFOEXW>())?;
+    // SAFETY: MONITORINFOEXW starts with MONITORINFO and cbSize announces the extended size.
+    if !unsafe { GetMonitorInfoW(monitor, &raw mut info.monitorInfo) }.as_bool() {
+        return Err(windows::core::Error::from_thread().into());
+    }
+    Ok(utf16_to_string(&info.szDevice))
+}
+
+/// The visible, non-minimized top-level windows with a title, with the process that owns each,
+/// its DPI awareness and the GDI device name of the monitor showing most of it.
+///
+/// # Errors
+///
+/// Returns an error if the windows can't be enumerated. A process that can't be opened (one
+/// that is protected, say) gets no name rather than failing the enumeration.
+pub fn top_level_windows() -> Result<Vec<TopLevelWindow>> {
+    let mut handles: Vec<HWND> = Vec::new();
+    // SAFETY: the callback only runs during EnumWindows, while `handles` is borrowed mutably.
+    unsafe { EnumWindows(Some(collect_window), LPARAM(std::ptr::from_mut(&mut handles) as isize))? };
+    let mut windows = Vec::new();
+    for hwnd in handles {
+        // SAFETY: the handle came from EnumWindows; calls on a window that has since been closed
+        // fail or return nothing instead of touching freed memory.
+        let (title, process_id, context) = unsafe {
+            if !IsWindowVisible(hwnd).as_bool() || IsIconic(hwnd).as_bool() {
+                continue;
+            }
+            let mut title = [0u16; 256];
+            let len = usize::try_from(GetWindowTextW(hwnd, &mut title)).unwrap_or_default();
+            let mut process_id = 0;
+            GetWindowThreadProcessId(hwnd, Some(&raw mut process_id));
+            (String::from_utf16_lossy(&title[..len]), process_id, GetWindowDpiAwarenessContext(hwnd))
+        };
+        if title.trim().is_empty() {
+            continue;
+        }
+        // SAFETY: plain call on a window handle; a stale handle yields the nearest monitor.
+        let Ok(monitor_device) = monitor_device_name(unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) }) else {
+            continue;
+        };
+        windows.push(TopLevelWindow {
+            title,
+            process_id,
+            process_name: process_name(process_id),
+            monitor_device,
+            awareness: dpi_awareness(context),
+        });
+    }
+    Ok(windows)
+}
+
+extern "system" fn collect_window(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    // SAFETY: lparam is the `&mut Vec<HWND>` passed by `top_level_windows`.
+    unsafe { &mut *(lparam.0 as *mut Vec<HWND>) }.push(hwnd);
+    true.into()
+}
+
+fn dpi_awareness(context: DPI_AWARENESS_CONTEXT) -> DpiAwareness {
+    // SAFETY: plain calls on a context value; an invalid one compares unequal and reports
+    // DPI_AWARENESS_INVALID.
+    unsafe {
+        if AreDpiAwarenessContextsEqual(context, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2).as_bool() {
+            return DpiAwareness::PerMonitorV2;
+        }
+        if AreDpiAwarenessContextsEqual(context, DPI_AWARENESS_CONTEXT_UNAWARE_GDISCALED).as_bool() {
+            return DpiAwareness::UnawareGdiScaled;
+        }
+        match GetAwarenessFromDpiAwarenessContext(context) {
+            DPI_AWARENESS_UNAWARE => DpiAwareness::Unaware,
+            DPI_AWARENESS_SYSTEM_AWARE => DpiAwareness::System,
+            DPI_AWARENESS_PER_MONITOR_AWARE => DpiAwareness::PerMonitor,
+            _ => DpiAwareness::Unknown,
+        }
+    }
+}
+
+/// The visible, non-minimized top-level windows with a title that aren't tool windows, with
+/// their rect in physical pixels.
+///
+/// # Errors
+///
+/// Returns an error if the windows can't be enumerated.
+pub fn window_frames() -> Result<Vec<WindowFrame>> {
+    let mut handles: Vec<HWND> = Vec::new();
+    // SAFETY: the callback only runs during EnumWindows, while `handles` is borrowed mutably.
+    unsafe { EnumWindows(Some(collect_window), LPARAM(std::ptr::from_mut(&mut handles) as isize))? };
+    let mut frames = Vec::new();
+    per_monitor_aware(|| {
+        for hwnd in handles {
+            let mut rect = RECT::default();
+            // SAFETY: the handle came from EnumWindows; calls on a window that has since been
+            // closed fail or return nothing instead of touching freed memory.
+            let title = unsafe {
+                let style = WINDOW_EX_STYLE(GetWindowLongW(hwnd, GWL_EXSTYLE).cast_unsigned());
+                if !IsWindowVisible(hwnd).as_bool() || IsIconic(hwnd).as_bool() || style.contains(WS_EX_TOOLWINDOW) {
+                    continue;
+                }
+                if GetWindowRect(hwnd, &raw mut rect).is_err() {
+                    continue;
+                }
+                let mut title = [0u16; 256];
+                let len = usize::try_from(GetWindowTextW(hwnd, &mut title)).unwrap_or_default();
+                String::from_utf16_lossy(&title[..len])
+            };
+            let rect = Rect {
+                x: rect.left,
+                y: rect.top,
+                width: rect.right.abs_diff(rect.left),
+                height: rect.bottom.abs_diff(rect.top),
+            };
+            if title.trim().is_empty() || rect.area() == 0 {
+                continue;
+            }
+            let mut process_id = 0;
+            // SAFETY: as above.
+            unsafe { GetWindowThreadProcessId(hwnd, Some(&raw mut process_id)) };
+            frames.push(WindowFrame { handle: hwnd.0 as isize, title, process_id, rect });
+        }
+    });
+    Ok(frames)
+}
+
+/// The GDI device name and work area (the monitor without the taskbar and docked toolbars) of
+/// every monitor, in physical pixels.
+///
+/// # Errors
+///
+/// Returns an error if the monitors can't be enumerated or queried.
+pub fn monitor_work_areas() -> Result<Vec<(String, Rect)>> {
+    per_monitor_aware(|| {
+        let mut monitors: Vec<HMONITOR> = Vec::new();
+        // SAFETY: the callback only runs during EnumDisplayMonitors, while `monitors` is
+        // borrowed mutably.
+        unsafe { EnumDisplayMonitors(None, None, Some(collect_monitor), LPARAM(std::ptr::from_mut(&mut monitors) as isize)) }.ok()?;
+        monitors
+            .into_iter()
+            .map(|monitor| {
+                let mut info = MONITORINFOEXW::default();
+                info.monitorInfo.cbSize = u32::try_from(size_of::<MONITORINFOEXW>())?;
+                // SAFETY: MONITORINFOEXW starts with MONITORINFO and cbSize announces the extended size.
+                if !unsafe { GetMonitorInfoW(monitor, &raw mut info.monitorInfo) }.as_bool() {
+                    return Err(windows::core::Error::from_thread().into());
+                }
+                let work = info.monitorInfo.rcWork;
+                let area = Rect {
+                    x: work.left,
+                    y: work.top,
+                    width: work.right.abs_diff(work.left),
+                    height: work.bottom.abs_diff(work.top),
+                };
+                Ok((utf16_to_string(&info.szDevice), area))
+            })
+            .collect()
+    })
+}
+
+/// Moves and resizes the window `handle` (an `HWND`) to `rect` in physical pixels, without
+/// activating it or changing its z-order.
+///
+/// # Errors
+///
+/// Returns an error if the window is gone or refuses, e.g. one of an elevated process.
+pub fn move_window(handle: isize, rect: &Rect) -> Result<()> {
+    let (width, height) = (i32::try_from(rect.width)?, i32::try_from(rect.height)?);
+    // SAFETY: a plain call on a window handle; a stale one fails.
+    per_monitor_aware(|| unsafe {
+        SetWindowPos(HWND(handle as *mut c_void), None, rect.x, rect.y, width, height, SWP_NOZORDER | SWP_NOACTIVATE)
+    })?;
+    Ok(())
+}
+
+extern "system" fn collect_monitor(monitor: HMONITOR, _: HDC, _: *mut RECT, lparam: LPARAM) -> BOOL {
+    // SAFETY: lparam is the `&mut Vec<HMONITOR>` passed by `monitor_work_areas`.
+    unsafe { &mut *(lparam.0 as *mut Vec<HMONITOR>) }.push(monitor);
+    true.into()
+}
+
+/// Makes the process per-monitor v2 DPI aware, so the window manager hands out physical pixels
+/// in every thread. The binary calls it first thing; library users decide for their process.
+/// Windows only takes the first setting, so if the manifest or an earlier call set one already
+/// this succeeds without changing it; [`thread_dpi_awareness`] tells which one is in effect.
+///
+/// # Errors
+///
+/// Returns an error if Windows refuses the setting, e.g. before Windows 10 1703.
+pub fn set_process_per_monitor_aware() -> Result<()> {
+    // SAFETY: a plain call without pointers.
+    match unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) } {
+        Err(err) if err.code() == ERROR_ACCESS_DENIED.to_hresult() => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// The DPI awareness the calling thread runs with, the process's unless the thread changed it.
+#[must_use]
+pub fn thread_dpi_awareness() -> DpiAwareness {
+    // SAFETY: a plain call without pointers.
+    dpi_awareness(unsafe { GetThreadDpiAwarenessContext() })
+}
+
+/// Runs `f` as a per-monitor DPI aware thread, so window and monitor coordinates are physical
+/// pixels whatever the process's awareness.
+fn per_monitor_aware<T>(f: impl FnOnce() -> T) -> T {
+    // SAFETY: plain calls; the thread's previous awareness is restored after `f`.
+    let previous = unsafe { SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+    let result = f();
+    if !previous.is_invalid() {
+        // SAFETY: as above.
+        unsafe { SetThreadDpiAwarenessContext(previous) };
+    }
+    result
+}
+
+/// File name of the process's executable, `notepad.exe`.
+fn process_name(process_id: u32) -> Option<String> {
+    // SAFETY: the handle is closed before returning; the buffer length is passed in `size`.
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+        let mut buffer = [0u16; 1024];
+        let mut size = u32::try_from(buffer.len()).ok()?;
+        let result = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(buffer.as_mut_ptr()), &raw mut size);
+        let _ = CloseHandle(process);
+        result.ok()?;
+        let path = String::from_utf16_lossy(&buffer[..usize::try_from(size).ok()?]);
+        Some(path.rsplit('\\').next().unwrap_or(&path).to_string())
+    }
+}
+
+/// `CLASS_MONITOR` from `icm.h` (`'mntr'`), the device class of displays for the WCS functions.
+const CLASS_MONITOR: u32 = 0x6D6E_7472;
+
+/// The device id color management knows the monitor by (`MONITOR\DEL40B6\{...}\0001`), found
+/// among the monitors of GDI device `gdi_device_name` by its interface path as in
+/// `DisplayInfo::device_path`. `None` if no monitor has that path.
+#[must_use]
+pub fn color_device_id(gdi_device_name: &str, device_path: &str) -> Option<String> {
+    let adapter: Vec<u16> = gdi_device_name.encode_utf16().chain(std::iter::once(0)).collect();
+    let size = u32::try_from(size_of::<DISPLAY_DEVICEW>()).ok()?;
+    for index in 0.. {
+        let mut interface = DISPLAY_DEVICEW { cb: size, ..DISPLAY_DEVICEW::default() };
+        let mut device = DISPLAY_DEVICEW { cb: size, ..DISPLAY_DEVICEW::default() };
+        // SAFETY: the name is NUL-terminated and outlives the calls; cb is set.
+        unsafe {
+            if !EnumDisplayDevicesW(PCWSTR(adapter.as_ptr()), index, &raw mut interface, EDD_GET_DEVICE_INTERFACE_NAME).as_bool() {
+                return None;
+            }
+            if !utf16_to_string(&interface.DeviceID).eq_ignore_ascii_case(device_path) {
+                continue;
+            }
+            // The same monitor without the flag gives its device instance id.
+            return EnumDisplayDevicesW(PCWSTR(adapter.as_ptr()), index, &raw mut device, 0)
+                .as_bool()
+                .then(|| utf16_to_string(&device.DeviceID));
+        }
+    }
+    None
+}
+
+/// Whether the current user has their own color profile associations for monitor `device_id`
+/// (see [`color_device_id`]) instead of the system-wide ones.
+///
+/// # Errors
+///
+/// Returns an error if color management can't be queried.
+pub fn uses_per_user_profiles(device_id: &str) -> Result<bool> {
+    let mut per_user = BOOL::default();
+    // SAFETY: the out-pointer is valid; the name is converted for the call.
+    unsafe { WcsGetUsePerUserProfiles(&HSTRING::from(device_id), CLASS_MONITOR, &raw mut per_user) }.ok()?;
+    Ok(per_user.as_bool())
+}
+
+/// Turns the current user's own color profile associations for monitor `device_id` on or off.
+///
+/// # Errors
+///
+/// Returns an error if color management refuses the change.
+pub fn set_per_user_profiles(device_id: &str, per_user: bool) -> Result<()> {
+    // SAFETY: the name is converted for the call.
+    unsafe { WcsSetUsePerUserProfiles(&HSTRING::from(device_id), CLASS_MONITOR, per_user) }.ok()?;
+    Ok(())
+}
+
+/// File name of the default ICC profile of monitor `device_id` in the user's (`per_user`) or
+/// the system-wide associations, `None` if there is none. `advanced_color` asks for the
+/// profile Windows 11 uses while HDR or Auto Color Management is on.
+///
+/// # Errors
+///
+/// Returns an error if color management can't be queried.
+pub fn default_color_profile(device_id: &str, per_user: bool, advanced_color: bool) -> Result<Option<String>> {
+    let scope = if per_user { WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER } else { WCS_PROFILE_MANAGEMENT_SCOPE_SYSTEM_WIDE };
+    let subtype = if advanced_color { CPST_EXTENDED_DISPLAY_COLOR_MODE } else { CPST_NONE };
+    let name = HSTRING::from(device_id);
+    let mut size = 0;
+    // SAFETY: the out-pointer is valid and the buffer holds the `size` bytes announced.
+    unsafe {
+        if !WcsGetDefaultColorProfileSize(scope, &name, CPT_ICC, subtype, 0, &raw mut size).as_bool() {
+            return match windows::core::Error::from_thread() {
+                err if is_not_found(&err) => Ok(None),
+                err => Err(err.into()),
+            };
+        }
+        let mut buffer = vec![0u16; usize::try_from(size)?.div_ceil(2)];
+        WcsGetDefaultColorProfile(scope, &name, CPT_ICC, subtype, 0, size, PWSTR(buffer.as_mut_ptr())).ok()?;
+        Ok(Some(utf16_to_string(&buffer)).filter(|name| !name.is_empty()))
+    }
+}
+
+/// The folder installed color profiles live in, `C:\Windows\System32\spool\drivers\color`.
+///
+/// # Errors
+///
+/// Returns an error if color management can't be queried.
+pub fn color_directory() -> Result<PathBuf> {
+    let mut buffer = [0u16; 260];
+    let mut size = u32::try_from(size_of_val(&buffer))?;
+    // SAFETY: `size` announces the buffer's length in bytes.
+    unsafe { GetColorDirectoryW(PCWSTR::null(), Some(PWSTR(buffer.as_mut_ptr())), &raw mut size) }.ok()?;
+    Ok(PathBuf::from(utf16_to_string(&buffer)))
+}
+
+/// No association exists; which of the two codes comes back depends on the Windows version.
+fn is_not_found(err: &windows::core::Error) -> bool {
+    err.code() == ERROR_FILE_NOT_FOUND.to_hresult() || err.code() == ERROR_NOT_FOUND.to_hresult()
+}
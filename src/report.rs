@@ -0,0 +1,132 @@
+//! The JSON forms of errors and results, shared by `--format json`, `--json-errors`, the daemon
+//! protocol and anything else printing them for programs.
+//!
+//! The field names and error codes are a stable interface: fields may be added, but not renamed
+//! or removed without bumping [`REPORT_VERSION`]. The tests compare them against checked-in
+//! documents.
+
+use crate::display::{ApplyReport, DisplayError};
+use crate::plan::ChangeSet;
+use crate::progress::DisplayProgress;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+/// Version of the [`CommandReport`] format, bumped on incompatible changes.
+pub const REPORT_VERSION: u32 = 1;
+
+/// The `code` of errors from outside the crate, which have no [`DisplayError::kind`].
+pub const OTHER_ERROR: &str = "other";
+
+/// The `code` of a command line the CLI can't parse.
+pub const USAGE_ERROR: &str = "invalid_arguments";
+
+/// An error as programs get it: a stable code to act on and the message for humans.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// [`DisplayError::kind`], or [`OTHER_ERROR`].
+    pub code: String,
+    pub message: String,
+    /// The Win32 error code or `HRESULT` behind it, see [`DisplayError::code`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_error: Option<i32>,
+    /// What the error is about, e.g. `source_id` and `scaling`, see [`DisplayError::details`].
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub details: Map<String, Value>,
+}
+
+impl ErrorReport {
+    /// An error without a [`DisplayError`] behind it, e.g. a command line mistake.
+    #[must_use]
+    pub fn other(message: impl Into<String>) -> Self {
+        Self { code: OTHER_ERROR.to_string(), message: message.into(), os_error: None, details: Map::new() }
+    }
+}
+
+impl From<&DisplayError> for ErrorReport {
+    fn from(err: &DisplayError) -> Self {
+        Self { code: err.kind().to_string(), message: err.to_string(), os_error: err.code(), details: err.details() }
+    }
+}
+
+/// Serializes as its [`ErrorReport`]. There's no way back, since the sources of errors from
+/// Windows and `std` can't be rebuilt; deserialize an [`ErrorReport`] instead.
+impl Serialize for DisplayError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorReport::from(self).serialize(serializer)
+    }
+}
+
+/// What `set` and `profile apply` print with `--format json`: a single document on stdout,
+/// whether the command succeeded or not.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandReport {
+    /// [`REPORT_VERSION`].
+    pub version: u32,
+    pub ok: bool,
+    /// The exit code of the process: 0 on success, 1 on errors, 2 if Windows adjusted the
+    /// change, 3 if it timed out and 4 without displays.
+    pub exit_code: u8,
+    /// What each display asked to change ended up with.
+    #[serde(default)]
+    pub reports: Vec<ApplyReport>,
+    /// The changes planned, for a dry run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<ChangeSet>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorReport>,
+    /// How far each display got, for a `profile apply` that failed or was cancelled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub progress: Vec<DisplayProgress>,
+}
+
+impl Default for CommandReport {
+    fn default() -> Self {
+        Self { version: REPORT_VERSION, ok: true, exit_code: 0, reports: Vec::new(), changes: None, error: None, progress: Vec::new() }
+    }
+}
+
+impl CommandReport {
+    /// Records how the command ended; an `error` makes the report not `ok`.
+    pub fn finish(&mut self, exit_code: u8, error: Option<ErrorReport>) {
+        self.ok = error.is_none();
+        self.exit_code = exit_code;
+        self.error = error;
+    }
+}
+
+/// What any command prints with `--json-errors`, a single document on stdout however it ends.
+///
+/// On success `ok` is set and `result` holds what the command prints as JSON otherwise, `null`
+/// for a command without JSON output; a command that ran but found something amiss, like
+/// `check`, still has `ok` set and tells by its `exit_code`. On failure the fields of the
+/// [`ErrorReport`] are inlined: `{"ok":false,"code":"unsupported_scaling","message":...}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outcome {
+    /// [`REPORT_VERSION`].
+    pub version: u32,
+    pub ok: bool,
+    /// As in [`CommandReport::exit_code`].
+    pub exit_code: u8,
+    /// `Some(Value::Null)` on success without output, so it reads back as it was written.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "present")]
+    pub result: Option<Value>,
+    #[serde(flatten)]
+    pub error: Option<ErrorReport>,
+}
+
+impl Outcome {
+    #[must_use]
+    pub fn success(exit_code: u8, result: Option<Value>) -> Self {
+        Self { version: REPORT_VERSION, ok: true, exit_code, result: Some(result.unwrap_or(Value::Null)), error: None }
+    }
+
+    #[must_use]
+    pub fn failure(exit_code: u8, error: ErrorReport) -> Self {
+        Self { version: REPORT_VERSION, ok: false, exit_code, result: None, error: Some(error) }
+    }
+}
+
+/// A field that's there, even as `null`, for `#[serde(deserialize_with)]`.
+fn present<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
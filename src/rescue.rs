@@ -0,0 +1,89 @@
+//! Moves windows that ended up off-screen back onto a monitor, for `--rescue-windows`.
+//!
+//! Windows doesn't always bring back windows that were on a display that went away or moved,
+//! e.g. after changing the primary display. All rects are in physical pixels of the virtual
+//! desktop.
+
+use crate::display::Result;
+use crate::layout::Rect;
+use crate::raw;
+use serde::Serialize;
+use std::fmt;
+use tracing::{info, warn};
+
+/// A visible, non-minimized top-level window that isn't a tool window, as returned by
+/// [`raw::window_frames`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowFrame {
+    /// The window's `HWND`.
+    pub handle: isize,
+    pub title: String,
+    pub process_id: u32,
+    pub rect: Rect,
+}
+
+/// A window [`rescue_offscreen_windows`] moved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RescuedWindow {
+    pub title: String,
+    pub process_id: u32,
+    pub from: Rect,
+    pub to: Rect,
+}
+
+impl fmt::Display for RescuedWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Moved \"{}\" from {} to {}", self.title, self.from, self.to)
+    }
+}
+
+/// Where to put a window at `rect` so it's on screen: `None` if it overlaps one of
+/// `work_areas` already, or there is none. Otherwise it goes to the nearest work area, as close
+/// to where it was as possible, keeping its size where it fits and shrunk to the work area
+/// where it doesn't.
+#[must_use]
+pub fn rescue_rect(rect: &Rect, work_areas: &[Rect]) -> Option<Rect> {
+    if work_areas.iter().any(|area| area.overlaps(rect)) {
+        return None;
+    }
+    let area = work_areas.iter().min_by_key(|area| distance(rect, area))?;
+    let (width, height) = (rect.width.min(area.width), rect.height.min(area.height));
+    Some(Rect {
+        x: rect.x.clamp(area.x, area.right().saturating_sub_unsigned(width)),
+        y: rect.y.clamp(area.y, area.bottom().saturating_sub_unsigned(height)),
+        width,
+        height,
+    })
+}
+
+/// Squared length of the gap between two rects that don't overlap.
+fn distance(a: &Rect, b: &Rect) -> u64 {
+    let dx = u64::from(b.x.saturating_sub(a.right()).max(a.x.saturating_sub(b.right())).max(0).unsigned_abs());
+    let dy = u64::from(b.y.saturating_sub(a.bottom()).max(a.y.saturating_sub(b.bottom())).max(0).unsigned_abs());
+    dx * dx + dy * dy
+}
+
+/// Moves every window that doesn't overlap any monitor's work area onto the nearest one, see
+/// [`rescue_rect`]. Works after changes made elsewhere too. A window that refuses to move (one
+/// of an elevated process, say) is logged and skipped.
+///
+/// # Errors
+///
+/// Returns an error if the windows or the monitors can't be enumerated.
+pub fn rescue_offscreen_windows() -> Result<Vec<RescuedWindow>> {
+    let work_areas: Vec<Rect> = raw::monitor_work_areas()?.into_iter().map(|(_, area)| area).collect();
+    let mut rescued = Vec::new();
+    for window in raw::window_frames()? {
+        let Some(to) = rescue_rect(&window.rect, &work_areas) else {
+            continue;
+        };
+        if let Err(err) = raw::move_window(window.handle, &to) {
+            warn!(title = %window.title, %err, "Off-screen window not moved");
+            continue;
+        }
+        let moved = RescuedWindow { title: window.title, process_id: window.process_id, from: window.rect, to };
+        info!("{moved}");
+        rescued.push(moved);
+    }
+    Ok(rescued)
+}
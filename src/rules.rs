@@ -0,0 +1,349 @@
+use crate::display::{apply_display_config, apply_source_modes, enumerate_displays, Connector, DisplayConfig, DisplayError, DisplayInfo, Result, Rotation, SourceMode, DPI_VALUES};
+use crate::layout::{find_overlap, fit_desktop, place, reflow, Align, Placement, Rect};
+use crate::schedule::{ScheduledAction, TimeOfDay};
+use crate::selector::DisplayFilter;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// Rules loaded from a TOML file, one `[[rule]]` table per rule and optionally
+/// `[[schedule]]` entries, see [`ScheduledAction`].
+///
+/// ```toml
+/// [[rule]]
+/// name = "Dell on the right"
+/// match = { vendor = "DEL", name = "U2720Q" }
+/// apply = { width = 3840, height = 2160, scaling = 150, right_of = "internal" }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub schedule: Vec<ScheduledAction>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(default)]
+    pub name: String,
+    /// Displays the rule applies to.
+    #[serde(default, rename = "match")]
+    pub criteria: DisplayFilter,
+    #[serde(default)]
+    pub apply: RuleAction,
+    /// `Some(false)` leaves resolution and rotation alone, as those blank the screen for a
+    /// moment (see [`Impact`](crate::plan::Impact)); the other settings still apply.
+    #[serde(default)]
+    pub allow_mode_switch: Option<bool>,
+}
+
+/// Settings a rule enforces; unset fields are left alone.
+///
+/// The placement fields take a reference display: `internal`, `primary`, a source id or a
+/// friendly name substring.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleAction {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    #[serde(deserialize_with = "crate::display::deserialize_optional_scaling")]
+    pub scaling: Option<i32>,
+    pub rotation: Option<Rotation>,
+    pub left_of: Option<String>,
+    pub right_of: Option<String>,
+    pub above: Option<String>,
+    pub below: Option<String>,
+    pub align: Align,
+    pub primary: bool,
+}
+
+impl RuleAction {
+    fn placement(&self) -> Option<(Placement, &str)> {
+        [
+            (Placement::LeftOf, &self.left_of),
+            (Placement::RightOf, &self.right_of),
+            (Placement::Above, &self.above),
+            (Placement::Below, &self.below),
+        ]
+        .into_iter()
+        .find_map(|(placement, reference)| reference.as_deref().map(|r| (placement, r)))
+    }
+
+    /// `self` with every setting `later` has replaced by it. A placement replaces the
+    /// placement as a whole, and `primary` can only be turned on.
+    #[must_use]
+    pub fn overridden_by(&self, later: &RuleAction) -> RuleAction {
+        let placed = if later.placement().is_some() { later } else { self };
+        RuleAction {
+            width: later.width.or(self.width),
+            height: later.height.or(self.height),
+            scaling: later.scaling.or(self.scaling),
+            rotation: later.rotation.or(self.rotation),
+            left_of: placed.left_of.clone(),
+            right_of: placed.right_of.clone(),
+            above: placed.above.clone(),
+            below: placed.below.clone(),
+            align: placed.align,
+            primary: self.primary || later.primary,
+        }
+    }
+}
+
+/// Lists the settings that are set, e.g. `scaling=150 right_of=internal`.
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(width) = self.width {
+            parts.push(format!("width={width}"));
+        }
+        if let Some(height) = self.height {
+            parts.push(format!("height={height}"));
+        }
+        if let Some(scaling) = self.scaling {
+            parts.push(format!("scaling={scaling}"));
+        }
+        if let Some(rotation) = self.rotation {
+            parts.push(format!("rotation={rotation}"));
+        }
+        for (key, reference) in [
+            ("left_of", &self.left_of),
+            ("right_of", &self.right_of),
+            ("above", &self.above),
+            ("below", &self.below),
+        ] {
+            if let Some(reference) = reference {
+                parts.push(format!("{key}={reference}"));
+            }
+        }
+        if self.primary {
+            parts.push("primary".to_string());
+        }
+        if parts.is_empty() {
+            f.write_str("nothing")
+        } else {
+            f.write_str(&parts.join(" "))
+        }
+    }
+}
+
+impl RuleSet {
+    /// # Errors
+    ///
+    /// Returns an error if `text` isn't a valid rules file.
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or isn't a valid rules file.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Pairs each display with the first rule matching it; displays no rule matches are left out.
+    #[must_use]
+    pub fn resolve<'a>(&'a self, displays: &'a [DisplayInfo]) -> Vec<(&'a Rule, &'a DisplayInfo)> {
+        displays
+            .iter()
+            .filter_map(|display| {
+                self.rules
+                    .iter()
+                    .find(|rule| rule.criteria.matches(display))
+                    .map(|rule| (rule, display))
+            })
+            .collect()
+    }
+}
+
+/// Changes needed to make the displays comply with a rule set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RulePlan {
+    /// Full layout to apply in one call, empty if resolutions, rotations and positions comply.
+    pub sources: Vec<SourceMode>,
+    /// Source ids with the scaling they need.
+    pub scaling: Vec<(u32, i32)>,
+    /// Source ids whose resolution or rotation a rule asks to change but doesn't allow a mode
+    /// switch for, so they keep it.
+    pub held_back: Vec<u32>,
+}
+
+impl RulePlan {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty() && self.scaling.is_empty()
+    }
+}
+
+fn find_reference<'a>(reference: &str, displays: &'a [DisplayInfo]) -> Option<&'a DisplayInfo> {
+    match reference {
+        "internal" => displays.iter().find(|d| d.connector == Connector::Internal),
+        "primary" => displays.iter().find(|d| d.primary),
+        _ => reference.parse::<u32>().map_or_else(
+            |_| {
+                let reference = reference.to_lowercase();
+                displays
+                    .iter()
+                    .find(|d| d.friendly_name.to_lowercase().contains(&reference))
+            },
+            |id| displays.iter().find(|d| d.source_id == id),
+        ),
+    }
+}
+
+/// Works out what applying `rules` to `displays` would change. Already compliant displays
+/// produce no changes, so applying the plan repeatedly is idempotent. Displays next to one
+/// whose resolution changes move along with its edges (see [`reflow`]) unless a rule places
+/// them.
+///
+/// # Errors
+///
+/// Returns an error if a rule refers to an unknown display, asks for a scaling value the
+/// display doesn't support, or the resulting layout overlaps or doesn't fit the virtual desktop.
+pub fn plan_rules(rules: &RuleSet, displays: &[DisplayInfo]) -> Result<RulePlan> {
+    let assignments = rules.resolve(displays);
+    let rule_for = |source_id: u32| {
+        assignments
+            .iter()
+            .find(|(_, d)| d.source_id == source_id)
+            .map(|(rule, _)| rule)
+    };
+
+    let mut sources: Vec<SourceMode> = displays.iter().map(SourceMode::of).collect();
+    let mut held_back = Vec::new();
+    for source in &mut sources {
+        if let Some(rule) = rule_for(source.source_id) {
+            let action = &rule.apply;
+            let wanted = (
+                action.width.unwrap_or(source.width),
+                action.height.unwrap_or(source.height),
+                action.rotation.unwrap_or(source.rotation),
+            );
+            if wanted == (source.width, source.height, source.rotation) {
+                continue;
+            }
+            if rule.allow_mode_switch == Some(false) {
+                held_back.push(source.source_id);
+            } else {
+                (source.width, source.height, source.rotation) = wanted;
+            }
+        }
+    }
+
+    // Displays next to a resized one follow its edges, see `reflow`; placements come after.
+    let before: Vec<(u32, Rect)> = displays.iter().map(|d| (d.source_id, Rect::of(d))).collect();
+    let sizes: Vec<(u32, (u32, u32))> = sources.iter().map(|s| (s.source_id, (s.width, s.height))).collect();
+    for (source, (_, rect)) in sources.iter_mut().zip(reflow(&before, &sizes)) {
+        (source.x, source.y) = (rect.x, rect.y);
+    }
+
+    let rect_of = |s: &SourceMode| Rect { x: s.x, y: s.y, width: s.width, height: s.height };
+    for (rule, display) in &assignments {
+        let Some((placement, reference)) = rule.apply.placement() else {
+            continue;
+        };
+        let reference_id = find_reference(reference, displays)
+            .filter(|r| r.source_id != display.source_id)
+            .ok_or_else(|| DisplayError::UnknownReference {
+                rule: rule.name.clone(),
+                reference: reference.to_string(),
+            })?
+            .source_id;
+        let reference_rect = sources
+            .iter()
+            .find(|s| s.source_id == reference_id)
+            .map(rect_of)
+            .ok_or(DisplayError::DisplayNotFound(reference_id))?;
+        if let Some(source) = sources.iter_mut().find(|s| s.source_id == display.source_id) {
+            let placed = place(rect_of(source), reference_rect, placement, rule.apply.align);
+            source.x = placed.x;
+            source.y = placed.y;
+        }
+    }
+
+    let mut layout: Vec<(u32, Rect)> = sources.iter().map(|s| (s.source_id, rect_of(s))).collect();
+    if let Some((a, b)) = find_overlap(&layout) {
+        return Err(DisplayError::Overlap(a, b));
+    }
+    let primary = assignments
+        .iter()
+        .find(|(rule, _)| rule.apply.primary)
+        .map(|(_, d)| d.source_id)
+        .or_else(|| displays.iter().find(|d| d.primary).map(|d| d.source_id));
+    if let Some(primary) = primary {
+        fit_desktop(&mut layout, primary)?;
+    }
+    for (source, (_, rect)) in sources.iter_mut().zip(&layout) {
+        source.x = rect.x;
+        source.y = rect.y;
+    }
+
+    let layout_changed = displays
+        .iter()
+        .zip(&sources)
+        .any(|(d, s)| SourceMode::of(d) != *s);
+
+    let mut scaling = Vec::new();
+    for (rule, display) in &assignments {
+        let Some(target) = rule.apply.scaling else {
+            continue;
+        };
+        if !DPI_VALUES.contains(&target) || target > display.scaling_max {
+            return Err(DisplayError::UnsupportedScaling {
+                source_id: display.source_id,
+                scaling: target,
+            });
+        }
+        if target != display.scaling_current {
+            scaling.push((display.source_id, target));
+        }
+    }
+
+    Ok(RulePlan {
+        sources: if layout_changed { sources } else { Vec::new() },
+        scaling,
+        held_back,
+    })
+}
+
+/// Enumerates the displays and applies whatever `rules`, with the schedule in effect now,
+/// require.
+///
+/// Returns whether anything was changed.
+///
+/// # Errors
+///
+/// Returns an error if planning fails or a change can't be applied.
+pub fn apply_rules(rules: &RuleSet) -> Result<bool> {
+    let displays = enumerate_displays()?;
+    let plan = plan_rules(&rules.at(TimeOfDay::now(), &displays), &displays)?;
+    for &source_id in &plan.held_back {
+        info!(source_id, "Resolution and rotation kept, the rule doesn't allow a mode switch");
+    }
+    if plan.is_empty() {
+        return Ok(false);
+    }
+
+    if !plan.sources.is_empty() {
+        apply_source_modes(&plan.sources)?;
+    }
+    if !plan.scaling.is_empty() {
+        let current = enumerate_displays()?;
+        for (source_id, scaling) in &plan.scaling {
+            let display = current
+                .iter()
+                .find(|d| d.source_id == *source_id)
+                .ok_or(DisplayError::DisplayNotFound(*source_id))?;
+            let config = DisplayConfig::builder().scaling(*scaling).resolve(display);
+            apply_display_config(display, &config)?;
+        }
+    }
+
+    info!("Rules applied");
+    Ok(true)
+}
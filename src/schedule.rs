@@ -0,0 +1,355 @@
+//! Time-of-day actions for `watch`, e.g. a lower resolution in the evening and the usual one
+//! back in the morning.
+//!
+//! An entry stays in effect from its time until the next entry for the same display, so the
+//! settings that should hold right now can be worked out at any moment. That keeps the watch
+//! loop correct across sleep and clock changes: it only has to recompute, never catch up.
+//!
+//! The one exception is an entry firing while none of its displays is connected: its `on_missing`
+//! decides whether a display connecting later still gets it, which the [`ActionQueue`] tracks.
+
+use crate::display::{app_dir, DisplayError, DisplayInfo, Result};
+use crate::rules::{Rule, RuleAction, RuleSet};
+use crate::selector::DisplayFilter;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use windows::Win32::System::SystemInformation::GetLocalTime;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+const QUEUE_FILE: &str = "queue.json";
+
+/// How long a queued entry waits for its display unless it sets `queue_ttl_minutes`.
+pub const DEFAULT_QUEUE_TTL: Duration = Duration::from_hours(12);
+
+/// A local time of day, written `HH:MM` in rules files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    seconds: u32,
+}
+
+impl TimeOfDay {
+    /// `None` if `hour` or `minute` is out of range.
+    #[must_use]
+    pub fn new(hour: u32, minute: u32) -> Option<Self> {
+        (hour < 24 && minute < 60).then_some(Self { seconds: hour * 3600 + minute * 60 })
+    }
+
+    /// The current local time, to the second.
+    #[must_use]
+    pub fn now() -> Self {
+        Self { seconds: local_seconds() }
+    }
+
+    #[must_use]
+    pub fn hour(self) -> u32 {
+        self.seconds / 3600
+    }
+
+    #[must_use]
+    pub fn minute(self) -> u32 {
+        self.seconds / 60 % 60
+    }
+
+    /// How long after `self` the clock next shows `later`; a full day if they're equal.
+    #[must_use]
+    pub fn until(self, later: Self) -> Duration {
+        let seconds = (later.seconds + SECONDS_PER_DAY - self.seconds) % SECONDS_PER_DAY;
+        Duration::from_secs(u64::from(if seconds == 0 { SECONDS_PER_DAY } else { seconds }))
+    }
+}
+
+/// Seconds since local midnight.
+fn local_seconds() -> u32 {
+    // SAFETY: GetLocalTime has no preconditions.
+    let time = unsafe { GetLocalTime() };
+    u32::from(time.wHour) * 3600 + u32::from(time.wMinute) * 60 + u32::from(time.wSecond)
+}
+
+impl FromStr for TimeOfDay {
+    type Err = DisplayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.split_once(':')
+            .and_then(|(hour, minute)| {
+                let hour = hour.trim().parse().ok()?;
+                let minute = minute.trim();
+                (minute.len() == 2).then_some(())?;
+                Self::new(hour, minute.parse().ok()?)
+            })
+            .ok_or_else(|| DisplayError::InvalidTime(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = DisplayError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour(), self.minute())
+    }
+}
+
+/// A `[[schedule]]` entry: from `at` until the next entry for the same display, the displays
+/// it matches get `apply` on top of whatever their rule asks for.
+///
+/// ```toml
+/// [[schedule]]
+/// name = "evening"
+/// at = "20:00"
+/// match = { connector = "internal" }
+/// apply = { scaling = 150 }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduledAction {
+    #[serde(default)]
+    pub name: String,
+    pub at: TimeOfDay,
+    /// Displays the entry applies to.
+    #[serde(default, rename = "match")]
+    pub criteria: DisplayFilter,
+    #[serde(default)]
+    pub apply: RuleAction,
+    /// What happens when no connected display matches the entry as it fires.
+    #[serde(default)]
+    pub on_missing: OnMissing,
+    /// How long `on_missing = "queue"` waits for a display, [`DEFAULT_QUEUE_TTL`] if unset.
+    #[serde(default)]
+    pub queue_ttl_minutes: Option<u32>,
+}
+
+/// What a display connecting after its schedule entry fired gets, see [`ActionQueue`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnMissing {
+    /// Nothing: it keeps its rule and any earlier entry until the next entry fires.
+    #[default]
+    Skip,
+    /// The entry, if it connects before the entry's time to live runs out.
+    Queue,
+}
+
+impl ScheduledAction {
+    fn queue_ttl(&self) -> Duration {
+        match self.on_missing {
+            OnMissing::Skip => Duration::ZERO,
+            OnMissing::Queue => self
+                .queue_ttl_minutes
+                .map_or(DEFAULT_QUEUE_TTL, |minutes| Duration::from_secs(u64::from(minutes) * 60)),
+        }
+    }
+
+    fn is(&self, action: &QueuedAction) -> bool {
+        self.name == action.entry && self.at == action.at
+    }
+}
+
+/// Seconds since the Unix epoch, the clock of [`ActionQueue`].
+#[must_use]
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+/// A schedule entry that fired while none of its displays was connected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedAction {
+    /// Name and time of the entry, which identify it in the rules file.
+    pub entry: String,
+    pub at: TimeOfDay,
+    /// The entry's `match`, as [`DisplayFilter`] prints it.
+    pub display: String,
+    pub apply: String,
+    /// When it fired and when it expires, in [`unix_time`]; the same for `on_missing = "skip"`.
+    pub fired: u64,
+    pub expires: u64,
+}
+
+impl QueuedAction {
+    #[must_use]
+    pub fn is_pending(&self, now: u64) -> bool {
+        now < self.expires
+    }
+}
+
+/// Schedule entries that missed their displays, kept in `%APPDATA%\display-tuner\queue.json`
+/// so that `watch` remembers them across restarts.
+///
+/// A pending entry applies to the first matching display that connects and leaves the queue;
+/// once expired, it keeps the entry from applying to displays connecting later, until it fires
+/// again. A display only ever has the latest entry in effect, so a newer entry for the same
+/// `match` replaces a queued one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionQueue {
+    pub actions: Vec<QueuedAction>,
+}
+
+impl ActionQueue {
+    /// `%APPDATA%\display-tuner\queue.json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::NoAppData`] if `APPDATA` isn't set.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(app_dir()?.join(QUEUE_FILE))
+    }
+
+    /// The queue saved at `path`, empty if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file or its directory can't be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The actions still waiting for a display at `now`.
+    pub fn pending(&self, now: u64) -> impl Iterator<Item = &QueuedAction> {
+        self.actions.iter().filter(move |action| action.is_pending(now))
+    }
+
+    /// Queues each of the `fired` entries that matches none of `displays` at `now`, replacing
+    /// what's queued for the same `match`. Entries that found a display leave the queue.
+    pub fn record(&mut self, fired: &[&ScheduledAction], displays: &[DisplayInfo], now: u64) {
+        for entry in fired {
+            self.actions.retain(|action| !entry.is(action));
+            if displays.iter().any(|d| entry.criteria.matches(d)) {
+                continue;
+            }
+            let display = entry.criteria.to_string();
+            self.actions.retain(|action| action.display != display);
+            self.actions.push(QueuedAction {
+                entry: entry.name.clone(),
+                at: entry.at,
+                display,
+                apply: entry.apply.to_string(),
+                fired: now,
+                expires: now + entry.queue_ttl().as_secs(),
+            });
+        }
+    }
+
+    /// `rules` without the entries that expired before a display of theirs connected.
+    /// Pending entries whose display is connected now take effect and leave the queue, as do
+    /// actions older than a day or of entries no longer in `rules`, since their entry fired
+    /// again since or is gone.
+    #[must_use]
+    pub fn apply_to(&mut self, rules: &RuleSet, displays: &[DisplayInfo], now: u64) -> RuleSet {
+        let entry_of = |action: &QueuedAction| rules.schedule.iter().find(|entry| entry.is(action));
+        self.actions.retain(|action| {
+            let Some(entry) = entry_of(action) else {
+                return false;
+            };
+            let connected = displays.iter().any(|d| entry.criteria.matches(d));
+            now.saturating_sub(action.fired) < u64::from(SECONDS_PER_DAY) && !(connected && action.is_pending(now))
+        });
+        let held: Vec<&ScheduledAction> = self.actions.iter().filter_map(entry_of).collect();
+        RuleSet {
+            rules: rules.rules.clone(),
+            schedule: rules
+                .schedule
+                .iter()
+                .filter(|entry| !held.iter().any(|held| std::ptr::eq(*held, *entry)))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl RuleSet {
+    /// The schedule entry in effect for `display` at `now`: the matching one that fired last,
+    /// counting back into yesterday. Of entries with the same time the later one in the file
+    /// wins.
+    #[must_use]
+    pub fn scheduled_for(&self, display: &DisplayInfo, now: TimeOfDay) -> Option<&ScheduledAction> {
+        self.schedule
+            .iter()
+            .filter(|entry| entry.criteria.matches(display))
+            .rev()
+            .min_by_key(|entry| entry.at.until(now).as_secs() % u64::from(SECONDS_PER_DAY))
+    }
+
+    /// The rules with the schedule in effect at `now` folded in: one rule per display that a
+    /// rule or schedule entry matches, with the schedule entry's settings taking precedence.
+    #[must_use]
+    pub fn at(&self, now: TimeOfDay, displays: &[DisplayInfo]) -> RuleSet {
+        if self.schedule.is_empty() {
+            return self.clone();
+        }
+        let rules = displays
+            .iter()
+            .filter_map(|display| {
+                let rule = self.rules.iter().find(|rule| rule.criteria.matches(display));
+                let entry = self.scheduled_for(display, now);
+                let (name, apply) = match (rule, entry) {
+                    (None, None) => return None,
+                    (Some(rule), None) => (rule.name.clone(), rule.apply.clone()),
+                    (None, Some(entry)) => (entry.name.clone(), entry.apply.clone()),
+                    (Some(rule), Some(entry)) => {
+                        (format!("{} + {}", rule.name, entry.name), rule.apply.overridden_by(&entry.apply))
+                    }
+                };
+                Some(Rule {
+                    name,
+                    criteria: DisplayFilter { id: Some(display.source_id), ..DisplayFilter::default() },
+                    apply,
+                    allow_mode_switch: rule.and_then(|rule| rule.allow_mode_switch),
+                })
+            })
+            .collect();
+        RuleSet { rules, schedule: Vec::new() }
+    }
+
+    /// Every schedule entry in the order it next fires after `now`, with how long until then.
+    /// Each entry fires once a day, so this covers the next 24 hours.
+    #[must_use]
+    pub fn upcoming(&self, now: TimeOfDay) -> Vec<(Duration, &ScheduledAction)> {
+        let mut upcoming: Vec<_> = self.schedule.iter().map(|entry| (now.until(entry.at), entry)).collect();
+        upcoming.sort_by_key(|(wait, _)| *wait);
+        upcoming
+    }
+
+    /// Schedule entries whose time falls after `since` and no later than `now`, wrapping past
+    /// midnight.
+    #[must_use]
+    pub fn fired_between(&self, since: TimeOfDay, now: TimeOfDay) -> Vec<&ScheduledAction> {
+        if since == now {
+            return Vec::new();
+        }
+        let elapsed = since.until(now);
+        self.schedule
+            .iter()
+            .filter(|entry| since.until(entry.at) <= elapsed)
+            .collect()
+    }
+}
@@ -0,0 +1,285 @@
+use crate::display::{Connector, DisplayError, DisplayInfo, Result};
+use crate::expr::FilterExpr;
+use crate::raw;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Criteria used to narrow down a list of displays.
+///
+/// Every criterion that is set must match (AND semantics); an empty filter matches everything.
+/// Rule files use the same fields for their `match` tables.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    /// Number shown by Windows Settings, see [`DisplayInfo::display_number`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_number: Option<u32>,
+    /// Case-insensitive substring of the friendly name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connector: Option<Connector>,
+    /// Case-insensitive substring of [`DisplayInfo::adapter_name`], e.g. `VID_17E9` for a
+    /// `DisplayLink` dock.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adapter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    /// EDID vendor code such as `DEL`, case-insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    /// EDID product code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_path: Option<String>,
+    /// A filter expression such as `connector=hdmi && width<2560`, see [`crate::expr`].
+    #[serde(rename = "where", skip_serializing_if = "Option::is_none")]
+    pub expr: Option<FilterExpr>,
+}
+
+impl DisplayFilter {
+    #[must_use]
+    pub fn matches(&self, display: &DisplayInfo) -> bool {
+        if self.id.is_some_and(|id| display.source_id != id) {
+            return false;
+        }
+        if self.display_number.is_some_and(|n| display.display_number() != Some(n)) {
+            return false;
+        }
+        if let Some(name) = &self.name
+            && !display
+                .friendly_name
+                .to_lowercase()
+                .contains(&name.to_lowercase())
+        {
+            return false;
+        }
+        if self.min_width.is_some_and(|min| display.width < min) {
+            return false;
+        }
+        if self.connector.is_some_and(|c| display.connector != c) {
+            return false;
+        }
+        if let Some(adapter) = &self.adapter
+            && !display
+                .adapter_name
+                .to_lowercase()
+                .contains(&adapter.to_lowercase())
+        {
+            return false;
+        }
+        if self.active.is_some_and(|a| display.active != a) {
+            return false;
+        }
+        if self
+            .vendor
+            .as_ref()
+            .is_some_and(|v| !display.edid_vendor().eq_ignore_ascii_case(v))
+        {
+            return false;
+        }
+        if self.product.is_some_and(|p| display.edid_product_id != p) {
+            return false;
+        }
+        if self
+            .device_path
+            .as_ref()
+            .is_some_and(|p| !display.device_path.eq_ignore_ascii_case(p))
+        {
+            return false;
+        }
+        if self.expr.as_ref().is_some_and(|e| !e.matches(display)) {
+            return false;
+        }
+        true
+    }
+}
+
+/// Lists the criteria that are set, e.g. `vendor=DEL product=16809`.
+impl fmt::Display for DisplayFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(id) = self.id {
+            parts.push(format!("id={id}"));
+        }
+        if let Some(number) = self.display_number {
+            parts.push(format!("display_number={number}"));
+        }
+        if let Some(name) = &self.name {
+            parts.push(format!("name={name}"));
+        }
+        if let Some(width) = self.min_width {
+            parts.push(format!("min_width={width}"));
+        }
+        if let Some(connector) = self.connector {
+            parts.push(format!("connector={connector}"));
+        }
+        if let Some(adapter) = &self.adapter {
+            parts.push(format!("adapter={adapter}"));
+        }
+        if let Some(active) = self.active {
+            parts.push(format!("active={active}"));
+        }
+        if let Some(vendor) = &self.vendor {
+            parts.push(format!("vendor={vendor}"));
+        }
+        if let Some(product) = self.product {
+            parts.push(format!("product={product}"));
+        }
+        if let Some(path) = &self.device_path {
+            parts.push(format!("device_path={path}"));
+        }
+        if let Some(expr) = &self.expr {
+            parts.push(format!("where='{expr}'"));
+        }
+        if parts.is_empty() {
+            f.write_str("any display")
+        } else {
+            f.write_str(&parts.join(" "))
+        }
+    }
+}
+
+/// User-chosen names for displays such as `left` or `tv`, each matching one display by the
+/// [`DisplayFilter`] fields, usually a device path or EDID ids.
+pub type Aliases = BTreeMap<String, DisplayFilter>;
+
+/// The alias of `display`, the first in name order if several match.
+#[must_use]
+pub fn alias_of<'a>(aliases: &'a Aliases, display: &DisplayInfo) -> Option<&'a str> {
+    aliases
+        .iter()
+        .find(|(_, filter)| filter.matches(display))
+        .map(|(alias, _)| alias.as_str())
+}
+
+/// The display called `alias` (case-insensitive), the first of `displays` its filter matches.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::UnknownAlias`] listing the defined aliases if there's no such alias,
+/// or [`DisplayError::AliasNotConnected`] if its display isn't among `displays`.
+pub fn resolve_alias<'a>(aliases: &Aliases, alias: &str, displays: &'a [DisplayInfo]) -> Result<&'a DisplayInfo> {
+    let (name, filter) = aliases
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(alias))
+        .ok_or_else(|| DisplayError::UnknownAlias {
+            alias: alias.to_string(),
+            defined: if aliases.is_empty() {
+                "none".to_string()
+            } else {
+                aliases.keys().map(String::as_str).collect::<Vec<_>>().join(", ")
+            },
+        })?;
+    displays
+        .iter()
+        .find(|d| filter.matches(d))
+        .ok_or_else(|| DisplayError::AliasNotConnected(name.clone()))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Id,
+    Name,
+    Resolution,
+}
+
+#[must_use]
+pub fn filter_displays(displays: Vec<DisplayInfo>, filter: &DisplayFilter) -> Vec<DisplayInfo> {
+    displays.into_iter().filter(|d| filter.matches(d)).collect()
+}
+
+/// Sorts displays by `key`, falling back to the source id so the order is stable.
+pub fn sort_displays(displays: &mut [DisplayInfo], key: SortKey) {
+    match key {
+        SortKey::Id => displays.sort_by_key(|d| d.source_id),
+        SortKey::Name => displays.sort_by(|a, b| {
+            a.friendly_name
+                .to_lowercase()
+                .cmp(&b.friendly_name.to_lowercase())
+                .then(a.source_id.cmp(&b.source_id))
+        }),
+        SortKey::Resolution => displays.sort_by_key(|d| (d.width, d.height, d.source_id)),
+    }
+}
+
+/// The displays in the order `--index` counts them: by source id, as `list` shows them
+/// without `--sort`. Displays sharing a source id on different adapters keep their
+/// enumeration order.
+fn index_order(displays: &[DisplayInfo]) -> Vec<&DisplayInfo> {
+    let mut ordered: Vec<&DisplayInfo> = displays.iter().collect();
+    ordered.sort_by_key(|d| d.source_id);
+    ordered
+}
+
+/// The 1-based position of `display` among `displays` in `--index` order, `None` if it isn't
+/// one of them.
+#[must_use]
+pub fn display_index(displays: &[DisplayInfo], display: &DisplayInfo) -> Option<usize> {
+    index_order(displays)
+        .iter()
+        .position(|d| d.source_id == display.source_id && d.adapter_id == display.adapter_id)
+        .map(|i| i + 1)
+}
+
+/// The display at 1-based `index` in `--index` order.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::IndexOutOfRange`] if there are fewer displays.
+pub fn display_at_index(displays: &[DisplayInfo], index: usize) -> Result<&DisplayInfo> {
+    index
+        .checked_sub(1)
+        .and_then(|i| index_order(displays).get(i).copied())
+        .ok_or(DisplayError::IndexOutOfRange { index, count: displays.len() })
+}
+
+/// The error for an `--id` that matches none of `displays`, suggesting `--index` if `id`
+/// would be a valid one; list positions are easily mistaken for source ids.
+#[must_use]
+pub fn id_not_found(displays: &[DisplayInfo], id: u32) -> DisplayError {
+    let hint = usize::try_from(id)
+        .ok()
+        .and_then(|index| display_at_index(displays, index).ok().map(|d| (index, d)))
+        .map(|(index, d)| format!("; did you mean --index {index} which is {}?", d.friendly_name))
+        .unwrap_or_default();
+    DisplayError::NoDisplayWithId { id, hint }
+}
+
+/// Finds the display driven by the GDI source `device`, e.g. `\\.\DISPLAY2` as reported by
+/// `GetMonitorInfo`.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::UnmatchedMonitorDevice`] naming `device` if no display matches.
+pub fn find_by_gdi_name<'a>(displays: &'a [DisplayInfo], device: &str) -> Result<&'a DisplayInfo> {
+    displays
+        .iter()
+        .find(|d| d.gdi_device_name.eq_ignore_ascii_case(device))
+        .ok_or_else(|| DisplayError::UnmatchedMonitorDevice(device.to_string()))
+}
+
+/// The display the mouse cursor is on.
+///
+/// # Errors
+///
+/// Returns an error if the monitor can't be determined or mapped to one of `displays`.
+pub fn display_under_cursor(displays: &[DisplayInfo]) -> Result<&DisplayInfo> {
+    find_by_gdi_name(displays, &raw::cursor_monitor_device()?)
+}
+
+/// The display showing most of the foreground window.
+///
+/// # Errors
+///
+/// Returns an error if no window has focus or its monitor can't be mapped to one of `displays`.
+pub fn display_of_focused_window(displays: &[DisplayInfo]) -> Result<&DisplayInfo> {
+    find_by_gdi_name(displays, &raw::foreground_monitor_device()?)
+}
@@ -0,0 +1,154 @@
+//! `selftest`: proves that the display APIs work on a machine without changing anything
+//! visible, for QA and fleet health checks.
+//!
+//! Every step only reads, or asks Windows to validate the configuration as it is. With
+//! `destructive` the configuration and scaling are also written back unchanged, which proves
+//! the process may change them.
+
+use crate::display::{enumerate_displays, supported_modes, ConfigSnapshot, DisplayError, DisplayInfo, Result};
+use crate::raw::{self, query_display_config, PathInfo};
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+use windows::Win32::Devices::Display::QDC_ONLY_ACTIVE_PATHS;
+
+/// One step of [`selftest`], for one display or for all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Step {
+    pub name: &'static str,
+    /// The display checked, `None` for steps about the whole configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<u32>,
+    pub passed: bool,
+    /// What was found, or the error.
+    pub detail: String,
+    #[serde(rename = "duration_ms", serialize_with = "crate::display::millis::serialize")]
+    pub duration: Duration,
+}
+
+/// Outcome of [`selftest`], printed one step per line or as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SelftestReport {
+    /// Whether every step passed.
+    pub passed: bool,
+    pub destructive: bool,
+    pub steps: Vec<Step>,
+}
+
+impl SelftestReport {
+    #[must_use]
+    pub fn new(destructive: bool) -> Self {
+        Self { passed: true, destructive, steps: Vec::new() }
+    }
+
+    /// Runs and times `step`, which describes what it found or fails; returns whether it passed.
+    fn run(&mut self, name: &'static str, source_id: Option<u32>, step: impl FnOnce() -> Result<String>) -> bool {
+        let started = Instant::now();
+        let result = step();
+        let duration = started.elapsed();
+        let passed = result.is_ok();
+        self.passed &= passed;
+        let detail = result.unwrap_or_else(|err| err.to_string());
+        self.steps.push(Step { name, source_id, passed, detail, duration });
+        passed
+    }
+}
+
+impl fmt::Display for SelftestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            let status = if step.passed { "PASS" } else { "FAIL" };
+            let display = step.source_id.map_or_else(String::new, |id| format!(", display {id}"));
+            writeln!(f, "[{status}] {}{display}: {} ({} ms)", step.name, step.detail, step.duration.as_millis())?;
+        }
+        let failed = self.steps.iter().filter(|s| !s.passed).count();
+        if failed == 0 {
+            write!(f, "Self-test passed")
+        } else {
+            write!(f, "Self-test failed: {failed} of {} steps", self.steps.len())
+        }
+    }
+}
+
+/// Runs every step, stopping early only when the displays can't be enumerated.
+#[must_use]
+pub fn selftest(destructive: bool) -> SelftestReport {
+    let mut report = SelftestReport::new(destructive);
+    let mut displays = Vec::new();
+    let enumerated = report.run("Enumeration", None, || {
+        displays = enumerate_displays()?;
+        let active = displays.iter().filter(|d| d.active).count();
+        Ok(format!("{} display(s) found, {active} active", displays.len()))
+    });
+    if !enumerated {
+        return report;
+    }
+    let active: Vec<&DisplayInfo> = displays.iter().filter(|d| d.active).collect();
+
+    for display in &active {
+        let id = Some(display.source_id);
+        report.run("Current mode", id, || current_mode(display));
+        report.run("DPI query", id, || {
+            let scale = raw::dpi_scale(&active_path(display)?)?;
+            Ok(format!("{}% scaling, {} steps from the recommended one ({} to {})", display.scaling_current, scale.current, scale.min, scale.max))
+        });
+        report.run("HMONITOR mapping", id, || monitor_mapping(display));
+    }
+    let mut snapshot = None;
+    report.run("SDC_VALIDATE", None, || {
+        let current = ConfigSnapshot::take()?;
+        current.validate()?;
+        snapshot = Some(current);
+        Ok("the current configuration is accepted unchanged".to_string())
+    });
+
+    if destructive {
+        if let Some(snapshot) = snapshot {
+            report.run("SetDisplayConfig", None, || {
+                snapshot.restore()?;
+                Ok("the current configuration was applied again".to_string())
+            });
+        }
+        for display in &active {
+            report.run("DPI set", Some(display.source_id), || {
+                let path = active_path(display)?;
+                let scale = raw::dpi_scale(&path)?;
+                raw::set_dpi_scale(&path, scale.current)?;
+                Ok(format!("{}% set again", display.scaling_current))
+            });
+        }
+    }
+    report
+}
+
+/// The current resolution of `display` among the modes its driver lists.
+fn current_mode(display: &DisplayInfo) -> Result<String> {
+    let modes = supported_modes(display)?;
+    // The driver lists modes in the monitor's own orientation.
+    let (width, height) = display.rotation.orient((display.width, display.height));
+    modes
+        .iter()
+        .any(|m| (m.width, m.height) == (width, height))
+        .then(|| format!("{width}x{height} @ {}Hz is among the {} modes listed", display.refresh_rate, modes.len()))
+        .ok_or(DisplayError::UnsupportedMode { source_id: display.source_id, width, height })
+}
+
+fn active_path(display: &DisplayInfo) -> Result<PathInfo> {
+    let (paths, _) = query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+    raw::find_adapter_path(&paths, display.adapter_id, display.source_id)
+        .copied()
+        .ok_or(DisplayError::DisplayNotFound(display.source_id))
+}
+
+/// Whether the window manager maps the center of `display` to the monitor of its GDI device, as
+/// `--current` and `--focused` rely on.
+fn monitor_mapping(display: &DisplayInfo) -> Result<String> {
+    let x = display.position_x.saturating_add_unsigned(display.width / 2);
+    let y = display.position_y.saturating_add_unsigned(display.height / 2);
+    let device = raw::monitor_device_at(x, y)?;
+    if device.eq_ignore_ascii_case(&display.gdi_device_name) {
+        Ok(format!("({x}, {y}) is on {device}"))
+    } else {
+        Err(DisplayError::UnmatchedMonitorDevice(device))
+    }
+}
@@ -0,0 +1,94 @@
+//! Waiting for the desktop to catch up with an apply, so scripts don't screenshot a black
+//! screen or place windows by a stale work area.
+//!
+//! `SetDisplayConfig` returns once Windows took the new configuration, while the monitor may
+//! still be syncing to the new signal and the shell hasn't moved the taskbar yet. A display
+//! counts as settled once enumeration reports the requested mode, the target is active and
+//! available again, and its work area fits the new size.
+
+use crate::display::{enumerate_displays, DisplayConfig, DisplayError, DisplayInfo, Result};
+use crate::layout::Rect;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How often the displays are enumerated while waiting.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether the work area the window manager reports for `display` belongs to its current
+/// size: inside the display and spanning it one way, as the taskbar takes a single edge. A
+/// display without a work area can't be told and counts as settled.
+#[must_use]
+pub fn work_area_settled(display: &DisplayInfo) -> bool {
+    let Some(area) = display.work_area else {
+        return true;
+    };
+    let bounds = Rect::of(display);
+    let inside = area.x >= bounds.x && area.y >= bounds.y && area.right() <= bounds.right() && area.bottom() <= bounds.bottom();
+    inside && (area.width == bounds.width || area.height == bounds.height)
+}
+
+/// Whether `display` as enumerated now shows `config` and is ready for use. Scaling under a
+/// custom scaling override only changes with the next sign-in, so it isn't waited for then.
+#[must_use]
+pub fn is_settled(display: &DisplayInfo, config: &DisplayConfig) -> bool {
+    display.active
+        && display.target_available
+        && (display.width, display.height) == (config.width, config.height)
+        && (display.custom_scaling || display.scaling_current == config.scaling)
+        && work_area_settled(display)
+}
+
+/// Blocks until `display` shows `config`, see [`is_settled`], or `timeout` runs out.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NotSettled`] if the display isn't settled within `timeout`, or an
+/// error if the displays can't be enumerated.
+pub fn wait_for_configuration(display: &DisplayInfo, config: &DisplayConfig, timeout: Duration) -> Result<()> {
+    let source_id = display.source_id;
+    wait_until(timeout, |displays| {
+        let settled = displays.iter().find(|d| d.source_id == source_id).is_some_and(|d| is_settled(d, config));
+        if settled { None } else { Some(source_id) }
+    })
+}
+
+/// Blocks until every active display is settled in whatever mode it's in, for changes whose
+/// outcome isn't known up front, or until `timeout` runs out.
+///
+/// # Errors
+///
+/// Returns [`DisplayError::NotSettled`] naming a display that isn't settled within `timeout`,
+/// or an error if the displays can't be enumerated.
+pub fn wait_for_desktop(timeout: Duration) -> Result<()> {
+    wait_until(timeout, |displays| {
+        displays
+            .iter()
+            .find(|d| {
+                let config = DisplayConfig { width: d.width, height: d.height, scaling: d.scaling_current };
+                !is_settled(d, &config)
+            })
+            .map(|d| d.source_id)
+    })
+}
+
+/// Polls until `unsettled` finds no display left to wait for.
+fn wait_until(timeout: Duration, unsettled: impl Fn(&[DisplayInfo]) -> Option<u32>) -> Result<()> {
+    let started = Instant::now();
+    loop {
+        // Enumeration fails for a moment while a mode switch is under way.
+        match enumerate_displays() {
+            Ok(displays) => match unsettled(&displays) {
+                None => {
+                    debug!(elapsed = ?started.elapsed(), "Displays settled");
+                    return Ok(());
+                }
+                Some(source_id) if started.elapsed() >= timeout => return Err(DisplayError::NotSettled(source_id)),
+                Some(_) => {}
+            },
+            Err(err) if started.elapsed() < timeout => debug!(%err, "Enumeration failed while waiting for the displays to settle"),
+            Err(err) => return Err(err),
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::display::Result;
+use std::path::Path;
+use tracing::info;
+use windows::Win32::Foundation::ERROR_FILE_NOT_FOUND;
+use windows::Win32::System::Registry::{RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ, RRF_RT_REG_SZ};
+use windows::core::{w, PCWSTR};
+
+/// Per-user programs started at logon.
+const RUN_KEY: PCWSTR = w!(r"Software\Microsoft\Windows\CurrentVersion\Run");
+const VALUE_NAME: PCWSTR = w!("display-tuner");
+
+/// The command line registered to run at logon.
+#[must_use]
+pub fn reapply_command(exe: &Path) -> String {
+    format!("\"{}\" reapply", exe.display())
+}
+
+/// Registers `display-tuner reapply` to run at logon for the current user, returning the
+/// registered command line.
+///
+/// # Errors
+///
+/// Returns an error if the executable path can't be determined or the registry can't be written.
+pub fn install() -> Result<String> {
+    let command = reapply_command(&std::env::current_exe()?);
+    let data: Vec<u16> = command.encode_utf16().chain(std::iter::once(0)).collect();
+    // SAFETY: constant key and value names; the data is NUL-terminated and its byte size given.
+    unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            RUN_KEY,
+            VALUE_NAME,
+            REG_SZ.0,
+            Some(data.as_ptr().cast()),
+            u32::try_from(data.len() * size_of::<u16>())?,
+        )
+        .ok()?;
+    }
+    info!(command, "Installed logon entry");
+    Ok(command)
+}
+
+/// Removes the logon entry, returning whether there was one.
+///
+/// # Errors
+///
+/// Returns an error if the registry can't be written.
+pub fn remove() -> Result<bool> {
+    // SAFETY: constant key and value names.
+    let result = unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, RUN_KEY, VALUE_NAME) };
+    if result == ERROR_FILE_NOT_FOUND {
+        return Ok(false);
+    }
+    result.ok()?;
+    Ok(true)
+}
+
+/// The registered logon command line, `None` if not installed.
+///
+/// # Errors
+///
+/// Returns an error if the registry can't be read.
+pub fn status() -> Result<Option<String>> {
+    let mut len = 0u32;
+    // SAFETY: a size query without a data buffer, then a read into a buffer of that size.
+    unsafe {
+        let result = RegGetValueW(HKEY_CURRENT_USER, RUN_KEY, VALUE_NAME, RRF_RT_REG_SZ, None, None, Some(&raw mut len));
+        if result == ERROR_FILE_NOT_FOUND {
+            return Ok(None);
+        }
+        result.ok()?;
+
+        let mut buffer = vec![0u16; (len as usize).div_ceil(size_of::<u16>())];
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            RUN_KEY,
+            VALUE_NAME,
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            Some(&raw mut len),
+        )
+        .ok()?;
+        Ok(Some(String::from_utf16_lossy(&buffer).trim_end_matches('\0').to_string()))
+    }
+}
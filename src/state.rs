@@ -0,0 +1,454 @@
+//! The complete display configuration as one file, for `state save` and `state restore`.
+//!
+//! A [`DisplayState`] holds every path and mode `QueryDisplayConfig` reports with
+//! `QDC_ALL_PATHS`, the monitors attached, and the DPI scaling of each active display. The CCD
+//! structures are mirrored in plain structs since the `windows` ones can't be serialized, and
+//! converted back unchanged for `SetDisplayConfig` with `SDC_USE_SUPPLIED_DISPLAY_CONFIG`.
+//!
+//! Adapter LUIDs are assigned at boot, so a state restored after a reboot is first mapped onto
+//! the adapters and targets the same monitors are attached to now, see [`DisplayState::remap`].
+
+use crate::display::{self, set_with_retries, AdapterId, DisplayError, Result};
+use crate::raw::{self, ModeInfo, PathInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use tracing::{debug, info};
+use windows::Win32::Devices::Display::{DISPLAYCONFIG_2DREGION, DISPLAYCONFIG_DESKTOP_IMAGE_INFO, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_MODE_INFO_0, DISPLAYCONFIG_MODE_INFO_TYPE_DESKTOP_IMAGE, DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE, DISPLAYCONFIG_MODE_INFO_TYPE_TARGET, DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_PATH_SOURCE_INFO, DISPLAYCONFIG_PATH_SOURCE_INFO_0, DISPLAYCONFIG_PATH_TARGET_INFO, DISPLAYCONFIG_PATH_TARGET_INFO_0, DISPLAYCONFIG_PIXELFORMAT, DISPLAYCONFIG_RATIONAL, DISPLAYCONFIG_ROTATION, DISPLAYCONFIG_SCALING, DISPLAYCONFIG_SCANLINE_ORDERING, DISPLAYCONFIG_SOURCE_MODE, DISPLAYCONFIG_TARGET_MODE, DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY, DISPLAYCONFIG_VIDEO_SIGNAL_INFO, DISPLAYCONFIG_VIDEO_SIGNAL_INFO_0, QDC_ALL_PATHS, QDC_ONLY_ACTIVE_PATHS, SDC_APPLY, SDC_USE_SUPPLIED_DISPLAY_CONFIG};
+use windows::Win32::Foundation::{LUID, POINTL, RECTL};
+use windows::Win32::Graphics::Gdi::DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+
+/// Format version written to state files; files of other versions are refused.
+pub const STATE_VERSION: u32 = 1;
+
+/// A `DISPLAYCONFIG_PATH_INFO`. Enum fields hold the raw value of the Win32 enum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedPath {
+    pub source_adapter: AdapterId,
+    pub source_id: u32,
+    /// Index into [`DisplayState::modes`].
+    pub source_mode: Option<u32>,
+    pub source_status: u32,
+    pub target_adapter: AdapterId,
+    pub target_id: u32,
+    /// Index into [`DisplayState::modes`].
+    pub target_mode: Option<u32>,
+    pub output_technology: i32,
+    pub rotation: i32,
+    pub scaling: i32,
+    /// Numerator and denominator.
+    pub refresh_rate: (u32, u32),
+    pub scan_line_ordering: i32,
+    pub target_available: bool,
+    pub target_status: u32,
+    pub flags: u32,
+}
+
+/// A `DISPLAYCONFIG_MODE_INFO`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedMode {
+    pub adapter: AdapterId,
+    /// The source or target id, depending on the kind.
+    pub id: u32,
+    pub kind: SavedModeKind,
+}
+
+/// The member of the `DISPLAYCONFIG_MODE_INFO` union `infoType` names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SavedModeKind {
+    Source { width: u32, height: u32, pixel_format: i32, position: (i32, i32) },
+    Target {
+        pixel_rate: u64,
+        h_sync: (u32, u32),
+        v_sync: (u32, u32),
+        active: (u32, u32),
+        total: (u32, u32),
+        /// The `videoStandard` member of the union, which also holds the additional signal
+        /// info bits.
+        video_standard: u32,
+        scan_line_ordering: i32,
+    },
+    /// Only reported to virtual mode aware queries; kept for completeness.
+    DesktopImage { source_size: (i32, i32), region: [i32; 4], clip: [i32; 4] },
+}
+
+/// A monitor attached when the state was saved or now.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedMonitor {
+    pub target_adapter: AdapterId,
+    pub target_id: u32,
+    pub device_path: String,
+    pub edid_manufacturer_id: u16,
+    pub edid_product_id: u16,
+    pub friendly_name: String,
+    /// DPI scaling in steps from the recommended value, `None` if the monitor was off.
+    pub dpi_steps: Option<i32>,
+}
+
+/// Everything `state restore` puts back, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayState {
+    pub version: u32,
+    pub paths: Vec<SavedPath>,
+    pub modes: Vec<SavedMode>,
+    pub monitors: Vec<SavedMonitor>,
+}
+
+impl DisplayState {
+    /// Captures the current configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration, a monitor's name or a display's scaling can't be
+    /// queried, or [`DisplayError::UnknownModeType`] for a mode this build can't save.
+    pub fn capture() -> Result<Self> {
+        let (paths, modes) = raw::query_display_config(QDC_ALL_PATHS)?;
+        Ok(Self {
+            version: STATE_VERSION,
+            paths: paths.iter().map(SavedPath::from).collect(),
+            modes: modes.iter().map(SavedMode::try_from).collect::<Result<_>>()?,
+            monitors: attached_monitors(&paths, true)?,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, isn't a state file or is of another
+    /// [`STATE_VERSION`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let state: Self = serde_json::from_slice(&fs::read(path)?)?;
+        if state.version != STATE_VERSION {
+            return Err(DisplayError::StateVersion(state.version));
+        }
+        Ok(state)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// The state moved onto `current`, the monitors attached now: each saved monitor is found
+    /// by device path, or else by EDID manufacturer and product, and its adapter and target id
+    /// take the place of the saved ones throughout. Paths and modes of adapters none of the
+    /// monitors was found on are dropped, and so are paths to saved monitors that are missing.
+    /// Also returns the names of those missing monitors.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::StateMismatch`] if a saved monitor is missing or one not in the
+    /// state is attached, unless `partial`.
+    pub fn remap(&self, current: &[SavedMonitor], partial: bool) -> Result<(Self, Vec<String>)> {
+        let pairs = match_monitors(&self.monitors, current);
+        let missing: Vec<String> = (0..self.monitors.len())
+            .filter(|i| !pairs.iter().any(|(saved, _)| saved == i))
+            .map(|i| self.monitors[i].friendly_name.clone())
+            .collect();
+        let unexpected: Vec<String> = (0..current.len())
+            .filter(|i| !pairs.iter().any(|(_, now)| now == i))
+            .map(|i| current[i].friendly_name.clone())
+            .collect();
+        if !partial && (!missing.is_empty() || !unexpected.is_empty()) {
+            return Err(DisplayError::StateMismatch { missing: names(&missing), unexpected: names(&unexpected) });
+        }
+
+        let mut adapters = HashMap::new();
+        let mut targets = HashMap::new();
+        for &(saved, now) in &pairs {
+            let (saved, now) = (&self.monitors[saved], &current[now]);
+            adapters.entry(saved.target_adapter).or_insert(now.target_adapter);
+            targets.insert((saved.target_adapter, saved.target_id), (now.target_adapter, now.target_id));
+        }
+
+        let mut indices = HashMap::new();
+        let mut modes = Vec::new();
+        for (index, mode) in self.modes.iter().enumerate() {
+            let Some(&adapter) = adapters.get(&mode.adapter) else {
+                continue;
+            };
+            let id = match mode.kind {
+                SavedModeKind::Target { .. } => targets.get(&(mode.adapter, mode.id)).map_or(mode.id, |&(_, id)| id),
+                _ => mode.id,
+            };
+            indices.insert(index, u32::try_from(modes.len())?);
+            modes.push(SavedMode { adapter, id, ..*mode });
+        }
+        let reindex = |index: Option<u32>| index.and_then(|i| indices.get(&(i as usize)).copied());
+
+        let mut paths = Vec::new();
+        for path in &self.paths {
+            let Some(&source_adapter) = adapters.get(&path.source_adapter) else {
+                continue;
+            };
+            let (target_adapter, target_id) = match targets.get(&(path.target_adapter, path.target_id)) {
+                Some(&target) => target,
+                // A connector with nothing attached then; still there if its adapter is.
+                None if !path.target_available => match adapters.get(&path.target_adapter) {
+                    Some(&adapter) => (adapter, path.target_id),
+                    None => continue,
+                },
+                None => continue,
+            };
+            paths.push(SavedPath {
+                source_adapter,
+                source_mode: reindex(path.source_mode),
+                target_adapter,
+                target_id,
+                target_mode: reindex(path.target_mode),
+                ..*path
+            });
+        }
+
+        let monitors = pairs
+            .iter()
+            .map(|&(saved, now)| SavedMonitor { dpi_steps: self.monitors[saved].dpi_steps, ..current[now].clone() })
+            .collect();
+        Ok((Self { version: self.version, paths, modes, monitors }, missing))
+    }
+
+    /// Applies the state as saved: the configuration in one `SetDisplayConfig` call, then the
+    /// DPI scaling of each display that was on. Returns the names of the saved monitors that
+    /// aren't attached and were left out, only ever non-empty with `partial`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DisplayError::StateMismatch`] as [`DisplayState::remap`] does, or an error if
+    /// Windows rejects the configuration or a scaling.
+    pub fn restore(&self, partial: bool) -> Result<Vec<String>> {
+        let (all_paths, _) = raw::query_display_config(QDC_ALL_PATHS)?;
+        let (state, missing) = self.remap(&attached_monitors(&all_paths, false)?, partial)?;
+        let paths: Vec<PathInfo> = state.paths.iter().map(PathInfo::from).collect();
+        let modes: Vec<ModeInfo> = state.modes.iter().map(ModeInfo::from).collect();
+        info!(paths = paths.len(), modes = modes.len(), "Restoring display state");
+        set_with_retries(|| Ok((paths.clone(), modes.clone())), SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG)?;
+
+        let (active, _) = raw::query_display_config(QDC_ONLY_ACTIVE_PATHS)?;
+        let mut rescaled = false;
+        for monitor in &state.monitors {
+            let Some(steps) = monitor.dpi_steps else {
+                continue;
+            };
+            let Some(path) = active.iter().find(|path| {
+                path.targetInfo.id == monitor.target_id && adapter_id(path.targetInfo.adapterId) == monitor.target_adapter
+            }) else {
+                continue;
+            };
+            if raw::dpi_scale(path)?.current != steps {
+                debug!(name = monitor.friendly_name, steps, "Restoring DPI scaling");
+                raw::set_dpi_scale(path, steps)?;
+                rescaled = true;
+            }
+        }
+        if rescaled && display::broadcast() {
+            raw::broadcast_dpi_change();
+        }
+        Ok(missing)
+    }
+}
+
+/// Pairs each saved monitor with the current one at the same device path, then the remaining
+/// ones by EDID manufacturer and product, in order. Indices into `saved` and `current`.
+fn match_monitors(saved: &[SavedMonitor], current: &[SavedMonitor]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut taken = BTreeSet::new();
+    for (i, monitor) in saved.iter().enumerate() {
+        if let Some(j) = (0..current.len()).find(|&j| !taken.contains(&j) && current[j].device_path.eq_ignore_ascii_case(&monitor.device_path)) {
+            taken.insert(j);
+            pairs.push((i, j));
+        }
+    }
+    for (i, monitor) in saved.iter().enumerate() {
+        if pairs.iter().any(|&(paired, _)| paired == i) {
+            continue;
+        }
+        let model = (monitor.edid_manufacturer_id, monitor.edid_product_id);
+        if let Some(j) = (0..current.len()).find(|&j| !taken.contains(&j) && (current[j].edid_manufacturer_id, current[j].edid_product_id) == model) {
+            taken.insert(j);
+            pairs.push((i, j));
+        }
+    }
+    pairs
+}
+
+fn names(names: &[String]) -> String {
+    if names.is_empty() { "none".to_string() } else { names.join(", ") }
+}
+
+/// The monitors on the targets of `paths`, once each; `QDC_ALL_PATHS` lists a target once per
+/// source that could drive it. With `dpi`, the scaling of the active ones is read too.
+fn attached_monitors(paths: &[PathInfo], dpi: bool) -> Result<Vec<SavedMonitor>> {
+    let mut monitors: Vec<SavedMonitor> = Vec::new();
+    for path in paths.iter().filter(|path| path.target_available()) {
+        let (target_adapter, target_id) = (adapter_id(path.targetInfo.adapterId), path.targetInfo.id);
+        let dpi_steps = if dpi && path.is_active() { Some(raw::dpi_scale(path)?.current) } else { None };
+        if let Some(monitor) = monitors.iter_mut().find(|m| (m.target_adapter, m.target_id) == (target_adapter, target_id)) {
+            monitor.dpi_steps = monitor.dpi_steps.or(dpi_steps);
+            continue;
+        }
+        let name = raw::target_device_name(path)?;
+        monitors.push(SavedMonitor {
+            target_adapter,
+            target_id,
+            device_path: raw::utf16_to_string(&name.monitorDevicePath),
+            edid_manufacturer_id: name.edidManufactureId,
+            edid_product_id: name.edidProductCodeId,
+            friendly_name: raw::utf16_to_string(&name.monitorFriendlyDeviceName),
+            dpi_steps,
+        });
+    }
+    Ok(monitors)
+}
+
+fn adapter_id(luid: LUID) -> AdapterId {
+    AdapterId { low: luid.LowPart, high: luid.HighPart }
+}
+
+fn luid(adapter: AdapterId) -> LUID {
+    LUID { LowPart: adapter.low, HighPart: adapter.high }
+}
+
+fn rational(rational: DISPLAYCONFIG_RATIONAL) -> (u32, u32) {
+    (rational.Numerator, rational.Denominator)
+}
+
+fn to_rational((numerator, denominator): (u32, u32)) -> DISPLAYCONFIG_RATIONAL {
+    DISPLAYCONFIG_RATIONAL { Numerator: numerator, Denominator: denominator }
+}
+
+fn rect(rect: RECTL) -> [i32; 4] {
+    [rect.left, rect.top, rect.right, rect.bottom]
+}
+
+fn to_rect([left, top, right, bottom]: [i32; 4]) -> RECTL {
+    RECTL { left, top, right, bottom }
+}
+
+impl From<&PathInfo> for SavedPath {
+    fn from(path: &PathInfo) -> Self {
+        let (source, target) = (&path.sourceInfo, &path.targetInfo);
+        Self {
+            source_adapter: path.adapter_id(),
+            source_id: source.id,
+            source_mode: path.source_mode_index().and_then(|i| u32::try_from(i).ok()),
+            source_status: source.statusFlags,
+            target_adapter: adapter_id(target.adapterId),
+            target_id: target.id,
+            target_mode: path.target_mode_index().and_then(|i| u32::try_from(i).ok()),
+            output_technology: target.outputTechnology.0,
+            rotation: target.rotation.0,
+            scaling: target.scaling.0,
+            refresh_rate: rational(target.refreshRate),
+            scan_line_ordering: target.scanLineOrdering.0,
+            target_available: path.target_available(),
+            target_status: target.statusFlags,
+            flags: path.flags,
+        }
+    }
+}
+
+impl From<&SavedPath> for PathInfo {
+    fn from(path: &SavedPath) -> Self {
+        let index = |index: Option<u32>| index.unwrap_or(DISPLAYCONFIG_PATH_MODE_IDX_INVALID);
+        Self(DISPLAYCONFIG_PATH_INFO {
+            sourceInfo: DISPLAYCONFIG_PATH_SOURCE_INFO {
+                adapterId: luid(path.source_adapter),
+                id: path.source_id,
+                Anonymous: DISPLAYCONFIG_PATH_SOURCE_INFO_0 { modeInfoIdx: index(path.source_mode) },
+                statusFlags: path.source_status,
+            },
+            targetInfo: DISPLAYCONFIG_PATH_TARGET_INFO {
+                adapterId: luid(path.target_adapter),
+                id: path.target_id,
+                Anonymous: DISPLAYCONFIG_PATH_TARGET_INFO_0 { modeInfoIdx: index(path.target_mode) },
+                outputTechnology: DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY(path.output_technology),
+                rotation: DISPLAYCONFIG_ROTATION(path.rotation),
+                scaling: DISPLAYCONFIG_SCALING(path.scaling),
+                refreshRate: to_rational(path.refresh_rate),
+                scanLineOrdering: DISPLAYCONFIG_SCANLINE_ORDERING(path.scan_line_ordering),
+                targetAvailable: path.target_available.into(),
+                statusFlags: path.target_status,
+            },
+            flags: path.flags,
+        })
+    }
+}
+
+impl TryFrom<&ModeInfo> for SavedMode {
+    type Error = DisplayError;
+
+    fn try_from(mode: &ModeInfo) -> Result<Self> {
+        let kind = if let Some(source) = mode.source_mode() {
+            SavedModeKind::Source {
+                width: source.width,
+                height: source.height,
+                pixel_format: source.pixelFormat.0,
+                position: (source.position.x, source.position.y),
+            }
+        } else if let Some(target) = mode.target_mode() {
+            let signal = &target.targetVideoSignalInfo;
+            SavedModeKind::Target {
+                pixel_rate: signal.pixelRate,
+                h_sync: rational(signal.hSyncFreq),
+                v_sync: rational(signal.vSyncFreq),
+                active: (signal.activeSize.cx, signal.activeSize.cy),
+                total: (signal.totalSize.cx, signal.totalSize.cy),
+                video_standard: raw::video_standard(signal),
+                scan_line_ordering: signal.scanLineOrdering.0,
+            }
+        } else if let Some(image) = mode.desktop_image_info() {
+            SavedModeKind::DesktopImage {
+                source_size: (image.PathSourceSize.x, image.PathSourceSize.y),
+                region: rect(image.DesktopImageRegion),
+                clip: rect(image.DesktopImageClip),
+            }
+        } else {
+            return Err(DisplayError::UnknownModeType(mode.infoType.0));
+        };
+        Ok(Self { adapter: adapter_id(mode.adapterId), id: mode.id, kind })
+    }
+}
+
+impl From<&SavedMode> for ModeInfo {
+    fn from(mode: &SavedMode) -> Self {
+        let (info_type, info) = match mode.kind {
+            SavedModeKind::Source { width, height, pixel_format, position: (x, y) } => (
+                DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE,
+                DISPLAYCONFIG_MODE_INFO_0 {
+                    sourceMode: DISPLAYCONFIG_SOURCE_MODE { width, height, pixelFormat: DISPLAYCONFIG_PIXELFORMAT(pixel_format), position: POINTL { x, y } },
+                },
+            ),
+            SavedModeKind::Target { pixel_rate, h_sync, v_sync, active, total, video_standard, scan_line_ordering } => (
+                DISPLAYCONFIG_MODE_INFO_TYPE_TARGET,
+                DISPLAYCONFIG_MODE_INFO_0 {
+                    targetMode: DISPLAYCONFIG_TARGET_MODE {
+                        targetVideoSignalInfo: DISPLAYCONFIG_VIDEO_SIGNAL_INFO {
+                            pixelRate: pixel_rate,
+                            hSyncFreq: to_rational(h_sync),
+                            vSyncFreq: to_rational(v_sync),
+                            activeSize: DISPLAYCONFIG_2DREGION { cx: active.0, cy: active.1 },
+                            totalSize: DISPLAYCONFIG_2DREGION { cx: total.0, cy: total.1 },
+                            Anonymous: DISPLAYCONFIG_VIDEO_SIGNAL_INFO_0 { videoStandard: video_standard },
+                            scanLineOrdering: DISPLAYCONFIG_SCANLINE_ORDERING(scan_line_ordering),
+                        },
+                    },
+                },
+            ),
+            SavedModeKind::DesktopImage { source_size: (x, y), region, clip } => (
+                DISPLAYCONFIG_MODE_INFO_TYPE_DESKTOP_IMAGE,
+                DISPLAYCONFIG_MODE_INFO_0 {
+                    desktopImageInfo: DISPLAYCONFIG_DESKTOP_IMAGE_INFO {
+                        PathSourceSize: POINTL { x, y },
+                        DesktopImageRegion: to_rect(region),
+                        DesktopImageClip: to_rect(clip),
+                    },
+                },
+            ),
+        };
+        Self(DISPLAYCONFIG_MODE_INFO { infoType: info_type, id: mode.id, adapterId: luid(mode.adapter), Anonymous: info })
+    }
+}
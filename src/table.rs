@@ -0,0 +1,206 @@
+//! Aligned table and CSV output for `list`.
+
+use crate::display::DisplayInfo;
+use crate::selector::{alias_of, display_index, Aliases};
+use std::fmt::Write as _;
+use windows::Win32::System::Console::{GetConsoleMode, GetStdHandle, SetConsoleMode, CONSOLE_MODE, ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_OUTPUT_HANDLE};
+
+/// Longer friendly names are cut off with an ellipsis so they don't push the other columns out.
+pub const MAX_NAME_WIDTH: usize = 24;
+
+/// `Index` is what `set --index` takes, `#` the number Windows Settings shows for the display.
+const HEADERS: [&str; 10] = ["Index", "ID", "#", "Name", "Resolution", "Refresh", "Scaling", "Primary", "Connector", "Notes"];
+
+const SCALING_COLUMN: usize = 6;
+const NOTES_COLUMN: usize = 9;
+
+const BOLD: &str = "\x1b[1m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders `displays` as a table with a header row, one line per display.
+///
+/// With `color`, the primary display is bold and scaling that differs from the recommended
+/// value is yellow. The notes column is left out when no display has notes. Displays with an
+/// entry in `aliases` show it before their name. The index counts among `all`, so it stays the
+/// same whatever subset or order `displays` is.
+#[must_use]
+pub fn render_table(displays: &[DisplayInfo], all: &[DisplayInfo], aliases: &Aliases, color: bool) -> String {
+    let rows: Vec<[String; 10]> = displays.iter().map(|d| row(d, display_index(all, d), alias_of(aliases, d))).collect();
+    let columns = if rows.iter().any(|r| !r[NOTES_COLUMN].is_empty()) { HEADERS.len() } else { NOTES_COLUMN };
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| rows.iter().map(|r| r[i].chars().count()).chain([HEADERS[i].len()]).max().unwrap_or(0))
+        .collect();
+
+    let mut out = String::new();
+    let header: Vec<String> = HEADERS.iter().map(ToString::to_string).collect();
+    write_line(&mut out, &header, &widths, |_| None);
+    for (display, cells) in displays.iter().zip(&rows) {
+        write_line(&mut out, cells, &widths, |i| {
+            if !color {
+                None
+            } else if i == SCALING_COLUMN && display.scaling_current != display.scaling_recommended {
+                Some(YELLOW)
+            } else if display.primary {
+                Some(BOLD)
+            } else {
+                None
+            }
+        });
+    }
+    out
+}
+
+fn row(d: &DisplayInfo, index: Option<usize>, alias: Option<&str>) -> [String; 10] {
+    let refresh = match d.dynamic_refresh_range() {
+        Some((min, max)) => format!("{}Hz (dynamic {min}–{max})", d.refresh_rate),
+        None if d.refresh_rate > 0 => format!("{}Hz", d.refresh_rate),
+        None => "-".to_string(),
+    };
+    let scaling = if !d.active {
+        "-".to_string()
+    } else if d.has_custom_scaling() {
+        format!("{}% custom", d.scaling_current)
+    } else if d.scaling_recommended != d.scaling_current {
+        format!("{}% (rec {}%)", d.scaling_current, d.scaling_recommended)
+    } else {
+        format!("{}%", d.scaling_current)
+    };
+    let mut notes = Vec::new();
+    if d.internal {
+        notes.push("built-in".to_string());
+    }
+    if d.bits_per_color > 0 {
+        notes.push(format!("{}-bit {}", d.bits_per_color, d.color_encoding));
+    }
+    if let Some(nits) = d.sdr_white_level {
+        notes.push(format!("HDR (SDR {nits} nits)"));
+    }
+    if let Some(serial) = &d.serial_number {
+        notes.push(format!("serial {serial}"));
+    }
+    if d.differs_from_native() {
+        let refresh = if d.native_refresh > 0 { format!("@{}Hz", d.native_refresh) } else { String::new() };
+        notes.push(format!("native {}x{}{refresh}", d.native_width, d.native_height));
+    }
+    if !d.active {
+        notes.push("inactive".to_string());
+    } else if !d.target_available {
+        notes.push("unavailable".to_string());
+    }
+    [
+        index.map_or_else(|| "-".to_string(), |i| i.to_string()),
+        d.source_id.to_string(),
+        d.display_number().map_or_else(|| "-".to_string(), |n| n.to_string()),
+        match alias {
+            Some(alias) => format!("{alias} ({})", truncate(&d.friendly_name, MAX_NAME_WIDTH)),
+            None => truncate(&d.friendly_name, MAX_NAME_WIDTH),
+        },
+        if !d.active {
+            "-".to_string()
+        } else if d.scaling_current == 100 {
+            format!("{}x{}", d.width, d.height)
+        } else {
+            format!("{}x{} (effective {}x{})", d.width, d.height, d.effective_width(), d.effective_height())
+        },
+        refresh,
+        scaling,
+        if d.primary { "yes" } else { "" }.to_string(),
+        d.connector.to_string(),
+        notes.join(", "),
+    ]
+}
+
+/// Renders `displays` as CSV with a header line. With `edid`, the EDID fields (see
+/// [`crate::edid`]) are added as columns, empty where unknown.
+#[must_use]
+pub fn render_csv(displays: &[DisplayInfo], edid: bool) -> String {
+    let mut header = vec!["id", "number", "name", "width", "height", "refresh", "scaling", "connector", "primary", "active"];
+    if edid {
+        header.extend(["serial_number", "model_name", "manufacture_week", "manufacture_year"]);
+    }
+    let mut out = header.join(",");
+    out.push('\n');
+    for d in displays {
+        let mut cells = vec![
+            d.source_id.to_string(),
+            d.display_number().map(|n| n.to_string()).unwrap_or_default(),
+            csv_field(&d.friendly_name),
+            d.width.to_string(),
+            d.height.to_string(),
+            d.refresh_rate.to_string(),
+            d.scaling_current.to_string(),
+            d.connector.to_string(),
+            d.primary.to_string(),
+            d.active.to_string(),
+        ];
+        if edid {
+            cells.extend([
+                d.serial_number.as_deref().map(csv_field).unwrap_or_default(),
+                d.model_name.as_deref().map(csv_field).unwrap_or_default(),
+                d.manufacture_week.map(|w| w.to_string()).unwrap_or_default(),
+                d.manufacture_year.map(|y| y.to_string()).unwrap_or_default(),
+            ]);
+        }
+        out.push_str(&cells.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quotes `text` if it contains a separator, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Pads each cell before coloring it, so escape codes don't count towards the width.
+fn write_line(out: &mut String, cells: &[String], widths: &[usize], style: impl Fn(usize) -> Option<&'static str>) {
+    let mut line = String::new();
+    for (i, width) in widths.iter().enumerate() {
+        let padded = format!("{:<width$}", cells[i]);
+        let padded = if i + 1 == widths.len() { padded.trim_end().to_string() } else { padded };
+        match style(i) {
+            Some(code) => {
+                let _ = write!(line, "{code}{padded}{RESET}");
+            }
+            None => line.push_str(&padded),
+        }
+        if i + 1 < widths.len() {
+            line.push_str("  ");
+        }
+    }
+    let _ = writeln!(out, "{}", line.trim_end());
+}
+
+/// Shortens `text` to `max` characters, ending in `…` when something was cut.
+#[must_use]
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(max.saturating_sub(1)).collect();
+    short.push('…');
+    short
+}
+
+/// Turns on escape sequence handling in the console stdout is attached to, `false` if that
+/// isn't possible (old conhost, or not a console).
+#[must_use]
+pub fn enable_colors() -> bool {
+    // SAFETY: the handle comes from GetStdHandle and the mode is a valid out-pointer.
+    unsafe {
+        let Ok(handle) = GetStdHandle(STD_OUTPUT_HANDLE) else {
+            return false;
+        };
+        let mut mode = CONSOLE_MODE::default();
+        if GetConsoleMode(handle, &raw mut mode).is_err() {
+            return false;
+        }
+        mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING == ENABLE_VIRTUAL_TERMINAL_PROCESSING
+            || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING).is_ok()
+    }
+}
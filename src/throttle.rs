@@ -0,0 +1,111 @@
+//! Keeps resident modes from hammering the display driver when commands arrive in bursts,
+//! e.g. a hotkey pressed several times in a row.
+//!
+//! Both types take the current time as an argument instead of reading the clock, so the
+//! callers decide what "now" is.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Collects commands per key and releases only the latest one for each key, `window` after the
+/// first one arrived and no sooner than `min_interval` after the previous release for the key.
+#[derive(Debug)]
+pub struct Coalescer<K, T> {
+    window: Duration,
+    min_interval: Duration,
+    /// Pending commands in the order their key first arrived: (key, command, first arrival).
+    pending: Vec<(K, T, Instant)>,
+    released: HashMap<K, Instant>,
+}
+
+impl<K: Eq + Hash + Clone, T> Coalescer<K, T> {
+    #[must_use]
+    pub fn new(window: Duration, min_interval: Duration) -> Self {
+        Self { window, min_interval, pending: Vec::new(), released: HashMap::new() }
+    }
+
+    /// Queues `command` for `key`, replacing a command still pending for the key.
+    pub fn push(&mut self, key: K, command: T, now: Instant) {
+        match self.pending.iter_mut().find(|(k, _, _)| *k == key) {
+            Some(pending) => pending.1 = command,
+            None => self.pending.push((key, command, now)),
+        }
+    }
+
+    fn due_at(&self, key: &K, arrived: Instant) -> Instant {
+        let due = arrived + self.window;
+        self.released
+            .get(key)
+            .map_or(due, |released| due.max(*released + self.min_interval))
+    }
+
+    /// When the next command becomes due, `None` if nothing is pending.
+    #[must_use]
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending.iter().map(|(key, _, arrived)| self.due_at(key, *arrived)).min()
+    }
+
+    /// Removes and returns the commands due at `now`, in the order their keys first arrived.
+    pub fn pop_due(&mut self, now: Instant) -> Vec<(K, T)> {
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(key, _, arrived)| self.due_at(key, *arrived) <= now);
+        self.pending = waiting;
+        for (key, _, _) in &due {
+            self.released.insert(key.clone(), now);
+        }
+        due.into_iter().map(|(key, command, _)| (key, command)).collect()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Spaces out events per key by at least `interval`.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    interval: Duration,
+    /// When the latest granted slot for each key starts.
+    last: HashMap<u32, Instant>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last: HashMap::new() }
+    }
+
+    /// Books the next slot for `key` and returns how long after `now` it starts.
+    pub fn reserve(&mut self, key: u32, now: Instant) -> Duration {
+        let start = self.last.get(&key).map_or(now, |last| now.max(*last + self.interval));
+        self.last.insert(key, start);
+        start - now
+    }
+}
+
+static APPLY_LIMITER: Mutex<Option<RateLimiter>> = Mutex::new(None);
+
+/// Makes changes to the same display at least `interval` apart for the rest of the process,
+/// `None` to apply them right away (the default).
+pub fn set_apply_interval(interval: Option<Duration>) {
+    *APPLY_LIMITER.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = interval.map(RateLimiter::new);
+}
+
+/// Blocks until the display `source_id` may be changed again, see [`set_apply_interval`].
+pub(crate) fn wait_for_apply(source_id: u32) {
+    let delay = APPLY_LIMITER
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .as_mut()
+        .map_or(Duration::ZERO, |limiter| limiter.reserve(source_id, Instant::now()));
+    if !delay.is_zero() {
+        debug!(source_id, ?delay, "Throttling display change");
+        thread::sleep(delay);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::display::{enumerate_displays, DisplayInfo, Result};
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{debug, warn};
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{ERROR_CLASS_ALREADY_EXISTS, HANDLE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Power::{GetSystemPowerStatus, RegisterPowerSettingNotification, SYSTEM_POWER_STATUS};
+use windows::Win32::System::SystemServices::GUID_ACDC_POWER_SOURCE;
+use windows::Win32::UI::WindowsAndMessaging::{CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, PostMessageW, PostQuitMessage, RegisterClassW, TranslateMessage, DEVICE_NOTIFY_WINDOW_HANDLE, MSG, PBT_APMRESUMEAUTOMATIC, PBT_POWERSETTINGCHANGE, WINDOW_EX_STYLE, WM_CLOSE, WM_DESTROY, WM_DEVICECHANGE, WM_DISPLAYCHANGE, WM_POWERBROADCAST, WNDCLASSW, WNDPROC, WS_OVERLAPPED};
+
+/// Something the watch loop should react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// Resolution or topology changed (`WM_DISPLAYCHANGE`).
+    DisplayChanged,
+    /// The system switched between AC and battery power, or reported the current source.
+    PowerSourceChanged,
+    /// The system woke up from sleep or hibernation.
+    Resumed,
+}
+
+/// Where the system draws power from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+/// The current power source; systems without a battery, or that can't tell, count as AC.
+///
+/// # Errors
+///
+/// Returns an error if the power status can't be read.
+pub fn power_source() -> Result<PowerSource> {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    // SAFETY: status is a valid out-pointer.
+    unsafe { GetSystemPowerStatus(&raw mut status)? };
+    Ok(if status.ACLineStatus == 0 { PowerSource::Battery } else { PowerSource::Ac })
+}
+
+thread_local! {
+    static EVENTS: RefCell<Option<Sender<WatchEvent>>> = const { RefCell::new(None) };
+    static DEVICE_SIGNALS: RefCell<Option<Sender<()>>> = const { RefCell::new(None) };
+}
+
+/// Starts a background thread that owns a hidden window and forwards display change, power
+/// source and resume notifications.
+///
+/// The window is a regular top-level window that is never shown: message-only windows don't
+/// receive the `WM_DISPLAYCHANGE` broadcast. The thread runs for the rest of the process.
+///
+/// # Errors
+///
+/// Returns an error if the window can't be created.
+pub fn watch_events() -> Result<Receiver<WatchEvent>> {
+    let (events_tx, events_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        EVENTS.with(|events| *events.borrow_mut() = Some(events_tx));
+        let created = create_listener_window();
+        let ok = created.is_ok();
+        let _ = ready_tx.send(created.map(|_| ()));
+        if ok {
+            run_message_loop();
+        }
+    });
+
+    ready_rx
+        .recv()
+        .unwrap_or_else(|_| Err(windows::core::Error::from_thread().into()))?;
+    Ok(events_rx)
+}
+
+fn create_listener_window() -> Result<HWND> {
+    unsafe {
+        let hwnd = create_hidden_window(w!("display-tuner-listener"), Some(listener_proc))?;
+        // The handle stays registered until the process exits.
+        let setting = GUID_ACDC_POWER_SOURCE;
+        RegisterPowerSettingNotification(HANDLE(hwnd.0), &raw const setting, DEVICE_NOTIFY_WINDOW_HANDLE)?;
+        debug!("Listening for display and power source changes");
+        Ok(hwnd)
+    }
+}
+
+/// Creates a never-shown top-level window of class `class_name`, registering the class on
+/// first use.
+///
+/// # Safety
+///
+/// `class_name` must be the same static string on every call, and `proc` must be a valid
+/// window procedure.
+unsafe fn create_hidden_window(class_name: PCWSTR, proc: WNDPROC) -> Result<HWND> {
+    unsafe {
+        let instance = GetModuleHandleW(None)?;
+        let class = WNDCLASSW {
+            lpfnWndProc: proc,
+            hInstance: instance.into(),
+            lpszClassName: class_name,
+            ..WNDCLASSW::default()
+        };
+        if RegisterClassW(&raw const class) == 0 {
+            let err = windows::core::Error::from_thread();
+            if err.code() != ERROR_CLASS_ALREADY_EXISTS.to_hresult() {
+                return Err(err.into());
+            }
+        }
+
+        Ok(CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            class_name,
+            w!("display-tuner"),
+            WS_OVERLAPPED,
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            Some(instance.into()),
+            None,
+        )?)
+    }
+}
+
+fn run_message_loop() {
+    let mut msg = MSG::default();
+    unsafe {
+        while GetMessageW(&raw mut msg, None, 0, 0).as_bool() {
+            let _ = TranslateMessage(&raw const msg);
+            DispatchMessageW(&raw const msg);
+        }
+    }
+}
+
+fn send(event: WatchEvent) {
+    EVENTS.with(|events| {
+        if let Some(tx) = events.borrow().as_ref()
+            && tx.send(event).is_err()
+        {
+            warn!("Watch event receiver dropped");
+        }
+    });
+}
+
+extern "system" fn listener_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg == WM_DISPLAYCHANGE {
+        send(WatchEvent::DisplayChanged);
+    } else if msg == WM_POWERBROADCAST && wparam.0 == PBT_POWERSETTINGCHANGE as usize {
+        send(WatchEvent::PowerSourceChanged);
+    } else if msg == WM_POWERBROADCAST && wparam.0 == PBT_APMRESUMEAUTOMATIC as usize {
+        send(WatchEvent::Resumed);
+    }
+    unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+}
+
+/// A change to the connected displays, reported by [`DisplayWatcher`].
+// Events go out one at a time, so boxing the display wouldn't save anything.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayEvent {
+    /// A display showed up, as it is now.
+    DisplayAdded(DisplayInfo),
+    /// The display with this device path is gone.
+    DisplayRemoved { device_path: String },
+    /// Resolution, refresh rate, rotation, position, scaling, primary or active state of a
+    /// display changed.
+    ModeChanged { before: DisplayInfo, after: DisplayInfo },
+}
+
+/// The events that turn the enumeration `before` into `after`: removals first, then changes
+/// and additions in the order of `after`. Displays are matched by device path.
+#[must_use]
+pub fn diff_displays(before: &[DisplayInfo], after: &[DisplayInfo]) -> Vec<DisplayEvent> {
+    let find = |displays: &[DisplayInfo], path: &str| displays.iter().find(|d| d.device_path == path).cloned();
+    let mut events: Vec<DisplayEvent> = before
+        .iter()
+        .filter(|old| find(after, &old.device_path).is_none())
+        .map(|old| DisplayEvent::DisplayRemoved { device_path: old.device_path.clone() })
+        .collect();
+    for new in after {
+        match find(before, &new.device_path) {
+            None => events.push(DisplayEvent::DisplayAdded(new.clone())),
+            Some(old) if mode_key(&old) != mode_key(new) => {
+                events.push(DisplayEvent::ModeChanged { before: old, after: new.clone() });
+            }
+            Some(_) => {}
+        }
+    }
+    events
+}
+
+/// What [`DisplayEvent::ModeChanged`] looks at.
+fn mode_key(display: &DisplayInfo) -> impl PartialEq + use<> {
+    (
+        (display.width, display.height, display.refresh_rate, display.rotation),
+        (display.position_x, display.position_y),
+        (display.scaling_current, display.primary, display.active),
+    )
+}
+
+/// Reports displays coming, going and changing mode on a channel, from a hidden window on a
+/// thread of its own; no polling needed. Dropping the watcher closes the window and stops its
+/// threads.
+///
+/// The window is a top-level one that is never shown, since message-only windows miss the
+/// `WM_DISPLAYCHANGE` and `WM_DEVICECHANGE` broadcasts. Docking or waking a monitor sends
+/// bursts of these; the displays are enumerated and compared once the burst has been quiet for
+/// the coalescing interval.
+pub struct DisplayWatcher {
+    events: Receiver<DisplayEvent>,
+    /// The window, as an address since `HWND` isn't `Send`.
+    window: usize,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl DisplayWatcher {
+    /// Enumerates the displays as the starting point and begins watching.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the displays can't be enumerated or the window can't be created.
+    pub fn start(coalesce: Duration) -> Result<Self> {
+        let snapshot = enumerate_displays()?;
+        let (events_tx, events_rx) = mpsc::channel();
+        let (signals_tx, signals_rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let window_thread = thread::spawn(move || {
+            DEVICE_SIGNALS.with(|signals| *signals.borrow_mut() = Some(signals_tx));
+            // SAFETY: the class name is a static string and the procedure a valid one.
+            let created = unsafe { create_hidden_window(w!("display-tuner-watcher"), Some(watcher_proc)) };
+            let ok = created.is_ok();
+            let _ = ready_tx.send(created.map(|hwnd| hwnd.0 as usize));
+            if ok {
+                run_message_loop();
+            }
+        });
+        let window = ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(windows::core::Error::from_thread().into()))?;
+        let differ_thread = thread::spawn(move || diff_on_signals(&signals_rx, &events_tx, snapshot, coalesce));
+        debug!("Watching for display changes");
+
+        Ok(Self { events: events_rx, window, threads: vec![window_thread, differ_thread] })
+    }
+
+    /// The events, in the order they happened.
+    #[must_use]
+    pub fn events(&self) -> &Receiver<DisplayEvent> {
+        &self.events
+    }
+}
+
+impl Drop for DisplayWatcher {
+    fn drop(&mut self) {
+        // The window goes away on WM_CLOSE and ends the message loop; that drops the signal
+        // sender, which in turn ends the diffing thread.
+        // SAFETY: the window belongs to this watcher and lives until its thread ends.
+        if let Err(err) = unsafe { PostMessageW(Some(HWND(self.window as *mut c_void)), WM_CLOSE, WPARAM(0), LPARAM(0)) } {
+            warn!(%err, "Can't close the display watcher window");
+            return;
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Enumerates and diffs after each burst of signals, until the window thread is gone or no one
+/// listens for events.
+fn diff_on_signals(signals: &Receiver<()>, events: &Sender<DisplayEvent>, mut snapshot: Vec<DisplayInfo>, coalesce: Duration) {
+    while signals.recv().is_ok() {
+        loop {
+            match signals.recv_timeout(coalesce) {
+                Ok(()) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        let current = match enumerate_displays() {
+            Ok(displays) => displays,
+            Err(err) => {
+                warn!(%err, "Can't enumerate displays after a change");
+                continue;
+            }
+        };
+        for event in diff_displays(&snapshot, &current) {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+        snapshot = current;
+    }
+}
+
+extern "system" fn watcher_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg == WM_DISPLAYCHANGE || msg == WM_DEVICECHANGE {
+        DEVICE_SIGNALS.with(|signals| {
+            if let Some(tx) = signals.borrow().as_ref() {
+                let _ = tx.send(());
+            }
+        });
+    } else if msg == WM_DESTROY {
+        unsafe { PostQuitMessage(0) };
+        return LRESULT(0);
+    }
+    unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "profiles")]
+
+use display_tuner::a11y::{large_text_scaling, LargeText, RecordedScaling};
+use display_tuner::display::DisplayInfo;
+
+fn display(path: &str, source_id: u32, current: i32, recommended: i32, max: i32) -> DisplayInfo {
+    DisplayInfo {
+        device_path: path.to_string(),
+        friendly_name: format!("Monitor {source_id}"),
+        source_id,
+        active: true,
+        scaling_current: current,
+        scaling_recommended: recommended,
+        scaling_max: max,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_large_text_is_two_steps_above_recommended() {
+    assert_eq!(large_text_scaling(&display("A", 1, 100, 150, 300)), 200);
+    // From wherever the display is now, and clamped to its largest scaling.
+    assert_eq!(large_text_scaling(&display("A", 1, 250, 100, 300)), 150);
+    assert_eq!(large_text_scaling(&display("A", 1, 100, 175, 200)), 200);
+}
+
+#[test]
+fn test_on_records_the_scaling_from_before_once() {
+    let mut state = LargeText::default();
+    let displays = [display("A", 1, 125, 150, 300), DisplayInfo { active: false, ..display("B", 2, 100, 100, 200) }];
+    let targets = state.turn_on(&displays);
+    assert_eq!(targets.iter().map(|&(d, scaling)| (d.source_id, scaling)).collect::<Vec<_>>(), [(1, 200)]);
+    assert!(state.is_on());
+
+    // On again, after it was applied: the recorded scaling stays the original one.
+    let applied = [display("A", 1, 200, 150, 300)];
+    state.turn_on(&applied);
+    assert_eq!(state.displays, [RecordedScaling { device_path: "A".to_string(), friendly_name: "Monitor 1".to_string(), scaling: 125 }]);
+}
+
+#[test]
+fn test_off_restores_the_displays_still_present() {
+    let mut state = LargeText::default();
+    state.turn_on(&[display("A", 1, 125, 150, 300), display("B", 2, 100, 100, 200)]);
+    // Re-enumerated with other source ids, B unplugged and C new.
+    let now = [display("C", 1, 100, 100, 200), display("A", 2, 200, 150, 300)];
+    let (restore, missing) = state.turn_off(&now);
+    assert_eq!(restore.iter().map(|&(d, scaling)| (d.source_id, scaling)).collect::<Vec<_>>(), [(2, 125)]);
+    assert_eq!(missing.iter().map(|r| (r.device_path.as_str(), r.scaling)).collect::<Vec<_>>(), [("B", 100)]);
+    assert!(!state.is_on());
+}
@@ -0,0 +1,54 @@
+use display_tuner::apply_log::{record, set_apply_log, ApplyLogEntry, MAX_LOG_BYTES};
+use display_tuner::display::{DisplayConfig, DisplayError, DisplayInfo};
+use std::fs;
+use std::time::Duration;
+
+fn display() -> DisplayInfo {
+    DisplayInfo {
+        source_id: 4,
+        device_path: r"\\?\DISPLAY#DEL41A8#5&1".to_string(),
+        width: 1920,
+        height: 1080,
+        scaling_current: 100,
+        ..DisplayInfo::default()
+    }
+}
+
+const REQUESTED: DisplayConfig = DisplayConfig { width: 2560, height: 1440, scaling: 125 };
+
+#[test]
+fn test_entry_records_error_code() {
+    let err = DisplayError::SetDisplayConfig(87);
+    let entry = ApplyLogEntry::new(&display(), &REQUESTED, Err(&err), Duration::from_millis(42));
+    assert!(!entry.ok);
+    assert_eq!(entry.error_code, Some(87));
+    assert_eq!(entry.duration_ms, 42);
+    assert_eq!(entry.actual, None);
+
+    let json = serde_json::to_string(&entry).unwrap();
+    assert!(json.contains(r#""requested":{"width":2560,"height":1440,"scaling":125}"#));
+    assert!(!json.contains("actual"));
+    assert_eq!(serde_json::from_str::<ApplyLogEntry>(&json).unwrap(), entry);
+}
+
+#[test]
+fn test_record_creates_directories_and_rotates() {
+    let dir = std::env::temp_dir().join(format!("display-tuner-log-{}", std::process::id()));
+    let path = dir.join("nested").join("applies.jsonl");
+    set_apply_log(Some(path.clone()));
+
+    let entry = ApplyLogEntry::new(&display(), &REQUESTED, Ok(None), Duration::ZERO);
+    record(&entry);
+    record(&entry);
+    let text = fs::read_to_string(&path).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    assert!(text.lines().all(|line| serde_json::from_str::<ApplyLogEntry>(line).unwrap().ok));
+
+    fs::write(&path, vec![b'x'; usize::try_from(MAX_LOG_BYTES).unwrap()]).unwrap();
+    record(&entry);
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    assert!(dir.join("nested").join("applies.jsonl.1").exists());
+
+    set_apply_log(None);
+    let _ = fs::remove_dir_all(dir);
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "profiles")]
+
+use display_tuner::auto_revert::{next_deadline, PendingRevert, POLL_INTERVAL, SLEEP_GAP};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_window_starts_over_after_sleep() {
+    let window = Duration::from_secs(20);
+    let start = SystemTime::now();
+    let deadline = start + window;
+
+    // Polls on time keep the deadline.
+    let now = start + POLL_INTERVAL;
+    assert_eq!(next_deadline(deadline, window, start, now), deadline);
+    assert_eq!(next_deadline(deadline, window, now, now + POLL_INTERVAL + SLEEP_GAP - Duration::from_millis(1)), deadline);
+
+    // An hour asleep past the deadline: a full window from the wake-up instead of reverting.
+    let woke = now + Duration::from_hours(1);
+    assert_eq!(next_deadline(deadline, window, now, woke), woke + window);
+
+    // A clock set back doesn't count as sleep.
+    assert_eq!(next_deadline(deadline, window, now, start - Duration::from_mins(1)), deadline);
+}
+
+#[test]
+fn test_pending_revert_round_trips() {
+    let pending = PendingRevert { id: 42, window_secs: 20, state: PathBuf::from(r"C:\Users\me\AppData\Roaming\display-tuner\auto-revert-state.json") };
+    let json = serde_json::to_string(&pending).unwrap();
+    assert_eq!(serde_json::from_str::<PendingRevert>(&json).unwrap(), pending);
+}
@@ -0,0 +1,155 @@
+use display_tuner::batch::{plan_batch, BatchPlan, ClauseAction, ClauseSettings, DisplayClause, Step};
+use display_tuner::display::{DisplayError, DisplayInfo, DisplayMode, RefreshRate, Rotation, SourceMode};
+use std::collections::BTreeMap;
+
+mod common;
+use common::display;
+
+fn displays() -> Vec<DisplayInfo> {
+    vec![display(1, 2560, 1440, 125, 200), display(2, 1920, 1080, 100, 150)]
+}
+
+fn modes() -> BTreeMap<u32, Vec<DisplayMode>> {
+    let mode = |width, height, refresh| DisplayMode { width, height, refresh };
+    BTreeMap::from([
+        (1, vec![mode(2560, 1440, 60), mode(2560, 1440, 144), mode(1920, 1080, 60), mode(1920, 1080, 120)]),
+        (2, vec![mode(1920, 1080, 60)]),
+    ])
+}
+
+fn plan(clauses: &[&str]) -> BatchPlan {
+    let clauses: Vec<DisplayClause> = clauses.iter().map(|c| c.parse().unwrap()).collect();
+    plan_batch(&clauses, &displays(), &modes())
+}
+
+fn problems(plan: &BatchPlan) -> Vec<Vec<&str>> {
+    plan.displays.iter().map(|d| d.problems.iter().map(String::as_str).collect()).collect()
+}
+
+#[test]
+fn test_parse_clauses() {
+    let clause: DisplayClause = "1:res=1920x1080,refresh=max,scaling=100".parse().unwrap();
+    let expected = ClauseSettings {
+        res: Some((1920, 1080)),
+        refresh: Some(RefreshRate::Max),
+        scaling: Some(100),
+        rotate: None,
+    };
+    assert_eq!(clause, DisplayClause { source_id: 1, action: ClauseAction::Set(expected) });
+
+    let clause: DisplayClause = " 2 : Disable ".parse().unwrap();
+    assert_eq!(clause, DisplayClause { source_id: 2, action: ClauseAction::Disable });
+
+    let clause: DisplayClause = "3:Rotate=90, refresh=144Hz, scaling=150%".parse().unwrap();
+    let expected = ClauseSettings {
+        res: None,
+        refresh: Some(RefreshRate::Hz(144)),
+        scaling: Some(150),
+        rotate: Some(Rotation::Rotate90),
+    };
+    assert_eq!(clause, DisplayClause { source_id: 3, action: ClauseAction::Set(expected) });
+
+    // Commas separate settings, so localized values use periods.
+    let clause: DisplayClause = "4:res=3.840x2.160,refresh=59.94,scaling=1.5x".parse().unwrap();
+    let expected = ClauseSettings {
+        res: Some((3840, 2160)),
+        refresh: Some(RefreshRate::Hz(59)),
+        scaling: Some(150),
+        rotate: None,
+    };
+    assert_eq!(clause, DisplayClause { source_id: 4, action: ClauseAction::Set(expected) });
+}
+
+#[test]
+fn test_parse_errors() {
+    let cases = [
+        ("1", "expected ID:KEY=VALUE,... or ID:disable"),
+        ("x:disable", "expected a display id before `:`, found `x`"),
+        ("1:res", "expected KEY=VALUE, found `res`"),
+        ("1:res=1920", "expected res=WIDTHxHEIGHT, found `1920`"),
+        ("1:refresh=fast", "expected refresh=HZ, max or min, found `fast`"),
+        ("1:scaling=0", "expected scaling=PERCENT, found `0`"),
+        ("1:rotate=45", "expected rotate=0, 90, 180 or 270, found `45`"),
+        ("1:scaling=100,scaling=125", "`scaling` is given twice"),
+        ("1:scaling=100,disable", "`disable` can't be combined with settings"),
+        ("1:colour=red", "unknown key `colour`, expected one of res, refresh, scaling, rotate, or disable alone"),
+    ];
+    for (clause, expected) in cases {
+        match clause.parse::<DisplayClause>() {
+            Err(DisplayError::InvalidClause { clause: text, reason }) => {
+                assert_eq!((text.as_str(), reason.as_str()), (clause, expected));
+            }
+            other => panic!("{clause:?} parsed as {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_plan_gaming_mode() {
+    let plan = plan(&["1:res=1920x1080,refresh=max,scaling=100", "2:disable"]);
+    assert_eq!(plan.rejected(), 0);
+
+    let first = &displays()[0];
+    let source = SourceMode { width: 1920, height: 1080, ..SourceMode::of(first) };
+    assert_eq!(
+        plan.displays[0].step,
+        Some(Step::Change { source: Some(source), refresh: Some(120), scaling: Some(100) })
+    );
+    assert_eq!(plan.displays[0].to_string(), "1920x1080, 120Hz, 100%");
+    assert_eq!(plan.displays[1].step, Some(Step::Disable));
+    assert_eq!(plan.displays[1].to_string(), "turn off");
+    assert!(plan.turns_off(2));
+    assert!(!plan.turns_off(1));
+    assert!(plan.changes_mode(1));
+    assert!(plan.changes_mode(2));
+    assert!(!self::plan(&["1:scaling=100"]).changes_mode(1));
+}
+
+#[test]
+fn test_plan_checks_every_clause() {
+    let plan = plan(&["1:res=1280x1024", "1:scaling=100", "2:refresh=144", "2:scaling=200", "9:disable"]);
+    assert_eq!(plan.rejected(), 5);
+    assert!(plan.displays.iter().all(|d| d.step.is_none()));
+    assert_eq!(
+        problems(&plan),
+        vec![
+            vec!["display 1 doesn't support 1280x1024 at 0°"],
+            vec!["display 1 appears in more than one clause"],
+            vec!["display 2 doesn't offer 144Hz at 1920x1080"],
+            vec!["display 2 appears in more than one clause", "display 2 supports 100-150% scaling"],
+            vec!["Display 9 not found"],
+        ]
+    );
+}
+
+#[test]
+fn test_plan_keeps_a_display_on() {
+    let plan = plan(&["1:disable", "2:disable"]);
+    assert_eq!(
+        problems(&plan),
+        vec![vec!["turning it off would leave no display on"], vec!["turning it off would leave no display on"]]
+    );
+}
+
+#[test]
+fn test_plan_follows_the_rotation() {
+    let second = &displays()[1];
+    let portrait = SourceMode { width: 1080, height: 1920, rotation: Rotation::Rotate90, ..SourceMode::of(second) };
+    let expected = Some(Step::Change { source: Some(portrait), refresh: None, scaling: None });
+
+    let turned = plan(&["2:rotate=90"]);
+    assert_eq!(turned.displays[0].step, expected);
+    assert_eq!(turned.displays[0].to_string(), "1080x1920, rotated 90°");
+    assert!(turned.displays[0].notes.is_empty());
+
+    let swapped = plan(&["2:rotate=90,res=1920x1080"]);
+    assert_eq!(swapped.displays[0].step, expected);
+    assert_eq!(swapped.displays[0].notes, vec!["1920x1080 isn't supported at 90°, using 1080x1920"]);
+}
+
+#[test]
+fn test_plan_skips_what_is_already_set() {
+    let plan = plan(&["1:res=2560x1440,refresh=60,scaling=125"]);
+    assert!(plan.displays[0].unchanged());
+    assert_eq!(plan.displays[0].to_string(), "already set");
+}
@@ -0,0 +1,61 @@
+use display_tuner::blur::{group_blurry_windows, BlurryProcess, DpiAwareness, TopLevelWindow};
+use display_tuner::display::DisplayInfo;
+
+fn display(source_id: u32, gdi_name: &str, scaling: i32) -> DisplayInfo {
+    DisplayInfo {
+        source_id,
+        friendly_name: format!("Display {source_id}"),
+        gdi_device_name: gdi_name.to_string(),
+        scaling_current: scaling,
+        active: true,
+        ..DisplayInfo::default()
+    }
+}
+
+fn window(title: &str, process: Option<&str>, monitor: &str, awareness: DpiAwareness) -> TopLevelWindow {
+    TopLevelWindow {
+        title: title.to_string(),
+        process_id: 42,
+        process_name: process.map(str::to_string),
+        monitor_device: monitor.to_string(),
+        awareness,
+    }
+}
+
+#[test]
+fn test_groups_unaware_windows_by_display_and_process() {
+    let displays = [display(1, r"\\.\DISPLAY1", 150), display(2, r"\\.\DISPLAY2", 100), display(3, r"\\.\DISPLAY3", 125)];
+    let windows = [
+        window("Setup", Some("setup.exe"), r"\\.\DISPLAY1", DpiAwareness::Unaware),
+        window("Report.xlsx", Some("legacy.exe"), r"\\.\display1", DpiAwareness::System),
+        window("Other.xlsx", Some("legacy.exe"), r"\\.\DISPLAY1", DpiAwareness::System),
+        window("Browser", Some("browser.exe"), r"\\.\DISPLAY1", DpiAwareness::PerMonitorV2),
+        window("Old tool", Some("tool.exe"), r"\\.\DISPLAY2", DpiAwareness::Unaware),
+        window("Service", None, r"\\.\DISPLAY1", DpiAwareness::UnawareGdiScaled),
+        window("Locked", Some("locked.exe"), r"\\.\DISPLAY1", DpiAwareness::Unknown),
+    ];
+
+    let report = group_blurry_windows(&displays, &windows);
+    assert_eq!(report.iter().map(|d| d.source_id).collect::<Vec<_>>(), [1, 3]);
+    assert_eq!(
+        report[0].processes,
+        vec![
+            BlurryProcess {
+                process: "legacy.exe".to_string(),
+                awareness: DpiAwareness::System,
+                windows: vec!["Report.xlsx".to_string(), "Other.xlsx".to_string()],
+            },
+            BlurryProcess { process: "pid 42".to_string(), awareness: DpiAwareness::UnawareGdiScaled, windows: vec!["Service".to_string()] },
+            BlurryProcess { process: "setup.exe".to_string(), awareness: DpiAwareness::Unaware, windows: vec!["Setup".to_string()] },
+        ]
+    );
+    assert!(report[1].processes.is_empty());
+    assert_eq!(report[1].to_string(), "Display 3 (id 3) at 125%: no blurry windows");
+}
+
+#[test]
+fn test_awareness_serializes_in_kebab_case() {
+    let json = serde_json::to_string(&[DpiAwareness::UnawareGdiScaled, DpiAwareness::PerMonitorV2]).unwrap();
+    assert_eq!(json, r#"["unaware-gdi-scaled","per-monitor-v2"]"#);
+    assert!(DpiAwareness::PerMonitor.is_per_monitor() && !DpiAwareness::System.is_per_monitor());
+}
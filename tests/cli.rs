@@ -0,0 +1,104 @@
+#![cfg(feature = "cli")]
+//! What the binary prints for programs with `--json-errors` and `--format json`, on failures
+//! that come before any display is touched.
+
+use display_tuner::report::{CommandReport, Outcome, USAGE_ERROR};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// An empty settings directory of its own for a test, standing in for `APPDATA`.
+fn app_data(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("display-tuner-cli-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("display-tuner")).unwrap();
+    dir
+}
+
+fn run(app_data: Option<&Path>, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_display-tuner"));
+    command.args(args);
+    match app_data {
+        Some(dir) => command.env("APPDATA", dir),
+        None => command.env_remove("APPDATA"),
+    };
+    command.output().unwrap()
+}
+
+/// Stdout as the single JSON document it has to be, checked against the exit code.
+fn outcome(output: &Output) -> Outcome {
+    let outcome: Outcome = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(i32::from(outcome.exit_code)));
+    outcome
+}
+
+#[test]
+fn test_json_errors_reports_a_display_error() {
+    let dir = app_data("missing-profile");
+    let outcome = outcome(&run(Some(&dir), &["--json-errors", "profile", "apply", "nowhere"]));
+    assert!(!outcome.ok);
+    assert_eq!(outcome.exit_code, 1);
+    assert_eq!(outcome.result, None);
+    let error = outcome.error.unwrap();
+    assert_eq!(error.code, "profile_not_found");
+    assert_eq!(error.message, "Profile 'nowhere' not found");
+    assert_eq!(error.details["profile"], "nowhere");
+}
+
+#[test]
+fn test_json_errors_reports_a_missing_app_data() {
+    let outcome = outcome(&run(None, &["--json-errors", "profile", "list"]));
+    assert!(!outcome.ok);
+    assert_eq!(outcome.error.unwrap().code, "no_app_data");
+}
+
+#[test]
+fn test_json_errors_reports_a_usage_error() {
+    let dir = app_data("usage");
+    let outcome = outcome(&run(Some(&dir), &["--json-errors", "set", "--id", "1", "--scaling", "lots"]));
+    assert!(!outcome.ok);
+    assert_eq!(outcome.exit_code, 2);
+    let error = outcome.error.unwrap();
+    assert_eq!(error.code, USAGE_ERROR);
+    assert!(error.message.contains("lots"), "{}", error.message);
+    assert!(error.details["argument"].as_str().unwrap().starts_with("--scaling"));
+}
+
+#[test]
+fn test_json_errors_keeps_text_off_stdout() {
+    let dir = app_data("success");
+    let output = run(Some(&dir), &["--json-errors", "config", "path"]);
+    let outcome = outcome(&output);
+    assert!(outcome.ok);
+    assert_eq!(outcome.exit_code, 0);
+    assert_eq!(outcome.result, Some(serde_json::Value::Null));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("config.toml"));
+}
+
+#[test]
+fn test_format_json_reports_errors_before_the_command_runs() {
+    let dir = app_data("broken-config");
+    fs::write(dir.join("display-tuner").join("config.toml"), "[safety]\nretries = \"many\"\n").unwrap();
+    let output = run(Some(&dir), &["set", "--id", "1", "--scaling", "150", "--format", "json"]);
+    let report: CommandReport = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!report.ok);
+    let error = report.error.unwrap();
+    assert_eq!(error.code, "invalid_config");
+    assert_eq!(error.details["line"], 2);
+
+    // The same failure with --json-errors.
+    let outcome = outcome(&run(Some(&dir), &["--json-errors", "set", "--id", "1", "--scaling", "150", "--format", "json"]));
+    assert_eq!(outcome.error.unwrap().code, "invalid_config");
+}
+
+#[test]
+fn test_arrange_order_parses_next_to_the_global_apply_order() {
+    let dir = app_data("arrange-order");
+    // Whatever the displays, the arguments parse: no usage error and no panic.
+    let arranged = outcome(&run(Some(&dir), &["--json-errors", "arrange", "--preset", "row", "--order", "2,1,3", "--dry-run"]));
+    assert_ne!(arranged.error.map(|e| e.code), Some(USAGE_ERROR.to_string()));
+
+    let ordered = outcome(&run(Some(&dir), &["--json-errors", "--apply-order", "sideways", "config", "path"]));
+    assert_eq!(ordered.error.unwrap().code, USAGE_ERROR);
+}
@@ -0,0 +1,202 @@
+#![cfg(feature = "daemon")]
+
+use display_tuner::client::{DaemonClient, DisplayConfigDelta, Selector};
+use display_tuner::daemon::{Daemon, ProgressMessage, Request, Response, PROTOCOL_VERSION};
+use display_tuner::display::{DisplayCapabilities, DisplayError, DisplayInfo};
+use display_tuner::progress::{DisplayProgress, PhaseStatus};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+/// An in-process pipe: every request line written is answered by `handler`.
+struct Loopback<F> {
+    handler: F,
+    sent: Vec<u8>,
+    replies: VecDeque<u8>,
+}
+
+fn loopback<F: FnMut(&Request) -> Response>(handler: F) -> Loopback<F> {
+    Loopback { handler, sent: Vec::new(), replies: VecDeque::new() }
+}
+
+impl<F: FnMut(&Request) -> Response> Write for Loopback<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sent.extend_from_slice(buf);
+        while let Some(end) = self.sent.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.sent.drain(..=end).collect();
+            let request = serde_json::from_slice(&line).map_err(io::Error::other)?;
+            let response = (self.handler)(&request);
+            self.replies.extend(serde_json::to_vec(&response).map_err(io::Error::other)?);
+            self.replies.push_back(b'\n');
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F> Read for Loopback<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.replies.read(buf)
+    }
+}
+
+fn displays() -> Vec<DisplayInfo> {
+    vec![DisplayInfo { source_id: 1, width: 2560, height: 1440, scaling_current: 125, ..DisplayInfo::default() }]
+}
+
+#[test]
+fn test_handshake_and_list() {
+    let mut daemon = Daemon::default();
+    let stream = loopback(move |request| match request {
+        // The real handshake, which doesn't touch the displays.
+        Request::Hello { .. } => daemon.handle(request),
+        Request::List => Response { ok: true, displays: displays(), ..Response::default() },
+        other => panic!("unexpected request {other:?}"),
+    });
+    let mut client = DaemonClient::over(stream).unwrap();
+    assert_eq!(client.list().unwrap(), displays());
+}
+
+#[test]
+fn test_capabilities_pair_with_displays() {
+    let capabilities = DisplayCapabilities { resolutions: 12, ddc_ci: true, ..DisplayCapabilities::default() };
+    let reply = capabilities.clone();
+    let stream = loopback(move |request| match request {
+        Request::Hello { version } => Response { ok: true, version: Some(*version), ..Response::default() },
+        Request::Capabilities => Response { ok: true, displays: displays(), capabilities: vec![reply.clone()], ..Response::default() },
+        other => panic!("unexpected request {other:?}"),
+    });
+    let mut client = DaemonClient::over(stream).unwrap();
+    assert_eq!(client.capabilities().unwrap(), vec![(displays()[0].clone(), capabilities)]);
+}
+
+#[test]
+fn test_set_maps_selector_and_delta() {
+    let stream = loopback(|request| match request {
+        Request::Hello { version } => Response { ok: true, version: Some(*version), ..Response::default() },
+        Request::Set { id, handle, width, height, scaling } => {
+            assert_eq!((*id, *handle, *width, *height, *scaling), (None, None, None, None, Some(150)));
+            Response { ok: true, displays: displays(), ..Response::default() }
+        }
+        other => panic!("unexpected request {other:?}"),
+    });
+    let mut client = DaemonClient::over(stream).unwrap();
+    let delta = DisplayConfigDelta { scaling: Some(150), ..DisplayConfigDelta::default() };
+    assert_eq!(client.set(Selector::All, delta).unwrap(), displays());
+}
+
+#[test]
+fn test_daemon_errors_are_surfaced() {
+    let stream = loopback(|request| match request {
+        Request::Hello { version } => Response { ok: true, version: Some(*version), ..Response::default() },
+        _ => Response { ok: false, error: Some("Profile 'desk' not found".to_string()), ..Response::default() },
+    });
+    let mut client = DaemonClient::over(stream).unwrap();
+    match client.apply_profile("desk") {
+        Err(DisplayError::Daemon(message)) => assert_eq!(message, "Profile 'desk' not found"),
+        other => panic!("expected a daemon error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_version_mismatch_is_a_clear_error() {
+    let newer = loopback(|_: &Request| Response {
+        ok: false,
+        error: Some("Protocol version 1 isn't supported".to_string()),
+        version: Some(PROTOCOL_VERSION + 1),
+        ..Response::default()
+    });
+    assert!(matches!(
+        DaemonClient::over(newer),
+        Err(DisplayError::ProtocolMismatch { client: PROTOCOL_VERSION, daemon }) if daemon == PROTOCOL_VERSION + 1
+    ));
+
+    // A daemon from before versioning doesn't know `hello`.
+    let older = loopback(|_: &Request| Response {
+        ok: false,
+        error: Some("Invalid request: unknown variant `hello`".to_string()),
+        ..Response::default()
+    });
+    assert!(matches!(
+        DaemonClient::over(older),
+        Err(DisplayError::ProtocolMismatch { client: PROTOCOL_VERSION, daemon: 0 })
+    ));
+}
+
+#[test]
+fn test_daemon_rejects_other_versions() {
+    let mut daemon = Daemon::default();
+    let accepted = daemon.handle(&Request::Hello { version: PROTOCOL_VERSION });
+    assert!(accepted.ok);
+    assert_eq!(accepted.version, Some(PROTOCOL_VERSION));
+
+    let rejected = daemon.handle(&Request::Hello { version: PROTOCOL_VERSION + 1 });
+    assert!(!rejected.ok);
+    assert_eq!(rejected.version, Some(PROTOCOL_VERSION));
+    assert!(rejected.error.is_some());
+}
+
+#[test]
+fn test_handles_are_paired_and_forwarded() {
+    let stream = loopback(|request| match request {
+        Request::Hello { version } => Response { ok: true, version: Some(*version), ..Response::default() },
+        Request::List => Response { ok: true, displays: displays(), handles: vec![4], ..Response::default() },
+        Request::Set { id, handle, .. } => {
+            assert_eq!((*id, *handle), (None, Some(4)));
+            Response { ok: true, displays: displays(), handles: vec![4], ..Response::default() }
+        }
+        other => panic!("unexpected request {other:?}"),
+    });
+    let mut client = DaemonClient::over(stream).unwrap();
+    assert_eq!(client.handles().unwrap(), vec![(4, displays()[0].clone())]);
+    let delta = DisplayConfigDelta { width: Some(1920), ..DisplayConfigDelta::default() };
+    assert_eq!(client.set(Selector::Handle(4), delta).unwrap(), displays());
+}
+
+#[test]
+fn test_stats_from_the_daemon() {
+    let mut daemon = Daemon::default();
+    let stream = loopback(move |request| daemon.handle(request));
+    let mut client = DaemonClient::over(stream).unwrap();
+    let stats = client.stats().unwrap();
+    assert!(stats.started_at > 0);
+    // The handshake was a request too.
+    assert!(stats.phases.request.count >= 1);
+}
+
+/// Answers with the `replies` lines in turn, whatever is sent.
+struct Scripted(VecDeque<u8>);
+
+impl Write for Scripted {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for Scripted {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn test_progress_lines_come_before_the_response() {
+    let hello = Response { ok: true, version: Some(PROTOCOL_VERSION), ..Response::default() };
+    let progress = DisplayProgress { index: 1, total: 1, source_id: 1, scaling: PhaseStatus::Running, ..DisplayProgress::default() };
+    let done = Response { ok: true, displays: displays(), ..Response::default() };
+    let mut lines = String::new();
+    for line in [serde_json::to_string(&hello), serde_json::to_string(&ProgressMessage { progress: progress.clone() }), serde_json::to_string(&done)] {
+        lines.push_str(&line.unwrap());
+        lines.push('\n');
+    }
+    let mut client = DaemonClient::over(Scripted(lines.into_bytes().into())).unwrap();
+    let mut seen = Vec::new();
+    assert_eq!(client.apply_profile_with_progress("desk", |p| seen.push(p.clone())).unwrap(), displays());
+    assert_eq!(seen, [progress]);
+}
@@ -0,0 +1,21 @@
+//! Fixtures shared by the integration tests.
+
+use display_tuner::display::DisplayInfo;
+
+/// An active, available display at 60Hz named after its source id, with the given resolution,
+/// scaling and largest scaling.
+#[must_use]
+pub fn display(source_id: u32, width: u32, height: u32, scaling: i32, scaling_max: i32) -> DisplayInfo {
+    DisplayInfo {
+        friendly_name: format!("Display {source_id}"),
+        source_id,
+        width,
+        height,
+        refresh_rate: 60,
+        scaling_current: scaling,
+        scaling_max,
+        active: true,
+        target_available: true,
+        ..DisplayInfo::default()
+    }
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "profiles")]
+
+use display_tuner::config::{Config, OutputFormat, TEMPLATE};
+use display_tuner::display::DisplayError;
+use display_tuner::groups::GroupConfig;
+
+const CONFIG: &str = r#"
+[output]
+format = "json"
+color = false
+
+[safety]
+confirm = 20
+auto_revert_seconds = 30
+validate_first = true
+retries = 2
+
+[aliases]
+tv = { vendor = "SAM" }
+left = { device_path = 'PATH-1' }
+
+[groups]
+desk = [{ vendor = "DEL" }]
+"#;
+
+fn error_location(text: &str) -> (usize, String) {
+    match Config::parse(text).unwrap_err() {
+        DisplayError::InvalidConfig { line, key, .. } => (line, key),
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn test_config_sections() {
+    let config = Config::parse(CONFIG).unwrap();
+    assert_eq!(config.output.format, Some(OutputFormat::Json));
+    assert_eq!(config.output.color, Some(false));
+    assert_eq!(config.safety.confirm, Some(20));
+    assert_eq!(config.safety.auto_revert_seconds, Some(30));
+    assert!(config.safety.validate_first);
+    assert_eq!(config.safety.retries, Some(2));
+    assert_eq!(config.safety.retry_delay, None);
+    assert_eq!(config.aliases.len(), 2);
+    assert_eq!(config.groups["desk"].len(), 1);
+}
+
+#[test]
+fn test_template_parses_to_defaults() {
+    let config = Config::parse(TEMPLATE).unwrap();
+    assert_eq!(config.output.format, None);
+    assert_eq!(config.safety.confirm, None);
+    assert!(!config.safety.validate_first);
+    assert!(config.aliases.is_empty() && config.groups.is_empty());
+}
+
+#[test]
+fn test_errors_name_line_and_key() {
+    assert_eq!(error_location("[output]\nformat = \"table\"\n\n[safety]\nretries = \"three\"\n"), (5, "safety.retries".to_string()));
+    assert_eq!(error_location("[output]\ncolour = true\n"), (2, "output.colour".to_string()));
+    assert_eq!(error_location("[output]\nformat = \"xml\"\n"), (2, "output.format".to_string()));
+    assert_eq!(error_location("[display]\n"), (1, "display".to_string()));
+}
+
+#[test]
+fn test_groups_file_wins_over_config() {
+    let config = Config::parse(CONFIG).unwrap();
+    let groups = GroupConfig::parse("[aliases]\ntv = { vendor = \"LGD\" }\n").unwrap();
+    let merged = config.merge_groups(groups);
+    assert_eq!(merged.aliases["tv"].vendor.as_deref(), Some("LGD"));
+    assert!(merged.aliases.contains_key("left"));
+    assert!(merged.groups.contains_key("desk"));
+}
@@ -0,0 +1,62 @@
+use display_tuner::copy::{plan_copy, Outcome, Property};
+use display_tuner::display::{DisplayInfo, DisplayMode, Rotation, ScaleMode};
+
+mod common;
+use common::display;
+
+fn modes(list: &[(u32, u32, u32)]) -> Vec<DisplayMode> {
+    list.iter().map(|&(width, height, refresh)| DisplayMode { width, height, refresh }).collect()
+}
+
+fn outcomes(from: &DisplayInfo, to: &DisplayInfo, modes: &[DisplayMode]) -> Vec<(Property, Outcome)> {
+    plan_copy(from, to, modes).properties.into_iter().map(|p| (p.property, p.outcome)).collect()
+}
+
+#[test]
+fn test_identical_monitor_copies_everything() {
+    let from = DisplayInfo { refresh_rate: 144, scale_mode: ScaleMode::AspectRatio, ..display(1, 2560, 1440, 125, 200) };
+    let to = display(2, 1920, 1080, 100, 200);
+    let report = plan_copy(&from, &to, &modes(&[(2560, 1440, 144), (2560, 1440, 60), (1920, 1080, 60)]));
+
+    assert!(report.properties.iter().all(|p| p.outcome == Outcome::Copied || p.property == Property::Rotation));
+    assert_eq!(report.properties[0].value, "2560x1440");
+    assert_eq!(report.properties[3].outcome, Outcome::Unchanged);
+    assert!(!report.failed());
+}
+
+#[test]
+fn test_unsupported_properties_are_skipped() {
+    let from = DisplayInfo { refresh_rate: 144, ..display(1, 3840, 2160, 250, 300) };
+    let to = display(2, 1920, 1080, 100, 150);
+    let planned = outcomes(&from, &to, &modes(&[(1920, 1080, 60)]));
+
+    assert_eq!(planned[0], (Property::Resolution, Outcome::Skipped("display 2 doesn't support it".to_string())));
+    // The refresh rate is checked at the resolution the display keeps.
+    assert_eq!(
+        planned[1],
+        (Property::RefreshRate, Outcome::Skipped("display 2 doesn't offer it at 1920x1080".to_string()))
+    );
+    assert_eq!(planned[2], (Property::Scaling, Outcome::Skipped("display 2 supports 100-150%".to_string())));
+    assert_eq!(planned[4], (Property::ScaleMode, Outcome::Unchanged));
+}
+
+#[test]
+fn test_portrait_source_copies_native_resolution_and_rotation() {
+    let from = DisplayInfo { rotation: Rotation::Rotate90, ..display(1, 1080, 1920, 100, 200) };
+    let to = display(2, 1920, 1080, 100, 200);
+    let planned = outcomes(&from, &to, &modes(&[(1920, 1080, 60)]));
+
+    assert_eq!(planned[0], (Property::Resolution, Outcome::Unchanged));
+    assert_eq!(planned[1], (Property::RefreshRate, Outcome::Unchanged));
+    assert_eq!(planned[3], (Property::Rotation, Outcome::Copied));
+}
+
+#[test]
+fn test_custom_scaling_and_unknown_refresh_are_skipped() {
+    let from = DisplayInfo { refresh_rate: 0, ..display(1, 1920, 1080, 150, 200) };
+    let to = DisplayInfo { custom_scaling: true, ..display(2, 1920, 1080, 110, 200) };
+    let planned = outcomes(&from, &to, &modes(&[(1920, 1080, 60)]));
+
+    assert_eq!(planned[1].1, Outcome::Skipped("unknown on the source display".to_string()));
+    assert_eq!(planned[2].1, Outcome::Skipped("display 2 has a custom scaling override".to_string()));
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "daemon")]
+
+use display_tuner::daemon::{Daemon, Request, Response};
+use display_tuner::display::DisplayInfo;
+
+#[test]
+fn test_request_wire_format() {
+    let request: Request = serde_json::from_str(r#"{"command":"set","id":1,"scaling":150}"#).unwrap();
+    assert_eq!(
+        request,
+        Request::Set { id: Some(1), handle: None, width: None, height: None, scaling: Some(150) }
+    );
+
+    let apply = Request::ProfileApply { name: "desk".to_string(), progress: false };
+    assert_eq!(
+        serde_json::to_string(&apply).unwrap(),
+        r#"{"command":"profile_apply","name":"desk"}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<Request>(r#"{"command":"profile_apply","name":"desk","progress":true}"#).unwrap(),
+        Request::ProfileApply { name: "desk".to_string(), progress: true }
+    );
+    assert_eq!(serde_json::to_string(&Request::Revert).unwrap(), r#"{"command":"revert"}"#);
+    assert_eq!(serde_json::to_string(&Request::Capabilities).unwrap(), r#"{"command":"capabilities"}"#);
+    assert_eq!(serde_json::to_string(&Request::Stats).unwrap(), r#"{"command":"stats"}"#);
+    assert_eq!(
+        serde_json::to_string(&Request::Hello { version: 1 }).unwrap(),
+        r#"{"command":"hello","version":1}"#
+    );
+
+    assert!(serde_json::from_str::<Request>(r#"{"command":"reboot"}"#).is_err());
+}
+
+#[test]
+fn test_response_round_trip() {
+    let response = Response {
+        ok: true,
+        error: None,
+        code: None,
+        displays: vec![DisplayInfo {
+            source_id: 3,
+            friendly_name: "DELL U2720Q".to_string(),
+            width: 3840,
+            height: 2160,
+            ..DisplayInfo::default()
+        }],
+        desktop: None,
+        version: None,
+        capabilities: Vec::new(),
+        handles: vec![7],
+        stats: None,
+    };
+    let line = serde_json::to_string(&response).unwrap();
+    assert!(!line.contains("error") && !line.contains("capabilities") && !line.contains("stats"));
+    assert!(line.contains(r#""handles":[7]"#));
+    assert_eq!(serde_json::from_str::<Response>(&line).unwrap(), response);
+
+    let failed: Response = serde_json::from_str(r#"{"ok":false,"error":"Display 9 not found","code":"display_not_found"}"#).unwrap();
+    assert!(!failed.ok && failed.displays.is_empty());
+    assert_eq!(failed.code.as_deref(), Some("display_not_found"));
+}
+
+#[test]
+fn test_stats_answer_without_touching_the_displays() {
+    let response = Daemon::default().handle(&Request::Stats);
+    assert!(response.ok);
+    assert!(response.displays.is_empty() && response.handles.is_empty());
+    assert!(response.stats.is_some());
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "ddcci")]
+
+use display_tuner::ddc::{DdcCache, DdcProbes};
+use display_tuner::display::{DisplayError, DisplayInfo, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn display(device_path: &str, x: i32) -> DisplayInfo {
+    DisplayInfo { device_path: device_path.to_string(), position_x: x, ..DisplayInfo::default() }
+}
+
+/// Takes `x` milliseconds; monitors at an even `x` answer, `y` 1 fails.
+fn slow_probe(x: i32, y: i32) -> Result<bool> {
+    thread::sleep(Duration::from_millis(x.unsigned_abs().into()));
+    if y == 1 {
+        return Err(DisplayError::DisplayNotFound(0));
+    }
+    Ok(x % 2 == 0)
+}
+
+#[test]
+fn test_probes_run_in_parallel_and_keep_their_order() {
+    let displays = [display("A", 300), display("B", 200), display("C", 301), display("D", 100)];
+    let refs: Vec<&DisplayInfo> = displays.iter().collect();
+    let started = Instant::now();
+    assert_eq!(DdcProbes::start_with(&refs, Duration::from_secs(5), slow_probe).wait(), [Some(true), Some(true), Some(false), Some(true)]);
+    assert!(started.elapsed() < Duration::from_millis(600), "{:?}", started.elapsed());
+}
+
+#[test]
+fn test_slow_and_failed_probes_give_no_answer() {
+    let failing = DisplayInfo { position_y: 1, ..display("B", 0) };
+    let displays = [display("A", 10), failing, display("C", 5000)];
+    let refs: Vec<&DisplayInfo> = displays.iter().collect();
+    let started = Instant::now();
+    assert_eq!(DdcProbes::start_with(&refs, Duration::from_millis(300), slow_probe).wait(), [Some(true), None, None]);
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+}
+
+static PROBED: AtomicUsize = AtomicUsize::new(0);
+
+// Has to be a `Probe`.
+#[allow(clippy::unnecessary_wraps)]
+fn counting_probe(_: i32, _: i32) -> Result<bool> {
+    PROBED.fetch_add(1, Ordering::SeqCst);
+    Ok(true)
+}
+
+#[test]
+fn test_cache_until_the_displays_change() {
+    let mut cache = DdcCache::default();
+    let displays = vec![display("A", 0), display("B", 2560)];
+    let timeout = Duration::from_secs(5);
+    assert_eq!(cache.probe_with(&displays, timeout, counting_probe), [true, true]);
+    assert_eq!(cache.probe_with(&displays, timeout, counting_probe), [true, true]);
+    assert_eq!(PROBED.load(Ordering::SeqCst), 2);
+
+    let moved = vec![display("A", 0), display("B", -2560)];
+    cache.probe_with(&moved, timeout, counting_probe);
+    assert_eq!(PROBED.load(Ordering::SeqCst), 4);
+
+    cache.invalidate();
+    cache.probe_with(&moved, timeout, counting_probe);
+    assert_eq!(PROBED.load(Ordering::SeqCst), 6);
+}
+
+static SLOW_PROBED: AtomicUsize = AtomicUsize::new(0);
+
+fn slow_counting_probe(x: i32, y: i32) -> Result<bool> {
+    SLOW_PROBED.fetch_add(1, Ordering::SeqCst);
+    slow_probe(x, y)
+}
+
+#[test]
+fn test_cache_probes_again_after_a_timeout() {
+    let mut cache = DdcCache::default();
+    let displays = vec![display("A", 0), display("B", 1000)];
+    let timeout = Duration::from_millis(200);
+    assert_eq!(cache.probe_with(&displays, timeout, slow_counting_probe), [true, false]);
+    // Only B, whose probe was still running.
+    assert_eq!(cache.probe_with(&displays, timeout, slow_counting_probe), [true, false]);
+    assert_eq!(SLOW_PROBED.load(Ordering::SeqCst), 3);
+}
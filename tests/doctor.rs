@@ -0,0 +1,26 @@
+use display_tuner::doctor::{Check, CheckStatus, DoctorReport};
+
+fn check(name: &'static str, status: CheckStatus) -> Check {
+    Check { name, status, detail: "details".to_string() }
+}
+
+#[test]
+fn test_report_applicable_ignores_info() {
+    let report = DoctorReport {
+        checks: vec![check("OS build", CheckStatus::Info), check("Enumeration", CheckStatus::Pass)],
+    };
+    assert!(report.applicable());
+    assert_eq!(
+        report.to_string(),
+        "[INFO] OS build: details\n[PASS] Enumeration: details\nThe configuration can be applied"
+    );
+}
+
+#[test]
+fn test_report_fails_on_any_failed_check() {
+    let report = DoctorReport {
+        checks: vec![check("Resolution", CheckStatus::Fail), check("Scaling", CheckStatus::Pass)],
+    };
+    assert!(!report.applicable());
+    assert!(report.to_string().ends_with("The configuration can't be applied"));
+}
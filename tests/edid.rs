@@ -0,0 +1,93 @@
+use display_tuner::edid::{parse_edid, EdidInfo};
+use display_tuner::raw::monitor_parameters_key;
+
+/// A Dell U2720Q with one CEA extension block.
+const DELL_U2720Q: [&str; 8] = [
+    "00ffffffffffff0010aca84132304e4c0c1e0104b53c22783a4ee5a5544da126",
+    "0e5054a54b00d1c0714f8180a9c0d1000101010101014dd000a0f0703e803020",
+    "3500544f2100001a000000ff0046384b584c31330a2020202020000000fc0044",
+    "454c4c205532373230510a20000000fd00184b1e8c3c000a202020202020015c",
+    "0203000000000000000000000000000000000000000000000000000000000000",
+    "0000000000000000000000000000000000000000000000000000000000000000",
+    "0000000000000000000000000000000000000000000000000000000000000000",
+    "00000000000000000000000000000000000000000000000000000000000000fb",
+];
+
+/// An LG TV: no serial number at all, and a model year instead of a date of manufacture.
+const LG_TV: [&str; 4] = [
+    "00ffffffffffff001e6d095b00000000ff1d0104b53c22783a4ee5a5544da126",
+    "0e5054a54b00d1c0714f8180a9c0d1000101010101014dd000a0f0703e803020",
+    "3500544f2100001a000000fd00184b1e8c3c000a202020202020000000fc004c",
+    "472054562053534352320a20000000100000000000000000000000000000004f",
+];
+
+/// A laptop panel: only the numeric serial, no week, and a text descriptor instead of a name.
+const LAPTOP_PANEL: [&str; 4] = [
+    "00ffffffffffff0006af3d1201010101001f0104b53c22783a4ee5a5544da126",
+    "0e5054a54b00d1c0714f8180a9c0d1000101010101014dd000a0f0703e803020",
+    "3500544f2100001a000000100000000000000000000000000000000000fe0042",
+    "31343048414e30342e300a2000000010000000000000000000000000000000ab",
+];
+
+fn bytes(hex: &[&str]) -> Vec<u8> {
+    let hex = hex.concat();
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+}
+
+#[test]
+fn test_descriptors_and_date() {
+    assert_eq!(
+        parse_edid(&bytes(&DELL_U2720Q)),
+        EdidInfo {
+            serial_number: Some("F8KXL13".to_string()),
+            model_name: Some("DELL U2720Q".to_string()),
+            manufacture_week: Some(12),
+            manufacture_year: Some(2020),
+        }
+    );
+}
+
+#[test]
+fn test_missing_fields_are_none() {
+    assert_eq!(
+        parse_edid(&bytes(&LG_TV)),
+        EdidInfo { model_name: Some("LG TV SSCR2".to_string()), ..EdidInfo::default() }
+    );
+    assert_eq!(
+        parse_edid(&bytes(&LAPTOP_PANEL)),
+        EdidInfo { serial_number: Some("16843009".to_string()), manufacture_year: Some(2021), ..EdidInfo::default() }
+    );
+}
+
+#[test]
+fn test_malformed_edids_parse_to_nothing() {
+    let edid = bytes(&DELL_U2720Q);
+    assert_eq!(parse_edid(&[]), EdidInfo::default());
+    assert_eq!(parse_edid(&edid[..100]), EdidInfo::default());
+
+    let mut corrupted = edid.clone();
+    corrupted[80] ^= 0x20;
+    assert_eq!(parse_edid(&corrupted), EdidInfo::default());
+
+    let mut headerless = edid.clone();
+    headerless[0] = 0xff;
+    headerless[127] = headerless[127].wrapping_sub(0xff);
+    assert_eq!(parse_edid(&headerless), EdidInfo::default());
+
+    // A serial descriptor with control characters is dropped; the numeric serial stands in.
+    let mut garbled = edid;
+    garbled[77] = 0x07;
+    garbled[127] = garbled[127].wrapping_add(b'F' - 0x07);
+    assert_eq!(parse_edid(&garbled).serial_number, Some("1280192562".to_string()));
+    assert_eq!(parse_edid(&garbled).model_name, Some("DELL U2720Q".to_string()));
+}
+
+#[test]
+fn test_registry_key_of_device_path() {
+    assert_eq!(
+        monitor_parameters_key(r"\\?\DISPLAY#DEL41A8#5&1a2b3c&0&UID4352#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}").as_deref(),
+        Some(r"SYSTEM\CurrentControlSet\Enum\DISPLAY\DEL41A8\5&1a2b3c&0&UID4352\Device Parameters")
+    );
+    assert_eq!(monitor_parameters_key(""), None);
+    assert_eq!(monitor_parameters_key(r"\\?\DISPLAY#DEL41A8"), None);
+}
@@ -0,0 +1,44 @@
+use display_tuner::display::DisplayInfo;
+use display_tuner::explain::{DpiSteps, EnumerationTrace, NameSource, ScalingSource};
+
+fn trace() -> EnumerationTrace {
+    EnumerationTrace {
+        dpi_steps: DpiSteps { min: -2, current: 1, max: 3 },
+        effective_dpi: Some(144),
+        scaling_source: ScalingSource::EffectiveDpi,
+        source_mode_index: Some(0),
+        target_mode_index: None,
+        path_flags: 9,
+        virtual_mode: true,
+        name_source: NameSource::Edid,
+    }
+}
+
+#[test]
+fn test_name_source() {
+    assert_eq!(NameSource::of(1, "DELL U2720Q"), NameSource::Edid);
+    assert_eq!(NameSource::of(3, "Studio Display"), NameSource::Forced);
+    assert_eq!(NameSource::of(4, "Generic PnP Monitor"), NameSource::Driver);
+    assert_eq!(NameSource::of(1, ""), NameSource::None);
+}
+
+#[test]
+fn test_trace_lines() {
+    assert_eq!(
+        trace().to_string(),
+        "  scaling: from the effective DPI (DPI steps min -2, current +1, max 3; effective DPI 144)\n  \
+         modes: source #0, target none\n  path flags: 0x00000009, virtual mode supported (not used)\n  name: from the EDID"
+    );
+}
+
+#[test]
+fn test_trace_is_under_debug_only_when_loaded() {
+    let mut display = DisplayInfo::default();
+    assert!(serde_json::to_value(&display).unwrap().get("debug").is_none());
+
+    display.debug = Some(trace());
+    let json = serde_json::to_value(&display).unwrap();
+    assert_eq!(json["debug"]["scaling_source"], "effective_dpi");
+    assert_eq!(json["debug"]["dpi_steps"]["min"], -2);
+    assert_eq!(serde_json::from_value::<DisplayInfo>(json).unwrap(), display);
+}
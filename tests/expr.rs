@@ -0,0 +1,140 @@
+use display_tuner::display::{Connector, DisplayInfo};
+use display_tuner::expr::{FilterExpr, ParseError};
+
+fn display(source_id: u32, name: &str, width: u32, connector: Connector) -> DisplayInfo {
+    DisplayInfo {
+        friendly_name: name.to_string(),
+        source_id,
+        width,
+        height: width * 9 / 16,
+        refresh_rate: 60,
+        scaling_current: 100,
+        connector,
+        internal: connector.is_internal(),
+        active: true,
+        // "DEL" in EDID byte order.
+        edid_manufacturer_id: 0xAC10,
+        ..DisplayInfo::default()
+    }
+}
+
+fn sample() -> Vec<DisplayInfo> {
+    vec![
+        display(3, "DELL U2720Q", 3840, Connector::DisplayPort),
+        DisplayInfo { scaling_current: 150, ..display(1, "Built-in Panel", 2560, Connector::Internal) },
+        DisplayInfo { edid_manufacturer_id: 0, refresh_rate: 120, ..display(7, "LG TV", 3840, Connector::Hdmi) },
+        DisplayInfo { active: false, ..display(5, "dell P2419H", 1920, Connector::Hdmi) },
+    ]
+}
+
+fn ids(expr: &str) -> Vec<u32> {
+    let expr: FilterExpr = expr.parse().unwrap();
+    sample().iter().filter(|d| expr.matches(d)).map(|d| d.source_id).collect()
+}
+
+fn parse_error(expr: &str) -> ParseError {
+    expr.parse::<FilterExpr>().unwrap_err()
+}
+
+#[test]
+fn test_comparisons() {
+    assert_eq!(ids("connector=hdmi && width<2560"), vec![5]);
+    assert_eq!(ids("width>=2560"), vec![3, 1, 7]);
+    assert_eq!(ids("width <= 2560 && height > 1080"), vec![1]);
+    assert_eq!(ids("id!=3 && id != 7"), vec![1, 5]);
+    assert_eq!(ids("scaling=150%"), vec![1]);
+    assert_eq!(ids("refresh>60"), vec![7]);
+    assert_eq!(ids("connector == DP"), vec![3]);
+    assert_eq!(ids("connector!=hdmi"), vec![3, 1]);
+}
+
+#[test]
+fn test_text_fields_ignore_case() {
+    assert_eq!(ids("name~dell"), vec![3, 5]);
+    assert_eq!(ids("name='lg tv'"), vec![7]);
+    assert_eq!(ids(r#"name~"LG TV" && width>1000"#), vec![7]);
+    assert_eq!(ids("name!=\"LG TV\""), vec![3, 1, 5]);
+    assert_eq!(ids("vendor=del"), vec![3, 1, 5]);
+}
+
+#[test]
+fn test_flags() {
+    assert_eq!(ids("internal"), vec![1]);
+    assert_eq!(ids("external && active"), vec![3, 7]);
+    assert_eq!(ids("active=false"), vec![5]);
+    assert_eq!(ids("internal != true"), vec![3, 7, 5]);
+}
+
+#[test]
+fn test_expression_keeps_its_text() {
+    let expr: FilterExpr = "  connector=hdmi  && width<2560 ".parse().unwrap();
+    assert_eq!(expr.to_string(), "connector=hdmi  && width<2560");
+}
+
+#[test]
+fn test_error_points_at_the_token() {
+    let err = parse_error("connector=hdmi && width 2560");
+    assert_eq!(err.message, "expected an operator after `width`, found `2560`");
+    assert_eq!(err.span, 24..28);
+    assert_eq!(
+        err.to_string(),
+        "expected an operator after `width`, found `2560`\n  connector=hdmi && width 2560\n                          ^^^^"
+    );
+}
+
+#[test]
+fn test_error_messages() {
+    let cases = [
+        ("", "expected a condition such as `width<2560`", 0..0),
+        ("width<2560 &&", "expected a condition such as `width<2560`", 13..13),
+        ("&& width<1", "expected a field name, found `&&`", 0..2),
+        ("'hdmi'=connector", "expected a field name, found \"hdmi\"", 0..6),
+        (
+            "colour=red",
+            "unknown field `colour`, expected one of name, vendor, connector, id, width, height, scaling, refresh, internal, external, active",
+            0..6,
+        ),
+        ("width", "expected an operator after `width`", 5..5),
+        ("width<", "expected a value after `<`", 6..6),
+        ("width< &&", "expected a value after `<`, found `&&`", 7..9),
+        ("width<abc", "expected a number for `width`, found `abc`", 6..9),
+        ("width>-1", "expected a number for `width`, found `-1`", 6..8),
+        ("connector<hdmi", "`<` doesn't apply to `connector`, use = !=", 9..10),
+        ("name>a", "`>` doesn't apply to `name`, use = != ~", 4..5),
+        (
+            "connector=usb",
+            "unknown connector `usb`, expected one of vga, dvi, hdmi, dp, internal, miracast, indirect, other",
+            10..13,
+        ),
+        ("active=maybe", "expected `true` or `false` for `active`", 7..12),
+        ("width<2560 internal", "expected `&&` before `internal`", 11..19),
+        ("width<2560 || internal", "only `&&` is supported between conditions", 11..12),
+        ("internal & active", "expected `&&`", 9..10),
+        ("width!2560", "expected `!=`", 5..6),
+        ("name~\"LG TV", "unterminated quote", 5..11),
+    ];
+    for (expr, message, span) in cases {
+        let err = parse_error(expr);
+        assert_eq!((err.message.as_str(), err.span), (message, span), "{expr:?}");
+    }
+}
+
+#[test]
+fn test_caret_counts_characters() {
+    let err = parse_error("name=Écran && größe=1");
+    assert_eq!(err.to_string().lines().last(), Some("                ^^^^^"));
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn test_rule_match_tables_accept_where() {
+    use display_tuner::selector::{filter_displays, DisplayFilter};
+
+    let filter: DisplayFilter = toml::from_str(r#"where = "external && width>=3840""#).unwrap();
+    assert_eq!(filter.to_string(), "where='external && width>=3840'");
+    let matched: Vec<u32> = filter_displays(sample(), &filter).iter().map(|d| d.source_id).collect();
+    assert_eq!(matched, vec![3, 7]);
+
+    let err = toml::from_str::<DisplayFilter>(r#"where = "width<""#).unwrap_err();
+    assert!(err.to_string().contains("expected a value after `<`"));
+}
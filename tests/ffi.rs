@@ -0,0 +1,80 @@
+#![cfg(feature = "ffi")]
+
+use display_tuner::display::DisplayInfo;
+use display_tuner::ffi::{dt_enumerate, dt_free_string, dt_last_error, dt_set, SetRequest, DT_ERROR, DT_NO_DISPLAYS, DT_OK};
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+fn last_error() -> String {
+    let mut message: *mut c_char = ptr::null_mut();
+    assert_eq!(unsafe { dt_last_error(&raw mut message) }, DT_OK);
+    let text = unsafe { CStr::from_ptr(message) }.to_str().unwrap().to_string();
+    unsafe { dt_free_string(message) };
+    text
+}
+
+fn set(json: &str) -> i32 {
+    let request = CString::new(json).unwrap();
+    unsafe { dt_set(request.as_ptr()) }
+}
+
+#[test]
+fn test_errors_are_per_thread() {
+    assert_eq!(set("[]"), DT_ERROR);
+    std::thread::spawn(|| {
+        let mut message: *mut c_char = ptr::null_mut();
+        assert_eq!(unsafe { dt_last_error(&raw mut message) }, DT_ERROR);
+        assert!(message.is_null());
+    })
+    .join()
+    .unwrap();
+    assert!(last_error().starts_with("Invalid JSON"));
+}
+
+#[test]
+fn test_invalid_requests_fail_with_a_message() {
+    assert_eq!(unsafe { dt_set(ptr::null()) }, DT_ERROR);
+    assert_eq!(last_error(), "Invalid argument: json_request is null");
+
+    assert_eq!(set("{\"id\": "), DT_ERROR);
+    assert!(last_error().starts_with("Invalid JSON"));
+
+    assert_eq!(set(r#"{"id": 1, "colour": "red"}"#), DT_ERROR);
+    assert!(last_error().contains("unknown field `colour`"));
+
+    assert_eq!(unsafe { dt_enumerate(ptr::null_mut()) }, DT_ERROR);
+    assert_eq!(last_error(), "Invalid argument: out_json is null");
+}
+
+#[test]
+fn test_enumerate_round_trips_as_json() {
+    let mut json: *mut c_char = ptr::null_mut();
+    match unsafe { dt_enumerate(&raw mut json) } {
+        DT_OK => {
+            let text = unsafe { CStr::from_ptr(json) }.to_str().unwrap().to_string();
+            unsafe { dt_free_string(json) };
+            serde_json::from_str::<Vec<DisplayInfo>>(&text).unwrap();
+        }
+        // No interactive session or no display, e.g. on a headless build agent.
+        code => {
+            assert!(code == DT_ERROR || code == DT_NO_DISPLAYS, "{code}");
+            assert!(json.is_null());
+            assert!(!last_error().is_empty());
+        }
+    }
+}
+
+#[test]
+fn test_set_request_fields() {
+    let request: SetRequest = serde_json::from_str(r#"{"id": 2, "scaling": 150, "allow_changes": true}"#).unwrap();
+    assert_eq!(
+        request,
+        SetRequest { id: Some(2), scaling: Some(150), allow_changes: true, ..SetRequest::default() }
+    );
+    for scaling in ["150", "150.0", "1.5"] {
+        let request: SetRequest = serde_json::from_str(&format!(r#"{{"scaling": {scaling}}}"#)).unwrap();
+        assert_eq!(request.scaling, Some(150), "{scaling}");
+    }
+    assert!(serde_json::from_str::<SetRequest>(r#"{"scaling": 0}"#).is_err());
+    unsafe { dt_free_string(ptr::null_mut()) };
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "profiles")]
+
+use display_tuner::display::{DisplayError, DisplayInfo};
+use display_tuner::groups::{resolve_group, GroupConfig};
+use display_tuner::selector::{alias_of, resolve_alias};
+
+const CONFIG: &str = r#"
+[groups]
+left-bank = [{ device_path = 'PATH-1' }, { vendor = "DEL", product = 100 }, { device_path = 'PATH-9' }]
+right-bank = [{ device_path = 'PATH-3' }]
+
+[aliases]
+left = { device_path = 'PATH-1' }
+tv = { vendor = "DEL", product = 100 }
+projector = { device_path = 'PATH-9' }
+"#;
+
+fn display(source_id: u32, device_path: &str, product: u16) -> DisplayInfo {
+    DisplayInfo {
+        source_id,
+        device_path: device_path.to_string(),
+        // "DEL" in EDID byte order.
+        edid_manufacturer_id: 0xAC10,
+        edid_product_id: product,
+        ..DisplayInfo::default()
+    }
+}
+
+fn connected() -> Vec<DisplayInfo> {
+    vec![display(1, "PATH-1", 1), display(2, "PATH-2", 100), display(3, "PATH-3", 3)]
+}
+
+fn ids(displays: &[DisplayInfo]) -> Vec<u32> {
+    displays.iter().map(|d| d.source_id).collect()
+}
+
+#[test]
+fn test_group_expands_to_connected_members() {
+    let config = GroupConfig::parse(CONFIG).unwrap();
+    assert_eq!(ids(&resolve_group(&config, "left-bank", &connected()).unwrap()), vec![1, 2]);
+    assert_eq!(ids(&resolve_group(&config, "right-bank", &connected()).unwrap()), vec![3]);
+    assert!(resolve_group(&config, "right-bank", &[]).unwrap().is_empty());
+    assert!(matches!(
+        resolve_group(&config, "center", &connected()),
+        Err(DisplayError::UnknownGroup(name)) if name == "center"
+    ));
+}
+
+#[test]
+fn test_group_membership_and_missing_members() {
+    let config = GroupConfig::parse(CONFIG).unwrap();
+    let displays = connected();
+    assert_eq!(config.groups_of(&displays[0]), vec!["left-bank"]);
+    assert_eq!(config.groups_of(&displays[2]), vec!["right-bank"]);
+
+    let missing: Vec<String> = config
+        .missing_members(&displays)
+        .iter()
+        .map(|(group, member)| format!("{group}: {member}"))
+        .collect();
+    assert_eq!(missing, vec!["left-bank: device_path=PATH-9"]);
+}
+
+#[test]
+fn test_unknown_member_fields_are_rejected() {
+    assert!(GroupConfig::parse("[groups]\nbank = [{ serial = '1' }]").is_err());
+}
+
+#[test]
+fn test_alias_resolves_to_its_display() {
+    let config = GroupConfig::parse(CONFIG).unwrap();
+    let displays = connected();
+    assert_eq!(resolve_alias(&config.aliases, "left", &displays).unwrap().source_id, 1);
+    assert_eq!(resolve_alias(&config.aliases, "TV", &displays).unwrap().source_id, 2);
+    assert_eq!(alias_of(&config.aliases, &displays[1]), Some("tv"));
+    assert_eq!(alias_of(&config.aliases, &displays[2]), None);
+}
+
+#[test]
+fn test_unknown_and_disconnected_aliases() {
+    let config = GroupConfig::parse(CONFIG).unwrap();
+    match resolve_alias(&config.aliases, "right", &connected()) {
+        Err(DisplayError::UnknownAlias { alias, defined }) => {
+            assert_eq!(alias, "right");
+            assert_eq!(defined, "left, projector, tv");
+        }
+        other => panic!("expected an unknown alias, got {other:?}"),
+    }
+    assert!(matches!(
+        resolve_alias(&config.aliases, "projector", &connected()),
+        Err(DisplayError::AliasNotConnected(alias)) if alias == "projector"
+    ));
+    assert!(matches!(
+        resolve_alias(&GroupConfig::default().aliases, "left", &connected()),
+        Err(DisplayError::UnknownAlias { defined, .. }) if defined == "none"
+    ));
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "daemon")]
+
+use display_tuner::display::DisplayInfo;
+use display_tuner::handles::{HandleTable, EVICT_AFTER_SECS, MAX_ENTRIES};
+
+const DAY: u64 = 24 * 60 * 60;
+
+fn monitor(path: &str, source_id: u32, product: u16) -> DisplayInfo {
+    DisplayInfo {
+        device_path: path.to_string(),
+        source_id,
+        edid_manufacturer_id: 0x10ac,
+        edid_product_id: product,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_handles_survive_source_id_reshuffles() {
+    let mut table = HandleTable::default();
+    let laptop = monitor(r"\\?\DISPLAY#BOE0A1B#1", 0, 0);
+    let dell = monitor(r"\\?\DISPLAY#DEL41B8#2", 1, 0x41b8);
+    assert_eq!(table.assign(&[laptop.clone(), dell.clone()], DAY), [1, 2]);
+    // Docked: Windows hands out the source ids anew and a third monitor shows up.
+    let lg = monitor(r"\\?\DISPLAY#GSM5B7F#3", 0, 0x5b7f);
+    let docked = [lg.clone(), DisplayInfo { source_id: 2, ..dell.clone() }, DisplayInfo { source_id: 1, ..laptop.clone() }];
+    assert_eq!(table.assign(&docked, 2 * DAY), [3, 2, 1]);
+    assert_eq!(table.resolve(2, &docked).map(|d| d.source_id), Some(2));
+    // Undocked and back: the same handles, none reused.
+    assert_eq!(table.assign(&[laptop], 3 * DAY), [1]);
+    assert_eq!(table.resolve(3, &[]), None);
+    assert_eq!(table.assign(&docked, 4 * DAY), [3, 2, 1]);
+    assert_eq!(table.next, 4);
+}
+
+#[test]
+fn test_moved_monitor_keeps_its_handle_unless_ambiguous() {
+    let mut table = HandleTable::default();
+    table.assign(&[monitor("port-a", 0, 0x41b8)], DAY);
+    // Plugged into another port.
+    let moved = [monitor("port-b", 0, 0x41b8)];
+    assert_eq!(table.assign(&moved, 2 * DAY), [1]);
+    assert_eq!(table.entries[0].device_path, "port-b");
+    // Two of the same model appearing where neither was seen: both are new.
+    let twins = [monitor("port-c", 0, 0x41b8), monitor("port-d", 1, 0x41b8)];
+    assert_eq!(table.assign(&twins, 3 * DAY), [2, 3]);
+    // Monitors without EDID ids can only be told apart by their port.
+    let generic = |path| DisplayInfo { edid_manufacturer_id: 0, ..monitor(path, 0, 0) };
+    let mut unknown = HandleTable::default();
+    unknown.assign(&[generic("port-a")], DAY);
+    assert_eq!(unknown.assign(&[generic("port-b")], DAY), [2]);
+}
+
+#[test]
+fn test_eviction_and_persistence() {
+    let mut table = HandleTable::default();
+    table.assign(&[monitor("old", 0, 1)], DAY);
+    table.assign(&[monitor("kept", 0, 2)], DAY + EVICT_AFTER_SECS);
+    table.assign(&[monitor("new", 0, 3)], 2 * DAY + EVICT_AFTER_SECS);
+    assert_eq!(table.entries.iter().map(|e| e.handle).collect::<Vec<_>>(), [2, 3]);
+
+    let many: Vec<DisplayInfo> = (0..=MAX_ENTRIES).map(|i| monitor(&format!("path-{i}"), 0, 0)).collect();
+    for (i, display) in many.iter().enumerate() {
+        table.assign(std::slice::from_ref(display), 3 * DAY + EVICT_AFTER_SECS + i as u64);
+    }
+    assert_eq!(table.entries.len(), MAX_ENTRIES);
+    assert!(table.entries.iter().all(|e| e.device_path.starts_with("path-")));
+    // Forgotten monitors come back with a new handle.
+    assert_eq!(table.assign(&[monitor("kept", 0, 2)], 4 * DAY + EVICT_AFTER_SECS), [u32::try_from(MAX_ENTRIES).unwrap() + 5]);
+
+    let path = std::env::temp_dir().join(format!("display-tuner-handles-{}.json", std::process::id()));
+    table.save(&path).unwrap();
+    assert_eq!(HandleTable::load(&path).unwrap(), table);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(HandleTable::load(&path).unwrap(), HandleTable::default());
+}
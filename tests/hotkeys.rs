@@ -0,0 +1,41 @@
+#![cfg(feature = "watch")]
+
+use display_tuner::hotkeys::{Hotkey, HotkeyConfig};
+
+#[test]
+fn test_parse_hotkey() {
+    let hotkey: Hotkey = "Ctrl+Alt+F1".parse().unwrap();
+    assert_eq!(hotkey, Hotkey { modifiers: 0x2 | 0x1, key: 0x70 });
+    assert_eq!(hotkey.to_string(), "Ctrl+Alt+F1");
+
+    let plus: Hotkey = "control + alt + plus".parse().unwrap();
+    assert_eq!(plus.key, 0xBB);
+    assert_eq!(plus.to_string(), "Ctrl+Alt+Plus");
+
+    assert_eq!("Win+Shift+d".parse::<Hotkey>().unwrap().to_string(), "Shift+Win+D");
+}
+
+#[test]
+fn test_reject_invalid_hotkeys() {
+    for text in ["F1", "Ctrl+", "Ctrl+Hyper+A", "Ctrl+F25", "Alt+Foo"] {
+        assert!(text.parse::<Hotkey>().is_err(), "{text}");
+    }
+}
+
+#[test]
+fn test_hotkey_config() {
+    let config = HotkeyConfig::parse(
+        r#"
+        [hotkeys]
+        "Ctrl+Alt+F1" = "profile apply docked"
+        "Ctrl+Alt+Plus" = "set --current --scaling +1"
+        "#,
+    )
+    .unwrap();
+    let bindings = config.bindings().unwrap();
+    assert_eq!(bindings.len(), 2);
+    assert_eq!(bindings[0].1, "profile apply docked");
+
+    let bad = HotkeyConfig::parse("[hotkeys]\n\"Ctrl+Nope\" = \"list\"").unwrap();
+    assert!(bad.bindings().is_err());
+}
@@ -0,0 +1,47 @@
+use display_tuner::icc::{has_mhc2_tag, ColorStatus, ProfileScope};
+
+/// A profile header and tag table with the given tag signatures; the tag data isn't needed.
+fn profile(tags: &[&[u8; 4]]) -> Vec<u8> {
+    let mut icc = vec![0u8; 128];
+    icc[36..40].copy_from_slice(b"acsp");
+    icc.extend_from_slice(&u32::try_from(tags.len()).unwrap().to_be_bytes());
+    for tag in tags {
+        icc.extend_from_slice(*tag);
+        icc.extend_from_slice(&[0; 8]);
+    }
+    icc
+}
+
+#[test]
+fn test_mhc2_tag_detection() {
+    assert!(has_mhc2_tag(&profile(&[b"desc", b"MHC2", b"wtpt"])));
+    assert!(!has_mhc2_tag(&profile(&[b"desc", b"wtpt"])));
+    assert!(!has_mhc2_tag(b"not a profile"));
+
+    let mut truncated = profile(&[b"desc", b"MHC2"]);
+    truncated.truncate(128 + 4 + 12);
+    assert!(!has_mhc2_tag(&truncated));
+    let mut foreign = profile(&[b"MHC2"]);
+    foreign[36..40].copy_from_slice(b"xxxx");
+    assert!(!has_mhc2_tag(&foreign));
+}
+
+#[test]
+fn test_status_reports_the_profile_in_effect() {
+    let mut status = ColorStatus {
+        source_id: 2,
+        friendly_name: "DELL U2720Q".to_string(),
+        scope: ProfileScope::System,
+        system_profile: Some("sRGB.icm".to_string()),
+        user_profile: Some("calibrated.icm".to_string()),
+        hdr_calibration: false,
+    };
+    assert_eq!(status.active_profile(), Some("sRGB.icm"));
+    status.scope = ProfileScope::User;
+    status.hdr_calibration = true;
+    assert_eq!(status.active_profile(), Some("calibrated.icm"));
+    assert_eq!(
+        status.to_string(),
+        "DELL U2720Q (id 2): user profiles, HDR calibration (MHC2)\n  system: sRGB.icm\n  user: calibrated.icm"
+    );
+}
@@ -0,0 +1,250 @@
+use display_tuner::display::{DisplayError, DisplayInfo, SourceMode};
+use display_tuner::layout::{desktop_excess, desktop_summary, find_overlap, fit_desktop, normalize, place, place_display, preset_layout, reflow, reflow_layout, Align, Placement, Preset, Rect};
+
+fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+    Rect { x, y, width, height }
+}
+
+fn display(source_id: u32, area: Rect) -> DisplayInfo {
+    DisplayInfo {
+        source_id,
+        width: area.width,
+        height: area.height,
+        position_x: area.x,
+        position_y: area.y,
+        primary: area.x == 0 && area.y == 0,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_place_each_side() {
+    let reference = rect(0, 0, 2560, 1440);
+    let small = rect(5000, 5000, 1920, 1080);
+
+    assert_eq!(place(small, reference, Placement::RightOf, Align::Start), rect(2560, 0, 1920, 1080));
+    assert_eq!(place(small, reference, Placement::LeftOf, Align::Start), rect(-1920, 0, 1920, 1080));
+    assert_eq!(place(small, reference, Placement::Above, Align::Start), rect(0, -1080, 1920, 1080));
+    assert_eq!(place(small, reference, Placement::Below, Align::Start), rect(0, 1440, 1920, 1080));
+}
+
+#[test]
+fn test_place_alignment() {
+    let reference = rect(0, 0, 2560, 1440);
+    let small = rect(0, 0, 1920, 1080);
+
+    assert_eq!(place(small, reference, Placement::RightOf, Align::Center).y, 180);
+    assert_eq!(place(small, reference, Placement::RightOf, Align::End).y, 360);
+    assert_eq!(place(small, reference, Placement::Below, Align::Center).x, 320);
+
+    // A taller display aligned to the bottom of a shorter one starts above it.
+    let tall = rect(0, 0, 1080, 1920);
+    assert_eq!(place(tall, reference, Placement::LeftOf, Align::End), rect(-1080, -480, 1080, 1920));
+}
+
+#[test]
+fn test_overlap_and_normalize() {
+    let mut layout = vec![(1, rect(-1920, 0, 1920, 1080)), (2, rect(-3840, 0, 1920, 1080))];
+    assert_eq!(find_overlap(&layout), None);
+
+    normalize(&mut layout, 1);
+    assert_eq!(layout, vec![(1, rect(0, 0, 1920, 1080)), (2, rect(-1920, 0, 1920, 1080))]);
+
+    layout.push((3, rect(-100, 500, 1920, 1080)));
+    assert_eq!(find_overlap(&layout), Some((1, 3)));
+}
+
+#[test]
+fn test_place_display_keeps_primary_at_origin() {
+    let displays = vec![display(1, rect(0, 0, 2560, 1440)), display(2, rect(2560, 0, 1920, 1080))];
+
+    let layout = place_display(&displays, 1, 2, Placement::RightOf, Align::Start).unwrap();
+    let positions: Vec<_> = layout.iter().map(|s| (s.source_id, s.x, s.y)).collect();
+    assert_eq!(positions, vec![(1, 0, 0), (2, -1920, 0)]);
+}
+
+#[test]
+fn test_place_display_rejects_overlap() {
+    let displays = vec![
+        display(1, rect(0, 0, 2560, 1440)),
+        display(2, rect(2560, 0, 1920, 1080)),
+        display(3, rect(-1920, 0, 1920, 1080)),
+    ];
+
+    let result = place_display(&displays, 3, 1, Placement::RightOf, Align::Start);
+    assert!(matches!(result, Err(DisplayError::Overlap(2, 3))));
+}
+
+#[test]
+fn test_union_spans_negative_coordinates() {
+    let primary = rect(0, 0, 1920, 1080);
+    let left = rect(-2560, -360, 2560, 1440);
+    assert_eq!(primary.union(&left), rect(-2560, -360, 4480, 1440));
+    assert_eq!(left.union(&primary), primary.union(&left));
+    assert_eq!(left.area(), 3_686_400);
+}
+
+#[test]
+fn test_desktop_summary_of_active_displays() {
+    let mut displays = vec![
+        display(1, rect(0, 0, 1920, 1080)),
+        display(2, rect(-2560, -360, 2560, 1440)),
+        display(3, rect(1920, 0, 1280, 1024)),
+    ];
+    assert!(desktop_summary(&displays).is_none());
+
+    displays[0].active = true;
+    displays[1].active = true;
+    let desktop = desktop_summary(&displays).unwrap();
+    assert_eq!(desktop.bounds, rect(-2560, -360, 4480, 1440));
+    // The strip of the bounds above the primary isn't covered.
+    assert_eq!(desktop.pixels, 1920 * 1080 + 2560 * 1440);
+    assert_eq!(desktop.displays.len(), 2);
+    assert_eq!(desktop.displays[1].source_id, 2);
+    assert_eq!(desktop.displays[1].rect.x, -2560);
+}
+
+fn active(source_id: u32, area: Rect) -> DisplayInfo {
+    DisplayInfo { active: true, ..display(source_id, area) }
+}
+
+fn positions(layout: &[SourceMode]) -> Vec<(u32, i32, i32)> {
+    layout.iter().map(|s| (s.source_id, s.x, s.y)).collect()
+}
+
+#[test]
+fn test_presets_keep_the_primary_at_the_origin() {
+    // Display 2 is the primary, sitting right of display 1.
+    let displays = [
+        active(1, rect(-1920, 0, 1920, 1080)),
+        active(2, rect(0, 0, 2560, 1440)),
+        active(3, rect(2560, 200, 1920, 1080)),
+        display(4, rect(9000, 0, 1920, 1080)),
+    ];
+
+    let row = preset_layout(&displays, &[], Preset::Row).unwrap();
+    assert_eq!(positions(&row), vec![(1, -1920, 0), (2, 0, 0), (3, 2560, 0)]);
+
+    let ordered = preset_layout(&displays, &[2, 1], Preset::Row).unwrap();
+    assert_eq!(positions(&ordered), vec![(2, 0, 0), (1, 2560, 0), (3, 4480, 0)]);
+
+    let column = preset_layout(&displays, &[], Preset::Column).unwrap();
+    assert_eq!(positions(&column), vec![(1, 0, -1080), (2, 0, 0), (3, 0, 1440)]);
+
+    let grid = preset_layout(&displays, &[1, 2, 3], Preset::Grid { columns: 2 }).unwrap();
+    assert_eq!(positions(&grid), vec![(1, -1920, 0), (2, 0, 0), (3, -1920, 1440)]);
+}
+
+#[test]
+fn test_preset_refuses_unknown_ids_and_oversized_desktops() {
+    let displays = [active(1, rect(0, 0, 3840, 2160)), active(2, rect(3840, 0, 3840, 2160))];
+    assert!(matches!(
+        preset_layout(&displays, &[7], Preset::Row),
+        Err(DisplayError::DisplayNotFound(7))
+    ));
+
+    let wall: Vec<DisplayInfo> = (1..=9).map(|id| active(id, rect(0, 0, 3840, 2160))).collect();
+    assert!(matches!(
+        preset_layout(&wall, &[], Preset::Row),
+        Err(DisplayError::DesktopTooLarge { width: 34560, height: 2160 })
+    ));
+    assert!(preset_layout(&wall, &[], Preset::Grid { columns: 3 }).is_ok());
+}
+
+#[test]
+fn test_reflow_moves_displays_right_of_and_below() {
+    let layout = [
+        (1, rect(0, 0, 1920, 1080)),
+        (2, rect(1920, 0, 2560, 1440)),
+        (3, rect(4480, 0, 1920, 1080)),
+        (4, rect(4480, 1080, 1920, 1080)),
+    ];
+    let reflowed = reflow(&layout, &[(2, (1920, 1080))]);
+    assert_eq!(
+        reflowed,
+        [
+            (1, rect(0, 0, 1920, 1080)),
+            (2, rect(1920, 0, 1920, 1080)),
+            (3, rect(3840, 0, 1920, 1080)),
+            (4, rect(3840, 1080, 1920, 1080)),
+        ]
+    );
+}
+
+#[test]
+fn test_reflow_keeps_alignment_and_scales_gaps() {
+    // Centered below a display that grows.
+    let below = [(1, rect(0, 0, 2560, 1440)), (2, rect(320, 1440, 1920, 1080))];
+    assert_eq!(reflow(&below, &[(1, (3840, 2160))])[1], (2, rect(960, 2160, 1920, 1080)));
+
+    // A 100 pixel gap next to a display twice as wide becomes 200.
+    let gap = [(1, rect(0, 0, 1920, 1080)), (2, rect(2020, 0, 1920, 1080))];
+    assert_eq!(reflow(&gap, &[(1, (3840, 2160))])[1], (2, rect(4040, 0, 1920, 1080)));
+}
+
+#[test]
+fn test_reflow_without_resizing_changes_nothing() {
+    let layout = [
+        (1, rect(0, 0, 2560, 1440)),
+        (2, rect(-1920, 360, 1920, 1080)),
+        (3, rect(2660, 200, 1920, 1080)),
+        (4, rect(500, 1440, 1280, 1024)),
+    ];
+    assert_eq!(reflow(&layout, &[]), layout);
+    assert_eq!(reflow(&layout, &[(1, (2560, 1440))]), layout);
+}
+
+#[test]
+fn test_reflow_layout_keeps_the_primary_at_the_origin() {
+    // Display 1 is bottom-aligned left of the primary, which shrinks.
+    let displays = [active(1, rect(-1920, 360, 1920, 1080)), active(2, rect(0, 0, 2560, 1440))];
+    let resized = SourceMode { width: 1920, height: 1080, ..SourceMode::of(&displays[1]) };
+    let layout = reflow_layout(&displays, &[resized]).unwrap();
+    assert_eq!(positions(&layout), vec![(1, -1920, 0), (2, 0, 0)]);
+    assert_eq!((layout[1].width, layout[1].height), (1920, 1080));
+}
+
+#[test]
+fn test_reflow_layout_refuses_overlaps() {
+    // Shrinking one display of a 2x2 grid can't keep every edge attached.
+    let displays = [
+        active(1, rect(0, 0, 1920, 1080)),
+        active(2, rect(1920, 0, 1920, 1080)),
+        active(3, rect(0, 1080, 1920, 1080)),
+        active(4, rect(1920, 1080, 1920, 1080)),
+    ];
+    let resized = SourceMode { width: 1280, height: 720, ..SourceMode::of(&displays[0]) };
+    assert!(matches!(reflow_layout(&displays, &[resized]), Err(DisplayError::Overlap(2, 3))));
+}
+
+#[test]
+fn test_desktop_excess_at_the_coordinate_limits() {
+    assert_eq!(desktop_excess(&[]), 0);
+    assert_eq!(desktop_excess(&[(1, rect(-32768, 0, 3840, 2160)), (2, rect(28927, 0, 3840, 2160))]), 0);
+    assert_eq!(desktop_excess(&[(1, rect(28928, 0, 3840, 2160))]), 1);
+    assert_eq!(desktop_excess(&[(1, rect(0, -34000, 1920, 1080)), (2, rect(0, 32000, 1920, 1080))]), 1232);
+    // Edges past i32 are measured, not saturated.
+    assert_eq!(desktop_excess(&[(1, rect(i32::MAX, 0, u32::MAX, 1))]), 2_147_450_880 + u64::from(u32::MAX));
+    assert_eq!(desktop_excess(&[(1, rect(i32::MIN, i32::MIN, 0, 0))]), 2_147_450_880);
+}
+
+#[test]
+fn test_fit_desktop_moves_the_primary_to_the_origin() {
+    // Far off the origin but within range of each other.
+    let mut layout = [(1, rect(1_000_000, 500_000, 3840, 2160)), (2, rect(1_003_840, 500_000, 3840, 2160))];
+    fit_desktop(&mut layout, 2).unwrap();
+    assert_eq!(layout, [(1, rect(-3840, 0, 3840, 2160)), (2, rect(0, 0, 3840, 2160))]);
+
+    // A wall 16 displays wide only fits with the primary in the middle.
+    let wall = |primary| {
+        let mut layout: Vec<(u32, Rect)> = (0..16).map(|i| (i, rect(i32::try_from(i).unwrap() * 3840, 0, 3840, 2160))).collect();
+        fit_desktop(&mut layout, primary).map(|()| layout)
+    };
+    assert!(matches!(wall(0), Err(DisplayError::DesktopOutOfRange(28673))));
+    let fitted = wall(8).unwrap();
+    assert_eq!((fitted[0].1.x, fitted[15].1.right()), (-30720, 30720));
+
+    let mut extreme = [(1, rect(i32::MIN, 0, 1920, 1080)), (2, rect(i32::MAX, 0, 1920, 1080))];
+    assert!(matches!(fit_desktop(&mut extreme, 1), Err(DisplayError::DesktopOutOfRange(_))));
+    assert_eq!(DisplayError::DesktopOutOfRange(1).kind(), "desktop_out_of_range");
+}
@@ -0,0 +1,53 @@
+use display_tuner::display::{ColorEncoding, Connector};
+use display_tuner::link::{bandwidth_hint, bits_per_pixel, mode_bandwidth, pixel_rate, LinkInfo};
+
+/// 4K60 over an HDMI adapter on a DP port, 8 bits RGB with standard CTA timings.
+fn hdmi_4k60() -> LinkInfo {
+    LinkInfo {
+        connector: Connector::Hdmi,
+        base_connector: Connector::DisplayPort,
+        pixel_rate: 594_000_000,
+        active: (3840, 2160),
+        total: (4400, 2250),
+        bits_per_pixel: 24,
+    }
+}
+
+#[test]
+fn test_reduced_blanking_pixel_rate() {
+    assert_eq!(pixel_rate(3840, 2160, 60), 522_614_400);
+    assert_eq!(pixel_rate(3840, 2160, 120), 1_075_804_800);
+    assert_eq!(pixel_rate(1920, 1080, 60), 133_320_000);
+    // 10 bits RGB at 4K120 is the ~32 Gbps HDMI 2.0 can't carry.
+    assert_eq!(mode_bandwidth(3840, 2160, 120, bits_per_pixel(10, ColorEncoding::Rgb)) / 1_000_000_000, 32);
+}
+
+#[test]
+fn test_bits_per_pixel_by_encoding() {
+    assert_eq!(bits_per_pixel(0, ColorEncoding::Rgb), 24);
+    assert_eq!(bits_per_pixel(10, ColorEncoding::YCbCr444), 30);
+    assert_eq!(bits_per_pixel(10, ColorEncoding::YCbCr422), 20);
+    assert_eq!(bits_per_pixel(8, ColorEncoding::YCbCr420), 12);
+}
+
+#[test]
+fn test_link_description() {
+    assert_eq!(hdmi_4k60().to_string(), "hdmi (over dp), 3840x2160 of 4400x2250 at 594.0 MHz, 24 bits per pixel, ~14 Gbps");
+    let link = LinkInfo { connector: Connector::DisplayPort, base_connector: Connector::DisplayPort, pixel_rate: 148_500_000, ..hdmi_4k60() };
+    assert!(link.to_string().starts_with("dp, "));
+    assert!(link.to_string().ends_with("at 148.5 MHz, 24 bits per pixel, ~3.6 Gbps"));
+}
+
+#[test]
+fn test_bandwidth_hint_only_beyond_the_link() {
+    let link = hdmi_4k60();
+    assert_eq!(
+        bandwidth_hint(&link, 3840, 2160, 120).as_deref(),
+        Some("3840x2160 @ 120Hz needs ~26 Gbps; the current dp link is reporting ~14 Gbps, check the cable and port")
+    );
+    // The current mode and smaller ones fit, whatever their timings.
+    assert_eq!(bandwidth_hint(&link, 3840, 2160, 60), None);
+    assert_eq!(bandwidth_hint(&link, 2560, 1440, 100), None);
+    // Without a signal there's nothing to compare with.
+    assert_eq!(bandwidth_hint(&LinkInfo::default(), 3840, 2160, 120), None);
+}
@@ -0,0 +1,71 @@
+use display_tuner::display::DisplayInfo;
+use display_tuner::metrics::{self, EventLoopStats, LatencyHistogram, Phase, PhaseStats, Stats, BUCKET_BOUNDS_MS};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[test]
+fn test_stats_json_schema() {
+    let histogram = LatencyHistogram { count: 2, sum_ms: 130, bounds_ms: vec![10, 50], counts: vec![1, 0, 1] };
+    let stats = Stats {
+        started_at: 1_760_000_000,
+        uptime_secs: 3600,
+        applies: 5,
+        failures: BTreeMap::from([("display_not_found".to_string(), 1)]),
+        last_topology_change: Some(1_760_000_100),
+        event_loop: EventLoopStats { last_lag_ms: 3, max_lag_ms: 2400 },
+        phases: PhaseStats { enumerate: histogram.clone(), ..PhaseStats::default() },
+    };
+    let empty = json!({"count": 0, "sum_ms": 0, "bounds_ms": [], "counts": []});
+    let expected = json!({
+        "started_at": 1_760_000_000,
+        "uptime_secs": 3600,
+        "applies": 5,
+        "failures": {"display_not_found": 1},
+        "last_topology_change": 1_760_000_100,
+        "event_loop": {"last_lag_ms": 3, "max_lag_ms": 2400},
+        "phases": {
+            "enumerate": {"count": 2, "sum_ms": 130, "bounds_ms": [10, 50], "counts": [1, 0, 1]},
+            "display_config": empty,
+            "scaling": empty,
+            "request": empty,
+        },
+    });
+    assert_eq!(serde_json::to_value(&stats).unwrap(), expected);
+    assert_eq!(serde_json::from_value::<Stats>(expected).unwrap(), stats);
+    // No topology change yet is `null`, not a missing field.
+    let fresh = serde_json::to_value(Stats::default()).unwrap();
+    assert_eq!(fresh["last_topology_change"], serde_json::Value::Null);
+}
+
+#[test]
+fn test_counters_accumulate() {
+    let before = metrics::snapshot();
+    metrics::record_apply(None);
+    metrics::record_apply(Some("overlap"));
+    metrics::record_phase(Phase::Scaling, Duration::from_millis(70));
+    metrics::record_phase(Phase::Scaling, Duration::from_mins(1));
+    let after = metrics::snapshot();
+
+    assert_eq!(after.applies - before.applies, 2);
+    assert_eq!(after.failures["overlap"] - before.failures.get("overlap").copied().unwrap_or(0), 1);
+    let scaling = after.phases.scaling;
+    assert_eq!(scaling.bounds_ms, BUCKET_BOUNDS_MS);
+    assert_eq!(scaling.counts.len(), BUCKET_BOUNDS_MS.len() + 1);
+    // 70 ms falls in the bucket up to 100 ms, a minute beyond the last bound.
+    assert_eq!(scaling.counts[2] - before.phases.scaling.counts[2], 1);
+    assert_eq!(scaling.counts[BUCKET_BOUNDS_MS.len()] - before.phases.scaling.counts[BUCKET_BOUNDS_MS.len()], 1);
+    assert_eq!(scaling.count - before.phases.scaling.count, 2);
+}
+
+#[test]
+fn test_topology_change_needs_other_displays() {
+    let display = |path: &str, active| DisplayInfo { device_path: path.to_string(), active, ..DisplayInfo::default() };
+    metrics::observe_topology(&[display("path-a", true), display("path-b", true)]);
+    // The same displays in another order aren't a change.
+    metrics::observe_topology(&[display("PATH-B", true), display("path-a", true)]);
+    assert_eq!(metrics::snapshot().last_topology_change, None);
+
+    metrics::observe_topology(&[display("path-a", true), display("path-b", false)]);
+    assert!(metrics::snapshot().last_topology_change.is_some());
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "async")]
+
+use display_tuner::nonblocking::spawn_blocking;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::pin;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+/// Wakes the thread blocked in [`block_on`].
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[test]
+fn test_future_resolves_with_the_result() {
+    let future = spawn_blocking(|| {
+        thread::sleep(Duration::from_millis(50));
+        21 * 2
+    });
+    assert_send(&future);
+    assert_eq!(block_on(future), 42);
+}
+
+#[test]
+fn test_pending_until_the_call_returns() {
+    let (release, released) = mpsc::channel::<()>();
+    let mut future = spawn_blocking(move || released.recv().is_ok());
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    assert!(pin!(&mut future).poll(&mut Context::from_waker(&waker)).is_pending());
+
+    release.send(()).unwrap();
+    assert!(block_on(future));
+}
+
+#[test]
+fn test_dropping_the_future_lets_the_call_finish() {
+    let (done, finished) = mpsc::channel();
+    drop(spawn_blocking(move || {
+        thread::sleep(Duration::from_millis(50));
+        done.send(()).unwrap();
+    }));
+    assert!(finished.recv_timeout(Duration::from_secs(5)).is_ok());
+}
+
+#[test]
+fn test_panic_is_resumed_on_poll() {
+    let future = spawn_blocking(|| -> u32 { panic!("driver exploded") });
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| block_on(future))).unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"driver exploded"));
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "notify")]
+
+use display_tuner::display::DisplayInfo;
+use display_tuner::notify::{change_lines, Toast, ToastLimiter};
+use std::time::{Duration, Instant};
+
+fn display(path: &str, name: &str, width: u32, height: u32, scaling: i32) -> DisplayInfo {
+    DisplayInfo {
+        device_path: path.to_string(),
+        friendly_name: name.to_string(),
+        source_id: 2,
+        width,
+        height,
+        scaling_current: scaling,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_only_changed_displays_are_announced() {
+    let before = [display("A", "DELL U2720", 2560, 1440, 100), display("B", "", 1920, 1080, 100), display("C", "LG", 1920, 1080, 125)];
+    let after = [display("A", "DELL U2720", 3840, 2160, 150), display("B", "", 1920, 1080, 125), display("C", "LG", 1920, 1080, 125), display("D", "New", 1280, 720, 100)];
+    assert_eq!(change_lines(&before, &after), ["Set DELL U2720 to 3840x2160 @ 150%", "Set display 2 to 1920x1080 @ 125%"]);
+    assert!(change_lines(&after, &after).is_empty());
+}
+
+#[test]
+fn test_toast_document() {
+    let toast = Toast { lines: vec!["Set A&B <1> to 1920x1080 @ 100%".to_string()], revert: false };
+    let xml = toast.xml();
+    assert!(xml.contains("<text>Set A&amp;B &lt;1&gt; to 1920x1080 @ 100%</text>"));
+    assert!(!xml.contains("<actions>"));
+    assert!(Toast { revert: true, ..toast }.xml().contains(r#"<action content="Revert" arguments="revert""#));
+}
+
+#[test]
+fn test_toasts_are_rate_limited() {
+    let t0 = Instant::now();
+    let mut limiter = ToastLimiter::new(Duration::from_secs(10));
+    assert!(limiter.allow(t0));
+    assert!(!limiter.allow(t0 + Duration::from_secs(9)));
+    assert!(limiter.allow(t0 + Duration::from_secs(10)));
+    // Skipped toasts don't push the next slot back.
+    assert!(!limiter.allow(t0 + Duration::from_secs(15)));
+    assert!(limiter.allow(t0 + Duration::from_secs(20)));
+}
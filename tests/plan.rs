@@ -0,0 +1,237 @@
+use display_tuner::display::{scaling_at_index, scaling_steps, ApplyOrder, ApplyPolicy, DisplayConfig, DisplayError, DisplayInfo, Rotation};
+use display_tuner::plan::{execute_on, plan, Backend, DisplayChange, Impact, Operation, Target};
+
+fn display(source_id: u32, width: u32, height: u32, scaling: i32) -> DisplayInfo {
+    DisplayInfo {
+        source_id,
+        width,
+        height,
+        refresh_rate: 60,
+        scaling_current: scaling,
+        target_available: true,
+        active: true,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_plan_lists_only_what_differs() {
+    let displays = [display(1, 2560, 1440, 125), display(2, 1920, 1080, 100)];
+    let targets = [
+        Target { refresh: Some(144), ..Target::config(1, &DisplayConfig { width: 1920, height: 1080, scaling: 125 }) },
+        Target::config(2, &DisplayConfig { width: 1920, height: 1080, scaling: 100 }),
+    ];
+    let changes = plan(&displays, &targets).unwrap();
+    assert_eq!(changes.changes.len(), 1);
+    assert_eq!(
+        changes.changes[0].operations,
+        [Operation::SetMode { from: (2560, 1440), to: (1920, 1080) }, Operation::SetRefresh { from: 60, to: 144 }]
+    );
+    assert_eq!(changes.to_string(), "Display 1: resolution 2560x1440 -> 1920x1080 (mode switch), refresh 60Hz -> 144Hz (mode switch)");
+    assert_eq!(plan(&displays, &targets[1..]).unwrap().to_string(), "No changes");
+}
+
+#[test]
+fn test_rotation_turns_the_resolution() {
+    let displays = [display(1, 2560, 1440, 100)];
+    let changes = plan(&displays, &[Target { source_id: 1, rotation: Some(Rotation::Rotate90), ..Target::default() }]).unwrap();
+    assert_eq!(
+        changes.changes[0].operations,
+        [
+            Operation::SetRotation { from: Rotation::Identity, to: Rotation::Rotate90 },
+            Operation::SetMode { from: (2560, 1440), to: (1440, 2560) },
+        ]
+    );
+    assert_eq!(changes.changes[0].after().rotation, Rotation::Rotate90);
+}
+
+#[test]
+fn test_inverse_plans_back_to_the_start() {
+    let before = [display(1, 2560, 1440, 125)];
+    let target = Target { scaling: Some(150), ..Target::config(1, &DisplayConfig { width: 1920, height: 1080, scaling: 150 }) };
+    let changes = plan(&before, &[target]).unwrap();
+
+    let inverse = changes.inverse();
+    assert_eq!(inverse.changes[0].display.width, 1920);
+    assert_eq!(
+        inverse.changes[0].operations,
+        [Operation::SetMode { from: (1920, 1080), to: (2560, 1440) }, Operation::SetScaling { from: 150, to: 125 }]
+    );
+    let after = [changes.changes[0].after()];
+    assert_eq!(plan(&after, &inverse.targets()).unwrap(), inverse);
+    assert!(plan(&before, &inverse.targets()).unwrap().is_empty());
+}
+
+#[test]
+fn test_plan_refuses_impossible_targets() {
+    let mut asleep = display(1, 2560, 1440, 100);
+    asleep.target_available = false;
+    let mut custom = display(2, 2560, 1440, 100);
+    custom.custom_scaling = true;
+    let displays = [asleep, custom, display(3, 2560, 1440, 100)];
+
+    let scaling = |source_id, scaling| Target { source_id, scaling: Some(scaling), ..Target::default() };
+    assert!(matches!(plan(&displays, &[scaling(1, 125)]), Err(DisplayError::TargetUnavailable(1))));
+    assert!(plan(&displays, &[scaling(1, 100)]).unwrap().is_empty());
+    assert!(matches!(plan(&displays, &[scaling(2, 125)]), Err(DisplayError::CustomScalingActive(2))));
+    assert!(matches!(plan(&displays, &[scaling(3, 130)]), Err(DisplayError::UnsupportedScaling { source_id: 3, scaling: 130 })));
+    assert!(matches!(plan(&displays, &[scaling(9, 125)]), Err(DisplayError::DisplayNotFound(9))));
+}
+
+#[test]
+fn test_operation_impact() {
+    assert_eq!(Operation::SetScaling { from: 100, to: 150 }.impact(), Impact::Seamless);
+    assert_eq!(Operation::SetMode { from: (2560, 1440), to: (1920, 1080) }.impact(), Impact::ModeSwitch);
+    assert_eq!(Operation::SetRefresh { from: 60, to: 144 }.impact(), Impact::ModeSwitch);
+    assert_eq!(Operation::SetRotation { from: Rotation::Identity, to: Rotation::Rotate90 }.impact(), Impact::ModeSwitch);
+}
+
+#[test]
+fn test_split_by_impact() {
+    let displays = [display(1, 2560, 1440, 100), display(2, 1920, 1080, 100)];
+    let targets = [
+        Target::config(1, &DisplayConfig { width: 1920, height: 1080, scaling: 125 }),
+        Target::config(2, &DisplayConfig { width: 2560, height: 1440, scaling: 100 }),
+    ];
+    let (seamless, mode_switch) = plan(&displays, &targets).unwrap().split_by_impact();
+    assert_eq!(seamless.to_string(), "Display 1: scaling 100% -> 125% (seamless)");
+    assert_eq!(mode_switch.changes.len(), 2);
+    assert_eq!(mode_switch.changes[1].operations, [Operation::SetMode { from: (1920, 1080), to: (2560, 1440) }]);
+}
+
+/// Stands in for Windows: the scaling is kept as steps from the recommended scaling of the
+/// current resolution, so a mode switch moves it, and after a switch the display still reports
+/// its old mode for `lag` queries.
+struct Mock {
+    size: (u32, u32),
+    previous: (u32, u32),
+    /// The recommended scaling at each resolution.
+    recommended: Vec<((u32, u32), i32)>,
+    steps: i32,
+    lag: u32,
+    pending: u32,
+    calls: Vec<&'static str>,
+}
+
+impl Mock {
+    fn new(display: &DisplayInfo, recommended: &[((u32, u32), i32)], lag: u32) -> Self {
+        let size = (display.width, display.height);
+        let mut mock = Self { size, previous: size, recommended: recommended.to_vec(), steps: 0, lag, pending: 0, calls: Vec::new() };
+        mock.steps = scaling_steps(mock.recommended_at(size), display.scaling_current).unwrap();
+        mock
+    }
+
+    fn recommended_at(&self, size: (u32, u32)) -> i32 {
+        self.recommended.iter().find(|(s, _)| *s == size).unwrap().1
+    }
+
+    /// The scaling in effect, clamped at 100% as Windows does.
+    fn scaling(&self) -> i32 {
+        let index = scaling_steps(100, self.recommended_at(self.size)).unwrap() + self.steps;
+        scaling_at_index(usize::try_from(index.max(0)).unwrap())
+    }
+}
+
+impl Backend for Mock {
+    fn check_fresh(&mut self, display: &DisplayInfo) -> Result<u64, DisplayError> {
+        if (display.width, display.height) == self.size { Ok(1) } else { Err(DisplayError::Stale(display.source_id)) }
+    }
+
+    fn set_modes(&mut self, changes: &[&DisplayChange], baseline: u64, _policy: ApplyPolicy) -> Result<(bool, u64), DisplayError> {
+        for operation in changes.iter().flat_map(|c| &c.operations) {
+            if let Operation::SetMode { to, .. } = *operation {
+                (self.previous, self.size, self.pending) = (self.size, to, self.lag);
+            }
+        }
+        self.calls.push("modes");
+        Ok((false, baseline))
+    }
+
+    fn live_scaling(&mut self, _display: &DisplayInfo) -> Result<((u32, u32), i32), DisplayError> {
+        if self.pending > 0 {
+            self.pending -= 1;
+            return Ok((self.previous, self.recommended_at(self.previous)));
+        }
+        Ok((self.size, self.recommended_at(self.size)))
+    }
+
+    fn set_scaling_steps(&mut self, _display: &DisplayInfo, steps: i32, _baseline: u64) -> Result<(), DisplayError> {
+        self.calls.push("scaling");
+        self.steps = steps;
+        Ok(())
+    }
+}
+
+const RECOMMENDED: [((u32, u32), i32); 3] = [((1920, 1080), 100), ((2560, 1440), 125), ((3840, 2160), 150)];
+
+#[test]
+fn test_scaling_after_a_new_resolution_counts_from_its_recommended_scaling() {
+    let displays = [DisplayInfo { scaling_recommended: 100, ..display(1, 1920, 1080, 100) }];
+    let changes = plan(&displays, &[Target::config(1, &DisplayConfig { width: 3840, height: 2160, scaling: 150 })]).unwrap();
+    let mut mock = Mock::new(&displays[0], &RECOMMENDED, 1);
+    execute_on(&mut mock, &changes, ApplyPolicy::Strict, None).unwrap();
+    // Counted from the 100% recommended at 1080p, the steps would land on 200%.
+    assert_eq!(mock.scaling(), 150);
+    assert_eq!(mock.calls, ["modes", "scaling"]);
+}
+
+#[test]
+fn test_scaling_first_is_set_again_when_the_recommended_scaling_moves() {
+    let displays = [DisplayInfo { scaling_recommended: 150, ..display(1, 3840, 2160, 150) }];
+    let changes = plan(&displays, &[Target::config(1, &DisplayConfig { width: 2560, height: 1440, scaling: 125 })]).unwrap();
+    let mut mock = Mock::new(&displays[0], &RECOMMENDED, 1);
+    execute_on(&mut mock, &changes, ApplyPolicy::Strict, None).unwrap();
+    assert_eq!(mock.scaling(), 125);
+    assert_eq!(mock.calls, ["scaling", "modes", "scaling"]);
+}
+
+#[test]
+fn test_scaling_without_a_new_resolution_counts_from_the_planned_recommended_scaling() {
+    let displays = [DisplayInfo { scaling_recommended: 150, ..display(1, 3840, 2160, 150) }];
+    let target = Target { source_id: 1, refresh: Some(144), scaling: Some(125), ..Target::default() };
+    let changes = plan(&displays, &[target]).unwrap();
+    let mut mock = Mock::new(&displays[0], &RECOMMENDED, 1);
+    execute_on(&mut mock, &changes, ApplyPolicy::Strict, Some(ApplyOrder::ResolutionFirst)).unwrap();
+    assert_eq!(mock.scaling(), 125);
+    assert_eq!(mock.calls, ["modes", "scaling"]);
+}
+
+#[test]
+fn test_steps_from_the_planned_recommended_scaling_would_land_off_after_the_switch() {
+    let displays = [DisplayInfo { scaling_recommended: 100, ..display(1, 1920, 1080, 125) }];
+    let changes = plan(&displays, &[Target::config(1, &DisplayConfig { width: 3840, height: 2160, scaling: 175 })]).unwrap();
+    let mut mock = Mock::new(&displays[0], &RECOMMENDED, 2);
+    execute_on(&mut mock, &changes, ApplyPolicy::Strict, Some(ApplyOrder::ResolutionFirst)).unwrap();
+    assert_eq!(mock.scaling(), 175);
+
+    // The same change with the steps counted from the 100% the plan started with, which Windows
+    // takes from the 150% recommended at 4K.
+    let mut planned = Mock::new(&displays[0], &RECOMMENDED, 0);
+    planned.size = (3840, 2160);
+    planned.steps = scaling_steps(displays[0].scaling_recommended, 175).unwrap();
+    assert_eq!(planned.scaling(), 225);
+}
+
+#[test]
+fn test_scaling_steps() {
+    assert_eq!(scaling_steps(150, 125), Some(-1));
+    assert_eq!(scaling_steps(300, 100), Some(-7));
+    assert_eq!(scaling_steps(100, 175), Some(3));
+    // Recommended past the table, as for very dense panels.
+    assert_eq!(scaling_steps(550, 500), Some(-1));
+    assert_eq!(scaling_steps(150, 110), None);
+}
+
+#[test]
+fn test_default_order_keeps_the_larger_scaling_out_of_the_transient() {
+    let shrink = plan(&[display(1, 3840, 2160, 200)], &[Target::config(1, &DisplayConfig { width: 1920, height: 1080, scaling: 100 })]).unwrap();
+    assert!(shrink.changes[0].scaling_first(None));
+    assert!(!shrink.changes[0].scaling_first(Some(ApplyOrder::ResolutionFirst)));
+
+    let grow = shrink.inverse();
+    assert!(!grow.changes[0].scaling_first(None));
+    assert!(grow.changes[0].scaling_first(Some(ApplyOrder::ScalingFirst)));
+
+    let mode_only = plan(&[display(1, 3840, 2160, 200)], &[Target::config(1, &DisplayConfig { width: 1920, height: 1080, scaling: 200 })]).unwrap();
+    assert!(!mode_only.changes[0].scaling_first(Some(ApplyOrder::ScalingFirst)));
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "profiles")]
+
+use display_tuner::display::{Connector, DisplayInfo};
+use display_tuner::policy::{check_policy, Policy, ScalingExpectation};
+
+fn display(source_id: u32, connector: Connector, width: u32, scaling: i32, recommended: i32) -> DisplayInfo {
+    DisplayInfo {
+        friendly_name: format!("Display {source_id}"),
+        source_id,
+        width,
+        height: 1440,
+        scaling_current: scaling,
+        scaling_recommended: recommended,
+        connector,
+        primary: source_id == 1,
+        // "DEL" big-endian, as stored by Windows.
+        edid_manufacturer_id: 0xac10,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_parse_scaling_expectation() {
+    assert_eq!("recommended".parse::<ScalingExpectation>().unwrap(), ScalingExpectation::Recommended);
+    assert_eq!("125%".parse::<ScalingExpectation>().unwrap(), ScalingExpectation::Percent(125));
+    assert!("large".parse::<ScalingExpectation>().is_err());
+
+    let policy = Policy::parse(r#"{"scaling": "recommended", "displays": [{"match": {"vendor": "DEL"}, "scaling": 150}]}"#).unwrap();
+    assert_eq!(policy.scaling, Some(ScalingExpectation::Recommended));
+    assert_eq!(policy.displays[0].scaling, Some(ScalingExpectation::Percent(150)));
+    assert!(Policy::parse(r#"{"scaling": "big"}"#).is_err());
+    for scaling in ["125", "125.0", "1.25", "\"1.25x\""] {
+        let policy = Policy::parse(&format!(r#"{{"scaling": {scaling}}}"#)).unwrap();
+        assert_eq!(policy.scaling, Some(ScalingExpectation::Percent(125)), "{scaling}");
+    }
+    assert!(Policy::parse(r#"{"scaling": -1}"#).is_err());
+    assert!(Policy::parse(r#"{"refresh": 60}"#).is_err());
+}
+
+#[test]
+fn test_recommended_scaling_is_per_display() {
+    let displays = [
+        display(1, Connector::Internal, 2560, 150, 150),
+        display(2, Connector::DisplayPort, 2560, 100, 125),
+    ];
+    let policy = Policy { scaling: Some(ScalingExpectation::Recommended), ..Policy::default() };
+    let results = check_policy(&policy, &displays);
+    assert!(results[0].passed());
+    assert!(!results[1].passed());
+    assert_eq!(results[1].failures[0].field, "scaling");
+    assert_eq!(results[1].failures[0].expected, "recommended 125%");
+    assert_eq!(results[1].failures[0].actual, "100%");
+}
+
+#[test]
+fn test_policy_entries_and_primary() {
+    let policy = Policy::parse(
+        r#"{
+            "scaling": 100,
+            "displays": [
+                {"match": {"connector": "internal"}, "scaling": 150},
+                {"match": {"vendor": "DEL"}, "width": 3840, "height": 2160}
+            ],
+            "primary": {"connector": "dp"}
+        }"#,
+    )
+    .unwrap();
+    let displays = [
+        display(1, Connector::Internal, 2560, 150, 150),
+        display(2, Connector::DisplayPort, 2560, 100, 125),
+    ];
+    let results = check_policy(&policy, &displays);
+
+    let fields = |i: usize| results[i].failures.iter().map(|f| f.field).collect::<Vec<_>>();
+    // The first matching entry wins, so the internal panel's resolution isn't checked.
+    assert_eq!(fields(0), vec!["primary"]);
+    assert_eq!(fields(1), vec!["resolution", "primary"]);
+    assert_eq!(results[1].failures[0].expected, "3840x2160");
+}
@@ -0,0 +1,302 @@
+#![cfg(feature = "profiles")]
+
+use display_tuner::display::{Connector, DisplayError, DisplayInfo, Rotation};
+use display_tuner::profile::{apply_profile, diff_profile, match_displays, ConnectionContext, Profile, ProfileEntry, Slot};
+use display_tuner::progress::{cancel, check_cancelled};
+use display_tuner::selector::DisplayFilter;
+
+fn display(source_id: u32, device_path: &str, product: u16, width: u32, height: u32) -> DisplayInfo {
+    DisplayInfo {
+        friendly_name: format!("Monitor {source_id}"),
+        source_id,
+        width,
+        height,
+        scaling_current: 100,
+        scaling_recommended: 100,
+        device_path: device_path.to_string(),
+        edid_manufacturer_id: 0x10ac,
+        edid_product_id: product,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_identical_profile_has_no_diff() {
+    let displays = vec![display(1, "path-a", 1, 2560, 1440), display(2, "path-b", 2, 1920, 1080)];
+    let profile = Profile::capture(&displays);
+
+    assert!(diff_profile(&profile, &displays).is_empty());
+}
+
+#[test]
+fn test_diff_reports_changed_fields() {
+    let displays = vec![display(1, "path-a", 1, 2560, 1440)];
+    let mut profile = Profile::capture(&displays);
+    profile.displays[0].width = 3840;
+    profile.displays[0].height = 2160;
+    profile.displays[0].scaling = 150;
+    profile.displays[0].rotation = Rotation::Rotate90;
+
+    let diff = diff_profile(&profile, &displays);
+    assert_eq!(diff.changed.len(), 1);
+    let fields: Vec<_> = diff.changed[0]
+        .fields
+        .iter()
+        .map(|f| (f.field, f.expected.as_str(), f.actual.as_str()))
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            ("resolution", "3840x2160", "2560x1440"),
+            ("scaling", "150%", "100%"),
+            ("rotation", "90°", "0°"),
+        ]
+    );
+}
+
+#[test]
+fn test_diff_lists_missing_and_unexpected() {
+    let connected = vec![display(1, "path-a", 1, 2560, 1440), display(3, "path-c", 3, 1920, 1080)];
+    let profile = Profile {
+        displays: vec![
+            ProfileEntry::capture(&connected[0]),
+            ProfileEntry::capture(&display(2, "path-b", 2, 1920, 1080)),
+        ],
+    };
+
+    let diff = diff_profile(&profile, &connected);
+    assert!(diff.changed.is_empty());
+    assert_eq!(diff.missing.len(), 1);
+    assert_eq!(diff.missing[0].device_path, "path-b");
+    assert_eq!(diff.unexpected.len(), 1);
+    assert_eq!(diff.unexpected[0].source_id, 3);
+}
+
+#[test]
+fn test_match_prefers_device_path_over_model() {
+    // Two identical monitors: the entry saved for the second one must pair with it by path
+    // even though the first one has the same EDID ids.
+    let connected = vec![display(1, "path-a", 7, 2560, 1440), display(2, "path-b", 7, 2560, 1440)];
+    let profile = Profile {
+        displays: vec![ProfileEntry::capture(&connected[1])],
+    };
+
+    let matched = match_displays(&profile, &connected);
+    assert_eq!(matched.pairs.len(), 1);
+    assert_eq!(matched.pairs[0].1.source_id, 2);
+
+    // After moving to another port the path changes, so the EDID model is used instead.
+    let moved = vec![display(5, "path-z", 7, 2560, 1440)];
+    let matched = match_displays(&profile, &moved);
+    assert_eq!(matched.pairs[0].1.source_id, 5);
+}
+
+/// The same monitor on the laptop's HDMI port and through a `DisplayLink` dock.
+fn docked(source_id: u32, device_path: &str, docked: bool) -> DisplayInfo {
+    DisplayInfo {
+        connector: if docked { Connector::Indirect } else { Connector::Hdmi },
+        adapter_name: if docked { r"\\?\USB#VID_17E9&PID_6006#1" } else { r"\\?\PCI#VEN_8086&DEV_A7A0#3" }.to_string(),
+        ..display(source_id, device_path, 7, 2560, 1440)
+    }
+}
+
+fn dock_entry(width: u32) -> ProfileEntry {
+    ProfileEntry {
+        width,
+        connection: Some(ConnectionContext { connector: Connector::Indirect, adapter: "vid_17e9".to_string() }),
+        ..ProfileEntry::capture(&docked(1, "path-dock", true))
+    }
+}
+
+#[test]
+fn test_connection_specific_entry_wins_over_generic() {
+    // The generic entry comes first and even has the exact device path.
+    let profile = Profile { displays: vec![ProfileEntry::capture(&docked(1, "path-dock", true)), dock_entry(1920)] };
+
+    let connected = [docked(1, "path-dock", true)];
+    let matched = match_displays(&profile, &connected);
+    assert_eq!(matched.pairs.len(), 1);
+    assert_eq!(matched.pairs[0].0.width, 1920);
+    assert!(matched.missing.is_empty());
+    assert_eq!(matched.superseded.len(), 1);
+
+    // Plugged in directly, only the generic entry applies.
+    let connected = [docked(2, "path-hdmi", false)];
+    let matched = match_displays(&profile, &connected);
+    assert_eq!(matched.pairs.len(), 1);
+    assert_eq!(matched.pairs[0].0.width, 2560);
+    assert!(matched.missing.is_empty());
+}
+
+#[test]
+fn test_entry_for_another_connection_never_matches() {
+    let profile = Profile { displays: vec![dock_entry(1920)] };
+
+    let connected = [docked(2, "path-dock", false)];
+    let matched = match_displays(&profile, &connected);
+    assert!(matched.pairs.is_empty());
+    assert_eq!(matched.missing.len(), 1);
+    assert_eq!(matched.unexpected.len(), 1);
+}
+
+#[test]
+fn test_merge_keeps_connection_entries() {
+    let mut profile = Profile { displays: vec![dock_entry(1920)] };
+    profile.merge(&[docked(1, "path-dock", true)]);
+
+    let entries: Vec<(u32, bool)> = profile.displays.iter().map(|e| (e.width, e.connection.is_some())).collect();
+    assert_eq!(entries, [(1920, true), (2560, false)]);
+    let json = serde_json::to_string(&profile).unwrap();
+    assert_eq!(json.matches(r#""connection":{"connector":"indirect","adapter":"vid_17e9"}"#).count(), 1);
+}
+
+#[test]
+fn test_merge_replaces_by_device_path_and_keeps_others() {
+    let mut state = Profile::capture(&[display(1, "path-a", 1, 2560, 1440), display(2, "path-b", 2, 1920, 1080)]);
+    state.merge(&[display(1, "path-a", 1, 1920, 1080), display(3, "path-c", 3, 3840, 2160)]);
+
+    let widths: Vec<(&str, u32)> = state.displays.iter().map(|e| (e.device_path.as_str(), e.width)).collect();
+    assert_eq!(widths, [("path-b", 1920), ("path-a", 1920), ("path-c", 3840)]);
+}
+
+#[test]
+fn test_recorded_for_keeps_connected_device_paths_only() {
+    let state = Profile::capture(&[display(1, "path-a", 1, 2560, 1440), display(2, "path-b", 2, 1920, 1080)]);
+    // Same model as path-b on another port: no record of its own.
+    let connected = [display(1, "path-a", 1, 1920, 1080), display(3, "path-c", 2, 1920, 1080)];
+
+    let recorded = state.recorded_for(&connected);
+    assert_eq!(recorded.displays.len(), 1);
+    assert_eq!(recorded.displays[0].device_path, "path-a");
+}
+
+#[test]
+fn test_profile_scaling_reads_ratios_and_writes_percent() {
+    let entry = |scaling: &str| {
+        format!(
+            r#"{{"displays": [{{"device_path": "PATH-1", "edid_manufacturer_id": 4268, "edid_product_id": 1, "friendly_name": "Monitor 1",
+            "width": 2560, "height": 1440, "scaling": {scaling}, "position_x": 0, "position_y": 0, "rotation": "identity"}}]}}"#
+        )
+    };
+    for scaling in ["125", "125.0", "1.25"] {
+        let profile: Profile = serde_json::from_str(&entry(scaling)).unwrap();
+        assert_eq!(profile.displays[0].scaling, 125, "{scaling}");
+        assert!(serde_json::to_string(&profile).unwrap().contains(r#""scaling":125,"#));
+    }
+    assert!(serde_json::from_str::<Profile>(&entry("0")).is_err());
+}
+
+#[test]
+fn test_slots_in_profile_json() {
+    let entry = |slot: &str| {
+        format!(r#"{{"displays": [{{"slot": {slot}, "width": 3840, "height": 2160, "scaling": 150, "position_x": 0, "position_y": 0, "rotation": "identity"}}]}}"#)
+    };
+    let slot = |text: &str| serde_json::from_str::<Profile>(&entry(text)).map(|p| p.displays[0].slot.clone().unwrap());
+    assert_eq!(slot(r#""internal""#).unwrap(), Slot::Internal);
+    assert_eq!(slot(r#""External[1]""#).unwrap(), Slot::External(1));
+    let any = slot(r#"{"any": {"min_width": 3840}}"#).unwrap();
+    assert_eq!(any, Slot::Any(DisplayFilter { min_width: Some(3840), ..DisplayFilter::default() }));
+    assert_eq!(any.to_string(), "any {min_width=3840}");
+    for bad in [r#""external""#, r#""external[-1]""#, r#""laptop""#, r#"{"any": {"colour": "red"}}"#] {
+        assert!(slot(bad).is_err(), "{bad}");
+    }
+
+    let profile: Profile = serde_json::from_str(&entry(r#"{"any": {"min_width": 3840}}"#)).unwrap();
+    let json = serde_json::to_string(&profile).unwrap();
+    assert!(json.contains(r#""slot":{"any":{"min_width":3840}}"#), "{json}");
+    assert_eq!(serde_json::from_str::<Profile>(&json).unwrap(), profile);
+    assert_eq!(profile.displays[0].label(), "slot any {min_width=3840}");
+}
+
+fn placed(source_id: u32, device_path: &str, x: i32, internal: bool) -> DisplayInfo {
+    DisplayInfo { active: true, position_x: x, internal, ..display(source_id, device_path, u16::try_from(source_id).unwrap(), 2560, 1440) }
+}
+
+fn slot_entry(slot: Slot, device_path: &str, width: u32) -> ProfileEntry {
+    ProfileEntry {
+        device_path: device_path.to_string(),
+        edid_manufacturer_id: 0,
+        edid_product_id: 0,
+        friendly_name: String::new(),
+        slot: Some(slot),
+        ..ProfileEntry::capture(&display(0, "", 0, width, 1440))
+    }
+}
+
+#[test]
+fn test_slot_candidates_follow_position() {
+    // Enumerated out of order, with an inactive display at the origin.
+    let displays = [
+        placed(3, "path-c", 2560, false),
+        DisplayInfo { active: false, ..placed(4, "path-d", 0, false) },
+        placed(1, "path-a", -1920, true),
+        placed(2, "path-b", 0, false),
+    ];
+    assert_eq!(Slot::Internal.candidates(&displays), [2]);
+    assert_eq!(Slot::External(0).candidates(&displays), [3]);
+    assert_eq!(Slot::External(1).candidates(&displays), [0]);
+    assert_eq!(Slot::External(2).candidates(&displays), [1]);
+    assert!(Slot::External(3).candidates(&displays).is_empty());
+    assert_eq!(Slot::Any(DisplayFilter::default()).candidates(&displays), [2, 3, 0, 1]);
+}
+
+#[test]
+fn test_slot_entries_survive_a_replaced_monitor() {
+    let profile = Profile { displays: vec![slot_entry(Slot::External(0), "path-old", 1920), slot_entry(Slot::Internal, "", 1280)] };
+
+    // The old unit is gone; a new one with another path and model takes its slot.
+    let connected = [placed(1, "path-laptop", 0, true), placed(2, "path-new", 2560, false)];
+    let matched = match_displays(&profile, &connected);
+    let pairs: Vec<(u32, u32)> = matched.pairs.iter().map(|(e, d)| (e.width, d.source_id)).collect();
+    assert_eq!(pairs, [(1920, 2), (1280, 1)]);
+    assert!(matched.missing.is_empty() && matched.unexpected.is_empty());
+
+    // With the old unit back, its exact path wins even though it's now the second external.
+    let connected = [placed(1, "path-laptop", 0, true), placed(2, "path-new", 2560, false), placed(3, "path-old", 5120, false)];
+    let matched = match_displays(&profile, &connected);
+    assert_eq!(matched.pairs[0].1.source_id, 3);
+    assert_eq!(matched.unexpected.len(), 1);
+    assert_eq!(matched.unexpected[0].source_id, 2);
+}
+
+#[test]
+fn test_slot_conflicts_resolve_deterministically() {
+    let wide = Slot::Any(DisplayFilter { min_width: Some(2560), ..DisplayFilter::default() });
+    // The `any` entry comes first, but positional slots are resolved before it; it moves on to
+    // the next display it matches. The second `external[0]` entry has nothing left.
+    let profile = Profile {
+        displays: vec![
+            slot_entry(wide.clone(), "", 100),
+            slot_entry(Slot::External(0), "", 200),
+            slot_entry(Slot::External(0), "", 300),
+        ],
+    };
+    let connected = [placed(2, "path-b", 2560, false), placed(1, "path-a", 0, false)];
+    let matched = match_displays(&profile, &connected);
+    let pairs: Vec<(u32, u32)> = matched.pairs.iter().map(|(e, d)| (e.width, d.source_id)).collect();
+    assert_eq!(pairs, [(100, 2), (200, 1)]);
+    assert_eq!(matched.missing.len(), 1);
+    assert_eq!(matched.missing[0].width, 300);
+
+    // The same result whatever order the displays are enumerated in.
+    let reversed = [connected[1].clone(), connected[0].clone()];
+    let again: Vec<(u32, u32)> = match_displays(&profile, &reversed).pairs.iter().map(|(e, d)| (e.width, d.source_id)).collect();
+    assert_eq!(again, pairs);
+}
+
+#[test]
+fn test_apply_forgets_an_earlier_cancel() {
+    cancel();
+    // Whether it gets to change anything depends on the machine; the cancel is cleared first.
+    let _ = apply_profile(&Profile { displays: Vec::new() }, &[]);
+    assert!(check_cancelled().is_ok());
+}
+
+#[test]
+fn test_profile_names_stay_in_the_profile_directory() {
+    for name in ["", "..", "../state", "..\\state", "sub/desk", "sub\\desk", "C:desk", "C:\\desk", "\\\\server\\share\\desk"] {
+        assert!(matches!(Profile::load(name), Err(DisplayError::InvalidProfileName(n)) if n == name), "{name}");
+        assert!(matches!(Profile::default().save(name), Err(DisplayError::InvalidProfileName(_))), "{name}");
+    }
+    assert!(!matches!(Profile::load("docked.v2"), Err(DisplayError::InvalidProfileName(_))));
+}
@@ -0,0 +1,45 @@
+use display_tuner::display::{DisplayError, DisplayInfo};
+use display_tuner::progress::{cancel, check_cancelled, DisplayProgress, PhaseStatus};
+use std::time::Duration;
+
+#[test]
+fn test_progress_line() {
+    let display = DisplayInfo { source_id: 3, friendly_name: "DELL U2720".to_string(), ..DisplayInfo::default() };
+    let mut progress = DisplayProgress::new(2, 6, &display);
+    progress.resolution = PhaseStatus::Running;
+    progress.elapsed = Duration::from_millis(350);
+    assert_eq!(progress.to_string(), "[2/6] DELL U2720: resolution ... (0.3s)");
+    assert!(!progress.is_finished());
+
+    progress.resolution = PhaseStatus::Ok;
+    progress.scaling = PhaseStatus::Ok;
+    progress.elapsed = Duration::from_millis(1250);
+    assert_eq!(progress.to_string(), "[2/6] DELL U2720: resolution ok, scaling ok (1.2s)");
+    assert!(progress.is_finished());
+    assert_eq!(DisplayProgress::new(1, 1, &DisplayInfo { source_id: 4, ..DisplayInfo::default() }).name, "Display 4");
+}
+
+#[test]
+fn test_progress_json() {
+    let progress = DisplayProgress {
+        index: 1,
+        total: 2,
+        source_id: 1,
+        name: "LG".to_string(),
+        resolution: PhaseStatus::RolledBack,
+        scaling: PhaseStatus::Skipped,
+        elapsed: Duration::from_millis(900),
+    };
+    let value = serde_json::to_value(&progress).unwrap();
+    assert_eq!(value["resolution"], "rolled_back");
+    assert_eq!(value["scaling"], "skipped");
+    assert_eq!(value["elapsed_ms"], 900);
+    assert_eq!(serde_json::from_value::<DisplayProgress>(value).unwrap(), progress);
+}
+
+#[test]
+fn test_cancel_fails_the_next_step() {
+    cancel();
+    assert!(matches!(check_cancelled(), Err(DisplayError::Cancelled)));
+    assert_eq!(DisplayError::Cancelled.kind(), "cancelled");
+}
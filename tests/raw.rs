@@ -0,0 +1,141 @@
+use display_tuner::display::{AdapterId, DisplayError};
+use display_tuner::raw::{config_fingerprint, dpi_set_header, find_adapter_path, find_path, retry_insufficient_buffer, source_mode, ModeInfo, PathInfo, SessionInfo, QUERY_ATTEMPTS};
+use windows::Win32::Devices::Display::{DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_MODE_INFO_TYPE, DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE, DISPLAYCONFIG_MODE_INFO_TYPE_TARGET, DISPLAYCONFIG_ROTATION_ROTATE90};
+use windows::Win32::Foundation::LUID;
+
+fn path(source_id: u32, mode_idx: u32) -> PathInfo {
+    let mut path = PathInfo::default();
+    path.sourceInfo.id = source_id;
+    path.sourceInfo.Anonymous.modeInfoIdx = mode_idx;
+    path
+}
+
+fn mode(info_type: DISPLAYCONFIG_MODE_INFO_TYPE) -> ModeInfo {
+    ModeInfo(DISPLAYCONFIG_MODE_INFO {
+        infoType: info_type,
+        ..DISPLAYCONFIG_MODE_INFO::default()
+    })
+}
+
+fn source(width: u32) -> ModeInfo {
+    let mut mode = mode(DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE);
+    mode.Anonymous.sourceMode.width = width;
+    mode
+}
+
+#[test]
+fn test_mode_index() {
+    assert_eq!(path(1, 2).source_mode_index(), Some(2));
+    assert_eq!(path(1, u32::MAX).source_mode_index(), None);
+}
+
+#[test]
+fn test_mode_union_follows_tag() {
+    let target = mode(DISPLAYCONFIG_MODE_INFO_TYPE_TARGET);
+    assert!(target.source_mode().is_none());
+    assert!(target.target_mode().is_some());
+    assert_eq!(source(1920).source_mode().map(|m| m.width), Some(1920));
+}
+
+#[test]
+fn test_source_mode_lookup() {
+    let paths = [path(1, 0), path(2, 5), path(3, 1)];
+    let target = mode(DISPLAYCONFIG_MODE_INFO_TYPE_TARGET);
+    let modes = [source(2560), target];
+
+    assert_eq!(source_mode(&paths[0], &modes).map(|m| m.width), Some(2560));
+    // Out of range and wrongly typed indices are rejected instead of misread.
+    assert!(source_mode(&paths[1], &modes).is_none());
+    assert!(source_mode(&paths[2], &modes).is_none());
+    assert_eq!(find_path(&paths, 2).map(PathInfo::source_id), Some(2));
+    assert!(find_path(&paths, 9).is_none());
+}
+
+#[test]
+fn test_query_retries_while_the_configuration_grows() {
+    // A video wall hotplugging: the mode count grows between sizing the buffers and querying.
+    let mut mode_count = 64;
+    let mut calls = 0;
+    let result = retry_insufficient_buffer(|| {
+        calls += 1;
+        let allocated = mode_count;
+        if calls < 3 {
+            mode_count += 2;
+        }
+        if mode_count > allocated { Err(DisplayError::QueryDisplayConfig(122)) } else { Ok(mode_count) }
+    });
+    assert_eq!(result.unwrap(), 68);
+    assert_eq!(calls, 3);
+}
+
+#[test]
+fn test_query_retries_are_bounded() {
+    let mut calls = 0;
+    let result: Result<(), _> = retry_insufficient_buffer(|| {
+        calls += 1;
+        Err(DisplayError::QueryDisplayConfig(122))
+    });
+    assert!(matches!(result, Err(DisplayError::QueryDisplayConfig(122))));
+    assert_eq!(calls, QUERY_ATTEMPTS);
+
+    // Other failures aren't retried.
+    let mut calls = 0;
+    let result: Result<(), _> = retry_insufficient_buffer(|| {
+        calls += 1;
+        Err(DisplayError::QueryDisplayConfig(87))
+    });
+    assert!(result.is_err());
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn test_only_visible_user_sessions_are_interactive() {
+    let session = |session_id, window_station: &str, visible| SessionInfo {
+        session_id,
+        window_station: window_station.to_string(),
+        visible,
+    };
+    assert!(session(1, "WinSta0", true).is_interactive());
+    // A service running as SYSTEM.
+    assert!(!session(0, "Service-0x0-3e7$", false).is_interactive());
+    // Session 0 has a WinSta0 too, but no user sees it.
+    assert!(!session(0, "WinSta0", true).is_interactive());
+    // A scheduled task set to run whether the user is logged on or not.
+    assert!(!session(1, "Service-0x0-4a1b2$", false).is_interactive());
+}
+
+#[test]
+fn test_config_fingerprint_tracks_changes() {
+    let paths = [path(1, 0), path(2, 1)];
+    let modes = [source(1920), source(2560)];
+    let fingerprint = config_fingerprint(&paths, &modes);
+    assert_eq!(config_fingerprint(&paths.clone(), &modes.clone()), fingerprint);
+
+    assert_ne!(config_fingerprint(&paths, &[source(1920), source(3840)]), fingerprint);
+    let mut rotated = paths;
+    rotated[1].targetInfo.rotation = DISPLAYCONFIG_ROTATION_ROTATE90;
+    assert_ne!(config_fingerprint(&rotated, &modes), fingerprint);
+    assert_ne!(config_fingerprint(&[path(2, 1), path(1, 0)], &modes), fingerprint);
+}
+
+#[test]
+fn test_dpi_request_targets_the_matched_adapter() {
+    let on_adapter = |source_id, low| {
+        let mut path = path(source_id, 0);
+        path.sourceInfo.adapterId = LUID { LowPart: low, HighPart: 0 };
+        path
+    };
+    // Two GPUs both numbering their sources from 0.
+    let paths = [on_adapter(0, 0x1111), on_adapter(1, 0x1111), on_adapter(0, 0x2222)];
+    let second_gpu = AdapterId { low: 0x2222, high: 0 };
+
+    let path = find_adapter_path(&paths, second_gpu, 0).unwrap();
+    assert_eq!(path.adapter_id(), second_gpu);
+    let header = dpi_set_header(path).unwrap();
+    assert_eq!((header.adapterId.LowPart, header.adapterId.HighPart, header.id), (0x2222, 0, 0));
+
+    assert!(find_adapter_path(&paths, second_gpu, 1).is_none());
+    // Displays saved without an adapter still find their source.
+    let first = find_adapter_path(&paths, AdapterId::default(), 0).unwrap();
+    assert_eq!(first.adapter_id(), AdapterId { low: 0x1111, high: 0 });
+}
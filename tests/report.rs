@@ -0,0 +1,143 @@
+//! The JSON forms checked against the documents in `tests/schema`. A failure here means a
+//! change breaks programs reading the output; fields may be added, but not renamed, removed or
+//! changed without bumping `REPORT_VERSION`.
+
+use display_tuner::display::{ApplyReport, ApplyTimings, DisplayCapabilities, DisplayConfig, DisplayError, DisplayInfo, Rotation, SourceMode};
+use display_tuner::layout::Rect;
+use display_tuner::plan::{ChangeSet, DisplayChange, Operation};
+use display_tuner::report::{CommandReport, ErrorReport, Outcome, REPORT_VERSION};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Fails unless `actual` has everything `expected` has, with the same values.
+fn assert_compatible(actual: &Value, expected: &Value, path: &str) {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, value) in expected {
+                let field = format!("{path}.{key}");
+                let found = actual.get(key).unwrap_or_else(|| panic!("{field} is gone"));
+                assert_compatible(found, value, &field);
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            assert_eq!(actual.len(), expected.len(), "{path} has another length");
+            for (i, (found, value)) in actual.iter().zip(expected).enumerate() {
+                assert_compatible(found, value, &format!("{path}[{i}]"));
+            }
+        }
+        _ => assert_eq!(actual, expected, "{path} changed"),
+    }
+}
+
+/// Checks `value` against the checked-in `document`, and that the document still reads back
+/// as `value`.
+fn assert_schema<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(value: &T, document: &str) {
+    assert_compatible(&serde_json::to_value(value).unwrap(), &serde_json::from_str(document).unwrap(), "$");
+    assert_eq!(&serde_json::from_str::<T>(document).unwrap(), value);
+}
+
+#[test]
+fn test_error_schema() {
+    let err = DisplayError::GetDpiInfo(-5);
+    assert_schema(&ErrorReport::from(&err), include_str!("schema/error.json"));
+    // Errors serialize as their report directly.
+    assert_eq!(serde_json::to_value(&err).unwrap(), serde_json::to_value(ErrorReport::from(&err)).unwrap());
+
+    assert_eq!(DisplayError::DisplayNotFound(3).kind(), "display_not_found");
+    assert_eq!(DisplayError::UnsupportedScaling { source_id: 1, scaling: 110 }.kind(), "unsupported_scaling");
+    assert_eq!(DisplayError::ApplyTimedOut.kind(), "apply_timed_out");
+    assert_eq!(ErrorReport::other("bad flag").code, "other");
+}
+
+#[test]
+fn test_apply_report_schema() {
+    let source = SourceMode { source_id: 2, width: 1920, height: 1080, x: 2560, y: 0, rotation: Rotation::Identity };
+    let report = ApplyReport {
+        source_id: 1,
+        requested: DisplayConfig { width: 2560, height: 1440, scaling: 125 },
+        actual: DisplayConfig { width: 2560, height: 1440, scaling: 100 },
+        allowed_changes: true,
+        side_effects: vec![(source, SourceMode { y: 180, ..source })],
+        pending_scaling: Some(125),
+        work_area: Some(Rect { x: 0, y: 0, width: 2560, height: 1392 }),
+        timings: ApplyTimings {
+            enumerate: Duration::from_millis(12),
+            resolution: Duration::from_millis(850),
+            scaling: Duration::from_millis(40),
+            verification: Duration::from_millis(15),
+        },
+    };
+    assert_schema(&report, include_str!("schema/apply_report.json"));
+}
+
+#[test]
+fn test_change_set_schema() {
+    let changes = ChangeSet {
+        changes: vec![DisplayChange {
+            display: DisplayInfo {
+                friendly_name: "DELL U2720Q".to_string(),
+                source_id: 1,
+                width: 3840,
+                height: 2160,
+                ..DisplayInfo::default()
+            },
+            operations: vec![
+                Operation::SetRotation { from: Rotation::Identity, to: Rotation::Rotate90 },
+                Operation::SetMode { from: (3840, 2160), to: (1440, 2560) },
+                Operation::SetRefresh { from: 60, to: 144 },
+                Operation::SetScaling { from: 150, to: 125 },
+            ],
+        }],
+    };
+    // The document holds only part of the display, so it's checked one way and the full value
+    // round-trips.
+    let document = serde_json::from_str(include_str!("schema/change_set.json")).unwrap();
+    assert_compatible(&serde_json::to_value(&changes).unwrap(), &document, "$");
+    assert_eq!(serde_json::from_value::<ChangeSet>(serde_json::to_value(&changes).unwrap()).unwrap(), changes);
+}
+
+#[test]
+fn test_capabilities_schema() {
+    let capabilities = DisplayCapabilities {
+        resolutions: 24,
+        scaling_min: 100,
+        scaling_max: 175,
+        hdr_supported: true,
+        rotation: true,
+        other_refresh_rates: true,
+        ..DisplayCapabilities::default()
+    };
+    assert_schema(&capabilities, include_str!("schema/capabilities.json"));
+}
+
+#[test]
+fn test_command_report_schema() {
+    let mut report = CommandReport::default();
+    assert!(report.ok);
+    report.finish(4, Some(ErrorReport::from(&DisplayError::NoDisplays)));
+    assert_eq!(report.version, REPORT_VERSION);
+    assert!(!report.ok);
+    assert_schema(&report, include_str!("schema/command_report.json"));
+}
+
+#[test]
+fn test_outcome_schema() {
+    let failure = Outcome::failure(1, ErrorReport::from(&DisplayError::UnsupportedScaling { source_id: 2, scaling: 110 }));
+    assert_schema(&failure, include_str!("schema/outcome.json"));
+
+    let success = Outcome::success(0, None);
+    assert_eq!(serde_json::to_value(&success).unwrap(), serde_json::json!({ "version": 1, "ok": true, "exit_code": 0, "result": null }));
+    assert_eq!(serde_json::from_value::<Outcome>(serde_json::to_value(&success).unwrap()).unwrap(), success);
+    let listed = Outcome::success(0, Some(serde_json::json!([{ "source_id": 1 }])));
+    assert_eq!(serde_json::from_str::<Outcome>(&serde_json::to_string(&listed).unwrap()).unwrap(), listed);
+}
+
+#[test]
+fn test_error_details() {
+    assert_eq!(ErrorReport::from(&DisplayError::Stale(3)).details["source_id"], 3);
+    assert_eq!(DisplayError::DesktopOutOfRange(42).details()["excess"], 42);
+    assert!(DisplayError::NoDisplays.details().is_empty());
+    assert!(ErrorReport::other("bad flag").details.is_empty());
+}
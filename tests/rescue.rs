@@ -0,0 +1,35 @@
+use display_tuner::layout::Rect;
+use display_tuner::rescue::rescue_rect;
+
+fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+    Rect { x, y, width, height }
+}
+
+// Two monitors side by side, each with a 40 pixel taskbar at the bottom.
+const AREAS: [Rect; 2] = [
+    Rect { x: 0, y: 0, width: 1920, height: 1040 },
+    Rect { x: 1920, y: 0, width: 2560, height: 1400 },
+];
+
+#[test]
+fn test_visible_windows_stay() {
+    assert_eq!(rescue_rect(&rect(100, 100, 800, 600), &AREAS), None);
+    // Partly off-screen is still reachable.
+    assert_eq!(rescue_rect(&rect(-700, 100, 800, 600), &AREAS), None);
+    assert_eq!(rescue_rect(&rect(100, 100, 800, 600), &[]), None);
+}
+
+#[test]
+fn test_offscreen_window_moves_to_nearest_area() {
+    // Left of the desktop, where a disabled display used to be.
+    assert_eq!(rescue_rect(&rect(-1500, 200, 800, 600), &AREAS), Some(rect(0, 200, 800, 600)));
+    // Right of the desktop goes to the right monitor, against its right edge.
+    assert_eq!(rescue_rect(&rect(5000, 900, 800, 600), &AREAS), Some(rect(3680, 800, 800, 600)));
+    // Under the left monitor's taskbar goes back above it.
+    assert_eq!(rescue_rect(&rect(1000, 1100, 400, 200), &AREAS), Some(rect(1000, 840, 400, 200)));
+}
+
+#[test]
+fn test_oversized_window_shrinks_to_area() {
+    assert_eq!(rescue_rect(&rect(-3000, 0, 2560, 1400), &AREAS), Some(rect(0, 0, 1920, 1040)));
+}
@@ -0,0 +1,167 @@
+#![cfg(feature = "watch")]
+
+use display_tuner::display::{Connector, DisplayError, DisplayInfo};
+use display_tuner::rules::{plan_rules, RuleSet};
+
+fn display(source_id: u32, name: &str, connector: Connector, x: i32, width: u32, height: u32) -> DisplayInfo {
+    DisplayInfo {
+        friendly_name: name.to_string(),
+        source_id,
+        width,
+        height,
+        scaling_current: 100,
+        scaling_recommended: 100,
+        scaling_max: 300,
+        connector,
+        position_x: x,
+        primary: x == 0,
+        // "DEL" big-endian, as stored by Windows.
+        edid_manufacturer_id: 0xac10,
+        ..DisplayInfo::default()
+    }
+}
+
+fn desk() -> Vec<DisplayInfo> {
+    vec![
+        display(1, "Built-in", Connector::Internal, 0, 1920, 1200),
+        display(2, "DELL U2720Q", Connector::DisplayPort, -2560, 2560, 1440),
+    ]
+}
+
+#[test]
+fn test_parse_rules() {
+    let rules = RuleSet::parse(
+        r#"
+        [[rule]]
+        name = "dell"
+        match = { vendor = "DEL", connector = "dp" }
+        apply = { width = 3840, height = 2160, scaling = 150, right_of = "internal", align = "center" }
+
+        [[rule]]
+        match = { name = "built-in" }
+        apply = { primary = true }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(rules.rules.len(), 2);
+    assert_eq!(rules.rules[0].apply.scaling, Some(150));
+
+    assert!(RuleSet::parse("[[rule]]\nmatch = { colour = \"red\" }").is_err());
+}
+
+#[test]
+fn test_first_matching_rule_wins() {
+    let rules = RuleSet::parse(
+        r#"
+        [[rule]]
+        name = "first"
+        match = { vendor = "del" }
+
+        [[rule]]
+        name = "second"
+        match = { connector = "dp" }
+        "#,
+    )
+    .unwrap();
+    let displays = desk();
+    let resolved = rules.resolve(&displays);
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved[0].0.name, "first");
+    assert_eq!(resolved[0].1.source_id, 2);
+}
+
+#[test]
+fn test_plan_places_and_scales() {
+    let rules = RuleSet::parse(
+        r#"
+        [[rule]]
+        match = { vendor = "DEL" }
+        apply = { scaling = 150, right_of = "internal" }
+        "#,
+    )
+    .unwrap();
+
+    let plan = plan_rules(&rules, &desk()).unwrap();
+    let positions: Vec<_> = plan.sources.iter().map(|s| (s.source_id, s.x, s.y)).collect();
+    assert_eq!(positions, vec![(1, 0, 0), (2, 1920, 0)]);
+    assert_eq!(plan.scaling, vec![(2, 150)]);
+}
+
+#[test]
+fn test_plan_is_empty_when_compliant() {
+    let rules = RuleSet::parse(
+        r#"
+        [[rule]]
+        match = { vendor = "DEL" }
+        apply = { width = 2560, height = 1440, scaling = 100, left_of = "internal" }
+        "#,
+    )
+    .unwrap();
+
+    assert!(plan_rules(&rules, &desk()).unwrap().is_empty());
+}
+
+#[test]
+fn test_plan_primary_renormalizes() {
+    let rules = RuleSet::parse(
+        r#"
+        [[rule]]
+        match = { vendor = "DEL" }
+        apply = { primary = true }
+        "#,
+    )
+    .unwrap();
+
+    let plan = plan_rules(&rules, &desk()).unwrap();
+    let positions: Vec<_> = plan.sources.iter().map(|s| (s.source_id, s.x)).collect();
+    assert_eq!(positions, vec![(1, 2560), (2, 0)]);
+}
+
+#[test]
+fn test_plan_rejects_unknown_reference_and_scaling() {
+    let rules = RuleSet::parse("[[rule]]\nname = \"r\"\napply = { below = \"projector\" }").unwrap();
+    assert!(matches!(
+        plan_rules(&rules, &desk()),
+        Err(DisplayError::UnknownReference { .. })
+    ));
+
+    let rules = RuleSet::parse("[[rule]]\napply = { scaling = 110 }").unwrap();
+    assert!(matches!(
+        plan_rules(&rules, &desk()),
+        Err(DisplayError::UnsupportedScaling { scaling: 110, .. })
+    ));
+}
+
+#[test]
+fn test_rule_without_mode_switch_keeps_resolution() {
+    let rules = RuleSet::parse(
+        r#"
+        [[rule]]
+        match = { vendor = "DEL" }
+        apply = { width = 3840, height = 2160, scaling = 150 }
+        allow_mode_switch = false
+        "#,
+    )
+    .unwrap();
+    let plan = plan_rules(&rules, &desk()).unwrap();
+    assert!(plan.sources.is_empty());
+    assert_eq!(plan.scaling, [(2, 150)]);
+    assert_eq!(plan.held_back, [2]);
+}
+
+#[test]
+fn test_plan_reflows_displays_next_to_a_resized_one() {
+    let rules = RuleSet::parse(
+        r#"
+        [[rule]]
+        match = { vendor = "DEL" }
+        apply = { width = 3840, height = 2160 }
+        "#,
+    )
+    .unwrap();
+
+    // The built-in display stays attached to the right edge of the wider Dell.
+    let plan = plan_rules(&rules, &desk()).unwrap();
+    let positions: Vec<_> = plan.sources.iter().map(|s| (s.source_id, s.x, s.y)).collect();
+    assert_eq!(positions, vec![(1, 0, 0), (2, -3840, 0)]);
+}
@@ -0,0 +1,185 @@
+#![cfg(feature = "watch")]
+
+use display_tuner::display::{Connector, DisplayInfo};
+use display_tuner::rules::{plan_rules, RuleSet};
+use display_tuner::schedule::{ActionQueue, TimeOfDay};
+use std::time::Duration;
+
+fn display(source_id: u32, name: &str, connector: Connector, x: i32, width: u32) -> DisplayInfo {
+    DisplayInfo {
+        friendly_name: name.to_string(),
+        source_id,
+        width,
+        height: 1440,
+        scaling_current: 100,
+        scaling_recommended: 100,
+        scaling_max: 300,
+        connector,
+        position_x: x,
+        primary: x == 0,
+        ..DisplayInfo::default()
+    }
+}
+
+fn desk() -> Vec<DisplayInfo> {
+    vec![
+        display(1, "Built-in", Connector::Internal, 0, 1920),
+        display(2, "DELL U2720Q", Connector::DisplayPort, 1920, 2560),
+    ]
+}
+
+fn at(hour: u32, minute: u32) -> TimeOfDay {
+    TimeOfDay::new(hour, minute).unwrap()
+}
+
+const NIGHT_MODE: &str = r#"
+    [[rule]]
+    name = "dell"
+    match = { connector = "dp" }
+    apply = { scaling = 125, right_of = "internal" }
+
+    [[schedule]]
+    name = "evening"
+    at = "20:00"
+    apply = { scaling = 150 }
+
+    [[schedule]]
+    name = "morning"
+    at = "07:00"
+    apply = { scaling = 100 }
+"#;
+
+#[test]
+fn test_parse_time_of_day() {
+    assert_eq!("20:00".parse::<TimeOfDay>().unwrap(), at(20, 0));
+    assert_eq!("7:05".parse::<TimeOfDay>().unwrap().to_string(), "07:05");
+    for invalid in ["24:00", "12:60", "20:0", "20", "noon"] {
+        assert!(invalid.parse::<TimeOfDay>().is_err(), "{invalid}");
+    }
+    assert!(RuleSet::parse("[[schedule]]\nat = \"25:00\"").is_err());
+}
+
+#[test]
+fn test_until_wraps_past_midnight() {
+    assert_eq!(at(20, 0).until(at(7, 0)), Duration::from_hours(11));
+    assert_eq!(at(7, 0).until(at(7, 0)), Duration::from_hours(24));
+}
+
+#[test]
+fn test_schedule_in_effect_counts_back_into_yesterday() {
+    let rules = RuleSet::parse(NIGHT_MODE).unwrap();
+    let displays = desk();
+    assert_eq!(rules.scheduled_for(&displays[0], at(21, 0)).unwrap().name, "evening");
+    assert_eq!(rules.scheduled_for(&displays[0], at(6, 59)).unwrap().name, "evening");
+    assert_eq!(rules.scheduled_for(&displays[0], at(7, 0)).unwrap().name, "morning");
+}
+
+#[test]
+fn test_schedule_overrides_rules() {
+    let rules = RuleSet::parse(NIGHT_MODE).unwrap();
+    let displays = desk();
+
+    let evening = plan_rules(&rules.at(at(22, 30), &displays), &displays).unwrap();
+    assert_eq!(evening.scaling, vec![(1, 150), (2, 150)]);
+    // The rule's placement survives the schedule entry.
+    assert!(evening.sources.is_empty());
+
+    let morning = plan_rules(&rules.at(at(9, 0), &displays), &displays).unwrap();
+    assert!(morning.is_empty());
+}
+
+#[test]
+fn test_upcoming_and_fired() {
+    let rules = RuleSet::parse(NIGHT_MODE).unwrap();
+    let upcoming: Vec<_> = rules
+        .upcoming(at(19, 0))
+        .into_iter()
+        .map(|(wait, entry)| (wait.as_secs() / 3600, entry.name.as_str()))
+        .collect();
+    assert_eq!(upcoming, vec![(1, "evening"), (12, "morning")]);
+
+    let fired: Vec<_> = rules.fired_between(at(19, 0), at(8, 0)).iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(fired, vec!["evening", "morning"]);
+    assert!(rules.fired_between(at(20, 0), at(21, 0)).is_empty());
+    assert!(rules.fired_between(at(20, 0), at(20, 0)).is_empty());
+}
+
+const TV: &str = r#"
+    [[schedule]]
+    name = "evening"
+    at = "20:00"
+    match = { connector = "hdmi" }
+    apply = { scaling = 150 }
+
+    [[schedule]]
+    name = "morning"
+    at = "07:00"
+    match = { connector = "hdmi" }
+    apply = { scaling = 100 }
+"#;
+
+fn with_tv() -> Vec<DisplayInfo> {
+    let mut displays = desk();
+    displays.push(display(3, "TV", Connector::Hdmi, 4480, 3840));
+    displays
+}
+
+fn names(rules: &RuleSet) -> Vec<&str> {
+    rules.schedule.iter().map(|e| e.name.as_str()).collect()
+}
+
+#[test]
+fn test_missed_entry_is_skipped_by_default() {
+    let rules = RuleSet::parse(TV).unwrap();
+    let mut queue = ActionQueue::default();
+    queue.record(&rules.fired_between(at(19, 0), at(20, 0)), &desk(), 1000);
+    assert_eq!(queue.actions.len(), 1);
+    assert_eq!(queue.pending(1000).count(), 0);
+
+    // The TV connecting later keeps the morning settings.
+    let tv = with_tv();
+    let now = queue.apply_to(&rules, &tv, 2000);
+    assert_eq!(names(&now), ["morning"]);
+    assert_eq!(now.scheduled_for(&tv[2], at(21, 0)).unwrap().name, "morning");
+
+    // Once the entry fires with the TV there, it's no longer held back.
+    queue.record(&rules.fired_between(at(19, 0), at(20, 0)), &tv, 90_000);
+    assert!(queue.actions.is_empty());
+    assert_eq!(names(&queue.apply_to(&rules, &tv, 90_000)), ["evening", "morning"]);
+}
+
+#[test]
+fn test_queued_entry_applies_when_its_display_connects() {
+    let rules = RuleSet::parse(&TV.replace("apply = { scaling = 150 }", "apply = { scaling = 150 }\n    on_missing = \"queue\"\n    queue_ttl_minutes = 60")).unwrap();
+    let mut queue = ActionQueue::default();
+    queue.record(&rules.fired_between(at(19, 0), at(20, 0)), &desk(), 1000);
+    assert_eq!(queue.pending(1000).map(|a| (a.entry.as_str(), a.apply.as_str())).collect::<Vec<_>>(), [("evening", "scaling=150")]);
+
+    // Still waiting while the TV is off.
+    assert_eq!(names(&queue.apply_to(&rules, &desk(), 2000)), ["morning"]);
+    let mut expired = queue.clone();
+    assert_eq!(names(&queue.apply_to(&rules, &with_tv(), 2800)), ["evening", "morning"]);
+    assert!(queue.actions.is_empty());
+
+    // After an hour it's stale.
+    assert_eq!(names(&expired.apply_to(&rules, &with_tv(), 4600)), ["morning"]);
+    assert_eq!(expired.actions.len(), 1);
+    // A day later the entry has fired again, so the record goes.
+    assert_eq!(names(&expired.apply_to(&rules, &with_tv(), 1000 + 86_400)), ["evening", "morning"]);
+}
+
+#[test]
+fn test_queue_keeps_the_latest_entry_per_display() {
+    let rules = RuleSet::parse(&TV.replace("[[schedule]]", "[[schedule]]\non_missing = \"queue\"")).unwrap();
+    let mut queue = ActionQueue::default();
+    queue.record(&rules.fired_between(at(19, 0), at(20, 0)), &desk(), 1000);
+    queue.record(&rules.fired_between(at(6, 0), at(7, 0)), &desk(), 40_000);
+    assert_eq!(queue.actions.len(), 1);
+    assert_eq!(queue.actions[0].entry, "morning");
+
+    let path = std::env::temp_dir().join(format!("display-tuner-queue-{}.json", std::process::id()));
+    queue.save(&path).unwrap();
+    assert_eq!(ActionQueue::load(&path).unwrap(), queue);
+    std::fs::remove_file(&path).unwrap();
+    assert!(ActionQueue::load(&path).unwrap().actions.is_empty());
+}
@@ -0,0 +1,119 @@
+use display_tuner::display::{Connector, DisplayError, DisplayInfo};
+use display_tuner::selector::{display_at_index, display_index, filter_displays, find_by_gdi_name, id_not_found, sort_displays, DisplayFilter, SortKey};
+
+fn display(source_id: u32, name: &str, width: u32, connector: Connector) -> DisplayInfo {
+    DisplayInfo {
+        friendly_name: name.to_string(),
+        source_id,
+        width,
+        height: width * 9 / 16,
+        scaling_current: 100,
+        scaling_recommended: 100,
+        connector,
+        active: true,
+        ..DisplayInfo::default()
+    }
+}
+
+fn sample() -> Vec<DisplayInfo> {
+    vec![
+        display(3, "DELL U2720Q", 3840, Connector::DisplayPort),
+        display(1, "Built-in Panel", 1920, Connector::Internal),
+        display(7, "LG TV", 3840, Connector::Hdmi),
+        display(5, "dell P2419H", 1920, Connector::DisplayPort),
+    ]
+}
+
+fn ids(displays: &[DisplayInfo]) -> Vec<u32> {
+    displays.iter().map(|d| d.source_id).collect()
+}
+
+#[test]
+fn test_empty_filter_matches_all() {
+    let displays = filter_displays(sample(), &DisplayFilter::default());
+    assert_eq!(ids(&displays), vec![3, 1, 7, 5]);
+}
+
+#[test]
+fn test_filters_compose() {
+    let filter = DisplayFilter {
+        name: Some("DELL".to_string()),
+        min_width: Some(2560),
+        ..DisplayFilter::default()
+    };
+    assert_eq!(ids(&filter_displays(sample(), &filter)), vec![3]);
+
+    let filter = DisplayFilter {
+        connector: Some(Connector::DisplayPort),
+        active: Some(false),
+        ..DisplayFilter::default()
+    };
+    assert!(filter_displays(sample(), &filter).is_empty());
+}
+
+#[test]
+fn test_sort_displays() {
+    let mut displays = sample();
+    sort_displays(&mut displays, SortKey::Id);
+    assert_eq!(ids(&displays), vec![1, 3, 5, 7]);
+
+    sort_displays(&mut displays, SortKey::Name);
+    assert_eq!(ids(&displays), vec![1, 5, 3, 7]);
+
+    sort_displays(&mut displays, SortKey::Resolution);
+    assert_eq!(ids(&displays), vec![1, 5, 3, 7]);
+}
+
+#[test]
+fn test_index_counts_in_source_id_order() {
+    let displays = sample();
+    assert_eq!(display_at_index(&displays, 2).unwrap().source_id, 3);
+    assert_eq!(display_index(&displays, &displays[2]), Some(4));
+    assert!(matches!(display_at_index(&displays, 0), Err(DisplayError::IndexOutOfRange { index: 0, count: 4 })));
+    assert!(matches!(display_at_index(&displays, 5), Err(DisplayError::IndexOutOfRange { index: 5, count: 4 })));
+}
+
+#[test]
+fn test_unknown_id_suggests_index() {
+    assert_eq!(
+        id_not_found(&sample(), 2).to_string(),
+        "No display with source id 2; did you mean --index 2 which is DELL U2720Q?"
+    );
+    assert_eq!(id_not_found(&sample(), 9).to_string(), "No display with source id 9");
+}
+
+#[test]
+fn test_find_by_gdi_name() {
+    let mut displays = sample();
+    for (i, d) in displays.iter_mut().enumerate() {
+        d.gdi_device_name = format!(r"\\.\DISPLAY{}", i + 1);
+    }
+
+    assert_eq!(find_by_gdi_name(&displays, r"\\.\display3").unwrap().source_id, 7);
+    let err = find_by_gdi_name(&displays, r"\\.\DISPLAY9").unwrap_err();
+    assert!(err.to_string().contains(r"\\.\DISPLAY9"));
+}
+
+#[test]
+fn test_filter_by_display_number() {
+    let mut displays = sample();
+    for (number, display) in displays.iter_mut().enumerate() {
+        display.gdi_device_name = format!(r"\\.\DISPLAY{}", number + 1);
+    }
+    assert_eq!(displays[2].display_number(), Some(3));
+    assert_eq!(DisplayInfo::default().display_number(), None);
+
+    let filter = DisplayFilter { display_number: Some(3), ..DisplayFilter::default() };
+    assert_eq!(ids(&filter_displays(displays, &filter)), vec![7]);
+}
+
+#[test]
+fn test_filter_by_adapter() {
+    let mut displays = sample();
+    displays[0].adapter_name = r"\\?\USB#VID_17E9&PID_6006#1".to_string();
+    displays[2].adapter_name = r"\\?\PCI#VEN_10DE&DEV_2484#3".to_string();
+
+    let filter = DisplayFilter { adapter: Some("vid_17e9".to_string()), ..DisplayFilter::default() };
+    assert_eq!(ids(&filter_displays(displays, &filter)), vec![3]);
+    assert_eq!(filter.to_string(), "adapter=vid_17e9");
+}
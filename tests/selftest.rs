@@ -0,0 +1,45 @@
+use display_tuner::selftest::{SelftestReport, Step};
+use std::time::Duration;
+
+fn step(name: &'static str, source_id: Option<u32>, passed: bool) -> Step {
+    Step { name, source_id, passed, detail: "details".to_string(), duration: Duration::from_millis(12) }
+}
+
+#[test]
+fn test_report_lists_steps_with_timings() {
+    let report = SelftestReport {
+        steps: vec![step("Enumeration", None, true), step("DPI query", Some(2), true)],
+        ..SelftestReport::new(false)
+    };
+    assert_eq!(
+        report.to_string(),
+        "[PASS] Enumeration: details (12 ms)\n[PASS] DPI query, display 2: details (12 ms)\nSelf-test passed"
+    );
+}
+
+#[test]
+fn test_report_json_for_fleet_checks() {
+    let report = SelftestReport {
+        passed: false,
+        steps: vec![step("SDC_VALIDATE", None, false), step("DPI set", Some(1), true)],
+        ..SelftestReport::new(true)
+    };
+    assert!(report.to_string().ends_with("Self-test failed: 1 of 2 steps"));
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["passed"], false);
+    assert_eq!(json["destructive"], true);
+    assert_eq!(json["steps"][0]["duration_ms"], 12);
+    assert!(json["steps"][0].get("source_id").is_none());
+    assert_eq!(json["steps"][1]["source_id"], 1);
+}
+
+#[test]
+fn test_selftest_passes_on_a_working_machine() {
+    let report = display_tuner::selftest::selftest(false);
+    // A headless machine fails the enumeration and stops there.
+    if !report.steps[0].passed {
+        assert_eq!(report.steps.len(), 1);
+        return;
+    }
+    assert!(report.passed, "{report}");
+}
@@ -0,0 +1,40 @@
+use display_tuner::display::{DisplayConfig, DisplayInfo};
+use display_tuner::layout::Rect;
+use display_tuner::settle::{is_settled, work_area_settled};
+
+fn display(work_area: Option<Rect>) -> DisplayInfo {
+    DisplayInfo {
+        source_id: 1,
+        width: 2560,
+        height: 1440,
+        position_x: 1920,
+        scaling_current: 125,
+        active: true,
+        target_available: true,
+        work_area,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_work_area_has_to_fit_the_new_size() {
+    // Taskbar at the bottom, and on the left.
+    assert!(work_area_settled(&display(Some(Rect { x: 1920, y: 0, width: 2560, height: 1392 }))));
+    assert!(work_area_settled(&display(Some(Rect { x: 1982, y: 0, width: 2498, height: 1440 }))));
+    // Still the 1920x1080 one from before the switch, or past the display's edge.
+    assert!(!work_area_settled(&display(Some(Rect { x: 1920, y: 0, width: 1920, height: 1032 }))));
+    assert!(!work_area_settled(&display(Some(Rect { x: 0, y: 0, width: 3840, height: 2112 }))));
+    assert!(work_area_settled(&display(None)));
+}
+
+#[test]
+fn test_settled_needs_the_requested_mode_and_an_available_target() {
+    let config = DisplayConfig { width: 2560, height: 1440, scaling: 125 };
+    let settled = display(Some(Rect { x: 1920, y: 0, width: 2560, height: 1392 }));
+    assert!(is_settled(&settled, &config));
+    assert!(!is_settled(&settled, &DisplayConfig { width: 3840, height: 2160, ..config.clone() }));
+    assert!(!is_settled(&DisplayInfo { target_available: false, ..settled.clone() }, &config));
+    // Scaling waits for a sign-in under a custom scaling override.
+    assert!(!is_settled(&settled, &DisplayConfig { scaling: 150, ..config.clone() }));
+    assert!(is_settled(&DisplayInfo { custom_scaling: true, ..settled }, &DisplayConfig { scaling: 150, ..config.clone() }));
+}
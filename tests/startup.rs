@@ -0,0 +1,12 @@
+#![cfg(feature = "profiles")]
+
+use display_tuner::startup::reapply_command;
+use std::path::Path;
+
+#[test]
+fn test_reapply_command_quotes_path() {
+    assert_eq!(
+        reapply_command(Path::new(r"C:\Program Files\display-tuner\display-tuner.exe")),
+        r#""C:\Program Files\display-tuner\display-tuner.exe" reapply"#
+    );
+}
@@ -0,0 +1,135 @@
+use display_tuner::display::{AdapterId, DisplayError};
+use display_tuner::raw::{ModeInfo, PathInfo};
+use display_tuner::state::{DisplayState, SavedMode, SavedModeKind, SavedMonitor, SavedPath, STATE_VERSION};
+use windows::Win32::Devices::Display::DISPLAYCONFIG_MODE_INFO_TYPE;
+
+const BOOT_1: AdapterId = AdapterId { low: 0x1111, high: 0 };
+const BOOT_2: AdapterId = AdapterId { low: 0x2222, high: 0 };
+
+fn monitor(adapter: AdapterId, target_id: u32, device_path: &str, product: u16) -> SavedMonitor {
+    SavedMonitor {
+        target_adapter: adapter,
+        target_id,
+        device_path: device_path.to_string(),
+        edid_manufacturer_id: 0x10ac,
+        edid_product_id: product,
+        friendly_name: format!("Monitor {product}"),
+        dpi_steps: None,
+    }
+}
+
+fn path(target_id: u32, source_mode: u32, target_mode: u32) -> SavedPath {
+    SavedPath {
+        source_adapter: BOOT_1,
+        source_id: target_id,
+        source_mode: Some(source_mode),
+        target_adapter: BOOT_1,
+        target_id,
+        target_mode: Some(target_mode),
+        refresh_rate: (60_000, 1000),
+        target_available: true,
+        flags: 1,
+        ..SavedPath::default()
+    }
+}
+
+fn target_mode(id: u32) -> SavedMode {
+    SavedMode {
+        adapter: BOOT_1,
+        id,
+        kind: SavedModeKind::Target {
+            pixel_rate: 533_250_000,
+            h_sync: (133_312_500, 1000),
+            v_sync: (60_000, 1000),
+            active: (2560, 1440),
+            total: (2720, 1481),
+            video_standard: 255,
+            scan_line_ordering: 1,
+        },
+    }
+}
+
+fn source_mode(id: u32, x: i32) -> SavedMode {
+    SavedMode { adapter: BOOT_1, id, kind: SavedModeKind::Source { width: 2560, height: 1440, pixel_format: 4, position: (x, 0) } }
+}
+
+/// Two monitors on one adapter, saved during the first boot.
+fn saved_state() -> DisplayState {
+    DisplayState {
+        version: STATE_VERSION,
+        paths: vec![path(1, 1, 0), path(2, 3, 2)],
+        modes: vec![target_mode(1), source_mode(1, 0), target_mode(2), source_mode(2, 2560)],
+        monitors: vec![
+            SavedMonitor { dpi_steps: Some(1), ..monitor(BOOT_1, 1, "PATH-A", 1) },
+            SavedMonitor { dpi_steps: Some(0), ..monitor(BOOT_1, 2, "PATH-B", 2) },
+        ],
+    }
+}
+
+#[test]
+fn test_remap_follows_the_adapter_luid() {
+    let current = [monitor(BOOT_2, 1, "PATH-A", 1), monitor(BOOT_2, 2, "PATH-B", 2)];
+    let (state, missing) = saved_state().remap(&current, false).unwrap();
+    assert!(missing.is_empty());
+    assert!(state.paths.iter().all(|p| p.source_adapter == BOOT_2 && p.target_adapter == BOOT_2));
+    assert!(state.modes.iter().all(|m| m.adapter == BOOT_2));
+    assert_eq!(state.paths[1].source_mode, Some(3));
+    assert_eq!(state.monitors[0].dpi_steps, Some(1));
+}
+
+#[test]
+fn test_remap_matches_by_edid_after_a_port_change() {
+    // Monitor 2 moved to another connector, so its device path and target id changed.
+    let current = [monitor(BOOT_2, 1, "PATH-A", 1), monitor(BOOT_2, 7, "PATH-C", 2)];
+    let (state, _) = saved_state().remap(&current, false).unwrap();
+    assert_eq!(state.paths[1].target_id, 7);
+    assert_eq!(state.modes[2].id, 7);
+    assert_eq!(state.monitors[1].device_path, "PATH-C");
+}
+
+#[test]
+fn test_remap_refuses_other_monitors_unless_partial() {
+    let current = [monitor(BOOT_2, 1, "PATH-A", 1), monitor(BOOT_2, 3, "PATH-D", 9)];
+    match saved_state().remap(&current, false).unwrap_err() {
+        DisplayError::StateMismatch { missing, unexpected } => {
+            assert_eq!(missing, "Monitor 2");
+            assert_eq!(unexpected, "Monitor 9");
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    let (state, missing) = saved_state().remap(&current, true).unwrap();
+    assert_eq!(missing, ["Monitor 2"]);
+    assert_eq!(state.paths.len(), 1);
+    assert_eq!(state.monitors.len(), 1);
+    // Modes of the adapter stay, so the indices of the path still point at its own.
+    assert_eq!(state.modes[state.paths[0].source_mode.unwrap() as usize], SavedMode { adapter: BOOT_2, ..source_mode(1, 0) });
+}
+
+#[test]
+fn test_remap_drops_adapters_without_known_monitors() {
+    let mut saved = saved_state();
+    let other = AdapterId { low: 0x3333, high: 0 };
+    saved.paths.push(SavedPath { source_adapter: other, target_adapter: other, target_available: false, flags: 0, source_mode: None, target_mode: None, ..path(5, 0, 0) });
+    saved.modes.push(SavedMode { adapter: other, ..source_mode(5, 5120) });
+    let current = [monitor(BOOT_2, 1, "PATH-A", 1), monitor(BOOT_2, 2, "PATH-B", 2)];
+    let (state, _) = saved.remap(&current, false).unwrap();
+    assert_eq!(state.paths.len(), 2);
+    assert_eq!(state.modes.len(), 4);
+}
+
+#[test]
+fn test_paths_and_modes_convert_unchanged() {
+    let state = saved_state();
+    for path in &state.paths {
+        assert_eq!(SavedPath::from(&PathInfo::from(path)), *path);
+    }
+    for mode in &state.modes {
+        assert_eq!(SavedMode::try_from(&ModeInfo::from(mode)).unwrap(), *mode);
+    }
+    let mut unknown = ModeInfo::from(&state.modes[0]);
+    unknown.infoType = DISPLAYCONFIG_MODE_INFO_TYPE(9);
+    assert!(matches!(SavedMode::try_from(&unknown), Err(DisplayError::UnknownModeType(9))));
+    let json = serde_json::to_string(&state).unwrap();
+    assert_eq!(serde_json::from_str::<DisplayState>(&json).unwrap(), state);
+}
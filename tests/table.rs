@@ -0,0 +1,104 @@
+use display_tuner::display::{Connector, DisplayInfo};
+use display_tuner::selector::Aliases;
+use display_tuner::table::{render_csv, render_table, truncate};
+
+fn display(source_id: u32, name: &str, primary: bool, scaling: i32) -> DisplayInfo {
+    DisplayInfo {
+        source_id,
+        friendly_name: name.to_string(),
+        width: 2560,
+        height: 1440,
+        refresh_rate: 144,
+        scaling_current: scaling,
+        scaling_recommended: 125,
+        primary,
+        connector: Connector::DisplayPort,
+        active: true,
+        target_available: true,
+        gdi_device_name: format!(r"\\.\DISPLAY{}", source_id + 1),
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_table_is_aligned() {
+    let displays = [display(1, "DELL U2720Q", true, 125), display(12, "LG", false, 150)];
+    let table = render_table(&displays, &displays, &Aliases::new(), false);
+    assert_eq!(
+        table,
+        "Index  ID  #   Name         Resolution                       Refresh  Scaling          Primary  Connector\n\
+         1      1   2   DELL U2720Q  2560x1440 (effective 2048x1152)  144Hz    125%             yes      dp\n\
+         2      12  13  LG           2560x1440 (effective 1707x960)   144Hz    150% (rec 125%)           dp\n"
+    );
+}
+
+#[test]
+fn test_table_colors_primary_and_scaling() {
+    let displays = [display(1, "A", true, 125), display(2, "B", false, 150)];
+    let table = render_table(&displays, &displays, &Aliases::new(), true);
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(!lines[0].contains('\x1b'));
+    assert!(lines[1].starts_with("\x1b[1m1 "));
+    assert!(lines[2].contains("\x1b[33m150% (rec 125%)\x1b[0m"));
+    assert!(!render_table(&displays, &displays, &Aliases::new(), false).contains('\x1b'));
+}
+
+#[test]
+fn test_table_notes_column_only_when_needed() {
+    let mut asleep = display(3, "C", false, 125);
+    asleep.target_available = false;
+    let table = render_table(&[asleep.clone()], &[asleep], &Aliases::new(), false);
+    assert!(table.lines().next().unwrap().ends_with("Notes"));
+    assert!(table.ends_with("unavailable\n"));
+}
+
+#[test]
+fn test_table_marks_inactive_displays() {
+    let off = DisplayInfo { friendly_name: "TV".to_string(), connector: Connector::Hdmi, target_available: true, ..DisplayInfo::default() };
+    let table = render_table(std::slice::from_ref(&off), std::slice::from_ref(&off), &Aliases::new(), false);
+    let cells: Vec<&str> = table.lines().nth(1).unwrap().split_whitespace().skip_while(|cell| *cell != "TV").collect();
+    assert_eq!(cells, ["TV", "-", "-", "-", "hdmi", "inactive"]);
+}
+
+#[test]
+fn test_index_counts_among_all_displays() {
+    let all = [display(4, "A", true, 100), display(1, "B", false, 100), display(2, "C", false, 100)];
+    let table = render_table(&all[..1], &all, &Aliases::new(), false);
+    assert!(table.lines().nth(1).unwrap().starts_with("3      4 "));
+}
+
+#[test]
+fn test_refresh_shows_dynamic_range() {
+    let mut panel = display(1, "Panel", true, 125);
+    panel.refresh_rate = 120;
+    panel.drr_supported = true;
+    assert!(render_table(&[panel.clone()], &[panel.clone()], &Aliases::new(), false).contains("  120Hz  "));
+    panel.drr_enabled = true;
+    assert_eq!(panel.dynamic_refresh_range(), Some((60, 120)));
+    assert!(render_table(&[panel.clone()], &[panel], &Aliases::new(), false).contains("  120Hz (dynamic 60–120)  "));
+}
+
+#[test]
+fn test_truncate_long_names() {
+    assert_eq!(truncate("Short", 10), "Short");
+    assert_eq!(truncate("Generic PnP Monitor Extra", 10), "Generic P…");
+}
+
+#[test]
+fn test_csv_quotes_names_and_adds_edid_columns() {
+    let mut dell = display(1, "DELL U2720Q, left", true, 125);
+    dell.serial_number = Some("F8KXL13".to_string());
+    dell.manufacture_year = Some(2020);
+    let lg = display(2, "LG \"TV\"", false, 150);
+
+    let csv = render_csv(&[dell.clone(), lg], false);
+    assert_eq!(
+        csv,
+        "id,number,name,width,height,refresh,scaling,connector,primary,active\n\
+         1,2,\"DELL U2720Q, left\",2560,1440,144,125,dp,true,true\n\
+         2,3,\"LG \"\"TV\"\"\",2560,1440,144,150,dp,false,true\n"
+    );
+    let csv = render_csv(&[dell], true);
+    assert!(csv.starts_with("id,number,name,width,height,refresh,scaling,connector,primary,active,serial_number,model_name,manufacture_week,manufacture_year\n"));
+    assert!(csv.ends_with(",true,true,F8KXL13,,,2020\n"));
+}
@@ -1,10 +1,22 @@
 use display_tuner::display;
 
+/// The displays attached now, or `None` on a headless machine (no display, or not running in
+/// the interactive session), where tests that need hardware have nothing to check.
+fn hardware_displays() -> Option<Vec<display::DisplayInfo>> {
+    match display::enumerate_displays() {
+        Ok(displays) => Some(displays),
+        Err(display::DisplayError::NoDisplays | display::DisplayError::NonInteractiveSession { .. }) => None,
+        Err(err) => panic!("enumerating failed: {err}"),
+    }
+}
+
 #[test]
 fn test_display_info() {
-    let displays = display::enumerate_displays().unwrap();
+    let Some(displays) = hardware_displays() else {
+        return;
+    };
     assert!(!displays.is_empty());
-    
+
     let first = &displays[0];
     assert_ne!(first.friendly_name, "");
     assert!(first.width > 0);
@@ -13,4 +25,691 @@ fn test_display_info() {
     assert!(first.scaling_recommended > 0);
     
     println!("{displays:?}");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_no_displays_is_not_a_failed_query() {
+    let err = display::DisplayError::NoDisplays;
+    assert!(err.to_string().starts_with("No active displays found"));
+    assert_eq!(err.code(), None);
+}
+
+#[test]
+fn test_set_config_errors_explain_reason() {
+    let message = display::DisplayError::ConfigRejected(87).to_string();
+    assert!(message.contains("doesn't support this mode"), "{message}");
+    assert!(message.contains("87"));
+}
+
+#[test]
+fn test_apply_report_detects_adjustments() {
+    use display::{ApplyReport, DisplayConfig, DisplayInfo};
+
+    let display = |source_id, width, x| DisplayInfo {
+        source_id,
+        width,
+        height: 1440,
+        scaling_current: 100,
+        position_x: x,
+        ..DisplayInfo::default()
+    };
+    let requested = DisplayConfig { width: 2560, height: 1440, scaling: 100 };
+    let before = [display(1, 1920, 0), display(2, 1920, 1920)];
+
+    let exact = ApplyReport::compare(1, &requested, &before, &[display(1, 2560, 0), display(2, 1920, 1920)], false);
+    assert!(!exact.adjusted());
+
+    let moved = [display(1, 2560, 0), display(2, 1920, 2560)];
+    let report = ApplyReport::compare(1, &requested, &before, &moved, true);
+    assert!(report.adjusted());
+    assert_eq!(report.side_effects.len(), 1);
+    assert_eq!(report.side_effects[0].1.x, 2560);
+}
+
+#[test]
+fn test_bit_depth_reporting() {
+    use display::{ColorEncoding, DisplayError, DisplayInfo};
+
+    let mut info = DisplayInfo {
+        source_id: 4,
+        friendly_name: "LG".to_string(),
+        width: 3840,
+        height: 2160,
+        scaling_current: 150,
+        scaling_recommended: 150,
+        target_available: true,
+        ..DisplayInfo::default()
+    };
+    assert_eq!(info.to_string(), "[id:4] LG — 3840x2160 @ 150% (effective 2560x1440)");
+
+    info.bits_per_color = 10;
+    info.color_encoding = ColorEncoding::YCbCr422;
+    assert_eq!(info.to_string(), "[id:4] LG — 3840x2160 @ 150% (effective 2560x1440), 10-bit YCbCr 4:2:2");
+
+    assert!(display::set_bit_depth(&info, 10).is_ok());
+    assert!(matches!(
+        display::set_bit_depth(&info, 8),
+        Err(DisplayError::BitDepthNotControllable { current: 10, requested: 8, .. })
+    ));
+}
+
+#[test]
+fn test_sdr_white_level_validation() {
+    use display::{DisplayError, DisplayInfo};
+
+    let sdr = DisplayInfo { source_id: 2, ..DisplayInfo::default() };
+    assert!(matches!(
+        display::set_sdr_white_level(&sdr, 500),
+        Err(DisplayError::SdrWhiteLevelOutOfRange(500))
+    ));
+    assert!(matches!(
+        display::set_sdr_white_level(&sdr, 200),
+        Err(DisplayError::AdvancedColorDisabled(2))
+    ));
+}
+
+#[test]
+fn test_step_scaling() {
+    let info = display::DisplayInfo {
+        scaling_current: 150,
+        scaling_max: 200,
+        ..display::DisplayInfo::default()
+    };
+    assert_eq!(info.step_scaling(1), 175);
+    assert_eq!(info.step_scaling(-2), 100);
+    assert_eq!(info.step_scaling(5), 200);
+    assert_eq!(info.step_scaling(-9), 100);
+}
+
+#[test]
+fn test_scaling_clamped_per_display() {
+    let info = display::DisplayInfo {
+        scaling_current: 150,
+        scaling_max: 200,
+        ..display::DisplayInfo::default()
+    };
+    assert!(info.can_step_scaling(2));
+    assert!(!info.can_step_scaling(3));
+    assert!(info.can_step_scaling(-2));
+    assert!(!info.can_step_scaling(-3));
+    assert_eq!(info.clamp_scaling(175), 175);
+    assert_eq!(info.clamp_scaling(250), 200);
+    assert_eq!(info.clamp_scaling(160), 150);
+    assert_eq!(info.clamp_scaling(50), 100);
+}
+
+#[test]
+fn test_scaling_outside_the_table() {
+    let info = display::DisplayInfo {
+        scaling_current: 110,
+        scaling_max: 200,
+        ..display::DisplayInfo::default()
+    };
+    assert!(info.has_custom_scaling());
+    assert!(info.to_string().contains("@ 110%"));
+    assert!(info.to_string().contains("(custom)"));
+    assert_eq!(info.step_scaling(1), 125);
+    assert_eq!(info.step_scaling(2), 150);
+    assert_eq!(info.step_scaling(-1), 100);
+    assert!(info.can_step_scaling(4));
+    assert!(!info.can_step_scaling(5));
+    assert!(!display::DisplayInfo { scaling_current: 125, ..info }.has_custom_scaling());
+
+    assert_eq!(display::scaling_at_index(0), 100);
+    assert_eq!(display::scaling_at_index(11), 500);
+    assert_eq!(display::scaling_at_index(13), 600);
+}
+
+#[test]
+fn test_scaling_sources_mismatch() {
+    let info = display::DisplayInfo {
+        scaling_current: 150,
+        scaling_ccd: Some(125),
+        scaling_shell: Some(150),
+        ..display::DisplayInfo::default()
+    };
+    assert_eq!(info.scaling_mismatch(), Some((125, 150)));
+    let json = serde_json::to_value(&info).unwrap();
+    assert_eq!((json["scaling_ccd"].as_i64(), json["scaling_shell"].as_i64()), (Some(125), Some(150)));
+
+    let agreed = display::DisplayInfo { scaling_ccd: Some(150), ..info.clone() };
+    assert_eq!(agreed.scaling_mismatch(), None);
+    let unknown = display::DisplayInfo { scaling_shell: None, ..info };
+    assert_eq!(unknown.scaling_mismatch(), None);
+}
+
+#[test]
+fn test_pick_refresh_rate() {
+    use display::{DisplayMode, RefreshRate};
+    let mode = |width, height, refresh| DisplayMode { width, height, refresh };
+    let modes = [
+        mode(2560, 1440, 165),
+        mode(2560, 1440, 120),
+        mode(2560, 1440, 119),
+        mode(2560, 1440, 60),
+        mode(2560, 1440, 59),
+        mode(1920, 1080, 240),
+        mode(1920, 1080, 1),
+    ];
+    assert_eq!(RefreshRate::Max.pick(&modes, 2560, 1440), Some(165));
+    assert_eq!(RefreshRate::Min.pick(&modes, 2560, 1440), Some(60));
+    assert_eq!(RefreshRate::Min.pick(&modes, 1920, 1080), Some(240));
+    assert_eq!(RefreshRate::Hz(119).pick(&modes, 2560, 1440), Some(119));
+    assert_eq!(RefreshRate::Hz(144).pick(&modes, 2560, 1440), None);
+    assert_eq!(RefreshRate::Max.pick(&modes, 1280, 720), None);
+}
+
+#[test]
+fn test_dpi_to_scaling() {
+    assert_eq!(display::dpi_to_scaling(96), 100);
+    assert_eq!(display::dpi_to_scaling(120), 125);
+    assert_eq!(display::dpi_to_scaling(106), 110);
+    assert_eq!(display::dpi_to_scaling(144), 150);
+}
+
+#[test]
+fn test_custom_scaling_refuses_scaling_change() {
+    let info = display::DisplayInfo {
+        scaling_current: 110,
+        scaling_max: 200,
+        custom_scaling: true,
+        target_available: true,
+        width: 1920,
+        height: 1080,
+        ..display::DisplayInfo::default()
+    };
+    let config = display::DisplayConfig { width: 1920, height: 1080, scaling: 125 };
+    assert!(matches!(
+        display::validate_display_config(&info, &config),
+        Err(display::DisplayError::CustomScalingActive(_))
+    ));
+}
+
+#[test]
+fn test_unavailable_target_is_refused() {
+    let info = display::DisplayInfo {
+        source_id: 3,
+        width: 1920,
+        height: 1080,
+        scaling_current: 100,
+        scaling_max: 200,
+        ..display::DisplayInfo::default()
+    };
+    let config = display::DisplayConfig { width: 1920, height: 1080, scaling: 125 };
+    assert!(matches!(
+        display::validate_display_config(&info, &config),
+        Err(display::DisplayError::TargetUnavailable(3))
+    ));
+}
+
+#[test]
+fn test_apply_backend_names() {
+    assert_eq!(display::ApplyBackend::default(), display::ApplyBackend::Ccd);
+    assert_eq!(display::ApplyBackend::Ccd.to_string(), "SetDisplayConfig");
+    assert_eq!(display::ApplyBackend::Legacy.to_string(), "ChangeDisplaySettingsEx");
+}
+
+#[test]
+fn test_tuner_refresh_bumps_generation() {
+    if hardware_displays().is_none() {
+        return;
+    }
+    let mut tuner = display::DisplayTuner::new().unwrap();
+    assert_eq!(tuner.generation(), 0);
+    assert!(!tuner.displays().is_empty());
+
+    tuner.refresh().unwrap();
+    assert_eq!(tuner.generation(), 1);
+    assert!(tuner.is_current().unwrap());
+}
+
+#[test]
+fn test_config_builder_keeps_unset_values() {
+    let info = display::DisplayInfo {
+        width: 2560,
+        height: 1440,
+        scaling_current: 125,
+        ..display::DisplayInfo::default()
+    };
+    let resolved = display::DisplayConfig::builder().scaling(150).resolve(&info);
+    assert_eq!(resolved, display::DisplayConfig { width: 2560, height: 1440, scaling: 150 });
+
+    let width: Option<u32> = None;
+    let resolved = display::DisplayConfig::builder().width(width).height(1080).resolve(&info);
+    assert_eq!(resolved, display::DisplayConfig { width: 2560, height: 1080, scaling: 125 });
+    assert_eq!(display::DisplayConfig::builder().resolve_refresh(&info).unwrap(), None);
+}
+
+#[test]
+fn test_scaling_for_effective_size() {
+    let info = display::DisplayInfo {
+        source_id: 1,
+        scaling_max: 300,
+        ..display::DisplayInfo::default()
+    };
+    assert_eq!(info.scaling_for_effective((3840, 2160), (2560, 1440)).unwrap(), 150);
+    assert_eq!(info.scaling_for_effective((3840, 2160), (2400, 1350)).unwrap(), 150);
+    assert_eq!(info.scaling_for_effective((3840, 2160), (1920, 1080)).unwrap(), 200);
+    assert_eq!(display::effective_size(3840, 2160, 175), (2194, 1234));
+
+    let err = info.scaling_for_effective((1920, 1080), (2560, 1440)).unwrap_err();
+    assert!(matches!(err, display::DisplayError::UnreachableEffectiveSize { .. }));
+    assert!(err.to_string().contains("1920x1080 (100%), 1536x864 (125%)"));
+}
+
+#[test]
+fn test_internal_classification_covers_every_output_technology() {
+    use display::Connector;
+    use windows::Win32::Devices::Display::*;
+
+    let internal = [
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_LVDS,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EMBEDDED,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_UDI_EMBEDDED,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL,
+    ];
+    let external = [
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_OTHER,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HD15,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_SVIDEO,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_COMPOSITE_VIDEO,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_COMPONENT_VIDEO,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DVI,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_D_JPN,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_SDI,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EXTERNAL,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_UDI_EXTERNAL,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_SDTVDONGLE,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_MIRACAST,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_WIRED,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_VIRTUAL,
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_USB_TUNNEL,
+    ];
+    for technology in internal {
+        assert!(Connector::from(technology).is_internal(), "{technology:?}");
+    }
+    for technology in external {
+        assert!(!Connector::from(technology).is_internal(), "{technology:?}");
+    }
+}
+
+#[test]
+fn test_pending_scaling_is_not_an_adjustment() {
+    use display::{ApplyReport, DisplayConfig, DisplayInfo};
+
+    let before = [DisplayInfo { source_id: 1, width: 2560, height: 1440, scaling_current: 100, ..DisplayInfo::default() }];
+    let requested = DisplayConfig { width: 2560, height: 1440, scaling: 150 };
+    let mut report = ApplyReport::compare(1, &requested, &before, &before, false);
+    assert_eq!(report.actual.scaling, 100);
+    assert!(report.adjusted());
+
+    report.pending_scaling = Some(150);
+    assert!(!report.adjusted());
+}
+
+#[test]
+fn test_validate_resolution_boundaries() {
+    use display::{DisplayConfig, DisplayInfo, DisplayMode, ValidationError};
+
+    let display = DisplayInfo { width: 1366, height: 768, ..DisplayInfo::default() };
+    let modes = [(1680, 1050), (1600, 900), (1440, 900), (1366, 768)].map(|(width, height)| DisplayMode { width, height, refresh: 60 });
+    let check = |width, height| DisplayConfig { width, height, scaling: 100 }.validate(&display, &modes);
+
+    assert_eq!(check(640, 480), Ok(()));
+    assert_eq!(check(480, 640), Ok(()));
+    assert_eq!(check(16384, 16384), Ok(()));
+    // The current resolution passes even though 1366 isn't a multiple of 8.
+    assert_eq!(check(1366, 768), Ok(()));
+    // So do listed modes.
+    assert_eq!(check(1680, 1050), Ok(()));
+    assert_eq!(check(1600, 900), Ok(()));
+    assert_eq!(check(1440, 900), Ok(()));
+
+    assert_eq!(check(632, 480), Err(ValidationError::TooSmall { width: 632, height: 480 }));
+    assert_eq!(check(640, 472), Err(ValidationError::TooSmall { width: 640, height: 472 }));
+    assert_eq!(check(1920, 108), Err(ValidationError::TooSmall { width: 1920, height: 108 }));
+    assert_eq!(check(16392, 1080), Err(ValidationError::TooLarge { width: 16392, height: 1080 }));
+    assert_eq!(check(1920, 16392), Err(ValidationError::TooLarge { width: 1920, height: 16392 }));
+    assert_eq!(check(1921, 1080), Err(ValidationError::NotMultipleOf8 { dimension: "width", value: 1921 }));
+    assert_eq!(check(1920, 1084), Err(ValidationError::NotMultipleOf8 { dimension: "height", value: 1084 }));
+    assert_eq!(check(1920, 108).unwrap_err().to_string(), "1920x108 is below the minimum of 640x480");
+}
+
+#[test]
+fn test_types_cross_thread_boundaries() {
+    fn check<T: Send + Sync + 'static>() {}
+    check::<display::DisplayInfo>();
+    check::<display::DisplayConfig>();
+    check::<display::DisplayError>();
+    check::<display::ApplyReport>();
+}
+
+#[test]
+fn test_apply_timings() {
+    use display::ApplyTimings;
+    use std::time::Duration;
+
+    let timings = ApplyTimings {
+        enumerate: Duration::from_millis(12),
+        resolution: Duration::from_millis(380),
+        scaling: Duration::ZERO,
+        verification: Duration::from_millis(15),
+    };
+    assert_eq!(timings.total(), Duration::from_millis(407));
+    assert_eq!(timings.to_string(), "enumerate 12 ms, resolution 380 ms, scaling 0 ms, verification 15 ms");
+    assert_eq!(
+        display::DisplayError::ApplyTimedOut.to_string(),
+        "apply timed out (the call may still complete in the background)"
+    );
+}
+
+#[test]
+fn test_capabilities_from_modes() {
+    use display::{DisplayCapabilities, DisplayInfo, DisplayMode};
+
+    let panel = DisplayInfo {
+        width: 2560,
+        height: 1600,
+        refresh_rate: 60,
+        scaling_current: 150,
+        scaling_max: 200,
+        internal: true,
+        ..DisplayInfo::default()
+    };
+    let mode = |width, height, refresh| DisplayMode { width, height, refresh };
+    let fixed = [mode(2560, 1600, 60), mode(2560, 1600, 59), mode(1920, 1200, 60), mode(1920, 1200, 120)];
+    let capabilities = DisplayCapabilities::from_modes(&panel, &fixed);
+    assert_eq!(capabilities.resolutions, 2);
+    assert_eq!((capabilities.scaling_min, capabilities.scaling_max), (100, 200));
+    assert!(capabilities.internal);
+    // 59Hz is 59.94Hz, the same rate as 60Hz.
+    assert!(!capabilities.other_refresh_rates);
+    #[cfg(feature = "ddcci")]
+    assert!(!capabilities.ddc_ci);
+
+    let variable = [mode(2560, 1600, 60), mode(2560, 1600, 165)];
+    assert!(DisplayCapabilities::from_modes(&panel, &variable).other_refresh_rates);
+    assert_eq!(DisplayCapabilities::from_modes(&panel, &[]).resolutions, 0);
+}
+
+#[test]
+fn test_native_mode() {
+    use display::{native_mode, DisplayInfo, DisplayMode, Rotation};
+
+    let mode = |width, height, refresh| DisplayMode { width, height, refresh };
+    let modes = [mode(3840, 2160, 60), mode(3840, 2160, 30), mode(1920, 1080, 120)];
+    let monitor = DisplayInfo {
+        width: 1920,
+        height: 1080,
+        refresh_rate: 60,
+        native_width: 2560,
+        native_height: 1440,
+        native_refresh: 144,
+        ..DisplayInfo::default()
+    };
+    let reported = native_mode(&monitor, &modes).unwrap();
+    assert!(reported.reported);
+    assert_eq!(reported.mode, mode(2560, 1440, 144));
+    assert!(monitor.differs_from_native());
+    assert!(!DisplayInfo { width: 2560, height: 1440, refresh_rate: 144, ..monitor.clone() }.differs_from_native());
+
+    // Portrait: the native mode is in the monitor's orientation, the resolution to set isn't.
+    let portrait = DisplayInfo { width: 1440, height: 2560, refresh_rate: 144, rotation: Rotation::Rotate90, ..monitor };
+    assert!(!portrait.differs_from_native());
+    assert_eq!(native_mode(&portrait, &modes).unwrap().rotated_size(portrait.rotation), (1440, 2560));
+
+    // A TV without a preferred mode falls back to the largest mode at its highest rate.
+    let tv = DisplayInfo { width: 1920, height: 1080, refresh_rate: 120, ..DisplayInfo::default() };
+    assert!(!tv.differs_from_native());
+    let largest = native_mode(&tv, &modes).unwrap();
+    assert!(!largest.reported);
+    assert_eq!(largest.mode, mode(3840, 2160, 60));
+    assert_eq!(native_mode(&tv, &[]), None);
+}
+
+#[test]
+fn test_native_refresh_goes_to_its_own_display() {
+    use display::{native_mode, refresh_targets, DisplayConfig, DisplayInfo};
+
+    let monitor = |source_id, native_refresh| DisplayInfo {
+        source_id,
+        width: 1920,
+        height: 1080,
+        refresh_rate: 60,
+        native_width: 2560,
+        native_height: 1440,
+        native_refresh,
+        ..DisplayInfo::default()
+    };
+    // The middle display reports its native mode without a rate, so `set --native` leaves its
+    // refresh rate alone.
+    let displays = [monitor(1, 144), monitor(2, 0), monitor(3, 165)];
+    let changes: Vec<(DisplayInfo, DisplayConfig)> = displays
+        .iter()
+        .map(|d| (d.clone(), DisplayConfig { width: 2560, height: 1440, scaling: 100 }))
+        .collect();
+    let refresh: Vec<(DisplayInfo, u32)> = displays
+        .iter()
+        .filter_map(|d| native_mode(d, &[]).map(|native| (d.clone(), native.mode.refresh)))
+        .filter(|&(_, hz)| hz > 1)
+        .collect();
+    let targets: Vec<(u32, u32)> = refresh_targets(&changes, &refresh).iter().map(|(d, _, hz)| (d.source_id, *hz)).collect();
+    assert_eq!(targets, [(1, 144), (3, 165)]);
+
+    // Rates for displays no longer among the changes are dropped.
+    assert_eq!(refresh_targets(&changes[..1], &refresh).len(), 1);
+}
+
+#[test]
+fn test_match_mode_in_all_orientations() {
+    use display::{match_mode, DisplayConfig, DisplayInfo, DisplayMode, ModeMatch, Rotation};
+
+    let modes = [
+        DisplayMode { width: 2560, height: 1440, refresh: 144 },
+        DisplayMode { width: 1920, height: 1080, refresh: 60 },
+    ];
+    for rotation in [Rotation::Identity, Rotation::Rotate180] {
+        assert!(!rotation.is_portrait());
+        assert_eq!(rotation.orient((2560, 1440)), (2560, 1440));
+        assert_eq!(match_mode(&modes, (2560, 1440), rotation), ModeMatch::Supported);
+        assert_eq!(match_mode(&modes, (1080, 1920), rotation), ModeMatch::Transposed);
+    }
+    for rotation in [Rotation::Rotate90, Rotation::Rotate270] {
+        assert!(rotation.is_portrait());
+        assert_eq!(rotation.orient((2560, 1440)), (1440, 2560));
+        assert_eq!(match_mode(&modes, (1440, 2560), rotation), ModeMatch::Supported);
+        assert_eq!(match_mode(&modes, (1920, 1080), rotation), ModeMatch::Transposed);
+    }
+    assert_eq!(match_mode(&modes, (1280, 1024), Rotation::Rotate90), ModeMatch::Unsupported);
+    assert_eq!(match_mode(&[], (2560, 1440), Rotation::Identity), ModeMatch::Unsupported);
+
+    // Rotating a quarter turns the current resolution with it unless one is given.
+    let landscape = DisplayInfo { width: 2560, height: 1440, scaling_current: 100, ..DisplayInfo::default() };
+    let turned = DisplayConfig::builder().rotation(Rotation::Rotate270).resolve(&landscape);
+    assert_eq!((turned.width, turned.height), (1440, 2560));
+    let flipped = DisplayConfig::builder().rotation(Rotation::Rotate180).resolve(&landscape);
+    assert_eq!((flipped.width, flipped.height), (2560, 1440));
+    let portrait = DisplayInfo { width: 1440, height: 2560, rotation: Rotation::Rotate90, ..landscape };
+    let back = DisplayConfig::builder().rotation(Rotation::Identity).resolve(&portrait);
+    assert_eq!((back.width, back.height), (2560, 1440));
+    let kept = DisplayConfig::builder().resolve(&portrait);
+    assert_eq!((kept.width, kept.height), (1440, 2560));
+}
+
+#[test]
+fn test_effective_size_rounds_like_windows() {
+    // Windows divides by the DPI with MulDiv, rounding each axis to the nearest pixel.
+    assert_eq!(display::effective_size(1366, 768, 125), (1093, 614));
+    assert_eq!(display::effective_size(1366, 768, 175), (781, 439));
+    assert_eq!(display::effective_size(2560, 1440, 150), (1707, 960));
+    assert_eq!(display::effective_size(3840, 2160, 150), (2560, 1440));
+    assert_eq!(display::effective_size(1601, 901, 125), (1281, 721));
+    assert_eq!(display::effective_size(1920, 1080, 100), (1920, 1080));
+
+    let info = display::DisplayInfo {
+        friendly_name: "Panel".to_string(),
+        width: 3840,
+        height: 2160,
+        scaling_current: 175,
+        scaling_recommended: 175,
+        target_available: true,
+        ..display::DisplayInfo::default()
+    };
+    assert_eq!((info.effective_width(), info.effective_height()), (2194, 1234));
+    assert_eq!(info.to_string(), "[id:0] Panel — 3840x2160 @ 175% (effective 2194x1234)");
+}
+
+#[test]
+fn test_parse_scaling_spellings() {
+    for text in ["150", "150.0", "1.5", "150%", "150.0%", " 150 % ", "1.5x", "1.5X", "149.6"] {
+        assert_eq!(display::parse_scaling(text), Some(150), "{text}");
+    }
+    // Below the ratio limit a bare number is a ratio; the suffixes settle it either way.
+    assert_eq!(display::parse_scaling("2"), Some(200));
+    assert_eq!(display::parse_scaling("2x"), Some(200));
+    assert_eq!(display::parse_scaling("2%"), Some(2));
+    assert_eq!(display::parse_scaling("10"), Some(10));
+    for text in ["", "0", "-1.5", "0%", "0.5%", "150x", "1.5y", "NaN", "inf", "%", "x"] {
+        assert_eq!(display::parse_scaling(text), None, "{text}");
+    }
+}
+
+#[test]
+fn test_parse_scaling_with_a_decimal_comma() {
+    for text in ["1,5", "1,5x", "150,0%", "149,6"] {
+        assert_eq!(display::parse_scaling(text), Some(150), "{text}");
+    }
+    assert_eq!(display::parse_scaling("1,25 x"), Some(125));
+    for text in ["1,5,0", "1.5,0x", ","] {
+        assert_eq!(display::parse_scaling(text), None, "{text}");
+    }
+}
+
+#[test]
+fn test_parse_resolution_with_group_separators() {
+    use display::{parse_dimension, parse_resolution};
+    for text in ["3840x2160", "3.840x2.160", "3,840x2,160", "3 840 x 2 160", "3\u{202f}840x2\u{2009}160", "3840 × 2160", "3.840X2160"] {
+        assert_eq!(parse_resolution(text), Some((3840, 2160)), "{text}");
+    }
+    assert_eq!(parse_dimension("15.360"), Some(15360));
+    assert_eq!(parse_dimension("1.000.000"), Some(1_000_000));
+    for text in ["1.5", "38.40", ".840", "3840.", "3..840", "1234.567", "3.84O", "", "+3840"] {
+        assert_eq!(parse_dimension(text), None, "{text}");
+    }
+    for text in ["3840", "0x2160", "3.840x", "3,84x2,16", "3840x2160x1"] {
+        assert_eq!(parse_resolution(text), None, "{text}");
+    }
+}
+
+#[test]
+fn test_parse_refresh_rate_spellings() {
+    use display::{parse_refresh_rate, RefreshRate};
+    for text in ["144", "144Hz", " 144 hz", "144.0", "144,0"] {
+        assert_eq!(parse_refresh_rate(text), Some(RefreshRate::Hz(144)), "{text}");
+    }
+    // Fractional rates round down like the driver's.
+    for text in ["59.94", "59,94", "59,94Hz", "59.999"] {
+        assert_eq!(parse_refresh_rate(text), Some(RefreshRate::Hz(59)), "{text}");
+    }
+    assert_eq!(parse_refresh_rate("MAX"), Some(RefreshRate::Max));
+    assert_eq!(parse_refresh_rate("min"), Some(RefreshRate::Min));
+    for text in ["", "fast", "-60", "59,9,4", "NaN", "1e9", "hz"] {
+        assert_eq!(parse_refresh_rate(text), None, "{text}");
+    }
+}
+
+#[test]
+fn test_scaling_percent_from_numbers() {
+    assert_eq!(display::scaling_percent(150.0), Some(150));
+    assert_eq!(display::scaling_percent(1.25), Some(125));
+    assert_eq!(display::scaling_percent(2.0), Some(200));
+    assert_eq!(display::scaling_percent(display::SCALING_RATIO_LIMIT), Some(10));
+    assert_eq!(display::scaling_percent(0.0), None);
+    assert_eq!(display::scaling_percent(f64::NAN), None);
+    assert_eq!(display::scaling_percent(1e12), None);
+}
+
+#[test]
+fn test_parse_mode_spellings() {
+    use display::{parse_mode, ModeSpec};
+    assert_eq!(parse_mode("17"), Some(ModeSpec::Index(17)));
+    let mode = |millihertz| Some(ModeSpec::Mode { width: 1920, height: 1080, millihertz });
+    for text in ["1920x1080@144", "1920X1080@144Hz", " 1920x1080 @ 144 hz ", "1920x1080@144.0"] {
+        assert_eq!(parse_mode(text), mode(144_000), "{text}");
+    }
+    assert_eq!(parse_mode("1920x1080@143.98"), mode(143_980));
+    assert_eq!(parse_mode("1920x1080@59.94hz").unwrap().to_string(), "1920x1080 @ 59.94Hz");
+    // Localized spellings read the same and print canonically.
+    for text in ["1.920x1.080@59,94", "1 920x1 080 @ 59,94 Hz", "1,920x1,080@59.94"] {
+        assert_eq!(parse_mode(text), mode(59_940), "{text}");
+    }
+    assert_eq!(parse_mode("3.840x2.160@143,98").unwrap().to_string(), "3840x2160 @ 143.98Hz");
+    for text in ["", "0", "-3", "1920x1080", "1920x1080@", "1920x1080@0", "1920x1080@NaN", "1920@144", "ax1080@60", "max", "19.20x1080@60", "1920x1080@59,9,4"] {
+        assert_eq!(parse_mode(text), None, "{text}");
+    }
+}
+
+#[test]
+fn test_find_mode_by_number_or_rate() {
+    use display::{DisplayMode, ModeSpec};
+    let mode = |width, height, refresh| DisplayMode { width, height, refresh };
+    let modes = [mode(2560, 1440, 144), mode(2560, 1440, 60), mode(1920, 1080, 144), mode(1920, 1080, 119), mode(1920, 1080, 60)];
+    assert_eq!(ModeSpec::Index(1).find(&modes), Some(modes[0]));
+    assert_eq!(ModeSpec::Index(5).find(&modes), Some(modes[4]));
+    assert_eq!(ModeSpec::Index(6).find(&modes), None);
+    let spec = |width, height, millihertz| ModeSpec::Mode { width, height, millihertz };
+    assert_eq!(spec(2560, 1440, 143_980).find(&modes), Some(modes[0]));
+    // 119.88Hz is listed rounded down.
+    assert_eq!(spec(1920, 1080, 119_880).find(&modes), Some(modes[3]));
+    assert_eq!(spec(1920, 1080, 165_000).find(&modes), None);
+
+    assert_eq!(ModeSpec::Index(9).nearest(&modes, 2), [(4, modes[3]), (5, modes[4])]);
+    assert_eq!(spec(1920, 1080, 165_000).nearest(&modes, 2), [(3, modes[2]), (4, modes[3])]);
+    assert_eq!(spec(1920, 1200, 60_000).nearest(&modes, 1), [(5, modes[4])]);
+}
+
+#[test]
+fn test_scale_resolution_snaps_to_a_mode() {
+    use display::{scale_resolution, DisplayMode};
+    let mode = |width, height, refresh| DisplayMode { width, height, refresh };
+    let native = mode(3840, 2160, 60);
+    // The driver lists the smallest mode the other way round.
+    let modes = [native, mode(2560, 1440, 60), mode(1920, 1080, 120), mode(1920, 1080, 60), mode(720, 1280, 60)];
+    let scaled = |percent| scale_resolution(native, &modes, percent).unwrap();
+
+    assert_eq!((scaled(50).exact, scaled(50).mode, scaled(50).deviation), ((1920, 1080), mode(1920, 1080, 120), 0.0));
+    assert_eq!(scaled(100).mode, native);
+    let third = scaled(33);
+    assert_eq!((third.exact, third.mode), ((1267, 713), mode(1280, 720, 60)));
+    assert!(third.deviation < 5.0);
+    assert!(scaled(20).deviation > 5.0);
+    assert_eq!(scale_resolution(native, &[], 50), None);
+}
+
+#[test]
+fn test_native_resolution_advice() {
+    let uhd = display::DisplayInfo { source_id: 1, width: 3840, height: 2160, scaling_current: 100, ..display::DisplayInfo::default() };
+    let advice = |display: &display::DisplayInfo, requested, native, scaling_given| {
+        display::native_resolution_advice(display, requested, native, scaling_given).map(|advice| advice.scaling)
+    };
+    let native = (3840, 2160);
+    assert_eq!(advice(&uhd, (1920, 1080), native, false), Some(200));
+    assert_eq!(advice(&uhd, (2560, 1440), native, false), Some(150));
+    assert_eq!(advice(&uhd, (1080, 1920), (2160, 3840), false), Some(200));
+    let scaled = display::DisplayInfo { scaling_current: 150, ..uhd.clone() };
+    assert_eq!(advice(&scaled, (3200, 1800), native, false), Some(175));
+
+    // The user chose the scaling, or isn't lowering the resolution to make things bigger.
+    assert_eq!(advice(&uhd, (1920, 1080), native, true), None);
+    assert_eq!(advice(&uhd, native, native, false), None);
+    assert_eq!(advice(&uhd, (5120, 2880), native, false), None);
+    assert_eq!(advice(&uhd, (1024, 768), native, false), None);
+    // Too close to native for another scaling step.
+    let qhd = display::DisplayInfo { width: 2560, height: 1440, ..uhd.clone() };
+    assert_eq!(advice(&qhd, (2400, 1350), (2560, 1440), false), None);
+
+    let text = display::native_resolution_advice(&uhd, (1920, 1080), native, false).unwrap().to_string();
+    assert!(text.starts_with("Display 1: 1920x1080 isn't the native 3840x2160"), "{text}");
+    assert!(text.ends_with("display-tuner set --id 1 --native --scaling 200"), "{text}");
+}
@@ -0,0 +1,58 @@
+use display_tuner::throttle::{Coalescer, RateLimiter};
+use std::time::{Duration, Instant};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn test_last_command_for_a_display_wins() {
+    let t0 = Instant::now();
+    let mut queue = Coalescer::new(ms(300), ms(250));
+    queue.push(1, 125, t0);
+    queue.push(2, 100, t0 + ms(50));
+    queue.push(1, 150, t0 + ms(100));
+    queue.push(1, 175, t0 + ms(200));
+
+    assert_eq!(queue.next_due(), Some(t0 + ms(300)));
+    assert!(queue.pop_due(t0 + ms(299)).is_empty());
+    assert_eq!(queue.pop_due(t0 + ms(300)), vec![(1, 175)]);
+    assert_eq!(queue.pop_due(t0 + ms(350)), vec![(2, 100)]);
+    assert!(queue.is_empty());
+    assert_eq!(queue.next_due(), None);
+}
+
+#[test]
+fn test_commands_due_together_keep_arrival_order() {
+    let t0 = Instant::now();
+    let mut queue = Coalescer::new(ms(300), ms(250));
+    queue.push("b", 1, t0);
+    queue.push("a", 2, t0 + ms(10));
+    queue.push("b", 3, t0 + ms(20));
+    assert_eq!(queue.pop_due(t0 + ms(1000)), vec![("b", 3), ("a", 2)]);
+}
+
+#[test]
+fn test_releases_for_a_key_are_spaced_out() {
+    let t0 = Instant::now();
+    let mut queue = Coalescer::new(ms(100), ms(500));
+    queue.push(1, "first", t0);
+    assert_eq!(queue.pop_due(t0 + ms(100)), vec![(1, "first")]);
+
+    queue.push(1, "second", t0 + ms(150));
+    assert_eq!(queue.next_due(), Some(t0 + ms(600)));
+    assert!(queue.pop_due(t0 + ms(250)).is_empty());
+    assert_eq!(queue.pop_due(t0 + ms(600)), vec![(1, "second")]);
+}
+
+#[test]
+fn test_rate_limiter_books_consecutive_slots() {
+    let t0 = Instant::now();
+    let mut limiter = RateLimiter::new(ms(250));
+    assert_eq!(limiter.reserve(1, t0), Duration::ZERO);
+    assert_eq!(limiter.reserve(1, t0 + ms(50)), ms(200));
+    assert_eq!(limiter.reserve(1, t0 + ms(60)), ms(440));
+    // Other displays aren't held up.
+    assert_eq!(limiter.reserve(2, t0 + ms(60)), Duration::ZERO);
+    assert_eq!(limiter.reserve(1, t0 + ms(2000)), Duration::ZERO);
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "watch")]
+
+use display_tuner::display::DisplayInfo;
+use display_tuner::watch::{diff_displays, DisplayEvent};
+
+fn display(path: &str, width: u32, scaling: i32) -> DisplayInfo {
+    DisplayInfo {
+        device_path: path.to_string(),
+        width,
+        height: width * 9 / 16,
+        scaling_current: scaling,
+        active: true,
+        ..DisplayInfo::default()
+    }
+}
+
+#[test]
+fn test_unchanged_snapshot_has_no_events() {
+    let snapshot = [display("PATH-1", 2560, 125), display("PATH-2", 1920, 100)];
+    assert!(diff_displays(&snapshot, &snapshot).is_empty());
+}
+
+#[test]
+fn test_dock_and_undock() {
+    let laptop = [display("PANEL", 1920, 150)];
+    let docked = [display("PANEL", 1920, 150), display("DELL", 3840, 150), display("LG", 2560, 100)];
+
+    assert_eq!(
+        diff_displays(&laptop, &docked),
+        vec![DisplayEvent::DisplayAdded(docked[1].clone()), DisplayEvent::DisplayAdded(docked[2].clone())]
+    );
+    assert_eq!(
+        diff_displays(&docked, &laptop),
+        vec![
+            DisplayEvent::DisplayRemoved { device_path: "DELL".to_string() },
+            DisplayEvent::DisplayRemoved { device_path: "LG".to_string() },
+        ]
+    );
+}
+
+#[test]
+fn test_mode_changes_and_ignored_fields() {
+    let before = [display("PATH-1", 2560, 125), display("PATH-2", 1920, 100)];
+    let mut after = before.clone();
+    after[1].scaling_current = 125;
+    after[0].friendly_name = "Renamed".to_string();
+
+    assert_eq!(
+        diff_displays(&before, &after),
+        vec![DisplayEvent::ModeChanged { before: before[1].clone(), after: after[1].clone() }]
+    );
+
+    let mut moved = before.clone();
+    moved[0].position_x = -2560;
+    assert!(matches!(&diff_displays(&before, &moved)[..], [DisplayEvent::ModeChanged { after, .. }] if after.position_x == -2560));
+}
+
+#[test]
+fn test_removal_comes_before_addition() {
+    let before = [display("OLD", 1920, 100)];
+    let after = [display("NEW", 1920, 100)];
+    assert_eq!(
+        diff_displays(&before, &after),
+        vec![
+            DisplayEvent::DisplayRemoved { device_path: "OLD".to_string() },
+            DisplayEvent::DisplayAdded(after[0].clone()),
+        ]
+    );
+}